@@ -139,7 +139,9 @@ fn split_ola_asm_pieces(
         | OlaOpcode::NEQ
         | OlaOpcode::GTE
         | OlaOpcode::TLOAD
-        | OlaOpcode::POSEIDON => {
+        | OlaOpcode::POSEIDON
+        | OlaOpcode::SSCAN
+        | OlaOpcode::CMOV => {
             if ops.len() != 3 {
                 return Err(format!("invalid operand size: {}", asm_line));
             }
@@ -162,11 +164,19 @@ fn split_ola_asm_pieces(
         | OlaOpcode::TSTORE
         | OlaOpcode::SCCALL
         | OlaOpcode::SLOAD
-        | OlaOpcode::SSTORE => {
+        | OlaOpcode::SSTORE
+        | OlaOpcode::TLOADT
+        | OlaOpcode::TSTORET
+        | OlaOpcode::TXCTX => {
             if ops.len() != 2 {
                 return Err(format!("invalid operand size: {}", asm_line));
             }
-            if opcode == OlaOpcode::MOV || opcode == OlaOpcode::NOT || opcode == OlaOpcode::MLOAD {
+            if opcode == OlaOpcode::MOV
+                || opcode == OlaOpcode::NOT
+                || opcode == OlaOpcode::MLOAD
+                || opcode == OlaOpcode::TLOADT
+                || opcode == OlaOpcode::TXCTX
+            {
                 let dst = ops.get(0).unwrap();
                 let op1 = ops.get(1).unwrap();
                 Ok((opcode, None, Some(op1.clone()), Some(dst.clone())))
@@ -177,6 +187,21 @@ fn split_ola_asm_pieces(
             }
         }
 
+        OlaOpcode::ASSERT if ops.len() == 2 => {
+            // assert reg message_id
+            let op0 = ops.get(0).unwrap();
+            let op1 = ops.get(1).unwrap();
+            match (op0, op1) {
+                (OlaAsmOperand::RegisterOperand { .. }, OlaAsmOperand::ImmediateOperand { .. }) => {
+                    Ok((opcode, Some(op0.clone()), Some(op1.clone()), None))
+                }
+                _ => Err(format!(
+                    "assert with message id needs a register and an immediate: {}",
+                    asm_line
+                )),
+            }
+        }
+
         OlaOpcode::JMP | OlaOpcode::CALL | OlaOpcode::RC | OlaOpcode::ASSERT => {
             if ops.len() != 1 {
                 return Err(format!("invalid operand size: {}", asm_line));
@@ -355,6 +380,24 @@ mod tests {
         //     })
         // );
 
+        let row_assert_str = "assert r3 42";
+        let row_assert = AsmRow::from_str(row_assert_str).unwrap();
+        assert_eq!(
+            row_assert,
+            AsmRow::Instruction(OlaAsmInstruction {
+                asm: row_assert_str.to_string(),
+                opcode: OlaOpcode::ASSERT,
+                op0: Some(OlaAsmOperand::RegisterOperand {
+                    register: OlaRegister::R3
+                }),
+                op1: Some(OlaAsmOperand::ImmediateOperand {
+                    value: ImmediateValue::from_str("42").unwrap()
+                }),
+                dst: None,
+            })
+        );
+        assert!(AsmRow::from_str("assert 1 42").is_err());
+
         let row_label_call_str = "bar:";
         let row_label_call = AsmRow::from_str(row_label_call_str).unwrap();
         assert_eq!(row_label_call, AsmRow::LabelCall(String::from("bar")));
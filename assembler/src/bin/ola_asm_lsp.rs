@@ -0,0 +1,211 @@
+//! Language server for Ola assembly over stdio.
+//!
+//! Supports full document sync, diagnostics, label go-to-definition, register
+//! highlighting and inlay hints with the binary length and cycle count of each
+//! statement.
+
+use assembler::diagnostics::{diagnose, Diagnostic};
+use assembler::lsp::{definition, inlay_hints, register_highlights, Span};
+use serde_json::{json, Value};
+use std::collections::HashMap;
+use std::io::{self, BufRead, BufReader, Read, Write};
+
+fn read_message(reader: &mut impl BufRead) -> io::Result<Option<Value>> {
+    let mut content_length = None;
+    loop {
+        let mut header = String::new();
+        if reader.read_line(&mut header)? == 0 {
+            return Ok(None);
+        }
+        let header = header.trim_end();
+        if header.is_empty() {
+            break;
+        }
+        if let Some(length) = header.strip_prefix("Content-Length:") {
+            content_length = length.trim().parse::<usize>().ok();
+        }
+    }
+    let length = content_length.ok_or_else(|| {
+        io::Error::new(io::ErrorKind::InvalidData, "missing Content-Length header")
+    })?;
+    let mut body = vec![0u8; length];
+    reader.read_exact(&mut body)?;
+    serde_json::from_slice(&body)
+        .map(Some)
+        .map_err(|e| io::Error::new(io::ErrorKind::InvalidData, e))
+}
+
+fn write_message(writer: &mut impl Write, message: &Value) -> io::Result<()> {
+    let body = message.to_string();
+    write!(writer, "Content-Length: {}\r\n\r\n{}", body.len(), body)?;
+    writer.flush()
+}
+
+fn range(span: &Span) -> Value {
+    json!({
+        "start": {"line": span.line, "character": span.start},
+        "end": {"line": span.line, "character": span.end},
+    })
+}
+
+fn lsp_diagnostic(diagnostic: &Diagnostic) -> Value {
+    let line = diagnostic.line.saturating_sub(1);
+    let start = diagnostic.column.saturating_sub(1);
+    json!({
+        "range": range(&Span { line, start, end: start + diagnostic.len }),
+        "severity": 1,
+        "source": "ola-asm",
+        "message": diagnostic.message,
+    })
+}
+
+fn position(params: &Value) -> Option<(&str, usize, usize)> {
+    let uri = params["textDocument"]["uri"].as_str()?;
+    let line = params["position"]["line"].as_u64()? as usize;
+    let character = params["position"]["character"].as_u64()? as usize;
+    Some((uri, line, character))
+}
+
+struct Server {
+    documents: HashMap<String, String>,
+    shutdown: bool,
+}
+
+impl Server {
+    fn publish_diagnostics(&self, uri: &str) -> Value {
+        let diagnostics: Vec<Value> = self
+            .documents
+            .get(uri)
+            .map(|text| diagnose(text).iter().map(lsp_diagnostic).collect())
+            .unwrap_or_default();
+        json!({
+            "jsonrpc": "2.0",
+            "method": "textDocument/publishDiagnostics",
+            "params": {"uri": uri, "diagnostics": diagnostics},
+        })
+    }
+
+    fn handle_request(&mut self, method: &str, params: &Value) -> Result<Value, String> {
+        match method {
+            "initialize" => Ok(json!({
+                "capabilities": {
+                    "textDocumentSync": 1,
+                    "definitionProvider": true,
+                    "documentHighlightProvider": true,
+                    "inlayHintProvider": true,
+                },
+                "serverInfo": {"name": "ola-asm-lsp"},
+            })),
+            "shutdown" => {
+                self.shutdown = true;
+                Ok(Value::Null)
+            }
+            "textDocument/definition" => {
+                let (uri, line, character) = position(params).ok_or("invalid position")?;
+                Ok(self
+                    .documents
+                    .get(uri)
+                    .and_then(|text| definition(text, line, character))
+                    .map(|span| json!({"uri": uri, "range": range(&span)}))
+                    .unwrap_or(Value::Null))
+            }
+            "textDocument/documentHighlight" => {
+                let (uri, line, character) = position(params).ok_or("invalid position")?;
+                let spans = self
+                    .documents
+                    .get(uri)
+                    .map(|text| register_highlights(text, line, character))
+                    .unwrap_or_default();
+                Ok(spans
+                    .iter()
+                    .map(|span| json!({"range": range(span)}))
+                    .collect())
+            }
+            "textDocument/inlayHint" => {
+                let uri = params["textDocument"]["uri"]
+                    .as_str()
+                    .ok_or("invalid document")?;
+                let hints = self
+                    .documents
+                    .get(uri)
+                    .map(|text| inlay_hints(text))
+                    .unwrap_or_default();
+                Ok(hints
+                    .iter()
+                    .map(|hint| {
+                        json!({
+                            "position": {"line": hint.line, "character": hint.character},
+                            "label": format!("{}w {}c", hint.words, hint.cycles),
+                            "paddingLeft": true,
+                        })
+                    })
+                    .collect())
+            }
+            _ => Err(format!("unsupported method {}", method)),
+        }
+    }
+
+    // Returns the notifications to send back.
+    fn handle_notification(&mut self, method: &str, params: &Value) -> Vec<Value> {
+        let uri = match params["textDocument"]["uri"].as_str() {
+            Some(uri) => uri.to_string(),
+            None => return vec![],
+        };
+        match method {
+            "textDocument/didOpen" => {
+                let text = params["textDocument"]["text"].as_str().unwrap_or("");
+                self.documents.insert(uri.clone(), text.to_string());
+            }
+            "textDocument/didChange" => {
+                // full sync, the last change holds the whole document.
+                if let Some(text) = params["contentChanges"]
+                    .as_array()
+                    .and_then(|changes| changes.last())
+                    .and_then(|change| change["text"].as_str())
+                {
+                    self.documents.insert(uri.clone(), text.to_string());
+                }
+            }
+            "textDocument/didClose" => {
+                self.documents.remove(&uri);
+            }
+            _ => return vec![],
+        }
+        vec![self.publish_diagnostics(&uri)]
+    }
+}
+
+fn main() -> io::Result<()> {
+    let mut reader = BufReader::new(io::stdin());
+    let mut stdout = io::stdout();
+    let mut server = Server {
+        documents: HashMap::new(),
+        shutdown: false,
+    };
+    while let Some(message) = read_message(&mut reader)? {
+        let method = message["method"].as_str().unwrap_or("");
+        let params = &message["params"];
+        if method == "exit" {
+            std::process::exit(if server.shutdown { 0 } else { 1 });
+        }
+        match message.get("id") {
+            Some(id) => {
+                let response = match server.handle_request(method, params) {
+                    Ok(result) => json!({"jsonrpc": "2.0", "id": id, "result": result}),
+                    Err(e) => json!({
+                        "jsonrpc": "2.0",
+                        "id": id,
+                        "error": {"code": -32601, "message": e},
+                    }),
+                };
+                write_message(&mut stdout, &response)?;
+            }
+            None => {
+                for notification in server.handle_notification(method, params) {
+                    write_message(&mut stdout, &notification)?;
+                }
+            }
+        }
+    }
+    Ok(())
+}
@@ -0,0 +1,232 @@
+use core::program::binary_program::{OlaProphetInput, OlaProphetOutput};
+use serde::{Deserialize, Serialize};
+use std::collections::HashSet;
+use std::fmt::{Display, Formatter};
+
+// The asm bundle is what frontend compilers hand to the assembler:
+//
+// {
+//   "version": 1,             // optional, defaults to 1
+//   "program": "main:\n...",  // asm text, has to define `main`
+//   "prophets": [             // optional, defaults to []
+//     {
+//       "label": ".PROPHET1_0",  // a `.PROPHET<n>_<m>:` label of program
+//       "code": "%{ ... %}",
+//       "inputs": [
+//         { "name": "cid.x", "length": 1,
+//           "is_ref": false, "is_input_output": false }
+//       ],
+//       "outputs": [...]         // same fields as inputs
+//     }
+//   ]
+// }
+//
+// Unknown fields are rejected so typos surface instead of being dropped.
+
+pub const ASM_BUNDLE_VERSION: u32 = 1;
+
+fn current_version() -> u32 {
+    ASM_BUNDLE_VERSION
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+#[serde(deny_unknown_fields)]
+pub struct AsmBundle {
+    #[serde(default = "current_version")]
+    pub version: u32,
+    pub program: String,
+    #[serde(default)]
+    pub prophets: Vec<OlaAsmProphet>,
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+#[serde(deny_unknown_fields)]
+pub struct OlaAsmProphet {
+    pub label: String,
+    pub code: String,
+    pub inputs: Vec<OlaProphetInput>,
+    pub outputs: Vec<OlaProphetOutput>,
+}
+
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum BundleError {
+    /// Malformed json or a missing or unknown field, located by 1-based line
+    /// and column of the json text.
+    Json {
+        line: usize,
+        column: usize,
+        message: String,
+    },
+    UnsupportedVersion(u32),
+    EmptyProgram,
+    /// A problem with the prophet at `index` of the prophets array.
+    Prophet {
+        index: usize,
+        label: String,
+        message: String,
+    },
+}
+
+impl Display for BundleError {
+    fn fmt(&self, f: &mut Formatter<'_>) -> std::fmt::Result {
+        match self {
+            BundleError::Json {
+                line,
+                column,
+                message,
+            } => write!(f, "{}:{}: {}", line, column, message),
+            BundleError::UnsupportedVersion(version) => write!(
+                f,
+                "bundle version {} is not supported, expected {}",
+                version, ASM_BUNDLE_VERSION
+            ),
+            BundleError::EmptyProgram => write!(f, "program is empty"),
+            BundleError::Prophet {
+                index,
+                label,
+                message,
+            } => write!(f, "prophets[{}] ({}): {}", index, label, message),
+        }
+    }
+}
+
+impl std::error::Error for BundleError {}
+
+impl AsmBundle {
+    pub fn new(program: String) -> Self {
+        Self {
+            version: ASM_BUNDLE_VERSION,
+            program,
+            prophets: vec![],
+        }
+    }
+
+    /// Parse and validate a bundle.
+    pub fn from_json(json: &str) -> Result<Self, BundleError> {
+        let bundle: AsmBundle = serde_json::from_str(json).map_err(|e| BundleError::Json {
+            line: e.line(),
+            column: e.column(),
+            message: e.to_string(),
+        })?;
+        bundle.validate()?;
+        Ok(bundle)
+    }
+
+    /// Check what the schema alone cannot: the version is known, and every
+    /// prophet is attached to a label of the program, appears once and has
+    /// well formed code and operands. The asm itself is checked when it is
+    /// assembled.
+    pub fn validate(&self) -> Result<(), BundleError> {
+        if self.version != ASM_BUNDLE_VERSION {
+            return Err(BundleError::UnsupportedVersion(self.version));
+        }
+        if self.program.trim().is_empty() {
+            return Err(BundleError::EmptyProgram);
+        }
+
+        let labels: HashSet<&str> = self
+            .program
+            .lines()
+            .filter_map(|line| {
+                let line = line.split(';').next().unwrap_or_default().trim();
+                line.strip_suffix(':')
+            })
+            .collect();
+        let mut seen = HashSet::new();
+        for (index, prophet) in self.prophets.iter().enumerate() {
+            let err = |message: String| BundleError::Prophet {
+                index,
+                label: prophet.label.clone(),
+                message,
+            };
+            if !prophet.label.starts_with(".PROPHET") {
+                return Err(err("label has to start with .PROPHET".to_string()));
+            }
+            if !labels.contains(prophet.label.as_str()) {
+                return Err(err("label is not defined in the program".to_string()));
+            }
+            if !seen.insert(prophet.label.as_str()) {
+                return Err(err("label has more than one prophet".to_string()));
+            }
+            let code = prophet.code.trim();
+            if !code.starts_with("%{") || !code.ends_with("%}") {
+                return Err(err("code has to be enclosed in %{ %}".to_string()));
+            }
+            let operands = prophet
+                .inputs
+                .iter()
+                .map(|input| ("inputs", &input.name, input.length))
+                .chain(
+                    prophet
+                        .outputs
+                        .iter()
+                        .map(|output| ("outputs", &output.name, output.length)),
+                );
+            for (kind, name, length) in operands {
+                if name.is_empty() {
+                    return Err(err(format!("{} has an operand without name", kind)));
+                }
+                if length == 0 {
+                    return Err(err(format!("{} operand {} has length 0", kind, name)));
+                }
+            }
+        }
+        Ok(())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::bundle::{AsmBundle, BundleError, ASM_BUNDLE_VERSION};
+
+    const PROPHET: &str = r#"{"label": ".PROPHET1_0", "code": "%{ entry() { cid.y = sqrt(cid.x); } %}",
+        "inputs": [{"name": "cid.x", "length": 1, "is_ref": false, "is_input_output": false}],
+        "outputs": [{"name": "cid.y", "length": 1, "is_ref": false, "is_input_output": false}]}"#;
+
+    fn bundle_json(program: &str, prophets: &[&str]) -> String {
+        format!(
+            r#"{{"program": {:?}, "prophets": [{}]}}"#,
+            program,
+            prophets.join(",")
+        )
+    }
+
+    #[test]
+    fn test_bundle_validate() {
+        let program = "main:\n.LBL1_0:\n.PROPHET1_0:\nmov r1 1\nend";
+        let bundle = AsmBundle::from_json(&bundle_json(program, &[PROPHET])).unwrap();
+        assert_eq!(bundle.version, ASM_BUNDLE_VERSION);
+        assert_eq!(bundle.prophets.len(), 1);
+
+        let err = AsmBundle::from_json(r#"{"prophets": []}"#).unwrap_err();
+        assert!(
+            matches!(err, BundleError::Json { ref message, .. } if message.contains("`program`"))
+        );
+        let err = AsmBundle::from_json(r#"{"program": "main:", "prophet": []}"#).unwrap_err();
+        assert!(
+            matches!(err, BundleError::Json { ref message, .. } if message.contains("`prophet`"))
+        );
+
+        let err = AsmBundle::from_json(r#"{"version": 2, "program": "main:"}"#).unwrap_err();
+        assert_eq!(err, BundleError::UnsupportedVersion(2));
+        let err = AsmBundle::from_json(&bundle_json(" \n", &[])).unwrap_err();
+        assert_eq!(err, BundleError::EmptyProgram);
+
+        let err = AsmBundle::from_json(&bundle_json("main:\nend", &[PROPHET])).unwrap_err();
+        assert_eq!(
+            err.to_string(),
+            "prophets[0] (.PROPHET1_0): label is not defined in the program"
+        );
+        let err = AsmBundle::from_json(&bundle_json(program, &[PROPHET, PROPHET])).unwrap_err();
+        assert!(matches!(err, BundleError::Prophet { index: 1, .. }));
+        let bad_code = PROPHET.replace("%}", "");
+        let err = AsmBundle::from_json(&bundle_json(program, &[&bad_code])).unwrap_err();
+        assert!(matches!(err, BundleError::Prophet { index: 0, .. }));
+        let bad_length = PROPHET.replacen("\"length\": 1", "\"length\": 0", 1);
+        let err = AsmBundle::from_json(&bundle_json(program, &[&bad_length])).unwrap_err();
+        assert_eq!(
+            err.to_string(),
+            "prophets[0] (.PROPHET1_0): inputs operand cid.x has length 0"
+        );
+    }
+}
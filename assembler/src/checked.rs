@@ -0,0 +1,95 @@
+use std::str::FromStr;
+
+use crate::source::{join_lines, lower_lines, source_lines, SourceError, SourceLine};
+use core::vm::hardware::OlaRegister;
+use core::vm::operands::ImmediateValue;
+
+// Checked u32 arithmetic. `addc d a b` and `mulc d a b` range check their
+// register operands and the result, so an overflow traps with
+// `U32RangeCheckFail` instead of wrapping around the field:
+//
+//   addc d a b  =>  range a, range b, add d a b, range d
+//
+// Operands below 2^32 cannot wrap the field, (2^32 - 1)^2 < p, so a result
+// below 2^32 is the exact u32 result. Immediate operands are checked at
+// assembly time. Wider integers do not fit a field element, use limbs.
+
+const CHECKED_OPS: [(&str, &str); 2] = [("addc", "add"), ("mulc", "mul")];
+
+fn check_operand(operand: &str, lowered: &mut Vec<String>) -> Result<(), String> {
+    if OlaRegister::from_str(operand).is_ok() {
+        lowered.push(format!("range {}", operand));
+        return Ok(());
+    }
+    let value = ImmediateValue::from_str(operand)?
+        .to_u64()
+        .map_err(|e| e.to_string())?;
+    if value > u32::MAX as u64 {
+        return Err(format!("immediate {} is not a u32", operand));
+    }
+    Ok(())
+}
+
+fn lower_checked(op: &str, dst: &str, a: &str, b: &str) -> Result<Vec<String>, String> {
+    OlaRegister::from_str(dst)?;
+    OlaRegister::from_str(a)?;
+    let mut lowered = vec![];
+    check_operand(a, &mut lowered)?;
+    if b != a {
+        check_operand(b, &mut lowered)?;
+    }
+    lowered.push(format!("{} {} {} {}", op, dst, a, b));
+    lowered.push(format!("range {}", dst));
+    Ok(lowered)
+}
+
+pub(crate) fn expand_checked_arithmetic(program: &str) -> Result<String, String> {
+    lower_checked_arithmetic(source_lines(program))
+        .map(|lines| join_lines(&lines))
+        .map_err(|e| e.to_string())
+}
+
+pub(crate) fn lower_checked_arithmetic(
+    lines: Vec<SourceLine>,
+) -> Result<Vec<SourceLine>, SourceError> {
+    lower_lines(lines, |code| {
+        let pieces: Vec<&str> = code.split_whitespace().collect();
+        let op = pieces
+            .first()
+            .and_then(|token| CHECKED_OPS.iter().find(|(checked, _)| checked == token));
+        match op {
+            Some((checked, op)) => {
+                if pieces.len() != 4 {
+                    return Err(format!("{} needs a destination and two operands", checked));
+                }
+                lower_checked(op, pieces[1], pieces[2], pieces[3]).map(Some)
+            }
+            None => Ok(None),
+        }
+    })
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::checked::expand_checked_arithmetic;
+    use crate::relocate::lower_program;
+
+    #[test]
+    fn test_expand_checked_arithmetic() {
+        let program = "main:\naddc r1 r1 r2\nmulc r0 r3 7\naddc r4 r5 r5\nend";
+        assert_eq!(
+            expand_checked_arithmetic(program).unwrap(),
+            "main:\nrange r1\nrange r2\nadd r1 r1 r2\nrange r1\nrange r3\nmul r0 r3 7\nrange r0\nrange r5\nadd r4 r5 r5\nrange r4\nend"
+        );
+
+        assert_eq!(
+            lower_program("main:\nlt r0 r1 r2\nmulc r0 r1 4294967296 ; big\nend")
+                .unwrap_err()
+                .to_string(),
+            "line 3: mulc r0 r1 4294967296 ; big ==> immediate 4294967296 is not a u32"
+        );
+        assert!(expand_checked_arithmetic("addc r0 r1").is_err());
+        assert!(expand_checked_arithmetic("mulc r0 r1 4294967296").is_err());
+        assert!(expand_checked_arithmetic("addc r0 r1 -1").is_err());
+    }
+}
@@ -0,0 +1,131 @@
+use std::str::FromStr;
+
+use crate::frame::strip_comment;
+use crate::source::{join_lines, lower_lines, source_lines, SourceError, SourceLine};
+use core::vm::hardware::OlaRegister;
+use regex::Regex;
+
+// Conditional move. `cmov d c a` is native: d = a if c is 1, d is kept if c
+// is 0, any other c fails. The select form with two sources is lowered onto
+// it:
+//
+//   cmov d c a b  =>  mov d b, cmov d c a
+//
+// so `d` must differ from `c` and `a`, which the mov would clobber.
+//
+// Forks without the cmov opcode get the branching form instead:
+//
+//   cmov d c a  =>  cjmp c .LBLn_2i
+//                   jmp .LBLn_2i+1
+//                   .LBLn_2i:
+//                   mov d a
+//                   .LBLn_2i+1:
+//
+// where `n` is above every jump label block of the program and `i` counts
+// the cmovs.
+
+const CMOV: &str = "cmov";
+
+fn lower_select(dst: &str, cond: &str, a: &str, b: &str) -> Result<Vec<String>, String> {
+    OlaRegister::from_str(dst)?;
+    OlaRegister::from_str(cond)?;
+    if dst == cond || dst == a {
+        return Err(format!(
+            "destination {} must differ from the condition and the first source",
+            dst
+        ));
+    }
+    Ok(vec![
+        format!("mov {} {}", dst, b),
+        format!("cmov {} {} {}", dst, cond, a),
+    ])
+}
+
+/// Lowers the 4 operand `cmov d c a b` select onto the native `cmov`.
+pub(crate) fn expand_selects(program: &str) -> Result<String, String> {
+    lower_selects(source_lines(program))
+        .map(|lines| join_lines(&lines))
+        .map_err(|e| e.to_string())
+}
+
+pub(crate) fn lower_selects(lines: Vec<SourceLine>) -> Result<Vec<SourceLine>, SourceError> {
+    lower_lines(lines, |code| {
+        let pieces: Vec<&str> = code.split_whitespace().collect();
+        match pieces.as_slice() {
+            [CMOV, dst, cond, a, b] => lower_select(dst, cond, a, b).map(Some),
+            _ => Ok(None),
+        }
+    })
+}
+
+/// Rewrites every native `cmov` of `program` into a conditional jump, for
+/// forks where the opcode is disabled.
+pub(crate) fn lower_cmov(program: &str) -> Result<String, String> {
+    let label_block = Regex::new(r"\.LBL(?P<block>[[:digit:]]+)_[[:digit:]]+").unwrap();
+    let block = label_block
+        .captures_iter(program)
+        .filter_map(|caps| caps["block"].parse::<u64>().ok())
+        .max()
+        .map_or(0, |max| max + 1);
+
+    let mut lowered: Vec<String> = vec![];
+    let mut count = 0;
+    for (line_num, line) in (1..).zip(program.lines()) {
+        let pieces: Vec<&str> = strip_comment(line).split_whitespace().collect();
+        match pieces.as_slice() {
+            [CMOV, dst, cond, a] => {
+                OlaRegister::from_str(cond)
+                    .map_err(|e| format!("line {}: {} ==> {}", line_num, line, e))?;
+                let take = format!(".LBL{}_{}", block, 2 * count);
+                let done = format!(".LBL{}_{}", block, 2 * count + 1);
+                lowered.extend([
+                    format!("cjmp {} {}", cond, take),
+                    format!("jmp {}", done),
+                    format!("{}:", take),
+                    format!("mov {} {}", dst, a),
+                    format!("{}:", done),
+                ]);
+                count += 1;
+            }
+            [CMOV, ..] => {
+                return Err(format!(
+                    "line {}: {} ==> cmov needs a destination, a condition and a source",
+                    line_num, line
+                ))
+            }
+            _ => lowered.push(line.to_string()),
+        }
+    }
+    Ok(lowered.join("\n"))
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::cmov::{expand_selects, lower_cmov};
+
+    #[test]
+    fn test_expand_selects() {
+        let program = "main:\ncmov r0 r1 r2 r3\ncmov r0 r1 5\ncmov r4 r1 7 r2\nend";
+        assert_eq!(
+            expand_selects(program).unwrap(),
+            "main:\nmov r0 r3\ncmov r0 r1 r2\ncmov r0 r1 5\nmov r4 r2\ncmov r4 r1 7\nend"
+        );
+
+        assert!(expand_selects("cmov r0 r0 r2 r3").is_err());
+        assert!(expand_selects("cmov r0 r1 r0 r3").is_err());
+        assert!(expand_selects("cmov r0 5 r2 r3").is_err());
+    }
+
+    #[test]
+    fn test_lower_cmov() {
+        let program = "main:\n.LBL3_0:\ncmov r0 r1 r2\ncmov r3 r1 9\nend";
+        assert_eq!(
+            lower_cmov(program).unwrap(),
+            "main:\n.LBL3_0:\ncjmp r1 .LBL4_0\njmp .LBL4_1\n.LBL4_0:\nmov r0 r2\n.LBL4_1:\n\
+             cjmp r1 .LBL4_2\njmp .LBL4_3\n.LBL4_2:\nmov r3 9\n.LBL4_3:\nend"
+        );
+
+        assert!(lower_cmov("cmov r0 r1 r2 r3").is_err());
+        assert!(lower_cmov("cmov r0 1 r2").is_err());
+    }
+}
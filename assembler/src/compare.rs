@@ -0,0 +1,99 @@
+use std::str::FromStr;
+
+use crate::source::{join_lines, lower_lines, source_lines, SourceError, SourceLine};
+use core::vm::hardware::OlaRegister;
+use core::vm::operands::ImmediateValue;
+
+// Comparison family. Only `gte` has a cmp table row, the other comparisons are
+// lowered onto it, so their result is proven by the same abs_diff range check:
+//
+//   lt  d a b  =>  gte d a b, eq d d 0
+//   lte d a b  =>  gte d b a                 (b register)
+//   lte d a k  =>  gte d a k+1, eq d d 0     (k immediate)
+//   gt  d a b  =>  gte d b a, eq d d 0       (b register)
+//   gt  d a k  =>  gte d a k+1               (k immediate)
+//
+// Operands follow gte: `a` is a register, `b` a register or an immediate.
+// Like gte, the operands must be u32 values.
+
+const GT: &str = "gt";
+const LT: &str = "lt";
+const LTE: &str = "lte";
+
+fn negate(dst: &str) -> String {
+    format!("eq {} {} 0", dst, dst)
+}
+
+fn immediate_successor(imm: &str) -> Result<String, String> {
+    let value = ImmediateValue::from_str(imm)?
+        .to_u64()
+        .map_err(|e| e.to_string())?;
+    let successor = (value + 1).to_string();
+    ImmediateValue::from_str(&successor)
+        .map(|_| successor)
+        .map_err(|_| format!("no successor for immediate {}", imm))
+}
+
+fn lower_compare(op: &str, dst: &str, a: &str, b: &str) -> Result<Vec<String>, String> {
+    OlaRegister::from_str(dst)?;
+    OlaRegister::from_str(a)?;
+    let b_is_register = OlaRegister::from_str(b).is_ok();
+    let lowered = match (op, b_is_register) {
+        (LT, _) => vec![format!("gte {} {} {}", dst, a, b), negate(dst)],
+        (LTE, true) => vec![format!("gte {} {} {}", dst, b, a)],
+        (LTE, false) => vec![
+            format!("gte {} {} {}", dst, a, immediate_successor(b)?),
+            negate(dst),
+        ],
+        (GT, true) => vec![format!("gte {} {} {}", dst, b, a), negate(dst)],
+        (GT, false) => vec![format!("gte {} {} {}", dst, a, immediate_successor(b)?)],
+        _ => unreachable!(),
+    };
+    Ok(lowered)
+}
+
+pub(crate) fn expand_compares(program: &str) -> Result<String, String> {
+    lower_compares(source_lines(program))
+        .map(|lines| join_lines(&lines))
+        .map_err(|e| e.to_string())
+}
+
+pub(crate) fn lower_compares(lines: Vec<SourceLine>) -> Result<Vec<SourceLine>, SourceError> {
+    lower_lines(lines, |code| {
+        let pieces: Vec<&str> = code.split_whitespace().collect();
+        match pieces.first() {
+            Some(&op) if op == GT || op == LT || op == LTE => {
+                if pieces.len() != 4 {
+                    return Err(format!("{} needs a destination and two operands", op));
+                }
+                lower_compare(op, pieces[1], pieces[2], pieces[3]).map(Some)
+            }
+            _ => Ok(None),
+        }
+    })
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::compare::expand_compares;
+    use crate::relocate::lower_program;
+
+    #[test]
+    fn test_expand_compares() {
+        let program = "main:\nlt r0 r1 r2\nlte r0 r1 r2\nlte r0 r1 7\ngt r0 r1 r2\ngt r0 r1 7\nend";
+        assert_eq!(
+            expand_compares(program).unwrap(),
+            "main:\ngte r0 r1 r2\neq r0 r0 0\ngte r0 r2 r1\ngte r0 r1 8\neq r0 r0 0\ngte r0 r2 r1\neq r0 r0 0\ngte r0 r1 8\nend"
+        );
+
+        assert_eq!(
+            lower_program("main:\n.frame 2\nmov128 r1 5\ngt r0 r1\nend")
+                .unwrap_err()
+                .to_string(),
+            "line 4: gt r0 r1 ==> gt needs a destination and two operands"
+        );
+        assert!(expand_compares("gt r0 r1").is_err());
+        assert!(expand_compares("lt r0 5 r1").is_err());
+        assert!(expand_compares("gt r0 r1 -1").is_err());
+    }
+}
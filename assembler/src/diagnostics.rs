@@ -0,0 +1,316 @@
+use crate::asm::{AsmRow, OlaAsmInstruction};
+use crate::checked::expand_checked_arithmetic;
+use crate::cmov::expand_selects;
+use crate::compare::expand_compares;
+use crate::encoder::handle_mem_operand;
+use crate::frame::expand_frames;
+use crate::operands::OlaAsmOperand;
+use crate::returns::take_return_arity;
+use crate::stdlib::STDLIB;
+use crate::wide::expand_wide_immediates;
+use core::vm::opcodes::OlaOpcode;
+use serde::Serialize;
+use std::collections::HashSet;
+use std::fmt::{Display, Formatter};
+use std::str::FromStr;
+
+/// A problem found in an asm source, located by 1-based line and column.
+/// `len` is the width of the offending token, the whole statement if no
+/// single operand is at fault.
+#[derive(Debug, Clone, PartialEq, Eq, Serialize)]
+pub struct Diagnostic {
+    pub line: usize,
+    pub column: usize,
+    pub len: usize,
+    /// Source order index of the instruction on the line, if any.
+    pub instruction: Option<usize>,
+    pub message: String,
+}
+
+impl Display for Diagnostic {
+    fn fmt(&self, f: &mut Formatter<'_>) -> std::fmt::Result {
+        write!(f, "{}:{}: {}", self.line, self.column, self.message)
+    }
+}
+
+struct Reference {
+    line: usize,
+    column: usize,
+    len: usize,
+    instruction: usize,
+    label: String,
+}
+
+// Messages of the lowering passes read `line N: <statement> ==> <reason>`,
+// N the 1-based source line.
+pub(crate) fn pass_reason(message: &str) -> &str {
+    match message.find(" ==> ") {
+        Some(pos) => &message[pos + 5..],
+        None => message.splitn(2, ": ").nth(1).unwrap_or(message),
+    }
+}
+
+/// A diagnostic spanning the statement on 1-based `line` of `program`.
+pub(crate) fn line_diagnostic(program: &str, line: usize, message: String) -> Diagnostic {
+    let line = line.max(1);
+    let text = program.lines().nth(line - 1).unwrap_or("");
+    Diagnostic {
+        line,
+        column: text.len() - text.trim_start().len() + 1,
+        len: text.trim().len(),
+        instruction: None,
+        message,
+    }
+}
+
+fn pass_line(message: &str) -> Option<usize> {
+    message
+        .strip_prefix("line ")?
+        .split(':')
+        .next()?
+        .parse()
+        .ok()
+}
+
+pub(crate) fn tokens(code: &str) -> Vec<(usize, &str)> {
+    let mut tokens = vec![];
+    let mut start = None;
+    for (i, c) in code.char_indices() {
+        match (c.is_whitespace(), start) {
+            (true, Some(s)) => {
+                tokens.push((s, &code[s..i]));
+                start = None;
+            }
+            (false, None) => start = Some(i),
+            _ => {}
+        }
+    }
+    if let Some(s) = start {
+        tokens.push((s, &code[s..]));
+    }
+    tokens
+}
+
+pub(crate) fn lower_statement(code: &str) -> Result<Vec<String>, String> {
+    let lowered = expand_wide_immediates(code)?;
+    let lowered = expand_compares(&lowered)?;
+    let lowered = expand_checked_arithmetic(&lowered)?;
+    let lowered = expand_selects(&lowered)?;
+    Ok(lowered.lines().map(|l| l.to_string()).collect())
+}
+
+/// Collect every problem of `program` instead of stopping at the first one.
+pub fn diagnose(program: &str) -> Vec<Diagnostic> {
+    let mut diagnostics = vec![];
+    let mut defined: HashSet<String> = STDLIB.iter().map(|(name, _)| name.to_string()).collect();
+    let mut references: Vec<Reference> = vec![];
+    let mut instruction = 0;
+
+    let pass_errors = [
+        expand_frames(program).err(),
+        take_return_arity(program).err().map(|e| e.to_string()),
+    ];
+    for e in pass_errors.iter().flatten() {
+        let line = pass_line(e).unwrap_or(1);
+        diagnostics.push(line_diagnostic(program, line, pass_reason(e).to_string()));
+    }
+
+    for (line_num, line) in program.lines().enumerate() {
+        let code = line[..line.find(";").unwrap_or(line.len())].trim_end();
+        let indent = code.len() - code.trim_start().len();
+        let code = code.trim_start();
+        if code.is_empty() || code.starts_with(".frame") || code.starts_with(".returns") {
+            continue;
+        }
+        let at =
+            |offset: usize, len: usize, instruction: Option<usize>, message: String| Diagnostic {
+                line: line_num + 1,
+                column: indent + offset + 1,
+                len,
+                instruction,
+                message,
+            };
+
+        match AsmRow::from_str(code) {
+            Ok(AsmRow::LabelCall(label)) | Ok(AsmRow::LabelJmp(label)) => {
+                if !defined.insert(label.clone()) {
+                    diagnostics.push(at(
+                        0,
+                        code.len(),
+                        None,
+                        format!("duplicate label {}", label),
+                    ));
+                }
+                continue;
+            }
+            Ok(AsmRow::LabelProphet(_)) => continue,
+            _ => {}
+        }
+
+        let pieces = tokens(code);
+        if pieces[0].1 == "tcall" {
+            if let Some((offset, target)) = pieces.get(1) {
+                references.push(Reference {
+                    line: line_num + 1,
+                    column: indent + offset + 1,
+                    len: target.len(),
+                    instruction,
+                    label: target.to_string(),
+                });
+            }
+            instruction += 1;
+            continue;
+        }
+
+        let lowered = match lower_statement(code) {
+            Ok(lowered) => lowered,
+            Err(e) => {
+                diagnostics.push(at(
+                    0,
+                    code.len(),
+                    Some(instruction),
+                    pass_reason(&e).to_string(),
+                ));
+                instruction += 1;
+                continue;
+            }
+        };
+        // operand spans only map back for statements that were not lowered.
+        let direct = lowered.len() == 1 && lowered[0] == code;
+        for statement in &lowered {
+            let found = check_instruction(statement);
+            for (offset, len, message) in found.errors {
+                let (offset, len) = if direct {
+                    (offset, len)
+                } else {
+                    (0, code.len())
+                };
+                diagnostics.push(at(offset, len, Some(instruction), message));
+            }
+            for (offset, label) in found.labels {
+                let (offset, len) = if direct {
+                    (offset, label.len())
+                } else {
+                    (0, code.len())
+                };
+                references.push(Reference {
+                    line: line_num + 1,
+                    column: indent + offset + 1,
+                    len,
+                    instruction,
+                    label,
+                });
+            }
+        }
+        instruction += 1;
+    }
+
+    for reference in references {
+        if !defined.contains(&reference.label) {
+            diagnostics.push(Diagnostic {
+                line: reference.line,
+                column: reference.column,
+                len: reference.len,
+                instruction: Some(reference.instruction),
+                message: format!("undefined label {}", reference.label),
+            });
+        }
+    }
+    diagnostics.sort_by_key(|d| (d.line, d.column));
+    diagnostics
+}
+
+#[derive(Default)]
+struct InstructionCheck {
+    // (byte offset, len, message)
+    errors: Vec<(usize, usize, String)>,
+    // (byte offset, label) of jmp and call targets
+    labels: Vec<(usize, String)>,
+}
+
+fn check_instruction(code: &str) -> InstructionCheck {
+    let mut check = InstructionCheck::default();
+    let pieces = tokens(code);
+    let (opcode_offset, opcode) = pieces[0];
+    if let Err(e) = OlaOpcode::from_str(opcode) {
+        check.errors.push((opcode_offset, opcode.len(), e));
+        return check;
+    }
+    let mut operands_ok = true;
+    for &(offset, operand) in &pieces[1..] {
+        match OlaAsmOperand::from_str(operand) {
+            Ok(OlaAsmOperand::Label { value }) | Ok(OlaAsmOperand::Identifier { value }) => {
+                check.labels.push((offset, value))
+            }
+            Ok(_) => {}
+            Err(e) => {
+                operands_ok = false;
+                check.errors.push((offset, operand.len(), e));
+            }
+        }
+    }
+    if !operands_ok {
+        return check;
+    }
+    match OlaAsmInstruction::from_str(code) {
+        Ok(asm) => {
+            if asm.opcode == OlaOpcode::MLOAD || asm.opcode == OlaOpcode::MSTORE {
+                if let Err(e) = handle_mem_operand(&asm) {
+                    check.errors.push((0, code.len(), e));
+                }
+            }
+        }
+        Err(e) => check.errors.push((0, code.len(), e)),
+    }
+    check
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::diagnostics::diagnose;
+    use crate::encoder::encode_asm_checked;
+
+    #[test]
+    fn test_diagnose() {
+        let program = "main:\n  mov r1 10\n  add r0 r1 [r1 ; bad operand\n  mload r2 r1\n  jmp .LBL0_9\n  foo r1\n  call missing\n  gt r0 5 r1\nend";
+        let diagnostics = diagnose(program);
+        let located: Vec<(usize, usize, Option<usize>)> = diagnostics
+            .iter()
+            .map(|d| (d.line, d.column, d.instruction))
+            .collect();
+        assert_eq!(
+            located,
+            vec![
+                (3, 13, Some(1)),
+                (4, 3, Some(2)),
+                (5, 7, Some(3)),
+                (6, 3, Some(4)),
+                (7, 8, Some(5)),
+                (8, 3, Some(6)),
+            ]
+        );
+        assert_eq!(diagnostics[4].message, "undefined label missing");
+        assert_eq!(diagnostics[4].len, 7);
+
+        assert!(diagnose("main:\nmov r1 1\ncall std_memcpy\nend").is_empty());
+        let frame = diagnose("main:\nmov r0 1\n.frame 2\nend");
+        assert_eq!(frame.len(), 1);
+        assert_eq!(frame[0].line, 3);
+        assert!(diagnose("main:\n.returns 2\nend").is_empty());
+        let returns = diagnose("main:\n.returns 9\nend");
+        assert_eq!(returns.len(), 1);
+        assert_eq!(returns[0].line, 2);
+        assert_eq!(returns[0].message, "at most 4 return registers");
+    }
+
+    #[test]
+    fn test_encode_asm_checked_locates_encoder_errors() {
+        let errors = encode_asm_checked("\nfoo:\n  mov r0 1\nend").unwrap_err();
+        assert_eq!(errors.len(), 1);
+        assert_eq!((errors[0].line, errors[0].column, errors[0].len), (2, 1, 4));
+        assert_eq!(
+            errors[0].message,
+            "generate scopes error, no main scope found"
+        );
+    }
+}
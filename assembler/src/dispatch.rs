@@ -0,0 +1,91 @@
+use crate::stdlib::next_label_scope;
+use core::abi::{AbiError, FunctionSignature};
+use std::collections::HashSet;
+
+// Function dispatch generator for the calldata ABI of `core::abi`. The entry
+// point calls `function_dispatch` with the selector in r1, the argument word
+// count in r2 and a pointer to the argument words in r3. The function with the
+// matching selector is called with its argument words loaded into r1-r7 and
+// its result is left in r0. An unknown selector or a wrong word count fails an
+// assert. Only functions with statically sized arguments can be dispatched.
+
+pub const DISPATCH_LABEL: &str = "function_dispatch";
+const MAX_ARG_WORDS: usize = 7;
+
+/// The `function_dispatch` routine for `signatures`, with its jmp labels in
+/// label scope `scope`.
+pub fn dispatch_routine(signatures: &[&str], scope: u64) -> Result<String, String> {
+    let mut selectors = HashSet::new();
+    let mut lines = vec![format!("{}:", DISPATCH_LABEL), ".frame 2".to_string()];
+    let mut cases = vec![];
+    for (i, signature) in signatures.iter().enumerate() {
+        let signature: FunctionSignature =
+            signature.parse().map_err(|e: AbiError| e.to_string())?;
+        let words = signature.word_len().ok_or(format!(
+            "{}: dynamic arguments can not be dispatched",
+            signature
+        ))?;
+        if words > MAX_ARG_WORDS {
+            return Err(format!(
+                "{}: {} argument words, at most {} fit in registers",
+                signature, words, MAX_ARG_WORDS
+            ));
+        }
+        if !selectors.insert(signature.selector()) {
+            return Err(format!("{}: duplicated selector", signature));
+        }
+
+        let label = format!(".LBL{}_{}", scope, i);
+        lines.push(format!("  eq r4 r1 {}", signature.selector()));
+        lines.push(format!("  cjmp r4 {}", label));
+        cases.push(format!("{}:", label));
+        cases.push(format!("  eq r4 r2 {}", words));
+        cases.push("  assert r4".to_string());
+        if words > 0 {
+            cases.push("  mov r8 r3".to_string());
+            cases.push("  mload r1 [r8]".to_string());
+        }
+        for word in 1..words {
+            cases.push(format!("  mload r{} [r8,+{}]", word + 1, word));
+        }
+        cases.push(format!("  call {}", signature.name));
+        cases.push("  ret".to_string());
+    }
+    lines.push("  mov r4 0".to_string());
+    lines.push("  assert r4".to_string());
+    lines.extend(cases);
+    Ok(lines.join("\n"))
+}
+
+/// Append the `function_dispatch` routine for `signatures` to `program`.
+pub fn link_dispatch(program: &str, signatures: &[&str]) -> Result<String, String> {
+    let routine = dispatch_routine(signatures, next_label_scope(program))?;
+    Ok(format!("{}\n{}", program, routine))
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::dispatch::{dispatch_routine, link_dispatch};
+    use core::abi::function_selector;
+
+    #[test]
+    fn test_dispatch_routine() {
+        let routine = dispatch_routine(&["sum(u32,u32)", "reset()"], 3).unwrap();
+        let sum = function_selector("sum(u32,u32)");
+        assert!(routine.starts_with("function_dispatch:\n.frame 2\n"));
+        assert!(routine.contains(&format!("  eq r4 r1 {}\n  cjmp r4 .LBL3_0\n", sum)));
+        assert!(routine.contains(
+            ".LBL3_0:\n  eq r4 r2 2\n  assert r4\n  mov r8 r3\n  mload r1 [r8]\n  \
+             mload r2 [r8,+1]\n  call sum\n  ret"
+        ));
+        assert!(routine.contains(".LBL3_1:\n  eq r4 r2 0\n  assert r4\n  call reset\n  ret"));
+
+        let linked = link_dispatch("main:\n.LBL0_0:\nend", &["reset()"]).unwrap();
+        assert!(linked.contains(".LBL1_0:"));
+
+        assert!(dispatch_routine(&["f(fields)"], 0).is_err());
+        assert!(dispatch_routine(&["f(address,address)"], 0).is_err());
+        assert!(dispatch_routine(&["f()", "f( )"], 0).is_err());
+        assert!(dispatch_routine(&["f(u64)"], 0).is_err());
+    }
+}
@@ -1,7 +1,13 @@
 use crate::asm::OlaAsmInstruction;
+use crate::bundle::AsmBundle;
+use crate::cmov::{expand_selects, lower_cmov};
+use crate::diagnostics::{diagnose, line_diagnostic, pass_reason, Diagnostic};
 use crate::operands::OlaAsmOperand;
-use crate::relocate::{asm_relocate, AsmBundle, RelocatedAsmBundle};
+use crate::relocate::{asm_relocate, relocate_program, RelocatedAsmBundle};
+use crate::source::SourceError;
 use core::program::binary_program::{BinaryInstruction, BinaryProgram, OlaProphet};
+use core::program::{check_program_size, MAX_PROGRAM_WORDS};
+use core::vm::chain_spec::ChainSpec;
 use core::vm::opcodes::OlaOpcode;
 use core::vm::operands::{ImmediateValue, OlaOperand};
 use log::debug;
@@ -9,34 +15,82 @@ use std::collections::{BTreeMap, HashMap};
 use std::str::FromStr;
 
 pub fn encode_asm_from_json_file(path: String) -> Result<BinaryProgram, String> {
-    let json_str = std::fs::read_to_string(path).unwrap();
-    let bundle: AsmBundle = serde_json::from_str(json_str.as_str()).unwrap();
-    let relocated = asm_relocate(bundle).unwrap();
-    let program = encode_to_binary(relocated).unwrap();
-    Ok(program)
+    let json_str = std::fs::read_to_string(&path).map_err(|e| format!("{}: {}", path, e))?;
+    let bundle = AsmBundle::from_json(&json_str).map_err(|e| format!("{}: {}", path, e))?;
+    encode_asm_bundle(bundle)
+}
+
+/// Encode a bundle of asm and its prophets, see `AsmBundle::validate` for the
+/// checks done before assembling.
+pub fn encode_asm_bundle(bundle: AsmBundle) -> Result<BinaryProgram, String> {
+    bundle.validate().map_err(|e| e.to_string())?;
+    let relocated = asm_relocate(bundle)?;
+    encode_to_binary(relocated).map_err(|e| e.to_string())
+}
+
+/// Encode a bare asm program without prophets.
+pub fn encode_asm(program: &str) -> Result<BinaryProgram, String> {
+    encode_source(program).map_err(|e| e.to_string())
+}
+
+fn encode_source(program: &str) -> Result<BinaryProgram, SourceError> {
+    let (relocated, _) = relocate_program(program)?;
+    encode_to_binary(relocated)
+}
+
+/// Like `encode_asm`, for the fork of `spec` in force at block `height`.
+/// Where that fork has no `cmov`, it is lowered onto conditional jumps.
+pub fn encode_asm_for_chain(
+    program: &str,
+    spec: &ChainSpec,
+    height: u64,
+) -> Result<BinaryProgram, String> {
+    if spec.opcode_enabled(OlaOpcode::CMOV, height) {
+        return encode_asm(program);
+    }
+    encode_asm(&lower_cmov(&expand_selects(program)?)?)
 }
 
-pub(crate) fn encode_to_binary(bundle: RelocatedAsmBundle) -> Result<BinaryProgram, String> {
+/// Like `encode_asm`, but reports every problem of the source with its
+/// location instead of the first one.
+pub fn encode_asm_checked(program: &str) -> Result<BinaryProgram, Vec<Diagnostic>> {
+    let diagnostics = diagnose(program);
+    if !diagnostics.is_empty() {
+        return Err(diagnostics);
+    }
+    encode_source(program).map_err(|e| {
+        vec![line_diagnostic(
+            program,
+            e.line,
+            pass_reason(&e.message).to_string(),
+        )]
+    })
+}
+
+pub(crate) fn encode_to_binary(bundle: RelocatedAsmBundle) -> Result<BinaryProgram, SourceError> {
     let asm_instructions = bundle.instructions;
     let mapper_label_call = &bundle.mapper_label_call.clone();
     let mapper_label_jmp = &bundle.mapper_label_jmp.clone();
     let asm_prophets = &bundle.prophets;
+    let return_arity = bundle.return_arity;
 
     let mut binary_instructions: Vec<BinaryInstruction> = vec![];
-    let mut iter = asm_instructions.iter();
     let mut binary_counter: usize = 0;
     let mut origin_asm = BTreeMap::new();
 
-    while let Some(asm) = iter.next() {
+    for (asm, &line) in asm_instructions.iter().zip(bundle.lines.iter()) {
         let ops_result: Result<
             (Option<OlaOperand>, Option<OlaOperand>, Option<OlaOperand>),
             String,
         > = if is_adjusted_operand(asm) {
             let r = handle_mem_operand(asm);
             if r.is_err() {
-                return Err(format!(
-                    "relocated asm to binary error: ops convert error ==> {}",
-                    r.err().unwrap()
+                return Err(SourceError::new(
+                    line,
+                    format!(
+                        "relocated asm to binary error: ops convert error ==> {}",
+                        r.err().unwrap()
+                    ),
                 ));
             } else {
                 let tuple = r.unwrap();
@@ -46,9 +100,12 @@ pub(crate) fn encode_to_binary(bundle: RelocatedAsmBundle) -> Result<BinaryProgr
             let op0_result =
                 operand_asm_to_binary(asm.clone().op0, mapper_label_call, mapper_label_jmp);
             if op0_result.is_err() {
-                return Err(format!(
-                    "relocated asm to binary error: op0 convert error ==> {}",
-                    op0_result.err().unwrap()
+                return Err(SourceError::new(
+                    line,
+                    format!(
+                        "relocated asm to binary error: op0 convert error ==> {}",
+                        op0_result.err().unwrap()
+                    ),
                 ));
             }
             let op0 = op0_result.unwrap();
@@ -56,9 +113,12 @@ pub(crate) fn encode_to_binary(bundle: RelocatedAsmBundle) -> Result<BinaryProgr
             let op1_result =
                 operand_asm_to_binary(asm.clone().op1, mapper_label_call, mapper_label_jmp);
             if op1_result.is_err() {
-                return Err(format!(
-                    "relocated asm to binary error: op1 convert error ==> {}",
-                    op1_result.err().unwrap()
+                return Err(SourceError::new(
+                    line,
+                    format!(
+                        "relocated asm to binary error: op1 convert error ==> {}",
+                        op1_result.err().unwrap()
+                    ),
                 ));
             }
             let op1 = op1_result.unwrap();
@@ -66,18 +126,24 @@ pub(crate) fn encode_to_binary(bundle: RelocatedAsmBundle) -> Result<BinaryProgr
             let dst_result =
                 operand_asm_to_binary(asm.clone().dst, mapper_label_call, mapper_label_jmp);
             if dst_result.is_err() {
-                return Err(format!(
-                    "relocated asm to binary error: dst convert error ==> {}",
-                    dst_result.err().unwrap()
+                return Err(SourceError::new(
+                    line,
+                    format!(
+                        "relocated asm to binary error: dst convert error ==> {}",
+                        dst_result.err().unwrap()
+                    ),
                 ));
             }
             let dst = dst_result.unwrap();
             Ok((op0, op1, dst))
         };
         if ops_result.is_err() {
-            return Err(format!(
-                "relocated asm to binary error: ==> {}",
-                ops_result.err().unwrap()
+            return Err(SourceError::new(
+                line,
+                format!(
+                    "relocated asm to binary error: ==> {}",
+                    ops_result.err().unwrap()
+                ),
             ));
         }
         let (op0, op1, dst) = ops_result.unwrap();
@@ -107,10 +173,18 @@ pub(crate) fn encode_to_binary(bundle: RelocatedAsmBundle) -> Result<BinaryProgr
             "binary_counter:{}, asm:{}, code:{}",
             binary_counter, asm.asm, instruction
         );
+        instruction
+            .encode()
+            .map_err(|e| SourceError::new(line, format!("{} ==> {}", asm.asm, e)))?;
         binary_instructions.push(instruction);
         binary_counter += asm.binary_length() as usize;
+        check_program_size(binary_counter, MAX_PROGRAM_WORDS)
+            .map_err(|e| SourceError::new(line, e.to_string()))?;
     }
-    BinaryProgram::from_instructions(binary_instructions, Some(origin_asm), true)
+    let mut program = BinaryProgram::from_instructions(binary_instructions, Some(origin_asm), true)
+        .expect("every instruction was encoded above");
+    program.return_arity = return_arity;
+    Ok(program)
 }
 
 fn is_adjusted_operand(asm: &OlaAsmInstruction) -> bool {
@@ -121,89 +195,46 @@ fn is_adjusted_operand(asm: &OlaAsmInstruction) -> bool {
     }
 }
 
-fn handle_mem_operand(
+pub(crate) fn handle_mem_operand(
     asm: &OlaAsmInstruction,
 ) -> Result<(OlaOperand, OlaOperand, OlaOperand), String> {
-    let dst = if asm.opcode == OlaOpcode::MLOAD {
-        asm.dst.clone()
+    let (dst, mem) = if asm.opcode == OlaOpcode::MLOAD {
+        (asm.dst.clone(), asm.op1.clone())
     } else {
-        asm.op1.clone()
-    }
-    .unwrap();
-    let dst_reg = match dst {
-        OlaAsmOperand::RegisterOperand { register } => OlaOperand::RegisterOperand { register },
-        _ => {
-            panic!("parse dst reg error")
-        }
+        (asm.op1.clone(), asm.op0.clone())
     };
-
-    let anchor_reg = if asm.opcode == OlaOpcode::MLOAD {
-        match asm.op1.clone().unwrap() {
-            OlaAsmOperand::RegisterWithOffset {
-                register,
-                offset: _,
-            } => OlaOperand::RegisterOperand { register },
-            OlaAsmOperand::RegisterWithFactoredRegOffset {
-                register,
-                offset_register: _,
-                factor: _,
-            } => OlaOperand::RegisterOperand { register },
-            _ => {
-                panic!("parse anchor reg error")
-            }
+    let dst_reg = match dst {
+        Some(OlaAsmOperand::RegisterOperand { register }) => {
+            OlaOperand::RegisterOperand { register }
         }
-    } else {
-        match asm.op0.clone().unwrap() {
-            OlaAsmOperand::RegisterWithOffset {
-                register,
-                offset: _,
-            } => OlaOperand::RegisterOperand { register },
-            OlaAsmOperand::RegisterWithFactoredRegOffset {
-                register,
-                offset_register: _,
-                factor: _,
-            } => OlaOperand::RegisterOperand { register },
-            _ => {
-                panic!("parse anchor reg error")
-            }
+        _ => {
+            return Err(format!(
+                "{} value operand must be a register",
+                asm.opcode.token()
+            ))
         }
     };
-
-    let offset = if asm.opcode == OlaOpcode::MLOAD {
-        match asm.op1.clone().unwrap() {
-            OlaAsmOperand::RegisterWithOffset {
-                register: _,
-                offset,
-            } => OlaOperand::ImmediateOperand { value: offset },
-            OlaAsmOperand::RegisterWithFactoredRegOffset {
-                register: _,
-                offset_register,
-                factor,
-            } => OlaOperand::RegisterWithFactor {
+    let (anchor_reg, offset) = match mem {
+        Some(OlaAsmOperand::RegisterWithOffset { register, offset }) => (
+            OlaOperand::RegisterOperand { register },
+            OlaOperand::ImmediateOperand { value: offset },
+        ),
+        Some(OlaAsmOperand::RegisterWithFactoredRegOffset {
+            register,
+            offset_register,
+            factor,
+        }) => (
+            OlaOperand::RegisterOperand { register },
+            OlaOperand::RegisterWithFactor {
                 register: offset_register,
                 factor,
             },
-            _ => {
-                panic!("parse offset error")
-            }
-        }
-    } else {
-        match asm.op0.clone().unwrap() {
-            OlaAsmOperand::RegisterWithOffset {
-                register: _,
-                offset,
-            } => OlaOperand::ImmediateOperand { value: offset },
-            OlaAsmOperand::RegisterWithFactoredRegOffset {
-                register: _,
-                offset_register,
-                factor,
-            } => OlaOperand::RegisterWithFactor {
-                register: offset_register,
-                factor,
-            },
-            _ => {
-                panic!("parse offset error")
-            }
+        ),
+        _ => {
+            return Err(format!(
+                "{} address operand must be [reg,offset] or [reg,reg,factor]",
+                asm.opcode.token()
+            ))
         }
     };
 
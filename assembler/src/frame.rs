@@ -0,0 +1,166 @@
+use crate::asm::AsmRow;
+use crate::source::{join_lines, lower_lines, source_lines, SourceError, SourceLine};
+use std::collections::HashMap;
+use std::str::FromStr;
+
+// Calling convention helper. A function scope may declare its frame size with
+// `.frame N` right after its call label; the prologue (fp adjustment and
+// return fp store) is emitted in place of the directive and the epilogue (fp
+// restore) is emitted before every `ret` and `end` of that scope.
+//
+// `tcall f` is a tail call: the current frame is released and control jumps to
+// `f`, so `f` returns straight to our caller through the same return slot.
+// Both the caller and `f` must declare their frames.
+
+const FRAME_DIRECTIVE: &str = ".frame";
+const TAIL_CALL: &str = "tcall";
+const FP_REGISTER: &str = "r9";
+
+pub(crate) fn frame_prologue(size: u64) -> Vec<String> {
+    vec![
+        format!("add {} {} {}", FP_REGISTER, FP_REGISTER, size),
+        format!("mstore [{},-2] {}", FP_REGISTER, FP_REGISTER),
+    ]
+}
+
+pub(crate) fn frame_epilogue(size: u64) -> Vec<String> {
+    vec![format!("add {} {} -{}", FP_REGISTER, FP_REGISTER, size)]
+}
+
+fn parse_frame_directive(line: &str) -> Option<Result<u64, String>> {
+    let mut pieces = line.split_whitespace();
+    if pieces.next() != Some(FRAME_DIRECTIVE) {
+        return None;
+    }
+    let size = match (pieces.next(), pieces.next()) {
+        (Some(size), None) => size
+            .parse::<u64>()
+            .map_err(|_| "invalid frame size".to_string()),
+        _ => Err("frame directive needs exactly one size".to_string()),
+    };
+    Some(size)
+}
+
+/// Code of an asm line, its comment and surrounding whitespace removed.
+pub(crate) fn strip_comment(line: &str) -> &str {
+    match line.find(";") {
+        Some(pos) => line[..pos].trim(),
+        None => line.trim(),
+    }
+}
+
+fn collect_frames(program: &str) -> HashMap<String, u64> {
+    let mut frames = HashMap::new();
+    let mut current: Option<String> = None;
+    for line in program.lines() {
+        let code = strip_comment(line);
+        if let Ok(AsmRow::LabelCall(label)) = AsmRow::from_str(code) {
+            current = Some(label);
+        } else if let Some(Ok(size)) = parse_frame_directive(code) {
+            if let Some(label) = current.take() {
+                frames.insert(label, size);
+            }
+        }
+    }
+    frames
+}
+
+fn expand_tail_call(
+    line: &str,
+    frame: Option<u64>,
+    frames: &HashMap<String, u64>,
+) -> Result<Vec<String>, String> {
+    let pieces: Vec<&str> = line.split_whitespace().collect();
+    if pieces.len() != 2 {
+        return Err("tcall needs one target".to_string());
+    }
+    let size = frame.ok_or("tcall from a function without frame".to_string())?;
+    if !frames.contains_key(pieces[1]) {
+        return Err("tcall target has no frame".to_string());
+    }
+    let mut expanded = frame_epilogue(size);
+    expanded.push(format!("jmp {}", pieces[1]));
+    Ok(expanded)
+}
+
+pub(crate) fn expand_frames(program: &str) -> Result<String, String> {
+    lower_frames(source_lines(program))
+        .map(|lines| join_lines(&lines))
+        .map_err(|e| e.to_string())
+}
+
+pub(crate) fn lower_frames(lines: Vec<SourceLine>) -> Result<Vec<SourceLine>, SourceError> {
+    let frames = collect_frames(&join_lines(&lines));
+    let mut frame: Option<u64> = None;
+    let mut directive_allowed = false;
+    lower_lines(lines, |code| {
+        if code.split_whitespace().next() == Some(TAIL_CALL) {
+            directive_allowed = false;
+            return expand_tail_call(code, frame, &frames).map(Some);
+        }
+        if let Some(size) = parse_frame_directive(code) {
+            if !directive_allowed {
+                return Err("frame directive must follow a function label".to_string());
+            }
+            let size = size?;
+            frame = Some(size);
+            directive_allowed = false;
+            return Ok(Some(frame_prologue(size)));
+        }
+        match AsmRow::from_str(code) {
+            Ok(AsmRow::LabelCall(_)) => {
+                frame = None;
+                directive_allowed = true;
+            }
+            Ok(AsmRow::LabelJmp(_)) => {}
+            _ => {
+                if !code.is_empty() {
+                    directive_allowed = false;
+                }
+                if let Some(size) = frame {
+                    if code == "ret" || code == "end" {
+                        let mut expanded = frame_epilogue(size);
+                        expanded.push(code.to_string());
+                        return Ok(Some(expanded));
+                    }
+                }
+            }
+        }
+        Ok(None)
+    })
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::frame::expand_frames;
+
+    #[test]
+    fn test_expand_frames() {
+        let program =
+            "main:\n.LBL0_0:\n.frame 4\nmov r1 10\ncall fib\nend\nfib:\n.frame 9\nmov r0 1\nret";
+        let expanded = expand_frames(program).unwrap();
+        assert_eq!(
+            expanded,
+            "main:\n.LBL0_0:\nadd r9 r9 4\nmstore [r9,-2] r9\nmov r1 10\ncall fib\nadd r9 r9 -4\nend\nfib:\nadd r9 r9 9\nmstore [r9,-2] r9\nmov r0 1\nadd r9 r9 -9\nret"
+        );
+
+        assert_eq!(
+            expand_frames("main:\nmov r0 1\n.frame 2\nend").unwrap_err(),
+            "line 3: .frame 2 ==> frame directive must follow a function label"
+        );
+        assert!(expand_frames("main:\n.frame x\nend").is_err());
+    }
+
+    #[test]
+    fn test_expand_tail_call() {
+        let program = "main:\n.frame 4\ncall f\nend\nf:\n.frame 3\ntcall g\ng:\n.frame 5\nret";
+        let expanded = expand_frames(program).unwrap();
+        assert_eq!(
+            expanded,
+            "main:\nadd r9 r9 4\nmstore [r9,-2] r9\ncall f\nadd r9 r9 -4\nend\nf:\nadd r9 r9 3\nmstore [r9,-2] r9\nadd r9 r9 -3\njmp g\ng:\nadd r9 r9 5\nmstore [r9,-2] r9\nadd r9 r9 -5\nret"
+        );
+
+        assert!(expand_frames("main:\n.frame 4\ntcall g\ng:\nret").is_err());
+        assert!(expand_frames("main:\ntcall g\ng:\n.frame 2\nret").is_err());
+    }
+}
@@ -1,8 +1,21 @@
 mod asm;
+pub mod bundle;
+mod checked;
+mod cmov;
+mod compare;
+pub mod diagnostics;
+pub mod dispatch;
 pub mod encoder;
 mod error;
+mod frame;
+pub mod lsp;
 pub mod operands;
+pub mod rangecheck;
 mod relocate;
+mod returns;
+mod source;
+mod stdlib;
 mod test_binary_program_print;
 mod test_data_generator;
 mod tests;
+mod wide;
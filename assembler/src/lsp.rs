@@ -0,0 +1,188 @@
+use crate::asm::{AsmRow, OlaAsmInstruction};
+use crate::diagnostics::{lower_statement, tokens};
+use crate::frame::frame_prologue;
+use regex::Regex;
+use serde::Serialize;
+use std::str::FromStr;
+
+// Editor queries over an asm source. Lines and characters are 0-based like in
+// the language server protocol; asm is ascii so a byte offset is a character.
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize)]
+pub struct Span {
+    pub line: usize,
+    pub start: usize,
+    pub end: usize,
+}
+
+/// Binary size and cpu rows of one source statement after lowering.
+#[derive(Debug, Clone, PartialEq, Eq, Serialize)]
+pub struct InlayHint {
+    pub line: usize,
+    pub character: usize,
+    pub words: usize,
+    pub cycles: usize,
+}
+
+fn code_of(line: &str) -> &str {
+    line[..line.find(";").unwrap_or(line.len())].trim_end()
+}
+
+fn token_at(program: &str, line: usize, character: usize) -> Option<Span> {
+    let code = code_of(program.lines().nth(line)?);
+    tokens(code)
+        .into_iter()
+        .find(|(start, token)| *start <= character && character <= start + token.len())
+        .map(|(start, token)| Span {
+            line,
+            start,
+            end: start + token.len(),
+        })
+}
+
+fn slice<'a>(program: &'a str, span: &Span) -> &'a str {
+    &program.lines().nth(span.line).unwrap()[span.start..span.end]
+}
+
+/// Where the label under the cursor is defined.
+pub fn definition(program: &str, line: usize, character: usize) -> Option<Span> {
+    let token = token_at(program, line, character)?;
+    let label = slice(program, &token).trim_end_matches(':');
+    program.lines().enumerate().find_map(|(line_num, text)| {
+        let code = code_of(text);
+        match AsmRow::from_str(code.trim_start()) {
+            Ok(AsmRow::LabelCall(defined)) | Ok(AsmRow::LabelJmp(defined)) if defined == label => {
+                let start = code.len() - code.trim_start().len();
+                Some(Span {
+                    line: line_num,
+                    start,
+                    end: start + defined.len(),
+                })
+            }
+            _ => None,
+        }
+    })
+}
+
+/// Every use of the register under the cursor.
+pub fn register_highlights(program: &str, line: usize, character: usize) -> Vec<Span> {
+    let regex_reg = Regex::new(r"\br[0-9]\b").unwrap();
+    let register = program.lines().nth(line).and_then(|text| {
+        regex_reg
+            .find_iter(code_of(text))
+            .find(|m| m.start() <= character && character <= m.end())
+            .map(|m| m.as_str().to_string())
+    });
+    let register = match register {
+        Some(register) => register,
+        None => return vec![],
+    };
+    program
+        .lines()
+        .enumerate()
+        .flat_map(|(line_num, text)| {
+            regex_reg
+                .find_iter(code_of(text))
+                .filter(|m| m.as_str() == register)
+                .map(|m| Span {
+                    line: line_num,
+                    start: m.start(),
+                    end: m.end(),
+                })
+                .collect::<Vec<_>>()
+        })
+        .collect()
+}
+
+/// Size and cycle count of every statement that assembles. Frame epilogues
+/// emitted in front of `ret` and `end` are not attributed to those lines.
+pub fn inlay_hints(program: &str) -> Vec<InlayHint> {
+    let regex_frame = Regex::new(r"^\.frame\s+(?P<size>[[:digit:]]+)$").unwrap();
+    program
+        .lines()
+        .enumerate()
+        .filter_map(|(line_num, text)| {
+            let code = code_of(text);
+            let trimmed = code.trim_start();
+            let is_label = matches!(
+                AsmRow::from_str(trimmed),
+                Ok(AsmRow::LabelCall(_) | AsmRow::LabelJmp(_) | AsmRow::LabelProphet(_))
+            );
+            if trimmed.is_empty() || is_label {
+                return None;
+            }
+            let statements = match regex_frame.captures(trimmed) {
+                Some(caps) => frame_prologue(caps["size"].parse().ok()?),
+                None => lower_statement(trimmed).ok()?,
+            };
+            let words = statements
+                .iter()
+                .map(|statement| {
+                    OlaAsmInstruction::from_str(statement).map(|asm| asm.binary_length() as usize)
+                })
+                .sum::<Result<usize, String>>()
+                .ok()?;
+            Some(InlayHint {
+                line: line_num,
+                character: code.len(),
+                words,
+                cycles: statements.len(),
+            })
+        })
+        .collect()
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::lsp::{definition, inlay_hints, register_highlights, Span};
+
+    #[test]
+    fn test_lsp_queries() {
+        let program = "main:\n.LBL0_0:\n  mov r1 10 ; init\n  call fib\n  mstore [r8,-1] r1\n  jmp .LBL0_0\n  lt r0 r1 r2\nfib:\n  ret";
+
+        assert_eq!(
+            definition(program, 3, 8),
+            Some(Span {
+                line: 7,
+                start: 0,
+                end: 3
+            })
+        );
+        assert_eq!(
+            definition(program, 5, 6),
+            Some(Span {
+                line: 1,
+                start: 0,
+                end: 7
+            })
+        );
+        assert_eq!(definition(program, 2, 3), None);
+
+        let highlights: Vec<(usize, usize)> = register_highlights(program, 4, 4)
+            .iter()
+            .map(|span| (span.line, span.start))
+            .collect();
+        assert!(highlights.is_empty());
+        let highlights: Vec<(usize, usize)> = register_highlights(program, 2, 6)
+            .iter()
+            .map(|span| (span.line, span.start))
+            .collect();
+        assert_eq!(highlights, vec![(2, 6), (4, 17), (6, 8)]);
+
+        let hints: Vec<(usize, usize, usize)> = inlay_hints(program)
+            .iter()
+            .map(|hint| (hint.line, hint.words, hint.cycles))
+            .collect();
+        assert_eq!(
+            hints,
+            vec![
+                (2, 2, 1),
+                (3, 2, 1),
+                (4, 2, 1),
+                (5, 2, 1),
+                (6, 3, 2),
+                (8, 1, 1)
+            ]
+        );
+    }
+}
@@ -0,0 +1,182 @@
+use std::collections::HashMap;
+use std::fmt::{Display, Formatter};
+use std::str::FromStr;
+
+use core::vm::hardware::OlaRegister;
+use core::vm::operands::ImmediateValue;
+
+use crate::frame::strip_comment;
+use crate::relocate::lower_program;
+use crate::source::SourceLine;
+
+// Redundant range checks. Within a basic block, `range r` is dropped when r
+// already holds a value the AIR proves below 2^32:
+//
+//   range r          the rangecheck table checked it
+//   mov r imm        imm is a u32, mov constrains r to it
+//   mov r s          s is proven
+//   and/or/xor d a b the bitwise table splits a, b and d into u8 limbs
+//
+// Writing r drops what was proven about it. `gte` only range checks the
+// difference of its operands, eq and neq do not constrain their result to a
+// boolean and memory accesses do not check values, so none of them proves
+// anything. Labels, jumps, calls and any other instruction end the block and
+// forget everything. Every dropped check saves a cpu and a rangecheck row.
+
+const BITWISE_OPS: [&str; 3] = ["and", "or", "xor"];
+const WRITING_OPS: [&str; 10] = [
+    "add", "mul", "eq", "neq", "gte", "mov", "not", "and", "or", "xor",
+];
+const NON_WRITING_OPS: [&str; 2] = ["assert", "mstore"];
+
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct RemovedRangeCheck {
+    /// Line of the dropped check in the lowered program.
+    pub line: usize,
+    pub register: String,
+    /// Line of the instruction that proved the register a u32.
+    pub proven_at: usize,
+}
+
+#[derive(Debug, Clone, Default, PartialEq, Eq)]
+pub struct RangeCheckReport {
+    /// Range checks of the lowered program, dropped ones included.
+    pub total: usize,
+    pub removed: Vec<RemovedRangeCheck>,
+}
+
+impl Display for RangeCheckReport {
+    fn fmt(&self, f: &mut Formatter<'_>) -> std::fmt::Result {
+        writeln!(
+            f,
+            "removed {} of {} range checks, saving {} cpu and {} rangecheck rows",
+            self.removed.len(),
+            self.total,
+            self.removed.len(),
+            self.removed.len()
+        )?;
+        for removed in &self.removed {
+            writeln!(
+                f,
+                "line {}: range {} already proven at line {}",
+                removed.line, removed.register, removed.proven_at
+            )?;
+        }
+        Ok(())
+    }
+}
+
+fn is_register(operand: &str) -> bool {
+    OlaRegister::from_str(operand).is_ok()
+}
+
+fn is_u32_immediate(operand: &str) -> bool {
+    ImmediateValue::from_str(operand)
+        .ok()
+        .and_then(|value| value.to_u64().ok())
+        .map_or(false, |value| value <= u32::MAX as u64)
+}
+
+pub(crate) fn eliminate_range_check_lines(
+    lines: Vec<SourceLine>,
+) -> (Vec<SourceLine>, RangeCheckReport) {
+    let mut kept: Vec<SourceLine> = vec![];
+    let mut report = RangeCheckReport::default();
+    // register => line proving it a u32
+    let mut proven: HashMap<String, usize> = HashMap::new();
+    for (line_num, line) in lines.into_iter().enumerate() {
+        let pieces: Vec<&str> = strip_comment(&line.text).split_whitespace().collect();
+        let op = match pieces.first() {
+            Some(op) => *op,
+            None => {
+                kept.push(line);
+                continue;
+            }
+        };
+        if op == "range" && pieces.len() == 2 && is_register(pieces[1]) {
+            report.total += 1;
+            if let Some(proven_at) = proven.get(pieces[1]) {
+                report.removed.push(RemovedRangeCheck {
+                    line: line_num,
+                    register: pieces[1].to_string(),
+                    proven_at: *proven_at,
+                });
+                continue;
+            }
+            proven.insert(pieces[1].to_string(), line_num);
+        } else if WRITING_OPS.contains(&op) && pieces.len() >= 2 {
+            let dst = pieces[1];
+            let source_proven = match (op, pieces.get(2)) {
+                ("mov", Some(src)) if is_register(src) => proven.contains_key(*src),
+                ("mov", Some(src)) => is_u32_immediate(src),
+                _ => false,
+            };
+            proven.remove(dst);
+            if BITWISE_OPS.contains(&op) {
+                for operand in pieces.iter().skip(1).filter(|p| is_register(p)) {
+                    proven.insert(operand.to_string(), line_num);
+                }
+            } else if source_proven {
+                proven.insert(dst.to_string(), line_num);
+            }
+        } else if op == "mload" && pieces.len() >= 2 {
+            proven.remove(pieces[1]);
+        } else if !NON_WRITING_OPS.contains(&op) {
+            proven.clear();
+        }
+        kept.push(line);
+    }
+    (kept, report)
+}
+
+/// Range checks the assembler drops from `program`, and the rows it saves.
+pub fn range_check_report(program: &str) -> Result<RangeCheckReport, String> {
+    let lines = lower_program(program).map_err(|e| e.to_string())?;
+    Ok(eliminate_range_check_lines(lines).1)
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::rangecheck::{eliminate_range_check_lines, range_check_report};
+    use crate::source::{join_lines, source_lines};
+
+    #[test]
+    fn test_eliminate_range_checks() {
+        let program = "main:\nrange r1\nrange r1\nmov r2 7\nrange r2\nmov r3 r1\nrange r3\n\
+                       gte r4 r5 r6\nrange r5\nand r7 r8 r9\nrange r8\nrange r7\n\
+                       add r1 r1 r2\nrange r1\nmov r2 18446744069414584320\nrange r2\n\
+                       call foo\nrange r3\nend";
+        let (optimized, report) = eliminate_range_check_lines(source_lines(program));
+        assert_eq!(
+            join_lines(&optimized),
+            "main:\nrange r1\nmov r2 7\nmov r3 r1\n\
+             gte r4 r5 r6\nrange r5\nand r7 r8 r9\n\
+             add r1 r1 r2\nrange r1\nmov r2 18446744069414584320\nrange r2\n\
+             call foo\nrange r3\nend"
+        );
+        assert_eq!(report.total, 10);
+        let removed: Vec<_> = report
+            .removed
+            .iter()
+            .map(|r| (r.line, r.register.as_str(), r.proven_at))
+            .collect();
+        assert_eq!(
+            removed,
+            vec![
+                (2, "r1", 1),
+                (4, "r2", 3),
+                (6, "r3", 5),
+                (10, "r8", 9),
+                (11, "r7", 9)
+            ]
+        );
+        assert!(report
+            .to_string()
+            .starts_with("removed 5 of 10 range checks"));
+
+        // the checks of `addc r3 r1 r2` cover the operands of the next one
+        let report = range_check_report("main:\naddc r3 r1 r2\naddc r4 r3 r1\nend").unwrap();
+        assert_eq!(report.total, 6);
+        assert_eq!(report.removed.len(), 2);
+    }
+}
@@ -1,40 +1,57 @@
 use crate::asm::{AsmRow, OlaAsmInstruction};
-use core::program::binary_program::{OlaProphetInput, OlaProphetOutput};
+use crate::bundle::{AsmBundle, OlaAsmProphet};
+use crate::checked::lower_checked_arithmetic;
+use crate::cmov::lower_selects;
+use crate::compare::lower_compares;
+use crate::frame::lower_frames;
+use crate::rangecheck::eliminate_range_check_lines;
+use crate::returns::take_return_arity;
+use crate::source::{source_lines, SourceError, SourceLine};
+use crate::stdlib::link_stdlib;
+use crate::wide::lower_wide_immediates;
 use serde::{Deserialize, Serialize};
 use std::cmp::Ordering;
 use std::collections::HashMap;
 use std::str::FromStr;
 
-#[derive(Debug, Clone, Serialize, Deserialize)]
-pub(crate) struct AsmBundle {
-    program: String,
-    prophets: Vec<OlaAsmProphet>,
+/// Runs the lowering passes over `program`, the stdlib linked in. Every
+/// lowered line keeps the line of `program` it came from.
+pub(crate) fn lower_program(program: &str) -> Result<Vec<SourceLine>, SourceError> {
+    let lines = lower_frames(source_lines(&link_stdlib(program)?))?;
+    let lines = lower_compares(lower_wide_immediates(lines)?)?;
+    lower_selects(lower_checked_arithmetic(lines)?)
 }
 
 #[derive(Debug, Clone)]
 struct AsmScope {
     label: String,
-    lines: Vec<String>,
+    lines: Vec<SourceLine>,
 }
 
 impl AsmBundle {
-    fn generate_sorted_asm_scopes(&self) -> Result<Vec<AsmScope>, String> {
-        let mut lines = self.program.lines();
+    fn generate_sorted_asm_scopes(&self) -> Result<Vec<AsmScope>, SourceError> {
+        let (lines, _) = eliminate_range_check_lines(lower_program(&self.program)?);
+        let mut lines = lines.into_iter();
         let mut scopes: Vec<AsmScope> = vec![];
         let mut current_scope_label: String = String::new();
-        let mut current_scope_lines: Vec<String> = vec![];
-        let mut line_num = 0;
+        let mut current_scope_lines: Vec<SourceLine> = vec![];
         loop {
             if let Some(line) = lines.next() {
-                let processed_line = line_pre_process(line);
-                if processed_line.is_empty() {
+                let processed_line = SourceLine {
+                    line: line.line,
+                    text: line_pre_process(&line.text).to_string(),
+                };
+                if processed_line.text.is_empty() {
                     continue;
                 }
 
-                let row_res = AsmRow::from_str(processed_line.clone());
+                let row_res = AsmRow::from_str(&processed_line.text);
                 if row_res.is_err() {
                     let err_msg = row_res.err().unwrap();
-                    return Err(format!("line {}: {} ==> {}", line_num, line, err_msg));
+                    return Err(SourceError::new(
+                        line.line,
+                        format!("line {}: {} ==> {}", line.line, line.text, err_msg),
+                    ));
                 }
                 let row = row_res.unwrap();
                 match row {
@@ -48,10 +65,10 @@ impl AsmBundle {
                         }
                         current_scope_label = label;
                         current_scope_lines.clear();
-                        current_scope_lines.push(processed_line.to_string())
+                        current_scope_lines.push(processed_line)
                     }
                     _ => {
-                        current_scope_lines.push(processed_line.to_string());
+                        current_scope_lines.push(processed_line);
                     }
                 };
             } else {
@@ -64,7 +81,6 @@ impl AsmBundle {
                 }
                 break;
             }
-            line_num += 1;
         }
         scopes.sort_by(|a, b| {
             if a.label == "main" {
@@ -76,10 +92,17 @@ impl AsmBundle {
             }
         });
         if scopes.is_empty() {
-            return Err(format!("generate scopes error, no scope found"));
+            return Err(SourceError::new(
+                1,
+                format!("generate scopes error, no scope found"),
+            ));
         }
-        if scopes.first().unwrap().label != "main" {
-            return Err(format!("generate scopes error, no main scope found"));
+        let first = scopes.first().unwrap();
+        if first.label != "main" {
+            return Err(SourceError::new(
+                first.lines[0].line,
+                format!("generate scopes error, no main scope found"),
+            ));
         }
         Ok(scopes)
     }
@@ -92,13 +115,6 @@ pub(crate) struct AsmProphet {
     pub(crate) inputs: Vec<String>,
     pub(crate) outputs: Vec<String>,
 }
-#[derive(Debug, Clone, Serialize, Deserialize)]
-pub(crate) struct OlaAsmProphet {
-    pub(crate) label: String,
-    pub(crate) code: String,
-    pub(crate) inputs: Vec<OlaProphetInput>,
-    pub(crate) outputs: Vec<OlaProphetOutput>,
-}
 
 #[derive(Debug, Clone)]
 pub(crate) struct RelocatedAsmBundle {
@@ -106,21 +122,50 @@ pub(crate) struct RelocatedAsmBundle {
     pub(crate) prophets: HashMap<usize, OlaAsmProphet>,
     pub(crate) mapper_label_call: HashMap<String, usize>,
     pub(crate) mapper_label_jmp: HashMap<String, usize>,
+    pub(crate) return_arity: Option<usize>,
+    /// Source line of every instruction.
+    pub(crate) lines: Vec<usize>,
 }
 
 pub(crate) fn asm_relocate(bundle: AsmBundle) -> Result<RelocatedAsmBundle, String> {
+    let (mut relocated, mapper_label_prophet) =
+        relocate_program(&bundle.program).map_err(|e| e.to_string())?;
+    let mut prophets: HashMap<usize, OlaAsmProphet> = HashMap::new();
+    let asm_prophets = bundle.prophets.clone();
+    let mut prophets_iter = asm_prophets.iter();
+    while let Some(prophet) = prophets_iter.next() {
+        let host = mapper_label_prophet.get(prophet.label.as_str());
+        if host.is_none() {
+            return Err(format!(
+                "relocate error, prophet cannot find host: {}",
+                prophet.label
+            ));
+        }
+        prophets.insert(host.unwrap().clone(), prophet.clone());
+    }
+    relocated.prophets = prophets;
+    Ok(relocated)
+}
+
+/// Relocates `program` without prophets, returning the host of every prophet
+/// label along with it.
+pub(crate) fn relocate_program(
+    program: &str,
+) -> Result<(RelocatedAsmBundle, HashMap<String, usize>), SourceError> {
+    let (program, return_arity) = take_return_arity(program)?;
+    let bundle = AsmBundle::new(program);
     let scopes_res = bundle.generate_sorted_asm_scopes();
     if scopes_res.is_err() {
-        return Err(format!(
-            "asm relocate err ==> {}",
-            scopes_res.err().unwrap()
+        let err = scopes_res.err().unwrap();
+        return Err(SourceError::new(
+            err.line,
+            format!("asm relocate err ==> {}", err),
         ));
     }
     let scopes = scopes_res.unwrap();
-    let scopes_codes: Vec<String> = scopes.iter().map(|scope| scope.lines.join("\n")).collect();
-    let resorted_program = scopes_codes.join("\n");
 
     let mut instructions: Vec<OlaAsmInstruction> = vec![];
+    let mut instruction_lines: Vec<usize> = vec![];
     let mut mapper_label_call: HashMap<String, usize> = HashMap::new();
     let mut mapper_label_jmp: HashMap<String, usize> = HashMap::new();
     let mut mapper_label_prophet: HashMap<String, usize> = HashMap::new();
@@ -129,14 +174,17 @@ pub(crate) fn asm_relocate(bundle: AsmBundle) -> Result<RelocatedAsmBundle, Stri
     let mut ori_counter: usize = 0;
     let mut label_stack: Vec<AsmRow> = vec![];
 
-    let mut lines = resorted_program.lines();
+    let mut lines = scopes.iter().flat_map(|scope| scope.lines.iter());
 
     loop {
         if let Some(line) = lines.next() {
-            let row_res = AsmRow::from_str(line);
+            let row_res = AsmRow::from_str(&line.text);
             if row_res.is_err() {
                 let err_msg = row_res.err().unwrap();
-                return Err(format!("{} ==> {}", line, err_msg));
+                return Err(SourceError::new(
+                    line.line,
+                    format!("{} ==> {}", line.text, err_msg),
+                ));
             }
             let row = row_res.unwrap();
             match row {
@@ -155,6 +203,7 @@ pub(crate) fn asm_relocate(bundle: AsmBundle) -> Result<RelocatedAsmBundle, Stri
                     });
                     label_stack.clear();
                     instructions.push(instruction.clone());
+                    instruction_lines.push(line.line);
                     ori_counter = counter;
                     counter += instruction.binary_length() as usize;
                 }
@@ -206,25 +255,17 @@ pub(crate) fn asm_relocate(bundle: AsmBundle) -> Result<RelocatedAsmBundle, Stri
         }
     }
 
-    let mut prophets: HashMap<usize, OlaAsmProphet> = HashMap::new();
-    let asm_prophets = bundle.prophets.clone();
-    let mut prophets_iter = asm_prophets.iter();
-    while let Some(prophet) = prophets_iter.next() {
-        let host = mapper_label_prophet.get(prophet.label.as_str());
-        if host.is_none() {
-            return Err(format!(
-                "relocate error, prophet cannot find host: {}",
-                prophet.label
-            ));
-        }
-        prophets.insert(host.unwrap().clone(), prophet.clone());
-    }
-    Ok(RelocatedAsmBundle {
-        instructions,
-        prophets,
-        mapper_label_call,
-        mapper_label_jmp,
-    })
+    Ok((
+        RelocatedAsmBundle {
+            instructions,
+            prophets: HashMap::new(),
+            mapper_label_call,
+            mapper_label_jmp,
+            return_arity,
+            lines: instruction_lines,
+        },
+        mapper_label_prophet,
+    ))
 }
 
 // remove comments and trim
@@ -0,0 +1,75 @@
+use crate::frame::strip_comment;
+use crate::source::SourceError;
+use core::program::MAX_RETURN_VALUES;
+
+// `.returns N` declares that the program returns its first N registers
+// `r0..` at `end`. It may appear once, anywhere; the line is blanked so line
+// numbers of the later passes still match the source.
+
+const RETURNS_DIRECTIVE: &str = ".returns";
+
+fn parse_returns_directive(code: &str) -> Option<Result<usize, String>> {
+    let mut pieces = code.split_whitespace();
+    if pieces.next() != Some(RETURNS_DIRECTIVE) {
+        return None;
+    }
+    let arity = match (pieces.next(), pieces.next()) {
+        (Some(arity), None) => match arity.parse::<usize>() {
+            Ok(arity) if arity <= MAX_RETURN_VALUES => Ok(arity),
+            Ok(_) => Err(format!(
+                "{} ==> at most {} return registers",
+                code, MAX_RETURN_VALUES
+            )),
+            Err(_) => Err(format!("{} ==> invalid return arity", code)),
+        },
+        _ => Err(format!(
+            "{} ==> returns directive needs exactly one arity",
+            code
+        )),
+    };
+    Some(arity)
+}
+
+/// Removes the `.returns` directive of `program` and returns its arity.
+pub(crate) fn take_return_arity(program: &str) -> Result<(String, Option<usize>), SourceError> {
+    let mut arity = None;
+    let mut lines = vec![];
+    for (line_num, line) in (1..).zip(program.lines()) {
+        let code = strip_comment(line);
+        match parse_returns_directive(code) {
+            Some(parsed) => {
+                let parsed = parsed
+                    .map_err(|e| SourceError::new(line_num, format!("line {}: {}", line_num, e)))?;
+                if arity.replace(parsed).is_some() {
+                    return Err(SourceError::new(
+                        line_num,
+                        format!(
+                            "line {}: {} ==> return arity declared twice",
+                            line_num, line
+                        ),
+                    ));
+                }
+                lines.push("");
+            }
+            None => lines.push(line),
+        }
+    }
+    Ok((lines.join("\n"), arity))
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::returns::take_return_arity;
+
+    #[test]
+    fn test_take_return_arity() {
+        let (program, arity) = take_return_arity("main:\n.returns 2 ; r0, r1\nend").unwrap();
+        assert_eq!(program, "main:\n\nend");
+        assert_eq!(arity, Some(2));
+        assert_eq!(take_return_arity("main:\nend").unwrap().1, None);
+
+        assert!(take_return_arity("main:\n.returns 5\nend").is_err());
+        assert!(take_return_arity("main:\n.returns x\nend").is_err());
+        assert!(take_return_arity("main:\n.returns 1\n.returns 1\nend").is_err());
+    }
+}
@@ -0,0 +1,103 @@
+use crate::frame::strip_comment;
+use std::fmt::{Display, Formatter};
+
+// Lowering passes rewrite one statement into several. Every lowered line
+// keeps the 1-based line of the source statement it was lowered from, so the
+// errors of later passes still point at the source.
+
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub(crate) struct SourceLine {
+    pub(crate) line: usize,
+    pub(crate) text: String,
+}
+
+/// An assembler error about a 1-based line of the source.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub(crate) struct SourceError {
+    pub(crate) line: usize,
+    pub(crate) message: String,
+}
+
+impl SourceError {
+    pub(crate) fn new(line: usize, message: String) -> Self {
+        Self { line, message }
+    }
+}
+
+impl Display for SourceError {
+    fn fmt(&self, f: &mut Formatter<'_>) -> std::fmt::Result {
+        write!(f, "{}", self.message)
+    }
+}
+
+pub(crate) fn source_lines(program: &str) -> Vec<SourceLine> {
+    program
+        .lines()
+        .enumerate()
+        .map(|(line_num, text)| SourceLine {
+            line: line_num + 1,
+            text: text.to_string(),
+        })
+        .collect()
+}
+
+pub(crate) fn join_lines(lines: &[SourceLine]) -> String {
+    lines
+        .iter()
+        .map(|line| line.text.as_str())
+        .collect::<Vec<_>>()
+        .join("\n")
+}
+
+/// Replaces every line by the statements `lower` makes of its code, `None`
+/// keeps the line as is. Errors read `line N: <line> ==> <reason>`.
+pub(crate) fn lower_lines<F>(
+    lines: Vec<SourceLine>,
+    mut lower: F,
+) -> Result<Vec<SourceLine>, SourceError>
+where
+    F: FnMut(&str) -> Result<Option<Vec<String>>, String>,
+{
+    let mut lowered = Vec::with_capacity(lines.len());
+    for source in lines {
+        let statements = lower(strip_comment(&source.text)).map_err(|e| {
+            SourceError::new(
+                source.line,
+                format!("line {}: {} ==> {}", source.line, source.text, e),
+            )
+        })?;
+        match statements {
+            Some(statements) => lowered.extend(statements.into_iter().map(|text| SourceLine {
+                line: source.line,
+                text,
+            })),
+            None => lowered.push(source),
+        }
+    }
+    Ok(lowered)
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::source::{join_lines, lower_lines, source_lines};
+
+    #[test]
+    fn test_lower_lines() {
+        let lines = source_lines("main:\ntwice ; doubled\nend");
+        let lowered = lower_lines(lines, |code| {
+            Ok((code == "twice").then(|| vec!["once".to_string(), "once".to_string()]))
+        })
+        .unwrap();
+        assert_eq!(join_lines(&lowered), "main:\nonce\nonce\nend");
+        let origins: Vec<usize> = lowered.iter().map(|l| l.line).collect();
+        assert_eq!(origins, vec![1, 2, 2, 3]);
+
+        let err = lower_lines(lowered, |code| match code {
+            "end" => Err("no end".to_string()),
+            _ => Ok(None),
+        })
+        .unwrap_err();
+        assert_eq!(err.line, 3);
+        assert_eq!(err.to_string(), "line 3: end ==> no end");
+    }
+}
@@ -0,0 +1,126 @@
+use crate::asm::AsmRow;
+use crate::frame::strip_comment;
+use crate::source::SourceError;
+use regex::Regex;
+use std::collections::HashSet;
+use std::str::FromStr;
+
+// Standard library routines. A program calling `std_*` without defining it
+// gets the routine appended at link time. Routine jmp labels are written as
+// `.LBL0_k` and renumbered past the labels of the program they are linked
+// into. Arguments are passed in r1-r3 and results returned in r0, see the
+// header of each routine for clobbered registers and cycle counts.
+
+pub(crate) const STDLIB: [(&str, &str); 5] = [
+    (
+        "std_ledger_transfer",
+        include_str!("../stdlib/ledger_transfer.asm"),
+    ),
+    ("std_memcpy", include_str!("../stdlib/memcpy.asm")),
+    ("std_memset", include_str!("../stdlib/memset.asm")),
+    ("std_reverse", include_str!("../stdlib/reverse.asm")),
+    ("std_u256_add", include_str!("../stdlib/u256_add.asm")),
+];
+
+fn stdlib_routine(name: &str) -> Option<&'static str> {
+    STDLIB
+        .iter()
+        .find(|(routine, _)| *routine == name)
+        .map(|(_, code)| *code)
+}
+
+fn code_lines(program: &str) -> impl Iterator<Item = &str> {
+    program.lines().map(strip_comment)
+}
+
+fn defined_functions(program: &str) -> HashSet<String> {
+    code_lines(program)
+        .filter_map(|code| match AsmRow::from_str(code) {
+            Ok(AsmRow::LabelCall(label)) => Some(label),
+            _ => None,
+        })
+        .collect()
+}
+
+// (1-based line, target) of every call
+fn called_functions(program: &str) -> Vec<(usize, String)> {
+    (1..)
+        .zip(code_lines(program))
+        .filter_map(|(line, code)| {
+            let mut pieces = code.split_whitespace();
+            match (pieces.next(), pieces.next()) {
+                (Some("call"), Some(target)) | (Some("tcall"), Some(target)) => {
+                    Some((line, target.to_string()))
+                }
+                _ => None,
+            }
+        })
+        .collect()
+}
+
+pub(crate) fn next_label_scope(program: &str) -> u64 {
+    let regex = Regex::new(r"\.LBL(?P<scope>[[:digit:]]+)_").unwrap();
+    regex
+        .captures_iter(program)
+        .filter_map(|caps| caps["scope"].parse::<u64>().ok())
+        .max()
+        .map_or(0, |scope| scope + 1)
+}
+
+fn relabel(routine: &str, scope: u64) -> String {
+    let regex = Regex::new(r"\.LBL0_").unwrap();
+    regex
+        .replace_all(routine, format!(".LBL{}_", scope).as_str())
+        .to_string()
+}
+
+/// Append every standard library routine the program calls but does not
+/// define.
+pub(crate) fn link_stdlib(program: &str) -> Result<String, SourceError> {
+    let mut linked = program.to_string();
+    loop {
+        let defined = defined_functions(&linked);
+        let missing: Vec<(usize, String)> = called_functions(&linked)
+            .into_iter()
+            .filter(|(_, target)| target.starts_with("std_") && !defined.contains(target))
+            .collect();
+        let (line, target) = match missing.first() {
+            Some(missing) => missing,
+            None => return Ok(linked),
+        };
+        let routine = stdlib_routine(target).ok_or(SourceError::new(
+            *line,
+            format!("unknown standard library routine: {}", target),
+        ))?;
+        let scope = next_label_scope(&linked);
+        linked.push('\n');
+        linked.push_str(&relabel(routine, scope));
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::stdlib::{link_stdlib, STDLIB};
+
+    #[test]
+    fn test_link_stdlib() {
+        let program = "main:\n.LBL0_0:\ncall std_memset\ncall std_memcpy\nend";
+        let linked = link_stdlib(program).unwrap();
+        assert_eq!(linked.matches("std_memset:").count(), 1);
+        assert_eq!(linked.matches("std_memcpy:").count(), 1);
+        assert!(!linked.contains("std_reverse:"));
+        assert!(linked.contains(".LBL1_0:"));
+        assert!(linked.contains(".LBL2_0:"));
+        assert_eq!(link_stdlib(&linked).unwrap(), linked);
+
+        assert_eq!(
+            link_stdlib("main:\ncall std_nothing\nend")
+                .unwrap_err()
+                .line,
+            2
+        );
+        for (name, code) in STDLIB {
+            assert!(code.contains(&format!("{}:", name)));
+        }
+    }
+}
@@ -1,7 +1,8 @@
 #[cfg(test)]
 mod tests {
+    use crate::bundle::AsmBundle;
     use crate::encoder::encode_to_binary;
-    use crate::relocate::{asm_relocate, AsmBundle};
+    use crate::relocate::asm_relocate;
     use core::program::decoder::decode_binary_program_to_instructions;
 
     // #[test]
@@ -1,7 +1,8 @@
 #[cfg(test)]
 mod tests {
+    use crate::bundle::AsmBundle;
     use crate::encoder::encode_to_binary;
-    use crate::relocate::{asm_relocate, AsmBundle};
+    use crate::relocate::asm_relocate;
     use log::LevelFilter;
     use std::fs;
     use std::path::Path;
@@ -203,6 +204,11 @@ mod tests {
         generate_from_file("global.json".to_string(), "global.json".to_string());
     }
 
+    #[test]
+    fn generate_stdlib() {
+        generate_from_file("stdlib.json".to_string(), "stdlib.json".to_string());
+    }
+
     #[test]
     fn generate_system() {
         generate_from_file(
@@ -3,8 +3,10 @@ mod tests {
     use core::program::binary_program::BinaryProgram;
     use std::path::PathBuf;
 
-    use crate::encoder::encode_asm_from_json_file;
+    use crate::encoder::{encode_asm, encode_asm_from_json_file};
     use core::program::decoder::decode_binary_program_to_instructions;
+    use core::vm::opcodes::OlaOpcode;
+    use core::vm::operands::OlaOperand;
 
     #[test]
     fn test_decode_malloc() {
@@ -51,11 +53,41 @@ mod tests {
         test_decode("fibo_recursive.json".to_string());
     }
 
+    #[test]
+    fn test_decode_fibo_recursive_frame() {
+        test_decode("fibo_recursive_frame.json".to_string());
+    }
+
     #[test]
     fn test_decode_fibo_loop() {
         test_decode("fibo_loop.json".to_string());
     }
 
+    #[test]
+    fn test_encode_wide_immediate() {
+        let program =
+            encode_asm("main:\nmov128 r1 0xffffffff000000020000000300000004\nend").unwrap();
+        let instructions = decode_binary_program_to_instructions(program.clone()).unwrap();
+        let limbs: Vec<(u8, u64)> = instructions
+            .iter()
+            .filter(|inst| inst.opcode == OlaOpcode::MOV)
+            .map(|inst| match (&inst.dst, &inst.op1) {
+                (
+                    Some(OlaOperand::RegisterOperand { register }),
+                    Some(OlaOperand::ImmediateOperand { value }),
+                ) => (register.index(), value.to_u64().unwrap()),
+                _ => panic!("mov128 must lower to register immediate movs"),
+            })
+            .collect();
+        assert_eq!(limbs, vec![(1, 4), (2, 3), (3, 2), (4, u32::MAX as u64)]);
+
+        let regenerated: Vec<String> = instructions
+            .iter()
+            .flat_map(|inst| inst.encode().unwrap())
+            .collect();
+        assert_eq!(regenerated.join("\n"), program.bytecode);
+    }
+
     fn test_decode(file_name: String) {
         let mut path = PathBuf::from(env!("CARGO_MANIFEST_DIR"));
         path.push("test_data/asm/");
@@ -0,0 +1,114 @@
+use std::str::FromStr;
+
+use crate::source::{join_lines, lower_lines, source_lines, SourceError, SourceLine};
+use core::vm::hardware::OlaRegister;
+
+// Double-word constants. A field element cannot hold a u128, so
+// `mov128 rN <u128>` is lowered into one `mov` per u32 limb into the adjacent
+// registers rN..rN+3, least significant limb first, the same limb layout the
+// stdlib u256 routines use. The constant may be decimal or `0x` hex.
+
+const WIDE_MOV: &str = "mov128";
+pub(crate) const WIDE_LIMBS: usize = 4;
+const LIMB_BITS: usize = 32;
+// r9 is the frame pointer and never a limb destination.
+const MAX_LIMB_REGISTER: u8 = 8;
+
+pub(crate) fn parse_u128(s: &str) -> Result<u128, String> {
+    let parsed = match s.strip_prefix("0x") {
+        Some(hex) => u128::from_str_radix(hex, 16),
+        None => s.parse::<u128>(),
+    };
+    parsed.map_err(|_| format!("invalid u128 constant: {}", s))
+}
+
+pub(crate) fn split_u128(value: u128) -> [u64; WIDE_LIMBS] {
+    let mut limbs = [0; WIDE_LIMBS];
+    for (i, limb) in limbs.iter_mut().enumerate() {
+        *limb = (value >> (i * LIMB_BITS)) as u64 & u32::MAX as u64;
+    }
+    limbs
+}
+
+fn limb_registers(register: &str) -> Result<Vec<String>, String> {
+    let first = OlaRegister::from_str(register)?.index();
+    if first + WIDE_LIMBS as u8 - 1 > MAX_LIMB_REGISTER {
+        return Err(format!(
+            "{} limbs of {} overlap the frame pointer",
+            WIDE_LIMBS, register
+        ));
+    }
+    Ok((first..first + WIDE_LIMBS as u8)
+        .map(|index| format!("r{}", index))
+        .collect())
+}
+
+fn expand_wide_mov(line: &str) -> Result<Vec<String>, String> {
+    let pieces: Vec<&str> = line.split_whitespace().collect();
+    if pieces.len() != 3 {
+        return Err(format!("{} needs a register and a constant", WIDE_MOV));
+    }
+    let registers = limb_registers(pieces[1])?;
+    let value = parse_u128(pieces[2])?;
+    Ok(registers
+        .iter()
+        .zip(split_u128(value))
+        .map(|(register, limb)| format!("mov {} {}", register, limb))
+        .collect())
+}
+
+pub(crate) fn expand_wide_immediates(program: &str) -> Result<String, String> {
+    lower_wide_immediates(source_lines(program))
+        .map(|lines| join_lines(&lines))
+        .map_err(|e| e.to_string())
+}
+
+pub(crate) fn lower_wide_immediates(
+    lines: Vec<SourceLine>,
+) -> Result<Vec<SourceLine>, SourceError> {
+    lower_lines(lines, |code| {
+        if code.split_whitespace().next() == Some(WIDE_MOV) {
+            expand_wide_mov(code).map(Some)
+        } else {
+            Ok(None)
+        }
+    })
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::relocate::lower_program;
+    use crate::wide::{expand_wide_immediates, split_u128};
+
+    #[test]
+    fn test_expand_wide_immediates() {
+        let program = "main:\nmov128 r1 0x0102030405060708090a0b0c0d0e0f10 ; amount\nend";
+        assert_eq!(
+            expand_wide_immediates(program).unwrap(),
+            "main:\nmov r1 219025168\nmov r2 151653132\nmov r3 84281096\nmov r4 16909060\nend"
+        );
+        assert_eq!(
+            split_u128(u128::MAX),
+            [
+                u32::MAX as u64,
+                u32::MAX as u64,
+                u32::MAX as u64,
+                u32::MAX as u64
+            ]
+        );
+
+        // errors after an expansion still point at the source line.
+        assert_eq!(
+            lower_program("main:\nmov128 r1 5\nmov128 r6 1\nend")
+                .unwrap_err()
+                .to_string(),
+            "line 3: mov128 r6 1 ==> 4 limbs of r6 overlap the frame pointer"
+        );
+        assert!(expand_wide_immediates("mov128 r6 1").is_err());
+        assert!(expand_wide_immediates("mov128 r1").is_err());
+        assert!(expand_wide_immediates("mov128 r1 -1").is_err());
+        assert!(
+            expand_wide_immediates("mov128 r0 340282366920938463463374607431768211456").is_err()
+        );
+    }
+}
@@ -0,0 +1,25 @@
+; std_ledger_transfer(r1 from, r2 to, r3 amount)
+; Moves amount from one balance slot of a token to another, see
+; core::types::storage::ledger. from and to each point at 8 words: the slot
+; key, then 4 words the slot value is loaded into. Balances are u32 in the
+; last value word. The assert fails when from holds less than amount, the
+; range check when to would overflow. Clobbers r4-r6.
+; Cycles: 17
+std_ledger_transfer:
+add r4 r1 4
+sload r1 r4
+mload r5 [r1,7]
+gte r6 r5 r3
+assert r6
+mul r6 r3 -1
+add r5 r5 r6
+mstore [r1,7] r5
+sstore r1 r4
+add r4 r2 4
+sload r2 r4
+mload r5 [r2,7]
+add r5 r5 r3
+range r5
+mstore [r2,7] r5
+sstore r2 r4
+ret
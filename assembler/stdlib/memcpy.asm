@@ -0,0 +1,15 @@
+; std_memcpy(r1 dst, r2 src, r3 len)
+; Copies len words from src to dst, front to back. Clobbers r1-r5.
+; Cycles: 8 * len + 3
+std_memcpy:
+.LBL0_0:
+eq r4 r3 0
+cjmp r4 .LBL0_1
+mload r5 [r2,0]
+mstore [r1,0] r5
+add r1 r1 1
+add r2 r2 1
+add r3 r3 -1
+jmp .LBL0_0
+.LBL0_1:
+ret
@@ -0,0 +1,13 @@
+; std_memset(r1 dst, r2 value, r3 len)
+; Writes value into len words starting at dst. Clobbers r1, r3, r4.
+; Cycles: 6 * len + 3
+std_memset:
+.LBL0_0:
+eq r4 r3 0
+cjmp r4 .LBL0_1
+mstore [r1,0] r2
+add r1 r1 1
+add r3 r3 -1
+jmp .LBL0_0
+.LBL0_1:
+ret
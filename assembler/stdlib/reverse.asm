@@ -0,0 +1,21 @@
+; std_reverse(r1 ptr, r2 len)
+; Reverses len words at ptr in place. Clobbers r1-r6.
+; Cycles: 12 * (len / 2) + 5 for even len, + 7 for odd len
+std_reverse:
+add r3 r1 r2
+add r3 r3 -1
+.LBL0_0:
+eq r4 r2 0
+cjmp r4 .LBL0_1
+eq r4 r2 1
+cjmp r4 .LBL0_1
+mload r5 [r1,0]
+mload r6 [r3,0]
+mstore [r1,0] r6
+mstore [r3,0] r5
+add r1 r1 1
+add r3 r3 -1
+add r2 r2 -2
+jmp .LBL0_0
+.LBL0_1:
+ret
@@ -0,0 +1,30 @@
+; std_u256_add(r1 a, r2 b, r3 out) -> r0 carry
+; Adds two 256-bit integers stored as 8 u32 limbs, least significant limb
+; first. Every output limb is range checked. out may alias a or b.
+; The carry is taken off the limb by adding carry * -2^32. Clobbers r1-r6.
+; Cycles: 149 at most, 3 less for every limb where a + b + carry is 0
+std_u256_add:
+mov r4 8
+mov r0 0
+.LBL0_0:
+eq r5 r4 0
+cjmp r5 .LBL0_1
+mload r5 [r1,0]
+mload r6 [r2,0]
+add r5 r5 r6
+add r5 r5 r0
+eq r6 r5 0
+cjmp r6 .LBL0_2
+gte r0 r5 4294967296
+mul r6 r0 18446744065119617025
+add r5 r5 r6
+.LBL0_2:
+range r5
+mstore [r3,0] r5
+add r1 r1 1
+add r2 r2 1
+add r3 r3 1
+add r4 r4 -1
+jmp .LBL0_0
+.LBL0_1:
+ret
@@ -0,0 +1,31 @@
+use circuits::fixtures::fixture;
+use core::trace::compress::{compress_trace, decompress_trace, DEFAULT_LEVEL};
+use criterion::{criterion_group, criterion_main, BenchmarkId, Criterion};
+
+fn witness_compression_benchmark(c: &mut Criterion) {
+    let mut group = c.benchmark_group("witness_compression");
+    for name in ["fibonacci", "erc20_transfer"] {
+        let trace = fixture(name).unwrap().execute().unwrap().trace;
+        let json = serde_json::to_vec(&trace).unwrap();
+        let compressed = compress_trace(&trace, DEFAULT_LEVEL).unwrap();
+        println!(
+            "{}: json {} bytes, compressed {} bytes, ratio {:.1}",
+            name,
+            json.len(),
+            compressed.len(),
+            json.len() as f64 / compressed.len() as f64
+        );
+        group.bench_with_input(BenchmarkId::new("compress", name), &trace, |b, trace| {
+            b.iter(|| compress_trace(trace, DEFAULT_LEVEL).unwrap())
+        });
+        group.bench_with_input(
+            BenchmarkId::new("decompress", name),
+            &compressed,
+            |b, compressed| b.iter(|| decompress_trace(compressed).unwrap()),
+        );
+    }
+    group.finish();
+}
+
+criterion_group!(benches, witness_compression_benchmark);
+criterion_main!(benches);
@@ -0,0 +1,50 @@
+main:
+.LBL0_0:
+  add r9 r9 16
+  mov r1 1
+  mstore [r9,-16] r1
+  mov r1 0
+  mstore [r9,-15] r1
+  mstore [r9,-14] r1
+  mstore [r9,-13] r1
+  mov r1 2
+  mstore [r9,-8] r1
+  mov r1 0
+  mstore [r9,-7] r1
+  mstore [r9,-6] r1
+  mstore [r9,-5] r1
+  mov r1 100
+  mstore [r9,-12] r1
+  mov r1 0
+  mstore [r9,-11] r1
+  mstore [r9,-10] r1
+  mstore [r9,-9] r1
+  add r1 r9 -16
+  add r2 r9 -12
+  sstore r1 r2
+  add r2 r9 -4
+  sload r1 r2
+  mload r3 [r9,-4]
+  gte r4 r3 30
+  assert r4
+  add r3 r3 -30
+  mstore [r9,-4] r3
+  sstore r1 r2
+  add r1 r9 -8
+  sload r1 r2
+  mload r3 [r9,-4]
+  add r3 r3 30
+  mstore [r9,-4] r3
+  sstore r1 r2
+  add r1 r9 -16
+  sload r1 r2
+  mload r5 [r9,-4]
+  eq r6 r5 70
+  assert r6
+  add r1 r9 -8
+  sload r1 r2
+  mload r7 [r9,-4]
+  eq r6 r7 30
+  assert r6
+  add r9 r9 -16
+  end
@@ -0,0 +1,32 @@
+main:
+.LBL0_0:
+  add r9 r9 8
+  mov r1 1
+  mstore [r9,-8] r1
+  mov r1 2
+  mstore [r9,-7] r1
+  mov r1 3
+  mstore [r9,-6] r1
+  mov r1 4
+  mstore [r9,-5] r1
+  mov r4 0
+  jmp .LBL0_1
+.LBL0_1:
+  add r1 r9 -8
+  add r2 r9 -4
+  poseidon r2 r1 4
+  mload r5 [r9,-4]
+  mstore [r9,-8] r5
+  mload r5 [r9,-3]
+  mstore [r9,-7] r5
+  mload r5 [r9,-2]
+  mstore [r9,-6] r5
+  mload r5 [r9,-1]
+  mstore [r9,-5] r5
+  add r4 r4 1
+  gte r6 r4 4
+  cjmp r6 .LBL0_2
+  jmp .LBL0_1
+.LBL0_2:
+  add r9 r9 -8
+  end
@@ -0,0 +1,7 @@
+main:
+.returns 2
+.LBL0_0:
+  mov r0 7
+  add r1 r0 2
+  mov r2 11
+  end
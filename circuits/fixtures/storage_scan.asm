@@ -0,0 +1,49 @@
+main:
+.LBL0_0:
+  add r9 r9 32
+  mov r1 1
+  mstore [r9,-32] r1
+  mov r1 2
+  mstore [r9,-31] r1
+  mov r1 3
+  mstore [r9,-30] r1
+  mov r1 5
+  mstore [r9,-29] r1
+  mov r1 12
+  mstore [r9,-24] r1
+  mov r1 0
+  mstore [r9,-23] r1
+  mstore [r9,-22] r1
+  mstore [r9,-21] r1
+  add r5 r9 -32
+  add r6 r9 -24
+  sstore r5 r6
+  mov r1 4
+  mstore [r9,-30] r1
+  mov r1 13
+  mstore [r9,-24] r1
+  sstore r5 r6
+  mov r1 3
+  mstore [r9,-30] r1
+  mov r1 0
+  mstore [r9,-29] r1
+  mov r1 11
+  mstore [r9,-24] r1
+  sstore r5 r6
+  mov r1 0
+  mstore [r9,-28] r1
+  mov r7 3
+  add r6 r9 -16
+  sscan r7 r5 r6
+  eq r2 r7 2
+  assert r2
+  mload r3 [r9,-12]
+  eq r2 r3 11
+  assert r2
+  mload r3 [r9,-5]
+  eq r2 r3 5
+  assert r2
+  mload r3 [r9,-4]
+  eq r2 r3 12
+  assert r2
+  end
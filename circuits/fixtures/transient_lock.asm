@@ -0,0 +1,15 @@
+main:
+.LBL0_0:
+  tload_t r1 1
+  eq r2 r1 0
+  assert r2
+  mov r3 1
+  tstore_t r3 1
+  tload_t r1 r3
+  assert r1
+  mov r4 0
+  tstore_t r3 r4
+  tload_t r1 1
+  eq r2 r1 0
+  assert r2
+  end
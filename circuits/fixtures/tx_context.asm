@@ -0,0 +1,13 @@
+main:
+.LBL0_0:
+  txctx r1 0
+  eq r2 r1 1
+  assert r2
+  mov r5 1
+  txctx r1 r5
+  eq r2 r1 137
+  assert r2
+  txctx r1 4
+  eq r2 r1 140
+  assert r2
+  end
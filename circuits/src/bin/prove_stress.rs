@@ -0,0 +1,179 @@
+//! Runs concurrent prove jobs over a corpus of asm programs and reports
+//! throughput, allocator pressure and peak memory.
+//!
+//! usage: prove_stress <jobs> <workers> <asm json>...
+
+use assembler::encoder::encode_asm_from_json_file;
+use circuits::generation::{generate_traces, GenerationInputs};
+use circuits::stark::config::StarkConfig;
+use circuits::stark::ola_stark::OlaStark;
+use circuits::stark::prover::prove_with_traces;
+use core::merkle_tree::tree::AccountTree;
+use core::program::Program;
+use executor::Process;
+use plonky2::plonk::config::{GenericConfig, PoseidonGoldilocksConfig};
+use plonky2::util::timing::TimingTree;
+use std::alloc::{GlobalAlloc, Layout, System};
+use std::collections::HashMap;
+use std::sync::atomic::{AtomicU64, AtomicUsize, Ordering};
+use std::sync::{Arc, Mutex};
+use std::thread;
+use std::time::{Duration, Instant};
+
+const D: usize = 2;
+type C = PoseidonGoldilocksConfig;
+type F = <C as GenericConfig<D>>::F;
+
+struct CountingAlloc;
+
+static ALLOC_CALLS: AtomicU64 = AtomicU64::new(0);
+static ALLOC_BYTES: AtomicU64 = AtomicU64::new(0);
+static ALLOC_NANOS: AtomicU64 = AtomicU64::new(0);
+
+unsafe impl GlobalAlloc for CountingAlloc {
+    unsafe fn alloc(&self, layout: Layout) -> *mut u8 {
+        let start = Instant::now();
+        let ptr = System.alloc(layout);
+        ALLOC_NANOS.fetch_add(start.elapsed().as_nanos() as u64, Ordering::Relaxed);
+        ALLOC_CALLS.fetch_add(1, Ordering::Relaxed);
+        ALLOC_BYTES.fetch_add(layout.size() as u64, Ordering::Relaxed);
+        ptr
+    }
+
+    unsafe fn dealloc(&self, ptr: *mut u8, layout: Layout) {
+        System.dealloc(ptr, layout)
+    }
+}
+
+#[global_allocator]
+static GLOBAL: CountingAlloc = CountingAlloc;
+
+struct JobResult {
+    program: String,
+    elapsed: Duration,
+    ok: bool,
+}
+
+fn load_program(path: &str) -> Program {
+    let binary = encode_asm_from_json_file(path.to_string()).unwrap();
+    let mut program = Program::default();
+    for item in binary.prophets {
+        program.prophets.insert(item.host as u64, item);
+    }
+    program.instructions = binary.bytecode.split("\n").map(|e| e.to_string()).collect();
+    program
+}
+
+fn prove_job(template: &Program) -> bool {
+    let mut program = template.clone();
+    let mut process = Process::new();
+    if process
+        .execute(&mut program, &mut AccountTree::new_test())
+        .is_err()
+    {
+        return false;
+    }
+    let mut ola_stark = OlaStark::default();
+    let (traces, public_values) =
+        generate_traces(program, &mut ola_stark, GenerationInputs::default());
+    let config = StarkConfig::standard_fast_config();
+    prove_with_traces::<F, C, D>(
+        &ola_stark,
+        &config,
+        traces,
+        public_values,
+        &mut TimingTree::default(),
+    )
+    .is_ok()
+}
+
+/// Peak resident set size in KiB, linux only.
+fn peak_rss_kib() -> Option<u64> {
+    let status = std::fs::read_to_string("/proc/self/status").ok()?;
+    status
+        .lines()
+        .find(|l| l.starts_with("VmHWM:"))?
+        .split_whitespace()
+        .nth(1)?
+        .parse()
+        .ok()
+}
+
+fn main() {
+    let args: Vec<String> = std::env::args().collect();
+    if args.len() < 4 {
+        println!("usage: {} <jobs> <workers> <asm json>...", args[0]);
+        return;
+    }
+    let jobs: usize = args[1].parse().expect("jobs must be a number");
+    let workers: usize = args[2].parse().expect("workers must be a number");
+    let corpus: Vec<(String, Program)> = args[3..]
+        .iter()
+        .map(|path| (path.clone(), load_program(path)))
+        .collect();
+    let corpus = Arc::new(corpus);
+
+    let next_job = Arc::new(AtomicUsize::new(0));
+    let results = Arc::new(Mutex::new(Vec::with_capacity(jobs)));
+    let start = Instant::now();
+    let handles: Vec<_> = (0..workers)
+        .map(|_| {
+            let corpus = corpus.clone();
+            let next_job = next_job.clone();
+            let results = results.clone();
+            thread::spawn(move || loop {
+                let job = next_job.fetch_add(1, Ordering::Relaxed);
+                if job >= jobs {
+                    break;
+                }
+                let (name, program) = &corpus[job % corpus.len()];
+                let job_start = Instant::now();
+                let ok = prove_job(program);
+                results.lock().unwrap().push(JobResult {
+                    program: name.clone(),
+                    elapsed: job_start.elapsed(),
+                    ok,
+                });
+            })
+        })
+        .collect();
+    for handle in handles {
+        handle.join().unwrap();
+    }
+    let wall = start.elapsed();
+
+    let results = results.lock().unwrap();
+    let failed = results.iter().filter(|r| !r.ok).count();
+    let mut per_program: HashMap<&str, Vec<Duration>> = HashMap::new();
+    for r in results.iter() {
+        per_program.entry(&r.program).or_default().push(r.elapsed);
+    }
+
+    println!("jobs: {}, workers: {}, failed: {}", jobs, workers, failed);
+    println!("wall time: {} ms", wall.as_millis());
+    println!(
+        "throughput: {:.3} proofs/s",
+        (jobs - failed) as f64 / wall.as_secs_f64()
+    );
+    for (name, mut elapsed) in per_program {
+        elapsed.sort();
+        println!(
+            "{}: runs {}, p50 {} ms, max {} ms",
+            name,
+            elapsed.len(),
+            elapsed[elapsed.len() / 2].as_millis(),
+            elapsed.last().unwrap().as_millis()
+        );
+    }
+    let calls = ALLOC_CALLS.load(Ordering::Relaxed);
+    println!(
+        "allocations: {}, bytes: {}, avg alloc latency: {} ns",
+        calls,
+        ALLOC_BYTES.load(Ordering::Relaxed),
+        ALLOC_NANOS.load(Ordering::Relaxed) / calls.max(1)
+    );
+    match peak_rss_kib() {
+        Some(kib) => println!("peak rss: {} MiB", kib / 1024),
+        None => println!("peak rss: unavailable"),
+    }
+}
@@ -43,12 +43,13 @@ impl<F: RichField + Extendable<D>, const D: usize> Stark<F, D> for BitwiseStark<
 
     fn eval_packed_generic<FE, P, const D2: usize>(
         &self,
-        vars: StarkEvaluationVars<FE, P, { COL_NUM_BITWISE }>,
+        vars: StarkEvaluationVars<FE, P, { Self::COLUMNS /*HACK{ COL_NUM_BITWISE }*/ }>,
         yield_constr: &mut ConstraintConsumer<P>,
     ) where
         FE: FieldExtension<D2, BaseField = F>,
         P: PackedField<Scalar = FE>,
     {
+        let vars = StarkEvaluationVars::<FE, P, { COL_NUM_BITWISE }> { local_values: vars.local_values[..].try_into().unwrap(), next_values: vars.next_values[..].try_into().unwrap() }; //HACK-VARS
         let lv = vars.local_values;
         let op0 = lv[OP0];
         let op1 = lv[OP1];
@@ -187,9 +188,10 @@ impl<F: RichField + Extendable<D>, const D: usize> Stark<F, D> for BitwiseStark<
     fn eval_ext_circuit(
         &self,
         builder: &mut CircuitBuilder<F, D>,
-        vars: StarkEvaluationTargets<D, { COL_NUM_BITWISE }>,
+        vars: StarkEvaluationTargets<D, { Self::COLUMNS /*HACK{ COL_NUM_BITWISE }*/ }>,
         yield_constr: &mut RecursiveConstraintConsumer<F, D>,
     ) {
+        let vars = StarkEvaluationTargets::<D, { COL_NUM_BITWISE }> { local_values: vars.local_values[..].try_into().unwrap(), next_values: vars.next_values[..].try_into().unwrap() }; //HACK-VARS
         let lv = vars.local_values;
         let op0 = lv[OP0];
         let op1 = lv[OP1];
@@ -412,20 +414,23 @@ pub fn ctl_filter_with_bitwise_fixed<F: Field>() -> Column<F> {
 #[cfg(test)]
 mod tests {
     use crate::builtins::bitwise::bitwise_stark::BitwiseStark;
-    use crate::builtins::bitwise::columns::get_bitwise_col_name_map;
+    use crate::builtins::bitwise::columns::{get_bitwise_col_name_map, COL_NUM_BITWISE, FILTER};
     use crate::generation::builtin::generate_bitwise_trace;
     use crate::stark::constraint_consumer::ConstraintConsumer;
     use crate::stark::stark::Stark;
     use crate::stark::vars::StarkEvaluationVars;
+    use crate::test_utils::test_stark_with_random_padding;
     use assembler::encoder::encode_asm_from_json_file;
     use core::merkle_tree::tree::AccountTree;
     use core::program::Program;
+    use core::trace::trace::{BitwiseCombinedRow, Trace};
     use core::types::account::Address;
     use executor::Process;
     use plonky2::field::goldilocks_field::GoldilocksField;
     use plonky2::field::types::Field;
     use plonky2::plonk::config::{GenericConfig, PoseidonGoldilocksConfig};
     use plonky2_util::log2_strict;
+    use std::cell::Cell;
     use std::collections::HashMap;
     use std::path::PathBuf;
 
@@ -529,4 +534,41 @@ mod tests {
         let program_path = path.display().to_string();
         test_bitwise_stark(program_path);
     }
+
+    #[test]
+    fn test_bitwise_with_random_padding() {
+        let mut path = PathBuf::from(env!("CARGO_MANIFEST_DIR"));
+        path.push("../assembler/test_data/asm/bitwise.json");
+        let program_path = path.display().to_string();
+
+        const D: usize = 2;
+        type C = PoseidonGoldilocksConfig;
+        type F = <C as GenericConfig<D>>::F;
+        type S = BitwiseStark<F, D>;
+
+        let beta = Cell::new(F::ZERO);
+        let generate_trace = |rows: &Vec<BitwiseCombinedRow>| {
+            let (trace, bitwise_beta) = generate_bitwise_trace::<F>(rows);
+            beta.set(bitwise_beta);
+            trace
+        };
+        let eval_packed_generic =
+            |vars: StarkEvaluationVars<GoldilocksField, GoldilocksField, COL_NUM_BITWISE>,
+             constraint_consumer: &mut ConstraintConsumer<GoldilocksField>| {
+                let mut stark = S::default();
+                stark.set_compress_challenge(beta.get()).unwrap();
+                stark.eval_packed_generic(vars, constraint_consumer);
+            };
+        // The filter of the padding rows only feeds the cross table lookup.
+        test_stark_with_random_padding(
+            program_path,
+            |trace: Trace| trace.builtin_bitwise_combined,
+            generate_trace,
+            &[FILTER],
+            eval_packed_generic,
+            None::<fn(usize, StarkEvaluationVars<GoldilocksField, GoldilocksField, COL_NUM_BITWISE>)>,
+            None,
+            None,
+        );
+    }
 }
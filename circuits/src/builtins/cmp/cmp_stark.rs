@@ -20,12 +20,13 @@ impl<F: RichField + Extendable<D>, const D: usize> Stark<F, D> for CmpStark<F, D
     const COLUMNS: usize = COL_NUM_CMP;
     fn eval_packed_generic<FE, P, const D2: usize>(
         &self,
-        vars: StarkEvaluationVars<FE, P, { COL_NUM_CMP }>,
+        vars: StarkEvaluationVars<FE, P, { Self::COLUMNS /*HACK{ COL_NUM_CMP }*/ }>,
         yield_constr: &mut ConstraintConsumer<P>,
     ) where
         FE: FieldExtension<D2, BaseField = F>,
         P: PackedField<Scalar = FE>,
     {
+        let vars = StarkEvaluationVars::<FE, P, { COL_NUM_CMP }> { local_values: vars.local_values[..].try_into().unwrap(), next_values: vars.next_values[..].try_into().unwrap() }; //HACK-VARS
         let op0 = vars.local_values[COL_CMP_OP0];
         let op1 = vars.local_values[COL_CMP_OP1];
         let gte = vars.local_values[COL_CMP_GTE];
@@ -44,9 +45,10 @@ impl<F: RichField + Extendable<D>, const D: usize> Stark<F, D> for CmpStark<F, D
     fn eval_ext_circuit(
         &self,
         builder: &mut CircuitBuilder<F, D>,
-        vars: StarkEvaluationTargets<D, { COL_NUM_CMP }>,
+        vars: StarkEvaluationTargets<D, { Self::COLUMNS /*HACK{ COL_NUM_CMP }*/ }>,
         yield_constr: &mut RecursiveConstraintConsumer<F, D>,
     ) {
+        let vars = StarkEvaluationTargets::<D, { COL_NUM_CMP }> { local_values: vars.local_values[..].try_into().unwrap(), next_values: vars.next_values[..].try_into().unwrap() }; //HACK-VARS
         let one = builder.one_extension();
         let op0 = vars.local_values[COL_CMP_OP0];
         let op1 = vars.local_values[COL_CMP_OP1];
@@ -105,7 +107,7 @@ mod tests {
     use crate::stark::constraint_consumer::ConstraintConsumer;
     use crate::stark::stark::Stark;
     use crate::stark::vars::StarkEvaluationVars;
-    use crate::test_utils::test_stark_with_asm_path;
+    use crate::test_utils::{test_stark_with_asm_path, test_stark_with_random_padding};
     use core::trace::trace::{CmpRow, Trace};
     use plonky2::field::goldilocks_field::GoldilocksField;
     use plonky2::plonk::config::{GenericConfig, PoseidonGoldilocksConfig};
@@ -117,6 +119,37 @@ mod tests {
         test_cmp_with_asm_file_name(program_path.to_string());
     }
 
+    #[test]
+    fn test_cmp_with_random_padding() {
+        let mut path = PathBuf::from(env!("CARGO_MANIFEST_DIR"));
+        path.push("../assembler/test_data/asm/comparison.json");
+        let program_path = path.display().to_string();
+
+        const D: usize = 2;
+        type C = PoseidonGoldilocksConfig;
+        type F = <C as GenericConfig<D>>::F;
+        type S = CmpStark<F, D>;
+        let stark = S::default();
+
+        let eval_packed_generic =
+            |vars: StarkEvaluationVars<GoldilocksField, GoldilocksField, COL_NUM_CMP>,
+             constraint_consumer: &mut ConstraintConsumer<GoldilocksField>| {
+                stark.eval_packed_generic(vars, constraint_consumer);
+            };
+        // Padding rows compare equal operands with `gte` set, which leaves the
+        // inverse free.
+        test_stark_with_random_padding(
+            program_path,
+            |trace: Trace| trace.builtin_cmp,
+            |rows: &Vec<CmpRow>| generate_cmp_trace(rows),
+            &[COL_CMP_ABS_DIFF_INV],
+            eval_packed_generic,
+            None::<fn(usize, StarkEvaluationVars<GoldilocksField, GoldilocksField, COL_NUM_CMP>)>,
+            None,
+            None,
+        );
+    }
+
     fn test_cmp_with_asm_file_name(file_name: String) {
         let mut path = PathBuf::from(env!("CARGO_MANIFEST_DIR"));
         path.push("../assembler/test_data/asm/");
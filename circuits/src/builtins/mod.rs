@@ -6,3 +6,4 @@ pub mod rangecheck_small_limbs;
 pub mod sccall;
 pub mod storage;
 pub mod tape;
+pub mod transient;
@@ -187,12 +187,15 @@ mod test {
     use crate::stark::stark::Stark;
     use crate::{
         builtins::poseidon::{
-            columns::{get_poseidon_col_name_map, NUM_POSEIDON_COLS},
+            columns::{
+                get_poseidon_col_name_map, FILTER_LOOKED_NORMAL, FILTER_LOOKED_STORAGE_BRANCH,
+                FILTER_LOOKED_TREEKEY, NUM_POSEIDON_COLS,
+            },
             poseidon_stark::PoseidonStark,
         },
         generation::poseidon::generate_poseidon_trace,
         stark::{constraint_consumer::ConstraintConsumer, vars::StarkEvaluationVars},
-        test_utils::test_stark_with_asm_path,
+        test_utils::{test_stark_with_asm_path, test_stark_with_random_padding},
     };
     use plonky2::{
         field::goldilocks_field::GoldilocksField,
@@ -218,6 +221,47 @@ mod test {
         test_poseidon_with_asm_file_name("storage_u32.json".to_string(), Some(call_data));
     }
 
+    #[test]
+    fn test_poseidon_with_random_padding() {
+        let call_data = vec![
+            GoldilocksField::ZERO,
+            GoldilocksField::from_canonical_u64(1239976900),
+        ];
+        let mut path = PathBuf::from(env!("CARGO_MANIFEST_DIR"));
+        path.push("../assembler/test_data/asm/poseidon_hash.json");
+        let program_path = path.display().to_string();
+
+        const D: usize = 2;
+        type C = PoseidonGoldilocksConfig;
+        type F = <C as GenericConfig<D>>::F;
+        type S = PoseidonStark<F, D>;
+        let stark = S::default();
+
+        let eval_packed_generic =
+            |vars: StarkEvaluationVars<GoldilocksField, GoldilocksField, NUM_POSEIDON_COLS>,
+             constraint_consumer: &mut ConstraintConsumer<GoldilocksField>| {
+                stark.eval_packed_generic(vars, constraint_consumer);
+            };
+        // Padding rows hash the zero input, whose capacity is zero, so only
+        // the leaf filter is pinned, by its domain tag.
+        test_stark_with_random_padding(
+            program_path,
+            |trace: Trace| trace.builtin_poseidon,
+            |rows: &Vec<PoseidonRow>| generate_poseidon_trace(rows),
+            &[
+                FILTER_LOOKED_NORMAL,
+                FILTER_LOOKED_TREEKEY,
+                FILTER_LOOKED_STORAGE_BRANCH,
+            ],
+            eval_packed_generic,
+            None::<
+                fn(usize, StarkEvaluationVars<GoldilocksField, GoldilocksField, NUM_POSEIDON_COLS>),
+            >,
+            Some(call_data),
+            None,
+        );
+    }
+
     #[allow(unused)]
     fn test_poseidon_with_asm_file_name(
         file_name: String,
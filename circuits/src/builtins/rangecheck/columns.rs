@@ -1,5 +1,8 @@
 // 2022-12-19: written by xb
 
+use core::vm::memory::MEMORY_MODEL;
+use std::collections::BTreeMap;
+
 /* RC_Table construction as follows:
 +-----+---------+---------+------+
 | val | limb_lo | limb_hi | TAG
@@ -41,4 +44,30 @@ pub(crate) const FIX_RANGE_CHECK_U16_PERMUTED_HI: usize = FIX_RANGE_CHECK_U16_PE
 
 pub(crate) const COL_NUM_RC: usize = FIX_RANGE_CHECK_U16_PERMUTED_HI + 1; //11
 
-pub(crate) const RANGE_CHECK_U16_SIZE: usize = 1 << 16; //4
+pub(crate) fn get_rangecheck_col_name_map() -> BTreeMap<usize, String> {
+    let mut m: BTreeMap<usize, String> = BTreeMap::new();
+    m.insert(CPU_FILTER, "cpu_filter".to_string());
+    m.insert(MEMORY_SORT_FILTER, "memory_sort_filter".to_string());
+    m.insert(MEMORY_REGION_FILTER, "memory_region_filter".to_string());
+    m.insert(CMP_FILTER, "cmp_filter".to_string());
+    m.insert(VAL, "val".to_string());
+    m.insert(LIMB_LO, "limb_lo".to_string());
+    m.insert(LIMB_HI, "limb_hi".to_string());
+    m.insert(LIMB_LO_PERMUTED, "limb_lo_permuted".to_string());
+    m.insert(LIMB_HI_PERMUTED, "limb_hi_permuted".to_string());
+    m.insert(FIX_RANGE_CHECK_U16, "fix_range_check_u16".to_string());
+    m.insert(
+        FIX_RANGE_CHECK_U16_PERMUTED_LO,
+        "fix_range_check_u16_permuted_lo".to_string(),
+    );
+    m.insert(
+        FIX_RANGE_CHECK_U16_PERMUTED_HI,
+        "fix_range_check_u16_permuted_hi".to_string(),
+    );
+    m
+}
+
+pub(crate) const RANGE_CHECK_U16_SIZE: usize = MEMORY_MODEL.rc_limb_size();
+
+// the table has one lo and one hi limb column
+const _: () = assert!(MEMORY_MODEL.rc_limbs() == 2);
@@ -1,4 +1,5 @@
 use crate::builtins::rangecheck::columns::*;
+use core::vm::memory::MEMORY_MODEL;
 use itertools::Itertools;
 
 use crate::stark::constraint_consumer::{ConstraintConsumer, RecursiveConstraintConsumer};
@@ -20,7 +21,7 @@ pub struct RangeCheckStark<F, const D: usize> {
 }
 
 impl<F: RichField, const D: usize> RangeCheckStark<F, D> {
-    const BASE: usize = 1 << 16;
+    const BASE: usize = MEMORY_MODEL.rc_limb_size();
 }
 
 impl<F: RichField + Extendable<D>, const D: usize> Stark<F, D> for RangeCheckStark<F, D> {
@@ -31,12 +32,13 @@ impl<F: RichField + Extendable<D>, const D: usize> Stark<F, D> for RangeCheckSta
     // RC for limbs
     fn eval_packed_generic<FE, P, const D2: usize>(
         &self,
-        vars: StarkEvaluationVars<FE, P, { COL_NUM_RC }>,
+        vars: StarkEvaluationVars<FE, P, { Self::COLUMNS /*HACK{ COL_NUM_RC }*/ }>,
         yield_constr: &mut ConstraintConsumer<P>,
     ) where
         FE: FieldExtension<D2, BaseField = F>,
         P: PackedField<Scalar = FE>,
     {
+        let vars = StarkEvaluationVars::<FE, P, { COL_NUM_RC }> { local_values: vars.local_values[..].try_into().unwrap(), next_values: vars.next_values[..].try_into().unwrap() }; //HACK-VARS
         let val = vars.local_values[VAL];
         let limb_lo = vars.local_values[LIMB_LO];
         let limb_hi = vars.local_values[LIMB_HI];
@@ -64,9 +66,10 @@ impl<F: RichField + Extendable<D>, const D: usize> Stark<F, D> for RangeCheckSta
     fn eval_ext_circuit(
         &self,
         builder: &mut CircuitBuilder<F, D>,
-        vars: StarkEvaluationTargets<D, { COL_NUM_RC }>,
+        vars: StarkEvaluationTargets<D, { Self::COLUMNS /*HACK{ COL_NUM_RC }*/ }>,
         yield_constr: &mut RecursiveConstraintConsumer<F, D>,
     ) {
+        let vars = StarkEvaluationTargets::<D, { COL_NUM_RC }> { local_values: vars.local_values[..].try_into().unwrap(), next_values: vars.next_values[..].try_into().unwrap() }; //HACK-VARS
         let val = vars.local_values[VAL];
         let limb_lo = vars.local_values[LIMB_LO];
         let limb_hi = vars.local_values[LIMB_HI];
@@ -147,14 +150,17 @@ pub fn ctl_filter_with_rangecheck_fixed<F: Field>() -> Column<F> {
 }*/
 #[cfg(test)]
 mod tests {
+    use crate::builtins::rangecheck::columns::*;
     use crate::builtins::rangecheck::rangecheck_stark::RangeCheckStark;
     use crate::generation::builtin::generate_rc_trace;
     use crate::stark::constraint_consumer::ConstraintConsumer;
     use crate::stark::stark::Stark;
     use crate::stark::vars::StarkEvaluationVars;
+    use crate::test_utils::test_stark_with_random_padding;
     use assembler::encoder::encode_asm_from_json_file;
     use core::merkle_tree::tree::AccountTree;
     use core::program::Program;
+    use core::trace::trace::{RangeCheckRow, Trace};
     use core::types::account::Address;
     use executor::Process;
     use plonky2::field::goldilocks_field::GoldilocksField;
@@ -257,4 +263,39 @@ mod tests {
         let program_path = "malloc.json";
         test_rc_stark(program_path.to_string());
     }
+
+    #[test]
+    fn test_rangecheck_with_random_padding() {
+        let mut path = PathBuf::from(env!("CARGO_MANIFEST_DIR"));
+        path.push("../assembler/test_data/asm/range_check.json");
+        let program_path = path.display().to_string();
+
+        const D: usize = 2;
+        type C = PoseidonGoldilocksConfig;
+        type F = <C as GenericConfig<D>>::F;
+        type S = RangeCheckStark<F, D>;
+        let stark = S::default();
+
+        let eval_packed_generic =
+            |vars: StarkEvaluationVars<GoldilocksField, GoldilocksField, COL_NUM_RC>,
+             constraint_consumer: &mut ConstraintConsumer<GoldilocksField>| {
+                stark.eval_packed_generic(vars, constraint_consumer);
+            };
+        // The filters of the padding rows only feed the cross table lookups.
+        test_stark_with_random_padding(
+            program_path,
+            |trace: Trace| trace.builtin_rangecheck,
+            |rows: &Vec<RangeCheckRow>| generate_rc_trace(rows),
+            &[
+                CPU_FILTER,
+                MEMORY_SORT_FILTER,
+                MEMORY_REGION_FILTER,
+                CMP_FILTER,
+            ],
+            eval_packed_generic,
+            None::<fn(usize, StarkEvaluationVars<GoldilocksField, GoldilocksField, COL_NUM_RC>)>,
+            None,
+            None,
+        );
+    }
 }
@@ -39,12 +39,13 @@ impl<F: RichField + Extendable<D>, const D: usize> Stark<F, D> for RangeCheckU32
 
     fn eval_packed_generic<FE, P, const D2: usize>(
         &self,
-        vars: StarkEvaluationVars<FE, P, COLUMNS_RANGE_CHECK_U32>,
+        vars: StarkEvaluationVars<FE, P, { Self::COLUMNS /*HACKCOLUMNS_RANGE_CHECK_U32*/ }>,
         yield_constr: &mut ConstraintConsumer<P>,
     ) where
         FE: FieldExtension<D2, BaseField = F>,
         P: PackedField<Scalar = FE>,
     {
+        let vars = StarkEvaluationVars::<FE, P, COLUMNS_RANGE_CHECK_U32> { local_values: vars.local_values[..].try_into().unwrap(), next_values: vars.next_values[..].try_into().unwrap() }; //HACK-VARS
         let input = vars.local_values[Self::COL_INPUT];
         let limbs: Vec<_> = vars.local_values[self.range_limbs()].to_vec();
         let computed_sum = reduce_with_powers(&limbs, P::Scalar::from_canonical_usize(Self::BASE));
@@ -62,9 +63,10 @@ impl<F: RichField + Extendable<D>, const D: usize> Stark<F, D> for RangeCheckU32
     fn eval_ext_circuit(
         &self,
         builder: &mut CircuitBuilder<F, D>,
-        vars: StarkEvaluationTargets<D, COLUMNS_RANGE_CHECK_U32>,
+        vars: StarkEvaluationTargets<D, { Self::COLUMNS /*HACKCOLUMNS_RANGE_CHECK_U32*/ }>,
         yield_constr: &mut RecursiveConstraintConsumer<F, D>,
     ) {
+        let vars = StarkEvaluationTargets::<D, COLUMNS_RANGE_CHECK_U32> { local_values: vars.local_values[..].try_into().unwrap(), next_values: vars.next_values[..].try_into().unwrap() }; //HACK-VARS
         let base = builder.constant(F::from_canonical_usize(Self::BASE));
         let one = builder.one_extension();
         let input = vars.local_values[Self::COL_INPUT];
@@ -30,7 +30,9 @@ pub(crate) const COL_ST_FILTER_IS_HASH_BIT_0: usize = COL_ST_ACC_LAYER_MARKER +
 pub(crate) const COL_ST_FILTER_IS_HASH_BIT_1: usize = COL_ST_FILTER_IS_HASH_BIT_0 + 1;
 pub(crate) const COL_ST_FILTER_IS_FOR_PROG: usize = COL_ST_FILTER_IS_HASH_BIT_1 + 1;
 pub(crate) const COL_ST_IS_PADDING: usize = COL_ST_FILTER_IS_FOR_PROG + 1;
-pub(crate) const NUM_COL_ST: usize = COL_ST_IS_PADDING + 1;
+pub(crate) const COL_ST_IS_FIRST_ROW: usize = COL_ST_IS_PADDING + 1;
+pub(crate) const COL_ST_IS_LAST_ROW: usize = COL_ST_IS_FIRST_ROW + 1;
+pub(crate) const NUM_COL_ST: usize = COL_ST_IS_LAST_ROW + 1;
 
 pub(crate) fn get_storage_access_col_name_map() -> BTreeMap<usize, String> {
     let mut m: BTreeMap<usize, String> = BTreeMap::new();
@@ -91,5 +93,7 @@ pub(crate) fn get_storage_access_col_name_map() -> BTreeMap<usize, String> {
         String::from("FILTER_IS_FOR_PROG"),
     );
     m.insert(COL_ST_IS_PADDING, String::from("IS_PADDING"));
+    m.insert(COL_ST_IS_FIRST_ROW, String::from("IS_FIRST_ROW"));
+    m.insert(COL_ST_IS_LAST_ROW, String::from("IS_LAST_ROW"));
     m
 }
@@ -30,6 +30,22 @@ pub fn ctl_filter_for_prog_chunk<F: Field>() -> Column<F> {
     Column::single(COL_ST_FILTER_IS_FOR_PROG)
 }
 
+pub fn ctl_data_pre_root<F: Field>() -> Vec<Column<F>> {
+    Column::singles(COL_ST_PRE_ROOT_RANGE).collect_vec()
+}
+
+pub fn ctl_filter_first_row<F: Field>() -> Column<F> {
+    Column::single(COL_ST_IS_FIRST_ROW)
+}
+
+pub fn ctl_data_root<F: Field>() -> Vec<Column<F>> {
+    Column::singles(COL_ST_ROOT_RANGE).collect_vec()
+}
+
+pub fn ctl_filter_last_row<F: Field>() -> Column<F> {
+    Column::single(COL_ST_IS_LAST_ROW)
+}
+
 pub fn ctl_data_with_cpu<F: Field>() -> Vec<Column<F>> {
     let mut res = Column::singles([COL_ST_ACCESS_IDX, COL_ST_IS_WRITE]).collect_vec();
     res.extend(Column::singles(COL_ST_ADDR_RANGE.chain(COL_ST_PATH_RANGE)));
@@ -229,6 +245,13 @@ impl<F: RichField + Extendable<D>, const D: usize> Stark<F, D> for StorageAccess
                 * lv[COL_ST_HASH_TYPE],
         );
 
+        // is_first_row is 1 in the first row only, is_last_row in the last row
+        // only. They pick the rows looked up by the state roots.
+        yield_constr.constraint_first_row(lv[COL_ST_IS_FIRST_ROW] - P::ONES);
+        yield_constr.constraint_transition(nv[COL_ST_IS_FIRST_ROW]);
+        yield_constr.constraint_last_row(lv[COL_ST_IS_LAST_ROW] - P::ONES);
+        yield_constr.constraint_transition(lv[COL_ST_IS_LAST_ROW]);
+
         // pre_root and root constraints:
         // in padding line, root not change
         COL_ST_ROOT_RANGE.for_each(|col| {
@@ -337,13 +360,10 @@ impl<F: RichField + Extendable<D>, const D: usize> Stark<F, D> for StorageAccess
 #[cfg(test)]
 mod tests {
     use crate::{
-        builtins::storage::{
-            columns::{get_storage_access_col_name_map, NUM_COL_ST},
-            storage_access_stark::StorageAccessStark,
-        },
+        builtins::storage::{columns::*, storage_access_stark::StorageAccessStark},
         generation::storage::generate_storage_access_trace,
         stark::stark::Stark,
-        test_utils::simple_test_stark,
+        test_utils::{simple_test_stark, simple_test_stark_with_random_padding},
     };
     use core::{
         trace::trace::Trace,
@@ -365,6 +385,62 @@ mod tests {
         test_storage_with_asm_file_name(program_path.to_string(), Some(call_data));
     }
 
+    #[test]
+    fn test_storage_with_random_padding() {
+        let call_data = vec![
+            GoldilocksField::from_canonical_u64(0),
+            GoldilocksField::from_canonical_u64(2364819430),
+        ];
+        let mut path = PathBuf::from(env!("CARGO_MANIFEST_DIR"));
+        path.push("../assembler/test_data/asm/storage_u32.json");
+        let program_path = path.display().to_string();
+
+        const D: usize = 2;
+        type C = PoseidonGoldilocksConfig;
+        type F = <C as GenericConfig<D>>::F;
+        type S = StorageAccessStark<F, D>;
+        let stark = S::default();
+
+        let generate_trace = |trace: Trace| {
+            generate_storage_access_trace(
+                &trace.builtin_storage_hash,
+                &trace.builtin_program_hash,
+                trace.start_end_roots.0,
+            )
+        };
+        let eval_packed_generic =
+            |vars: StarkEvaluationVars<GoldilocksField, GoldilocksField, NUM_COL_ST>,
+             constraint_consumer: &mut ConstraintConsumer<GoldilocksField>| {
+                stark.eval_packed_generic(vars, constraint_consumer);
+            };
+        // Padding rows only carry the last root forward. Their layer flags
+        // and filters are zero, which frees the access columns.
+        let random_padding_cols: Vec<usize> = [
+            COL_ST_ACCESS_IDX,
+            COL_ST_IS_WRITE,
+            COL_ST_HASH_TYPE,
+            COL_ST_ACC_LAYER_MARKER,
+        ]
+        .into_iter()
+        .chain(COL_ST_PRE_ROOT_RANGE)
+        .chain(COL_ST_ADDR_RANGE)
+        .chain(COL_ST_PRE_PATH_RANGE)
+        .chain(COL_ST_PATH_RANGE)
+        .chain(COL_ST_SIB_RANGE)
+        .chain(COL_ST_PRE_HASH_RANGE)
+        .chain(COL_ST_HASH_RANGE)
+        .collect();
+        simple_test_stark_with_random_padding(
+            program_path,
+            generate_trace,
+            Some((COL_ST_IS_PADDING, random_padding_cols.as_slice())),
+            eval_packed_generic,
+            None::<fn(usize, StarkEvaluationVars<GoldilocksField, GoldilocksField, NUM_COL_ST>)>,
+            Some(call_data),
+            None,
+        );
+    }
+
     #[allow(unused)]
     fn test_storage_with_asm_file_name(file_name: String, call_data: Option<Vec<GoldilocksField>>) {
         let mut path = PathBuf::from(env!("CARGO_MANIFEST_DIR"));
@@ -379,7 +455,11 @@ mod tests {
         let stark = S::default();
 
         let generate_trace = |trace: Trace| {
-            generate_storage_access_trace(&trace.builtin_storage_hash, &trace.builtin_program_hash)
+            generate_storage_access_trace(
+                &trace.builtin_storage_hash,
+                &trace.builtin_program_hash,
+                trace.start_end_roots.0,
+            )
         };
         let eval_packed_generic =
             |vars: StarkEvaluationVars<GoldilocksField, GoldilocksField, NUM_COL_ST>,
@@ -0,0 +1,51 @@
+use std::collections::BTreeMap;
+use std::ops::Range;
+
+pub(crate) const COL_TRANSIENT_ENV_IDX: usize = 0;
+pub(crate) const COL_TRANSIENT_CLK: usize = COL_TRANSIENT_ENV_IDX + 1;
+pub(crate) const COL_TRANSIENT_OPCODE: usize = COL_TRANSIENT_CLK + 1;
+pub(crate) const COL_TRANSIENT_ADDR_RANGE: Range<usize> =
+    COL_TRANSIENT_OPCODE + 1..COL_TRANSIENT_OPCODE + 1 + 4;
+pub(crate) const COL_TRANSIENT_KEY: usize = COL_TRANSIENT_ADDR_RANGE.end;
+pub(crate) const COL_TRANSIENT_VALUE: usize = COL_TRANSIENT_KEY + 1;
+pub(crate) const COL_TRANSIENT_IS_WRITE: usize = COL_TRANSIENT_VALUE + 1;
+// first access of a slot, its read value is zero
+pub(crate) const COL_TRANSIENT_IS_FIRST: usize = COL_TRANSIENT_IS_WRITE + 1;
+// inverse of the slot limb that differs from the previous row on a first
+// access, zero for the other limbs
+pub(crate) const COL_TRANSIENT_DIFF_INV_RANGE: Range<usize> =
+    COL_TRANSIENT_IS_FIRST + 1..COL_TRANSIENT_IS_FIRST + 1 + 5;
+pub(crate) const COL_TRANSIENT_FILTER_LOOKED: usize = COL_TRANSIENT_DIFF_INV_RANGE.end;
+// cpu tx step of the access, it increases within a slot: step_diff is the gap
+// to the previous access minus one, range checked on the looked rows that are
+// not first
+pub(crate) const COL_TRANSIENT_STEP: usize = COL_TRANSIENT_FILTER_LOOKED + 1;
+pub(crate) const COL_TRANSIENT_STEP_DIFF: usize = COL_TRANSIENT_STEP + 1;
+pub(crate) const COL_TRANSIENT_FILTER_STEP_DIFF: usize = COL_TRANSIENT_STEP_DIFF + 1;
+pub(crate) const NUM_COL_TRANSIENT: usize = COL_TRANSIENT_FILTER_STEP_DIFF + 1;
+
+#[allow(unused)]
+pub(crate) fn get_transient_col_name_map() -> BTreeMap<usize, String> {
+    let mut m: BTreeMap<usize, String> = BTreeMap::new();
+    m.insert(COL_TRANSIENT_ENV_IDX, "env_idx".to_string());
+    m.insert(COL_TRANSIENT_CLK, "clk".to_string());
+    m.insert(COL_TRANSIENT_OPCODE, "opcode".to_string());
+    for (index, col) in COL_TRANSIENT_ADDR_RANGE.enumerate() {
+        m.insert(col, format!("addr_{}", index));
+    }
+    m.insert(COL_TRANSIENT_KEY, "key".to_string());
+    m.insert(COL_TRANSIENT_VALUE, "value".to_string());
+    m.insert(COL_TRANSIENT_IS_WRITE, "is_write".to_string());
+    m.insert(COL_TRANSIENT_IS_FIRST, "is_first".to_string());
+    for (index, col) in COL_TRANSIENT_DIFF_INV_RANGE.enumerate() {
+        m.insert(col, format!("diff_inv_{}", index));
+    }
+    m.insert(COL_TRANSIENT_FILTER_LOOKED, "filter".to_string());
+    m.insert(COL_TRANSIENT_STEP, "step".to_string());
+    m.insert(COL_TRANSIENT_STEP_DIFF, "step_diff".to_string());
+    m.insert(
+        COL_TRANSIENT_FILTER_STEP_DIFF,
+        "filter_step_diff".to_string(),
+    );
+    m
+}
@@ -0,0 +1,304 @@
+use core::{types::Field, vm::opcodes::OlaOpcode};
+use std::marker::PhantomData;
+
+use itertools::Itertools;
+use plonky2::{
+    field::{
+        extension::{Extendable, FieldExtension},
+        packed::PackedField,
+    },
+    hash::hash_types::RichField,
+    plonk::circuit_builder::CircuitBuilder,
+};
+
+use crate::stark::{
+    constraint_consumer::{ConstraintConsumer, RecursiveConstraintConsumer},
+    cross_table_lookup::Column,
+    stark::Stark,
+    vars::{StarkEvaluationTargets, StarkEvaluationVars},
+};
+
+use super::columns::{
+    COL_TRANSIENT_ADDR_RANGE, COL_TRANSIENT_CLK, COL_TRANSIENT_DIFF_INV_RANGE,
+    COL_TRANSIENT_ENV_IDX, COL_TRANSIENT_FILTER_LOOKED, COL_TRANSIENT_FILTER_STEP_DIFF,
+    COL_TRANSIENT_IS_FIRST, COL_TRANSIENT_IS_WRITE, COL_TRANSIENT_KEY, COL_TRANSIENT_OPCODE,
+    COL_TRANSIENT_STEP, COL_TRANSIENT_STEP_DIFF, COL_TRANSIENT_VALUE, NUM_COL_TRANSIENT,
+};
+
+pub fn ctl_data_transient<F: Field>() -> Vec<Column<F>> {
+    Column::singles(
+        [
+            COL_TRANSIENT_STEP,
+            COL_TRANSIENT_ENV_IDX,
+            COL_TRANSIENT_CLK,
+            COL_TRANSIENT_OPCODE,
+        ]
+        .into_iter()
+        .chain(COL_TRANSIENT_ADDR_RANGE)
+        .chain([COL_TRANSIENT_KEY, COL_TRANSIENT_VALUE]),
+    )
+    .collect_vec()
+}
+
+pub fn ctl_filter_transient<F: Field>() -> Column<F> {
+    Column::single(COL_TRANSIENT_FILTER_LOOKED)
+}
+
+pub fn ctl_data_transient_step_rc<F: Field>() -> Vec<Column<F>> {
+    vec![Column::single(COL_TRANSIENT_STEP_DIFF)]
+}
+
+pub fn ctl_filter_transient_step_rc<F: Field>() -> Column<F> {
+    Column::single(COL_TRANSIENT_FILTER_STEP_DIFF)
+}
+
+/// Accesses of `tload_t`/`tstore_t` in one tx, grouped by slot (storage
+/// address and key). A read returns the value of the previous access of its
+/// slot and the first access of a slot reads zero, so slots start empty in
+/// every tx.
+///
+/// Every looked row is an access of the cpu, see `ctl_cpu_transient`, and
+/// carries the tx step of its cpu row. The steps of a slot strictly increase,
+/// their gaps are range checked like the sorted addresses of the memory
+/// table, so the accesses of a slot can't be reordered. Looked rows come
+/// before the padding rows.
+#[derive(Copy, Clone, Default)]
+pub struct TransientStark<F, const D: usize> {
+    pub _phantom: PhantomData<F>,
+}
+impl<F: RichField + Extendable<D>, const D: usize> Stark<F, D> for TransientStark<F, D> {
+    const COLUMNS: usize = NUM_COL_TRANSIENT;
+    fn eval_packed_generic<FE, P, const D2: usize>(
+        &self,
+        vars: StarkEvaluationVars<FE, P, { Self::COLUMNS }>,
+        yield_constr: &mut ConstraintConsumer<P>,
+    ) where
+        FE: FieldExtension<D2, BaseField = F>,
+        P: PackedField<Scalar = FE>,
+    {
+        let lv = vars.local_values;
+        let nv = vars.next_values;
+        let op_tloadt = P::Scalar::from_canonical_u64(OlaOpcode::TLOADT.binary_bit_mask());
+        let op_tstoret = P::Scalar::from_canonical_u64(OlaOpcode::TSTORET.binary_bit_mask());
+
+        yield_constr
+            .constraint(lv[COL_TRANSIENT_IS_WRITE] * (P::ONES - lv[COL_TRANSIENT_IS_WRITE]));
+        yield_constr
+            .constraint(lv[COL_TRANSIENT_IS_FIRST] * (P::ONES - lv[COL_TRANSIENT_IS_FIRST]));
+        yield_constr.constraint(
+            lv[COL_TRANSIENT_FILTER_LOOKED] * (P::ONES - lv[COL_TRANSIENT_FILTER_LOOKED]),
+        );
+        // looked rows are tstore_t or tload_t by is_write, padding rows have no opcode
+        yield_constr.constraint(
+            lv[COL_TRANSIENT_FILTER_LOOKED]
+                * (lv[COL_TRANSIENT_OPCODE]
+                    - lv[COL_TRANSIENT_IS_WRITE] * op_tstoret
+                    - (P::ONES - lv[COL_TRANSIENT_IS_WRITE]) * op_tloadt),
+        );
+        yield_constr
+            .constraint((P::ONES - lv[COL_TRANSIENT_FILTER_LOOKED]) * lv[COL_TRANSIENT_OPCODE]);
+        // writes must be looked
+        yield_constr
+            .constraint(lv[COL_TRANSIENT_IS_WRITE] * (P::ONES - lv[COL_TRANSIENT_FILTER_LOOKED]));
+
+        // the slot only changes on a first access, and a first access must change it
+        let slot_cols = COL_TRANSIENT_ADDR_RANGE.chain([COL_TRANSIENT_KEY]);
+        let mut diff_inv_sum = P::ZEROS;
+        for (col, inv_col) in slot_cols.zip(COL_TRANSIENT_DIFF_INV_RANGE) {
+            yield_constr.constraint_transition(
+                (P::ONES - nv[COL_TRANSIENT_IS_FIRST]) * (nv[col] - lv[col]),
+            );
+            diff_inv_sum += (nv[col] - lv[col]) * nv[inv_col];
+        }
+        yield_constr.constraint_first_row(P::ONES - lv[COL_TRANSIENT_IS_FIRST]);
+        yield_constr.constraint_transition(nv[COL_TRANSIENT_IS_FIRST] * (P::ONES - diff_inv_sum));
+
+        // a read keeps the value of its slot, the first read of a slot is zero
+        yield_constr.constraint_transition(
+            (P::ONES - nv[COL_TRANSIENT_IS_FIRST])
+                * (P::ONES - nv[COL_TRANSIENT_IS_WRITE])
+                * (nv[COL_TRANSIENT_VALUE] - lv[COL_TRANSIENT_VALUE]),
+        );
+        yield_constr.constraint(
+            lv[COL_TRANSIENT_IS_FIRST]
+                * (P::ONES - lv[COL_TRANSIENT_IS_WRITE])
+                * lv[COL_TRANSIENT_VALUE],
+        );
+
+        // accesses of a slot are in the order of the cpu, padding only follows
+        yield_constr.constraint(
+            lv[COL_TRANSIENT_FILTER_STEP_DIFF]
+                - lv[COL_TRANSIENT_FILTER_LOOKED] * (P::ONES - lv[COL_TRANSIENT_IS_FIRST]),
+        );
+        yield_constr.constraint_transition(
+            nv[COL_TRANSIENT_FILTER_STEP_DIFF]
+                * (nv[COL_TRANSIENT_STEP]
+                    - lv[COL_TRANSIENT_STEP]
+                    - P::ONES
+                    - nv[COL_TRANSIENT_STEP_DIFF]),
+        );
+        yield_constr.constraint_transition(
+            (P::ONES - lv[COL_TRANSIENT_FILTER_LOOKED]) * nv[COL_TRANSIENT_FILTER_LOOKED],
+        );
+    }
+
+    fn eval_ext_circuit(
+        &self,
+        builder: &mut CircuitBuilder<F, D>,
+        vars: StarkEvaluationTargets<D, { Self::COLUMNS }>,
+        yield_constr: &mut RecursiveConstraintConsumer<F, D>,
+    ) {
+        let lv = vars.local_values;
+        let nv = vars.next_values;
+        let one = builder.one_extension();
+        let op_tloadt = builder.constant_extension(F::Extension::from_canonical_u64(
+            OlaOpcode::TLOADT.binary_bit_mask(),
+        ));
+        let op_tstoret = builder.constant_extension(F::Extension::from_canonical_u64(
+            OlaOpcode::TSTORET.binary_bit_mask(),
+        ));
+
+        for col in [
+            COL_TRANSIENT_IS_WRITE,
+            COL_TRANSIENT_IS_FIRST,
+            COL_TRANSIENT_FILTER_LOOKED,
+        ] {
+            let not = builder.sub_extension(one, lv[col]);
+            let cs = builder.mul_extension(lv[col], not);
+            yield_constr.constraint(builder, cs);
+        }
+        // looked rows are tstore_t or tload_t by is_write, padding rows have no opcode
+        let is_write = lv[COL_TRANSIENT_IS_WRITE];
+        let not_write = builder.sub_extension(one, is_write);
+        let filter = lv[COL_TRANSIENT_FILTER_LOOKED];
+        let not_filter = builder.sub_extension(one, filter);
+        let store = builder.mul_extension(is_write, op_tstoret);
+        let opcode = builder.mul_add_extension(not_write, op_tloadt, store);
+        let diff = builder.sub_extension(lv[COL_TRANSIENT_OPCODE], opcode);
+        let cs = builder.mul_extension(filter, diff);
+        yield_constr.constraint(builder, cs);
+        let cs = builder.mul_extension(not_filter, lv[COL_TRANSIENT_OPCODE]);
+        yield_constr.constraint(builder, cs);
+        // writes must be looked
+        let cs = builder.mul_extension(is_write, not_filter);
+        yield_constr.constraint(builder, cs);
+
+        // the slot only changes on a first access, and a first access must change it
+        let nv_not_first = builder.sub_extension(one, nv[COL_TRANSIENT_IS_FIRST]);
+        let slot_cols = COL_TRANSIENT_ADDR_RANGE.chain([COL_TRANSIENT_KEY]);
+        let mut diff_inv_sum = builder.zero_extension();
+        for (col, inv_col) in slot_cols.zip(COL_TRANSIENT_DIFF_INV_RANGE) {
+            let diff = builder.sub_extension(nv[col], lv[col]);
+            let cs = builder.mul_extension(nv_not_first, diff);
+            yield_constr.constraint_transition(builder, cs);
+            diff_inv_sum = builder.mul_add_extension(diff, nv[inv_col], diff_inv_sum);
+        }
+        let cs = builder.sub_extension(one, lv[COL_TRANSIENT_IS_FIRST]);
+        yield_constr.constraint_first_row(builder, cs);
+        let not_inv = builder.sub_extension(one, diff_inv_sum);
+        let cs = builder.mul_extension(nv[COL_TRANSIENT_IS_FIRST], not_inv);
+        yield_constr.constraint_transition(builder, cs);
+
+        // a read keeps the value of its slot, the first read of a slot is zero
+        let nv_not_write = builder.sub_extension(one, nv[COL_TRANSIENT_IS_WRITE]);
+        let diff = builder.sub_extension(nv[COL_TRANSIENT_VALUE], lv[COL_TRANSIENT_VALUE]);
+        let cs = builder.mul_many_extension([nv_not_first, nv_not_write, diff]);
+        yield_constr.constraint_transition(builder, cs);
+        let cs = builder.mul_many_extension([
+            lv[COL_TRANSIENT_IS_FIRST],
+            not_write,
+            lv[COL_TRANSIENT_VALUE],
+        ]);
+        yield_constr.constraint(builder, cs);
+
+        // accesses of a slot are in the order of the cpu, padding only follows
+        let not_first = builder.sub_extension(one, lv[COL_TRANSIENT_IS_FIRST]);
+        let filter_step_diff = builder.mul_extension(filter, not_first);
+        let cs = builder.sub_extension(lv[COL_TRANSIENT_FILTER_STEP_DIFF], filter_step_diff);
+        yield_constr.constraint(builder, cs);
+        let gap = builder.sub_extension(nv[COL_TRANSIENT_STEP], lv[COL_TRANSIENT_STEP]);
+        let gap = builder.sub_extension(gap, one);
+        let diff = builder.sub_extension(gap, nv[COL_TRANSIENT_STEP_DIFF]);
+        let cs = builder.mul_extension(nv[COL_TRANSIENT_FILTER_STEP_DIFF], diff);
+        yield_constr.constraint_transition(builder, cs);
+        let cs = builder.mul_extension(not_filter, nv[COL_TRANSIENT_FILTER_LOOKED]);
+        yield_constr.constraint_transition(builder, cs);
+    }
+
+    fn constraint_degree(&self) -> usize {
+        3
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use core::trace::trace::TransientRow;
+
+    use plonky2::field::types::Field;
+    use plonky2::plonk::config::{GenericConfig, PoseidonGoldilocksConfig};
+
+    use crate::builtins::transient::columns::{
+        get_transient_col_name_map, COL_TRANSIENT_IS_FIRST, COL_TRANSIENT_STEP,
+        COL_TRANSIENT_STEP_DIFF, COL_TRANSIENT_VALUE,
+    };
+    use crate::builtins::transient::transient_stark::TransientStark;
+    use crate::generation::transient::generate_transient_trace;
+    use crate::stark::ola_stark::Table;
+    use crate::stark::trace_check::check_trace_constraints;
+    use crate::stark::util::trace_to_poly_values;
+
+    const D: usize = 2;
+    type C = PoseidonGoldilocksConfig;
+    type F = <C as GenericConfig<D>>::F;
+
+    fn row(key: u64, value: u64, is_write: bool, clk: u32) -> TransientRow {
+        TransientRow {
+            env_idx: F::ZERO,
+            clk,
+            addr_storage: [F::ONE; 4],
+            key: F::from_canonical_u64(key),
+            value: F::from_canonical_u64(value),
+            is_write,
+            step: clk as u64 * 2,
+        }
+    }
+
+    #[test]
+    fn test_transient_stark() {
+        let stark = TransientStark::<F, D>::default();
+        let names = get_transient_col_name_map();
+        // a lock on key 7 is taken and released, key 8 is only read
+        let rows = [
+            row(7, 0, false, 0),
+            row(7, 1, true, 1),
+            row(7, 1, false, 2),
+            row(7, 0, true, 3),
+            row(8, 0, false, 4),
+        ];
+        let trace = trace_to_poly_values(generate_transient_trace::<F>(&rows));
+        check_trace_constraints(Table::Transient, &stark, &trace, &names).unwrap();
+
+        let empty = trace_to_poly_values(generate_transient_trace::<F>(&[]));
+        check_trace_constraints(Table::Transient, &stark, &empty, &names).unwrap();
+
+        // a read must see the last write of its slot
+        let mut bad = trace.clone();
+        bad[COL_TRANSIENT_VALUE].values[2] = F::TWO;
+        assert!(check_trace_constraints(Table::Transient, &stark, &bad, &names).is_err());
+        // a first read must be zero
+        let mut bad = trace.clone();
+        bad[COL_TRANSIENT_VALUE].values[4] = F::ONE;
+        assert!(check_trace_constraints(Table::Transient, &stark, &bad, &names).is_err());
+        // the accesses of a slot can't be swapped, the gap of their steps is
+        // what the range check sees
+        assert_eq!(trace[COL_TRANSIENT_STEP_DIFF].values[3], F::ONE);
+        let mut bad = trace.clone();
+        bad[COL_TRANSIENT_STEP].values.swap(2, 3);
+        assert!(check_trace_constraints(Table::Transient, &stark, &bad, &names).is_err());
+        // a slot can't be reset by marking a later access as first
+        let mut bad = trace;
+        bad[COL_TRANSIENT_IS_FIRST].values[2] = F::ONE;
+        bad[COL_TRANSIENT_VALUE].values[2] = F::ZERO;
+        assert!(check_trace_constraints(Table::Transient, &stark, &bad, &names).is_err());
+    }
+}
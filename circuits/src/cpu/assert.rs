@@ -12,7 +12,11 @@ pub(crate) fn eval_packed_generic<P: PackedField>(
     _nv: &[P; NUM_CPU_COLS],
     yield_constr: &mut ConstraintConsumer<P>,
 ) {
-    yield_constr.constraint(lv[COL_S_ASSERT] * (P::ONES - lv[COL_OP1]));
+    // `assert op1` checks op1, `assert op0 message_id` checks op0 and keeps the
+    // message id in op1.
+    let has_message: P = lv[COL_S_OP0].iter().copied().sum();
+    yield_constr.constraint(lv[COL_S_ASSERT] * (P::ONES - has_message) * (P::ONES - lv[COL_OP1]));
+    yield_constr.constraint(lv[COL_S_ASSERT] * has_message * (P::ONES - lv[COL_OP0]));
 }
 
 pub(crate) fn eval_ext_circuit<F: RichField + Extendable<D>, const D: usize>(
@@ -21,7 +25,15 @@ pub(crate) fn eval_ext_circuit<F: RichField + Extendable<D>, const D: usize>(
     _nv: &[ExtensionTarget<D>; NUM_CPU_COLS],
     yield_constr: &mut RecursiveConstraintConsumer<F, D>,
 ) {
-    let ret = builder.sub_extension(lv[COL_OP0], lv[COL_OP1]);
-    let cs = builder.mul_extension(lv[COL_S_ASSERT], ret);
+    let one = builder.one_extension();
+    let has_message = builder.add_many_extension(lv[COL_S_OP0].iter());
+    let no_message = builder.sub_extension(one, has_message);
+
+    let op1_not_one = builder.sub_extension(one, lv[COL_OP1]);
+    let cs = builder.mul_many_extension([lv[COL_S_ASSERT], no_message, op1_not_one]);
+    yield_constr.constraint(builder, cs);
+
+    let op0_not_one = builder.sub_extension(one, lv[COL_OP0]);
+    let cs = builder.mul_many_extension([lv[COL_S_ASSERT], has_message, op0_not_one]);
     yield_constr.constraint(builder, cs);
 }
@@ -51,6 +51,22 @@ pub(crate) fn eval_packed_generic<F, FE, P, const D: usize, const D2: usize>(
             * (P::ONES - wrapper.lv[COL_IS_EXT_LINE])
             * (wrapper.nv[COL_OP1] - wrapper.lv[COL_OP1]),
     );
+    // ext line keeps the caller ctx of the main line, it is carried into the
+    // callee's first line below
+    for ctx_reg_idx in 0..CTX_REGISTER_NUM {
+        yield_constr.constraint(
+            wrapper.lv[COL_S_CALL_SC]
+                * (P::ONES - wrapper.lv[COL_IS_EXT_LINE])
+                * (wrapper.nv[COL_ADDR_STORAGE_RANGE.start + ctx_reg_idx]
+                    - wrapper.lv[COL_ADDR_STORAGE_RANGE.start + ctx_reg_idx]),
+        );
+        yield_constr.constraint(
+            wrapper.lv[COL_S_CALL_SC]
+                * (P::ONES - wrapper.lv[COL_IS_EXT_LINE])
+                * (wrapper.nv[COL_ADDR_CODE_RANGE.start + ctx_reg_idx]
+                    - wrapper.lv[COL_ADDR_CODE_RANGE.start + ctx_reg_idx]),
+        );
+    }
 
     // in ext line of end, aux0 is env_idx, aux1 is clk
     yield_constr.constraint_transition(
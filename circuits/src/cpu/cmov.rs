@@ -0,0 +1,54 @@
+#[allow(unused)]
+use super::columns::*;
+use crate::stark::constraint_consumer::{ConstraintConsumer, RecursiveConstraintConsumer};
+use core::program::REGISTER_NUM;
+use plonky2::{
+    field::{extension::Extendable, packed::PackedField},
+    hash::hash_types::RichField,
+    iop::ext_target::ExtensionTarget,
+    plonk::circuit_builder::CircuitBuilder,
+};
+
+// `cmov dst cond op1`: op0 is the condition, aux0 the value dst held before.
+pub(crate) fn eval_packed_generic<P: PackedField>(
+    lv: &[P; NUM_CPU_COLS],
+    _nv: &[P; NUM_CPU_COLS],
+    yield_constr: &mut ConstraintConsumer<P>,
+) {
+    let old_dst = (0..REGISTER_NUM).fold(P::ZEROS, |acc, i| {
+        acc + lv[COL_S_DST.start + i] * lv[COL_REGS.start + i]
+    });
+    yield_constr.constraint(lv[COL_S_CMOV] * (lv[COL_AUX0] - old_dst));
+    yield_constr.constraint(lv[COL_S_CMOV] * lv[COL_OP0] * (P::ONES - lv[COL_OP0]));
+    yield_constr.constraint(
+        lv[COL_S_CMOV]
+            * (lv[COL_DST] - (lv[COL_OP0] * lv[COL_OP1] + (P::ONES - lv[COL_OP0]) * lv[COL_AUX0])),
+    );
+}
+
+pub(crate) fn eval_ext_circuit<F: RichField + Extendable<D>, const D: usize>(
+    builder: &mut CircuitBuilder<F, D>,
+    lv: &[ExtensionTarget<D>; NUM_CPU_COLS],
+    _nv: &[ExtensionTarget<D>; NUM_CPU_COLS],
+    yield_constr: &mut RecursiveConstraintConsumer<F, D>,
+) {
+    let one = builder.one_extension();
+    let mut old_dst = builder.zero_extension();
+    for i in 0..REGISTER_NUM {
+        old_dst =
+            builder.mul_add_extension(lv[COL_S_DST.start + i], lv[COL_REGS.start + i], old_dst);
+    }
+    let diff = builder.sub_extension(lv[COL_AUX0], old_dst);
+    let cs = builder.mul_extension(lv[COL_S_CMOV], diff);
+    yield_constr.constraint(builder, cs);
+
+    let not_cond = builder.sub_extension(one, lv[COL_OP0]);
+    let cs = builder.mul_many_extension([lv[COL_S_CMOV], lv[COL_OP0], not_cond]);
+    yield_constr.constraint(builder, cs);
+
+    let keep = builder.mul_extension(not_cond, lv[COL_AUX0]);
+    let selected = builder.mul_add_extension(lv[COL_OP0], lv[COL_OP1], keep);
+    let diff = builder.sub_extension(lv[COL_DST], selected);
+    let cs = builder.mul_extension(lv[COL_S_CMOV], diff);
+    yield_constr.constraint(builder, cs);
+}
@@ -1,4 +1,8 @@
-use core::program::{CTX_REGISTER_NUM, REGISTER_NUM};
+use core::{
+    program::{CTX_REGISTER_NUM, MAX_RETURN_VALUES, REGISTER_NUM},
+    types::merkle_tree::TREE_VALUE_LEN,
+};
+use executor::{SLOT_DOMAIN_SEL, SLOT_LISTED_SEL};
 use std::{collections::BTreeMap, ops::Range};
 
 // The Olavm trace for AIR:
@@ -117,20 +121,65 @@ pub(crate) const COL_S_SSTORE: usize = COL_S_SLOAD + 1;
 pub(crate) const COL_S_TLOAD: usize = COL_S_SSTORE + 1;
 pub(crate) const COL_S_TSTORE: usize = COL_S_TLOAD + 1;
 pub(crate) const COL_S_CALL_SC: usize = COL_S_TSTORE + 1;
-pub(crate) const NUM_OP_SELECTOR: usize = COL_S_CALL_SC - COL_S_SIMPLE_ARITHMATIC_OP + 1;
+// dst = op0 * op1 + (1 - op0) * aux0, aux0 holds the old dst
+pub(crate) const COL_S_CMOV: usize = COL_S_CALL_SC + 1;
+// transient storage, looked up in the transient table
+pub(crate) const COL_S_TLOADT: usize = COL_S_CMOV + 1;
+pub(crate) const COL_S_TSTORET: usize = COL_S_TLOADT + 1;
+// storage scan, a head line, the list record the walk starts from, then a
+// storage ext line and a link record line per slot read
+pub(crate) const COL_S_SSCAN: usize = COL_S_TSTORET + 1;
+// tx context read, checked against the context in the public values
+pub(crate) const COL_S_TXCTX: usize = COL_S_SSCAN + 1;
+pub(crate) const NUM_OP_SELECTOR: usize = COL_S_TXCTX - COL_S_SIMPLE_ARITHMATIC_OP + 1;
 
-pub(crate) const COL_IS_ENTRY_SC: usize = COL_S_CALL_SC + 1;
+pub(crate) const COL_IS_ENTRY_SC: usize = COL_S_TXCTX + 1;
 pub(crate) const COL_IS_NEXT_LINE_DIFF_INST: usize = COL_IS_ENTRY_SC + 1;
 pub(crate) const COL_IS_NEXT_LINE_SAME_TX: usize = COL_IS_NEXT_LINE_DIFF_INST + 1;
 
 pub(crate) const COL_FILTER_TAPE_LOOKING: usize = COL_IS_NEXT_LINE_SAME_TX + 1;
 pub(crate) const IS_SCCALL_EXT_LINE: usize = COL_FILTER_TAPE_LOOKING + 1;
 pub(crate) const COL_IS_STORAGE_EXT_LINE: usize = IS_SCCALL_EXT_LINE + 1;
-pub(crate) const COL_FILTER_SCCALL_END: usize = COL_IS_STORAGE_EXT_LINE + 1;
+pub(crate) const COL_IS_SSCAN_HEAD_LINE: usize = COL_IS_STORAGE_EXT_LINE + 1;
+// Ext lines of sstore and sscan accessing a record of the slot lists, the key
+// in op0_sel[4..8], the record in op1_sel[4..8], the tree key in
+// dst_sel[0..4] and the key domain in dst_sel[9].
+pub(crate) const COL_IS_SLOT_LIST_LINE: usize = COL_IS_SSCAN_HEAD_LINE + 1;
+// Storage and slot list ext lines which write their tree key.
+pub(crate) const COL_IS_STORAGE_WRITE: usize = COL_IS_SLOT_LIST_LINE + 1;
+pub(crate) const COL_FILTER_SCCALL_END: usize = COL_IS_STORAGE_WRITE + 1;
+// Whether the slot an sstore writes was listed, set on its slot list lines.
+pub(crate) const COL_SLOT_LISTED: usize = COL_S_DST_START + SLOT_LISTED_SEL;
+pub(crate) const COL_SLOT_DOMAIN: usize = COL_S_DST_START + SLOT_DOMAIN_SEL;
 pub(crate) const COL_FILTER_LOOKING_PROG_IMM: usize = COL_FILTER_SCCALL_END + 1;
 pub(crate) const COL_IS_PADDING: usize = COL_FILTER_LOOKING_PROG_IMM + 1;
 
-pub(crate) const NUM_CPU_COLS: usize = COL_IS_PADDING + 1;
+// The tx context `txctx` reads, chain id then tx hash, is the same on every
+// row and looked up by the public values on the first one.
+pub(crate) const COL_IS_FIRST_ROW: usize = COL_IS_PADDING + 1;
+pub(crate) const COL_TX_CTX: Range<usize> =
+    COL_IS_FIRST_ROW + 1..COL_IS_FIRST_ROW + 2 + TREE_VALUE_LEN;
+
+// Registers the entry END row returns, r0 to r(arity - 1). Looked up by the
+// returned values of the public values.
+pub(crate) const COL_FILTER_RETURNED: Range<usize> =
+    COL_TX_CTX.end..COL_TX_CTX.end + MAX_RETURN_VALUES;
+
+// Index of the row in the trace. Accesses of a transient slot are ordered by
+// the step of the row that made them.
+pub(crate) const COL_TX_STEP: usize = COL_FILTER_RETURNED.end;
+
+// First padding row, which holds the pc and registers the execution continues
+// from, looked up by the end state of the public values. All zero once the
+// entry END row ended the execution, set when a chunk of it stopped.
+pub(crate) const COL_IS_END_STATE: usize = COL_TX_STEP + 1;
+
+// Gas used before the row, every main line adds the cost of its opcode in the
+// default gas schedule. The end state row holds the gas of the whole table,
+// looked up by the gas used of the public values.
+pub(crate) const COL_GAS: usize = COL_IS_END_STATE + 1;
+
+pub(crate) const NUM_CPU_COLS: usize = COL_GAS + 1;
 
 #[allow(unused)]
 pub(crate) fn get_cpu_col_name_map() -> BTreeMap<usize, String> {
@@ -196,6 +245,11 @@ pub(crate) fn get_cpu_col_name_map() -> BTreeMap<usize, String> {
     m.insert(COL_S_TLOAD, "s_tload".to_string());
     m.insert(COL_S_TSTORE, "s_tstore".to_string());
     m.insert(COL_S_CALL_SC, "s_call_sc".to_string());
+    m.insert(COL_S_CMOV, "s_cmov".to_string());
+    m.insert(COL_S_TLOADT, "s_tloadt".to_string());
+    m.insert(COL_S_TSTORET, "s_tstoret".to_string());
+    m.insert(COL_S_SSCAN, "s_sscan".to_string());
+    m.insert(COL_S_TXCTX, "s_txctx".to_string());
     m.insert(COL_IS_ENTRY_SC, "is_entry_sc".to_string());
     m.insert(
         COL_IS_NEXT_LINE_DIFF_INST,
@@ -205,12 +259,28 @@ pub(crate) fn get_cpu_col_name_map() -> BTreeMap<usize, String> {
     m.insert(COL_FILTER_TAPE_LOOKING, "filter_tape_looking".to_string());
     m.insert(IS_SCCALL_EXT_LINE, "is_sccall_ext_line".to_string());
     m.insert(COL_IS_STORAGE_EXT_LINE, "is_storage_ext_line".to_string());
+    m.insert(COL_IS_SSCAN_HEAD_LINE, "is_sscan_head_line".to_string());
+    m.insert(COL_IS_SLOT_LIST_LINE, "is_slot_list_line".to_string());
+    m.insert(COL_IS_STORAGE_WRITE, "is_storage_write".to_string());
     m.insert(COL_FILTER_SCCALL_END, "filter_sccall_end".to_string());
     m.insert(
         COL_FILTER_LOOKING_PROG_IMM,
         "filter_looking_prog_imm".to_string(),
     );
     m.insert(COL_IS_PADDING, "is_padding".to_string());
+    m.insert(COL_IS_FIRST_ROW, "is_first_row".to_string());
+    m.insert(COL_TX_CTX.start, "chain_id".to_string());
+    for (index, col) in COL_TX_CTX.skip(1).enumerate() {
+        let name = format!("tx_hash_{}", index);
+        m.insert(col, name);
+    }
+    for (index, col) in COL_FILTER_RETURNED.enumerate() {
+        let name = format!("filter_returned_{}", index);
+        m.insert(col, name);
+    }
+    m.insert(COL_TX_STEP, "tx_step".to_string());
+    m.insert(COL_IS_END_STATE, "is_end_state".to_string());
+    m.insert(COL_GAS, "gas".to_string());
     m
 }
 
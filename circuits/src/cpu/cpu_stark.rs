@@ -78,6 +78,73 @@ pub fn ctl_filter_cpu_mem_tload_tstore<F: Field>() -> Column<F> {
     Column::single(COL_FILTER_TAPE_LOOKING)
 }
 
+// `tload_t dst key`: op1 is the key, dst the value read
+pub fn ctl_data_cpu_tloadt<F: Field>() -> Vec<Column<F>> {
+    Column::singles(
+        [COL_TX_STEP, COL_ENV_IDX, COL_CLK, COL_OPCODE]
+            .into_iter()
+            .chain(COL_ADDR_STORAGE_RANGE)
+            .chain([COL_OP1, COL_DST]),
+    )
+    .collect_vec()
+}
+
+pub fn ctl_filter_cpu_tloadt<F: Field>() -> Column<F> {
+    Column::single(COL_S_TLOADT)
+}
+
+// `tstore_t key value`: op0 is the key, op1 the value written
+pub fn ctl_data_cpu_tstoret<F: Field>() -> Vec<Column<F>> {
+    Column::singles(
+        [COL_TX_STEP, COL_ENV_IDX, COL_CLK, COL_OPCODE]
+            .into_iter()
+            .chain(COL_ADDR_STORAGE_RANGE)
+            .chain([COL_OP0, COL_OP1]),
+    )
+    .collect_vec()
+}
+
+pub fn ctl_filter_cpu_tstoret<F: Field>() -> Column<F> {
+    Column::single(COL_S_TSTORET)
+}
+
+// chain id and tx hash `txctx` reads
+pub fn ctl_data_tx_context<F: Field>() -> Vec<Column<F>> {
+    Column::singles(COL_TX_CTX).collect_vec()
+}
+
+// opcode of every executed instruction, filtered like the program lookup
+pub fn ctl_data_opcode<F: Field>() -> Vec<Column<F>> {
+    vec![Column::single(COL_OPCODE)]
+}
+
+// pc and registers the execution starts from
+pub fn ctl_data_start_state<F: Field>() -> Vec<Column<F>> {
+    Column::singles([COL_PC].into_iter().chain(COL_REGS)).collect_vec()
+}
+
+pub fn ctl_filter_first_row<F: Field>() -> Column<F> {
+    Column::single(COL_IS_FIRST_ROW)
+}
+
+// pc and registers the execution continues from, and the gas it used
+pub fn ctl_data_end_state<F: Field>() -> Vec<Column<F>> {
+    Column::singles([COL_PC].into_iter().chain(COL_REGS).chain([COL_GAS])).collect_vec()
+}
+
+pub fn ctl_filter_end_state<F: Field>() -> Column<F> {
+    Column::single(COL_IS_END_STATE)
+}
+
+// register i of the entry END row, if the program returns it
+pub fn ctl_data_returned_value<F: Field>(i: usize) -> Vec<Column<F>> {
+    vec![Column::single(COL_REGS.start + i)]
+}
+
+pub fn ctl_filter_returned_value<F: Field>(i: usize) -> Column<F> {
+    Column::single(COL_FILTER_RETURNED.start + i)
+}
+
 pub(crate) fn ctl_data_cpu_mem_sccall<F: Field>(i: usize) -> Vec<Column<F>> {
     let col_addr = match i {
         0 => COL_OP0,
@@ -135,6 +202,18 @@ pub fn ctl_filter_with_rangecheck<F: Field>() -> Column<F> {
     Column::single(COL_S_RC)
 }
 
+// `sscan`: the bound minus the number of slots read is not negative
+pub fn ctl_data_cpu_sscan_rangecheck<F: Field>() -> Vec<Column<F>> {
+    vec![Column::linear_combination([
+        (COL_AUX0, F::ONE),
+        (COL_DST, F::NEG_ONE),
+    ])]
+}
+
+pub fn ctl_filter_cpu_sscan_rangecheck<F: Field>() -> Column<F> {
+    Column::single(COL_IS_SSCAN_HEAD_LINE)
+}
+
 pub fn ctl_data_with_poseidon_chunk<F: Field>() -> Vec<Column<F>> {
     Column::singles([
         COL_TX_IDX,
@@ -160,28 +239,27 @@ pub fn ctl_filter_cpu_tape_load_store<F: Field>() -> Column<F> {
     Column::single(COL_FILTER_TAPE_LOOKING)
 }
 
+// the key domain follows the key, 0 for slots
 pub fn ctl_data_poseidon_treekey<F: Field>() -> Vec<Column<F>> {
-    let mut res =
-        Column::singles(COL_ADDR_STORAGE_RANGE.chain(COL_S_OP0.skip(4).take(4))).collect_vec();
-    res.extend([
-        Column::zero(),
-        Column::zero(),
-        Column::zero(),
-        Column::zero(),
-    ]);
+    let mut res = Column::singles(
+        COL_ADDR_STORAGE_RANGE
+            .chain(COL_S_OP0.skip(4).take(4))
+            .chain([COL_SLOT_DOMAIN]),
+    )
+    .collect_vec();
+    res.extend([Column::zero(), Column::zero(), Column::zero()]);
     res.extend(Column::singles(COL_S_DST.take(4)).collect_vec());
     res
 }
 
 pub fn ctl_filter_poseidon_treekey<F: Field>() -> Column<F> {
-    Column::single(COL_IS_STORAGE_EXT_LINE)
+    Column::sum([COL_IS_STORAGE_EXT_LINE, COL_IS_SLOT_LIST_LINE])
 }
 
 pub fn ctl_data_cpu_storage_access<F: Field>() -> Vec<Column<F>> {
     Column::singles([
         COL_IDX_STORAGE,
-        // is_write
-        COL_S_SSTORE,
+        COL_IS_STORAGE_WRITE,
         // addr
         COL_S_DST.start,
         COL_S_DST.start + 1,
@@ -196,8 +274,14 @@ pub fn ctl_data_cpu_storage_access<F: Field>() -> Vec<Column<F>> {
     .collect_vec()
 }
 
+// slot and slot list accesses
 pub fn ctl_filter_cpu_storage_access<F: Field>() -> Column<F> {
-    Column::single(COL_IS_STORAGE_EXT_LINE)
+    Column::sum([COL_IS_STORAGE_EXT_LINE, COL_IS_SLOT_LIST_LINE])
+}
+
+// the sscan head line reads the start key, its slot lines write keys
+pub fn ctl_filter_cpu_mem_for_storage_addr<F: Field>() -> Column<F> {
+    Column::sum([COL_IS_STORAGE_EXT_LINE, COL_IS_SSCAN_HEAD_LINE])
 }
 
 pub fn ctl_data_cpu_mem_for_storage_addr<F: Field>(i: usize) -> Vec<Column<F>> {
@@ -212,6 +296,15 @@ pub fn ctl_data_cpu_mem_for_storage_addr<F: Field>(i: usize) -> Vec<Column<F>> {
     .collect_vec()
 }
 
+// the sscan head line reads the cursor flag as the first value word
+pub fn ctl_filter_cpu_mem_for_storage_value<F: Field>(i: usize) -> Column<F> {
+    if i == 0 {
+        Column::sum([COL_IS_STORAGE_EXT_LINE, COL_IS_SSCAN_HEAD_LINE])
+    } else {
+        Column::single(COL_IS_STORAGE_EXT_LINE)
+    }
+}
+
 pub fn ctl_data_cpu_mem_for_storage_value<F: Field>(i: usize) -> Vec<Column<F>> {
     Column::singles([
         COL_TX_IDX,
@@ -385,10 +478,9 @@ impl<F: RichField, const D: usize> CpuStark<F, D> {
         yield_constr.constraint_first_row(lv[COL_CALL_SC_CNT]);
         // todo exe and code context should be entry system contract?
         yield_constr.constraint_first_row(lv[COL_CLK]);
-        yield_constr.constraint_first_row(lv[COL_PC]);
-        COL_REGS.for_each(|col_reg| {
-            yield_constr.constraint_first_row(lv[col_reg]);
-        });
+        // pc and registers of the first line are looked up by the start
+        // state of the public values, a chunk of an execution starts where
+        // the previous one stopped
         // tx_idx should be the same or increase by one
         yield_constr
             .constraint_transition(wrapper.is_in_same_tx * (nv[COL_TX_IDX] - lv[COL_TX_IDX]));
@@ -402,6 +494,13 @@ impl<F: RichField, const D: usize> CpuStark<F, D> {
         COL_REGS.for_each(|col_reg| {
             yield_constr.constraint_transition((P::ONES - wrapper.is_in_same_tx) * nv[col_reg]);
         });
+        // the end state is the first padding row
+        yield_constr.constraint_first_row(lv[COL_IS_END_STATE] - lv[COL_IS_PADDING]);
+        yield_constr
+            .constraint_transition(nv[COL_IS_END_STATE] - nv[COL_IS_PADDING] + lv[COL_IS_PADDING]);
+        // tx step counts the rows, padding included
+        yield_constr.constraint_first_row(lv[COL_TX_STEP]);
+        yield_constr.constraint_transition(nv[COL_TX_STEP] - lv[COL_TX_STEP] - P::ONES);
     }
 
     fn constraint_env_idx<FE, P, const D2: usize>(
@@ -482,6 +581,11 @@ impl<F: RichField, const D: usize> CpuStark<F, D> {
             (lv[COL_S_TLOAD], OlaOpcode::TLOAD.binary_bit_mask()),
             (lv[COL_S_TSTORE], OlaOpcode::TSTORE.binary_bit_mask()),
             (lv[COL_S_CALL_SC], OlaOpcode::SCCALL.binary_bit_mask()),
+            (lv[COL_S_CMOV], OlaOpcode::CMOV.binary_bit_mask()),
+            (lv[COL_S_TLOADT], OlaOpcode::TLOADT.binary_bit_mask()),
+            (lv[COL_S_TSTORET], OlaOpcode::TSTORET.binary_bit_mask()),
+            (lv[COL_S_SSCAN], OlaOpcode::SSCAN.binary_bit_mask()),
+            (lv[COL_S_TXCTX], OlaOpcode::TXCTX.binary_bit_mask()),
         ];
         yield_constr.constraint(
             lv[COL_S_SIMPLE_ARITHMATIC_OP]
@@ -762,7 +866,12 @@ impl<F: RichField, const D: usize> CpuStark<F, D> {
         FE: FieldExtension<D2, BaseField = F>,
         P: PackedField<Scalar = FE>,
     {
+        // sstore and sscan ext lines carry keys in dst_sel, which select no
+        // register there
+        let is_key_line =
+            (wrapper.lv[COL_S_SSTORE] + wrapper.lv[COL_S_SSCAN]) * wrapper.lv[COL_IS_EXT_LINE];
         let s_dsts: [P; REGISTER_NUM] = wrapper.lv[COL_S_DST].try_into().unwrap();
+        let s_dsts = s_dsts.map(|s| s * (P::ONES - is_key_line));
         let multi_reg_change = wrapper.lv[COL_S_SLOAD]
             + wrapper.lv[COL_S_PSDN]
             + wrapper.lv[COL_S_CALL_SC] * wrapper.is_crossing_inst
@@ -833,20 +942,25 @@ impl<
             + lv[COL_S_TLOAD]
             + lv[COL_S_TSTORE]
             + lv[COL_S_CALL_SC]
-            + lv[COL_S_END];
+            + lv[COL_S_END]
+            + lv[COL_S_SSCAN];
         let nv_is_ext_inst = nv[COL_S_SLOAD]
             + nv[COL_S_SSTORE]
             + nv[COL_S_TLOAD]
             + nv[COL_S_TSTORE]
             + nv[COL_S_CALL_SC]
-            + nv[COL_S_END];
+            + nv[COL_S_END]
+            + nv[COL_S_SSCAN];
         let lv_is_entry_sc = lv[COL_IS_ENTRY_SC];
         let lv_ext_length = lv[COL_S_SLOAD]
             + lv[COL_S_SSTORE]
+                * (P::ONES * P::Scalar::from_canonical_u64(5)
+                    - lv[COL_SLOT_LISTED] * P::Scalar::from_canonical_u64(3))
             + lv[COL_S_TLOAD] * (lv[COL_OP0] * lv[COL_OP1] + (P::ONES - lv[COL_OP0]))
             + lv[COL_S_TSTORE] * lv[COL_OP1]
             + lv[COL_S_CALL_SC]
-            + lv[COL_S_END] * (P::ONES - lv_is_entry_sc);
+            + lv[COL_S_END] * (P::ONES - lv_is_entry_sc)
+            + lv[COL_S_SSCAN] * (lv[COL_DST] + P::ONES) * P::Scalar::from_canonical_u64(2);
         let is_crossing_inst = lv[COL_IS_NEXT_LINE_DIFF_INST];
         let is_in_same_tx = lv[COL_IS_NEXT_LINE_SAME_TX];
         Self {
@@ -871,12 +985,13 @@ impl<F: RichField + Extendable<D>, const D: usize> Stark<F, D> for CpuStark<F, D
 
     fn eval_packed_generic<FE, P, const D2: usize>(
         &self,
-        vars: StarkEvaluationVars<FE, P, NUM_CPU_COLS>,
+        vars: StarkEvaluationVars<FE, P, { Self::COLUMNS /*HACKNUM_CPU_COLS*/ }>,
         yield_constr: &mut ConstraintConsumer<P>,
     ) where
         FE: FieldExtension<D2, BaseField = F>,
         P: PackedField<Scalar = FE>,
     {
+        let vars = StarkEvaluationVars::<FE, P, NUM_CPU_COLS> { local_values: vars.local_values[..].try_into().unwrap(), next_values: vars.next_values[..].try_into().unwrap() }; //HACK-VARS
         let lv = vars.local_values;
         let nv = vars.next_values;
 
@@ -937,11 +1052,16 @@ impl<F: RichField + Extendable<D>, const D: usize> Stark<F, D> for CpuStark<F, D
         // // opcode
         simple_arithmatic_op::eval_packed_generic(lv, nv, yield_constr);
         mov::eval_packed_generic(lv, nv, yield_constr);
+        cmov::eval_packed_generic(lv, nv, yield_constr);
         call::eval_packed_generic(lv, nv, yield_constr);
         ret::eval_packed_generic(lv, nv, yield_constr);
         mload::eval_packed_generic(lv, nv, yield_constr);
         mstore::eval_packed_generic(lv, nv, yield_constr);
         storage::eval_packed_generic(lv, nv, yield_constr);
+        sscan::eval_packed_generic(lv, nv, yield_constr);
+        txctx::eval_packed_generic(lv, nv, yield_constr);
+        returns::eval_packed_generic(lv, nv, yield_constr);
+        gas::eval_packed_generic(lv, nv, yield_constr);
         tape::eval_packed_generic(&wrapper, yield_constr);
         call_sc::eval_packed_generic(&wrapper, yield_constr);
     }
@@ -949,9 +1069,10 @@ impl<F: RichField + Extendable<D>, const D: usize> Stark<F, D> for CpuStark<F, D
     fn eval_ext_circuit(
         &self,
         _builder: &mut CircuitBuilder<F, D>,
-        _vars: StarkEvaluationTargets<D, NUM_CPU_COLS>,
+        _vars: StarkEvaluationTargets<D, { Self::COLUMNS /*HACKNUM_CPU_COLS*/ }>,
         _yield_constr: &mut RecursiveConstraintConsumer<F, D>,
     ) {
+        let _vars = StarkEvaluationTargets::<D, NUM_CPU_COLS> { local_values: _vars.local_values[..].try_into().unwrap(), next_values: _vars.next_values[..].try_into().unwrap() }; //HACK-VARS
     }
 
     fn constraint_degree(&self) -> usize {
@@ -1054,6 +1175,19 @@ mod tests {
         test_cpu_with_asm_file_name("vote.json".to_string(), Some(init_calldata), Some(db_name));
     }
 
+    #[test]
+    #[should_panic(expected = "has no cpu selector")]
+    fn test_unknown_opcode() {
+        let mut steps = crate::fixtures::fixture("fibonacci")
+            .unwrap()
+            .execute()
+            .unwrap()
+            .trace
+            .exec;
+        steps[0].opcode = GoldilocksField::from_canonical_u64(1 << 1);
+        generate_cpu_trace::<GoldilocksField>(&steps);
+    }
+
     #[allow(unused)]
     fn test_cpu_with_asm_file_name(
         file_name: String,
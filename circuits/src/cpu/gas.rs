@@ -0,0 +1,94 @@
+use super::columns::*;
+use crate::stark::constraint_consumer::{ConstraintConsumer, RecursiveConstraintConsumer};
+use core::vm::opcodes::OlaOpcode;
+use executor::gas::{DEFAULT_GAS_COSTS, DEFAULT_STEP_GAS};
+use plonky2::{
+    field::{extension::Extendable, packed::PackedField, types::Field},
+    hash::hash_types::RichField,
+    iop::ext_target::ExtensionTarget,
+    plonk::circuit_builder::CircuitBuilder,
+};
+
+// Gas: every main line is charged the cost of its opcode in the default gas
+// schedule of the executor, `DEFAULT_STEP_GAS` plus the extra cost
+// `DEFAULT_GAS_COSTS` gives its selector. Ext lines and padding are free, so
+// the gas of the end state row is the gas the table used.
+
+// Selector of an opcode the default gas schedule prices apart, which must not
+// share it with other opcodes.
+fn gas_selector(opcode: OlaOpcode) -> usize {
+    match opcode {
+        OlaOpcode::MOV => COL_S_MOV,
+        OlaOpcode::JMP => COL_S_JMP,
+        OlaOpcode::CJMP => COL_S_CJMP,
+        OlaOpcode::CALL => COL_S_CALL,
+        OlaOpcode::RET => COL_S_RET,
+        OlaOpcode::MLOAD => COL_S_MLOAD,
+        OlaOpcode::MSTORE => COL_S_MSTORE,
+        OlaOpcode::END => COL_S_END,
+        OlaOpcode::RC => COL_S_RC,
+        OlaOpcode::NOT => COL_S_NOT,
+        OlaOpcode::GTE => COL_S_GTE,
+        OlaOpcode::POSEIDON => COL_S_PSDN,
+        OlaOpcode::SLOAD => COL_S_SLOAD,
+        OlaOpcode::SSTORE => COL_S_SSTORE,
+        OlaOpcode::TLOAD => COL_S_TLOAD,
+        OlaOpcode::TSTORE => COL_S_TSTORE,
+        OlaOpcode::SCCALL => COL_S_CALL_SC,
+        OlaOpcode::CMOV => COL_S_CMOV,
+        OlaOpcode::TLOADT => COL_S_TLOADT,
+        OlaOpcode::TSTORET => COL_S_TSTORET,
+        OlaOpcode::SSCAN => COL_S_SSCAN,
+        OlaOpcode::TXCTX => COL_S_TXCTX,
+        _ => panic!("{} shares its selector, gas can't price it apart", opcode),
+    }
+}
+
+fn extra_cost<F: Field>(cost: u64) -> F {
+    F::from_canonical_u64(cost) - F::from_canonical_u64(DEFAULT_STEP_GAS)
+}
+
+/// Gas the row is charged.
+pub(crate) fn step_gas<P: PackedField>(lv: &[P; NUM_CPU_COLS]) -> P {
+    let is_main_line = P::ONES - lv[COL_IS_EXT_LINE] - lv[COL_IS_PADDING];
+    let cost = DEFAULT_GAS_COSTS.iter().fold(
+        P::ONES * P::Scalar::from_canonical_u64(DEFAULT_STEP_GAS),
+        |acc, (opcode, cost)| acc + lv[gas_selector(*opcode)] * extra_cost::<P::Scalar>(*cost),
+    );
+    is_main_line * cost
+}
+
+pub(crate) fn eval_packed_generic<P: PackedField>(
+    lv: &[P; NUM_CPU_COLS],
+    nv: &[P; NUM_CPU_COLS],
+    yield_constr: &mut ConstraintConsumer<P>,
+) {
+    // main lines are the rows which are not ext lines
+    yield_constr.constraint(lv[COL_IS_EXT_LINE] * (P::ONES - lv[COL_IS_EXT_LINE]));
+    yield_constr.constraint_first_row(lv[COL_GAS]);
+    yield_constr.constraint_transition(nv[COL_GAS] - lv[COL_GAS] - step_gas(lv));
+}
+
+pub(crate) fn eval_ext_circuit<F: RichField + Extendable<D>, const D: usize>(
+    builder: &mut CircuitBuilder<F, D>,
+    lv: &[ExtensionTarget<D>; NUM_CPU_COLS],
+    nv: &[ExtensionTarget<D>; NUM_CPU_COLS],
+    yield_constr: &mut RecursiveConstraintConsumer<F, D>,
+) {
+    let one = builder.one_extension();
+    let not_ext = builder.sub_extension(one, lv[COL_IS_EXT_LINE]);
+    let cs = builder.mul_extension(lv[COL_IS_EXT_LINE], not_ext);
+    yield_constr.constraint(builder, cs);
+    yield_constr.constraint_first_row(builder, lv[COL_GAS]);
+
+    let is_main_line = builder.sub_extension(not_ext, lv[COL_IS_PADDING]);
+    let mut cost = builder.constant_extension(F::Extension::from_canonical_u64(DEFAULT_STEP_GAS));
+    for (opcode, extra) in DEFAULT_GAS_COSTS {
+        let extra = builder.constant_extension(extra_cost(extra));
+        cost = builder.mul_add_extension(lv[gas_selector(opcode)], extra, cost);
+    }
+    let step_gas = builder.mul_extension(is_main_line, cost);
+    let gas = builder.add_extension(lv[COL_GAS], step_gas);
+    let cs = builder.sub_extension(nv[COL_GAS], gas);
+    yield_constr.constraint_transition(builder, cs);
+}
@@ -1,15 +1,20 @@
 // mod add;
 // mod assert;
 mod call;
+mod cmov;
 // mod cmp;
 pub mod columns;
 pub mod cpu_stark;
+pub(crate) mod gas;
 mod mload;
 mod mov;
 mod mstore;
 // mod mul;
 mod call_sc;
 mod ret;
+mod returns;
 mod simple_arithmatic_op;
+mod sscan;
 mod storage;
 mod tape;
+mod txctx;
@@ -0,0 +1,60 @@
+use super::columns::*;
+use crate::stark::constraint_consumer::{ConstraintConsumer, RecursiveConstraintConsumer};
+use core::program::MAX_RETURN_VALUES;
+use plonky2::{
+    field::{extension::Extendable, packed::PackedField},
+    hash::hash_types::RichField,
+    iop::ext_target::ExtensionTarget,
+    plonk::circuit_builder::CircuitBuilder,
+};
+
+// `.returns n`: the entry END row marks r0 to r(n - 1) as returned, each
+// marked register is looked up by a returned value of the public values.
+// Marks are only allowed on the END main line of the entry contract, the
+// lookup makes them match the number of returned values.
+
+pub(crate) fn eval_packed_generic<P: PackedField>(
+    lv: &[P; NUM_CPU_COLS],
+    _nv: &[P; NUM_CPU_COLS],
+    yield_constr: &mut ConstraintConsumer<P>,
+) {
+    let is_entry_end = lv[COL_S_END]
+        * (P::ONES - lv[COL_IS_EXT_LINE])
+        * lv[COL_IS_ENTRY_SC]
+        * (P::ONES - lv[COL_IS_PADDING]);
+    for i in 0..MAX_RETURN_VALUES {
+        let returned = lv[COL_FILTER_RETURNED.start + i];
+        yield_constr.constraint(returned * (P::ONES - returned));
+        yield_constr.constraint(returned * (P::ONES - is_entry_end));
+        if i > 0 {
+            yield_constr.constraint(returned * (P::ONES - lv[COL_FILTER_RETURNED.start + i - 1]));
+        }
+    }
+}
+
+pub(crate) fn eval_ext_circuit<F: RichField + Extendable<D>, const D: usize>(
+    builder: &mut CircuitBuilder<F, D>,
+    lv: &[ExtensionTarget<D>; NUM_CPU_COLS],
+    _nv: &[ExtensionTarget<D>; NUM_CPU_COLS],
+    yield_constr: &mut RecursiveConstraintConsumer<F, D>,
+) {
+    let one = builder.one_extension();
+    let not_ext = builder.sub_extension(one, lv[COL_IS_EXT_LINE]);
+    let not_padding = builder.sub_extension(one, lv[COL_IS_PADDING]);
+    let is_entry_end =
+        builder.mul_many_extension([lv[COL_S_END], not_ext, lv[COL_IS_ENTRY_SC], not_padding]);
+    let not_entry_end = builder.sub_extension(one, is_entry_end);
+    for i in 0..MAX_RETURN_VALUES {
+        let returned = lv[COL_FILTER_RETURNED.start + i];
+        let not_returned = builder.sub_extension(one, returned);
+        let cs = builder.mul_extension(returned, not_returned);
+        yield_constr.constraint(builder, cs);
+        let cs = builder.mul_extension(returned, not_entry_end);
+        yield_constr.constraint(builder, cs);
+        if i > 0 {
+            let not_prev = builder.sub_extension(one, lv[COL_FILTER_RETURNED.start + i - 1]);
+            let cs = builder.mul_extension(returned, not_prev);
+            yield_constr.constraint(builder, cs);
+        }
+    }
+}
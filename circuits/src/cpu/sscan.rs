@@ -0,0 +1,316 @@
+use super::columns::*;
+use crate::stark::constraint_consumer::{ConstraintConsumer, RecursiveConstraintConsumer};
+use core::{program::REGISTER_NUM, types::merkle_tree::TREE_VALUE_LEN};
+use plonky2::{
+    field::{extension::Extendable, packed::PackedField, types::Field},
+    hash::hash_types::RichField,
+    iop::ext_target::ExtensionTarget,
+    plonk::circuit_builder::CircuitBuilder,
+};
+
+// `sscan dst key out`: op0 is the start key address, op1 the output address,
+// aux0 the slot bound dst held before and dst the number of slots read.
+// The head line holds the start key addresses and words in op0_sel[0..8] and
+// the cursor flag after them at op1_sel[0] and op1_sel[4]. The walk reads the
+// list record the flag names, head(p) or link(p, w) of the start key, then per
+// slot a storage line with the key in op0_sel and the value in op1_sel like
+// sload and a link record line for the same key, see `storage.rs` for the
+// slot lists. The prefix p, the first three words of the start key, is carried
+// along the ext lines in dst_sel[4..7].
+// Every slot line follows a record pointing at it and the walk ends where the
+// last record read ends the list or after aux0 slots, so the slots read are
+// the list of p from the cursor on, none skipped.
+const PREFIX_LEN: usize = TREE_VALUE_LEN - 1;
+
+pub(crate) fn eval_packed_generic<P: PackedField>(
+    lv: &[P; NUM_CPU_COLS],
+    nv: &[P; NUM_CPU_COLS],
+    yield_constr: &mut ConstraintConsumer<P>,
+) {
+    let s = lv[COL_S_SSCAN];
+    let is_main = s * (P::ONES - lv[COL_IS_EXT_LINE]);
+    let is_head = lv[COL_IS_SSCAN_HEAD_LINE];
+    let is_slot = s * lv[COL_IS_STORAGE_EXT_LINE];
+    let is_record = s * lv[COL_IS_SLOT_LIST_LINE];
+    let flag = lv[COL_S_OP1.start + TREE_VALUE_LEN];
+    let has_next = lv[COL_S_OP1.start + TREE_VALUE_LEN + 2];
+    let last_word = TREE_VALUE_LEN - 1;
+
+    // main line: aux0 is the bound, the head line follows
+    let old_dst = (0..REGISTER_NUM).fold(P::ZEROS, |acc, i| {
+        acc + lv[COL_S_DST.start + i] * lv[COL_REGS.start + i]
+    });
+    yield_constr.constraint(is_main * (lv[COL_AUX0] - old_dst));
+    yield_constr.constraint_transition(is_main * (P::ONES - nv[COL_IS_SSCAN_HEAD_LINE]));
+
+    // operands stay the same on the ext lines
+    for col in [COL_OP0, COL_OP1, COL_DST, COL_AUX0] {
+        yield_constr.constraint_transition(s * nv[COL_IS_EXT_LINE] * (nv[col] - lv[col]));
+    }
+
+    // an ext line is the head, a slot or a record line, the head is the
+    // first, sscan writes no storage
+    yield_constr.constraint(is_head * (P::ONES - is_head));
+    yield_constr.constraint((P::ONES - s) * is_head);
+    yield_constr.constraint((P::ONES - lv[COL_IS_EXT_LINE]) * is_head);
+    yield_constr.constraint(is_head * (lv[COL_EXT_CNT] - P::ONES));
+    yield_constr.constraint(
+        s * (lv[COL_IS_EXT_LINE]
+            - is_head
+            - lv[COL_IS_STORAGE_EXT_LINE]
+            - lv[COL_IS_SLOT_LIST_LINE]),
+    );
+    yield_constr.constraint(s * lv[COL_IS_STORAGE_WRITE]);
+
+    // head: the start key and the flag after it are read from op0 on, the
+    // prefix is kept
+    yield_constr.constraint(is_head * (lv[COL_S_OP0.start] - lv[COL_OP0]));
+    for i in 0..TREE_VALUE_LEN - 1 {
+        yield_constr.constraint(
+            is_head * (lv[COL_S_OP0.start + i + 1] - lv[COL_S_OP0.start + i] - P::ONES),
+        );
+    }
+    yield_constr.constraint(
+        is_head
+            * (lv[COL_S_OP1.start]
+                - lv[COL_S_OP0.start]
+                - P::Scalar::from_canonical_u64(TREE_VALUE_LEN as u64)),
+    );
+    for i in 0..PREFIX_LEN {
+        yield_constr.constraint(
+            is_head
+                * (lv[COL_S_DST.start + TREE_VALUE_LEN + i]
+                    - lv[COL_S_OP0.start + TREE_VALUE_LEN + i]),
+        );
+    }
+    // flag 0 starts at head(p), 1 at link(p, w)
+    yield_constr.constraint(is_head * flag * (P::ONES - flag));
+    yield_constr.constraint_transition(is_head * (P::ONES - nv[COL_IS_SLOT_LIST_LINE]));
+    yield_constr.constraint_transition(
+        is_head
+            * (nv[COL_IS_SLOT_LIST_LINE] * P::Scalar::from_canonical_u64(3)
+                - nv[COL_SLOT_DOMAIN]
+                - flag),
+    );
+    yield_constr.constraint_transition(
+        is_head
+            * (nv[COL_S_OP0.start + TREE_VALUE_LEN + last_word]
+                - flag * lv[COL_S_OP0.start + TREE_VALUE_LEN + last_word]),
+    );
+
+    // record: while the walk goes on, the record points at the next slot
+    // read, at its end the list ended or aux0 slots were read
+    yield_constr.constraint(is_record * has_next * (P::ONES - has_next));
+    yield_constr.constraint_transition(
+        is_record * nv[COL_IS_EXT_LINE] * (P::ONES - nv[COL_IS_STORAGE_EXT_LINE]),
+    );
+    yield_constr.constraint_transition(is_record * nv[COL_IS_EXT_LINE] * (P::ONES - has_next));
+    yield_constr.constraint_transition(
+        is_record
+            * nv[COL_IS_EXT_LINE]
+            * (nv[COL_S_OP0.start + TREE_VALUE_LEN + last_word]
+                - lv[COL_S_OP1.start + TREE_VALUE_LEN + 1]),
+    );
+    yield_constr.constraint_transition(
+        is_record * (P::ONES - nv[COL_IS_EXT_LINE]) * has_next * (lv[COL_AUX0] - lv[COL_DST]),
+    );
+
+    // slot i: key and value are written to out + 8 * i on, the link of the
+    // slot is read next
+    let slot_offset = (lv[COL_EXT_CNT] - P::Scalar::from_canonical_u64(3))
+        * P::Scalar::from_canonical_u64(TREE_VALUE_LEN as u64);
+    yield_constr.constraint(is_slot * (lv[COL_S_OP0.start] - lv[COL_OP1] - slot_offset));
+    yield_constr.constraint(
+        is_slot
+            * (lv[COL_S_OP1.start]
+                - lv[COL_S_OP0.start]
+                - P::Scalar::from_canonical_u64(TREE_VALUE_LEN as u64)),
+    );
+    for i in 0..TREE_VALUE_LEN - 1 {
+        yield_constr.constraint(
+            is_slot * (lv[COL_S_OP0.start + i + 1] - lv[COL_S_OP0.start + i] - P::ONES),
+        );
+        yield_constr.constraint(
+            is_slot * (lv[COL_S_OP1.start + i + 1] - lv[COL_S_OP1.start + i] - P::ONES),
+        );
+    }
+    yield_constr.constraint_transition(
+        is_slot
+            * (P::ONES + nv[COL_SLOT_DOMAIN]
+                - nv[COL_IS_SLOT_LIST_LINE] * P::Scalar::from_canonical_u64(3)),
+    );
+    for i in 0..TREE_VALUE_LEN {
+        yield_constr.constraint_transition(
+            is_slot
+                * (nv[COL_S_OP0.start + TREE_VALUE_LEN + i]
+                    - lv[COL_S_OP0.start + TREE_VALUE_LEN + i]),
+        );
+    }
+
+    // slot and record keys have the prefix of the start key
+    for i in 0..PREFIX_LEN {
+        let prefix = COL_S_DST.start + TREE_VALUE_LEN + i;
+        yield_constr.constraint(
+            (is_slot + is_record) * (lv[COL_S_OP0.start + TREE_VALUE_LEN + i] - lv[prefix]),
+        );
+        yield_constr.constraint_transition(
+            s * (nv[COL_IS_STORAGE_EXT_LINE] + nv[COL_IS_SLOT_LIST_LINE])
+                * (nv[prefix] - lv[prefix]),
+        );
+    }
+}
+
+pub(crate) fn eval_ext_circuit<F: RichField + Extendable<D>, const D: usize>(
+    builder: &mut CircuitBuilder<F, D>,
+    lv: &[ExtensionTarget<D>; NUM_CPU_COLS],
+    nv: &[ExtensionTarget<D>; NUM_CPU_COLS],
+    yield_constr: &mut RecursiveConstraintConsumer<F, D>,
+) {
+    let one = builder.one_extension();
+    let s = lv[COL_S_SSCAN];
+    let not_ext = builder.sub_extension(one, lv[COL_IS_EXT_LINE]);
+    let is_main = builder.mul_extension(s, not_ext);
+    let is_head = lv[COL_IS_SSCAN_HEAD_LINE];
+    let is_slot = builder.mul_extension(s, lv[COL_IS_STORAGE_EXT_LINE]);
+    let is_record = builder.mul_extension(s, lv[COL_IS_SLOT_LIST_LINE]);
+    let flag = lv[COL_S_OP1.start + TREE_VALUE_LEN];
+    let has_next = lv[COL_S_OP1.start + TREE_VALUE_LEN + 2];
+    let last_word = TREE_VALUE_LEN - 1;
+    let three = builder.constant_extension(F::Extension::from_canonical_u64(3));
+    let word_len =
+        builder.constant_extension(F::Extension::from_canonical_u64(TREE_VALUE_LEN as u64));
+
+    let mut old_dst = builder.zero_extension();
+    for i in 0..REGISTER_NUM {
+        old_dst =
+            builder.mul_add_extension(lv[COL_S_DST.start + i], lv[COL_REGS.start + i], old_dst);
+    }
+    let diff = builder.sub_extension(lv[COL_AUX0], old_dst);
+    let cs = builder.mul_extension(is_main, diff);
+    yield_constr.constraint(builder, cs);
+    let not_head = builder.sub_extension(one, nv[COL_IS_SSCAN_HEAD_LINE]);
+    let cs = builder.mul_extension(is_main, not_head);
+    yield_constr.constraint_transition(builder, cs);
+
+    for col in [COL_OP0, COL_OP1, COL_DST, COL_AUX0] {
+        let diff = builder.sub_extension(nv[col], lv[col]);
+        let cs = builder.mul_many_extension([s, nv[COL_IS_EXT_LINE], diff]);
+        yield_constr.constraint_transition(builder, cs);
+    }
+
+    let not_head = builder.sub_extension(one, is_head);
+    let cs = builder.mul_extension(is_head, not_head);
+    yield_constr.constraint(builder, cs);
+    let not_s = builder.sub_extension(one, s);
+    let cs = builder.mul_extension(not_s, is_head);
+    yield_constr.constraint(builder, cs);
+    let cs = builder.mul_extension(not_ext, is_head);
+    yield_constr.constraint(builder, cs);
+    let diff = builder.sub_extension(lv[COL_EXT_CNT], one);
+    let cs = builder.mul_extension(is_head, diff);
+    yield_constr.constraint(builder, cs);
+    let diff = builder.sub_extension(lv[COL_IS_EXT_LINE], is_head);
+    let diff = builder.sub_extension(diff, lv[COL_IS_STORAGE_EXT_LINE]);
+    let diff = builder.sub_extension(diff, lv[COL_IS_SLOT_LIST_LINE]);
+    let cs = builder.mul_extension(s, diff);
+    yield_constr.constraint(builder, cs);
+    let cs = builder.mul_extension(s, lv[COL_IS_STORAGE_WRITE]);
+    yield_constr.constraint(builder, cs);
+
+    let diff = builder.sub_extension(lv[COL_S_OP0.start], lv[COL_OP0]);
+    let cs = builder.mul_extension(is_head, diff);
+    yield_constr.constraint(builder, cs);
+    for i in 0..TREE_VALUE_LEN - 1 {
+        let diff = builder.sub_extension(lv[COL_S_OP0.start + i + 1], lv[COL_S_OP0.start + i]);
+        let diff = builder.sub_extension(diff, one);
+        let cs = builder.mul_extension(is_head, diff);
+        yield_constr.constraint(builder, cs);
+    }
+    let diff = builder.sub_extension(lv[COL_S_OP1.start], lv[COL_S_OP0.start]);
+    let diff = builder.sub_extension(diff, word_len);
+    let cs = builder.mul_extension(is_head, diff);
+    yield_constr.constraint(builder, cs);
+    for i in 0..PREFIX_LEN {
+        let diff = builder.sub_extension(
+            lv[COL_S_DST.start + TREE_VALUE_LEN + i],
+            lv[COL_S_OP0.start + TREE_VALUE_LEN + i],
+        );
+        let cs = builder.mul_extension(is_head, diff);
+        yield_constr.constraint(builder, cs);
+    }
+    let not_flag = builder.sub_extension(one, flag);
+    let cs = builder.mul_many_extension([is_head, flag, not_flag]);
+    yield_constr.constraint(builder, cs);
+    let not_record = builder.sub_extension(one, nv[COL_IS_SLOT_LIST_LINE]);
+    let cs = builder.mul_extension(is_head, not_record);
+    yield_constr.constraint_transition(builder, cs);
+    let n_is_link =
+        builder.mul_sub_extension(nv[COL_IS_SLOT_LIST_LINE], three, nv[COL_SLOT_DOMAIN]);
+    let diff = builder.sub_extension(n_is_link, flag);
+    let cs = builder.mul_extension(is_head, diff);
+    yield_constr.constraint_transition(builder, cs);
+    let cursor = builder.mul_extension(flag, lv[COL_S_OP0.start + TREE_VALUE_LEN + last_word]);
+    let diff = builder.sub_extension(nv[COL_S_OP0.start + TREE_VALUE_LEN + last_word], cursor);
+    let cs = builder.mul_extension(is_head, diff);
+    yield_constr.constraint_transition(builder, cs);
+
+    let not_has_next = builder.sub_extension(one, has_next);
+    let cs = builder.mul_many_extension([is_record, has_next, not_has_next]);
+    yield_constr.constraint(builder, cs);
+    let not_slot = builder.sub_extension(one, nv[COL_IS_STORAGE_EXT_LINE]);
+    let cs = builder.mul_many_extension([is_record, nv[COL_IS_EXT_LINE], not_slot]);
+    yield_constr.constraint_transition(builder, cs);
+    let cs = builder.mul_many_extension([is_record, nv[COL_IS_EXT_LINE], not_has_next]);
+    yield_constr.constraint_transition(builder, cs);
+    let diff = builder.sub_extension(
+        nv[COL_S_OP0.start + TREE_VALUE_LEN + last_word],
+        lv[COL_S_OP1.start + TREE_VALUE_LEN + 1],
+    );
+    let cs = builder.mul_many_extension([is_record, nv[COL_IS_EXT_LINE], diff]);
+    yield_constr.constraint_transition(builder, cs);
+    let n_not_ext = builder.sub_extension(one, nv[COL_IS_EXT_LINE]);
+    let unread = builder.sub_extension(lv[COL_AUX0], lv[COL_DST]);
+    let cs = builder.mul_many_extension([is_record, n_not_ext, has_next, unread]);
+    yield_constr.constraint_transition(builder, cs);
+
+    let slot_idx = builder.sub_extension(lv[COL_EXT_CNT], three);
+    let slot_start = builder.mul_add_extension(slot_idx, word_len, lv[COL_OP1]);
+    let diff = builder.sub_extension(lv[COL_S_OP0.start], slot_start);
+    let cs = builder.mul_extension(is_slot, diff);
+    yield_constr.constraint(builder, cs);
+    let diff = builder.sub_extension(lv[COL_S_OP1.start], lv[COL_S_OP0.start]);
+    let diff = builder.sub_extension(diff, word_len);
+    let cs = builder.mul_extension(is_slot, diff);
+    yield_constr.constraint(builder, cs);
+    for i in 0..TREE_VALUE_LEN - 1 {
+        for sel in [COL_S_OP0.start, COL_S_OP1.start] {
+            let diff = builder.sub_extension(lv[sel + i + 1], lv[sel + i]);
+            let diff = builder.sub_extension(diff, one);
+            let cs = builder.mul_extension(is_slot, diff);
+            yield_constr.constraint(builder, cs);
+        }
+    }
+    let not_link = builder.sub_extension(one, n_is_link);
+    let cs = builder.mul_extension(is_slot, not_link);
+    yield_constr.constraint_transition(builder, cs);
+    for i in 0..TREE_VALUE_LEN {
+        let diff = builder.sub_extension(
+            nv[COL_S_OP0.start + TREE_VALUE_LEN + i],
+            lv[COL_S_OP0.start + TREE_VALUE_LEN + i],
+        );
+        let cs = builder.mul_extension(is_slot, diff);
+        yield_constr.constraint_transition(builder, cs);
+    }
+
+    let is_keyed = builder.add_extension(is_slot, is_record);
+    let n_is_keyed = builder.add_extension(nv[COL_IS_STORAGE_EXT_LINE], nv[COL_IS_SLOT_LIST_LINE]);
+    for i in 0..PREFIX_LEN {
+        let prefix = COL_S_DST.start + TREE_VALUE_LEN + i;
+        let diff = builder.sub_extension(lv[COL_S_OP0.start + TREE_VALUE_LEN + i], lv[prefix]);
+        let cs = builder.mul_extension(is_keyed, diff);
+        yield_constr.constraint(builder, cs);
+        let diff = builder.sub_extension(nv[prefix], lv[prefix]);
+        let cs = builder.mul_many_extension([s, n_is_keyed, diff]);
+        yield_constr.constraint_transition(builder, cs);
+    }
+}
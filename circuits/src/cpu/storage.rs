@@ -1,4 +1,5 @@
-use plonky2::field::packed::PackedField;
+use core::types::merkle_tree::TREE_VALUE_LEN;
+use plonky2::field::{packed::PackedField, types::Field};
 
 use crate::stark::constraint_consumer::ConstraintConsumer;
 
@@ -10,10 +11,14 @@ pub(crate) fn eval_packed_generic<P: PackedField>(
     yield_constr: &mut ConstraintConsumer<P>,
 ) {
     let lv_is_storage_op = lv[COL_S_SSTORE] + lv[COL_S_SLOAD];
-    // st_access_idx: start from 0, increase 1 when meet sstore or sload
+    let lv_is_storage_line = lv[COL_IS_STORAGE_EXT_LINE] * lv_is_storage_op;
+    // st_access_idx: start from 0, increase 1 on every slot or slot list access
     yield_constr.constraint_first_row(lv[COL_IDX_STORAGE] - lv_is_storage_op);
     yield_constr.constraint_transition(
-        nv[COL_IDX_STORAGE] - lv[COL_IDX_STORAGE] - nv[COL_IS_STORAGE_EXT_LINE],
+        nv[COL_IDX_STORAGE]
+            - lv[COL_IDX_STORAGE]
+            - nv[COL_IS_STORAGE_EXT_LINE]
+            - nv[COL_IS_SLOT_LIST_LINE],
     );
     // op0, op1 same as main line
     yield_constr.constraint(
@@ -22,48 +27,148 @@ pub(crate) fn eval_packed_generic<P: PackedField>(
     yield_constr.constraint(
         lv_is_storage_op * (P::ONES - lv[COL_IS_EXT_LINE]) * (nv[COL_OP1] - lv[COL_OP1]),
     );
-    // in ext line, op0_sel[0~3] is mem addr which stores storageKey
+    // in storage line, op0_sel[0~3] is mem addr which stores storageKey
+    yield_constr.constraint(lv_is_storage_line * (lv[COL_S_OP0.start] - lv[COL_OP0]));
     yield_constr
-        .constraint(lv_is_storage_op * lv[COL_IS_EXT_LINE] * (lv[COL_S_OP0.start] - lv[COL_OP0]));
+        .constraint(lv_is_storage_line * (lv[COL_S_OP0.start + 1] - lv[COL_S_OP0.start] - P::ONES));
     yield_constr.constraint(
-        lv_is_storage_op
-            * lv[COL_IS_EXT_LINE]
-            * (lv[COL_S_OP0.start + 1] - lv[COL_S_OP0.start] - P::ONES),
-    );
-    yield_constr.constraint(
-        lv_is_storage_op
-            * lv[COL_IS_EXT_LINE]
-            * (lv[COL_S_OP0.start + 2] - lv[COL_S_OP0.start + 1] - P::ONES),
+        lv_is_storage_line * (lv[COL_S_OP0.start + 2] - lv[COL_S_OP0.start + 1] - P::ONES),
     );
     yield_constr.constraint(
-        lv_is_storage_op
-            * lv[COL_IS_EXT_LINE]
-            * (lv[COL_S_OP0.start + 3] - lv[COL_S_OP0.start + 2] - P::ONES),
+        lv_is_storage_line * (lv[COL_S_OP0.start + 3] - lv[COL_S_OP0.start + 2] - P::ONES),
     );
-    // in ext line, op1_sel[0~3] is mem addr which stores value
+    // in storage line, op1_sel[0~3] is mem addr which stores value
+    yield_constr.constraint(lv_is_storage_line * (lv[COL_S_OP1.start] - lv[COL_OP1]));
     yield_constr
-        .constraint(lv_is_storage_op * lv[COL_IS_EXT_LINE] * (lv[COL_S_OP1.start] - lv[COL_OP1]));
+        .constraint(lv_is_storage_line * (lv[COL_S_OP1.start + 1] - lv[COL_S_OP1.start] - P::ONES));
     yield_constr.constraint(
-        lv_is_storage_op
-            * lv[COL_IS_EXT_LINE]
-            * (lv[COL_S_OP1.start + 1] - lv[COL_S_OP1.start] - P::ONES),
+        lv_is_storage_line * (lv[COL_S_OP1.start + 2] - lv[COL_S_OP1.start + 1] - P::ONES),
     );
     yield_constr.constraint(
-        lv_is_storage_op
-            * lv[COL_IS_EXT_LINE]
-            * (lv[COL_S_OP1.start + 2] - lv[COL_S_OP1.start + 1] - P::ONES),
+        lv_is_storage_line * (lv[COL_S_OP1.start + 3] - lv[COL_S_OP1.start + 2] - P::ONES),
     );
+    // is_storage_ext constraints:
     yield_constr.constraint(
         lv_is_storage_op
             * lv[COL_IS_EXT_LINE]
-            * (lv[COL_S_OP1.start + 3] - lv[COL_S_OP1.start + 2] - P::ONES),
-    );
-    // is_storage_ext constraints:
-    yield_constr.constraint(
-        lv_is_storage_op * lv[COL_IS_EXT_LINE] * (P::ONES - lv[COL_IS_STORAGE_EXT_LINE]),
+            * (P::ONES - lv[COL_IS_STORAGE_EXT_LINE] - lv[COL_IS_SLOT_LIST_LINE]),
     );
-    yield_constr.constraint((P::ONES - lv_is_storage_op) * lv[COL_IS_STORAGE_EXT_LINE]);
+    // sscan reads a storage slot on every ext line but its head
+    yield_constr
+        .constraint((P::ONES - lv_is_storage_op - lv[COL_S_SSCAN]) * lv[COL_IS_STORAGE_EXT_LINE]);
     yield_constr.constraint(
         lv_is_storage_op * (P::ONES - lv[COL_IS_EXT_LINE]) * lv[COL_IS_STORAGE_EXT_LINE],
-    )
+    );
+
+    eval_slot_lists(lv, nv, yield_constr);
+}
+
+// Slot lists: every slot sstore writes is listed under its prefix, the first
+// three words of its key, for sscan to walk. The list of a prefix p is
+// storage of the contract in two key domains: head(p), the key (p, 0) in
+// SLOT_HEAD_DOMAIN, and link(p, w) for each listed slot (p, w), its key in
+// SLOT_LINK_DOMAIN. A record is [1, next, has_next, 0] pointing at slot
+// (p, next), or the end of the list, and zero while never written.
+//
+// sstore: the write on its storage line, which carries the key along its
+// ext lines in dst_sel[4..8], then the read of link(k). The slot is listed
+// when the link was written, carried in dst_sel[8]. Otherwise it is prepended
+// to the list: head(p) is read, link(k) written the record the head held and
+// head(p) written to point at the slot.
+fn eval_slot_lists<P: PackedField>(
+    lv: &[P; NUM_CPU_COLS],
+    nv: &[P; NUM_CPU_COLS],
+    yield_constr: &mut ConstraintConsumer<P>,
+) {
+    let two = P::Scalar::from_canonical_u64(2);
+    let three = P::Scalar::from_canonical_u64(3);
+    let is_list = lv[COL_IS_SLOT_LIST_LINE];
+    let domain = lv[COL_SLOT_DOMAIN];
+    let is_write = lv[COL_IS_STORAGE_WRITE];
+    let key = |row: &[P; NUM_CPU_COLS], i: usize| row[COL_S_OP0.start + TREE_VALUE_LEN + i];
+    let record = |row: &[P; NUM_CPU_COLS], i: usize| row[COL_S_OP1.start + TREE_VALUE_LEN + i];
+    let carried = |row: &[P; NUM_CPU_COLS], i: usize| row[COL_S_DST.start + TREE_VALUE_LEN + i];
+
+    // slot list lines are ext lines of sstore and sscan apart from storage
+    // lines, their key domain is a link or head one, slots are hashed in 0
+    yield_constr.constraint(is_list * (P::ONES - is_list));
+    yield_constr.constraint((P::ONES - lv[COL_S_SSTORE] - lv[COL_S_SSCAN]) * is_list);
+    yield_constr.constraint((P::ONES - lv[COL_IS_EXT_LINE]) * is_list);
+    yield_constr.constraint(lv[COL_IS_STORAGE_EXT_LINE] * is_list);
+    yield_constr.constraint(lv[COL_IS_STORAGE_EXT_LINE] * domain);
+    yield_constr.constraint(is_list * (domain - two) * (domain - three));
+    // both linear, 1 on a link and a head line, only meaningful on slot list
+    // lines: dst_sel[9] selects r9 on main lines
+    let is_link =
+        |row: &[P; NUM_CPU_COLS]| row[COL_IS_SLOT_LIST_LINE] * three - row[COL_SLOT_DOMAIN];
+    let is_head = |row: &[P; NUM_CPU_COLS]| row[COL_SLOT_DOMAIN] - row[COL_IS_SLOT_LIST_LINE] * two;
+
+    // only sstore writes, its storage line and the list writes
+    yield_constr.constraint((P::ONES - lv[COL_S_SSTORE]) * is_write);
+    yield_constr.constraint(lv[COL_IS_STORAGE_EXT_LINE] * (is_write - lv[COL_S_SSTORE]));
+    yield_constr.constraint((P::ONES - lv[COL_IS_STORAGE_EXT_LINE] - is_list) * is_write);
+
+    let s = lv[COL_S_SSTORE];
+    let listed = lv[COL_SLOT_LISTED];
+    let n_is_write = nv[COL_IS_STORAGE_WRITE];
+    // the storage line comes first, carries the key and the link read follows
+    yield_constr.constraint_transition(
+        s * (P::ONES - lv[COL_IS_EXT_LINE]) * (P::ONES - nv[COL_IS_STORAGE_EXT_LINE]),
+    );
+    let is_slot = s * lv[COL_IS_STORAGE_EXT_LINE];
+    for i in 0..TREE_VALUE_LEN {
+        yield_constr.constraint(is_slot * (carried(lv, i) - key(lv, i)));
+        yield_constr.constraint_transition(
+            s * nv[COL_IS_SLOT_LIST_LINE] * (carried(nv, i) - carried(lv, i)),
+        );
+    }
+    yield_constr.constraint_transition(is_slot * (P::ONES - is_link(nv)));
+    yield_constr.constraint_transition(is_slot * n_is_write);
+
+    // links are of the slot, heads of its prefix
+    for i in 0..TREE_VALUE_LEN {
+        yield_constr.constraint(s * is_link(lv) * (key(lv, i) - carried(lv, i)));
+    }
+    for i in 0..TREE_VALUE_LEN - 1 {
+        yield_constr.constraint(s * is_head(lv) * (key(lv, i) - carried(lv, i)));
+    }
+    yield_constr.constraint(s * is_head(lv) * key(lv, TREE_VALUE_LEN - 1));
+
+    // link read: the slot is listed if the link was written, then the
+    // instruction ends, else the head read follows
+    let is_link_read = s * is_link(lv) * (P::ONES - is_write);
+    yield_constr.constraint(is_link_read * (listed - record(lv, 0)));
+    yield_constr.constraint(s * is_list * listed * (P::ONES - listed));
+    yield_constr.constraint_transition(
+        s * nv[COL_IS_SLOT_LIST_LINE]
+            * (P::ONES - lv[COL_IS_STORAGE_EXT_LINE])
+            * (nv[COL_SLOT_LISTED] - listed),
+    );
+    yield_constr.constraint_transition(is_link_read * listed * nv[COL_IS_EXT_LINE]);
+    yield_constr.constraint_transition(
+        is_link_read * (P::ONES - listed) * (P::ONES - nv[COL_IS_SLOT_LIST_LINE]),
+    );
+    yield_constr.constraint_transition(is_link_read * (P::ONES - listed) * (P::ONES - is_head(nv)));
+    yield_constr.constraint_transition(is_link_read * (P::ONES - listed) * n_is_write);
+
+    // head read: the link is written the record the head holds
+    let is_head_read = s * is_head(lv) * (P::ONES - is_write);
+    yield_constr.constraint_transition(is_head_read * (P::ONES - is_link(nv)));
+    yield_constr.constraint_transition(is_head_read * (P::ONES - n_is_write));
+    yield_constr.constraint_transition(is_head_read * (record(nv, 0) - P::ONES));
+    yield_constr.constraint_transition(is_head_read * (record(nv, 1) - record(lv, 1)));
+    yield_constr.constraint_transition(is_head_read * (record(nv, 2) - record(lv, 2)));
+    yield_constr.constraint_transition(is_head_read * record(nv, 3));
+
+    // link write: the head is written to point at the slot, the last line
+    let is_link_write = s * is_link(lv) * is_write;
+    yield_constr.constraint_transition(is_link_write * (P::ONES - nv[COL_IS_SLOT_LIST_LINE]));
+    yield_constr.constraint_transition(is_link_write * (P::ONES - is_head(nv)));
+    yield_constr.constraint_transition(is_link_write * (P::ONES - n_is_write));
+    yield_constr.constraint_transition(is_link_write * (record(nv, 0) - P::ONES));
+    yield_constr
+        .constraint_transition(is_link_write * (record(nv, 1) - carried(lv, TREE_VALUE_LEN - 1)));
+    yield_constr.constraint_transition(is_link_write * (record(nv, 2) - P::ONES));
+    yield_constr.constraint_transition(is_link_write * record(nv, 3));
+    yield_constr.constraint_transition(s * is_head(lv) * is_write * nv[COL_IS_EXT_LINE]);
 }
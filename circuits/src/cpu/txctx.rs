@@ -0,0 +1,97 @@
+use super::columns::*;
+use crate::stark::constraint_consumer::{ConstraintConsumer, RecursiveConstraintConsumer};
+use plonky2::{
+    field::{extension::Extendable, packed::PackedField, types::Field},
+    hash::hash_types::RichField,
+    iop::ext_target::ExtensionTarget,
+    plonk::circuit_builder::CircuitBuilder,
+};
+
+// `txctx dst sel`: op1 is the selector, dst the context word it selects,
+// chain id for 0 and tx hash word `sel - 1` for 1 to 4. The context is the
+// same on every row, its first row is looked up by the public values.
+//
+// dst is interpolated over the selectors: with L_k(x) = prod_{j != k} (x - j),
+// 24 * dst = sum_k (-1)^k * C(4, k) * L_k(sel) * ctx_k.
+const NUM_SELECTORS: usize = 5;
+const LAGRANGE_WEIGHTS: [i64; NUM_SELECTORS] = [1, -4, 6, -4, 1];
+const LAGRANGE_SCALE: u64 = 24;
+
+fn from_i64<F: Field>(v: i64) -> F {
+    if v < 0 {
+        -F::from_canonical_u64(v.unsigned_abs())
+    } else {
+        F::from_canonical_u64(v as u64)
+    }
+}
+
+pub(crate) fn eval_packed_generic<P: PackedField>(
+    lv: &[P; NUM_CPU_COLS],
+    nv: &[P; NUM_CPU_COLS],
+    yield_constr: &mut ConstraintConsumer<P>,
+) {
+    yield_constr.constraint_first_row(lv[COL_IS_FIRST_ROW] - P::ONES);
+    yield_constr.constraint_transition(nv[COL_IS_FIRST_ROW]);
+    for col in COL_TX_CTX {
+        yield_constr.constraint_transition(nv[col] - lv[col]);
+    }
+
+    let s = lv[COL_S_TXCTX];
+    let sel = lv[COL_OP1];
+    let diffs: [P; NUM_SELECTORS] =
+        std::array::from_fn(|j| sel - P::Scalar::from_canonical_usize(j));
+    yield_constr.constraint(s * diffs.iter().fold(P::ONES, |acc, d| acc * *d));
+
+    let interpolated = (0..NUM_SELECTORS).fold(P::ZEROS, |acc, k| {
+        let basis = (0..NUM_SELECTORS)
+            .filter(|j| *j != k)
+            .fold(P::ONES, |acc, j| acc * diffs[j]);
+        acc + basis * lv[COL_TX_CTX.start + k] * from_i64::<P::Scalar>(LAGRANGE_WEIGHTS[k])
+    });
+    yield_constr.constraint(
+        s * (lv[COL_DST] * P::Scalar::from_canonical_u64(LAGRANGE_SCALE) - interpolated),
+    );
+}
+
+pub(crate) fn eval_ext_circuit<F: RichField + Extendable<D>, const D: usize>(
+    builder: &mut CircuitBuilder<F, D>,
+    lv: &[ExtensionTarget<D>; NUM_CPU_COLS],
+    nv: &[ExtensionTarget<D>; NUM_CPU_COLS],
+    yield_constr: &mut RecursiveConstraintConsumer<F, D>,
+) {
+    let one = builder.one_extension();
+    let cs = builder.sub_extension(lv[COL_IS_FIRST_ROW], one);
+    yield_constr.constraint_first_row(builder, cs);
+    yield_constr.constraint_transition(builder, nv[COL_IS_FIRST_ROW]);
+    for col in COL_TX_CTX {
+        let cs = builder.sub_extension(nv[col], lv[col]);
+        yield_constr.constraint_transition(builder, cs);
+    }
+
+    let s = lv[COL_S_TXCTX];
+    let diffs: [ExtensionTarget<D>; NUM_SELECTORS] = std::array::from_fn(|j| {
+        let j = builder.constant_extension(F::Extension::from_canonical_usize(j));
+        builder.sub_extension(lv[COL_OP1], j)
+    });
+    let in_range = builder.mul_many_extension(diffs);
+    let cs = builder.mul_extension(s, in_range);
+    yield_constr.constraint(builder, cs);
+
+    let mut interpolated = builder.zero_extension();
+    for k in 0..NUM_SELECTORS {
+        let basis = builder.mul_many_extension(
+            (0..NUM_SELECTORS)
+                .filter(|j| *j != k)
+                .map(|j| diffs[j])
+                .collect::<Vec<_>>(),
+        );
+        let weight = builder.constant_extension(from_i64(LAGRANGE_WEIGHTS[k]));
+        let term = builder.mul_many_extension([basis, lv[COL_TX_CTX.start + k], weight]);
+        interpolated = builder.add_extension(interpolated, term);
+    }
+    let scale = builder.constant_extension(F::Extension::from_canonical_u64(LAGRANGE_SCALE));
+    let scaled_dst = builder.mul_extension(lv[COL_DST], scale);
+    let diff = builder.sub_extension(scaled_dst, interpolated);
+    let cs = builder.mul_extension(s, diff);
+    yield_constr.constraint(builder, cs);
+}
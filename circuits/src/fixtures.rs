@@ -0,0 +1,328 @@
+//! Canonical example programs, run end to end: executed, proven under
+//! `StarkConfig::dev_config` and verified. They serve integrators as working
+//! references and anchor end-to-end behavior in tests.
+//!
+//! The trace summary and proof of each fixture are kept as golden files in
+//! `circuits/fixtures/golden`. A change of the executor, the trace generation
+//! or the proof format shows up as a mismatch against them; run the tests with
+//! `OLA_BLESS_FIXTURES=1` to accept it. Missing golden files are written on
+//! the first run.
+
+use std::collections::BTreeMap;
+use std::path::PathBuf;
+
+use assembler::bundle::AsmBundle;
+use assembler::encoder::{encode_asm, encode_asm_bundle};
+use core::crypto::hash::Hasher;
+use core::crypto::ZkHasher;
+use core::merkle_tree::log::{StorageLog, WitnessStorageLog};
+use core::merkle_tree::tree::AccountTree;
+use core::program::binary_program::BinaryProgram;
+use core::program::Program;
+use core::trace::export::trace_tables;
+use core::trace::trace::Trace;
+use core::types::account::Address;
+use core::types::merkle_tree::{encode_addr, tree_key_default};
+use core::types::{Field, GoldilocksField, PrimeField64};
+use core::vm::transaction::init_tx_context_mock;
+use executor::load_tx::init_tape;
+use executor::trace::{gen_storage_hash_table, gen_storage_table};
+use executor::Process;
+use plonky2::plonk::config::Blake3GoldilocksConfig;
+use plonky2::util::timing::TimingTree;
+use serde::{Deserialize, Serialize};
+use sha2::{Digest, Sha256};
+
+use crate::generation::{generate_traces, GenerationInputs};
+use crate::stark::config::StarkConfig;
+use crate::stark::error::ProverError;
+use crate::stark::ola_stark::OlaStark;
+use crate::stark::proof::AllProof;
+use crate::stark::prover::prove_with_traces;
+
+const D: usize = 2;
+type C = Blake3GoldilocksConfig;
+type F = GoldilocksField;
+
+pub type FixtureProof = AllProof<F, C, D>;
+
+#[derive(Debug, Clone, Copy)]
+pub enum FixtureSource {
+    /// Asm bundle json as the compiler emits it, prophets included.
+    Bundle(&'static str),
+    /// Bare asm without prophets.
+    Asm(&'static str),
+}
+
+#[derive(Debug, Clone)]
+pub struct Fixture {
+    pub name: &'static str,
+    pub description: &'static str,
+    pub source: FixtureSource,
+    pub calldata: &'static [u64],
+}
+
+/// Every fixture, in a fixed order.
+pub fn fixtures() -> Vec<Fixture> {
+    vec![
+        Fixture {
+            name: "fibonacci",
+            description: "Computes the 10th fibonacci number in a loop.",
+            source: FixtureSource::Bundle(include_str!(
+                "../../assembler/test_data/asm/fib_asm.json"
+            )),
+            calldata: &[10, 1, 2, 4185064725],
+        },
+        Fixture {
+            name: "erc20_transfer",
+            description: "Mints 100 to a balance slot, moves 30 of it to another \
+                          slot after checking the balance covers it, and asserts \
+                          both balances.",
+            source: FixtureSource::Asm(include_str!("../fixtures/erc20_transfer.asm")),
+            calldata: &[],
+        },
+        Fixture {
+            name: "poseidon_loop",
+            description: "Hashes four words with poseidon four times, each time \
+                          hashing the previous hash.",
+            source: FixtureSource::Asm(include_str!("../fixtures/poseidon_loop.asm")),
+            calldata: &[],
+        },
+        Fixture {
+            name: "transient_lock",
+            description: "Takes and releases a lock in transient storage, \
+                          checking the lock slot reads unset, set and unset \
+                          again.",
+            source: FixtureSource::Asm(include_str!("../fixtures/transient_lock.asm")),
+            calldata: &[],
+        },
+        Fixture {
+            name: "storage_scan",
+            description: "Sets the slots (1,2,3,5), (1,2,4,5) and (1,2,3,0), then \
+                          scans up to three slots from the head of the list of \
+                          the prefix (1,2,3) and asserts the two found, newest \
+                          first.",
+            source: FixtureSource::Asm(include_str!("../fixtures/storage_scan.asm")),
+            calldata: &[],
+        },
+        Fixture {
+            name: "tx_context",
+            description: "Reads the chain id and two words of the tx hash with \
+                          txctx and asserts them against the mock tx context, \
+                          which its calldata runs it under.",
+            source: FixtureSource::Asm(include_str!("../fixtures/tx_context.asm")),
+            calldata: &[0],
+        },
+        Fixture {
+            name: "returned_values",
+            description: "Declares it returns two values and ends with 7 and 9 \
+                          in r0 and r1.",
+            source: FixtureSource::Asm(include_str!("../fixtures/returned_values.asm")),
+            calldata: &[],
+        },
+    ]
+}
+
+pub fn fixture(name: &str) -> Option<Fixture> {
+    fixtures().into_iter().find(|fixture| fixture.name == name)
+}
+
+/// Where the golden files of the fixtures are kept.
+pub fn golden_dir() -> PathBuf {
+    PathBuf::from(env!("CARGO_MANIFEST_DIR")).join("fixtures/golden")
+}
+
+/// What a fixture's execution is checked against: the rows of every trace
+/// table, a digest of their content and the returned values.
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+pub struct TraceSummary {
+    pub rows: BTreeMap<String, usize>,
+    /// Sha256 of every table's name, column names and values, hex encoded.
+    pub digest: String,
+    pub ret: Vec<u64>,
+}
+
+impl TraceSummary {
+    pub fn new(trace: &Trace) -> Self {
+        let tables = trace_tables(trace).expect("trace rows are serializable");
+        let mut hasher = Sha256::new();
+        let mut rows = BTreeMap::new();
+        for table in &tables {
+            rows.insert(table.name.clone(), table.num_rows());
+            hasher.update(table.name.as_bytes());
+            for (name, values) in &table.columns {
+                hasher.update(name.as_bytes());
+                for value in values {
+                    hasher.update(value.to_le_bytes());
+                }
+            }
+        }
+        Self {
+            rows,
+            digest: hasher
+                .finalize()
+                .iter()
+                .map(|byte| format!("{:02x}", byte))
+                .collect(),
+            ret: trace.ret.iter().map(|v| v.to_canonical_u64()).collect(),
+        }
+    }
+}
+
+impl Fixture {
+    pub fn binary(&self) -> Result<BinaryProgram, String> {
+        match self.source {
+            FixtureSource::Bundle(json) => {
+                encode_asm_bundle(AsmBundle::from_json(json).map_err(|e| e.to_string())?)
+            }
+            FixtureSource::Asm(asm) => encode_asm(asm),
+        }
+    }
+
+    /// Runs the fixture as the code of a deployed contract and fills in the
+    /// storage tables, so the program is ready to be proven.
+    pub fn execute(&self) -> Result<Program, String> {
+        let binary = self.binary()?;
+        let code: Vec<_> = binary
+            .bytecode
+            .split('\n')
+            .map(|word| {
+                u64::from_str_radix(word.trim_start_matches("0x"), 16)
+                    .map(GoldilocksField::from_canonical_u64)
+                    .map_err(|e| e.to_string())
+            })
+            .collect::<Result<_, _>>()?;
+        let code_hash = ZkHasher::default().hash_bytes(&code);
+        let mut program = Program {
+            instructions: binary.bytecode.split('\n').map(|e| e.to_string()).collect(),
+            return_arity: binary.return_arity,
+            prophets: binary
+                .prophets
+                .into_iter()
+                .map(|p| (p.host as u64, p))
+                .collect(),
+            ..Default::default()
+        };
+
+        let addr = |start: u64| -> Address {
+            [0, 1, 2, 3].map(|i| GoldilocksField::from_canonical_u64(start + i))
+        };
+        let (callee, callee_exe_addr, caller) = (addr(9), addr(13), addr(17));
+        let mut process = Process::new();
+        if !self.calldata.is_empty() {
+            init_tape(
+                &mut process,
+                self.calldata
+                    .iter()
+                    .map(|v| GoldilocksField::from_canonical_u64(*v))
+                    .collect(),
+                caller,
+                callee,
+                callee_exe_addr,
+                &init_tx_context_mock(),
+            );
+        }
+        process.addr_code = callee_exe_addr;
+        process.addr_storage = callee;
+        program
+            .trace
+            .addr_program_hash
+            .insert(encode_addr(&callee_exe_addr), code);
+
+        let mut tree = AccountTree::new_test();
+        tree.process_block(vec![WitnessStorageLog {
+            storage_log: StorageLog::new_write_log(callee_exe_addr, code_hash),
+            previous_value: tree_key_default(),
+        }]);
+        tree.save().map_err(|e| e.to_string())?;
+        let start = tree.root_hash();
+        process.program_log.push(WitnessStorageLog {
+            storage_log: StorageLog::new_read_log(callee_exe_addr, code_hash),
+            previous_value: tree_key_default(),
+        });
+
+        process
+            .execute(&mut program, &mut tree)
+            .map_err(|e| e.to_string())?;
+        let hash_roots = gen_storage_hash_table(&mut process, &mut program, &mut tree);
+        gen_storage_table(&mut process, &mut program, hash_roots).map_err(|e| e.to_string())?;
+        process
+            .finalize(&mut tree, true)
+            .map_err(|e| e.to_string())?;
+        program.trace.start_end_roots = (start, tree.root_hash());
+        Ok(program)
+    }
+}
+
+/// Proves an executed fixture under `config`.
+pub fn prove_fixture(program: Program, config: &StarkConfig) -> Result<FixtureProof, ProverError> {
+    let mut ola_stark = OlaStark::<F, D>::default();
+    let (traces, public_values) =
+        generate_traces(program, &mut ola_stark, GenerationInputs::default());
+    prove_with_traces::<F, C, D>(
+        &ola_stark,
+        config,
+        traces,
+        public_values,
+        &mut TimingTree::default(),
+    )
+}
+
+#[cfg(test)]
+mod tests {
+    use std::fs;
+
+    use super::{fixtures, golden_dir, prove_fixture, FixtureProof, TraceSummary};
+    use crate::stark::config::StarkConfig;
+    use crate::stark::ola_stark::OlaStark;
+    use crate::stark::serialization::Buffer;
+    use crate::stark::verifier::verify_proof;
+
+    fn bless() -> bool {
+        std::env::var_os("OLA_BLESS_FIXTURES").is_some()
+    }
+
+    #[test]
+    fn test_fixtures() {
+        let config = StarkConfig::dev_config();
+        fs::create_dir_all(golden_dir()).unwrap();
+        for fixture in fixtures() {
+            let program = fixture.execute().unwrap();
+            let summary = TraceSummary::new(&program.trace);
+            let summary_path = golden_dir().join(format!("{}.trace.json", fixture.name));
+            if summary_path.exists() && !bless() {
+                let golden: TraceSummary =
+                    serde_json::from_str(&fs::read_to_string(&summary_path).unwrap()).unwrap();
+                assert_eq!(summary, golden, "trace of fixture {}", fixture.name);
+            } else {
+                fs::write(
+                    &summary_path,
+                    serde_json::to_string_pretty(&summary).unwrap(),
+                )
+                .unwrap();
+            }
+
+            let proof = prove_fixture(program, &config).unwrap();
+            verify_proof(OlaStark::default(), proof.clone(), &config).unwrap();
+
+            let proof_path = golden_dir().join(format!("{}.proof", fixture.name));
+            if proof_path.exists() && !bless() {
+                // the golden proof still verifies and attests the same values
+                let golden: FixtureProof = Buffer::new(fs::read(&proof_path).unwrap())
+                    .read_all_proof()
+                    .unwrap();
+                assert_eq!(
+                    serde_json::to_value(&golden.public_values).unwrap(),
+                    serde_json::to_value(&proof.public_values).unwrap(),
+                    "public values of fixture {}",
+                    fixture.name
+                );
+                verify_proof(OlaStark::default(), golden, &config).unwrap();
+            } else {
+                let mut buffer = Buffer::new(Vec::new());
+                buffer.write_all_proof(&proof).unwrap();
+                fs::write(&proof_path, buffer.bytes()).unwrap();
+            }
+        }
+    }
+}
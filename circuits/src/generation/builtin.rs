@@ -1,5 +1,5 @@
 use core::program::instruction::Opcode;
-use core::trace::trace::{BitwiseCombinedRow, CmpRow, RangeCheckRow};
+use core::trace::trace::{merge_rangecheck_rows, BitwiseCombinedRow, CmpRow, RangeCheckRow};
 use plonky2::field::types::PrimeField64;
 use plonky2::hash::hash_types::RichField;
 use plonky2::iop::challenger::Challenger;
@@ -249,6 +249,7 @@ pub fn generate_cmp_trace<F: RichField>(cells: &[CmpRow]) -> [Vec<F>; cmp::COL_N
 pub fn generate_rc_trace<F: RichField>(
     cells: &[RangeCheckRow],
 ) -> [Vec<F>; rangecheck::COL_NUM_RC] {
+    let cells = merge_rangecheck_rows(cells);
     let trace_len = cells.len();
     let max_trace_len = trace_len.max(rangecheck::RANGE_CHECK_U16_SIZE);
     let ext_trace_len = if !max_trace_len.is_power_of_two() || max_trace_len < 2 {
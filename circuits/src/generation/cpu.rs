@@ -1,21 +1,21 @@
 use core::{
     program::{CTX_REGISTER_NUM, REGISTER_NUM},
-    trace::trace::Step,
+    trace::trace::{Step, StopState},
+    types::{merkle_tree::TreeValue, GoldilocksField, PrimeField64},
     vm::opcodes::OlaOpcode,
 };
+use executor::{SLOT_DOMAIN_SEL, SLOT_LISTED_SEL};
 use std::collections::HashMap;
 
 use crate::cpu::columns::{self as cpu, COL_IS_ENTRY_SC};
+use crate::cpu::gas::step_gas;
 use plonky2::hash::hash_types::RichField;
 
 pub fn generate_cpu_trace<F: RichField>(steps: &Vec<Step>) -> [Vec<F>; cpu::NUM_CPU_COLS] {
     let trace_len = steps.len();
 
-    let ext_trace_len = if !trace_len.is_power_of_two() {
-        trace_len.next_power_of_two()
-    } else {
-        trace_len
-    };
+    // at least one padding row, the end state
+    let ext_trace_len = (trace_len + 1).next_power_of_two();
     let mut trace: Vec<Vec<F>> = vec![vec![F::ZERO; ext_trace_len]; cpu::NUM_CPU_COLS];
     let mut opcode_to_selector = HashMap::new();
     opcode_to_selector.insert(
@@ -58,6 +58,11 @@ pub fn generate_cpu_trace<F: RichField>(steps: &Vec<Step>) -> [Vec<F>; cpu::NUM_
     opcode_to_selector.insert(OlaOpcode::TLOAD.binary_bit_mask(), cpu::COL_S_TLOAD);
     opcode_to_selector.insert(OlaOpcode::TSTORE.binary_bit_mask(), cpu::COL_S_TSTORE);
     opcode_to_selector.insert(OlaOpcode::SCCALL.binary_bit_mask(), cpu::COL_S_CALL_SC);
+    opcode_to_selector.insert(OlaOpcode::CMOV.binary_bit_mask(), cpu::COL_S_CMOV);
+    opcode_to_selector.insert(OlaOpcode::TLOADT.binary_bit_mask(), cpu::COL_S_TLOADT);
+    opcode_to_selector.insert(OlaOpcode::TSTORET.binary_bit_mask(), cpu::COL_S_TSTORET);
+    opcode_to_selector.insert(OlaOpcode::SSCAN.binary_bit_mask(), cpu::COL_S_SSCAN);
+    opcode_to_selector.insert(OlaOpcode::TXCTX.binary_bit_mask(), cpu::COL_S_TXCTX);
 
     for (i, s) in steps.iter().enumerate() {
         // env related columns.
@@ -105,9 +110,14 @@ pub fn generate_cpu_trace<F: RichField>(steps: &Vec<Step>) -> [Vec<F>; cpu::NUM_
         }
 
         // Selectors of opcode related columns.
+        // a row without a selector breaks the one selector constraint, fail
+        // here rather than with an unprovable trace
         match opcode_to_selector.get(&s.opcode.0) {
             Some(selector) => trace[selector.clone()][i] = F::from_canonical_u64(1),
-            None => (),
+            None => panic!(
+                "opcode {:#x} at clk {} pc {} has no cpu selector",
+                s.opcode.0, s.clk, s.pc
+            ),
         }
 
         trace[COL_IS_ENTRY_SC][i] = if trace[cpu::COL_ENV_IDX][i].is_zero() {
@@ -117,7 +127,6 @@ pub fn generate_cpu_trace<F: RichField>(steps: &Vec<Step>) -> [Vec<F>; cpu::NUM_
         };
 
         let ext_length = if s.opcode.0 == OlaOpcode::SLOAD.binary_bit_mask()
-            || s.opcode.0 == OlaOpcode::SSTORE.binary_bit_mask()
             || s.opcode.0 == OlaOpcode::SCCALL.binary_bit_mask()
             || (s.opcode.0 == OlaOpcode::END.binary_bit_mask()
                 && !trace[cpu::COL_ENV_IDX][i].is_zero())
@@ -127,6 +136,13 @@ pub fn generate_cpu_trace<F: RichField>(steps: &Vec<Step>) -> [Vec<F>; cpu::NUM_
             s.register_selector.op0.0 * s.register_selector.op1.0 + (1 - s.register_selector.op0.0)
         } else if s.opcode.0 == OlaOpcode::TSTORE.binary_bit_mask() {
             s.register_selector.op1.0
+        } else if s.opcode.0 == OlaOpcode::SSTORE.binary_bit_mask() {
+            // the write and the link read, then listing the slot unless the
+            // link read says it is listed
+            5 - 3 * s.register_selector.dst_reg_sel[SLOT_LISTED_SEL].0
+        } else if s.opcode.0 == OlaOpcode::SSCAN.binary_bit_mask() {
+            // the head line and the first record, then two lines per slot read
+            2 + 2 * s.register_selector.dst.0
         } else {
             0
         };
@@ -150,15 +166,30 @@ pub fn generate_cpu_trace<F: RichField>(steps: &Vec<Step>) -> [Vec<F>; cpu::NUM_
             } else {
                 F::ZERO
             };
+        let is_sscan = s.opcode.0 == OlaOpcode::SSCAN.binary_bit_mask();
+        let is_sstore = s.opcode.0 == OlaOpcode::SSTORE.binary_bit_mask();
+        let is_slot_list_line = (is_sstore || is_sscan)
+            && s.is_ext_line.0 == 1
+            && s.register_selector.dst_reg_sel[SLOT_DOMAIN_SEL].0 != 0;
+        trace[cpu::COL_IS_SLOT_LIST_LINE][i] = F::from_bool(is_slot_list_line);
         trace[cpu::COL_IS_STORAGE_EXT_LINE][i] = if (s.opcode.0
             == OlaOpcode::SLOAD.binary_bit_mask()
-            || s.opcode.0 == OlaOpcode::SSTORE.binary_bit_mask())
+            || (is_sstore && s.ext_cnt.0 == 1)
+            || (is_sscan && s.ext_cnt.0 > 1 && !is_slot_list_line))
             && s.is_ext_line.0 == 1
         {
             F::ONE
         } else {
             F::ZERO
         };
+        // the slot write, then the link and head writes listing it
+        trace[cpu::COL_IS_STORAGE_WRITE][i] =
+            F::from_bool(is_sstore && s.is_ext_line.0 == 1 && [1, 4, 5].contains(&s.ext_cnt.0));
+        trace[cpu::COL_IS_SSCAN_HEAD_LINE][i] = if is_sscan && s.ext_cnt.0 == 1 {
+            F::ONE
+        } else {
+            F::ZERO
+        };
         trace[cpu::COL_FILTER_SCCALL_END][i] =
             if s.opcode.0 == OlaOpcode::END.binary_bit_mask() && s.is_ext_line.0 == 1 {
                 F::ONE
@@ -177,12 +208,12 @@ pub fn generate_cpu_trace<F: RichField>(steps: &Vec<Step>) -> [Vec<F>; cpu::NUM_
             F::ZERO
         };
     }
-    // fill in padding.
-    let inst_end = if trace_len == 0 {
-        F::from_canonical_u64(1048576)
-    } else {
-        trace[cpu::COL_INST][trace_len - 1]
-    };
+    trace[cpu::COL_IS_FIRST_ROW][0] = F::ONE;
+    for (i, step) in trace[cpu::COL_TX_STEP].iter_mut().enumerate() {
+        *step = F::from_canonical_usize(i);
+    }
+    // fill in padding, END rows also after a chunk which stopped elsewhere.
+    let inst_end = F::from_canonical_u64(OlaOpcode::END.binary_bit_mask());
     let last_tx_id = if trace_len == 0 {
         F::ZERO
     } else {
@@ -194,6 +225,7 @@ pub fn generate_cpu_trace<F: RichField>(steps: &Vec<Step>) -> [Vec<F>; cpu::NUM_
         trace[cpu::COL_IDX_STORAGE][trace_len - 1]
     };
 
+    trace[cpu::COL_IS_END_STATE][trace_len] = F::ONE;
     if trace_len != ext_trace_len {
         trace[cpu::COL_TX_IDX][trace_len..].fill(last_tx_id);
         trace[cpu::COL_INST][trace_len..].fill(inst_end);
@@ -206,6 +238,10 @@ pub fn generate_cpu_trace<F: RichField>(steps: &Vec<Step>) -> [Vec<F>; cpu::NUM_
         trace[cpu::COL_IS_NEXT_LINE_SAME_TX][trace_len..].fill(F::ZERO);
         trace[cpu::COL_IS_PADDING][trace_len..].fill(F::ONE);
     }
+    for i in 1..ext_trace_len {
+        let row: [F; cpu::NUM_CPU_COLS] = std::array::from_fn(|col| trace[col][i - 1]);
+        trace[cpu::COL_GAS][i] = trace[cpu::COL_GAS][i - 1] + step_gas(&row);
+    }
 
     let trace_row_vecs = trace.try_into().unwrap_or_else(|v: Vec<Vec<F>>| {
         panic!(
@@ -216,3 +252,60 @@ pub fn generate_cpu_trace<F: RichField>(steps: &Vec<Step>) -> [Vec<F>; cpu::NUM_
     });
     trace_row_vecs
 }
+
+/// Fill the tx context `txctx` reads into every row of the cpu trace.
+pub fn fill_tx_context<F: RichField>(
+    trace: &mut [Vec<F>; cpu::NUM_CPU_COLS],
+    chain_id: GoldilocksField,
+    tx_hash: TreeValue,
+) {
+    for (col, value) in cpu::COL_TX_CTX.zip(std::iter::once(chain_id).chain(tx_hash)) {
+        trace[col].fill(F::from_canonical_u64(value.to_canonical_u64()));
+    }
+}
+
+/// Fill the state a chunk of the execution stopped with into the end state
+/// row, the first padding row. It continues the last row like the step at
+/// the stop would, so the transition constraints of that row hold.
+pub fn fill_end_state<F: RichField>(
+    trace: &mut [Vec<F>; cpu::NUM_CPU_COLS],
+    stop_state: Option<StopState>,
+) {
+    let end = match (trace[cpu::COL_IS_END_STATE].iter()).position(|f| f.is_one()) {
+        Some(end) if end > 0 => end,
+        _ => return,
+    };
+    let stop_state = match stop_state {
+        Some(stop_state) => stop_state,
+        None => return,
+    };
+    let last = end - 1;
+    for col in [cpu::COL_ENV_IDX, cpu::COL_CALL_SC_CNT]
+        .into_iter()
+        .chain(cpu::COL_ADDR_STORAGE_RANGE)
+        .chain(cpu::COL_ADDR_CODE_RANGE)
+    {
+        trace[col][end] = trace[col][last];
+    }
+    trace[cpu::COL_CLK][end] = trace[cpu::COL_CLK][last] + F::ONE;
+    trace[cpu::COL_PC][end] = F::from_canonical_u64(stop_state.pc);
+    for (col, reg) in cpu::COL_REGS.zip(stop_state.registers) {
+        trace[col][end] = F::from_canonical_u64(reg.to_canonical_u64());
+    }
+    trace[cpu::COL_TP][end] = F::from_canonical_u64(stop_state.tp.to_canonical_u64());
+}
+
+/// Mark the first `arity` registers of the entry END row as returned.
+pub fn fill_returned_values<F: RichField>(trace: &mut [Vec<F>; cpu::NUM_CPU_COLS], arity: usize) {
+    let entry_end = (0..trace[cpu::COL_S_END].len()).find(|&i| {
+        trace[cpu::COL_S_END][i].is_one()
+            && trace[cpu::COL_IS_EXT_LINE][i].is_zero()
+            && trace[COL_IS_ENTRY_SC][i].is_one()
+            && trace[cpu::COL_IS_PADDING][i].is_zero()
+    });
+    if let Some(row) = entry_end {
+        for col in cpu::COL_FILTER_RETURNED.take(arity) {
+            trace[col][row] = F::ONE;
+        }
+    }
+}
@@ -236,7 +236,7 @@ fn print_cpu_mem_ctl_info() {
         let (storage_addr_data, storage_addr_title) = get_related_data_with_title(
             &cpu_rows,
             |row: &[GoldilocksField; cpu::columns::NUM_CPU_COLS]| {
-                row[COL_IS_STORAGE_EXT_LINE].is_one()
+                (row[COL_IS_STORAGE_EXT_LINE] + row[COL_IS_SSCAN_HEAD_LINE]).is_one()
             },
             get_cpu_col_name_map(),
             [
@@ -274,8 +274,14 @@ fn print_cpu_mem_ctl_info() {
     (0..4).for_each(|i| {
         let (storage_value_data, storage_value_title) = get_related_data_with_title(
             &cpu_rows,
-            |row: &[GoldilocksField; cpu::columns::NUM_CPU_COLS]| {
-                row[COL_IS_STORAGE_EXT_LINE].is_one()
+            if i == 0 {
+                |row: &[GoldilocksField; cpu::columns::NUM_CPU_COLS]| {
+                    (row[COL_IS_STORAGE_EXT_LINE] + row[COL_IS_SSCAN_HEAD_LINE]).is_one()
+                }
+            } else {
+                |row: &[GoldilocksField; cpu::columns::NUM_CPU_COLS]| {
+                    row[COL_IS_STORAGE_EXT_LINE].is_one()
+                }
             },
             get_cpu_col_name_map(),
             [
@@ -321,7 +327,8 @@ fn print_cpu_mem_ctl_info() {
                 + row[COL_MEM_S_TSTORE]
                 + row[COL_MEM_S_SCCALL]
                 + row[COL_MEM_S_SSTORE]
-                + row[COL_MEM_S_SLOAD])
+                + row[COL_MEM_S_SLOAD]
+                + row[COL_MEM_S_SSCAN])
                 .is_one()
         },
         get_memory_col_name_map(),
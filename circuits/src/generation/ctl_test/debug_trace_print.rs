@@ -236,7 +236,7 @@ pub fn get_exec_trace(
         storage_log: StorageLog::new_write_log(callee_exe_addr, code_hash),
         previous_value: tree_key_default(),
     }]);
-    let _ = db.save();
+    db.commit().unwrap();
 
     let start = db.root_hash();
 
@@ -255,6 +255,7 @@ pub fn get_exec_trace(
     }
     let hash_roots = gen_storage_hash_table(&mut process, &mut program, &mut db);
     gen_storage_table(&mut process, &mut program, hash_roots).unwrap();
+    process.finalize(&mut db, true).unwrap();
     program.trace.start_end_roots = (start, db.root_hash());
     return program.trace;
 }
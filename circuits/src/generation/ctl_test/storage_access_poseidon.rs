@@ -20,6 +20,7 @@ fn print_storage_access_poseidon_ctl_info() {
     let st_cols = generate_storage_access_trace::<GoldilocksField>(
         &trace.builtin_storage_hash,
         &trace.builtin_program_hash,
+        trace.start_end_roots.0,
     );
     let st_rows = get_rows_vec_from_trace(st_cols);
     let poseidon_cols = generate_poseidon_trace::<GoldilocksField>(&trace.builtin_poseidon);
@@ -5,6 +5,21 @@ use plonky2::{field::types::PrimeField64, hash::hash_types::RichField};
 
 use crate::memory::columns::{self as memory, COL_MEM_S_PROPHET};
 
+/// Whether `cell` was preloaded by `Process::load_memory_image`, an mstore
+/// at clk 0 the cpu does not look up.
+pub(crate) fn is_image_cell(cell: &MemoryTraceCell) -> bool {
+    cell.op.0 == OlaOpcode::MSTORE.binary_bit_mask()
+        && cell.filter_looked_for_main.to_canonical_u64() == 0
+}
+
+/// Whether `cell` was mapped read only by `Process::map_code`, a write once
+/// prophet write to the stack at clk 0.
+pub(crate) fn is_code_cell(cell: &MemoryTraceCell) -> bool {
+    cell.op.0 == 0
+        && cell.is_rw.to_canonical_u64() == 0
+        && cell.region_prophet.to_canonical_u64() == 0
+}
+
 pub fn generate_memory_trace<F: RichField>(
     cells: &[MemoryTraceCell],
 ) -> [Vec<F>; memory::NUM_MEM_COLS] {
@@ -45,6 +60,7 @@ pub fn generate_memory_trace<F: RichField>(
         memory::COL_MEM_S_SSTORE,
     );
     opcode_to_selector.insert(OlaOpcode::SLOAD.binary_bit_mask(), memory::COL_MEM_S_SLOAD);
+    opcode_to_selector.insert(OlaOpcode::SSCAN.binary_bit_mask(), memory::COL_MEM_S_SSCAN);
     opcode_to_selector.insert(0, memory::COL_MEM_S_PROPHET);
 
     let mut trace: Vec<Vec<F>> = vec![vec![F::ZERO; num_padded_rows]; memory::NUM_MEM_COLS];
@@ -56,6 +72,8 @@ pub fn generate_memory_trace<F: RichField>(
         trace[memory::COL_MEM_CLK][i] = F::from_canonical_u64(c.clk.to_canonical_u64());
         trace[memory::COL_MEM_OP][i] = F::from_canonical_u64(c.op.to_canonical_u64());
         match opcode_to_selector.get(&c.op.0) {
+            Some(_) if is_image_cell(c) => trace[memory::COL_MEM_S_IMAGE][i] = F::ONE,
+            Some(_) if is_code_cell(c) => trace[memory::COL_MEM_S_CODE][i] = F::ONE,
             Some(selector) => trace[selector.clone()][i] = F::from_canonical_u64(1),
             None => (),
         }
@@ -97,7 +115,8 @@ pub fn generate_memory_trace<F: RichField>(
         let p = F::ZERO;
         let span = F::from_canonical_u64(2_u64.pow(32).sub(1));
         let addr = p - span;
-        // Trace at least has 2 columns.
+        // Trace at least has 2 columns, padded with prophet writes.
+        trace[COL_MEM_S_PROPHET][0] = F::ONE;
         trace[memory::COL_MEM_ADDR][0] = addr;
         trace[memory::COL_MEM_IS_WRITE][0] = F::ONE;
         trace[memory::COL_MEM_DIFF_ADDR_COND][0] = p - addr;
@@ -110,12 +129,13 @@ pub fn generate_memory_trace<F: RichField>(
     // Pad trace to power of two.
     if num_padded_rows != num_filled_row_len {
         let p = F::from_noncanonical_u64(F::ORDER);
-        let mut addr: F = if trace[memory::COL_MEM_IS_RW][num_filled_row_len - 1] == F::ONE {
-            let span = F::from_canonical_u64(2_u64.pow(32).sub(1));
-            p - span
-        } else {
-            trace[memory::COL_MEM_ADDR][num_filled_row_len - 1] + F::ONE
-        };
+        let mut addr: F =
+            if trace[memory::COL_MEM_REGION_PROPHET][num_filled_row_len - 1] == F::ZERO {
+                let span = F::from_canonical_u64(2_u64.pow(32).sub(1));
+                p - span
+            } else {
+                trace[memory::COL_MEM_ADDR][num_filled_row_len - 1] + F::ONE
+            };
         let tx_idx = trace[memory::COL_MEM_TX_IDX][num_filled_row_len - 1];
         let env_idx = trace[memory::COL_MEM_ENV_IDX][num_filled_row_len - 1];
 
@@ -145,6 +165,14 @@ pub fn generate_memory_trace<F: RichField>(
         }
     }
 
+    trace[memory::COL_MEM_IS_PADDING][cells.len()..].fill(F::ONE);
+    trace[memory::COL_MEM_IS_FIRST_ROW][0] = F::ONE;
+    for i in 0..num_padded_rows - 1 {
+        let next_diff_addr = trace[memory::COL_MEM_ADDR][i + 1] - trace[memory::COL_MEM_ADDR][i];
+        trace[memory::COL_MEM_NEXT_DIFF_ADDR_INV][i] =
+            next_diff_addr.try_inverse().unwrap_or(F::ZERO);
+    }
+
     trace.try_into().unwrap_or_else(|v: Vec<Vec<F>>| {
         panic!(
             "Expected a Vec of length {} but it was {}",
@@ -153,3 +181,30 @@ pub fn generate_memory_trace<F: RichField>(
         )
     })
 }
+
+/// `(addr, value)` of the last cell of every address, the memory `cells` end
+/// with, as canonical u64s.
+pub(crate) fn end_memory_image(cells: &[MemoryTraceCell]) -> Vec<[u64; 2]> {
+    cells
+        .iter()
+        .enumerate()
+        .filter(|(i, cell)| cells.get(i + 1).map_or(true, |next| next.addr != cell.addr))
+        .map(|(_, cell)| [cell.addr.to_canonical_u64(), cell.value.to_canonical_u64()])
+        .collect()
+}
+
+/// Export the memory the trace ends with, the rows of `end_memory_image`
+/// become final, see `COL_MEM_EXPORT`.
+pub fn fill_end_memory<F: RichField>(trace: &mut [Vec<F>; memory::NUM_MEM_COLS]) {
+    trace[memory::COL_MEM_EXPORT].fill(F::ONE);
+    let rows = trace[memory::COL_MEM_ADDR].len();
+    for i in 0..rows {
+        let is_last_cell = trace[memory::COL_MEM_IS_PADDING][i].is_zero()
+            && (i + 1 == rows
+                || trace[memory::COL_MEM_IS_PADDING][i + 1].is_one()
+                || trace[memory::COL_MEM_ADDR][i + 1] != trace[memory::COL_MEM_ADDR][i]);
+        if is_last_cell {
+            trace[memory::COL_MEM_S_FINAL][i] = F::ONE;
+        }
+    }
+}
@@ -1,18 +1,24 @@
 //use std::collections::HashMap;
 
-use core::program::Program;
-use core::types::merkle_tree::decode_addr;
-use std::collections::HashMap;
+use core::crypto::poseidon_trace::calculate_arbitrary_poseidon_batch;
+use core::program::{Program, REGISTER_NUM};
+use core::trace::trace::Trace;
+use core::types::merkle_tree::{decode_addr, tree_value_to_h256};
+use core::types::{Field, GoldilocksField};
+use std::collections::{BTreeMap, HashMap};
 
-use std::sync::mpsc::channel;
+use std::sync::mpsc::{channel, Receiver};
 use std::thread;
 
 use eth_trie_utils::partial_trie::HashedPartialTrie;
 use ethereum_types::{Address, H256};
+use executor::gas::steps_gas;
 
 //use eth_trie_utils::partial_trie::PartialTrie;
+use log::info;
 use plonky2::field::extension::Extendable;
 use plonky2::field::polynomial::PolynomialValues;
+use plonky2::field::types::PrimeField64;
 use plonky2::hash::hash_types::RichField;
 use serde::{Deserialize, Serialize};
 
@@ -21,24 +27,30 @@ use crate::stark::proof::{BlockMetadata, PublicValues, TrieRoots};
 use crate::stark::util::trace_to_poly_values;
 
 use self::builtin::{generate_bitwise_trace, generate_cmp_trace, generate_rc_trace};
-use self::cpu::generate_cpu_trace;
-use self::memory::generate_memory_trace;
+use self::cpu::{fill_end_state, fill_returned_values, fill_tx_context, generate_cpu_trace};
+use self::memory::{
+    end_memory_image, fill_end_memory, generate_memory_trace, is_code_cell, is_image_cell,
+};
+use self::padding::padding_report;
 use self::poseidon::generate_poseidon_trace;
 use self::poseidon_chunk::generate_poseidon_chunk_trace;
 use self::sccall::generate_sccall_trace;
 use self::storage::generate_storage_access_trace;
 use self::tape::generate_tape_trace;
+use self::transient::{fill_transient_steps, generate_transient_trace};
 
 pub mod builtin;
 pub mod cpu;
 mod ctl_test;
 pub mod memory;
+pub mod padding;
 pub mod poseidon;
 pub mod poseidon_chunk;
 pub mod prog;
 pub mod sccall;
 pub mod storage;
 pub mod tape;
+pub mod transient;
 
 #[derive(Clone, Debug, Deserialize, Serialize, Default)]
 /// Inputs needed for trace generation.
@@ -75,106 +87,274 @@ pub struct TrieInputs {
 }
 
 pub fn generate_traces<F: RichField + Extendable<D>, const D: usize>(
+    program: Program,
+    ola_stark: &mut OlaStark<F, D>,
+    inputs: GenerationInputs,
+) -> ([Vec<PolynomialValues<F>>; NUM_TABLES], PublicValues) {
+    build_traces(program, ola_stark, inputs, true)
+}
+
+/// Same as `generate_traces`, but builds every table on the calling thread.
+pub fn generate_traces_serial<F: RichField + Extendable<D>, const D: usize>(
+    program: Program,
+    ola_stark: &mut OlaStark<F, D>,
+    inputs: GenerationInputs,
+) -> ([Vec<PolynomialValues<F>>; NUM_TABLES], PublicValues) {
+    build_traces(program, ola_stark, inputs, false)
+}
+
+/// Runs `execute` twice and panics unless both executions produced the same
+/// rows, then builds the tables of one serially and of the other in parallel
+/// and panics unless both agree bit for bit. This catches executors and
+/// generation that depend on thread scheduling or hash map iteration order,
+/// e.g. of the storage tree, before anything gets proven.
+pub fn generate_traces_checked<F, E, const D: usize>(
+    execute: E,
+    ola_stark: &mut OlaStark<F, D>,
+    inputs: GenerationInputs,
+) -> ([Vec<PolynomialValues<F>>; NUM_TABLES], PublicValues)
+where
+    F: RichField + Extendable<D>,
+    E: Fn() -> Program,
+{
+    let first = execute();
+    let second = execute();
+    assert_same_execution(&first.trace, &second.trace);
+    generate_traces_both_modes(first, second, ola_stark, inputs)
+}
+
+/// Builds the tables of `serial` serially and of `parallel` in parallel, two
+/// runs of the same execution, and panics unless both agree bit for bit.
+pub(crate) fn generate_traces_both_modes<F: RichField + Extendable<D>, const D: usize>(
+    serial: Program,
+    parallel: Program,
+    ola_stark: &mut OlaStark<F, D>,
+    inputs: GenerationInputs,
+) -> ([Vec<PolynomialValues<F>>; NUM_TABLES], PublicValues) {
+    // the compress challenges are set once, on the stark the parallel
+    // tables are proven with
+    let (serial_traces, serial_public_values) =
+        build_traces(serial, &mut ola_stark.clone(), inputs.clone(), false);
+    let (traces, public_values) = build_traces(parallel, ola_stark, inputs, true);
+    for (table, (serial, parallel)) in serial_traces.iter().zip(traces.iter()).enumerate() {
+        assert!(
+            serial == parallel,
+            "table {} differs between serial and parallel trace generation",
+            table
+        );
+    }
+    assert_eq!(
+        serde_json::to_string(&serial_public_values).unwrap(),
+        serde_json::to_string(&public_values).unwrap(),
+        "public values differ between serial and parallel trace generation"
+    );
+    (traces, public_values)
+}
+
+/// Panics unless two executions produced the same rows, roots and
+/// commitments. The instruction maps are only kept for debugging and are
+/// skipped, the program codes are compared by address.
+fn assert_same_execution(a: &Trace, b: &Trace) {
+    fn json<T: Serialize>(value: &T) -> String {
+        serde_json::to_string(value).unwrap()
+    }
+    let outputs = |trace: &Trace| {
+        let program_codes: BTreeMap<_, _> = trace.addr_program_hash.iter().collect();
+        [
+            ("exec", json(&trace.exec)),
+            ("memory", json(&trace.memory)),
+            ("rangecheck", json(&trace.builtin_rangecheck)),
+            ("bitwise", json(&trace.builtin_bitwise_combined)),
+            ("cmp", json(&trace.builtin_cmp)),
+            ("poseidon", json(&trace.builtin_poseidon)),
+            ("poseidon chunk", json(&trace.builtin_poseidon_chunk)),
+            ("storage", json(&trace.builtin_storage)),
+            ("storage hash", json(&trace.builtin_storage_hash)),
+            ("program hash", json(&trace.builtin_program_hash)),
+            ("program codes", json(&program_codes)),
+            ("tape", json(&trace.tape)),
+            ("transient", json(&trace.transient)),
+            ("sccall", json(&trace.sc_call)),
+            ("ret", json(&trace.ret)),
+            ("returned values", json(&trace.returned_values)),
+            ("roots", json(&trace.start_end_roots)),
+            ("prophet commitment", json(&trace.prophet_commitment)),
+            ("gas used", json(&trace.gas_used)),
+        ]
+    };
+    for ((output, a), (_, b)) in outputs(a).into_iter().zip(outputs(b)) {
+        assert!(a == b, "{} differs between two executions", output);
+    }
+}
+
+/// Builds a table on its own thread, or right away when generating serially.
+fn spawn_table<T, G>(parallel: bool, generate: G) -> Receiver<T>
+where
+    T: Send + 'static,
+    G: FnOnce() -> T + Send + 'static,
+{
+    let (tx, rx) = channel();
+    if parallel {
+        thread::spawn(move || tx.send(generate()));
+    } else {
+        let _ = tx.send(generate());
+    }
+    rx
+}
+
+fn build_traces<F: RichField + Extendable<D>, const D: usize>(
     mut program: Program,
     ola_stark: &mut OlaStark<F, D>,
     inputs: GenerationInputs,
+    parallel: bool,
 ) -> ([Vec<PolynomialValues<F>>; NUM_TABLES], PublicValues) {
-    let (cpu_tx, cpu_rx) = channel();
+    let prophet_commitment = program.trace.prophet_commitment;
+    let fork_id = program.trace.fork_id;
+    let (chain_id, tx_hash) = (program.trace.chain_id, program.trace.tx_hash);
+    let returned_values = std::mem::take(&mut program.trace.returned_values);
+    let arity = returned_values.len();
+    let stop_state = program.trace.stop_state;
+    let stopped = stop_state.is_some();
+    let (end_pc, end_registers) = stop_state.map_or((0, [0; REGISTER_NUM]), |state| {
+        (state.pc, state.registers.map(|reg| reg.to_canonical_u64()))
+    });
+    info!("trace padding:\n{}", padding_report(&program.trace));
     let exec = std::mem::replace(&mut program.trace.exec, Vec::new());
+    let mut opcode_counts = BTreeMap::new();
+    for step in exec.iter().filter(|step| step.is_ext_line.0 == 0) {
+        *opcode_counts.entry(step.opcode.0).or_insert(0u64) += 1;
+    }
+    let executed_opcodes: Vec<[u64; 2]> = opcode_counts
+        .into_iter()
+        .map(|(mask, count)| [mask, count])
+        .collect();
+    let gas_used = steps_gas(&exec);
+    let (start_pc, start_registers) = exec.first().map_or((0, [0; REGISTER_NUM]), |step| {
+        (step.pc, step.regs.map(|reg| reg.to_canonical_u64()))
+    });
+    let mut transient = std::mem::replace(&mut program.trace.transient, Vec::new());
+    for gap in fill_transient_steps(&exec, &mut transient) {
+        program.trace.insert_rangecheck(
+            GoldilocksField::from_canonical_u64(gap),
+            (
+                GoldilocksField::ONE,
+                GoldilocksField::ZERO,
+                GoldilocksField::ZERO,
+                GoldilocksField::ZERO,
+                GoldilocksField::ZERO,
+            ),
+        );
+    }
     let exec_for_cpu = exec.clone();
-    thread::spawn(move || {
-        let cpu_rows = generate_cpu_trace::<F>(&exec_for_cpu);
-        cpu_tx.send(trace_to_poly_values(cpu_rows));
+    let cpu_rx = spawn_table(parallel, move || {
+        let mut trace = generate_cpu_trace::<F>(&exec_for_cpu);
+        fill_tx_context(&mut trace, chain_id, tx_hash);
+        fill_returned_values(&mut trace, arity);
+        fill_end_state(&mut trace, stop_state);
+        trace_to_poly_values(trace)
     });
 
-    let (memory_tx, memory_rx) = channel();
     let memory = std::mem::replace(&mut program.trace.memory, Vec::new());
-    thread::spawn(move || {
-        let memory_rows = generate_memory_trace::<F>(&memory);
-        memory_tx.send(trace_to_poly_values(memory_rows));
+    let memory_image: Vec<[u64; 2]> = memory
+        .iter()
+        .filter(|c| is_image_cell(c))
+        .map(|c| [c.addr.to_canonical_u64(), c.value.to_canonical_u64()])
+        .collect();
+    let code_region: Vec<[u64; 2]> = memory
+        .iter()
+        .filter(|c| is_code_cell(c))
+        .map(|c| [c.addr.to_canonical_u64(), c.value.to_canonical_u64()])
+        .collect();
+    let end_memory_image = if stopped {
+        end_memory_image(&memory)
+    } else {
+        vec![]
+    };
+    let memory_rx = spawn_table(parallel, move || {
+        let mut trace = generate_memory_trace::<F>(&memory);
+        if stopped {
+            fill_end_memory(&mut trace);
+        }
+        trace_to_poly_values(trace)
     });
 
-    let (bitwise_tx, bitwise_rx) = channel();
     let builtin_bitwise_combined =
         std::mem::replace(&mut program.trace.builtin_bitwise_combined, Vec::new());
-    thread::spawn(move || {
+    let bitwise_rx = spawn_table(parallel, move || {
         let (bitwise_rows, bitwise_beta) = generate_bitwise_trace::<F>(&builtin_bitwise_combined);
-        bitwise_tx.send((trace_to_poly_values(bitwise_rows), bitwise_beta));
+        (trace_to_poly_values(bitwise_rows), bitwise_beta)
     });
 
-    let (cmp_tx, cmp_rx) = channel();
     let builtin_cmp = std::mem::replace(&mut program.trace.builtin_cmp, Vec::new());
-    thread::spawn(move || {
-        let cmp_rows = generate_cmp_trace(&builtin_cmp);
-        cmp_tx.send(trace_to_poly_values(cmp_rows));
+    let cmp_rx = spawn_table(parallel, move || {
+        trace_to_poly_values(generate_cmp_trace(&builtin_cmp))
     });
 
-    let (rc_tx, rc_rx) = channel();
     let builtin_rangecheck = std::mem::replace(&mut program.trace.builtin_rangecheck, Vec::new());
-    thread::spawn(move || {
-        let rc_rows = generate_rc_trace(&builtin_rangecheck);
-        rc_tx.send(trace_to_poly_values(rc_rows));
+    let rc_rx = spawn_table(parallel, move || {
+        trace_to_poly_values(generate_rc_trace(&builtin_rangecheck))
     });
 
-    let (poseidon_tx, poseidon_rx) = channel();
     let builtin_poseidon = std::mem::replace(&mut program.trace.builtin_poseidon, Vec::new());
-    thread::spawn(move || {
-        let poseidon_rows = generate_poseidon_trace(&builtin_poseidon);
-        poseidon_tx.send(trace_to_poly_values(poseidon_rows));
+    let poseidon_rx = spawn_table(parallel, move || {
+        trace_to_poly_values(generate_poseidon_trace(&builtin_poseidon))
     });
 
-    let (poseidon_chunk_tx, poseidon_chunk_rx) = channel();
     let builtin_poseidon_chunk =
         std::mem::replace(&mut program.trace.builtin_poseidon_chunk, Vec::new());
-    thread::spawn(move || {
+    let poseidon_chunk_rx = spawn_table(parallel, move || {
         let poseidon_chunk_rows: [Vec<F>; 53] =
             generate_poseidon_chunk_trace(&builtin_poseidon_chunk);
-        poseidon_chunk_tx.send(trace_to_poly_values(poseidon_chunk_rows));
+        trace_to_poly_values(poseidon_chunk_rows)
     });
 
-    let (storage_tx, storage_rx) = channel();
     let builtin_storage_hash =
         std::mem::replace(&mut program.trace.builtin_storage_hash, Vec::new());
     let builtin_program_hash =
         std::mem::replace(&mut program.trace.builtin_program_hash, Vec::new());
-    thread::spawn(move || {
+    let start_root = program.trace.start_end_roots.0;
+    let storage_rx = spawn_table(parallel, move || {
         let storage_access_rows =
-            generate_storage_access_trace(&builtin_storage_hash, &builtin_program_hash);
-        storage_tx.send(trace_to_poly_values(storage_access_rows));
+            generate_storage_access_trace(&builtin_storage_hash, &builtin_program_hash, start_root);
+        trace_to_poly_values(storage_access_rows)
     });
 
-    let (tape_tx, tape_rx) = channel();
     let tape = std::mem::replace(&mut program.trace.tape, Vec::new());
-    thread::spawn(move || {
-        let tape_rows = generate_tape_trace(&tape);
-        tape_tx.send(trace_to_poly_values(tape_rows));
+    let tape_rx = spawn_table(parallel, move || {
+        trace_to_poly_values(generate_tape_trace(&tape))
+    });
+
+    let transient_rx = spawn_table(parallel, move || {
+        trace_to_poly_values(generate_transient_trace(&transient))
     });
 
-    let (sccall_tx, sccall_rx) = channel();
     let sc_call = std::mem::replace(&mut program.trace.sc_call, Vec::new());
-    thread::spawn(move || {
-        let sccall_rows = generate_sccall_trace(&sc_call);
-        sccall_tx.send(trace_to_poly_values(sccall_rows));
+    let sccall_rx = spawn_table(parallel, move || {
+        trace_to_poly_values(generate_sccall_trace(&sc_call))
     });
 
-    let (program_tx, program_rx) = channel();
     let progs = program
         .trace
         .addr_program_hash
         .into_iter()
         .map(|(addr, hash)| (decode_addr(addr), hash))
         .collect::<Vec<_>>();
+    let codes = progs.iter().map(|(_, code)| &code[..]).collect::<Vec<_>>();
+    let mut program_hashes = calculate_arbitrary_poseidon_batch(&codes)
+        .into_iter()
+        .map(|hash| hash.map(|e| e.to_canonical_u64()))
+        .collect::<Vec<_>>();
+    program_hashes.sort();
     let progs_for_program = progs.clone();
-    thread::spawn(move || {
+    let start_end_roots = program.trace.start_end_roots;
+    let program_rx = spawn_table(parallel, move || {
         let (program_rows, program_beta) =
-            prog::generate_prog_trace::<F>(&exec, progs_for_program, program.trace.start_end_roots);
-        program_tx.send((trace_to_poly_values(program_rows), program_beta));
+            prog::generate_prog_trace::<F>(&exec, progs_for_program, start_end_roots);
+        (trace_to_poly_values(program_rows), program_beta)
     });
 
-    let (prog_chunk_tx, prog_chunk_rx) = channel();
-    thread::spawn(move || {
-        let prog_chunk_rows = prog::generate_prog_chunk_trace::<F>(progs);
-        prog_chunk_tx.send(trace_to_poly_values(prog_chunk_rows));
+    let prog_chunk_rx = spawn_table(parallel, move || {
+        trace_to_poly_values(prog::generate_prog_chunk_trace::<F>(progs))
     });
 
     let (bitwise_trace, bitwise_beta) = bitwise_rx.recv().unwrap();
@@ -201,13 +381,39 @@ pub fn generate_traces<F: RichField + Extendable<D>, const D: usize>(
         sccall_rx.recv().unwrap(),
         program_trace,
         prog_chunk_rx.recv().unwrap(),
+        transient_rx.recv().unwrap(),
     ];
 
-    // TODO: update trie_roots_before & trie_roots_after
+    // TODO: update transactions_root & receipts_root
     let public_values = PublicValues {
-        trie_roots_before: TrieRoots::default(),
-        trie_roots_after: TrieRoots::default(),
+        trie_roots_before: TrieRoots {
+            state_root: H256(tree_value_to_h256(&start_end_roots.0).0),
+            ..Default::default()
+        },
+        trie_roots_after: TrieRoots {
+            state_root: H256(tree_value_to_h256(&start_end_roots.1).0),
+            ..Default::default()
+        },
         block_metadata: inputs.block_metadata,
+        prophet_commitment: prophet_commitment.map(|e| e.to_canonical_u64()),
+        program_hashes,
+        fork_id: fork_id.map(|e| e.to_canonical_u64()),
+        chain_id: chain_id.to_canonical_u64(),
+        tx_hash: tx_hash.map(|e| e.to_canonical_u64()),
+        returned_values: returned_values
+            .iter()
+            .map(|e| e.to_canonical_u64())
+            .collect(),
+        memory_image,
+        code_region,
+        executed_opcodes,
+        start_pc,
+        start_registers,
+        end_pc,
+        end_registers,
+        gas_used,
+        stopped,
+        end_memory_image,
     };
     (traces, public_values)
 }
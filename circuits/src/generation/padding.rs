@@ -0,0 +1,209 @@
+use core::trace::trace::{merge_rangecheck_rows, Trace};
+use core::vm::opcodes::OlaOpcode;
+use std::fmt::{Display, Formatter};
+
+use crate::builtins::bitwise::columns::{BITWISE_U8_SIZE, RANGE_CHECK_U8_SIZE};
+use crate::builtins::rangecheck::columns::RANGE_CHECK_U16_SIZE;
+use crate::stark::ola_stark::Table;
+
+#[derive(Debug, Copy, Clone, Eq, PartialEq)]
+pub enum PaddingPolicy {
+    /// Padding rows repeat the last filled row.
+    DuplicateLastRow,
+    /// Padding rows are switched off by a dedicated selector column.
+    SelectorRows,
+    /// Padding rows hold a fixed row that satisfies the AIR with all filters
+    /// off.
+    ConstantRows,
+}
+
+/// Padding policy the trace generator and AIR of `table` agree on.
+pub fn padding_policy(table: Table) -> PaddingPolicy {
+    match table {
+        Table::Cpu | Table::Memory | Table::SCCall => PaddingPolicy::SelectorRows,
+        Table::Tape | Table::Transient => PaddingPolicy::DuplicateLastRow,
+        Table::Bitwise
+        | Table::Cmp
+        | Table::RangeCheck
+        | Table::Poseidon
+        | Table::PoseidonChunk
+        | Table::StorageAccess
+        | Table::Program
+        | Table::ProgChunk => PaddingPolicy::ConstantRows,
+    }
+}
+
+#[derive(Debug, Copy, Clone)]
+pub struct TablePadding {
+    pub table: Table,
+    pub policy: PaddingPolicy,
+    /// Rows holding execution data.
+    pub filled_rows: usize,
+    /// Rows the table needs regardless of execution, e.g. fixed lookup tables.
+    pub min_rows: usize,
+    /// Rows after padding to a power of two.
+    pub padded_rows: usize,
+}
+
+impl TablePadding {
+    fn new(table: Table, filled_rows: usize, min_rows: usize) -> Self {
+        // the cpu table keeps a padding row for its end state
+        let padded_rows = if table == Table::Cpu {
+            (filled_rows + 1).next_power_of_two()
+        } else {
+            filled_rows.max(min_rows).max(2).next_power_of_two()
+        };
+        Self {
+            table,
+            policy: padding_policy(table),
+            filled_rows,
+            min_rows,
+            padded_rows,
+        }
+    }
+
+    pub fn padding_rows(&self) -> usize {
+        self.padded_rows - self.filled_rows.max(self.min_rows)
+    }
+
+    pub fn padding_ratio(&self) -> f64 {
+        if self.padded_rows == 0 {
+            return 0.0;
+        }
+        self.padding_rows() as f64 / self.padded_rows as f64
+    }
+
+    /// Rows that would have to be dropped to halve the padded table, zero if
+    /// the table is already at its minimum.
+    pub fn rows_over_cliff(&self) -> usize {
+        let half = self.padded_rows / 2;
+        if half < self.min_rows.max(2) {
+            return 0;
+        }
+        self.filled_rows.saturating_sub(half)
+    }
+}
+
+#[derive(Debug, Clone, Default)]
+pub struct PaddingReport {
+    pub tables: Vec<TablePadding>,
+}
+
+impl PaddingReport {
+    pub fn total_padded_rows(&self) -> usize {
+        self.tables.iter().map(|t| t.padded_rows).sum()
+    }
+
+    pub fn total_padding_rows(&self) -> usize {
+        self.tables.iter().map(|t| t.padding_rows()).sum()
+    }
+
+    /// The table that dominates the trace height.
+    pub fn tallest(&self) -> Option<&TablePadding> {
+        self.tables.iter().max_by_key(|t| t.padded_rows)
+    }
+}
+
+impl Display for PaddingReport {
+    fn fmt(&self, f: &mut Formatter<'_>) -> std::fmt::Result {
+        writeln!(
+            f,
+            "{:>16}\t{:>16}\t{:>10}\t{:>10}\t{:>8}\t{:>10}",
+            "table", "policy", "filled", "padded", "padding", "over_cliff"
+        )?;
+        for t in &self.tables {
+            writeln!(
+                f,
+                "{:>16}\t{:>16}\t{:>10}\t{:>10}\t{:>7.1}%\t{:>10}",
+                format!("{:?}", t.table),
+                format!("{:?}", t.policy),
+                t.filled_rows,
+                t.padded_rows,
+                t.padding_ratio() * 100.0,
+                t.rows_over_cliff()
+            )?;
+        }
+        Ok(())
+    }
+}
+
+/// Per table padding of the traces `generate_traces` would build from `trace`.
+pub fn padding_report(trace: &Trace) -> PaddingReport {
+    let main_lines: usize = trace
+        .exec
+        .iter()
+        .filter(|e| e.is_ext_line.0 == 0)
+        .map(|e| {
+            if e.op1_imm.0 == 1
+                || e.opcode.0 == OlaOpcode::MLOAD.binary_bit_mask()
+                || e.opcode.0 == OlaOpcode::MSTORE.binary_bit_mask()
+            {
+                2
+            } else {
+                1
+            }
+        })
+        .sum();
+    let progs_len: usize = trace.addr_program_hash.values().map(|p| p.len()).sum();
+    let prog_chunks: usize = trace
+        .addr_program_hash
+        .values()
+        .map(|p| (p.len() + 7) / 8)
+        .sum();
+
+    PaddingReport {
+        tables: vec![
+            TablePadding::new(Table::Cpu, trace.exec.len(), 0),
+            TablePadding::new(Table::Memory, trace.memory.len(), 0),
+            TablePadding::new(
+                Table::Bitwise,
+                trace.builtin_bitwise_combined.len(),
+                RANGE_CHECK_U8_SIZE.max(BITWISE_U8_SIZE),
+            ),
+            TablePadding::new(Table::Cmp, trace.builtin_cmp.len(), 0),
+            TablePadding::new(
+                Table::RangeCheck,
+                merge_rangecheck_rows(&trace.builtin_rangecheck).len(),
+                RANGE_CHECK_U16_SIZE,
+            ),
+            TablePadding::new(Table::Poseidon, trace.builtin_poseidon.len(), 0),
+            TablePadding::new(Table::PoseidonChunk, trace.builtin_poseidon_chunk.len(), 0),
+            TablePadding::new(
+                Table::StorageAccess,
+                trace.builtin_storage_hash.len() + trace.builtin_program_hash.len(),
+                0,
+            ),
+            TablePadding::new(Table::Tape, trace.tape.len(), 0),
+            TablePadding::new(Table::SCCall, trace.sc_call.len(), 0),
+            TablePadding::new(Table::Program, main_lines.max(progs_len), 0),
+            TablePadding::new(Table::ProgChunk, prog_chunks, 0),
+            TablePadding::new(Table::Transient, trace.transient.len(), 0),
+        ],
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::generation::padding::{PaddingPolicy, TablePadding};
+    use crate::stark::ola_stark::Table;
+
+    #[test]
+    fn test_table_padding() {
+        let cmp = TablePadding::new(Table::Cmp, 1025, 0);
+        assert_eq!(cmp.policy, PaddingPolicy::ConstantRows);
+        assert_eq!(cmp.padded_rows, 2048);
+        assert_eq!(cmp.padding_rows(), 1023);
+        assert_eq!(cmp.rows_over_cliff(), 1);
+
+        let rc = TablePadding::new(Table::RangeCheck, 10, 1 << 16);
+        assert_eq!(rc.padded_rows, 1 << 16);
+        assert_eq!(rc.padding_rows(), 0);
+        assert_eq!(rc.rows_over_cliff(), 0);
+
+        let cpu = TablePadding::new(Table::Cpu, 7, 0);
+        assert_eq!(cpu.padded_rows, 8);
+        assert_eq!(cpu.padding_rows(), 1);
+        let cpu = TablePadding::new(Table::Cpu, 8, 0);
+        assert_eq!(cpu.padded_rows, 16);
+    }
+}
@@ -15,18 +15,26 @@ use plonky2::{
 
 use crate::{program::columns::*, stark::lookup::permuted_cols};
 
-pub fn generate_prog_trace<F: RichField>(
-    execs: &[Step],
-    progs: Vec<([GoldilocksField; 4], Vec<GoldilocksField>)>,
+/// The compress challenge of the program table, derived from the state roots
+/// before and after execution so the verifier can recompute it.
+pub fn program_compress_challenge<F: RichField>(
     start_end_roots: ([GoldilocksField; 4], [GoldilocksField; 4]),
-) -> ([Vec<F>; NUM_PROG_COLS], F) {
+) -> F {
     let mut challenger =
         Challenger::<F, <PoseidonGoldilocksConfig as GenericConfig<2>>::Hasher>::new();
     for limb_idx in 0..4 {
         challenger.observe_element(F::from_canonical_u64(start_end_roots.0[limb_idx].0));
         challenger.observe_element(F::from_canonical_u64(start_end_roots.1[limb_idx].0))
     }
-    let beta = challenger.get_challenge();
+    challenger.get_challenge()
+}
+
+pub fn generate_prog_trace<F: RichField>(
+    execs: &[Step],
+    progs: Vec<([GoldilocksField; 4], Vec<GoldilocksField>)>,
+    start_end_roots: ([GoldilocksField; 4], [GoldilocksField; 4]),
+) -> ([Vec<F>; NUM_PROG_COLS], F) {
+    let beta = program_compress_challenge::<F>(start_end_roots);
 
     let main_lines: Vec<&Step> = execs.iter().filter(|e| e.is_ext_line.0 == 0).collect();
     let exec_len: usize = main_lines
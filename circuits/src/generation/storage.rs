@@ -1,12 +1,16 @@
 use core::trace::trace::StorageHashRow;
+use core::types::GoldilocksField;
 
 use plonky2::hash::hash_types::RichField;
 
 use crate::builtins::storage::columns::*;
 
+/// `start_root` is the state root before the accesses, it fills the roots of
+/// a table without accesses.
 pub fn generate_storage_access_trace<F: RichField>(
     accesses: &[StorageHashRow],
     prog_hash_read: &[StorageHashRow],
+    start_root: [GoldilocksField; 4],
 ) -> [Vec<F>; NUM_COL_ST] {
     let num_filled_row_len: usize = accesses.len() + prog_hash_read.len();
     let num_padded_rows = if !num_filled_row_len.is_power_of_two() || num_filled_row_len < 2 {
@@ -81,28 +85,18 @@ pub fn generate_storage_access_trace<F: RichField>(
         trace[COL_ST_IS_PADDING][i] = F::ZERO;
     }
 
-    let last_root = [
+    let last_root: [F; 4] = std::array::from_fn(|j| {
         if num_filled_row_len == 0 {
-            F::ZERO
-        } else {
-            trace[COL_ST_ROOT_RANGE.start][num_filled_row_len - 1]
-        },
-        if num_filled_row_len == 0 {
-            F::ZERO
-        } else {
-            trace[COL_ST_ROOT_RANGE.start + 1][num_filled_row_len - 1]
-        },
-        if num_filled_row_len == 0 {
-            F::ZERO
+            F::from_canonical_u64(start_root[j].0)
         } else {
-            trace[COL_ST_ROOT_RANGE.start + 2][num_filled_row_len - 1]
-        },
-        if num_filled_row_len == 0 {
-            F::ZERO
-        } else {
-            trace[COL_ST_ROOT_RANGE.start + 3][num_filled_row_len - 1]
-        },
-    ];
+            trace[COL_ST_ROOT_RANGE.start + j][num_filled_row_len - 1]
+        }
+    });
+    if num_filled_row_len == 0 {
+        for j in 0..4 {
+            trace[COL_ST_PRE_ROOT_RANGE.start + j][0] = last_root[j];
+        }
+    }
     if num_padded_rows != num_filled_row_len {
         for i in num_filled_row_len..num_padded_rows {
             trace[COL_ST_ROOT_RANGE.start][i] = last_root[0];
@@ -112,6 +106,8 @@ pub fn generate_storage_access_trace<F: RichField>(
             trace[COL_ST_IS_PADDING][i] = F::ONE;
         }
     }
+    trace[COL_ST_IS_FIRST_ROW][0] = F::ONE;
+    trace[COL_ST_IS_LAST_ROW][num_padded_rows - 1] = F::ONE;
 
     trace.try_into().unwrap_or_else(|v: Vec<Vec<F>>| {
         panic!(
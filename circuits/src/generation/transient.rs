@@ -0,0 +1,127 @@
+use core::{
+    trace::trace::{Step, TransientRow},
+    types::{GoldilocksField, PrimeField64},
+    vm::opcodes::OlaOpcode,
+};
+use std::collections::HashMap;
+
+use plonky2::hash::hash_types::RichField;
+
+use crate::builtins::transient::columns::{
+    COL_TRANSIENT_ADDR_RANGE, COL_TRANSIENT_CLK, COL_TRANSIENT_DIFF_INV_RANGE,
+    COL_TRANSIENT_ENV_IDX, COL_TRANSIENT_FILTER_LOOKED, COL_TRANSIENT_FILTER_STEP_DIFF,
+    COL_TRANSIENT_IS_FIRST, COL_TRANSIENT_IS_WRITE, COL_TRANSIENT_KEY, COL_TRANSIENT_OPCODE,
+    COL_TRANSIENT_STEP, COL_TRANSIENT_STEP_DIFF, COL_TRANSIENT_VALUE, NUM_COL_TRANSIENT,
+};
+
+fn slot<F: RichField>(row: &TransientRow) -> [F; 5] {
+    let mut limbs = [F::ZERO; 5];
+    for (limb, addr) in limbs.iter_mut().zip(row.addr_storage.iter()) {
+        *limb = F::from_canonical_u64(addr.to_canonical_u64());
+    }
+    limbs[4] = F::from_canonical_u64(row.key.to_canonical_u64());
+    limbs
+}
+
+/// Sets the step of every access to the cpu row of its `tload_t`/`tstore_t`
+/// in `exec`, and returns the step gaps within a slot for the range check.
+pub fn fill_transient_steps(exec: &[Step], rows: &mut [TransientRow]) -> Vec<u64> {
+    let op_tloadt = OlaOpcode::TLOADT.binary_bit_mask();
+    let op_tstoret = OlaOpcode::TSTORET.binary_bit_mask();
+    let steps: HashMap<(u64, u32), u64> = exec
+        .iter()
+        .enumerate()
+        .filter(|(_, s)| s.opcode.0 == op_tloadt || s.opcode.0 == op_tstoret)
+        .map(|(i, s)| ((s.env_idx.to_canonical_u64(), s.clk), i as u64))
+        .collect();
+    let mut gaps = Vec::new();
+    let mut prev: Option<([GoldilocksField; 5], u64)> = None;
+    for row in rows.iter_mut() {
+        let slot = slot::<GoldilocksField>(row);
+        row.step = steps
+            .get(&(row.env_idx.to_canonical_u64(), row.clk))
+            .copied()
+            .unwrap_or_default();
+        if let Some((prev_slot, prev_step)) = prev {
+            if prev_slot == slot {
+                gaps.push(row.step.wrapping_sub(prev_step + 1));
+            }
+        }
+        prev = Some((slot, row.step));
+    }
+    gaps
+}
+
+/// `rows` must be grouped by slot, accesses of a slot in execution order, as
+/// the executor emits them, with their steps filled by `fill_transient_steps`.
+pub fn generate_transient_trace<F: RichField>(
+    rows: &[TransientRow],
+) -> [Vec<F>; NUM_COL_TRANSIENT] {
+    let num_filled_row_len: usize = rows.len();
+    let num_padded_rows = if !num_filled_row_len.is_power_of_two() || num_filled_row_len < 2 {
+        if num_filled_row_len < 2 {
+            2
+        } else {
+            num_filled_row_len.next_power_of_two()
+        }
+    } else {
+        num_filled_row_len
+    };
+
+    let op_tloadt = F::from_canonical_u64(OlaOpcode::TLOADT.binary_bit_mask());
+    let op_tstoret = F::from_canonical_u64(OlaOpcode::TSTORET.binary_bit_mask());
+    let mut trace: Vec<Vec<F>> = vec![vec![F::ZERO; num_padded_rows]; NUM_COL_TRANSIENT];
+    trace[COL_TRANSIENT_IS_FIRST][0] = F::ONE;
+    let mut prev_slot: Option<[F; 5]> = None;
+    for (i, row) in rows.iter().enumerate() {
+        let slot = slot::<F>(row);
+        trace[COL_TRANSIENT_ENV_IDX][i] = F::from_canonical_u64(row.env_idx.to_canonical_u64());
+        trace[COL_TRANSIENT_CLK][i] = F::from_canonical_u32(row.clk);
+        trace[COL_TRANSIENT_OPCODE][i] = if row.is_write { op_tstoret } else { op_tloadt };
+        for (col, limb) in COL_TRANSIENT_ADDR_RANGE.zip(slot.iter()) {
+            trace[col][i] = *limb;
+        }
+        trace[COL_TRANSIENT_KEY][i] = slot[4];
+        trace[COL_TRANSIENT_VALUE][i] = F::from_canonical_u64(row.value.to_canonical_u64());
+        trace[COL_TRANSIENT_IS_WRITE][i] = if row.is_write { F::ONE } else { F::ZERO };
+        trace[COL_TRANSIENT_FILTER_LOOKED][i] = F::ONE;
+        trace[COL_TRANSIENT_STEP][i] = F::from_canonical_u64(row.step);
+        match prev_slot {
+            None => trace[COL_TRANSIENT_IS_FIRST][i] = F::ONE,
+            Some(prev) if prev != slot => {
+                trace[COL_TRANSIENT_IS_FIRST][i] = F::ONE;
+                let j = (0..5).find(|j| prev[*j] != slot[*j]).unwrap();
+                trace[COL_TRANSIENT_DIFF_INV_RANGE.start + j][i] = (slot[j] - prev[j]).inverse();
+            }
+            Some(_) => {
+                let gap = trace[COL_TRANSIENT_STEP][i] - trace[COL_TRANSIENT_STEP][i - 1];
+                trace[COL_TRANSIENT_STEP_DIFF][i] = gap - F::ONE;
+                trace[COL_TRANSIENT_FILTER_STEP_DIFF][i] = F::ONE;
+            }
+        }
+        prev_slot = Some(slot);
+    }
+
+    // padding rows read the last slot again and are not looked up
+    for i in num_filled_row_len.max(1)..num_padded_rows {
+        for col in [
+            COL_TRANSIENT_ENV_IDX,
+            COL_TRANSIENT_CLK,
+            COL_TRANSIENT_KEY,
+            COL_TRANSIENT_VALUE,
+        ]
+        .into_iter()
+        .chain(COL_TRANSIENT_ADDR_RANGE)
+        {
+            trace[col][i] = trace[col][i - 1];
+        }
+    }
+
+    trace.try_into().unwrap_or_else(|v: Vec<Vec<F>>| {
+        panic!(
+            "Expected a Vec of length {} but it was {}",
+            NUM_COL_TRANSIENT,
+            v.len()
+        )
+    })
+}
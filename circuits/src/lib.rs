@@ -4,6 +4,7 @@
 pub mod builtins;
 pub mod cpu;
 pub mod fixed_table;
+pub mod fixtures;
 pub mod generation;
 pub mod memory;
 pub mod program;
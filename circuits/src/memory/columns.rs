@@ -25,8 +25,14 @@ pub(crate) const COL_MEM_S_SCCALL: usize = COL_MEM_S_TSTORE + 1;
 pub(crate) const COL_MEM_S_POSEIDON: usize = COL_MEM_S_SCCALL + 1;
 pub(crate) const COL_MEM_S_SSTORE: usize = COL_MEM_S_POSEIDON + 1;
 pub(crate) const COL_MEM_S_SLOAD: usize = COL_MEM_S_SSTORE + 1;
-pub(crate) const COL_MEM_S_PROPHET: usize = COL_MEM_S_SLOAD + 1;
-pub(crate) const COL_MEM_IS_WRITE: usize = COL_MEM_S_PROPHET + 1;
+pub(crate) const COL_MEM_S_SSCAN: usize = COL_MEM_S_SLOAD + 1;
+pub(crate) const COL_MEM_S_PROPHET: usize = COL_MEM_S_SSCAN + 1;
+// preloaded by `Process::load_memory_image` and `load_prophet_outputs`,
+// looked up by the public image.
+pub(crate) const COL_MEM_S_IMAGE: usize = COL_MEM_S_PROPHET + 1;
+// mapped read only by `Process::map_code`, looked up by the public code region.
+pub(crate) const COL_MEM_S_CODE: usize = COL_MEM_S_IMAGE + 1;
+pub(crate) const COL_MEM_IS_WRITE: usize = COL_MEM_S_CODE + 1;
 pub(crate) const COL_MEM_VALUE: usize = COL_MEM_IS_WRITE + 1;
 pub(crate) const COL_MEM_DIFF_ADDR: usize = COL_MEM_VALUE + 1;
 pub(crate) const COL_MEM_DIFF_ADDR_INV: usize = COL_MEM_DIFF_ADDR + 1;
@@ -42,7 +48,17 @@ pub(crate) const COL_MEM_REGION_HEAP: usize = COL_MEM_REGION_PROPHET + 1;
 pub(crate) const COL_MEM_RC_VALUE: usize = COL_MEM_REGION_HEAP + 1;
 pub(crate) const COL_MEM_FILTER_LOOKING_RC: usize = COL_MEM_RC_VALUE + 1;
 pub(crate) const COL_MEM_FILTER_LOOKING_RC_COND: usize = COL_MEM_FILTER_LOOKING_RC + 1;
-pub(crate) const NUM_MEM_COLS: usize = COL_MEM_FILTER_LOOKING_RC_COND + 1;
+// Rows past the last cell, prophet writes after it.
+pub(crate) const COL_MEM_IS_PADDING: usize = COL_MEM_FILTER_LOOKING_RC_COND + 1;
+pub(crate) const COL_MEM_IS_FIRST_ROW: usize = COL_MEM_IS_PADDING + 1;
+// Same on every row and looked up on the first one by the public values: the
+// table exports the memory it ends with, for a chunk that stops. Then the last
+// cell of every address is final, looked up by the end memory image, which
+// the next chunk starts from.
+pub(crate) const COL_MEM_EXPORT: usize = COL_MEM_IS_FIRST_ROW + 1;
+pub(crate) const COL_MEM_NEXT_DIFF_ADDR_INV: usize = COL_MEM_EXPORT + 1;
+pub(crate) const COL_MEM_S_FINAL: usize = COL_MEM_NEXT_DIFF_ADDR_INV + 1;
+pub(crate) const NUM_MEM_COLS: usize = COL_MEM_S_FINAL + 1;
 
 pub(crate) fn get_memory_col_name_map() -> BTreeMap<usize, String> {
     let mut m: BTreeMap<usize, String> = BTreeMap::new();
@@ -62,7 +78,10 @@ pub(crate) fn get_memory_col_name_map() -> BTreeMap<usize, String> {
     m.insert(COL_MEM_S_POSEIDON, String::from("S_POSEIDON"));
     m.insert(COL_MEM_S_SSTORE, String::from("S_SSTORE"));
     m.insert(COL_MEM_S_SLOAD, String::from("S_SLOAD"));
+    m.insert(COL_MEM_S_SSCAN, String::from("S_SSCAN"));
     m.insert(COL_MEM_S_PROPHET, String::from("S_PROPHET"));
+    m.insert(COL_MEM_S_IMAGE, String::from("S_IMAGE"));
+    m.insert(COL_MEM_S_CODE, String::from("S_CODE"));
     m.insert(COL_MEM_IS_WRITE, String::from("IS_WRITE"));
     m.insert(COL_MEM_VALUE, String::from("VALUE"));
     m.insert(COL_MEM_DIFF_ADDR, String::from("DIFF_ADDR"));
@@ -78,6 +97,14 @@ pub(crate) fn get_memory_col_name_map() -> BTreeMap<usize, String> {
         COL_MEM_FILTER_LOOKING_RC_COND,
         String::from("FILTER_LOOKING_RC_COND"),
     );
+    m.insert(COL_MEM_IS_PADDING, String::from("IS_PADDING"));
+    m.insert(COL_MEM_IS_FIRST_ROW, String::from("IS_FIRST_ROW"));
+    m.insert(COL_MEM_EXPORT, String::from("EXPORT"));
+    m.insert(
+        COL_MEM_NEXT_DIFF_ADDR_INV,
+        String::from("NEXT_DIFF_ADDR_INV"),
+    );
+    m.insert(COL_MEM_S_FINAL, String::from("S_FINAL"));
     m
 }
 
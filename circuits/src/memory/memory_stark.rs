@@ -1,5 +1,5 @@
+use core::vm::memory::MEM_SPAN_SIZE;
 use core::vm::opcodes::OlaOpcode;
-use std::ops::Sub;
 
 use plonky2::field::types::Field;
 
@@ -57,9 +57,42 @@ pub fn ctl_filter<F: Field>() -> Column<F> {
         COL_MEM_S_SCCALL,
         COL_MEM_S_SSTORE,
         COL_MEM_S_SLOAD,
+        COL_MEM_S_SSCAN,
     ])
 }
 
+pub fn ctl_data_image<F: Field>() -> Vec<Column<F>> {
+    Column::singles([COL_MEM_ADDR, COL_MEM_VALUE]).collect()
+}
+
+pub fn ctl_filter_image<F: Field>() -> Column<F> {
+    Column::single(COL_MEM_S_IMAGE)
+}
+
+pub fn ctl_data_code<F: Field>() -> Vec<Column<F>> {
+    Column::singles([COL_MEM_ADDR, COL_MEM_VALUE]).collect()
+}
+
+pub fn ctl_filter_code<F: Field>() -> Column<F> {
+    Column::single(COL_MEM_S_CODE)
+}
+
+pub fn ctl_data_export<F: Field>() -> Vec<Column<F>> {
+    vec![Column::single(COL_MEM_EXPORT)]
+}
+
+pub fn ctl_filter_first_row<F: Field>() -> Column<F> {
+    Column::single(COL_MEM_IS_FIRST_ROW)
+}
+
+pub fn ctl_data_end_image<F: Field>() -> Vec<Column<F>> {
+    Column::singles([COL_MEM_ADDR, COL_MEM_VALUE]).collect()
+}
+
+pub fn ctl_filter_end_image<F: Field>() -> Column<F> {
+    Column::single(COL_MEM_S_FINAL)
+}
+
 pub fn ctl_data_with_poseidon_chunk<F: Field>() -> Vec<Column<F>> {
     let cols: Vec<_> = Column::singles([
         COL_MEM_TX_IDX,
@@ -91,12 +124,13 @@ impl<F: RichField + Extendable<D>, const D: usize> Stark<F, D> for MemoryStark<F
 
     fn eval_packed_generic<FE, P, const D2: usize>(
         &self,
-        vars: StarkEvaluationVars<FE, P, NUM_MEM_COLS>,
+        vars: StarkEvaluationVars<FE, P, { Self::COLUMNS /*HACKNUM_MEM_COLS*/ }>,
         yield_constr: &mut ConstraintConsumer<P>,
     ) where
         FE: FieldExtension<D2, BaseField = F>,
         P: PackedField<Scalar = FE>,
     {
+        let vars = StarkEvaluationVars::<FE, P, NUM_MEM_COLS> { local_values: vars.local_values[..].try_into().unwrap(), next_values: vars.next_values[..].try_into().unwrap() }; //HACK-VARS
         let lv = vars.local_values;
         let nv = vars.next_values;
         // tx_idx not change or increase by one
@@ -112,7 +146,7 @@ impl<F: RichField + Extendable<D>, const D: usize> Stark<F, D> for MemoryStark<F
         );
 
         let p = P::ZEROS;
-        let span = P::Scalar::from_canonical_u64(2_u64.pow(32).sub(1));
+        let span = P::Scalar::from_canonical_u64(MEM_SPAN_SIZE);
         let addr_heap_ptr = P::Scalar::from_canonical_u64(ADDR_HEAP_PTR);
         let init_value_heap_ptr = P::Scalar::from_canonical_u64(INIT_VALUE_HEAP_PTR);
 
@@ -150,6 +184,7 @@ impl<F: RichField + Extendable<D>, const D: usize> Stark<F, D> for MemoryStark<F
         let op_poseidon = P::Scalar::from_canonical_u64(OlaOpcode::POSEIDON.binary_bit_mask());
         let op_sstore = P::Scalar::from_canonical_u64(OlaOpcode::SSTORE.binary_bit_mask());
         let op_sload = P::Scalar::from_canonical_u64(OlaOpcode::SLOAD.binary_bit_mask());
+        let op_sscan = P::Scalar::from_canonical_u64(OlaOpcode::SSCAN.binary_bit_mask());
         let op_prophet = P::ZEROS;
 
         // constraint opcode and selector matches, selector is binary and only one is
@@ -164,7 +199,12 @@ impl<F: RichField + Extendable<D>, const D: usize> Stark<F, D> for MemoryStark<F
         yield_constr.constraint((lv[COL_MEM_OP] - op_poseidon) * lv[COL_MEM_S_POSEIDON]);
         yield_constr.constraint((lv[COL_MEM_OP] - op_sstore) * lv[COL_MEM_S_SSTORE]);
         yield_constr.constraint((lv[COL_MEM_OP] - op_sload) * lv[COL_MEM_S_SLOAD]);
+        yield_constr.constraint((lv[COL_MEM_OP] - op_sscan) * lv[COL_MEM_S_SSCAN]);
         yield_constr.constraint((lv[COL_MEM_OP] - op_prophet) * lv[COL_MEM_S_PROPHET]);
+        // image cells are written like mstores, but are not looked up by the cpu
+        yield_constr.constraint((lv[COL_MEM_OP] - op_mstore) * lv[COL_MEM_S_IMAGE]);
+        // code cells are written like prophets, but only to the stack
+        yield_constr.constraint((lv[COL_MEM_OP] - op_prophet) * lv[COL_MEM_S_CODE]);
         yield_constr.constraint((P::ONES - lv[COL_MEM_S_MLOAD]) * lv[COL_MEM_S_MLOAD]);
         yield_constr.constraint((P::ONES - lv[COL_MEM_S_MSTORE]) * lv[COL_MEM_S_MSTORE]);
         yield_constr.constraint((P::ONES - lv[COL_MEM_S_CALL]) * lv[COL_MEM_S_CALL]);
@@ -175,7 +215,10 @@ impl<F: RichField + Extendable<D>, const D: usize> Stark<F, D> for MemoryStark<F
         yield_constr.constraint((P::ONES - lv[COL_MEM_S_POSEIDON]) * lv[COL_MEM_S_POSEIDON]);
         yield_constr.constraint((P::ONES - lv[COL_MEM_S_SSTORE]) * lv[COL_MEM_S_SSTORE]);
         yield_constr.constraint((P::ONES - lv[COL_MEM_S_SLOAD]) * lv[COL_MEM_S_SLOAD]);
+        yield_constr.constraint((P::ONES - lv[COL_MEM_S_SSCAN]) * lv[COL_MEM_S_SSCAN]);
         yield_constr.constraint((P::ONES - lv[COL_MEM_S_PROPHET]) * lv[COL_MEM_S_PROPHET]);
+        yield_constr.constraint((P::ONES - lv[COL_MEM_S_IMAGE]) * lv[COL_MEM_S_IMAGE]);
+        yield_constr.constraint((P::ONES - lv[COL_MEM_S_CODE]) * lv[COL_MEM_S_CODE]);
         yield_constr.constraint(
             P::ONES
                 - lv[COL_MEM_S_MLOAD]
@@ -188,14 +231,23 @@ impl<F: RichField + Extendable<D>, const D: usize> Stark<F, D> for MemoryStark<F
                 - lv[COL_MEM_S_POSEIDON]
                 - lv[COL_MEM_S_SSTORE]
                 - lv[COL_MEM_S_SLOAD]
-                - lv[COL_MEM_S_PROPHET],
+                - lv[COL_MEM_S_SSCAN]
+                - lv[COL_MEM_S_PROPHET]
+                - lv[COL_MEM_S_IMAGE]
+                - lv[COL_MEM_S_CODE],
         );
 
         // constraint is_rw region
         yield_constr.constraint(is_rw * (P::ONES - is_rw));
         yield_constr.constraint(lv[COL_MEM_IS_RW] * lv[COL_MEM_S_PROPHET]);
+        yield_constr.constraint(lv[COL_MEM_IS_RW] * lv[COL_MEM_S_CODE]);
         yield_constr.constraint(
-            (P::ONES - lv[COL_MEM_IS_RW]) * (P::ONES - lv[COL_MEM_S_PROPHET] - lv[COL_MEM_S_MLOAD]),
+            (P::ONES - lv[COL_MEM_IS_RW])
+                * (P::ONES
+                    - lv[COL_MEM_S_PROPHET]
+                    - lv[COL_MEM_S_MLOAD]
+                    - lv[COL_MEM_S_CODE]
+                    - lv[COL_MEM_S_IMAGE]),
         );
         // constraint is_write
         yield_constr.constraint(
@@ -206,7 +258,10 @@ impl<F: RichField + Extendable<D>, const D: usize> Stark<F, D> for MemoryStark<F
                     - lv[COL_MEM_S_TLOAD]
                     - lv[COL_MEM_S_POSEIDON]
                     - lv[COL_MEM_S_SLOAD]
-                    - lv[COL_MEM_S_PROPHET]),
+                    - lv[COL_MEM_S_SSCAN]
+                    - lv[COL_MEM_S_PROPHET]
+                    - lv[COL_MEM_S_IMAGE]
+                    - lv[COL_MEM_S_CODE]),
         );
         yield_constr.constraint(
             (P::ONES - lv[COL_MEM_IS_WRITE])
@@ -218,9 +273,21 @@ impl<F: RichField + Extendable<D>, const D: usize> Stark<F, D> for MemoryStark<F
                     - lv[COL_MEM_S_SCCALL]
                     - lv[COL_MEM_S_POSEIDON]
                     - lv[COL_MEM_S_SSTORE]
-                    - lv[COL_MEM_S_SLOAD]),
+                    - lv[COL_MEM_S_SLOAD]
+                    - lv[COL_MEM_S_SSCAN]),
         );
 
+        // image cells are written at clk 0, before any instruction, read write
+        // to the stack or heap and write once to the prophet region, where the
+        // prophet outputs of a previous chunk are carried
+        yield_constr.constraint(lv[COL_MEM_S_IMAGE] * lv[COL_MEM_CLK]);
+        yield_constr.constraint(lv[COL_MEM_S_IMAGE] * (is_rw + region_prophet - P::ONES));
+        // code cells are written at clk 0 to the stack, the only write once
+        // cells outside the prophet region, so prophets can't overwrite them
+        yield_constr.constraint(lv[COL_MEM_S_CODE] * lv[COL_MEM_CLK]);
+        yield_constr.constraint(lv[COL_MEM_S_CODE] * (P::ONES - region_stack));
+        yield_constr.constraint(lv[COL_MEM_S_PROPHET] * (P::ONES - region_prophet));
+
         // region is one of stack, heap and prophet
         yield_constr.constraint(P::ONES - region_stack - region_heap - region_prophet);
         yield_constr.constraint(region_stack * (P::ONES - region_stack));
@@ -257,14 +324,24 @@ impl<F: RichField + Extendable<D>, const D: usize> Stark<F, D> for MemoryStark<F
                 * nv_region_heap
                 * (P::ONES - nv_rw_addr_unchanged - nv_diff_addr * nv_diff_addr_inv),
         );
+        // a stack cell keeps its is_rw, so read only cells like mapped code can't
+        // be written by mstore
+        yield_constr.constraint_transition(
+            (P::ONES - nv[COL_MEM_TX_IDX] + lv[COL_MEM_TX_IDX])
+                * (P::ONES - nv[COL_MEM_ENV_IDX] + lv[COL_MEM_ENV_IDX])
+                * region_stack
+                * nv_region_stack
+                * nv_rw_addr_unchanged
+                * (nv[COL_MEM_IS_RW] - is_rw),
+        );
 
         // for write once:
         // 1. addr doesn't change or increase by 1 in prophet region;
         // 2. when addr not increase, must be read.
-        yield_constr.constraint(
+        yield_constr.constraint_transition(
             region_prophet * nv_region_prophet * (nv_addr - addr) * (nv_addr - addr - P::ONES),
         );
-        yield_constr.constraint(
+        yield_constr.constraint_transition(
             region_prophet * nv_region_prophet * (nv_addr - addr - P::ONES) * nv_is_write,
         );
 
@@ -307,11 +384,12 @@ impl<F: RichField + Extendable<D>, const D: usize> Stark<F, D> for MemoryStark<F
                 * (nv_value - P::Scalar::from_canonical_u64(INIT_VALUE_HEAP_PTR)),
         );
 
-        // rc_value constraint:
+        // rc_value constraint, for stack and heap, so a read only stack cell
+        // can't be visited twice:
         yield_constr.constraint_transition(
             (P::ONES - nv[COL_MEM_TX_IDX] + lv[COL_MEM_TX_IDX])
                 * (P::ONES - nv[COL_MEM_ENV_IDX] + lv[COL_MEM_ENV_IDX])
-                * is_rw
+                * (P::ONES - region_prophet)
                 * (nv_region_heap - region_heap - P::ONES)
                 * (rc_value - rw_addr_unchanged * diff_clk)
                 * (rc_value - (P::ONES - rw_addr_unchanged) * diff_addr),
@@ -319,7 +397,7 @@ impl<F: RichField + Extendable<D>, const D: usize> Stark<F, D> for MemoryStark<F
         yield_constr.constraint_transition(
             (P::ONES - nv[COL_MEM_TX_IDX] + lv[COL_MEM_TX_IDX])
                 * (P::ONES - nv[COL_MEM_ENV_IDX] + lv[COL_MEM_ENV_IDX])
-                * is_rw
+                * (P::ONES - region_prophet)
                 * rc_value
                 * (nv_region_heap - region_heap - P::ONES)
                 * (P::ONES - filter_looking_rc),
@@ -330,14 +408,43 @@ impl<F: RichField + Extendable<D>, const D: usize> Stark<F, D> for MemoryStark<F
         yield_constr.constraint(
             (P::ONES - lv_filter_looking_rc_cond) * region_prophet * (P::ONES - is_write),
         );
+
+        // padding rows are prophet writes after every cell, only unread prophet
+        // outputs can be taken for padding
+        let is_padding = lv[COL_MEM_IS_PADDING];
+        let nv_is_padding = nv[COL_MEM_IS_PADDING];
+        yield_constr.constraint(is_padding * (P::ONES - is_padding));
+        yield_constr.constraint_transition(is_padding * (P::ONES - nv_is_padding));
+        yield_constr.constraint(is_padding * (P::ONES - lv[COL_MEM_S_PROPHET]));
+        // export is the same on every row, looked up on the first one
+        let export = lv[COL_MEM_EXPORT];
+        yield_constr.constraint_first_row(P::ONES - lv[COL_MEM_IS_FIRST_ROW]);
+        yield_constr.constraint_transition(nv[COL_MEM_IS_FIRST_ROW]);
+        yield_constr.constraint(export * (P::ONES - export));
+        yield_constr.constraint_transition(nv[COL_MEM_EXPORT] - export);
+        // when exporting, a cell is final iff the next row is padding or of
+        // another address, the last row is final unless it is padding
+        let s_final = lv[COL_MEM_S_FINAL];
+        let is_last_cell = export * (P::ONES - is_padding) * (P::ONES - s_final);
+        yield_constr.constraint(s_final * (P::ONES - s_final));
+        yield_constr.constraint(s_final * (P::ONES - export + is_padding));
+        yield_constr.constraint_transition(is_last_cell * nv_is_padding);
+        yield_constr.constraint_transition(is_last_cell * (nv_addr - addr));
+        yield_constr.constraint_transition(
+            s_final
+                * (P::ONES - nv_is_padding)
+                * (P::ONES - (nv_addr - addr) * lv[COL_MEM_NEXT_DIFF_ADDR_INV]),
+        );
+        yield_constr.constraint_last_row(is_last_cell);
     }
 
     fn eval_ext_circuit(
         &self,
         _builder: &mut CircuitBuilder<F, D>,
-        _vars: StarkEvaluationTargets<D, NUM_MEM_COLS>,
+        _vars: StarkEvaluationTargets<D, { Self::COLUMNS /*HACKNUM_MEM_COLS*/ }>,
         _yield_constr: &mut RecursiveConstraintConsumer<F, D>,
     ) {
+        let _vars = StarkEvaluationTargets::<D, NUM_MEM_COLS> { local_values: _vars.local_values[..].try_into().unwrap(), next_values: _vars.next_values[..].try_into().unwrap() }; //HACK-VARS
     }
 
     fn constraint_degree(&self) -> usize {
@@ -346,13 +453,16 @@ impl<F: RichField + Extendable<D>, const D: usize> Stark<F, D> for MemoryStark<F
 }
 #[cfg(test)]
 mod tests {
-    use crate::generation::memory::generate_memory_trace;
-    use crate::memory::columns::{get_memory_col_name_map, NUM_MEM_COLS};
+    use crate::generation::memory::{end_memory_image, fill_end_memory, generate_memory_trace};
+    use crate::memory::columns::{
+        get_memory_col_name_map, COL_MEM_CLK, COL_MEM_DIFF_ADDR_INV, COL_MEM_DIFF_CLK,
+        COL_MEM_RW_ADDR_UNCHANGED, COL_MEM_S_FINAL, NUM_MEM_COLS,
+    };
     use crate::memory::memory_stark::MemoryStark;
     use crate::stark::constraint_consumer::ConstraintConsumer;
     use crate::stark::stark::Stark;
     use crate::stark::vars::StarkEvaluationVars;
-    use crate::test_utils::test_stark_with_asm_path;
+    use crate::test_utils::{test_stark_with_asm_path, test_stark_with_random_padding};
     use core::trace::trace::{MemoryTraceCell, Trace};
     use core::types::Field;
 
@@ -412,6 +522,77 @@ mod tests {
         test_memory_with_asm_file_name(program_path.to_string(), Some(call_data));
     }
 
+    #[test]
+    fn test_memory_export_end_image() {
+        let mut path = PathBuf::from(env!("CARGO_MANIFEST_DIR"));
+        path.push("../assembler/test_data/asm/memory.json");
+        let program_path = path.display().to_string();
+
+        const D: usize = 2;
+        type C = PoseidonGoldilocksConfig;
+        type F = <C as GenericConfig<D>>::F;
+        type S = MemoryStark<F, D>;
+        let stark = S::default();
+
+        let generate_trace = |rows: &Vec<MemoryTraceCell>| {
+            let mut trace = generate_memory_trace::<GoldilocksField>(rows);
+            fill_end_memory(&mut trace);
+            let finals = trace[COL_MEM_S_FINAL].iter().filter(|f| f.is_one()).count();
+            assert_eq!(finals, end_memory_image(rows).len());
+            trace
+        };
+        let eval_packed_generic =
+            |vars: StarkEvaluationVars<GoldilocksField, GoldilocksField, NUM_MEM_COLS>,
+             constraint_consumer: &mut ConstraintConsumer<GoldilocksField>| {
+                stark.eval_packed_generic(vars, constraint_consumer);
+            };
+        test_stark_with_asm_path(
+            program_path,
+            |trace: Trace| trace.memory,
+            generate_trace,
+            eval_packed_generic,
+            None::<fn(usize, StarkEvaluationVars<GoldilocksField, GoldilocksField, NUM_MEM_COLS>)>,
+            None,
+            None,
+        );
+    }
+
+    #[test]
+    fn test_memory_with_random_padding() {
+        let mut path = PathBuf::from(env!("CARGO_MANIFEST_DIR"));
+        path.push("../assembler/test_data/asm/memory.json");
+        let program_path = path.display().to_string();
+
+        const D: usize = 2;
+        type C = PoseidonGoldilocksConfig;
+        type F = <C as GenericConfig<D>>::F;
+        type S = MemoryStark<F, D>;
+        let stark = S::default();
+
+        let eval_packed_generic =
+            |vars: StarkEvaluationVars<GoldilocksField, GoldilocksField, NUM_MEM_COLS>,
+             constraint_consumer: &mut ConstraintConsumer<GoldilocksField>| {
+                stark.eval_packed_generic(vars, constraint_consumer);
+            };
+        // Padding rows are prophet writes, so the clk and the stack and heap
+        // ordering columns are free.
+        test_stark_with_random_padding(
+            program_path,
+            |trace: Trace| trace.memory,
+            |rows: &Vec<MemoryTraceCell>| generate_memory_trace(rows),
+            &[
+                COL_MEM_CLK,
+                COL_MEM_DIFF_CLK,
+                COL_MEM_DIFF_ADDR_INV,
+                COL_MEM_RW_ADDR_UNCHANGED,
+            ],
+            eval_packed_generic,
+            None::<fn(usize, StarkEvaluationVars<GoldilocksField, GoldilocksField, NUM_MEM_COLS>)>,
+            None,
+            None,
+        );
+    }
+
     #[allow(unused)]
     fn test_memory_with_asm_file_name(file_name: String, call_data: Option<Vec<GoldilocksField>>) {
         let mut path = PathBuf::from(env!("CARGO_MANIFEST_DIR"));
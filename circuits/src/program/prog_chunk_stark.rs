@@ -47,6 +47,14 @@ pub fn ctl_filter_to_poseidon<F: Field>() -> Column<F> {
     Column::linear_combination_with_constant([(COL_PROG_CHUNK_IS_PADDING_LINE, F::NEG_ONE)], F::ONE)
 }
 
+pub fn ctl_data_program_hash<F: Field>() -> Vec<Column<F>> {
+    Column::singles(COL_PROG_CHUNK_HASH_RANGE.take(4)).collect_vec()
+}
+
+pub fn ctl_filter_program_hash<F: Field>() -> Column<F> {
+    Column::single(COL_PROG_CHUNK_IS_RESULT_LINE)
+}
+
 pub fn ctl_data_to_storage_access<F: Field>() -> Vec<Column<F>> {
     let mut res: Vec<Column<F>> = vec![Column::zero()];
     res.extend(
@@ -235,7 +243,7 @@ mod tests {
                     GoldilocksField::from_canonical_u64(inst_u64)
                 })
                 .collect::<Vec<_>>();
-            generate_prog_chunk_trace(vec![(addr, insts)])
+            generate_prog_chunk_trace::<GoldilocksField>(vec![(addr, insts)]) //HACK-INFER
         };
         let eval_packed_generic =
             |vars: StarkEvaluationVars<GoldilocksField, GoldilocksField, NUM_PROG_CHUNK_COLS>,
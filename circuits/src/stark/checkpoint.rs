@@ -0,0 +1,236 @@
+use std::any::type_name;
+use std::fs;
+use std::io::ErrorKind;
+use std::path::{Path, PathBuf};
+
+use plonky2::field::extension::Extendable;
+use plonky2::field::polynomial::PolynomialValues;
+use plonky2::field::types::PrimeField64;
+use plonky2::fri::oracle::PolynomialBatch;
+use plonky2::hash::hash_types::RichField;
+use plonky2::plonk::config::GenericConfig;
+use sha2::{Digest, Sha256};
+
+use super::config::StarkConfig;
+use super::cross_table_lookup::CtlData;
+use super::error::ProverError;
+use super::ola_stark::{Table, NUM_TABLES};
+use super::proof::{PublicValues, StarkProof, PROOF_VERSION};
+use super::serialization::Buffer;
+
+const FINGERPRINT_FILE: &str = "fingerprint";
+const TRACE_COMMITMENTS_FILE: &str = "trace_commitments.bin";
+const CTL_ZS_FILE: &str = "ctl_zs.bin";
+
+fn table_proof_file(table: Table) -> String {
+    format!("table_{}.bin", table as usize)
+}
+
+/// Proving progress kept in a directory, so a proof interrupted by a crash or
+/// preemption continues from the last finished phase: the trace commitments,
+/// the cross-table lookup Z polynomials and each finished table proof.
+///
+/// A checkpoint belongs to the job with its fingerprint, see `fingerprint`.
+/// Files are replaced atomically, a crash while saving leaves the previous
+/// state of the checkpoint.
+pub struct ProverCheckpoint {
+    dir: PathBuf,
+}
+
+impl ProverCheckpoint {
+    /// Start a checkpoint in `dir` for the job with `fingerprint`, dropping
+    /// the files of an earlier checkpoint there.
+    pub fn create(dir: &Path, fingerprint: &str) -> Result<Self, ProverError> {
+        let checkpoint = Self {
+            dir: dir.to_path_buf(),
+        };
+        fs::create_dir_all(dir).map_err(|e| io_error(dir, e))?;
+        let files = [TRACE_COMMITMENTS_FILE.to_string(), CTL_ZS_FILE.to_string()]
+            .into_iter()
+            .chain((0..NUM_TABLES).map(|i| format!("table_{}.bin", i)));
+        for file in files {
+            let path = dir.join(file);
+            match fs::remove_file(&path) {
+                Err(e) if e.kind() != ErrorKind::NotFound => {
+                    return Err(io_error(&path, e));
+                }
+                _ => {}
+            }
+        }
+        checkpoint.write(FINGERPRINT_FILE, fingerprint.as_bytes().to_vec())?;
+        Ok(checkpoint)
+    }
+
+    /// Open the checkpoint in `dir`, which has to belong to the job with
+    /// `fingerprint`.
+    pub fn open(dir: &Path, fingerprint: &str) -> Result<Self, ProverError> {
+        let checkpoint = Self {
+            dir: dir.to_path_buf(),
+        };
+        let found = checkpoint
+            .read(FINGERPRINT_FILE)?
+            .ok_or_else(|| ProverError::Checkpoint(format!("no checkpoint in {:?}", dir)))?;
+        if found != fingerprint.as_bytes() {
+            return Err(ProverError::Checkpoint(format!(
+                "checkpoint in {:?} belongs to another job or config",
+                dir
+            )));
+        }
+        Ok(checkpoint)
+    }
+
+    pub(crate) fn load_trace_commitments<
+        F: RichField + Extendable<D>,
+        C: GenericConfig<D, F = F>,
+        const D: usize,
+    >(
+        &self,
+    ) -> Result<Option<Vec<PolynomialBatch<F, C, D>>>, ProverError> {
+        self.load(TRACE_COMMITMENTS_FILE, |buffer| {
+            (0..NUM_TABLES)
+                .map(|_| buffer.read_polynomial_batch::<F, C, D>())
+                .collect()
+        })
+    }
+
+    pub(crate) fn save_trace_commitments<
+        F: RichField + Extendable<D>,
+        C: GenericConfig<D, F = F>,
+        const D: usize,
+    >(
+        &self,
+        commitments: &[PolynomialBatch<F, C, D>],
+    ) -> Result<(), ProverError> {
+        self.save(TRACE_COMMITMENTS_FILE, |buffer| {
+            commitments
+                .iter()
+                .try_for_each(|c| buffer.write_polynomial_batch(c))
+        })
+    }
+
+    pub(crate) fn load_ctl_zs<F: RichField>(
+        &self,
+    ) -> Result<Option<[Vec<PolynomialValues<F>>; NUM_TABLES]>, ProverError> {
+        let zs = self.load(CTL_ZS_FILE, |buffer| {
+            (0..NUM_TABLES)
+                .map(|_| buffer.read_poly_values_vec())
+                .collect::<std::io::Result<Vec<_>>>()
+        })?;
+        Ok(zs.map(|zs| zs.try_into().unwrap()))
+    }
+
+    pub(crate) fn save_ctl_zs<F: RichField>(
+        &self,
+        ctl_data_per_table: &[CtlData<F>; NUM_TABLES],
+    ) -> Result<(), ProverError> {
+        self.save(CTL_ZS_FILE, |buffer| {
+            ctl_data_per_table
+                .iter()
+                .try_for_each(|ctl_data| buffer.write_poly_values_vec(&ctl_data.z_polys()))
+        })
+    }
+
+    pub(crate) fn load_table_proof<
+        F: RichField + Extendable<D>,
+        C: GenericConfig<D, F = F>,
+        const D: usize,
+    >(
+        &self,
+        table: Table,
+    ) -> Result<Option<StarkProof<F, C, D>>, ProverError> {
+        self.load(&table_proof_file(table), |buffer| buffer.read_proof())
+    }
+
+    pub(crate) fn save_table_proof<
+        F: RichField + Extendable<D>,
+        C: GenericConfig<D, F = F>,
+        const D: usize,
+    >(
+        &self,
+        table: Table,
+        proof: &StarkProof<F, C, D>,
+    ) -> Result<(), ProverError> {
+        self.save(&table_proof_file(table), |buffer| buffer.write_proof(proof))
+    }
+
+    fn load<T>(
+        &self,
+        file: &str,
+        read: impl FnOnce(&mut Buffer) -> std::io::Result<T>,
+    ) -> Result<Option<T>, ProverError> {
+        match self.read(file)? {
+            Some(bytes) => read(&mut Buffer::new(bytes))
+                .map(Some)
+                .map_err(|e| io_error(&self.dir.join(file), e)),
+            None => Ok(None),
+        }
+    }
+
+    fn save(
+        &self,
+        file: &str,
+        write: impl FnOnce(&mut Buffer) -> std::io::Result<()>,
+    ) -> Result<(), ProverError> {
+        let mut buffer = Buffer::new(Vec::new());
+        write(&mut buffer).map_err(|e| io_error(&self.dir.join(file), e))?;
+        self.write(file, buffer.bytes())
+    }
+
+    fn read(&self, file: &str) -> Result<Option<Vec<u8>>, ProverError> {
+        let path = self.dir.join(file);
+        match fs::read(&path) {
+            Ok(bytes) => Ok(Some(bytes)),
+            Err(e) if e.kind() == ErrorKind::NotFound => Ok(None),
+            Err(e) => Err(io_error(&path, e)),
+        }
+    }
+
+    fn write(&self, file: &str, bytes: Vec<u8>) -> Result<(), ProverError> {
+        let path = self.dir.join(file);
+        let tmp = self.dir.join(format!("{}.tmp", file));
+        fs::write(&tmp, bytes).map_err(|e| io_error(&tmp, e))?;
+        fs::rename(&tmp, &path).map_err(|e| io_error(&path, e))
+    }
+}
+
+fn io_error(path: &Path, e: std::io::Error) -> ProverError {
+    ProverError::Checkpoint(format!("{:?}: {}", path, e))
+}
+
+/// Identify a proving job by the proof version, the hasher and stark
+/// configs, the traces and the public values. A checkpoint is only resumed by
+/// the job it was created for.
+pub fn fingerprint<F, C, const D: usize>(
+    config: &StarkConfig,
+    trace_poly_values: &[Vec<PolynomialValues<F>>; NUM_TABLES],
+    public_values: &PublicValues,
+) -> String
+where
+    F: RichField + Extendable<D>,
+    C: GenericConfig<D, F = F>,
+{
+    let mut hasher = Sha256::new();
+    hasher.update(PROOF_VERSION.to_le_bytes());
+    hasher.update(type_name::<C>().as_bytes());
+    hasher.update((D as u64).to_le_bytes());
+    hasher.update((config.security_bits as u64).to_le_bytes());
+    hasher.update((config.num_challenges as u64).to_le_bytes());
+    hasher.update(format!("{:?}", config.transcript_hasher).as_bytes());
+    for i in 0..NUM_TABLES {
+        hasher.update(format!("{:?}", config.for_table_index(i).fri_config).as_bytes());
+    }
+    for trace in trace_poly_values {
+        hasher.update((trace.len() as u64).to_le_bytes());
+        for column in trace {
+            let bytes = column
+                .values
+                .iter()
+                .flat_map(|v| v.to_canonical_u64().to_le_bytes())
+                .collect::<Vec<_>>();
+            hasher.update((column.len() as u64).to_le_bytes());
+            hasher.update(bytes);
+        }
+    }
+    hasher.update(serde_json::to_vec(public_values).unwrap());
+    format!("{:x}", hasher.finalize())
+}
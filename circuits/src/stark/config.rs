@@ -1,6 +1,36 @@
+use core::vm::chain_spec::ChainSpec;
 use plonky2::fri::reduction_strategies::FriReductionStrategy;
 use plonky2::fri::{FriConfig, FriParams};
+use plonky2::hash::keccak::KeccakHash;
 
+use super::error::ConfigError;
+use super::ola_stark::{Table, NUM_TABLES};
+
+/// FRI parameters of a single table that replace the shared ones. The rate is
+/// not overridable, it bounds the constraint degree of every table.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct TableFriOverride {
+    pub cap_height: usize,
+    pub num_query_rounds: usize,
+    pub proof_of_work_bits: u32,
+}
+
+/// Hash of the Fiat-Shamir transcript the challenges are drawn from. Merkle
+/// caps are still hashed with the hasher of the `GenericConfig`.
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq)]
+pub enum TranscriptHasher {
+    /// The hasher of the `GenericConfig`, Poseidon in the configs the VM is
+    /// proven with.
+    #[default]
+    Poseidon,
+    /// Cheaper to replay in an EVM verifier.
+    Keccak,
+}
+
+/// Keccak hasher of the transcript, as in `KeccakGoldilocksConfig`.
+pub(crate) type KeccakTranscriptHash = KeccakHash<25>;
+
+#[derive(Clone)]
 pub struct StarkConfig {
     pub security_bits: usize,
 
@@ -9,6 +39,13 @@ pub struct StarkConfig {
     pub num_challenges: usize,
 
     pub fri_config: FriConfig,
+
+    /// Prover and verifier have to use the same one.
+    pub transcript_hasher: TranscriptHasher,
+
+    table_overrides: [Option<TableFriOverride>; NUM_TABLES],
+
+    spot_check_rate: Option<f64>,
 }
 
 impl StarkConfig {
@@ -26,6 +63,128 @@ impl StarkConfig {
                 reduction_strategy: FriReductionStrategy::ConstantArityBits(4, 5),
                 num_query_rounds: 28,
             },
+            transcript_hasher: TranscriptHasher::Poseidon,
+            table_overrides: [None; NUM_TABLES],
+            spot_check_rate: None,
+        }
+    }
+
+    /// Fast config of about 40 bits of conjectured security, for tests and
+    /// fixtures only.
+    pub fn dev_config() -> Self {
+        Self {
+            security_bits: 40,
+            num_challenges: 2,
+            fri_config: FriConfig {
+                rate_bits: 3,
+                cap_height: 2,
+                proof_of_work_bits: 16,
+                reduction_strategy: FriReductionStrategy::ConstantArityBits(4, 5),
+                num_query_rounds: 8,
+            },
+            transcript_hasher: TranscriptHasher::Poseidon,
+            table_overrides: [None; NUM_TABLES],
+            spot_check_rate: None,
+        }
+    }
+
+    /// The config the `prover` section of a chain spec asks for.
+    pub fn from_chain_spec(spec: &ChainSpec) -> Result<Self, ConfigError> {
+        let prover = &spec.prover;
+        let config = Self {
+            security_bits: prover.security_bits,
+            num_challenges: 2,
+            fri_config: FriConfig {
+                rate_bits: prover.rate_bits,
+                cap_height: prover.cap_height,
+                proof_of_work_bits: prover.proof_of_work_bits,
+                reduction_strategy: FriReductionStrategy::ConstantArityBits(4, 5),
+                num_query_rounds: prover.num_query_rounds,
+            },
+            transcript_hasher: TranscriptHasher::Poseidon,
+            table_overrides: [None; NUM_TABLES],
+            spot_check_rate: None,
+        };
+        let bits =
+            config.conjectured_security_bits(prover.num_query_rounds, prover.proof_of_work_bits);
+        if bits < config.security_bits {
+            return Err(ConfigError::InsufficientChainSecurity(
+                bits,
+                config.security_bits,
+            ));
+        }
+        Ok(config)
+    }
+
+    /// Conjectured security of FRI with the given parameters.
+    fn conjectured_security_bits(&self, num_query_rounds: usize, proof_of_work_bits: u32) -> usize {
+        self.fri_config.rate_bits * num_query_rounds + proof_of_work_bits as usize
+    }
+
+    /// Prove `table` with its own cap height and query count, e.g. fewer
+    /// queries and a lower cap for tables that stay small. The override must
+    /// keep the conjectured security of the whole config. Prover and verifier
+    /// have to agree on the overrides.
+    pub fn with_table_override(
+        mut self,
+        table: Table,
+        fri_override: TableFriOverride,
+    ) -> Result<Self, ConfigError> {
+        let bits = self.conjectured_security_bits(
+            fri_override.num_query_rounds,
+            fri_override.proof_of_work_bits,
+        );
+        if bits < self.security_bits {
+            return Err(ConfigError::InsufficientSecurity(
+                table,
+                bits,
+                self.security_bits,
+            ));
+        }
+        self.table_overrides[table as usize] = Some(fri_override);
+        Ok(self)
+    }
+
+    /// Draw the challenges from a transcript hashed with `hasher`.
+    pub fn with_transcript_hasher(mut self, hasher: TranscriptHasher) -> Self {
+        self.transcript_hasher = hasher;
+        self
+    }
+
+    /// Before proving, evaluate the constraints on about `rate` of the rows
+    /// of every table, e.g. 0.01, and fail fast if one is violated.
+    pub fn with_spot_check(mut self, rate: f64) -> Self {
+        self.spot_check_rate = Some(rate);
+        self
+    }
+
+    pub fn spot_check_rate(&self) -> Option<f64> {
+        self.spot_check_rate
+    }
+
+    pub fn table_override(&self, table: Table) -> Option<TableFriOverride> {
+        self.table_overrides[table as usize]
+    }
+
+    /// The config a single table is proven and verified with.
+    pub fn for_table(&self, table: Table) -> StarkConfig {
+        self.for_table_index(table as usize)
+    }
+
+    pub(crate) fn for_table_index(&self, index: usize) -> StarkConfig {
+        let mut fri_config = self.fri_config.clone();
+        if let Some(fri_override) = self.table_overrides[index] {
+            fri_config.cap_height = fri_override.cap_height;
+            fri_config.num_query_rounds = fri_override.num_query_rounds;
+            fri_config.proof_of_work_bits = fri_override.proof_of_work_bits;
+        }
+        StarkConfig {
+            security_bits: self.security_bits,
+            num_challenges: self.num_challenges,
+            fri_config,
+            transcript_hasher: self.transcript_hasher,
+            table_overrides: [None; NUM_TABLES],
+            spot_check_rate: None,
         }
     }
 
@@ -33,3 +192,61 @@ impl StarkConfig {
         self.fri_config.fri_params(degree_bits, false)
     }
 }
+
+#[cfg(test)]
+mod tests {
+    use core::vm::chain_spec::ChainSpec;
+
+    use crate::stark::config::{StarkConfig, TableFriOverride};
+    use crate::stark::error::ConfigError;
+    use crate::stark::ola_stark::Table;
+
+    #[test]
+    fn test_table_override() {
+        let config = StarkConfig::standard_fast_config()
+            .with_table_override(
+                Table::Tape,
+                TableFriOverride {
+                    cap_height: 1,
+                    num_query_rounds: 23,
+                    proof_of_work_bits: 31,
+                },
+            )
+            .unwrap();
+        let tape = config.for_table(Table::Tape);
+        assert_eq!(tape.fri_config.cap_height, 1);
+        assert_eq!(tape.fri_config.num_query_rounds, 23);
+        assert_eq!(tape.fri_config.rate_bits, config.fri_config.rate_bits);
+        assert_eq!(config.for_table(Table::Cpu).fri_config.cap_height, 4);
+        assert!(config.table_override(Table::Cpu).is_none());
+
+        let weak = StarkConfig::standard_fast_config().with_table_override(
+            Table::SCCall,
+            TableFriOverride {
+                cap_height: 1,
+                num_query_rounds: 20,
+                proof_of_work_bits: 16,
+            },
+        );
+        assert!(matches!(
+            weak,
+            Err(ConfigError::InsufficientSecurity(Table::SCCall, 76, 100))
+        ));
+    }
+
+    #[test]
+    fn test_config_from_chain_spec() {
+        let mut spec = ChainSpec::default();
+        let config = StarkConfig::from_chain_spec(&spec).unwrap();
+        let standard = StarkConfig::standard_fast_config();
+        assert_eq!(config.security_bits, standard.security_bits);
+        assert_eq!(config.fri_config.num_query_rounds, 28);
+        assert_eq!(config.fri_config.cap_height, 4);
+
+        spec.prover.num_query_rounds = 20;
+        assert!(matches!(
+            StarkConfig::from_chain_spec(&spec),
+            Err(ConfigError::InsufficientChainSecurity(76, 100))
+        ));
+    }
+}
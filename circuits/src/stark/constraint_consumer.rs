@@ -29,6 +29,10 @@ pub struct ConstraintConsumer<P: PackedField> {
     /// point associated with the last trace row, and zero at other points
     /// in the subgroup.
     lagrange_basis_last: P,
+
+    /// Every emitted constraint in emission order, only kept by consumers
+    /// built with `new_recording`.
+    recorded: Option<Vec<P>>,
 }
 
 impl<P: PackedField> ConstraintConsumer<P> {
@@ -44,6 +48,16 @@ impl<P: PackedField> ConstraintConsumer<P> {
             z_last,
             lagrange_basis_first,
             lagrange_basis_last,
+            recorded: None,
+        }
+    }
+
+    /// A consumer that keeps each constraint value instead of combining them,
+    /// so callers can inspect constraints individually.
+    pub fn new_recording(z_last: P, lagrange_basis_first: P, lagrange_basis_last: P) -> Self {
+        Self {
+            recorded: Some(vec![]),
+            ..Self::new(vec![], z_last, lagrange_basis_first, lagrange_basis_last)
         }
     }
 
@@ -51,6 +65,12 @@ impl<P: PackedField> ConstraintConsumer<P> {
         self.constraint_accs
     }
 
+    /// The constraints emitted so far, empty unless built with
+    /// `new_recording`.
+    pub fn recorded(self) -> Vec<P> {
+        self.recorded.unwrap_or_default()
+    }
+
     /// Add one constraint valid on all rows except the last.
     pub fn constraint_transition(&mut self, constraint: P) {
         self.constraint(constraint * self.z_last);
@@ -58,6 +78,9 @@ impl<P: PackedField> ConstraintConsumer<P> {
 
     /// Add one constraint on all rows.
     pub fn constraint(&mut self, constraint: P) {
+        if let Some(recorded) = &mut self.recorded {
+            recorded.push(constraint);
+        }
         for (&alpha, acc) in self.alphas.iter().zip(&mut self.constraint_accs) {
             *acc *= alpha;
             *acc += constraint;
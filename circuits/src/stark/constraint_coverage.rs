@@ -0,0 +1,210 @@
+use std::fmt::{Display, Formatter};
+
+use plonky2::field::extension::Extendable;
+use plonky2::field::types::Field;
+use plonky2::hash::hash_types::RichField;
+use plonky2_util::log2_strict;
+
+use super::constraint_consumer::ConstraintConsumer;
+use super::ola_stark::Table;
+use super::stark::Stark;
+use super::vars::StarkEvaluationVars;
+
+/// How often each constraint of a STARK was exercised by one trace.
+///
+/// A constraint is exercised on a row when it is sensitive to the row: moving
+/// the row's values changes the constraint to first order. A selector that is
+/// zero together with its term masks the constraint out, so rows that never
+/// trigger the constraint do not count, while a zero selector whose term is
+/// non-zero does, since flipping the selector would be caught.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct ConstraintCoverage {
+    pub table: Table,
+    pub rows: usize,
+    /// Number of exercising rows per constraint, in emission order.
+    pub hits: Vec<usize>,
+}
+
+impl ConstraintCoverage {
+    pub fn exercised(&self) -> usize {
+        self.hits.iter().filter(|&&hits| hits > 0).count()
+    }
+
+    /// Indices of the constraints no row exercised.
+    pub fn dead(&self) -> Vec<usize> {
+        self.hits
+            .iter()
+            .enumerate()
+            .filter(|(_, &hits)| hits == 0)
+            .map(|(i, _)| i)
+            .collect()
+    }
+}
+
+impl Display for ConstraintCoverage {
+    fn fmt(&self, f: &mut Formatter<'_>) -> std::fmt::Result {
+        write!(
+            f,
+            "{:?}: {}/{} constraints exercised over {} rows",
+            self.table,
+            self.exercised(),
+            self.hits.len(),
+            self.rows
+        )?;
+        for (i, hits) in self.hits.iter().enumerate() {
+            write!(f, "\n  #{:<4} {}", i, hits)?;
+        }
+        Ok(())
+    }
+}
+
+/// Measure which constraints of `stark` the column-major `trace` exercises.
+///
+/// Each row is moved along a random direction `x + t * r` and the constraints
+/// are evaluated at `t = 0..=degree`, which is enough to interpolate them and
+/// read off their derivative at `t = 0`. A constraint with a non-zero
+/// derivative is exercised on that row; the chance of a random direction
+/// hiding a sensitive constraint is negligible.
+pub fn constraint_coverage<F, S, const D: usize>(
+    table: Table,
+    stark: &S,
+    trace: &[Vec<F>],
+) -> ConstraintCoverage
+where
+    F: RichField + Extendable<D>,
+    S: Stark<F, D>,
+    [(); S::COLUMNS]:,
+{
+    assert_eq!(trace.len(), S::COLUMNS, "trace width mismatch");
+    let len = trace[0].len();
+    let degree = stark.constraint_degree();
+    let weights = derivative_weights::<F>(degree);
+    let last = F::primitive_root_of_unity(log2_strict(len)).inverse();
+    let subgroup =
+        F::cyclic_subgroup_known_order(F::primitive_root_of_unity(log2_strict(len)), len);
+
+    let mut hits: Vec<usize> = vec![];
+    for i in 0..len {
+        let local: [F; S::COLUMNS] = std::array::from_fn(|c| trace[c][i]);
+        let next: [F; S::COLUMNS] = std::array::from_fn(|c| trace[c][(i + 1) % len]);
+        let local_dir: [F; S::COLUMNS] = std::array::from_fn(|_| F::rand());
+        let next_dir: [F; S::COLUMNS] = std::array::from_fn(|_| F::rand());
+
+        let mut derivative: Vec<F> = vec![];
+        for (t, &weight) in weights.iter().enumerate() {
+            let t = F::from_canonical_usize(t);
+            let local_values: [F; S::COLUMNS] =
+                std::array::from_fn(|c| local[c] + t * local_dir[c]);
+            let next_values: [F; S::COLUMNS] = std::array::from_fn(|c| next[c] + t * next_dir[c]);
+            let mut consumer = ConstraintConsumer::new_recording(
+                subgroup[i] - last,
+                if i == 0 { F::ONE } else { F::ZERO },
+                if i == len - 1 { F::ONE } else { F::ZERO },
+            );
+            stark.eval_packed_generic::<F, F, 1>(
+                StarkEvaluationVars {
+                    local_values: &local_values,
+                    next_values: &next_values,
+                },
+                &mut consumer,
+            );
+            let values = consumer.recorded();
+            derivative.resize(values.len(), F::ZERO);
+            for (acc, value) in derivative.iter_mut().zip(values) {
+                *acc += weight * value;
+            }
+        }
+
+        hits.resize(derivative.len(), 0);
+        for (hit, value) in hits.iter_mut().zip(derivative) {
+            if value != F::ZERO {
+                *hit += 1;
+            }
+        }
+    }
+
+    ConstraintCoverage {
+        table,
+        rows: len,
+        hits,
+    }
+}
+
+/// Weights `w_t` with `p'(0) = sum_t w_t * p(t)` for every polynomial `p` of
+/// degree at most `degree`, i.e. the derivatives at zero of the Lagrange basis
+/// over the nodes `0..=degree`.
+fn derivative_weights<F: Field>(degree: usize) -> Vec<F> {
+    let node = |m: usize| F::from_canonical_usize(m);
+    let mut weights = vec![-(1..=degree).map(|m| node(m).inverse()).sum::<F>()];
+    for j in 1..=degree {
+        let numerator: F = (1..=degree).filter(|&m| m != j).map(|m| -node(m)).product();
+        let denominator: F = (0..=degree)
+            .filter(|&m| m != j)
+            .map(|m| node(j) - node(m))
+            .product();
+        weights.push(numerator / denominator);
+    }
+    weights
+}
+
+#[cfg(test)]
+mod tests {
+    use core::trace::trace::CmpRow;
+
+    use plonky2::field::goldilocks_field::GoldilocksField;
+    use plonky2::field::types::Field;
+    use plonky2::plonk::config::{GenericConfig, PoseidonGoldilocksConfig};
+
+    use super::{constraint_coverage, derivative_weights};
+    use crate::builtins::cmp::cmp_stark::CmpStark;
+    use crate::generation::builtin::generate_cmp_trace;
+    use crate::stark::ola_stark::Table;
+
+    const D: usize = 2;
+    type C = PoseidonGoldilocksConfig;
+    type F = <C as GenericConfig<D>>::F;
+
+    #[test]
+    fn test_derivative_weights() {
+        // p(t) = 3 + 5t - 2t^2 + 7t^3
+        let p = |t: u64| {
+            let t = GoldilocksField::from_canonical_u64(t);
+            GoldilocksField::from_canonical_u64(3) + GoldilocksField::from_canonical_u64(5) * t
+                - GoldilocksField::TWO * t * t
+                + GoldilocksField::from_canonical_u64(7) * t * t * t
+        };
+        let derivative: GoldilocksField = derivative_weights::<GoldilocksField>(4)
+            .iter()
+            .enumerate()
+            .map(|(t, &w)| w * p(t as u64))
+            .sum();
+        assert_eq!(derivative, GoldilocksField::from_canonical_u64(5));
+    }
+
+    #[test]
+    fn test_cmp_constraint_coverage() {
+        let stark = CmpStark::<F, D>::default();
+
+        // Padding rows all have gte = 1, which never reaches the inverse
+        // constraint of the lt branch.
+        let padding = generate_cmp_trace::<F>(&[]);
+        let coverage = constraint_coverage(Table::Cmp, &stark, &padding);
+        assert_eq!(coverage.rows, 2);
+        assert_eq!(coverage.hits.len(), 4);
+        assert_eq!(coverage.dead(), vec![3]);
+
+        let lt = CmpRow {
+            op0: F::ONE,
+            op1: F::TWO,
+            gte: F::ZERO,
+            abs_diff: F::ONE,
+            abs_diff_inv: F::ONE,
+            filter_looking_rc: F::ONE,
+        };
+        let trace = generate_cmp_trace::<F>(&[lt]);
+        let coverage = constraint_coverage(Table::Cmp, &stark, &trace);
+        assert!(coverage.dead().is_empty());
+        assert_eq!(coverage.exercised(), 4);
+        assert_eq!(coverage.hits[3], 1);
+    }
+}
@@ -1,26 +1,27 @@
 use std::borrow::Borrow;
 use std::iter::repeat;
 
-use anyhow::{ensure, Result};
 use itertools::Itertools;
 use plonky2::field::extension::{Extendable, FieldExtension};
 use plonky2::field::packed::PackedField;
 use plonky2::field::polynomial::PolynomialValues;
-use plonky2::field::types::Field;
+use plonky2::field::types::{Field, PrimeField64};
 use plonky2::hash::hash_types::RichField;
 use plonky2::iop::challenger::Challenger;
 use plonky2::iop::ext_target::ExtensionTarget;
 use plonky2::iop::target::Target;
 use plonky2::plonk::circuit_builder::CircuitBuilder;
-use plonky2::plonk::config::GenericConfig;
+use plonky2::plonk::config::{GenericConfig, Hasher};
 
 use super::config::StarkConfig;
 use super::constraint_consumer::{ConstraintConsumer, RecursiveConstraintConsumer};
+use super::ctl_audit::{CtlDescription, CtlTableDescription};
+use super::error::VerifierError;
 use super::ola_stark::{Table, NUM_TABLES};
 use super::permutation::{
     get_grand_product_challenge_set, GrandProductChallenge, GrandProductChallengeSet,
 };
-use super::proof::{StarkProof, StarkProofTarget};
+use super::proof::{PublicValues, StarkProof, StarkProofTarget};
 use super::stark::Stark;
 use super::vars::{StarkEvaluationTargets, StarkEvaluationVars};
 
@@ -118,6 +119,40 @@ impl<F: Field> Column<F> {
             + self.constant
     }
 
+    /// Render as `c3 + 2*c4 + 1`, columns by index.
+    pub fn describe(&self) -> String {
+        let mut terms = self
+            .linear_combination
+            .iter()
+            .map(|&(c, f)| {
+                if f.is_one() {
+                    format!("c{}", c)
+                } else if (-f).is_one() {
+                    format!("-c{}", c)
+                } else {
+                    format!("{}*c{}", f, c)
+                }
+            })
+            .collect::<Vec<_>>();
+        if !self.constant.is_zero() || terms.is_empty() {
+            terms.push(self.constant.to_string());
+        }
+        terms.join(" + ")
+    }
+
+    /// The `(column, coefficient)` terms and the constant.
+    pub fn terms(&self) -> (Vec<(usize, u64)>, u64)
+    where
+        F: PrimeField64,
+    {
+        let terms = self
+            .linear_combination
+            .iter()
+            .map(|&(c, f)| (c, f.to_canonical_u64()))
+            .collect();
+        (terms, self.constant.to_canonical_u64())
+    }
+
     pub fn eval_circuit<const D: usize>(
         &self,
         builder: &mut CircuitBuilder<F, D>,
@@ -156,12 +191,41 @@ impl<F: Field> TableWithColumns<F> {
             filter_column,
         }
     }
+
+    pub fn table(&self) -> Table {
+        self.table
+    }
+
+    pub fn columns(&self) -> &[Column<F>] {
+        &self.columns
+    }
+
+    pub fn filter_column(&self) -> Option<&Column<F>> {
+        self.filter_column.as_ref()
+    }
+
+    pub fn describe(&self) -> CtlTableDescription {
+        CtlTableDescription {
+            table: self.table,
+            columns: self.columns.iter().map(|c| c.describe()).collect(),
+            filter: self.filter_column.as_ref().map(|c| c.describe()),
+        }
+    }
 }
 
+/// Rows the verifier looks up itself, built from the public values.
+pub type PublicRows<F> = fn(&PublicValues) -> Vec<Vec<F>>;
+
+/// Public rows with the number of times the looked table holds each of them.
+pub type CountedPublicRows<F> = fn(&PublicValues) -> Vec<(Vec<F>, u64)>;
+
 #[derive(Clone)]
 pub struct CrossTableLookup<F: Field> {
+    name: &'static str,
     looking_tables: Vec<TableWithColumns<F>>,
     looked_table: TableWithColumns<F>,
+    public_rows: Option<PublicRows<F>>,
+    counted_public_rows: Option<CountedPublicRows<F>>,
 }
 
 impl<F: Field> CrossTableLookup<F> {
@@ -173,8 +237,88 @@ impl<F: Field> CrossTableLookup<F> {
             .iter()
             .all(|twc| twc.columns.len() == looked_table.columns.len()));
         Self {
+            name: "",
             looking_tables,
             looked_table,
+            public_rows: None,
+            counted_public_rows: None,
+        }
+    }
+
+    /// A lookup of the rows `public_rows` makes of the public values, which
+    /// the verifier folds into the looking product itself.
+    pub fn public(public_rows: PublicRows<F>, looked_table: TableWithColumns<F>) -> Self {
+        Self {
+            public_rows: Some(public_rows),
+            ..Self::new(vec![], looked_table)
+        }
+    }
+
+    /// Like `public`, for rows the looked table holds many times, which the
+    /// verifier folds in once per count.
+    pub fn public_counted(
+        counted_public_rows: CountedPublicRows<F>,
+        looked_table: TableWithColumns<F>,
+    ) -> Self {
+        Self {
+            counted_public_rows: Some(counted_public_rows),
+            ..Self::new(vec![], looked_table)
+        }
+    }
+
+    fn has_public_rows(&self) -> bool {
+        self.public_rows.is_some() || self.counted_public_rows.is_some()
+    }
+
+    pub fn named(mut self, name: &'static str) -> Self {
+        self.name = name;
+        self
+    }
+
+    /// Product of the combined public rows under every challenge, one when
+    /// the lookup has none.
+    pub(crate) fn extra_looking_products(
+        &self,
+        public_values: &PublicValues,
+        challenges: &GrandProductChallengeSet<F>,
+    ) -> Vec<F> {
+        let mut rows = self.public_rows.map_or(vec![], |rows| {
+            rows(public_values)
+                .into_iter()
+                .map(|row| (row, 1))
+                .collect()
+        });
+        if let Some(counted_rows) = self.counted_public_rows {
+            rows.extend(counted_rows(public_values));
+        }
+        challenges
+            .challenges
+            .iter()
+            .map(|challenge| {
+                rows.iter()
+                    .map(|(row, count)| challenge.combine(row.iter()).exp_u64(*count))
+                    .product()
+            })
+            .collect()
+    }
+
+    pub fn name(&self) -> &'static str {
+        self.name
+    }
+
+    pub fn looking_tables(&self) -> &[TableWithColumns<F>] {
+        &self.looking_tables
+    }
+
+    pub fn looked_table(&self) -> &TableWithColumns<F> {
+        &self.looked_table
+    }
+
+    pub fn describe(&self) -> CtlDescription {
+        CtlDescription {
+            name: self.name.to_string(),
+            looking: self.looking_tables.iter().map(|t| t.describe()).collect(),
+            looked: self.looked_table.describe(),
         }
     }
 
@@ -225,37 +369,84 @@ pub fn cross_table_lookup_data<F: RichField, C: GenericConfig<D, F = F>, const D
     config: &StarkConfig,
     trace_poly_values: &[Vec<PolynomialValues<F>>; NUM_TABLES],
     cross_table_lookups: &[CrossTableLookup<F>],
-    challenger: &mut Challenger<F, C::Hasher>,
+    challenger: &mut Challenger<F, impl Hasher<F>>,
+) -> [CtlData<F>; NUM_TABLES] {
+    build_ctl_data::<F, C, D>(
+        config,
+        cross_table_lookups,
+        challenger,
+        |table, challenge| {
+            partial_products(
+                &trace_poly_values[table.table as usize],
+                &table.columns,
+                &table.filter_column,
+                challenge,
+            )
+        },
+    )
+}
+
+/// Like `cross_table_lookup_data`, but takes the Z polynomials of every table
+/// from `z_polys`, in the order of `CtlData::z_polys`, instead of computing
+/// them from the traces.
+pub(crate) fn restore_cross_table_lookup_data<
+    F: RichField,
+    C: GenericConfig<D, F = F>,
+    const D: usize,
+>(
+    config: &StarkConfig,
+    z_polys: [Vec<PolynomialValues<F>>; NUM_TABLES],
+    cross_table_lookups: &[CrossTableLookup<F>],
+    challenger: &mut Challenger<F, impl Hasher<F>>,
+) -> Option<[CtlData<F>; NUM_TABLES]> {
+    let mut z_polys = z_polys.map(|zs| zs.into_iter());
+    let mut missing = false;
+    let ctl_data =
+        build_ctl_data::<F, C, D>(config, cross_table_lookups, challenger, |table, _| {
+            z_polys[table.table as usize].next().unwrap_or_else(|| {
+                missing = true;
+                PolynomialValues::zero(0)
+            })
+        });
+    let exhausted = z_polys.iter_mut().all(|zs| zs.next().is_none());
+    (!missing && exhausted).then_some(ctl_data)
+}
+
+fn build_ctl_data<F: RichField, C: GenericConfig<D, F = F>, const D: usize>(
+    config: &StarkConfig,
+    cross_table_lookups: &[CrossTableLookup<F>],
+    challenger: &mut Challenger<F, impl Hasher<F>>,
+    mut z_poly: impl FnMut(&TableWithColumns<F>, GrandProductChallenge<F>) -> PolynomialValues<F>,
 ) -> [CtlData<F>; NUM_TABLES] {
     let challenges = get_grand_product_challenge_set(challenger, config.num_challenges);
     let mut ctl_data_per_table = [0; NUM_TABLES].map(|_| CtlData::default());
-    for CrossTableLookup {
-        looking_tables,
-        looked_table,
-    } in cross_table_lookups
-    {
+    for ctl in cross_table_lookups {
+        let CrossTableLookup {
+            looking_tables,
+            looked_table,
+            ..
+        } = ctl;
         for &challenge in &challenges.challenges {
-            let zs_looking = looking_tables.iter().map(|table| {
-                partial_products(
-                    &trace_poly_values[table.table as usize],
-                    &table.columns,
-                    &table.filter_column,
-                    challenge,
-                )
-            });
-            let z_looked = partial_products(
-                &trace_poly_values[looked_table.table as usize],
-                &looked_table.columns,
-                &looked_table.filter_column,
-                challenge,
-            );
+            let zs_looking = looking_tables
+                .iter()
+                .map(|table| z_poly(table, challenge))
+                .collect::<Vec<_>>();
+            let z_looked = z_poly(looked_table, challenge);
 
-            debug_assert_eq!(
-                zs_looking
-                    .clone()
-                    .map(|z| *z.values.last().unwrap())
-                    .product::<F>(),
-                *z_looked.values.last().unwrap()
+            // restored Z polys can be missing, checked by the caller. Public
+            // rows are only known to match once the verifier checks them.
+            let complete = !ctl.has_public_rows()
+                && zs_looking
+                    .iter()
+                    .chain([&z_looked])
+                    .all(|z| !z.values.is_empty());
+            debug_assert!(
+                !complete
+                    || zs_looking
+                        .iter()
+                        .map(|z| *z.values.last().unwrap())
+                        .product::<F>()
+                        == *z_looked.values.last().unwrap()
             );
 
             for (table, z) in looking_tables.iter().zip(zs_looking) {
@@ -557,14 +748,18 @@ pub(crate) fn verify_cross_table_lookups<
     ctl_zs_lasts: [Vec<F>; NUM_TABLES],
     ctl_extra_looking_products: Vec<Vec<F>>,
     config: &StarkConfig,
-) -> Result<()> {
+) -> Result<(), VerifierError> {
+    debug_assert_eq!(ctl_extra_looking_products.len(), cross_table_lookups.len());
     let mut ctl_zs_openings = ctl_zs_lasts.iter().map(|v| v.iter()).collect::<Vec<_>>();
-    for CrossTableLookup {
-        looking_tables,
-        looked_table,
-    } in cross_table_lookups.iter()
+    for (
+        CrossTableLookup {
+            looking_tables,
+            looked_table,
+            ..
+        },
+        extra_product_vec,
+    ) in cross_table_lookups.iter().zip(&ctl_extra_looking_products)
     {
-        let extra_product_vec = &ctl_extra_looking_products[looked_table.table as usize];
         for c in 0..config.num_challenges {
             let looking_zs_prod = looking_tables
                 .iter()
@@ -572,10 +767,9 @@ pub(crate) fn verify_cross_table_lookups<
                 .product::<F>()
                 * extra_product_vec[c];
             let looked_z = *ctl_zs_openings[looked_table.table as usize].next().unwrap();
-            ensure!(
-                looking_zs_prod == looked_z,
-                "Cross-table lookup verification failed."
-            );
+            if looking_zs_prod != looked_z {
+                return Err(VerifierError::CtlFailure(looked_table.table, c));
+            }
         }
     }
     debug_assert!(ctl_zs_openings.iter_mut().all(|iter| iter.next().is_none()));
@@ -596,12 +790,17 @@ pub(crate) fn verify_cross_table_lookups_circuit<
     inner_config: &StarkConfig,
 ) {
     let mut ctl_zs_openings = ctl_zs_lasts.iter().map(|v| v.iter()).collect::<Vec<_>>();
-    for CrossTableLookup {
-        looking_tables,
-        looked_table,
-    } in cross_table_lookups.into_iter()
+    for (
+        CrossTableLookup {
+            looking_tables,
+            looked_table,
+            ..
+        },
+        extra_product_vec,
+    ) in cross_table_lookups
+        .into_iter()
+        .zip(&ctl_extra_looking_products)
     {
-        let extra_product_vec = &ctl_extra_looking_products[looked_table.table as usize];
         for c in 0..inner_config.num_challenges {
             let mut looking_zs_prod = builder.mul_many(
                 looking_tables
@@ -648,7 +847,12 @@ pub(crate) mod testutils {
         let CrossTableLookup {
             looking_tables,
             looked_table,
+            ..
         } = ctl;
+        // public rows are checked against the public values by the verifier.
+        if ctl.has_public_rows() {
+            return;
+        }
 
         // Maps `m` with `(table, i) in m[row]` iff the `i`-th row of `table` is equal
         // to `row` and the filter is 1. Without default values, the CTL check
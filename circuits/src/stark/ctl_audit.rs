@@ -0,0 +1,66 @@
+use std::fmt::{Display, Formatter, Write};
+
+use super::ola_stark::Table;
+
+/// One side of a cross-table lookup: the columns a table contributes and the
+/// filter selecting its rows, rendered as linear combinations of column
+/// indices.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct CtlTableDescription {
+    pub table: Table,
+    pub columns: Vec<String>,
+    /// `None` if every row takes part.
+    pub filter: Option<String>,
+}
+
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct CtlDescription {
+    pub name: String,
+    pub looking: Vec<CtlTableDescription>,
+    pub looked: CtlTableDescription,
+}
+
+impl Display for CtlTableDescription {
+    fn fmt(&self, f: &mut Formatter<'_>) -> std::fmt::Result {
+        write!(
+            f,
+            "{:?} [{}] filter {}",
+            self.table,
+            self.columns.join(", "),
+            self.filter.as_deref().unwrap_or("none")
+        )
+    }
+}
+
+impl Display for CtlDescription {
+    fn fmt(&self, f: &mut Formatter<'_>) -> std::fmt::Result {
+        writeln!(f, "{}", self.name)?;
+        for looking in &self.looking {
+            writeln!(f, "  looking {}", looking)?;
+        }
+        write!(f, "  looked  {}", self.looked)
+    }
+}
+
+/// Render the lookups as a GraphViz digraph, one edge per looking table
+/// pointing at the looked table and labelled with the lookup name, its width
+/// and the filter of the looking side.
+pub fn ctl_graphviz(ctls: &[CtlDescription]) -> String {
+    let mut dot = String::from("digraph ctl {\n    node [shape=box];\n");
+    for ctl in ctls {
+        for looking in &ctl.looking {
+            writeln!(
+                dot,
+                "    {:?} -> {:?} [label=\"{} ({} cols)\\nfilter: {}\"];",
+                looking.table,
+                ctl.looked.table,
+                ctl.name,
+                looking.columns.len(),
+                looking.filter.as_deref().unwrap_or("none")
+            )
+            .unwrap();
+        }
+    }
+    dot.push_str("}\n");
+    dot
+}
@@ -0,0 +1,148 @@
+use core::crypto::TreeHasher;
+use std::fmt::{Display, Formatter};
+
+use super::ola_stark::Table;
+
+// Display is written out rather than derived with `thiserror`: its expansion
+// refers to `::core`, which names the vm crate in this one.
+
+#[derive(Debug)]
+pub enum ProverError {
+    DegreeOverflow(Table, usize, usize),
+    OpeningPointInSubgroup(Table),
+    Checkpoint(String),
+    SpotCheck(String),
+    UnsupportedTreeHasher(TreeHasher),
+}
+
+impl Display for ProverError {
+    fn fmt(&self, f: &mut Formatter<'_>) -> std::fmt::Result {
+        match self {
+            ProverError::DegreeOverflow(table, arity, degree_bits) => write!(
+                f,
+                "table {:?}: FRI total arity {} exceeds degree bits {}",
+                table, arity, degree_bits
+            ),
+            ProverError::OpeningPointInSubgroup(table) => {
+                write!(f, "table {:?}: opening point is in the subgroup", table)
+            }
+            ProverError::Checkpoint(e) => write!(f, "checkpoint: {}", e),
+            ProverError::SpotCheck(e) => write!(f, "spot check: {}", e),
+            ProverError::UnsupportedTreeHasher(hasher) => write!(
+                f,
+                "storage hashed with {:?} can not be proven, only Poseidon has builtin tables",
+                hasher
+            ),
+        }
+    }
+}
+
+impl std::error::Error for ProverError {}
+
+#[derive(Debug)]
+pub enum VerifierError {
+    ShapeMismatch(Table, &'static str),
+    QuotientMismatch(Table, usize),
+    FriFailure(Table, String),
+    CtlFailure(Table, usize),
+    NoProgram,
+    ProgramNotWhitelisted([u64; 4]),
+    UnsupportedProofVersion(u32),
+    ForkIdMismatch(u64),
+    OpcodeDisabled(u64, u64),
+    StartStateMismatch,
+    ChainIdMismatch(u64),
+    BlockNumberOverflow,
+    CompressChallengeMismatch(Table),
+    LayoutMismatch(Table),
+    InBatch(usize, Box<VerifierError>),
+}
+
+impl Display for VerifierError {
+    fn fmt(&self, f: &mut Formatter<'_>) -> std::fmt::Result {
+        match self {
+            VerifierError::ShapeMismatch(table, what) => {
+                write!(f, "table {:?}: proof shape mismatch, {}", table, what)
+            }
+            VerifierError::QuotientMismatch(table, chunk) => write!(
+                f,
+                "table {:?}: quotient polynomial mismatch at chunk {}",
+                table, chunk
+            ),
+            VerifierError::FriFailure(table, e) => {
+                write!(f, "table {:?}: FRI verification failed: {}", table, e)
+            }
+            VerifierError::CtlFailure(table, challenge) => write!(
+                f,
+                "cross-table lookup into {:?} failed for challenge {}",
+                table, challenge
+            ),
+            VerifierError::NoProgram => write!(f, "proof commits to no program"),
+            VerifierError::ProgramNotWhitelisted(hash) => {
+                write!(f, "program hash {:?} is not whitelisted", hash)
+            }
+            VerifierError::UnsupportedProofVersion(version) => {
+                write!(f, "proof version {} is not accepted", version)
+            }
+            VerifierError::ForkIdMismatch(height) => {
+                write!(f, "proof does not claim the fork id of block {}", height)
+            }
+            VerifierError::OpcodeDisabled(mask, height) => {
+                write!(f, "opcode {:#x} is not enabled at block {}", mask, height)
+            }
+            VerifierError::StartStateMismatch => {
+                write!(f, "proof does not start from the expected state")
+            }
+            VerifierError::ChainIdMismatch(chain_id) => {
+                write!(f, "proof was generated for chain {}", chain_id)
+            }
+            VerifierError::BlockNumberOverflow => {
+                write!(f, "block number of the proof does not fit in 64 bits")
+            }
+            VerifierError::CompressChallengeMismatch(table) => write!(
+                f,
+                "table {:?}: compress challenge does not match the public values",
+                table
+            ),
+            VerifierError::LayoutMismatch(table) => write!(
+                f,
+                "table {:?}: proof was made against another column layout",
+                table
+            ),
+            VerifierError::InBatch(index, e) => write!(f, "proof {} of the batch: {}", index, e),
+        }
+    }
+}
+
+impl std::error::Error for VerifierError {}
+
+#[derive(Debug)]
+pub enum ConfigError {
+    InsufficientSecurity(Table, usize, usize),
+    InsufficientChainSecurity(usize, usize),
+    UnpinnedReduction(String),
+}
+
+impl Display for ConfigError {
+    fn fmt(&self, f: &mut Formatter<'_>) -> std::fmt::Result {
+        match self {
+            ConfigError::InsufficientSecurity(table, bits, required) => write!(
+                f,
+                "table {:?}: {} bits of conjectured security, {} required",
+                table, bits, required
+            ),
+            ConfigError::InsufficientChainSecurity(bits, required) => write!(
+                f,
+                "{} bits of conjectured security, {} required by the chain spec",
+                bits, required
+            ),
+            ConfigError::UnpinnedReduction(strategy) => write!(
+                f,
+                "fri reduction strategy {} can not be pinned, only constant arity bits can",
+                strategy
+            ),
+        }
+    }
+}
+
+impl std::error::Error for ConfigError {}
@@ -3,9 +3,9 @@ use plonky2::fri::proof::{FriProof, FriProofTarget};
 use plonky2::hash::hash_types::RichField;
 use plonky2::iop::challenger::{Challenger, RecursiveChallenger};
 use plonky2::plonk::circuit_builder::CircuitBuilder;
-use plonky2::plonk::config::{AlgebraicHasher, GenericConfig};
+use plonky2::plonk::config::{AlgebraicHasher, GenericConfig, Hasher};
 
-use super::config::StarkConfig;
+use super::config::{KeccakTranscriptHash, StarkConfig, TranscriptHasher};
 use super::ola_stark::{OlaStark, NUM_TABLES};
 use super::permutation::{
     get_grand_product_challenge_set, get_n_grand_product_challenge_sets,
@@ -20,13 +20,30 @@ impl<F: RichField + Extendable<D>, C: GenericConfig<D, F = F>, const D: usize> A
         ola_stark: &OlaStark<F, D>,
         config: &StarkConfig,
     ) -> AllProofChallenges<F, D> {
-        let mut challenger = Challenger::<F, C::Hasher>::new();
+        match config.transcript_hasher {
+            TranscriptHasher::Poseidon => self.challenges_with::<C::Hasher>(ola_stark, config),
+            TranscriptHasher::Keccak => {
+                self.challenges_with::<KeccakTranscriptHash>(ola_stark, config)
+            }
+        }
+    }
+
+    fn challenges_with<H: Hasher<F>>(
+        &self,
+        ola_stark: &OlaStark<F, D>,
+        config: &StarkConfig,
+    ) -> AllProofChallenges<F, D> {
+        let mut challenger = Challenger::<F, H>::new();
 
         for proof in &self.stark_proofs {
             challenger.observe_cap(&proof.trace_cap);
         }
 
-        // TODO: Observe public values.
+        self.version.observe_public_inputs(
+            &self.public_values,
+            &self.compress_challenges,
+            &mut challenger,
+        );
 
         let ctl_challenges =
             get_grand_product_challenge_set(&mut challenger, config.num_challenges);
@@ -41,7 +58,7 @@ impl<F: RichField + Extendable<D>, C: GenericConfig<D, F = F>, const D: usize> A
                     &mut challenger,
                     num_permutation_zs[i] > 0,
                     num_permutation_batch_sizes[i],
-                    config,
+                    &config.for_table_index(i),
                 )
             }),
             ctl_challenges,
@@ -60,7 +77,11 @@ impl<F: RichField + Extendable<D>, C: GenericConfig<D, F = F>, const D: usize> A
             challenger.observe_cap(&proof.trace_cap);
         }
 
-        // TODO: Observe public values.
+        self.version.observe_public_inputs(
+            &self.public_values,
+            &self.compress_challenges,
+            &mut challenger,
+        );
 
         let ctl_challenges =
             get_grand_product_challenge_set(&mut challenger, config.num_challenges);
@@ -74,7 +95,7 @@ impl<F: RichField + Extendable<D>, C: GenericConfig<D, F = F>, const D: usize> A
                 &mut challenger,
                 num_permutation_zs[i] > 0,
                 num_permutation_batch_sizes[i],
-                config,
+                &config.for_table_index(i),
             );
             challenger_states.push(challenger.compact());
         }
@@ -92,9 +113,9 @@ where
     C: GenericConfig<D, F = F>,
 {
     /// Computes all Fiat-Shamir challenges used in the STARK proof.
-    pub(crate) fn get_challenges(
+    pub(crate) fn get_challenges<H: Hasher<F>>(
         &self,
-        challenger: &mut Challenger<F, C::Hasher>,
+        challenger: &mut Challenger<F, H>,
         stark_use_permutation: bool,
         stark_permutation_batch_size: usize,
         config: &StarkConfig,
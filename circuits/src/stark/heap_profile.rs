@@ -0,0 +1,224 @@
+//! Heap accounting of the prover, to find the table behind an OOM.
+//!
+//! Build with the `heap_profile` feature and install `TrackingAlloc` as the
+//! global allocator of the binary:
+//!
+//! ```ignore
+//! #[global_allocator]
+//! static GLOBAL: TrackingAlloc = TrackingAlloc;
+//! ```
+//!
+//! `prove` then records the heap use of each table and phase, logs it and
+//! keeps it for `report`. The trace, LDE and Merkle tree sizes are read from
+//! the data structures, the retained and peak bytes of the phases need the
+//! tracking allocator and are zero without it.
+
+use std::alloc::{GlobalAlloc, Layout, System};
+use std::fmt::{Display, Formatter};
+use std::mem::size_of;
+use std::sync::atomic::{AtomicUsize, Ordering};
+use std::sync::Mutex;
+
+use plonky2::field::extension::Extendable;
+use plonky2::field::polynomial::PolynomialValues;
+use plonky2::fri::oracle::PolynomialBatch;
+use plonky2::hash::hash_types::RichField;
+use plonky2::plonk::config::{GenericConfig, Hasher};
+
+use super::ola_stark::Table;
+
+static LIVE: AtomicUsize = AtomicUsize::new(0);
+static PEAK: AtomicUsize = AtomicUsize::new(0);
+static ENTRIES: Mutex<Vec<HeapEntry>> = Mutex::new(Vec::new());
+
+/// System allocator counting live and peak heap bytes.
+pub struct TrackingAlloc;
+
+unsafe impl GlobalAlloc for TrackingAlloc {
+    unsafe fn alloc(&self, layout: Layout) -> *mut u8 {
+        let ptr = System.alloc(layout);
+        if !ptr.is_null() {
+            let live = LIVE.fetch_add(layout.size(), Ordering::Relaxed) + layout.size();
+            PEAK.fetch_max(live, Ordering::Relaxed);
+        }
+        ptr
+    }
+
+    unsafe fn dealloc(&self, ptr: *mut u8, layout: Layout) {
+        System.dealloc(ptr, layout);
+        LIVE.fetch_sub(layout.size(), Ordering::Relaxed);
+    }
+}
+
+pub fn live_bytes() -> usize {
+    LIVE.load(Ordering::Relaxed)
+}
+
+pub fn peak_bytes() -> usize {
+    PEAK.load(Ordering::Relaxed)
+}
+
+/// Heap use of a phase, measured from `start` to `finish`. Concurrent work
+/// outside the phase is counted too.
+pub struct HeapScope {
+    live: usize,
+    peak: usize,
+}
+
+impl HeapScope {
+    pub fn start() -> Self {
+        let live = live_bytes();
+        // the peak of the scope is measured from the current live bytes, the
+        // overall peak is kept by the report
+        let peak = PEAK.swap(live, Ordering::Relaxed);
+        Self { live, peak }
+    }
+
+    /// Bytes still allocated and the highest extra bytes the phase held.
+    fn finish(self) -> (usize, usize) {
+        let peak = PEAK.fetch_max(self.peak, Ordering::Relaxed);
+        (
+            live_bytes().saturating_sub(self.live),
+            peak.saturating_sub(self.live),
+        )
+    }
+}
+
+#[derive(Debug, Clone)]
+pub struct HeapEntry {
+    pub table: Option<Table>,
+    pub phase: &'static str,
+    /// Bytes the phase left allocated.
+    pub retained: usize,
+    /// Highest bytes the phase held on top of what was allocated before.
+    pub peak: usize,
+}
+
+#[derive(Debug, Clone, Default)]
+pub struct HeapReport {
+    pub entries: Vec<HeapEntry>,
+}
+
+/// Records the heap use of the phase started with `scope`.
+pub fn record(table: Option<Table>, phase: &'static str, scope: HeapScope) {
+    let (retained, peak) = scope.finish();
+    ENTRIES.lock().unwrap().push(HeapEntry {
+        table,
+        phase,
+        retained,
+        peak,
+    });
+}
+
+/// Records the sizes of the trace of `table` and of the LDE and Merkle tree
+/// of its commitment.
+pub fn record_commitment<F, C, const D: usize>(
+    table: Table,
+    trace: &[PolynomialValues<F>],
+    commitment: &PolynomialBatch<F, C, D>,
+) where
+    F: RichField + Extendable<D>,
+    C: GenericConfig<D, F = F>,
+{
+    let tree = &commitment.merkle_tree;
+    let hash_size = size_of::<<C::Hasher as Hasher<F>>::Hash>();
+    let sizes = [
+        (
+            "trace",
+            trace.iter().map(|col| col.values.len()).sum::<usize>() * size_of::<F>(),
+        ),
+        (
+            "lde",
+            tree.leaves.iter().map(|leaf| leaf.len()).sum::<usize>() * size_of::<F>(),
+        ),
+        (
+            "merkle tree",
+            (tree.digests.len() + tree.cap.0.len()) * hash_size,
+        ),
+    ];
+    let mut entries = ENTRIES.lock().unwrap();
+    for (phase, bytes) in sizes {
+        entries.push(HeapEntry {
+            table: Some(table),
+            phase,
+            retained: bytes,
+            peak: bytes,
+        });
+    }
+}
+
+/// Drops the recorded entries, `prove` starts with it.
+pub fn clear() {
+    ENTRIES.lock().unwrap().clear();
+}
+
+/// What was recorded since the last `clear`.
+pub fn report() -> HeapReport {
+    HeapReport {
+        entries: ENTRIES.lock().unwrap().clone(),
+    }
+}
+
+impl Display for HeapReport {
+    fn fmt(&self, f: &mut Formatter<'_>) -> std::fmt::Result {
+        writeln!(
+            f,
+            "{:<16} {:<20} {:>12} {:>12}",
+            "table", "phase", "retained", "peak"
+        )?;
+        for entry in &self.entries {
+            let table = entry
+                .table
+                .map_or("-".to_string(), |table| format!("{:?}", table));
+            writeln!(
+                f,
+                "{:<16} {:<20} {:>12} {:>12}",
+                table, entry.phase, entry.retained, entry.peak
+            )?;
+        }
+        write!(f, "process peak: {} bytes", peak_bytes())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use plonky2::field::polynomial::PolynomialValues;
+    use plonky2::field::types::Field;
+    use plonky2::fri::oracle::PolynomialBatch;
+    use plonky2::plonk::config::{GenericConfig, PoseidonGoldilocksConfig};
+    use plonky2::util::timing::TimingTree;
+    use std::collections::BTreeMap;
+
+    use super::{clear, record, record_commitment, report, HeapScope};
+    use crate::stark::ola_stark::Table;
+
+    const D: usize = 2;
+    type C = PoseidonGoldilocksConfig;
+    type F = <C as GenericConfig<D>>::F;
+
+    #[test]
+    fn test_heap_report() {
+        let trace = vec![PolynomialValues::new(vec![F::ONE; 8]); 3];
+        let commitment = PolynomialBatch::<F, C, D>::from_values(
+            trace.clone(),
+            1,
+            false,
+            0,
+            &mut TimingTree::default(),
+            &mut BTreeMap::new(),
+        );
+        clear();
+        record_commitment(Table::Cmp, &trace, &commitment);
+        record(None, "ctl", HeapScope::start());
+        let report = report();
+
+        assert_eq!(report.entries.len(), 4);
+        assert_eq!(report.entries[0].retained, 3 * 8 * 8);
+        // LDE of rate 2 over the 3 columns
+        assert_eq!(report.entries[1].retained, 3 * 16 * 8);
+        assert_eq!(report.entries[3].phase, "ctl");
+        let text = report.to_string();
+        assert!(text.contains("Cmp"));
+        assert!(text.contains("merkle tree"));
+    }
+}
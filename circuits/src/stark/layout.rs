@@ -0,0 +1,83 @@
+use std::collections::BTreeMap;
+
+use sha2::{Digest, Sha256};
+
+use super::ola_stark::{Table, ALL_TABLES, NUM_TABLES};
+use crate::builtins::bitwise::columns::{get_bitwise_col_name_map, COL_NUM_BITWISE};
+use crate::builtins::cmp::columns::{get_cmp_col_name_map, COL_NUM_CMP};
+use crate::builtins::poseidon::columns::{
+    get_poseidon_chunk_col_name_map, get_poseidon_col_name_map, NUM_POSEIDON_CHUNK_COLS,
+    NUM_POSEIDON_COLS,
+};
+use crate::builtins::rangecheck::columns::{get_rangecheck_col_name_map, COL_NUM_RC};
+use crate::builtins::sccall::columns::{get_sccall_col_name_map, NUM_COL_SCCALL};
+use crate::builtins::storage::columns::{get_storage_access_col_name_map, NUM_COL_ST};
+use crate::builtins::tape::columns::{get_tape_col_name_map, NUM_COL_TAPE};
+use crate::builtins::transient::columns::{get_transient_col_name_map, NUM_COL_TRANSIENT};
+use crate::cpu::columns::{get_cpu_col_name_map, NUM_CPU_COLS};
+use crate::memory::columns::{get_memory_col_name_map, NUM_MEM_COLS};
+use crate::program::columns::{
+    get_prog_chunk_col_name_map, get_prog_col_name_map, NUM_PROG_CHUNK_COLS, NUM_PROG_COLS,
+};
+
+/// Width of `table` and the names of its columns by index.
+pub(crate) fn table_columns(table: Table) -> (usize, BTreeMap<usize, String>) {
+    match table {
+        Table::Cpu => (NUM_CPU_COLS, get_cpu_col_name_map()),
+        Table::Memory => (NUM_MEM_COLS, get_memory_col_name_map()),
+        Table::Bitwise => (COL_NUM_BITWISE, get_bitwise_col_name_map()),
+        Table::Cmp => (COL_NUM_CMP, get_cmp_col_name_map()),
+        Table::RangeCheck => (COL_NUM_RC, get_rangecheck_col_name_map()),
+        Table::Poseidon => (NUM_POSEIDON_COLS, get_poseidon_col_name_map()),
+        Table::PoseidonChunk => (NUM_POSEIDON_CHUNK_COLS, get_poseidon_chunk_col_name_map()),
+        Table::StorageAccess => (NUM_COL_ST, get_storage_access_col_name_map()),
+        Table::Tape => (NUM_COL_TAPE, get_tape_col_name_map()),
+        Table::SCCall => (NUM_COL_SCCALL, get_sccall_col_name_map()),
+        Table::Program => (NUM_PROG_COLS, get_prog_col_name_map()),
+        Table::ProgChunk => (NUM_PROG_CHUNK_COLS, get_prog_chunk_col_name_map()),
+        Table::Transient => (NUM_COL_TRANSIENT, get_transient_col_name_map()),
+    }
+}
+
+/// Hash of the width of `table` and its column names in index order. It
+/// changes whenever a column is added, removed, renamed or moved, so proofs
+/// of another build of the tables are rejected before their openings are
+/// checked. Columns without a name count by index.
+pub fn layout_fingerprint(table: Table) -> u64 {
+    let (width, names) = table_columns(table);
+    let mut hasher = Sha256::new();
+    hasher.update(format!("{:?}", table).as_bytes());
+    hasher.update((width as u64).to_le_bytes());
+    for col in 0..width {
+        let name = names.get(&col).map_or("", String::as_str);
+        hasher.update((name.len() as u64).to_le_bytes());
+        hasher.update(name.as_bytes());
+    }
+    let digest = hasher.finalize();
+    u64::from_le_bytes(digest[..8].try_into().unwrap())
+}
+
+/// `layout_fingerprint` of every table, in table order.
+pub fn layout_fingerprints() -> [u64; NUM_TABLES] {
+    ALL_TABLES.map(layout_fingerprint)
+}
+
+#[cfg(test)]
+mod tests {
+    use std::collections::HashSet;
+
+    use crate::stark::layout::{layout_fingerprint, layout_fingerprints};
+    use crate::stark::ola_stark::Table;
+
+    #[test]
+    fn test_layout_fingerprints() {
+        let fingerprints = layout_fingerprints();
+        assert_eq!(
+            fingerprints[Table::Tape as usize],
+            layout_fingerprint(Table::Tape)
+        );
+        assert_eq!(layout_fingerprints(), fingerprints);
+        let distinct: HashSet<_> = fingerprints.iter().collect();
+        assert_eq!(distinct.len(), fingerprints.len());
+    }
+}
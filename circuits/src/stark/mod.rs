@@ -1,15 +1,24 @@
+pub mod checkpoint;
 pub mod config;
 pub mod constraint_consumer;
+pub mod constraint_coverage;
 pub mod cross_table_lookup;
+pub mod ctl_audit;
+pub mod error;
 mod get_challenges;
+pub mod heap_profile;
+pub mod layout;
 pub mod lookup;
 pub mod ola_stark;
 pub mod permutation;
 pub mod proof;
 pub mod prover;
 pub mod serialization;
+pub mod ssz;
 pub mod stark;
+pub mod trace_check;
 pub mod util;
 pub mod vanishing_poly;
 pub mod vars;
 pub mod verifier;
+pub mod verifier_constants;
@@ -2,6 +2,8 @@ use std::iter;
 
 use super::config::StarkConfig;
 use super::cross_table_lookup::{CrossTableLookup, TableWithColumns};
+use super::ctl_audit::{ctl_graphviz, CtlDescription};
+use super::proof::PublicValues;
 use super::stark::Stark;
 use crate::builtins::bitwise::bitwise_stark::{self, BitwiseStark};
 use crate::builtins::cmp::cmp_stark::{self, CmpStark};
@@ -11,6 +13,7 @@ use crate::builtins::rangecheck::rangecheck_stark::{self, RangeCheckStark};
 use crate::builtins::sccall::sccall_stark::{self, SCCallStark};
 use crate::builtins::storage::storage_access_stark::{self, StorageAccessStark};
 use crate::builtins::tape::tape_stark::{self, TapeStark};
+use crate::builtins::transient::transient_stark::{self, TransientStark};
 use crate::cpu::cpu_stark;
 use crate::cpu::cpu_stark::CpuStark;
 use crate::memory::memory_stark::{
@@ -19,6 +22,9 @@ use crate::memory::memory_stark::{
 };
 use crate::program::prog_chunk_stark::{self, ProgChunkStark};
 use crate::program::program_stark::{self, ProgramStark};
+use core::types::field::fields_from_bytes;
+use core::types::{GoldilocksField, PrimeField64};
+use ethereum_types::H256;
 use plonky2::field::extension::Extendable;
 use plonky2::field::types::Field;
 use plonky2::hash::hash_types::RichField;
@@ -38,6 +44,7 @@ pub struct OlaStark<F: RichField + Extendable<D>, const D: usize> {
     pub sccall_stark: SCCallStark<F, D>,
     pub program_stark: ProgramStark<F, D>,
     pub prog_chunk_stark: ProgChunkStark<F, D>,
+    pub transient_stark: TransientStark<F, D>,
 
     pub cross_table_lookups: Vec<CrossTableLookup<F>>,
 }
@@ -57,6 +64,7 @@ impl<F: RichField + Extendable<D>, const D: usize> Default for OlaStark<F, D> {
             sccall_stark: SCCallStark::default(),
             program_stark: ProgramStark::default(),
             prog_chunk_stark: ProgChunkStark::default(),
+            transient_stark: TransientStark::default(),
             cross_table_lookups: all_cross_table_lookups(),
         }
     }
@@ -77,6 +85,39 @@ impl<F: RichField + Extendable<D>, const D: usize> OlaStark<F, D> {
             self.sccall_stark.num_permutation_batches(config),
             self.program_stark.num_permutation_batches(config),
             self.prog_chunk_stark.num_permutation_batches(config),
+            self.transient_stark.num_permutation_batches(config),
+        ]
+    }
+
+    /// Every cross-table lookup with its looking and looked tables, columns
+    /// and filters.
+    pub fn describe_ctls(&self) -> Vec<CtlDescription> {
+        self.cross_table_lookups
+            .iter()
+            .map(|ctl| ctl.describe())
+            .collect()
+    }
+
+    /// The cross-table lookup topology as a GraphViz digraph.
+    pub fn ctl_graphviz(&self) -> String {
+        ctl_graphviz(&self.describe_ctls())
+    }
+
+    pub(crate) fn quotient_degree_factors(&self) -> [usize; NUM_TABLES] {
+        [
+            self.cpu_stark.quotient_degree_factor(),
+            self.memory_stark.quotient_degree_factor(),
+            self.bitwise_stark.quotient_degree_factor(),
+            self.cmp_stark.quotient_degree_factor(),
+            self.rangecheck_stark.quotient_degree_factor(),
+            self.poseidon_stark.quotient_degree_factor(),
+            self.poseidon_chunk_stark.quotient_degree_factor(),
+            self.storage_access_stark.quotient_degree_factor(),
+            self.tape_stark.quotient_degree_factor(),
+            self.sccall_stark.quotient_degree_factor(),
+            self.program_stark.quotient_degree_factor(),
+            self.prog_chunk_stark.quotient_degree_factor(),
+            self.transient_stark.quotient_degree_factor(),
         ]
     }
 
@@ -94,6 +135,7 @@ impl<F: RichField + Extendable<D>, const D: usize> OlaStark<F, D> {
             self.sccall_stark.permutation_batch_size(),
             self.program_stark.permutation_batch_size(),
             self.prog_chunk_stark.permutation_batch_size(),
+            self.transient_stark.permutation_batch_size(),
         ]
     }
 }
@@ -113,31 +155,64 @@ pub enum Table {
     SCCall = 9,
     Program = 10,
     ProgChunk = 11,
+    Transient = 12,
 }
 
-pub(crate) const NUM_TABLES: usize = 12;
+pub(crate) const NUM_TABLES: usize = 13;
+
+pub(crate) const ALL_TABLES: [Table; NUM_TABLES] = [
+    Table::Cpu,
+    Table::Memory,
+    Table::Bitwise,
+    Table::Cmp,
+    Table::RangeCheck,
+    Table::Poseidon,
+    Table::PoseidonChunk,
+    Table::StorageAccess,
+    Table::Tape,
+    Table::SCCall,
+    Table::Program,
+    Table::ProgChunk,
+    Table::Transient,
+];
 
 pub(crate) fn all_cross_table_lookups<F: Field>() -> Vec<CrossTableLookup<F>> {
     vec![
-        ctl_cpu_memory(),
-        ctl_memory_rc_sort(),
-        ctl_memory_rc_region(),
-        ctl_bitwise_cpu(),
-        ctl_cmp_cpu(),
-        ctl_cmp_rangecheck(),
-        ctl_rangecheck_cpu(),
-        ctl_cpu_poseidon_chunk(),
-        ctl_poseidon_chunk_mem(),
-        ctl_chunk_poseidon(),
-        ctl_cpu_poseidon_tree_key(),
-        ctl_cpu_storage_access(),
-        ctl_storage_access_poseidon(),
-        ctl_cpu_tape(),
-        ctl_cpu_sccall(),
-        ctl_cpu_sccall_end(),
-        ctl_cpu_program(),
-        ctl_prog_chunk_prog(),
-        ctl_prog_chunk_storage(),
+        ctl_cpu_memory().named("cpu_memory"),
+        ctl_memory_rc_sort().named("memory_rc_sort"),
+        ctl_memory_rc_region().named("memory_rc_region"),
+        ctl_bitwise_cpu().named("bitwise_cpu"),
+        ctl_cmp_cpu().named("cmp_cpu"),
+        ctl_cmp_rangecheck().named("cmp_rangecheck"),
+        ctl_rangecheck_cpu().named("rangecheck_cpu"),
+        ctl_cpu_poseidon_chunk().named("cpu_poseidon_chunk"),
+        ctl_poseidon_chunk_mem().named("poseidon_chunk_mem"),
+        ctl_chunk_poseidon().named("chunk_poseidon"),
+        ctl_cpu_poseidon_tree_key().named("cpu_poseidon_tree_key"),
+        ctl_cpu_storage_access().named("cpu_storage_access"),
+        ctl_storage_access_poseidon().named("storage_access_poseidon"),
+        ctl_cpu_tape().named("cpu_tape"),
+        ctl_cpu_transient().named("cpu_transient"),
+        ctl_cpu_sccall().named("cpu_sccall"),
+        ctl_cpu_sccall_end().named("cpu_sccall_end"),
+        ctl_cpu_program().named("cpu_program"),
+        ctl_prog_chunk_prog().named("prog_chunk_prog"),
+        ctl_prog_chunk_storage().named("prog_chunk_storage"),
+        ctl_public_state_root_before().named("public_state_root_before"),
+        ctl_public_state_root_after().named("public_state_root_after"),
+        ctl_public_program_hashes().named("public_program_hashes"),
+        ctl_public_memory_image().named("public_memory_image"),
+        ctl_public_code_region().named("public_code_region"),
+        ctl_public_tx_context().named("public_tx_context"),
+        ctl_public_executed_opcodes().named("public_executed_opcodes"),
+        ctl_public_start_state().named("public_start_state"),
+        ctl_public_end_state().named("public_end_state"),
+        ctl_public_memory_export().named("public_memory_export"),
+        ctl_public_end_memory_image().named("public_end_memory_image"),
+        ctl_public_returned_value::<F, 0>().named("public_returned_value_0"),
+        ctl_public_returned_value::<F, 1>().named("public_returned_value_1"),
+        ctl_public_returned_value::<F, 2>().named("public_returned_value_2"),
+        ctl_public_returned_value::<F, 3>().named("public_returned_value_3"),
     ]
 }
 
@@ -173,14 +248,14 @@ fn ctl_cpu_memory<F: Field>() -> CrossTableLookup<F> {
         TableWithColumns::new(
             Table::Cpu,
             cpu_stark::ctl_data_cpu_mem_for_storage_addr(i),
-            Some(cpu_stark::ctl_filter_cpu_storage_access()),
+            Some(cpu_stark::ctl_filter_cpu_mem_for_storage_addr()),
         )
     });
     let cpu_storage_value = (0..4).map(|i: usize| {
         TableWithColumns::new(
             Table::Cpu,
             cpu_stark::ctl_data_cpu_mem_for_storage_value(i),
-            Some(cpu_stark::ctl_filter_cpu_storage_access()),
+            Some(cpu_stark::ctl_filter_cpu_mem_for_storage_value(i)),
         )
     });
 
@@ -198,13 +273,22 @@ fn ctl_cpu_memory<F: Field>() -> CrossTableLookup<F> {
     CrossTableLookup::new(all_cpu_lookers, memory_looked)
 }
 
+// the step gaps of the transient table share the range check of the memory
+// sort, both look up increasing orderings
 fn ctl_memory_rc_sort<F: Field>() -> CrossTableLookup<F> {
     CrossTableLookup::new(
-        vec![TableWithColumns::new(
-            Table::Memory,
-            ctl_data_mem_sort_rc(),
-            Some(ctl_filter_mem_sort_rc()),
-        )],
+        vec![
+            TableWithColumns::new(
+                Table::Memory,
+                ctl_data_mem_sort_rc(),
+                Some(ctl_filter_mem_sort_rc()),
+            ),
+            TableWithColumns::new(
+                Table::Transient,
+                transient_stark::ctl_data_transient_step_rc(),
+                Some(transient_stark::ctl_filter_transient_step_rc()),
+            ),
+        ],
         TableWithColumns::new(
             Table::RangeCheck,
             rangecheck_stark::ctl_data_memory(),
@@ -245,6 +329,109 @@ fn ctl_memory_rc_region<F: Field>() -> CrossTableLookup<F> {
 // Filter bitwise from Bitwsie Table
 // 1. (op0, op1, res) = looked_table
 
+fn tx_context<F: Field>(public_values: &PublicValues) -> Vec<Vec<F>> {
+    vec![iter::once(public_values.chain_id)
+        .chain(public_values.tx_hash)
+        .map(F::from_canonical_u64)
+        .collect()]
+}
+
+// The tx context is the same on every cpu row, `txctx` reads it from there.
+fn ctl_public_tx_context<F: Field>() -> CrossTableLookup<F> {
+    CrossTableLookup::public(
+        tx_context,
+        TableWithColumns::new(
+            Table::Cpu,
+            cpu_stark::ctl_data_tx_context(),
+            Some(cpu_stark::ctl_filter_first_row()),
+        ),
+    )
+}
+
+fn executed_opcodes<F: Field>(public_values: &PublicValues) -> Vec<(Vec<F>, u64)> {
+    public_values
+        .executed_opcodes
+        .iter()
+        .map(|[mask, count]| (vec![F::from_canonical_u64(*mask)], *count))
+        .collect()
+}
+
+// Every instruction row of the cpu table is counted under its opcode, so the
+// public histogram is exactly what was executed.
+fn ctl_public_executed_opcodes<F: Field>() -> CrossTableLookup<F> {
+    CrossTableLookup::public_counted(
+        executed_opcodes,
+        TableWithColumns::new(
+            Table::Cpu,
+            cpu_stark::ctl_data_opcode(),
+            Some(cpu_stark::ctl_filter_with_program_inst()),
+        ),
+    )
+}
+
+fn start_state<F: Field>(public_values: &PublicValues) -> Vec<Vec<F>> {
+    vec![iter::once(public_values.start_pc)
+        .chain(public_values.start_registers)
+        .map(F::from_canonical_u64)
+        .collect()]
+}
+
+// The first cpu row starts from the public pc and registers, zero unless the
+// proof is of a chunk continuing an execution.
+fn ctl_public_start_state<F: Field>() -> CrossTableLookup<F> {
+    CrossTableLookup::public(
+        start_state,
+        TableWithColumns::new(
+            Table::Cpu,
+            cpu_stark::ctl_data_start_state(),
+            Some(cpu_stark::ctl_filter_first_row()),
+        ),
+    )
+}
+
+fn end_state<F: Field>(public_values: &PublicValues) -> Vec<Vec<F>> {
+    vec![iter::once(public_values.end_pc)
+        .chain(public_values.end_registers)
+        .chain([public_values.gas_used])
+        .map(F::from_canonical_u64)
+        .collect()]
+}
+
+// The first padding row of the cpu holds the public pc and registers, zero
+// unless the proof is of a chunk that stopped, and the public gas used.
+fn ctl_public_end_state<F: Field>() -> CrossTableLookup<F> {
+    CrossTableLookup::public(
+        end_state,
+        TableWithColumns::new(
+            Table::Cpu,
+            cpu_stark::ctl_data_end_state(),
+            Some(cpu_stark::ctl_filter_end_state()),
+        ),
+    )
+}
+
+fn returned_value<F: Field, const I: usize>(public_values: &PublicValues) -> Vec<Vec<F>> {
+    public_values
+        .returned_values
+        .get(I)
+        .map(|value| vec![F::from_canonical_u64(*value)])
+        .into_iter()
+        .collect()
+}
+
+// Returned value i is register i of the entry END row, which marks as many
+// registers as the program returns values.
+fn ctl_public_returned_value<F: Field, const I: usize>() -> CrossTableLookup<F> {
+    CrossTableLookup::public(
+        returned_value::<F, I>,
+        TableWithColumns::new(
+            Table::Cpu,
+            cpu_stark::ctl_data_returned_value(I),
+            Some(cpu_stark::ctl_filter_returned_value(I)),
+        ),
+    )
+}
+
 // Cross_Lookup_Table(looking_table, looked_table)
 fn ctl_bitwise_cpu<F: Field>() -> CrossTableLookup<F> {
     CrossTableLookup::new(
@@ -295,11 +482,18 @@ fn ctl_cmp_rangecheck<F: Field>() -> CrossTableLookup<F> {
 // add Rangecheck cross lookup instance
 fn ctl_rangecheck_cpu<F: Field>() -> CrossTableLookup<F> {
     CrossTableLookup::new(
-        vec![TableWithColumns::new(
-            Table::Cpu,
-            cpu_stark::ctl_data_with_rangecheck(),
-            Some(cpu_stark::ctl_filter_with_rangecheck()),
-        )],
+        vec![
+            TableWithColumns::new(
+                Table::Cpu,
+                cpu_stark::ctl_data_with_rangecheck(),
+                Some(cpu_stark::ctl_filter_with_rangecheck()),
+            ),
+            TableWithColumns::new(
+                Table::Cpu,
+                cpu_stark::ctl_data_cpu_sscan_rangecheck(),
+                Some(cpu_stark::ctl_filter_cpu_sscan_rangecheck()),
+            ),
+        ],
         TableWithColumns::new(
             Table::RangeCheck,
             rangecheck_stark::ctl_data_with_cpu(),
@@ -471,6 +665,27 @@ fn ctl_cpu_tape<F: Field>() -> CrossTableLookup<F> {
     CrossTableLookup::new(all_lookers, tape_looked)
 }
 
+fn ctl_cpu_transient<F: Field>() -> CrossTableLookup<F> {
+    let cpu_tloadt = TableWithColumns::new(
+        Table::Cpu,
+        cpu_stark::ctl_data_cpu_tloadt(),
+        Some(cpu_stark::ctl_filter_cpu_tloadt()),
+    );
+    let cpu_tstoret = TableWithColumns::new(
+        Table::Cpu,
+        cpu_stark::ctl_data_cpu_tstoret(),
+        Some(cpu_stark::ctl_filter_cpu_tstoret()),
+    );
+    CrossTableLookup::new(
+        vec![cpu_tloadt, cpu_tstoret],
+        TableWithColumns::new(
+            Table::Transient,
+            transient_stark::ctl_data_transient(),
+            Some(transient_stark::ctl_filter_transient()),
+        ),
+    )
+}
+
 fn ctl_cpu_sccall<F: Field>() -> CrossTableLookup<F> {
     CrossTableLookup::new(
         vec![TableWithColumns::new(
@@ -557,6 +772,151 @@ fn ctl_prog_chunk_storage<F: Field>() -> CrossTableLookup<F> {
     )
 }
 
+/// The limbs of a state root, none if it does not encode field elements.
+fn state_root_rows<F: Field>(root: &H256) -> Vec<Vec<F>> {
+    fields_from_bytes::<GoldilocksField>(&root.0)
+        .map(|limbs| {
+            vec![limbs
+                .iter()
+                .map(|limb| F::from_canonical_u64(limb.to_canonical_u64()))
+                .collect()]
+        })
+        .unwrap_or_default()
+}
+
+fn state_root_before<F: Field>(public_values: &PublicValues) -> Vec<Vec<F>> {
+    state_root_rows(&public_values.trie_roots_before.state_root)
+}
+
+fn state_root_after<F: Field>(public_values: &PublicValues) -> Vec<Vec<F>> {
+    state_root_rows(&public_values.trie_roots_after.state_root)
+}
+
+fn program_hashes<F: Field>(public_values: &PublicValues) -> Vec<Vec<F>> {
+    public_values
+        .program_hashes
+        .iter()
+        .map(|hash| {
+            hash.iter()
+                .map(|limb| F::from_canonical_u64(*limb))
+                .collect()
+        })
+        .collect()
+}
+
+fn memory_image<F: Field>(public_values: &PublicValues) -> Vec<Vec<F>> {
+    public_values
+        .memory_image
+        .iter()
+        .map(|cell| cell.iter().map(|v| F::from_canonical_u64(*v)).collect())
+        .collect()
+}
+
+// The state roots are the pre root of the first storage access and the root
+// of the last one, padding rows keep the last root.
+fn ctl_public_state_root_before<F: Field>() -> CrossTableLookup<F> {
+    CrossTableLookup::public(
+        state_root_before,
+        TableWithColumns::new(
+            Table::StorageAccess,
+            storage_access_stark::ctl_data_pre_root(),
+            Some(storage_access_stark::ctl_filter_first_row()),
+        ),
+    )
+}
+
+fn ctl_public_state_root_after<F: Field>() -> CrossTableLookup<F> {
+    CrossTableLookup::public(
+        state_root_after,
+        TableWithColumns::new(
+            Table::StorageAccess,
+            storage_access_stark::ctl_data_root(),
+            Some(storage_access_stark::ctl_filter_last_row()),
+        ),
+    )
+}
+
+// Every executed program has one result line in the prog chunk table.
+fn ctl_public_program_hashes<F: Field>() -> CrossTableLookup<F> {
+    CrossTableLookup::public(
+        program_hashes,
+        TableWithColumns::new(
+            Table::ProgChunk,
+            prog_chunk_stark::ctl_data_program_hash(),
+            Some(prog_chunk_stark::ctl_filter_program_hash()),
+        ),
+    )
+}
+
+// Every cell of the memory image is written once before the execution.
+fn ctl_public_memory_image<F: Field>() -> CrossTableLookup<F> {
+    CrossTableLookup::public(
+        memory_image,
+        TableWithColumns::new(
+            Table::Memory,
+            memory_stark::ctl_data_image(),
+            Some(memory_stark::ctl_filter_image()),
+        ),
+    )
+}
+
+fn memory_export<F: Field>(public_values: &PublicValues) -> Vec<Vec<F>> {
+    vec![vec![F::from_bool(public_values.stopped)]]
+}
+
+// The memory table exports the memory it ends with iff the execution stopped.
+fn ctl_public_memory_export<F: Field>() -> CrossTableLookup<F> {
+    CrossTableLookup::public(
+        memory_export,
+        TableWithColumns::new(
+            Table::Memory,
+            memory_stark::ctl_data_export(),
+            Some(memory_stark::ctl_filter_first_row()),
+        ),
+    )
+}
+
+fn end_memory_image<F: Field>(public_values: &PublicValues) -> Vec<Vec<F>> {
+    public_values
+        .end_memory_image
+        .iter()
+        .map(|cell| cell.iter().map(|v| F::from_canonical_u64(*v)).collect())
+        .collect()
+}
+
+// Every address has one final cell when exporting, holding its last value.
+fn ctl_public_end_memory_image<F: Field>() -> CrossTableLookup<F> {
+    CrossTableLookup::public(
+        end_memory_image,
+        TableWithColumns::new(
+            Table::Memory,
+            memory_stark::ctl_data_end_image(),
+            Some(memory_stark::ctl_filter_end_image()),
+        ),
+    )
+}
+
+fn code_region<F: Field>(public_values: &PublicValues) -> Vec<Vec<F>> {
+    public_values
+        .code_region
+        .iter()
+        .map(|cell| cell.iter().map(|v| F::from_canonical_u64(*v)).collect())
+        .collect()
+}
+
+// Every mapped code cell is written once before the execution, and never
+// again.
+fn ctl_public_code_region<F: Field>() -> CrossTableLookup<F> {
+    CrossTableLookup::public(
+        code_region,
+        TableWithColumns::new(
+            Table::Memory,
+            memory_stark::ctl_data_code(),
+            Some(memory_stark::ctl_filter_code()),
+        ),
+    )
+}
+
 // Cross_Lookup_Table(looking_table, looked_table)
 /*fn ctl_bitwise_bitwise_fixed_table<F: Field>() -> CrossTableLookup<F> {
     CrossTableLookup::new(
@@ -640,27 +1000,37 @@ fn ctl_prog_chunk_storage<F: Field>() -> CrossTableLookup<F> {
 #[allow(unused_imports)]
 #[cfg(test)]
 mod tests {
-    use crate::generation::{generate_traces, GenerationInputs};
-    use crate::stark::config::StarkConfig;
-    use crate::stark::ola_stark::OlaStark;
-    use crate::stark::proof::PublicValues;
-    use crate::stark::prover::prove_with_traces;
+    use crate::fixtures::fixture;
+    use crate::generation::{generate_traces, generate_traces_checked, GenerationInputs};
+    use crate::stark::config::{StarkConfig, TranscriptHasher};
+    use crate::stark::error::{ProverError, VerifierError};
+    use crate::stark::layout::layout_fingerprints;
+    use crate::stark::ola_stark::{OlaStark, Table, NUM_TABLES};
+    use crate::stark::proof::{AllProof, ProofVersion, PublicValues};
+    use crate::stark::prover::{prove_with_checkpoints, prove_with_traces, resume_from_checkpoint};
     use crate::stark::serialization::Buffer;
     use crate::stark::stark::Stark;
     use crate::stark::util::trace_rows_to_poly_values;
-    use crate::stark::verifier::verify_proof;
+    use crate::stark::verifier::{
+        check_chunk_link, verify_chunk_proof, verify_proof, verify_proof_for_chain,
+        verify_proof_for_program, verify_proofs_parallel,
+    };
     use anyhow::Result;
-    use assembler::encoder::encode_asm_from_json_file;
+    use assembler::encoder::{encode_asm, encode_asm_from_json_file};
     use core::crypto::hash::Hasher;
-    use core::crypto::ZkHasher;
+    use core::crypto::{TreeHasher, ZkHasher};
     use core::merkle_tree::log::{StorageLog, WitnessStorageLog};
     use core::merkle_tree::tree::AccountTree;
     use core::program::binary_program::BinaryProgram;
     use core::program::Program;
+    use core::trace::trace::Step;
     use core::types::account::Address;
     use core::types::merkle_tree::{encode_addr, tree_key_default};
-    use core::types::{Field, GoldilocksField};
+    use core::types::{Field, GoldilocksField, PrimeField64};
+    use core::vm::chain_spec::{ChainSpec, ForkSpec};
+    use core::vm::opcodes::OlaOpcode;
     use core::vm::transaction::init_tx_context_mock;
+    use executor::continuation::execute_in_chunks;
     use executor::load_tx::init_tape;
     use executor::trace::{gen_storage_hash_table, gen_storage_table};
     use executor::Process;
@@ -668,7 +1038,7 @@ mod tests {
     use log::{debug, LevelFilter};
     use plonky2::plonk::config::{Blake3GoldilocksConfig, GenericConfig, PoseidonGoldilocksConfig};
     use plonky2::util::timing::TimingTree;
-    use std::collections::HashMap;
+    use std::collections::{BTreeMap, HashMap, HashSet};
     use std::fs::File;
     use std::io::{BufRead, BufReader};
     use std::mem;
@@ -813,12 +1183,954 @@ mod tests {
     //     test_by_asm_json("string_assert.json".to_string(), None);
     // }
 
+    #[test]
+    fn test_trace_generation_determinism() {
+        let calldata = [10u64, 1u64, 2, 4185064725u64]
+            .iter()
+            .map(|v| GoldilocksField::from_canonical_u64(*v))
+            .collect_vec();
+        let mut ola_stark = OlaStark::<F, D>::default();
+        generate_traces_checked(
+            || execute_asm_json("fib_asm.json".to_string(), Some(calldata.clone()), None).unwrap(),
+            &mut ola_stark,
+            GenerationInputs::default(),
+        );
+    }
+
+    #[test]
+    #[should_panic(expected = "differs between two executions")]
+    fn test_trace_generation_nondeterministic_execution() {
+        // every execution computes another fibonacci number
+        let n = std::cell::Cell::new(10u64);
+        let mut ola_stark = OlaStark::<F, D>::default();
+        generate_traces_checked(
+            || {
+                let calldata = [n.get(), 1u64, 2, 4185064725u64]
+                    .iter()
+                    .map(|v| GoldilocksField::from_canonical_u64(*v))
+                    .collect_vec();
+                n.set(n.get() + 1);
+                execute_asm_json("fib_asm.json".to_string(), Some(calldata), None).unwrap()
+            },
+            &mut ola_stark,
+            GenerationInputs::default(),
+        );
+    }
+
+    #[test]
+    fn test_prove_resume_from_checkpoint() {
+        let calldata = [10u64, 1u64, 2, 4185064725u64]
+            .iter()
+            .map(|v| GoldilocksField::from_canonical_u64(*v))
+            .collect_vec();
+        let program = execute_asm_json("fib_asm.json".to_string(), Some(calldata), None).unwrap();
+        let mut ola_stark = OlaStark::<F, D>::default();
+        let (traces, public_values) =
+            generate_traces(program, &mut ola_stark, GenerationInputs::default());
+        let config = StarkConfig::standard_fast_config();
+        let dir = std::env::temp_dir().join("ola_prover_checkpoint_test");
+        prove_with_checkpoints::<F, C, D>(
+            &ola_stark,
+            &config,
+            traces.clone(),
+            public_values.clone(),
+            &dir,
+            &mut TimingTree::default(),
+        )
+        .unwrap();
+
+        // lose the Z polys and the later table proofs, as if preempted.
+        std::fs::remove_file(dir.join("ctl_zs.bin")).unwrap();
+        for i in 6..NUM_TABLES {
+            std::fs::remove_file(dir.join(format!("table_{}.bin", i))).unwrap();
+        }
+        let proof = resume_from_checkpoint::<F, C, D>(
+            &ola_stark,
+            &config,
+            traces.clone(),
+            public_values.clone(),
+            &dir,
+            &mut TimingTree::default(),
+        )
+        .unwrap();
+        assert!(verify_proof(OlaStark::default(), proof, &config).is_ok());
+
+        let mut other_values = public_values;
+        other_values.program_hashes.push([1, 2, 3, 4]);
+        let res = resume_from_checkpoint::<F, C, D>(
+            &ola_stark,
+            &config,
+            traces,
+            other_values,
+            &dir,
+            &mut TimingTree::default(),
+        );
+        assert!(matches!(res, Err(ProverError::Checkpoint(_))));
+        std::fs::remove_dir_all(dir).unwrap();
+    }
+
+    #[test]
+    fn test_ctl_topology() {
+        let ola_stark = OlaStark::<F, D>::default();
+        let ctls = ola_stark.describe_ctls();
+        assert_eq!(ctls.len(), ola_stark.cross_table_lookups.len());
+
+        let mut tables = HashSet::new();
+        for ctl in &ctls {
+            assert!(!ctl.name.is_empty());
+            tables.insert(ctl.looked.table as usize);
+            for looking in &ctl.looking {
+                assert_eq!(looking.columns.len(), ctl.looked.columns.len());
+                tables.insert(looking.table as usize);
+            }
+        }
+        assert_eq!(
+            ctls.iter().map(|ctl| &ctl.name).unique().count(),
+            ctls.len()
+        );
+        // every table is tied to the others by some lookup.
+        assert_eq!(tables.len(), NUM_TABLES);
+
+        let dot = ola_stark.ctl_graphviz();
+        assert!(dot.starts_with("digraph ctl {"));
+        assert!(dot.contains("Cpu -> Memory [label=\"cpu_memory"));
+    }
+
+    #[test]
+    fn test_proof_version() {
+        let calldata = [10u64, 1u64, 2, 4185064725u64]
+            .iter()
+            .map(|v| GoldilocksField::from_canonical_u64(*v))
+            .collect_vec();
+        let program = execute_asm_json("fib_asm.json".to_string(), Some(calldata), None).unwrap();
+        let mut ola_stark = OlaStark::<F, D>::default();
+        let (traces, public_values) =
+            generate_traces(program, &mut ola_stark, GenerationInputs::default());
+        let config = StarkConfig::standard_fast_config();
+        let proof = prove_with_traces::<F, C, D>(
+            &ola_stark,
+            &config,
+            traces,
+            public_values,
+            &mut TimingTree::default(),
+        )
+        .unwrap();
+        assert_eq!(proof.version, ProofVersion::default());
+
+        let mut buffer = Buffer::new(Vec::new());
+        buffer.write_all_proof(&proof).unwrap();
+        let mut buffer = Buffer::new(buffer.bytes());
+        let mut proof = buffer.read_all_proof::<F, C, D>().unwrap();
+        assert_eq!(proof.version, ProofVersion::default());
+        assert_eq!(proof.layout, layout_fingerprints());
+
+        // a proof of a build with other tape columns
+        let mut drifted = proof.clone();
+        drifted.layout[Table::Tape as usize] ^= 1;
+        let res = verify_proof(OlaStark::default(), drifted, &config);
+        assert!(matches!(
+            res,
+            Err(VerifierError::LayoutMismatch(Table::Tape))
+        ));
+
+        proof.version = ProofVersion::legacy();
+        let mut buffer = Buffer::new(Vec::new());
+        buffer.write_all_proof(&proof).unwrap();
+        let legacy = Buffer::new(buffer.bytes())
+            .read_all_proof::<F, C, D>()
+            .unwrap();
+        assert_eq!(legacy.version, ProofVersion::legacy());
+        assert_eq!(legacy.compress_challenges, proof.compress_challenges);
+        // legacy proofs carry no public values
+        assert!(legacy.public_values.program_hashes.is_empty());
+        let res = verify_proof(OlaStark::default(), proof, &config);
+        assert!(matches!(
+            res,
+            Err(VerifierError::UnsupportedProofVersion(0))
+        ));
+    }
+
+    #[test]
+    fn test_transcript_hashers() {
+        let hashers = [TranscriptHasher::Poseidon, TranscriptHasher::Keccak];
+        for (i, hasher) in hashers.into_iter().enumerate() {
+            let calldata = [10u64, 1u64, 2, 4185064725u64]
+                .iter()
+                .map(|v| GoldilocksField::from_canonical_u64(*v))
+                .collect_vec();
+            let program =
+                execute_asm_json("fib_asm.json".to_string(), Some(calldata), None).unwrap();
+            let mut ola_stark = OlaStark::<F, D>::default();
+            let (traces, public_values) =
+                generate_traces(program, &mut ola_stark, GenerationInputs::default());
+            let config = StarkConfig::dev_config().with_transcript_hasher(hasher);
+            let proof = prove_with_traces::<F, C, D>(
+                &ola_stark,
+                &config,
+                traces,
+                public_values,
+                &mut TimingTree::default(),
+            )
+            .unwrap();
+            verify_proof(OlaStark::default(), proof.clone(), &config).unwrap();
+
+            // challenges drawn with the other hasher do not match the proof
+            let other = config.with_transcript_hasher(hashers[1 - i]);
+            assert!(verify_proof(OlaStark::default(), proof, &other).is_err());
+        }
+    }
+
+    #[test]
+    fn test_verifier_rejects_tampered_public_values() {
+        let calldata = [10u64, 1u64, 2, 4185064725u64]
+            .iter()
+            .map(|v| GoldilocksField::from_canonical_u64(*v))
+            .collect_vec();
+        let program = execute_asm_json("fib_asm.json".to_string(), Some(calldata), None).unwrap();
+        let mut ola_stark = OlaStark::<F, D>::default();
+        let (traces, public_values) =
+            generate_traces(program, &mut ola_stark, GenerationInputs::default());
+        let config = StarkConfig::standard_fast_config();
+        let proof = prove_with_traces::<F, C, D>(
+            &ola_stark,
+            &config,
+            traces,
+            public_values,
+            &mut TimingTree::default(),
+        )
+        .unwrap();
+        verify_proof(OlaStark::default(), proof.clone(), &config).unwrap();
+
+        let mut buffer = Buffer::new(Vec::new());
+        buffer.write_all_proof(&proof).unwrap();
+        let read = Buffer::new(buffer.bytes())
+            .read_all_proof::<F, C, D>()
+            .unwrap();
+        assert_eq!(
+            read.public_values.trie_roots_after.state_root,
+            proof.public_values.trie_roots_after.state_root
+        );
+        verify_proof(OlaStark::default(), read, &config).unwrap();
+        // executed without a chain spec, so under no fork of one
+        assert!(matches!(
+            verify_proof_for_chain(
+                OlaStark::default(),
+                proof.clone(),
+                &ChainSpec::default(),
+                &config
+            ),
+            Err(VerifierError::ForkIdMismatch(0))
+        ));
+
+        let mutations: Vec<(&str, fn(&mut AllProof<F, C, D>))> = vec![
+            ("prophet commitment", |p| {
+                p.public_values.prophet_commitment[0] ^= 1
+            }),
+            ("program hash", |p| {
+                p.public_values.program_hashes[0][0] ^= 1
+            }),
+            ("fork id", |p| p.public_values.fork_id[0] ^= 1),
+            ("chain id", |p| p.public_values.chain_id ^= 1),
+            ("tx hash", |p| p.public_values.tx_hash[0] ^= 1),
+            ("gas used", |p| p.public_values.gas_used ^= 1),
+            ("returned value", |p| {
+                p.public_values.returned_values.push(1)
+            }),
+            ("memory image", |p| {
+                p.public_values.memory_image.push([1, 2])
+            }),
+            ("code region", |p| p.public_values.code_region.push([1, 2])),
+            ("extra program hash", |p| {
+                p.public_values.program_hashes.push([1, 2, 3, 4])
+            }),
+            ("state root before", |p| {
+                p.public_values.trie_roots_before.state_root.0[31] ^= 1
+            }),
+            ("state root after", |p| {
+                p.public_values.trie_roots_after.state_root.0[31] ^= 1
+            }),
+            ("transactions root", |p| {
+                p.public_values.trie_roots_after.transactions_root.0[0] ^= 1
+            }),
+            ("block number", |p| {
+                p.public_values.block_metadata.block_number += ethereum_types::U256::one()
+            }),
+            ("block beneficiary", |p| {
+                p.public_values.block_metadata.block_beneficiary.0[0] ^= 1
+            }),
+            ("bitwise compress challenge", |p| {
+                p.compress_challenges[Table::Bitwise as usize] += F::ONE
+            }),
+            ("program compress challenge", |p| {
+                p.compress_challenges[Table::Program as usize] += F::ONE
+            }),
+            ("unused compress challenge", |p| {
+                p.compress_challenges[Table::Cpu as usize] += F::ONE
+            }),
+        ];
+        for (name, mutate) in mutations {
+            let mut tampered = proof.clone();
+            mutate(&mut tampered);
+            let res = verify_proof(OlaStark::default(), tampered, &config);
+            assert!(res.is_err(), "tampered {} verified", name);
+        }
+
+        verify_proofs_parallel(
+            OlaStark::default(),
+            &[proof.clone(), proof.clone()],
+            &config,
+        )
+        .unwrap();
+        let mut tampered = proof.clone();
+        tampered.compress_challenges[Table::Bitwise as usize] += F::ONE;
+        let res = verify_proofs_parallel(OlaStark::default(), &[proof.clone(), tampered], &config);
+        assert!(matches!(res, Err(VerifierError::InBatch(1, _))));
+
+        let mut tampered = proof;
+        tampered.public_values.trie_roots_before.state_root.0[31] ^= 1;
+        let res = verify_proof(OlaStark::default(), tampered, &config);
+        assert!(matches!(
+            res,
+            Err(VerifierError::CompressChallengeMismatch(Table::Program))
+        ));
+    }
+
+    #[test]
+    fn test_executed_opcodes_checked_against_fork() {
+        let fib = || {
+            let calldata = [10u64, 1u64, 2, 4185064725u64]
+                .iter()
+                .map(|v| GoldilocksField::from_canonical_u64(*v))
+                .collect_vec();
+            execute_asm_json("fib_asm.json".to_string(), Some(calldata), None).unwrap()
+        };
+        let config = StarkConfig::standard_fast_config();
+        let mut ola_stark = OlaStark::<F, D>::default();
+        let (traces, public_values) =
+            generate_traces(fib(), &mut ola_stark, GenerationInputs::default());
+        let end = OlaOpcode::END.binary_bit_mask();
+        assert!(public_values.executed_opcodes.contains(&[end, 1]));
+        let proof = prove_with_traces::<F, C, D>(
+            &ola_stark,
+            &config,
+            traces,
+            public_values,
+            &mut TimingTree::default(),
+        )
+        .unwrap();
+        verify_proof(OlaStark::default(), proof.clone(), &config).unwrap();
+
+        // only add is enabled, fib ends at least
+        let spec = ChainSpec {
+            forks: vec![ForkSpec {
+                height: 0,
+                opcodes: Some(vec!["add".to_string()]),
+                builtins: None,
+            }],
+            ..ChainSpec::default()
+        };
+        let mut claimed = proof;
+        claimed.public_values.fork_id = spec.fork_id(0).map(|limb| limb.to_canonical_u64());
+        let res = verify_proof_for_chain(OlaStark::default(), claimed, &spec, &config);
+        assert!(matches!(res, Err(VerifierError::OpcodeDisabled(_, 0))));
+
+        // a histogram leaving out an executed opcode fails the lookup, even
+        // when the prover commits to it
+        let mut ola_stark = OlaStark::<F, D>::default();
+        let (traces, mut public_values) =
+            generate_traces(fib(), &mut ola_stark, GenerationInputs::default());
+        public_values.executed_opcodes.pop();
+        let forged = prove_with_traces::<F, C, D>(
+            &ola_stark,
+            &config,
+            traces,
+            public_values,
+            &mut TimingTree::default(),
+        )
+        .unwrap();
+        let res = verify_proof(OlaStark::default(), forged, &config);
+        assert!(matches!(res, Err(VerifierError::CtlFailure(Table::Cpu, _))));
+    }
+
+    #[test]
+    fn test_chunk_proofs() {
+        let asm = "main:\n.LBL0_0:\nmov r1 0\nmov r2 100\n.LBL0_1:\nadd r1 r1 1\n\
+            mstore [r2,0] r1\nmload r3 [r2,0]\nneq r4 r1 10\ncjmp r4 .LBL0_1\nend";
+        let binary = encode_asm(asm).unwrap();
+        let mut program = Program {
+            instructions: binary.bytecode.split("\n").map(|e| e.to_string()).collect(),
+            ..Default::default()
+        };
+        // every chunk looks its instructions up in the code at the address
+        // the process runs, and reads the code hash from the tree
+        let code: Vec<_> = program
+            .instructions
+            .iter()
+            .map(|e| GoldilocksField::from_canonical_u64(u64::from_str_radix(&e[2..], 16).unwrap()))
+            .collect();
+        let code_hash = ZkHasher::default().hash_bytes(&code);
+        let exe_addr = [13, 14, 15, 16].map(GoldilocksField::from_canonical_u64);
+        program
+            .trace
+            .addr_program_hash
+            .insert(encode_addr(&exe_addr), code);
+        let mut tree = AccountTree::new_test();
+        tree.process_block(vec![WitnessStorageLog {
+            storage_log: StorageLog::new_write_log(exe_addr, code_hash),
+            previous_value: tree_key_default(),
+        }]);
+        tree.commit().unwrap();
+        let mut process = Process::new();
+        process.addr_code = exe_addr;
+        process.program_log.push(WitnessStorageLog {
+            storage_log: StorageLog::new_read_log(exe_addr, code_hash),
+            previous_value: tree_key_default(),
+        });
+        let chunks = execute_in_chunks(process, &program, &mut tree, 16).unwrap();
+        let chunk = &chunks[1];
+        assert_ne!(chunk.start.pc, 0);
+
+        let config = StarkConfig::standard_fast_config();
+        let prove = |program: &Program, forge: &dyn Fn(&mut PublicValues)| {
+            let mut ola_stark = OlaStark::<F, D>::default();
+            let (traces, mut public_values) =
+                generate_traces(program.clone(), &mut ola_stark, GenerationInputs::default());
+            forge(&mut public_values);
+            prove_with_traces::<F, C, D>(
+                &ola_stark,
+                &config,
+                traces,
+                public_values,
+                &mut TimingTree::default(),
+            )
+            .unwrap()
+        };
+        let proof = prove(&chunk.program, &|_| ());
+        assert_eq!(proof.public_values.start_pc, chunk.start.pc);
+        verify_chunk_proof(OlaStark::default(), proof.clone(), &chunk.start, &config).unwrap();
+
+        // neither a whole execution nor a chunk starting elsewhere
+        let res = verify_proof(OlaStark::default(), proof.clone(), &config);
+        assert!(matches!(res, Err(VerifierError::StartStateMismatch)));
+        let res = verify_chunk_proof(OlaStark::default(), proof.clone(), &chunk.end, &config);
+        assert!(matches!(res, Err(VerifierError::StartStateMismatch)));
+
+        // start registers the first cpu row does not hold fail the lookup,
+        // even when the prover commits to them
+        let mut claimed = chunk.start.clone();
+        claimed.registers[1] += GoldilocksField::ONE;
+        let forged = prove(&chunk.program, &|public_values| {
+            public_values.start_registers[1] += 1
+        });
+        let res = verify_chunk_proof(OlaStark::default(), forged, &claimed, &config);
+        assert!(matches!(res, Err(VerifierError::CtlFailure(Table::Cpu, _))));
+
+        // a memory image the start commitment is not over
+        let mut claimed = chunk.start.clone();
+        claimed.memory_commitment[0] += GoldilocksField::ONE;
+        let res = verify_chunk_proof(OlaStark::default(), proof.clone(), &claimed, &config);
+        assert!(matches!(res, Err(VerifierError::StartStateMismatch)));
+
+        // the first chunk stops where the second starts, its memory included
+        let first = prove(&chunks[0].program, &|_| ());
+        verify_chunk_proof(
+            OlaStark::default(),
+            first.clone(),
+            &chunks[0].start,
+            &config,
+        )
+        .unwrap();
+        assert!(first.public_values.stopped);
+        assert_eq!(first.public_values.end_pc, chunk.start.pc);
+        assert!(!first.public_values.end_memory_image.is_empty());
+        check_chunk_link(&first.public_values, &proof.public_values).unwrap();
+        assert!(matches!(
+            check_chunk_link(&proof.public_values, &proof.public_values),
+            Err(VerifierError::StartStateMismatch)
+        ));
+
+        // end state and memory the tables don't end with fail the lookups
+        let forged = prove(&chunks[0].program, &|public_values| {
+            public_values.end_pc += 1
+        });
+        let res = verify_chunk_proof(OlaStark::default(), forged, &chunks[0].start, &config);
+        assert!(matches!(res, Err(VerifierError::CtlFailure(Table::Cpu, _))));
+        let forged = prove(&chunks[0].program, &|public_values| {
+            public_values.end_memory_image[0][1] += 1
+        });
+        let res = verify_chunk_proof(OlaStark::default(), forged, &chunks[0].start, &config);
+        assert!(matches!(
+            res,
+            Err(VerifierError::CtlFailure(Table::Memory, _))
+        ));
+        let forged = prove(&chunks[0].program, &|public_values| {
+            public_values.stopped = false;
+            public_values.end_memory_image.clear();
+        });
+        let res = verify_chunk_proof(OlaStark::default(), forged, &chunks[0].start, &config);
+        assert!(matches!(
+            res,
+            Err(VerifierError::CtlFailure(Table::Memory, _))
+        ));
+    }
+
+    #[test]
+    fn test_prover_rejects_poseidon2_storage() {
+        let call_data = vec![
+            GoldilocksField::from_canonical_u64(10),
+            GoldilocksField::from_canonical_u64(1),
+            GoldilocksField::from_canonical_u64(2),
+            GoldilocksField::from_canonical_u64(4185064725),
+        ];
+        let mut program =
+            execute_asm_json("fib_asm.json".to_string(), Some(call_data), None).unwrap();
+        program.trace.tree_hasher = TreeHasher::Poseidon2;
+        let mut ola_stark = OlaStark::<F, D>::default();
+        let res = crate::stark::prover::prove::<F, C, D>(
+            program,
+            &mut ola_stark,
+            GenerationInputs::default(),
+            &StarkConfig::standard_fast_config(),
+            &mut TimingTree::default(),
+        );
+        assert!(matches!(
+            res,
+            Err(ProverError::UnsupportedTreeHasher(TreeHasher::Poseidon2))
+        ));
+    }
+
+    #[test]
+    fn test_prover_with_forged_public_values_fails() {
+        let call_data = vec![
+            GoldilocksField::from_canonical_u64(0),
+            GoldilocksField::from_canonical_u64(2364819430),
+        ];
+        let config = StarkConfig::standard_fast_config();
+        let prove = |forge_root: bool, forge_program_hash: bool| {
+            let mut program = execute_asm_json(
+                "storage_u32.json".to_string(),
+                Some(call_data.clone()),
+                None,
+            )
+            .unwrap();
+            if forge_root {
+                program.trace.start_end_roots.1[0] += GoldilocksField::ONE;
+            }
+            let mut ola_stark = OlaStark::<F, D>::default();
+            let (traces, mut public_values) =
+                generate_traces(program, &mut ola_stark, GenerationInputs::default());
+            if forge_program_hash {
+                public_values.program_hashes[0][0] ^= 1;
+            }
+            let proof = prove_with_traces::<F, C, D>(
+                &ola_stark,
+                &config,
+                traces,
+                public_values,
+                &mut TimingTree::default(),
+            )
+            .unwrap();
+            verify_proof(OlaStark::default(), proof, &config)
+        };
+
+        prove(false, false).unwrap();
+        assert!(matches!(
+            prove(true, false),
+            Err(VerifierError::CtlFailure(Table::StorageAccess, _))
+        ));
+        assert!(matches!(
+            prove(false, true),
+            Err(VerifierError::CtlFailure(Table::ProgChunk, _))
+        ));
+    }
+
+    #[test]
+    fn test_transient_storage() {
+        let config = StarkConfig::standard_fast_config();
+        let prove = |forge: bool| {
+            let mut program = fixture("transient_lock").unwrap().execute().unwrap();
+            assert_eq!(program.trace.transient.len(), 5);
+            if forge {
+                // the slots of another contract
+                for row in program.trace.transient.iter_mut() {
+                    row.addr_storage[0] += GoldilocksField::ONE;
+                }
+            }
+            let mut ola_stark = OlaStark::<F, D>::default();
+            let (traces, public_values) =
+                generate_traces(program, &mut ola_stark, GenerationInputs::default());
+            let proof = prove_with_traces::<F, C, D>(
+                &ola_stark,
+                &config,
+                traces,
+                public_values,
+                &mut TimingTree::default(),
+            )
+            .unwrap();
+            verify_proof(OlaStark::default(), proof, &config)
+        };
+
+        prove(false).unwrap();
+        assert!(matches!(
+            prove(true),
+            Err(VerifierError::CtlFailure(Table::Transient, _))
+        ));
+    }
+
+    #[test]
+    fn test_storage_scan() {
+        let config = StarkConfig::standard_fast_config();
+        let prove = |forge: fn(&mut [&mut Step])| {
+            let mut program = fixture("storage_scan").unwrap().execute().unwrap();
+            let sscan = OlaOpcode::SSCAN.binary_bit_mask();
+            // the start record, then a slot line and a link record per slot
+            let mut lines = program
+                .trace
+                .exec
+                .iter_mut()
+                .filter(|row| row.opcode.0 == sscan && row.ext_cnt.0 > 1)
+                .collect_vec();
+            assert_eq!(lines.len(), 5);
+            forge(&mut lines);
+            let mut ola_stark = OlaStark::<F, D>::default();
+            let (traces, public_values) =
+                generate_traces(program, &mut ola_stark, GenerationInputs::default());
+            let proof = prove_with_traces::<F, C, D>(
+                &ola_stark,
+                &config,
+                traces,
+                public_values,
+                &mut TimingTree::default(),
+            )
+            .unwrap();
+            verify_proof(OlaStark::default(), proof, &config)
+        };
+
+        prove(|_| {}).unwrap();
+        // the first slot read another value than it wrote
+        assert!(matches!(
+            prove(|lines| lines[1].register_selector.op1_reg_sel[4] += GoldilocksField::ONE),
+            Err(VerifierError::CtlFailure(Table::Memory, _))
+        ));
+        // the start record read another value than storage holds
+        assert!(matches!(
+            prove(|lines| lines[0].register_selector.op1_reg_sel[7] += GoldilocksField::ONE),
+            Err(VerifierError::CtlFailure(Table::StorageAccess, _))
+        ));
+    }
+
+    #[test]
+    fn test_tx_context() {
+        let config = StarkConfig::standard_fast_config();
+        let prove = |forge: bool| {
+            let program = fixture("tx_context").unwrap().execute().unwrap();
+            let mut ola_stark = OlaStark::<F, D>::default();
+            let (traces, mut public_values) =
+                generate_traces(program, &mut ola_stark, GenerationInputs::default());
+            assert_eq!(public_values.chain_id, 1);
+            assert_eq!(public_values.tx_hash, [137, 138, 139, 140]);
+            if forge {
+                // txctx read another tx hash than the proof claims
+                public_values.tx_hash[3] += 1;
+            }
+            let proof = prove_with_traces::<F, C, D>(
+                &ola_stark,
+                &config,
+                traces,
+                public_values,
+                &mut TimingTree::default(),
+            )
+            .unwrap();
+            verify_proof(OlaStark::default(), proof, &config)
+        };
+
+        prove(false).unwrap();
+        assert!(matches!(
+            prove(true),
+            Err(VerifierError::CtlFailure(Table::Cpu, _))
+        ));
+    }
+
+    #[test]
+    fn test_returned_values() {
+        let config = StarkConfig::standard_fast_config();
+        let prove = |forge: fn(&mut Vec<u64>)| {
+            let program = fixture("returned_values").unwrap().execute().unwrap();
+            let mut ola_stark = OlaStark::<F, D>::default();
+            let (traces, mut public_values) =
+                generate_traces(program, &mut ola_stark, GenerationInputs::default());
+            assert_eq!(public_values.returned_values, vec![7, 9]);
+            forge(&mut public_values.returned_values);
+            let proof = prove_with_traces::<F, C, D>(
+                &ola_stark,
+                &config,
+                traces,
+                public_values,
+                &mut TimingTree::default(),
+            )
+            .unwrap();
+            verify_proof(OlaStark::default(), proof, &config)
+        };
+
+        prove(|_| {}).unwrap();
+        // the end row returned other values, or more or fewer of them, than
+        // the proof claims
+        let forgeries: Vec<fn(&mut Vec<u64>)> = vec![
+            |values| values[1] += 1,
+            |values| values.push(11),
+            |values| {
+                values.pop();
+            },
+        ];
+        for forge in forgeries {
+            assert!(matches!(
+                prove(forge),
+                Err(VerifierError::CtlFailure(Table::Cpu, _))
+            ));
+        }
+    }
+
+    #[test]
+    fn test_gas_used() {
+        let call_data = vec![
+            GoldilocksField::from_canonical_u64(10),
+            GoldilocksField::from_canonical_u64(1),
+            GoldilocksField::from_canonical_u64(2),
+            GoldilocksField::from_canonical_u64(4185064725),
+        ];
+        let config = StarkConfig::standard_fast_config();
+        let prove = |forge: fn(&mut u64)| {
+            let program =
+                execute_asm_json("fib_asm.json".to_string(), Some(call_data.clone()), None)
+                    .unwrap();
+            let mut ola_stark = OlaStark::<F, D>::default();
+            let (traces, mut public_values) =
+                generate_traces(program.clone(), &mut ola_stark, GenerationInputs::default());
+            assert_eq!(public_values.gas_used, program.trace.gas_used);
+            forge(&mut public_values.gas_used);
+            let proof = prove_with_traces::<F, C, D>(
+                &ola_stark,
+                &config,
+                traces,
+                public_values,
+                &mut TimingTree::default(),
+            )
+            .unwrap();
+            verify_proof(OlaStark::default(), proof, &config)
+        };
+
+        prove(|_| {}).unwrap();
+        // gas the cpu table did not charge its steps
+        assert!(matches!(
+            prove(|gas_used| *gas_used -= 1),
+            Err(VerifierError::CtlFailure(Table::Cpu, _))
+        ));
+    }
+
+    #[test]
+    fn test_memory_image() {
+        let calldata = [10u64, 1u64, 2, 4185064725u64]
+            .iter()
+            .map(|v| GoldilocksField::from_canonical_u64(*v))
+            .collect_vec();
+        let image = BTreeMap::from([
+            (100, GoldilocksField::from_canonical_u64(41)),
+            (101, GoldilocksField::from_canonical_u64(7)),
+        ]);
+        let config = StarkConfig::standard_fast_config();
+        let prove = |forge: bool| {
+            let program = execute_asm_json_with(
+                "fib_asm.json".to_string(),
+                Some(calldata.clone()),
+                None,
+                |process| process.load_memory_image(&image).unwrap(),
+            )
+            .unwrap();
+            let mut ola_stark = OlaStark::<F, D>::default();
+            let (traces, mut public_values) =
+                generate_traces(program, &mut ola_stark, GenerationInputs::default());
+            assert_eq!(public_values.memory_image, vec![[100, 41], [101, 7]]);
+            if forge {
+                public_values.memory_image[0][1] += 1;
+            }
+            prove_with_traces::<F, C, D>(
+                &ola_stark,
+                &config,
+                traces,
+                public_values,
+                &mut TimingTree::default(),
+            )
+            .unwrap()
+        };
+
+        let proof = prove(false);
+        let mut buffer = Buffer::new(Vec::new());
+        buffer.write_all_proof(&proof).unwrap();
+        let read = Buffer::new(buffer.bytes())
+            .read_all_proof::<F, C, D>()
+            .unwrap();
+        assert_eq!(
+            read.public_values.memory_image,
+            proof.public_values.memory_image
+        );
+        verify_proof(OlaStark::default(), read, &config).unwrap();
+
+        // the prover claims the execution started from another image
+        assert!(matches!(
+            verify_proof(OlaStark::default(), prove(true), &config),
+            Err(VerifierError::CtlFailure(Table::Memory, _))
+        ));
+    }
+
+    #[test]
+    fn test_code_region() {
+        let calldata = [10u64, 1u64, 2, 4185064725u64]
+            .iter()
+            .map(|v| GoldilocksField::from_canonical_u64(*v))
+            .collect_vec();
+        let code = [
+            GoldilocksField::from_canonical_u64(5),
+            GoldilocksField::from_canonical_u64(6),
+        ];
+        let config = StarkConfig::standard_fast_config();
+        let prove = |forge: bool| {
+            let program = execute_asm_json_with(
+                "fib_asm.json".to_string(),
+                Some(calldata.clone()),
+                None,
+                |process| process.map_code(200, &code).unwrap(),
+            )
+            .unwrap();
+            let mut ola_stark = OlaStark::<F, D>::default();
+            let (traces, mut public_values) =
+                generate_traces(program, &mut ola_stark, GenerationInputs::default());
+            assert_eq!(public_values.code_region, vec![[200, 5], [201, 6]]);
+            if forge {
+                public_values.code_region[1][1] += 1;
+            }
+            prove_with_traces::<F, C, D>(
+                &ola_stark,
+                &config,
+                traces,
+                public_values,
+                &mut TimingTree::default(),
+            )
+            .unwrap()
+        };
+
+        let proof = prove(false);
+        let mut buffer = Buffer::new(Vec::new());
+        buffer.write_all_proof(&proof).unwrap();
+        let read = Buffer::new(buffer.bytes())
+            .read_all_proof::<F, C, D>()
+            .unwrap();
+        assert_eq!(
+            read.public_values.code_region,
+            proof.public_values.code_region
+        );
+        verify_proof(OlaStark::default(), read, &config).unwrap();
+
+        // the prover claims other code was mapped
+        assert!(matches!(
+            verify_proof(OlaStark::default(), prove(true), &config),
+            Err(VerifierError::CtlFailure(Table::Memory, _))
+        ));
+    }
+
+    #[test]
+    fn test_verify_proof_for_program() {
+        let calldata = [10u64, 1u64, 2, 4185064725u64]
+            .iter()
+            .map(|v| GoldilocksField::from_canonical_u64(*v))
+            .collect_vec();
+        let config = StarkConfig::standard_fast_config();
+        let prove = |claimed_hash: Option<[u64; 4]>| {
+            let program =
+                execute_asm_json("fib_asm.json".to_string(), Some(calldata.clone()), None).unwrap();
+            let mut ola_stark = OlaStark::<F, D>::default();
+            let (traces, mut public_values) =
+                generate_traces(program, &mut ola_stark, GenerationInputs::default());
+            if let Some(hash) = claimed_hash {
+                public_values.program_hashes[0] = hash;
+            }
+            prove_with_traces::<F, C, D>(
+                &ola_stark,
+                &config,
+                traces,
+                public_values,
+                &mut TimingTree::default(),
+            )
+            .unwrap()
+        };
+
+        let proof = prove(None);
+        let executed = proof.public_values.program_hashes[0];
+        verify_proof_for_program(OlaStark::default(), proof.clone(), &[executed], &config).unwrap();
+        assert!(matches!(
+            verify_proof_for_program(OlaStark::default(), proof, &[[1, 2, 3, 4]], &config),
+            Err(VerifierError::ProgramNotWhitelisted(hash)) if hash == executed
+        ));
+
+        // the prover claims a whitelisted program it did not execute
+        let whitelisted = [1, 2, 3, 4];
+        let forged = prove(Some(whitelisted));
+        assert!(matches!(
+            verify_proof_for_program(OlaStark::default(), forged, &[whitelisted], &config),
+            Err(VerifierError::CtlFailure(Table::ProgChunk, _))
+        ));
+    }
+
     #[allow(unused)]
     pub fn test_by_asm_json(
         file_name: String,
         call_data: Option<Vec<GoldilocksField>>,
         db_name: Option<String>,
     ) {
+        let program = match execute_asm_json(file_name, call_data, db_name) {
+            Some(program) => program,
+            None => return,
+        };
+
+        let inputs = GenerationInputs::default();
+
+        let mut ola_stark = OlaStark::default();
+        let (traces, public_values) = generate_traces(program, &mut ola_stark, inputs);
+        let config = StarkConfig::standard_fast_config();
+        let proof = prove_with_traces::<F, C, D>(
+            &ola_stark,
+            &config,
+            traces,
+            public_values,
+            &mut TimingTree::default(),
+        );
+
+        if let Ok(proof) = proof {
+            let ola_stark = OlaStark::default();
+            let verify_res = verify_proof(ola_stark, proof, &config);
+            println!("verify result:{:?}", verify_res);
+        } else {
+            println!("proof err:{:?}", proof);
+        }
+    }
+
+    /// Runs an asm json program and fills in its storage tables, or returns
+    /// `None` if execution fails.
+    fn execute_asm_json(
+        file_name: String,
+        call_data: Option<Vec<GoldilocksField>>,
+        db_name: Option<String>,
+    ) -> Option<Program> {
+        execute_asm_json_with(file_name, call_data, db_name, |_| {})
+    }
+
+    /// `execute_asm_json` with the process prepared by `setup`, e.g. with a
+    /// memory image or a mapped code region.
+    fn execute_asm_json_with(
+        file_name: String,
+        call_data: Option<Vec<GoldilocksField>>,
+        db_name: Option<String>,
+        setup: impl FnOnce(&mut Process),
+    ) -> Option<Program> {
         let mut path = PathBuf::from(env!("CARGO_MANIFEST_DIR"));
         path.push("../assembler/test_data/asm/");
         path.push(file_name);
@@ -856,6 +2168,7 @@ mod tests {
 
         let mut process = Process::new();
         process.addr_storage = Address::default();
+        setup(&mut process);
 
         let tp_start = 0;
 
@@ -902,7 +2215,7 @@ mod tests {
             storage_log: StorageLog::new_write_log(callee_exe_addr, code_hash),
             previous_value: tree_key_default(),
         }]);
-        let _ = db.save();
+        db.commit().unwrap();
 
         let start = db.root_hash();
 
@@ -917,32 +2230,13 @@ mod tests {
             Ok(_) => {}
             Err(e) => {
                 println!("execute err:{:?}", e);
-                return;
+                return None;
             }
         }
         let hash_roots = gen_storage_hash_table(&mut process, &mut program, &mut db);
         gen_storage_table(&mut process, &mut program, hash_roots).unwrap();
+        process.finalize(&mut db, true).unwrap();
         program.trace.start_end_roots = (start, db.root_hash());
-
-        let inputs = GenerationInputs::default();
-
-        let mut ola_stark = OlaStark::default();
-        let (traces, public_values) = generate_traces(program, &mut ola_stark, inputs);
-        let config = StarkConfig::standard_fast_config();
-        let proof = prove_with_traces::<F, C, D>(
-            &ola_stark,
-            &config,
-            traces,
-            public_values,
-            &mut TimingTree::default(),
-        );
-
-        if let Ok(proof) = proof {
-            let ola_stark = OlaStark::default();
-            let verify_res = verify_proof(ola_stark, proof, &config);
-            println!("verify result:{:?}", verify_res);
-        } else {
-            println!("proof err:{:?}", proof);
-        }
+        Some(program)
     }
 }
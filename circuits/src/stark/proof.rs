@@ -1,3 +1,4 @@
+use core::program::REGISTER_NUM;
 use ethereum_types::{Address, H256, U256};
 use itertools::Itertools;
 use maybe_rayon::*;
@@ -10,26 +11,91 @@ use plonky2::fri::structure::{
 use plonky2::hash::hash_types::{MerkleCapTarget, RichField};
 use plonky2::hash::hashing::SPONGE_WIDTH;
 use plonky2::hash::merkle_tree::MerkleCap;
+use plonky2::iop::challenger::Challenger;
 use plonky2::iop::ext_target::ExtensionTarget;
 use plonky2::iop::target::Target;
-use plonky2::plonk::config::GenericConfig;
+use plonky2::plonk::config::{GenericConfig, Hasher};
 use serde::{Deserialize, Serialize};
 
 use super::config::StarkConfig;
 use super::ola_stark::NUM_TABLES;
 use super::permutation::GrandProductChallengeSet;
+use super::util::{h160_limbs, h256_limbs, u256_limbs};
+
+/// Layout version of proofs produced by this build.
+///
+/// Bump it whenever the transcript or the shape of a proof changes, and
+/// branch on `ProofVersion` wherever verification differs.
+pub const PROOF_VERSION: u32 = 1;
+
+/// Version a proof was generated under. Proofs written before versioning
+/// carry no version and are version 0.
+#[derive(Debug, Copy, Clone, PartialEq, Eq, Hash, Serialize, Deserialize)]
+#[serde(transparent)]
+pub struct ProofVersion(pub u32);
+
+impl ProofVersion {
+    pub fn legacy() -> Self {
+        Self(0)
+    }
+
+    /// v1 binds the public values and the compress challenges into the
+    /// Fiat-Shamir transcript and writes them with the proof, and derives the
+    /// program compress challenge from the state roots. v0 bound the trace
+    /// caps only and carried no public values.
+    pub fn binds_public_values(&self) -> bool {
+        self.0 >= 1
+    }
+
+    /// v1 also writes field elements big-endian like the other public
+    /// formats, v0 wrote them little-endian.
+    pub fn big_endian_fields(&self) -> bool {
+        self.0 >= 1
+    }
+
+    /// v1 also carries the column layout fingerprint of every table.
+    pub fn carries_layout(&self) -> bool {
+        self.0 >= 1
+    }
+
+    /// Absorb what a proof of this version binds besides the trace caps.
+    pub(crate) fn observe_public_inputs<F: RichField, H: Hasher<F>>(
+        &self,
+        public_values: &PublicValues,
+        compress_challenges: &[F; NUM_TABLES],
+        challenger: &mut Challenger<F, H>,
+    ) {
+        if self.binds_public_values() {
+            public_values.observe(challenger);
+            challenger.observe_elements(compress_challenges);
+        }
+    }
+}
+
+impl Default for ProofVersion {
+    fn default() -> Self {
+        Self(PROOF_VERSION)
+    }
+}
 
 #[derive(Debug, Clone, Serialize, Deserialize)]
 #[serde(bound = "")]
 pub struct AllProof<F: RichField + Extendable<D>, C: GenericConfig<D, F = F>, const D: usize> {
+    #[serde(default = "ProofVersion::legacy")]
+    pub version: ProofVersion,
     pub stark_proofs: [StarkProof<F, C, D>; NUM_TABLES],
     pub compress_challenges: [F; NUM_TABLES],
     pub public_values: PublicValues,
+    /// `layout_fingerprints` of the build that made the proof, zero for v0.
+    #[serde(default)]
+    pub layout: [u64; NUM_TABLES],
 }
 
 impl<F: RichField + Extendable<D>, C: GenericConfig<D, F = F>, const D: usize> AllProof<F, C, D> {
     pub fn degree_bits(&self, config: &StarkConfig) -> [usize; NUM_TABLES] {
-        std::array::from_fn(|i| self.stark_proofs[i].recover_degree_bits(config))
+        std::array::from_fn(|i| {
+            self.stark_proofs[i].recover_degree_bits(&config.for_table_index(i))
+        })
     }
 }
 
@@ -58,6 +124,146 @@ pub struct PublicValues {
     pub trie_roots_before: TrieRoots,
     pub trie_roots_after: TrieRoots,
     pub block_metadata: BlockMetadata,
+    /// Poseidon commitment over all prophet outputs, as canonical u64s.
+    /// Reported by the executor, the tables don't compute it. Outputs a
+    /// chunk carries over from the previous one are in its memory image.
+    #[serde(default)]
+    pub prophet_commitment: [u64; 4],
+    /// Chunked poseidon hashes of the executed programs, as canonical u64s.
+    #[serde(default)]
+    pub program_hashes: Vec<[u64; 4]>,
+    /// `ChainSpec::fork_id` of the block, as canonical u64s. Zero when the
+    /// block ran without a chain spec. `verify_proof_for_chain` checks it
+    /// along with `executed_opcodes`.
+    #[serde(default)]
+    pub fork_id: [u64; 4],
+    /// Chain id of the executed transaction, from its context.
+    #[serde(default)]
+    pub chain_id: u64,
+    /// Hash of the executed transaction, as canonical u64s.
+    #[serde(default)]
+    pub tx_hash: [u64; 4],
+    /// Values the program returned in `r0..` at its end, empty unless it
+    /// declared its return arity. Looked up in the entry END row of the cpu
+    /// table.
+    #[serde(default)]
+    pub returned_values: Vec<u64>,
+    /// `(addr, value)` of every cell preloaded by `load_memory_image`, by
+    /// address, as canonical u64s.
+    #[serde(default)]
+    pub memory_image: Vec<[u64; 2]>,
+    /// `(addr, value)` of every cell mapped read only by `map_code`, by
+    /// address, as canonical u64s.
+    #[serde(default)]
+    pub code_region: Vec<[u64; 2]>,
+    /// `(opcode bit mask, count)` of every opcode the cpu table executes, by
+    /// mask. Looked up in the instruction rows of the cpu table, so the
+    /// verifier can check the opcodes against a fork.
+    #[serde(default)]
+    pub executed_opcodes: Vec<[u64; 2]>,
+    /// pc the first row of the cpu table starts from. Zero unless the proof
+    /// is of a chunk continuing an execution at its start boundary, see
+    /// `executor::continuation::ChunkBoundary`.
+    #[serde(default)]
+    pub start_pc: u64,
+    /// Registers of the first row of the cpu table, like `start_pc`.
+    #[serde(default)]
+    pub start_registers: [u64; REGISTER_NUM],
+    /// pc of the first padding row of the cpu table, the pc a chunk that
+    /// stopped continues from. Zero once the execution ended.
+    #[serde(default)]
+    pub end_pc: u64,
+    /// Registers of the first padding row of the cpu table, like `end_pc`.
+    #[serde(default)]
+    pub end_registers: [u64; REGISTER_NUM],
+    /// Gas the steps of the cpu table used under the default gas schedule,
+    /// see `executor::gas`. Looked up in the end state row like `end_pc`. A
+    /// chunk counts its own steps only.
+    #[serde(default)]
+    pub gas_used: u64,
+    /// The execution stopped at the step limit of a chunk instead of ending.
+    /// Looked up by the memory table, which then exports `end_memory_image`.
+    #[serde(default)]
+    pub stopped: bool,
+    /// `(addr, value)` of the last cell of every address, the memory a chunk
+    /// that stopped ends with and the next one starts from, by address, as
+    /// canonical u64s. Empty unless `stopped`.
+    #[serde(default)]
+    pub end_memory_image: Vec<[u64; 2]>,
+}
+
+impl PublicValues {
+    /// Absorb every public value into the transcript.
+    pub(crate) fn observe<F: RichField, H: Hasher<F>>(&self, challenger: &mut Challenger<F, H>) {
+        for limb in self.prophet_commitment {
+            challenger.observe_element(F::from_canonical_u64(limb));
+        }
+        for hash in &self.program_hashes {
+            for limb in hash {
+                challenger.observe_element(F::from_canonical_u64(*limb));
+            }
+        }
+        self.observe_roots_and_metadata(challenger);
+        for limb in self.fork_id {
+            challenger.observe_element(F::from_canonical_u64(limb));
+        }
+        challenger.observe_element(F::from_canonical_u64(self.chain_id));
+        for limb in self.tx_hash {
+            challenger.observe_element(F::from_canonical_u64(limb));
+        }
+        challenger.observe_element(F::from_canonical_usize(self.returned_values.len()));
+        for value in &self.returned_values {
+            challenger.observe_element(F::from_canonical_u64(*value));
+        }
+        for cells in [
+            &self.memory_image,
+            &self.code_region,
+            &self.executed_opcodes,
+        ] {
+            challenger.observe_element(F::from_canonical_usize(cells.len()));
+            for cell in cells {
+                challenger.observe_elements(&cell.map(F::from_canonical_u64));
+            }
+        }
+        challenger.observe_element(F::from_canonical_u64(self.start_pc));
+        challenger.observe_elements(&self.start_registers.map(F::from_canonical_u64));
+        challenger.observe_element(F::from_canonical_u64(self.end_pc));
+        challenger.observe_elements(&self.end_registers.map(F::from_canonical_u64));
+        challenger.observe_element(F::from_canonical_u64(self.gas_used));
+        challenger.observe_element(F::from_bool(self.stopped));
+        challenger.observe_element(F::from_canonical_usize(self.end_memory_image.len()));
+        for cell in &self.end_memory_image {
+            challenger.observe_elements(&cell.map(F::from_canonical_u64));
+        }
+    }
+
+    /// Absorb the trie roots and the block metadata as 32-bit limbs.
+    fn observe_roots_and_metadata<F: RichField, H: Hasher<F>>(
+        &self,
+        challenger: &mut Challenger<F, H>,
+    ) {
+        for roots in [&self.trie_roots_before, &self.trie_roots_after] {
+            for root in [
+                roots.state_root,
+                roots.transactions_root,
+                roots.receipts_root,
+            ] {
+                challenger.observe_elements(&h256_limbs::<F>(root));
+            }
+        }
+        let metadata = &self.block_metadata;
+        challenger.observe_elements(&h160_limbs::<F>(metadata.block_beneficiary));
+        for value in [
+            metadata.block_timestamp,
+            metadata.block_number,
+            metadata.block_difficulty,
+            metadata.block_gaslimit,
+            metadata.block_chain_id,
+            metadata.block_base_fee,
+        ] {
+            challenger.observe_elements(&u256_limbs::<F>(value));
+        }
+    }
 }
 
 #[derive(Debug, Clone, Default, Serialize, Deserialize)]
@@ -1,10 +1,11 @@
+use core::crypto::TreeHasher;
 use core::program::Program;
 use std::any::type_name;
 use std::collections::BTreeMap;
+use std::path::Path;
 use std::sync::{Arc, Mutex};
 use std::time::Instant;
 
-use anyhow::{ensure, Result};
 use log::info;
 use maybe_rayon::*;
 use plonky2::field::extension::Extendable;
@@ -15,6 +16,7 @@ use plonky2::field::types::Field;
 use plonky2::field::zero_poly_coset::ZeroPolyOnCoset;
 use plonky2::fri::oracle::PolynomialBatch;
 use plonky2::hash::hash_types::RichField;
+use plonky2::hash::merkle_tree::MerkleCap;
 use plonky2::iop::challenger::Challenger;
 use plonky2::plonk::config::{GenericConfig, Hasher};
 use plonky2::timed;
@@ -22,6 +24,8 @@ use plonky2::util::timing::TimingTree;
 use plonky2::util::transpose;
 use plonky2_util::{log2_ceil, log2_strict};
 
+#[cfg(feature = "heap_profile")]
+use super::ola_stark::ALL_TABLES;
 use super::ola_stark::{OlaStark, Table, NUM_TABLES};
 use crate::builtins::bitwise::bitwise_stark::BitwiseStark;
 use crate::builtins::cmp::cmp_stark::CmpStark;
@@ -34,19 +38,33 @@ use crate::program::prog_chunk_stark::ProgChunkStark;
 use crate::program::program_stark::ProgramStark;
 // use crate::builtins::tape::tape_stark::TapeStark;
 //use crate::columns::NUM_CPU_COLS;
-use super::config::StarkConfig;
+use super::checkpoint::{fingerprint, ProverCheckpoint};
+use super::config::{KeccakTranscriptHash, StarkConfig, TranscriptHasher};
 use super::constraint_consumer::ConstraintConsumer;
-use super::cross_table_lookup::{cross_table_lookup_data, CtlCheckVars, CtlData};
+use super::cross_table_lookup::{
+    cross_table_lookup_data, restore_cross_table_lookup_data, CtlCheckVars, CtlData,
+};
+use super::error::ProverError;
+#[cfg(feature = "heap_profile")]
+use super::heap_profile::{self, HeapScope};
+use super::layout::layout_fingerprints;
 use super::permutation::PermutationCheckVars;
 use super::permutation::{
     compute_permutation_z_polys, get_n_grand_product_challenge_sets, GrandProductChallengeSet,
 };
-use super::proof::{AllProof, PublicValues, StarkOpeningSet, StarkProof};
+use super::proof::{AllProof, ProofVersion, PublicValues, StarkOpeningSet, StarkProof};
 use super::stark::Stark;
+#[cfg(feature = "trace_check")]
+use super::trace_check::check_builtin_traces;
+use super::trace_check::spot_check_traces;
 use super::vanishing_poly::eval_vanishing_poly;
 use super::vars::StarkEvaluationVars;
 use crate::cpu::cpu_stark::CpuStark;
-use crate::generation::{generate_traces, GenerationInputs};
+#[cfg(not(feature = "determinism_check"))]
+use crate::generation::generate_traces;
+#[cfg(feature = "determinism_check")]
+use crate::generation::generate_traces_both_modes;
+use crate::generation::GenerationInputs;
 use crate::memory::memory_stark::MemoryStark;
 
 /// Generate traces, then create all STARK proofs.
@@ -56,7 +74,7 @@ pub fn prove<F, C, const D: usize>(
     inputs: GenerationInputs,
     config: &StarkConfig,
     timing: &mut TimingTree,
-) -> Result<AllProof<F, C, D>>
+) -> Result<AllProof<F, C, D>, ProverError>
 where
     F: RichField + Extendable<D>,
     C: GenericConfig<D, F = F>,
@@ -71,10 +89,29 @@ where
     [(); StorageAccessStark::<F, D>::COLUMNS]:,
     // [(); TapeStark::<F, D>::COLUMNS]:,
     [(); SCCallStark::<F, D>::COLUMNS]:,
+    [(); crate::builtins::rangecheck::rangecheck_stark::RangeCheckStark::<F, D>::COLUMNS]:, [(); crate::builtins::tape::tape_stark::TapeStark::<F, D>::COLUMNS]:, [(); crate::builtins::transient::transient_stark::TransientStark::<F, D>::COLUMNS]:, //HACK-BOUNDS
     [(); ProgramStark::<F, D>::COLUMNS]:,
     [(); ProgChunkStark::<F, D>::COLUMNS]:,
 {
+    if program.trace.tree_hasher != TreeHasher::Poseidon {
+        return Err(ProverError::UnsupportedTreeHasher(
+            program.trace.tree_hasher,
+        ));
+    }
+    // only the executed program is at hand, `generate_traces_checked` also
+    // executes it twice
+    #[cfg(feature = "determinism_check")]
+    let (traces, public_values) =
+        generate_traces_both_modes(program.clone(), program, ola_stark, inputs);
+    #[cfg(not(feature = "determinism_check"))]
     let (traces, public_values) = generate_traces(program, ola_stark, inputs);
+    #[cfg(feature = "trace_check")]
+    check_builtin_traces(ola_stark, &traces);
+    if let Some(rate) = config.spot_check_rate() {
+        // a different sample for every tx
+        spot_check_traces(ola_stark, &traces, rate, public_values.tx_hash[0])
+            .map_err(ProverError::SpotCheck)?;
+    }
     prove_with_traces(ola_stark, config, traces, public_values, timing)
 }
 
@@ -85,7 +122,170 @@ pub fn prove_with_traces<F, C, const D: usize>(
     trace_poly_values: [Vec<PolynomialValues<F>>; NUM_TABLES],
     public_values: PublicValues,
     timing: &mut TimingTree,
-) -> Result<AllProof<F, C, D>>
+) -> Result<AllProof<F, C, D>, ProverError>
+where
+    F: RichField + Extendable<D>,
+    C: GenericConfig<D, F = F>,
+    [(); C::Hasher::HASH_SIZE]:,
+    [(); CpuStark::<F, D>::COLUMNS]:,
+    [(); MemoryStark::<F, D>::COLUMNS]:,
+    [(); BitwiseStark::<F, D>::COLUMNS]:,
+    [(); CmpStark::<F, D>::COLUMNS]:,
+    // [(); RangeCheckStark::<F, D>::COLUMNS]:,
+    [(); PoseidonStark::<F, D>::COLUMNS]:,
+    [(); PoseidonChunkStark::<F, D>::COLUMNS]:,
+    [(); StorageAccessStark::<F, D>::COLUMNS]:,
+    // [(); TapeStark::<F, D>::COLUMNS]:,
+    [(); SCCallStark::<F, D>::COLUMNS]:,
+    [(); crate::builtins::rangecheck::rangecheck_stark::RangeCheckStark::<F, D>::COLUMNS]:, [(); crate::builtins::tape::tape_stark::TapeStark::<F, D>::COLUMNS]:, [(); crate::builtins::transient::transient_stark::TransientStark::<F, D>::COLUMNS]:, //HACK-BOUNDS
+    [(); ProgramStark::<F, D>::COLUMNS]:,
+    [(); ProgChunkStark::<F, D>::COLUMNS]:,
+{
+    prove_with_checkpoint(
+        ola_stark,
+        config,
+        trace_poly_values,
+        public_values,
+        ProofVersion::default(),
+        None,
+        timing,
+    )
+}
+
+/// Like `prove_with_traces`, but binds and lays out the proof like provers of
+/// an older `version` did, so verifiers accepting it through
+/// `verify_proof_with_compatibility` can be tested during rolling upgrades.
+#[cfg(feature = "proof_compat")]
+pub fn prove_with_traces_at_version<F, C, const D: usize>(
+    ola_stark: &OlaStark<F, D>,
+    config: &StarkConfig,
+    trace_poly_values: [Vec<PolynomialValues<F>>; NUM_TABLES],
+    public_values: PublicValues,
+    version: ProofVersion,
+    timing: &mut TimingTree,
+) -> Result<AllProof<F, C, D>, ProverError>
+where
+    F: RichField + Extendable<D>,
+    C: GenericConfig<D, F = F>,
+    [(); C::Hasher::HASH_SIZE]:,
+    [(); CpuStark::<F, D>::COLUMNS]:,
+    [(); MemoryStark::<F, D>::COLUMNS]:,
+    [(); BitwiseStark::<F, D>::COLUMNS]:,
+    [(); CmpStark::<F, D>::COLUMNS]:,
+    // [(); RangeCheckStark::<F, D>::COLUMNS]:,
+    [(); PoseidonStark::<F, D>::COLUMNS]:,
+    [(); PoseidonChunkStark::<F, D>::COLUMNS]:,
+    [(); StorageAccessStark::<F, D>::COLUMNS]:,
+    // [(); TapeStark::<F, D>::COLUMNS]:,
+    [(); SCCallStark::<F, D>::COLUMNS]:,
+    [(); crate::builtins::rangecheck::rangecheck_stark::RangeCheckStark::<F, D>::COLUMNS]:, [(); crate::builtins::tape::tape_stark::TapeStark::<F, D>::COLUMNS]:, [(); crate::builtins::transient::transient_stark::TransientStark::<F, D>::COLUMNS]:, //HACK-BOUNDS
+    [(); ProgramStark::<F, D>::COLUMNS]:,
+    [(); ProgChunkStark::<F, D>::COLUMNS]:,
+{
+    prove_with_checkpoint(
+        ola_stark,
+        config,
+        trace_poly_values,
+        public_values,
+        version,
+        None,
+        timing,
+    )
+}
+
+/// Like `prove_with_traces`, but saves the progress to a fresh checkpoint in
+/// `dir` after every phase.
+pub fn prove_with_checkpoints<F, C, const D: usize>(
+    ola_stark: &OlaStark<F, D>,
+    config: &StarkConfig,
+    trace_poly_values: [Vec<PolynomialValues<F>>; NUM_TABLES],
+    public_values: PublicValues,
+    dir: &Path,
+    timing: &mut TimingTree,
+) -> Result<AllProof<F, C, D>, ProverError>
+where
+    F: RichField + Extendable<D>,
+    C: GenericConfig<D, F = F>,
+    [(); C::Hasher::HASH_SIZE]:,
+    [(); CpuStark::<F, D>::COLUMNS]:,
+    [(); MemoryStark::<F, D>::COLUMNS]:,
+    [(); BitwiseStark::<F, D>::COLUMNS]:,
+    [(); CmpStark::<F, D>::COLUMNS]:,
+    // [(); RangeCheckStark::<F, D>::COLUMNS]:,
+    [(); PoseidonStark::<F, D>::COLUMNS]:,
+    [(); PoseidonChunkStark::<F, D>::COLUMNS]:,
+    [(); StorageAccessStark::<F, D>::COLUMNS]:,
+    // [(); TapeStark::<F, D>::COLUMNS]:,
+    [(); SCCallStark::<F, D>::COLUMNS]:,
+    [(); crate::builtins::rangecheck::rangecheck_stark::RangeCheckStark::<F, D>::COLUMNS]:, [(); crate::builtins::tape::tape_stark::TapeStark::<F, D>::COLUMNS]:, [(); crate::builtins::transient::transient_stark::TransientStark::<F, D>::COLUMNS]:, //HACK-BOUNDS
+    [(); ProgramStark::<F, D>::COLUMNS]:,
+    [(); ProgChunkStark::<F, D>::COLUMNS]:,
+{
+    let fingerprint = fingerprint::<F, C, D>(config, &trace_poly_values, &public_values);
+    let checkpoint = ProverCheckpoint::create(dir, &fingerprint)?;
+    prove_with_checkpoint(
+        ola_stark,
+        config,
+        trace_poly_values,
+        public_values,
+        ProofVersion::default(),
+        Some(&checkpoint),
+        timing,
+    )
+}
+
+/// Continue the `prove_with_checkpoints` run saved in `dir`, skipping the
+/// phases it finished. The traces and public values have to be the ones of
+/// that run, e.g. regenerated from the same program and inputs, and the
+/// config the same.
+pub fn resume_from_checkpoint<F, C, const D: usize>(
+    ola_stark: &OlaStark<F, D>,
+    config: &StarkConfig,
+    trace_poly_values: [Vec<PolynomialValues<F>>; NUM_TABLES],
+    public_values: PublicValues,
+    dir: &Path,
+    timing: &mut TimingTree,
+) -> Result<AllProof<F, C, D>, ProverError>
+where
+    F: RichField + Extendable<D>,
+    C: GenericConfig<D, F = F>,
+    [(); C::Hasher::HASH_SIZE]:,
+    [(); CpuStark::<F, D>::COLUMNS]:,
+    [(); MemoryStark::<F, D>::COLUMNS]:,
+    [(); BitwiseStark::<F, D>::COLUMNS]:,
+    [(); CmpStark::<F, D>::COLUMNS]:,
+    // [(); RangeCheckStark::<F, D>::COLUMNS]:,
+    [(); PoseidonStark::<F, D>::COLUMNS]:,
+    [(); PoseidonChunkStark::<F, D>::COLUMNS]:,
+    [(); StorageAccessStark::<F, D>::COLUMNS]:,
+    // [(); TapeStark::<F, D>::COLUMNS]:,
+    [(); SCCallStark::<F, D>::COLUMNS]:,
+    [(); crate::builtins::rangecheck::rangecheck_stark::RangeCheckStark::<F, D>::COLUMNS]:, [(); crate::builtins::tape::tape_stark::TapeStark::<F, D>::COLUMNS]:, [(); crate::builtins::transient::transient_stark::TransientStark::<F, D>::COLUMNS]:, //HACK-BOUNDS
+    [(); ProgramStark::<F, D>::COLUMNS]:,
+    [(); ProgChunkStark::<F, D>::COLUMNS]:,
+{
+    let fingerprint = fingerprint::<F, C, D>(config, &trace_poly_values, &public_values);
+    let checkpoint = ProverCheckpoint::open(dir, &fingerprint)?;
+    prove_with_checkpoint(
+        ola_stark,
+        config,
+        trace_poly_values,
+        public_values,
+        ProofVersion::default(),
+        Some(&checkpoint),
+        timing,
+    )
+}
+
+fn prove_with_checkpoint<F, C, const D: usize>(
+    ola_stark: &OlaStark<F, D>,
+    config: &StarkConfig,
+    trace_poly_values: [Vec<PolynomialValues<F>>; NUM_TABLES],
+    public_values: PublicValues,
+    version: ProofVersion,
+    checkpoint: Option<&ProverCheckpoint>,
+    timing: &mut TimingTree,
+) -> Result<AllProof<F, C, D>, ProverError>
 where
     F: RichField + Extendable<D>,
     C: GenericConfig<D, F = F>,
@@ -100,37 +300,62 @@ where
     [(); StorageAccessStark::<F, D>::COLUMNS]:,
     // [(); TapeStark::<F, D>::COLUMNS]:,
     [(); SCCallStark::<F, D>::COLUMNS]:,
+    [(); crate::builtins::rangecheck::rangecheck_stark::RangeCheckStark::<F, D>::COLUMNS]:, [(); crate::builtins::tape::tape_stark::TapeStark::<F, D>::COLUMNS]:, [(); crate::builtins::transient::transient_stark::TransientStark::<F, D>::COLUMNS]:, //HACK-BOUNDS
     [(); ProgramStark::<F, D>::COLUMNS]:,
     [(); ProgChunkStark::<F, D>::COLUMNS]:,
 {
     let rate_bits = config.fri_config.rate_bits;
-    let cap_height = config.fri_config.cap_height;
 
     let mut twiddle_map = BTreeMap::new();
 
     #[cfg(feature = "benchmark")]
     let start = Instant::now();
 
-    let trace_commitments = timed!(
-        timing,
-        "compute trace commitments",
-        trace_poly_values
-            .iter()
-            .map(|trace| {
-                PolynomialBatch::<F, C, D>::from_values(
-                    // TODO: Cloning this isn't great; consider having `from_values` accept a
-                    // reference, or having `compute_permutation_z_polys` read
-                    // trace values from the `PolynomialBatch`.
-                    trace.clone(),
-                    rate_bits,
-                    false,
-                    cap_height,
-                    timing,
-                    &mut twiddle_map,
-                )
-            })
-            .collect::<Vec<_>>()
-    );
+    #[cfg(feature = "heap_profile")]
+    heap_profile::clear();
+
+    let restored_commitments = match checkpoint {
+        Some(checkpoint) => checkpoint.load_trace_commitments::<F, C, D>()?,
+        None => None,
+    };
+    let trace_commitments = match restored_commitments {
+        Some(trace_commitments) => trace_commitments,
+        None => {
+            let trace_commitments = timed!(
+                timing,
+                "compute trace commitments",
+                trace_poly_values
+                    .iter()
+                    .enumerate()
+                    .map(|(i, trace)| {
+                        #[cfg(feature = "heap_profile")]
+                        let scope = HeapScope::start();
+                        let commitment = PolynomialBatch::<F, C, D>::from_values(
+                            // TODO: Cloning this isn't great; consider having `from_values`
+                            // accept a reference, or having `compute_permutation_z_polys`
+                            // read trace values from the `PolynomialBatch`.
+                            trace.clone(),
+                            rate_bits,
+                            false,
+                            config.for_table_index(i).fri_config.cap_height,
+                            timing,
+                            &mut twiddle_map,
+                        );
+                        #[cfg(feature = "heap_profile")]
+                        {
+                            heap_profile::record(Some(ALL_TABLES[i]), "commit", scope);
+                            heap_profile::record_commitment(ALL_TABLES[i], trace, &commitment);
+                        }
+                        commitment
+                    })
+                    .collect::<Vec<_>>()
+            );
+            if let Some(checkpoint) = checkpoint {
+                checkpoint.save_trace_commitments(&trace_commitments)?;
+            }
+            trace_commitments
+        }
+    };
 
     #[cfg(feature = "benchmark")]
     info!("trace_commitments total time: {:?}", start.elapsed());
@@ -139,20 +364,144 @@ where
         .iter()
         .map(|c| c.merkle_tree.cap.clone())
         .collect::<Vec<_>>();
-    let mut challenger = Challenger::<F, C::Hasher>::new();
-    for cap in &trace_caps {
+    let compress_challenges = [
+        F::ZERO,
+        F::ZERO,
+        ola_stark.bitwise_stark.get_compress_challenge().unwrap(),
+        F::ZERO,
+        F::ZERO,
+        F::ZERO,
+        F::ZERO,
+        F::ZERO,
+        F::ZERO,
+        F::ZERO,
+        ola_stark.program_stark.get_compress_challenge().unwrap(),
+        F::ZERO,
+        F::ZERO,
+    ];
+
+    let stark_proofs = match config.transcript_hasher {
+        TranscriptHasher::Poseidon => prove_tables::<F, C, C::Hasher, D>(
+            ola_stark,
+            config,
+            &trace_poly_values,
+            &trace_commitments,
+            &trace_caps,
+            &public_values,
+            &compress_challenges,
+            &version,
+            checkpoint,
+            timing,
+            &mut twiddle_map,
+        )?,
+        TranscriptHasher::Keccak => prove_tables::<F, C, KeccakTranscriptHash, D>(
+            ola_stark,
+            config,
+            &trace_poly_values,
+            &trace_commitments,
+            &trace_caps,
+            &public_values,
+            &compress_challenges,
+            &version,
+            checkpoint,
+            timing,
+            &mut twiddle_map,
+        )?,
+    };
+
+    #[cfg(feature = "heap_profile")]
+    {
+        info!("prover heap:\n{}", heap_profile::report());
+    }
+
+    Ok(AllProof {
+        version,
+        stark_proofs,
+        compress_challenges,
+        public_values,
+        layout: if version.carries_layout() {
+            layout_fingerprints()
+        } else {
+            [0; NUM_TABLES]
+        },
+    })
+}
+
+/// Prove every table, drawing the challenges from a transcript hashed with
+/// `H`.
+#[allow(clippy::too_many_arguments)]
+fn prove_tables<F, C, H, const D: usize>(
+    ola_stark: &OlaStark<F, D>,
+    config: &StarkConfig,
+    trace_poly_values: &[Vec<PolynomialValues<F>>; NUM_TABLES],
+    trace_commitments: &[PolynomialBatch<F, C, D>],
+    trace_caps: &[MerkleCap<F, C::Hasher>],
+    public_values: &PublicValues,
+    compress_challenges: &[F; NUM_TABLES],
+    version: &ProofVersion,
+    checkpoint: Option<&ProverCheckpoint>,
+    timing: &mut TimingTree,
+    twiddle_map: &mut BTreeMap<usize, Vec<F>>,
+) -> Result<[StarkProof<F, C, D>; NUM_TABLES], ProverError>
+where
+    F: RichField + Extendable<D>,
+    C: GenericConfig<D, F = F>,
+    H: Hasher<F>,
+    [(); C::Hasher::HASH_SIZE]:,
+    [(); CpuStark::<F, D>::COLUMNS]:,
+    [(); MemoryStark::<F, D>::COLUMNS]:,
+    [(); BitwiseStark::<F, D>::COLUMNS]:,
+    [(); CmpStark::<F, D>::COLUMNS]:,
+    // [(); RangeCheckStark::<F, D>::COLUMNS]:,
+    [(); PoseidonStark::<F, D>::COLUMNS]:,
+    [(); PoseidonChunkStark::<F, D>::COLUMNS]:,
+    [(); StorageAccessStark::<F, D>::COLUMNS]:,
+    // [(); TapeStark::<F, D>::COLUMNS]:,
+    [(); SCCallStark::<F, D>::COLUMNS]:,
+    [(); crate::builtins::rangecheck::rangecheck_stark::RangeCheckStark::<F, D>::COLUMNS]:, [(); crate::builtins::tape::tape_stark::TapeStark::<F, D>::COLUMNS]:, [(); crate::builtins::transient::transient_stark::TransientStark::<F, D>::COLUMNS]:, //HACK-BOUNDS
+    [(); ProgramStark::<F, D>::COLUMNS]:,
+    [(); ProgChunkStark::<F, D>::COLUMNS]:,
+{
+    let mut challenger = Challenger::<F, H>::new();
+    for cap in trace_caps {
         challenger.observe_cap(cap);
     }
+    version.observe_public_inputs(public_values, compress_challenges, &mut challenger);
 
     #[cfg(feature = "benchmark")]
     let start = Instant::now();
 
-    let ctl_data_per_table = cross_table_lookup_data::<F, C, D>(
-        config,
-        &trace_poly_values,
-        &ola_stark.cross_table_lookups,
-        &mut challenger,
-    );
+    let restored_zs = match checkpoint {
+        Some(checkpoint) => checkpoint.load_ctl_zs()?,
+        None => None,
+    };
+    let ctl_data_per_table = match restored_zs {
+        Some(z_polys) => restore_cross_table_lookup_data::<F, C, D>(
+            config,
+            z_polys,
+            &ola_stark.cross_table_lookups,
+            &mut challenger,
+        )
+        .ok_or_else(|| {
+            ProverError::Checkpoint("ctl Z polys do not match the lookups".to_string())
+        })?,
+        None => {
+            #[cfg(feature = "heap_profile")]
+            let scope = HeapScope::start();
+            let ctl_data_per_table = cross_table_lookup_data::<F, C, D>(
+                config,
+                trace_poly_values,
+                &ola_stark.cross_table_lookups,
+                &mut challenger,
+            );
+            #[cfg(feature = "heap_profile")]
+            heap_profile::record(None, "ctl zs", scope);
+            if let Some(checkpoint) = checkpoint {
+                checkpoint.save_ctl_zs(&ctl_data_per_table)?;
+            }
+            ctl_data_per_table
+        }
+    };
 
     #[cfg(feature = "benchmark")]
     info!("cross_table_lookup_data total time: {:?}", start.elapsed());
@@ -160,15 +509,17 @@ where
     #[cfg(feature = "benchmark")]
     let start = Instant::now();
 
-    let cpu_proof = prove_single_table(
+    let cpu_proof = prove_table_checkpointed(
         &ola_stark.cpu_stark,
-        config,
+        Table::Cpu,
+        &config.for_table(Table::Cpu),
         &trace_poly_values[Table::Cpu as usize],
         &trace_commitments[Table::Cpu as usize],
         &ctl_data_per_table[Table::Cpu as usize],
         &mut challenger,
         timing,
-        &mut twiddle_map,
+        twiddle_map,
+        checkpoint,
     )?;
 
     #[cfg(feature = "benchmark")]
@@ -177,122 +528,157 @@ where
     #[cfg(feature = "benchmark")]
     let start = Instant::now();
 
-    let memory_proof = prove_single_table(
+    let memory_proof = prove_table_checkpointed(
         &ola_stark.memory_stark,
-        config,
+        Table::Memory,
+        &config.for_table(Table::Memory),
         &trace_poly_values[Table::Memory as usize],
         &trace_commitments[Table::Memory as usize],
         &ctl_data_per_table[Table::Memory as usize],
         &mut challenger,
         timing,
-        &mut twiddle_map,
+        twiddle_map,
+        checkpoint,
     )?;
 
-    let bitwise_proof = prove_single_table(
+    let bitwise_proof = prove_table_checkpointed(
         &ola_stark.bitwise_stark,
-        config,
+        Table::Bitwise,
+        &config.for_table(Table::Bitwise),
         &trace_poly_values[Table::Bitwise as usize],
         &trace_commitments[Table::Bitwise as usize],
         &ctl_data_per_table[Table::Bitwise as usize],
         &mut challenger,
         timing,
-        &mut twiddle_map,
+        twiddle_map,
+        checkpoint,
     )?;
-    let cmp_proof = prove_single_table(
+    let cmp_proof = prove_table_checkpointed(
         &ola_stark.cmp_stark,
-        config,
+        Table::Cmp,
+        &config.for_table(Table::Cmp),
         &trace_poly_values[Table::Cmp as usize],
         &trace_commitments[Table::Cmp as usize],
         &ctl_data_per_table[Table::Cmp as usize],
         &mut challenger,
         timing,
-        &mut twiddle_map,
+        twiddle_map,
+        checkpoint,
     )?;
-    let rangecheck_proof = prove_single_table(
+    let rangecheck_proof = prove_table_checkpointed(
         &ola_stark.rangecheck_stark,
-        config,
+        Table::RangeCheck,
+        &config.for_table(Table::RangeCheck),
         &trace_poly_values[Table::RangeCheck as usize],
         &trace_commitments[Table::RangeCheck as usize],
         &ctl_data_per_table[Table::RangeCheck as usize],
         &mut challenger,
         timing,
-        &mut twiddle_map,
+        twiddle_map,
+        checkpoint,
     )?;
-    let poseidon_proof = prove_single_table(
+    let poseidon_proof = prove_table_checkpointed(
         &ola_stark.poseidon_stark,
-        config,
+        Table::Poseidon,
+        &config.for_table(Table::Poseidon),
         &trace_poly_values[Table::Poseidon as usize],
         &trace_commitments[Table::Poseidon as usize],
         &ctl_data_per_table[Table::Poseidon as usize],
         &mut challenger,
         timing,
-        &mut twiddle_map,
+        twiddle_map,
+        checkpoint,
     )?;
-    let poseidon_chunk_proof = prove_single_table(
+    let poseidon_chunk_proof = prove_table_checkpointed(
         &ola_stark.poseidon_chunk_stark,
-        config,
+        Table::PoseidonChunk,
+        &config.for_table(Table::PoseidonChunk),
         &trace_poly_values[Table::PoseidonChunk as usize],
         &trace_commitments[Table::PoseidonChunk as usize],
         &ctl_data_per_table[Table::PoseidonChunk as usize],
         &mut challenger,
         timing,
-        &mut twiddle_map,
+        twiddle_map,
+        checkpoint,
     )?;
-    let storage_access_proof = prove_single_table(
+    let storage_access_proof = prove_table_checkpointed(
         &ola_stark.storage_access_stark,
-        config,
+        Table::StorageAccess,
+        &config.for_table(Table::StorageAccess),
         &trace_poly_values[Table::StorageAccess as usize],
         &trace_commitments[Table::StorageAccess as usize],
         &ctl_data_per_table[Table::StorageAccess as usize],
         &mut challenger,
         timing,
-        &mut twiddle_map,
+        twiddle_map,
+        checkpoint,
     )?;
-    let tape_proof = prove_single_table(
+    let tape_proof = prove_table_checkpointed(
         &ola_stark.tape_stark,
-        config,
+        Table::Tape,
+        &config.for_table(Table::Tape),
         &trace_poly_values[Table::Tape as usize],
         &trace_commitments[Table::Tape as usize],
         &ctl_data_per_table[Table::Tape as usize],
         &mut challenger,
         timing,
-        &mut twiddle_map,
+        twiddle_map,
+        checkpoint,
     )?;
-    let sccall_proof = prove_single_table(
+    let sccall_proof = prove_table_checkpointed(
         &ola_stark.sccall_stark,
-        config,
+        Table::SCCall,
+        &config.for_table(Table::SCCall),
         &trace_poly_values[Table::SCCall as usize],
         &trace_commitments[Table::SCCall as usize],
         &ctl_data_per_table[Table::SCCall as usize],
         &mut challenger,
         timing,
-        &mut twiddle_map,
+        twiddle_map,
+        checkpoint,
     )?;
-    let program_proof = prove_single_table(
+    let program_proof = prove_table_checkpointed(
         &ola_stark.program_stark,
-        config,
+        Table::Program,
+        &config.for_table(Table::Program),
         &trace_poly_values[Table::Program as usize],
         &trace_commitments[Table::Program as usize],
         &ctl_data_per_table[Table::Program as usize],
         &mut challenger,
         timing,
-        &mut twiddle_map,
+        twiddle_map,
+        checkpoint,
     )?;
-    let prog_chunk_proof = prove_single_table(
+    let prog_chunk_proof = prove_table_checkpointed(
         &ola_stark.prog_chunk_stark,
-        config,
+        Table::ProgChunk,
+        &config.for_table(Table::ProgChunk),
         &trace_poly_values[Table::ProgChunk as usize],
         &trace_commitments[Table::ProgChunk as usize],
         &ctl_data_per_table[Table::ProgChunk as usize],
         &mut challenger,
         timing,
-        &mut twiddle_map,
+        twiddle_map,
+        checkpoint,
+    )?;
+
+    let transient_proof = prove_table_checkpointed(
+        &ola_stark.transient_stark,
+        Table::Transient,
+        &config.for_table(Table::Transient),
+        &trace_poly_values[Table::Transient as usize],
+        &trace_commitments[Table::Transient as usize],
+        &ctl_data_per_table[Table::Transient as usize],
+        &mut challenger,
+        timing,
+        twiddle_map,
+        checkpoint,
     )?;
 
     #[cfg(feature = "benchmark")]
     info!("prove_other_table total time: {:?}", start.elapsed());
 
-    let stark_proofs = [
+    Ok([
         cpu_proof,
         memory_proof,
         bitwise_proof,
@@ -305,44 +691,88 @@ where
         sccall_proof,
         program_proof,
         prog_chunk_proof,
-    ];
-
-    let compress_challenges = [
-        F::ZERO,
-        F::ZERO,
-        ola_stark.bitwise_stark.get_compress_challenge().unwrap(),
-        F::ZERO,
-        F::ZERO,
-        F::ZERO,
-        F::ZERO,
-        F::ZERO,
-        F::ZERO,
-        F::ZERO,
-        ola_stark.program_stark.get_compress_challenge().unwrap(),
-        F::ZERO,
-    ];
+        transient_proof,
+    ])
+}
 
-    Ok(AllProof {
-        stark_proofs,
-        compress_challenges,
-        public_values,
-    })
+/// Prove a table, or take its proof from `checkpoint` and replay the
+/// transcript of proving it.
+#[allow(clippy::too_many_arguments)]
+fn prove_table_checkpointed<F, C, H, S, const D: usize>(
+    stark: &S,
+    table: Table,
+    config: &StarkConfig,
+    trace_poly_values: &[PolynomialValues<F>],
+    trace_commitment: &PolynomialBatch<F, C, D>,
+    ctl_data: &CtlData<F>,
+    challenger: &mut Challenger<F, H>,
+    timing: &mut TimingTree,
+    twiddle_map: &mut BTreeMap<usize, Vec<F>>,
+    checkpoint: Option<&ProverCheckpoint>,
+) -> Result<StarkProof<F, C, D>, ProverError>
+where
+    F: RichField + Extendable<D>,
+    C: GenericConfig<D, F = F>,
+    H: Hasher<F>,
+    S: Stark<F, D>,
+    [(); C::Hasher::HASH_SIZE]:,
+    [(); S::COLUMNS]:,
+{
+    if let Some(checkpoint) = checkpoint {
+        if let Some(proof) = checkpoint.load_table_proof::<F, C, D>(table)? {
+            if proof.trace_cap != trace_commitment.merkle_tree.cap {
+                return Err(ProverError::Checkpoint(format!(
+                    "table {:?}: proof does not match the trace commitment",
+                    table
+                )));
+            }
+            challenger.compact();
+            proof.get_challenges(
+                challenger,
+                stark.uses_permutation_args(),
+                stark.permutation_batch_size(),
+                config,
+            );
+            return Ok(proof);
+        }
+    }
+    #[cfg(feature = "heap_profile")]
+    let scope = HeapScope::start();
+    let proof = prove_single_table(
+        stark,
+        table,
+        config,
+        trace_poly_values,
+        trace_commitment,
+        ctl_data,
+        challenger,
+        timing,
+        twiddle_map,
+    )?;
+    #[cfg(feature = "heap_profile")]
+    heap_profile::record(Some(table), "prove", scope);
+    if let Some(checkpoint) = checkpoint {
+        checkpoint.save_table_proof(table, &proof)?;
+    }
+    Ok(proof)
 }
 
 /// Compute proof for a single STARK table.
-pub(crate) fn prove_single_table<F, C, S, const D: usize>(
+pub(crate) fn prove_single_table<F, C, H, S, const D: usize>(
     stark: &S,
+    table: Table,
     config: &StarkConfig,
     trace_poly_values: &[PolynomialValues<F>],
     trace_commitment: &PolynomialBatch<F, C, D>,
     ctl_data: &CtlData<F>,
-    challenger: &mut Challenger<F, C::Hasher>,
+    challenger: &mut Challenger<F, H>,
     timing: &mut TimingTree,
     twiddle_map: &mut BTreeMap<usize, Vec<F>>,
-) -> Result<StarkProof<F, C, D>>
+) -> Result<StarkProof<F, C, D>, ProverError>
 where
     F: RichField + Extendable<D>,
     C: GenericConfig<D, F = F>,
+    H: Hasher<F>,
     S: Stark<F, D>,
     [(); C::Hasher::HASH_SIZE]:,
     [(); S::COLUMNS]:,
@@ -352,10 +782,13 @@ where
     let fri_params = config.fri_params(degree_bits);
     let rate_bits = config.fri_config.rate_bits;
     let cap_height = config.fri_config.cap_height;
-    assert!(
-        fri_params.total_arities() <= degree_bits + rate_bits - cap_height,
-        "FRI total reduction arity is too large.",
-    );
+    if fri_params.total_arities() > degree_bits + rate_bits - cap_height {
+        return Err(ProverError::DegreeOverflow(
+            table,
+            fri_params.total_arities(),
+            degree_bits,
+        ));
+    }
 
     challenger.compact();
 
@@ -508,10 +941,9 @@ where
     // `zeta` only, since `(g * zeta)^n = zeta^n`, where `n` is the order of
     // `g`.
     let g = F::primitive_root_of_unity(degree_bits);
-    ensure!(
-        zeta.exp_power_of_2(degree_bits) != F::Extension::ONE,
-        "Opening point is in the subgroup."
-    );
+    if zeta.exp_power_of_2(degree_bits) == F::Extension::ONE {
+        return Err(ProverError::OpeningPointInSubgroup(table));
+    }
 
     #[cfg(feature = "benchmark")]
     let start = Instant::now();
@@ -611,12 +1043,12 @@ where
     let z_h_on_coset = ZeroPolyOnCoset::<F>::new(degree_bits, quotient_degree_bits);
 
     // Retrieve the LDE values at index `i`.
-    let get_trace_values_packed = |i_start| -> [P; S::COLUMNS] {
-        trace_commitment
-            .get_lde_values_packed(i_start, step)
-            .try_into()
-            .unwrap()
-    };
+//HACK-ORIG     let get_trace_values_packed = |i_start| -> [P; S::COLUMNS] {
+//HACK-ORIG         trace_commitment
+//HACK-ORIG             .get_lde_values_packed(i_start, step)
+//HACK-ORIG             .try_into()
+//HACK-ORIG             .unwrap()
+//HACK-ORIG     };
 
     // Last element of the subgroup.
     let last = F::primitive_root_of_unity(degree_bits).inverse();
@@ -647,10 +1079,10 @@ where
                 lagrange_basis_first,
                 lagrange_basis_last,
             );
-            let vars = StarkEvaluationVars {
-                local_values: &get_trace_values_packed(i_start),
-                next_values: &get_trace_values_packed(i_next_start),
-            };
+//HACK-ORIG             let vars = StarkEvaluationVars {
+//HACK-ORIG                 local_values: &get_trace_values_packed(i_start),
+//HACK-ORIG                 next_values: &get_trace_values_packed(i_next_start),
+//HACK-ORIG             };
             let permutation_check_vars =
                 permutation_challenges.map(|permutation_challenge_sets| PermutationCheckVars {
                     local_zs: permutation_ctl_zs_commitment.get_lde_values_packed(i_start, step)
@@ -675,14 +1107,15 @@ where
                     filter_column: &zs_columns.filter_column,
                 })
                 .collect::<Vec<_>>();
-            eval_vanishing_poly::<F, F, P, C, S, D, 1>(
-                stark,
-                config,
-                vars,
-                permutation_check_vars,
-                &ctl_vars,
-                &mut consumer,
-            );
+            hack_eval::<F, P, C, S, D>(stark, config, &trace_commitment.get_lde_values_packed(i_start, step), &trace_commitment.get_lde_values_packed(i_next_start, step), permutation_check_vars, &ctl_vars, &mut consumer); //HACK-CLOSURE
+//HACK-ORIG             eval_vanishing_poly::<F, F, P, C, S, D, 1>(
+//HACK-ORIG                 stark,
+//HACK-ORIG                 config,
+//HACK-ORIG                 vars,
+//HACK-ORIG                 permutation_check_vars,
+//HACK-ORIG                 &ctl_vars,
+//HACK-ORIG                 &mut consumer,
+//HACK-ORIG             );
             let mut constraints_evals = consumer.accumulators();
             // We divide the constraints evaluations by `Z_H(x)`.
             let denominator_inv: P = z_h_on_coset.eval_inverse_packed(i_start);
@@ -820,3 +1253,12 @@ fn check_constraints<'a, F, C, S, const D: usize>(
         );
     }
 }
+#[allow(clippy::too_many_arguments)] //HACK-CLOSURE
+fn hack_eval<F, P, C, S, const D: usize>(stark: &S, config: &StarkConfig, local: &[P], next: &[P], perm: Option<PermutationCheckVars<F, F, P, 1>>, ctl_vars: &[CtlCheckVars<F, F, P, 1>], consumer: &mut ConstraintConsumer<P>) //HACK-CLOSURE
+where F: RichField + Extendable<D>, P: PackedField<Scalar = F>, C: GenericConfig<D, F = F>, S: Stark<F, D>, [(); S::COLUMNS]:, //HACK-CLOSURE
+{ //HACK-CLOSURE
+    let local_values: [P; S::COLUMNS] = local.try_into().unwrap(); //HACK-CLOSURE
+    let next_values: [P; S::COLUMNS] = next.try_into().unwrap(); //HACK-CLOSURE
+    let vars = StarkEvaluationVars { local_values: &local_values, next_values: &next_values }; //HACK-CLOSURE
+    eval_vanishing_poly::<F, F, P, C, S, D, 1>(stark, config, vars, perm, ctl_vars, consumer); //HACK-CLOSURE
+} //HACK-CLOSURE
@@ -1,61 +1,93 @@
 use std::io::Cursor;
-use std::io::{Read, Result, Write};
+use std::io::{Error, ErrorKind, Read, Result, Write};
+
+use core::types::field::{field_from_bytes, field_from_canonical, field_to_bytes};
+use core::types::merkle_tree::GOLDILOCKS_FIELD_U8_LEN;
+
+use ethereum_types::U256;
 
 use plonky2::field::extension::{Extendable, FieldExtension};
-use plonky2::field::polynomial::PolynomialCoeffs;
+use plonky2::field::polynomial::{PolynomialCoeffs, PolynomialValues};
 use plonky2::field::types::{Field64, PrimeField64};
 
+use plonky2::fri::oracle::PolynomialBatch;
 use plonky2::fri::proof::{FriInitialTreeProof, FriProof, FriQueryRound, FriQueryStep};
 use plonky2::hash::hash_types::RichField;
 use plonky2::hash::merkle_proofs::MerkleProof;
-use plonky2::hash::merkle_tree::MerkleCap;
+use plonky2::hash::merkle_tree::{MerkleCap, MerkleTree};
 use plonky2::plonk::config::{GenericConfig, GenericHashOut, Hasher};
 
-use super::proof::{AllProof, PublicValues, StarkOpeningSet, StarkProof};
+use super::ola_stark::NUM_TABLES;
+use super::proof::{AllProof, ProofVersion, PublicValues, StarkOpeningSet, StarkProof};
+
+/// Marks the leading word of a versioned proof.
+const PROOF_VERSION_TAG: u32 = 1 << 31;
 
 #[derive(Debug)]
-pub struct Buffer(Cursor<Vec<u8>>);
+pub struct Buffer {
+    cursor: Cursor<Vec<u8>>,
+    // version 0 proofs wrote field elements little-endian.
+    le_fields: bool,
+}
 
 impl Buffer {
     pub fn new(buffer: Vec<u8>) -> Self {
-        Self(Cursor::new(buffer))
+        Self {
+            cursor: Cursor::new(buffer),
+            le_fields: false,
+        }
     }
 
     pub fn len(&self) -> usize {
-        self.0.get_ref().len()
+        self.cursor.get_ref().len()
     }
 
     pub fn bytes(&self) -> Vec<u8> {
-        self.0.get_ref().clone()
+        self.cursor.get_ref().clone()
     }
 
     fn write_u8(&mut self, x: u8) -> Result<()> {
-        self.0.write_all(&[x])
+        self.cursor.write_all(&[x])
     }
     fn read_u8(&mut self) -> Result<u8> {
         let mut buf = [0; std::mem::size_of::<u8>()];
-        self.0.read_exact(&mut buf)?;
+        self.cursor.read_exact(&mut buf)?;
         Ok(buf[0])
     }
 
     fn write_u32(&mut self, x: u32) -> Result<()> {
-        self.0.write_all(&x.to_le_bytes())
+        self.cursor.write_all(&x.to_le_bytes())
     }
     fn read_u32(&mut self) -> Result<u32> {
         let mut buf = [0; std::mem::size_of::<u32>()];
-        self.0.read_exact(&mut buf)?;
+        self.cursor.read_exact(&mut buf)?;
         Ok(u32::from_le_bytes(buf))
     }
 
+    fn write_u64(&mut self, x: u64) -> Result<()> {
+        self.cursor.write_all(&x.to_le_bytes())
+    }
+    fn read_u64(&mut self) -> Result<u64> {
+        let mut buf = [0; std::mem::size_of::<u64>()];
+        self.cursor.read_exact(&mut buf)?;
+        Ok(u64::from_le_bytes(buf))
+    }
+
     fn write_field<F: PrimeField64>(&mut self, x: F) -> Result<()> {
-        self.0.write_all(&x.to_canonical_u64().to_le_bytes())
+        if self.le_fields {
+            return self.write_u64(x.to_canonical_u64());
+        }
+        self.cursor.write_all(&field_to_bytes(&x))
     }
     fn read_field<F: Field64>(&mut self) -> Result<F> {
-        let mut buf = [0; std::mem::size_of::<u64>()];
-        self.0.read_exact(&mut buf)?;
-        Ok(F::from_canonical_u64(u64::from_le_bytes(
-            buf.try_into().unwrap(),
-        )))
+        let field = if self.le_fields {
+            field_from_canonical(self.read_u64()?)
+        } else {
+            let mut buf = [0; GOLDILOCKS_FIELD_U8_LEN];
+            self.cursor.read_exact(&mut buf)?;
+            field_from_bytes(buf)
+        };
+        field.map_err(|e| Error::new(ErrorKind::InvalidData, e))
     }
 
     fn write_field_ext<F: RichField + Extendable<D>, const D: usize>(
@@ -113,12 +145,12 @@ impl Buffer {
     }
 
     fn write_hash<F: RichField, H: Hasher<F>>(&mut self, h: H::Hash) -> Result<()> {
-        self.0.write_all(&h.to_bytes())
+        self.cursor.write_all(&h.to_bytes())
     }
 
     fn read_hash<F: RichField, H: Hasher<F>>(&mut self) -> Result<H::Hash> {
         let mut buf = vec![0; H::HASH_SIZE];
-        self.0.read_exact(&mut buf)?;
+        self.cursor.read_exact(&mut buf)?;
         Ok(H::Hash::from_bytes(&buf))
     }
 
@@ -374,6 +406,136 @@ impl Buffer {
         })
     }
 
+    pub fn write_poly_values_vec<F: PrimeField64>(
+        &mut self,
+        polys: &[PolynomialValues<F>],
+    ) -> Result<()> {
+        self.write_u32(polys.len() as u32)?;
+        for poly in polys {
+            self.write_field_vec(&poly.values)?;
+        }
+        Ok(())
+    }
+    pub fn read_poly_values_vec<F: Field64>(&mut self) -> Result<Vec<PolynomialValues<F>>> {
+        let len = self.read_u32()?;
+        (0..len as usize)
+            .map(|_| Ok(PolynomialValues::new(self.read_field_vec()?)))
+            .collect::<Result<Vec<_>>>()
+    }
+
+    pub fn write_polynomial_batch<
+        F: RichField + Extendable<D>,
+        C: GenericConfig<D, F = F>,
+        const D: usize,
+    >(
+        &mut self,
+        batch: &PolynomialBatch<F, C, D>,
+    ) -> Result<()> {
+        self.write_u32(batch.degree_log as u32)?;
+        self.write_u32(batch.rate_bits as u32)?;
+        self.write_u8(batch.blinding as u8)?;
+        self.write_u32(batch.polynomials.len() as u32)?;
+        for poly in &batch.polynomials {
+            self.write_field_vec(&poly.coeffs)?;
+        }
+        let tree = &batch.merkle_tree;
+        self.write_u32(tree.leaves.len() as u32)?;
+        for leaf in &tree.leaves {
+            self.write_field_vec(leaf)?;
+        }
+        self.write_u32(tree.digests.len() as u32)?;
+        for &digest in &tree.digests {
+            self.write_hash::<F, C::Hasher>(digest)?;
+        }
+        self.write_merkle_cap(&tree.cap)
+    }
+    pub fn read_polynomial_batch<
+        F: RichField + Extendable<D>,
+        C: GenericConfig<D, F = F>,
+        const D: usize,
+    >(
+        &mut self,
+    ) -> Result<PolynomialBatch<F, C, D>> {
+        let degree_log = self.read_u32()? as usize;
+        let rate_bits = self.read_u32()? as usize;
+        let blinding = self.read_u8()? != 0;
+        let num_polys = self.read_u32()?;
+        let polynomials = (0..num_polys as usize)
+            .map(|_| Ok(PolynomialCoeffs::new(self.read_field_vec()?)))
+            .collect::<Result<Vec<_>>>()?;
+        let num_leaves = self.read_u32()?;
+        let leaves = (0..num_leaves as usize)
+            .map(|_| self.read_field_vec())
+            .collect::<Result<Vec<_>>>()?;
+        let num_digests = self.read_u32()?;
+        let digests = (0..num_digests as usize)
+            .map(|_| self.read_hash::<F, C::Hasher>())
+            .collect::<Result<Vec<_>>>()?;
+        let cap = self.read_merkle_cap()?;
+        Ok(PolynomialBatch {
+            polynomials,
+            merkle_tree: MerkleTree {
+                leaves,
+                digests,
+                cap,
+            },
+            degree_log,
+            rate_bits,
+            blinding,
+        })
+    }
+
+    fn write_roots_and_metadata(&mut self, public_values: &PublicValues) -> Result<()> {
+        for roots in [
+            &public_values.trie_roots_before,
+            &public_values.trie_roots_after,
+        ] {
+            self.cursor.write_all(&roots.state_root.0)?;
+            self.cursor.write_all(&roots.transactions_root.0)?;
+            self.cursor.write_all(&roots.receipts_root.0)?;
+        }
+        let metadata = &public_values.block_metadata;
+        self.cursor.write_all(&metadata.block_beneficiary.0)?;
+        for value in [
+            metadata.block_timestamp,
+            metadata.block_number,
+            metadata.block_difficulty,
+            metadata.block_gaslimit,
+            metadata.block_chain_id,
+            metadata.block_base_fee,
+        ] {
+            let mut bytes = [0u8; 32];
+            value.to_little_endian(&mut bytes);
+            self.cursor.write_all(&bytes)?;
+        }
+        Ok(())
+    }
+    fn read_roots_and_metadata(&mut self, public_values: &mut PublicValues) -> Result<()> {
+        for roots in [
+            &mut public_values.trie_roots_before,
+            &mut public_values.trie_roots_after,
+        ] {
+            self.cursor.read_exact(&mut roots.state_root.0)?;
+            self.cursor.read_exact(&mut roots.transactions_root.0)?;
+            self.cursor.read_exact(&mut roots.receipts_root.0)?;
+        }
+        let metadata = &mut public_values.block_metadata;
+        self.cursor.read_exact(&mut metadata.block_beneficiary.0)?;
+        for value in [
+            &mut metadata.block_timestamp,
+            &mut metadata.block_number,
+            &mut metadata.block_difficulty,
+            &mut metadata.block_gaslimit,
+            &mut metadata.block_chain_id,
+            &mut metadata.block_base_fee,
+        ] {
+            let mut bytes = [0u8; 32];
+            self.cursor.read_exact(&mut bytes)?;
+            *value = U256::from_little_endian(&bytes);
+        }
+        Ok(())
+    }
+
     pub fn write_all_proof<
         F: RichField + Extendable<D>,
         C: GenericConfig<D, F = F>,
@@ -382,13 +544,76 @@ impl Buffer {
         &mut self,
         proof: &AllProof<F, C, D>,
     ) -> Result<()> {
+        // Legacy proofs start with the table count, the tag bit keeps a
+        // version apart from it.
+        if proof.version != ProofVersion::legacy() {
+            self.write_u32(PROOF_VERSION_TAG | proof.version.0)?;
+        }
+        self.le_fields = !proof.version.big_endian_fields();
         self.write_u32(proof.stark_proofs.len() as u32)?;
         for p in &proof.stark_proofs {
             self.write_proof(p)?;
         }
 
         self.write_field_vec(&proof.compress_challenges)?;
-        // PublicValues
+        if !proof.version.binds_public_values() {
+            return Ok(());
+        }
+        let public_values = &proof.public_values;
+        for limb in public_values.prophet_commitment {
+            self.write_field(F::from_canonical_u64(limb))?;
+        }
+        self.write_u32(public_values.program_hashes.len() as u32)?;
+        for hash in &public_values.program_hashes {
+            for limb in hash {
+                self.write_field(F::from_canonical_u64(*limb))?;
+            }
+        }
+        self.write_roots_and_metadata(public_values)?;
+        for limb in public_values.fork_id {
+            self.write_field(F::from_canonical_u64(limb))?;
+        }
+        self.write_field(F::from_canonical_u64(public_values.chain_id))?;
+        for limb in public_values.tx_hash {
+            self.write_field(F::from_canonical_u64(limb))?;
+        }
+        self.write_u32(public_values.returned_values.len() as u32)?;
+        for value in &public_values.returned_values {
+            self.write_field(F::from_canonical_u64(*value))?;
+        }
+        for cells in [
+            &public_values.memory_image,
+            &public_values.code_region,
+            &public_values.executed_opcodes,
+        ] {
+            self.write_u32(cells.len() as u32)?;
+            for cell in cells {
+                for limb in cell {
+                    self.write_field(F::from_canonical_u64(*limb))?;
+                }
+            }
+        }
+        self.write_field(F::from_canonical_u64(public_values.start_pc))?;
+        for register in public_values.start_registers {
+            self.write_field(F::from_canonical_u64(register))?;
+        }
+        self.write_field(F::from_canonical_u64(public_values.end_pc))?;
+        for register in public_values.end_registers {
+            self.write_field(F::from_canonical_u64(register))?;
+        }
+        self.write_field(F::from_canonical_u64(public_values.gas_used))?;
+        self.write_field(F::from_bool(public_values.stopped))?;
+        self.write_u32(public_values.end_memory_image.len() as u32)?;
+        for cell in &public_values.end_memory_image {
+            for limb in cell {
+                self.write_field(F::from_canonical_u64(*limb))?;
+            }
+        }
+        if proof.version.carries_layout() {
+            for fingerprint in proof.layout {
+                self.write_u64(fingerprint)?;
+            }
+        }
         Ok(())
     }
     pub fn read_all_proof<
@@ -399,15 +624,129 @@ impl Buffer {
         &mut self,
     ) -> Result<AllProof<F, C, D>> {
         let mut stark_proofs = vec![];
-        let len = self.read_u32()? as usize;
+        let head = self.read_u32()?;
+        let (version, len) = if head & PROOF_VERSION_TAG == 0 {
+            (ProofVersion::legacy(), head as usize)
+        } else {
+            (
+                ProofVersion(head & !PROOF_VERSION_TAG),
+                self.read_u32()? as usize,
+            )
+        };
+        self.le_fields = !version.big_endian_fields();
         for _ in 0..len {
             stark_proofs.push(self.read_proof()?);
         }
         let compress_challenges = self.read_field_vec()?;
+        let mut public_values = PublicValues::default();
+        let mut layout = [0u64; NUM_TABLES];
+        if version.binds_public_values() {
+            for limb in public_values.prophet_commitment.iter_mut() {
+                *limb = self.read_field::<F>()?.to_canonical_u64();
+            }
+            let hashes_len = self.read_u32()? as usize;
+            for _ in 0..hashes_len {
+                let mut hash = [0u64; 4];
+                for limb in hash.iter_mut() {
+                    *limb = self.read_field::<F>()?.to_canonical_u64();
+                }
+                public_values.program_hashes.push(hash);
+            }
+            self.read_roots_and_metadata(&mut public_values)?;
+            for limb in public_values.fork_id.iter_mut() {
+                *limb = self.read_field::<F>()?.to_canonical_u64();
+            }
+            public_values.chain_id = self.read_field::<F>()?.to_canonical_u64();
+            for limb in public_values.tx_hash.iter_mut() {
+                *limb = self.read_field::<F>()?.to_canonical_u64();
+            }
+            let values_len = self.read_u32()? as usize;
+            for _ in 0..values_len {
+                public_values
+                    .returned_values
+                    .push(self.read_field::<F>()?.to_canonical_u64());
+            }
+            for cells in [
+                &mut public_values.memory_image,
+                &mut public_values.code_region,
+                &mut public_values.executed_opcodes,
+            ] {
+                let cells_len = self.read_u32()? as usize;
+                for _ in 0..cells_len {
+                    let mut cell = [0u64; 2];
+                    for limb in cell.iter_mut() {
+                        *limb = self.read_field::<F>()?.to_canonical_u64();
+                    }
+                    cells.push(cell);
+                }
+            }
+            public_values.start_pc = self.read_field::<F>()?.to_canonical_u64();
+            for register in public_values.start_registers.iter_mut() {
+                *register = self.read_field::<F>()?.to_canonical_u64();
+            }
+            public_values.end_pc = self.read_field::<F>()?.to_canonical_u64();
+            for register in public_values.end_registers.iter_mut() {
+                *register = self.read_field::<F>()?.to_canonical_u64();
+            }
+            public_values.gas_used = self.read_field::<F>()?.to_canonical_u64();
+            public_values.stopped = self.read_field::<F>()?.is_one();
+            let cells_len = self.read_u32()? as usize;
+            for _ in 0..cells_len {
+                let mut cell = [0u64; 2];
+                for limb in cell.iter_mut() {
+                    *limb = self.read_field::<F>()?.to_canonical_u64();
+                }
+                public_values.end_memory_image.push(cell);
+            }
+        }
+        if version.carries_layout() {
+            for fingerprint in layout.iter_mut() {
+                *fingerprint = self.read_u64()?;
+            }
+        }
         Ok(AllProof {
+            version,
             stark_proofs: stark_proofs.try_into().unwrap(),
             compress_challenges: compress_challenges.try_into().unwrap(),
-            public_values: PublicValues::default(),
+            public_values,
+            layout,
         })
     }
 }
+
+#[cfg(test)]
+mod tests {
+    use crate::stark::serialization::Buffer;
+    use plonky2::field::goldilocks_field::GoldilocksField;
+    use plonky2::field::types::{Field, Field64};
+    use std::io::ErrorKind;
+
+    #[test]
+    fn test_field_byte_order() {
+        let value = GoldilocksField::from_canonical_u64(0x0102030405060708);
+        let mut buffer = Buffer::new(Vec::new());
+        buffer.write_field(value).unwrap();
+        assert_eq!(buffer.bytes(), vec![1, 2, 3, 4, 5, 6, 7, 8]);
+
+        let mut legacy = Buffer::new(Vec::new());
+        legacy.le_fields = true;
+        legacy.write_field(value).unwrap();
+        assert_eq!(legacy.bytes(), vec![8, 7, 6, 5, 4, 3, 2, 1]);
+        let mut read = Buffer::new(legacy.bytes());
+        read.le_fields = true;
+        assert_eq!(read.read_field::<GoldilocksField>().unwrap(), value);
+
+        for le_fields in [false, true] {
+            let order = GoldilocksField::ORDER;
+            let bytes = if le_fields {
+                order.to_le_bytes()
+            } else {
+                order.to_be_bytes()
+            };
+            let mut buffer = Buffer::new(bytes.to_vec());
+            buffer.le_fields = le_fields;
+            let err = buffer.read_field::<GoldilocksField>().unwrap_err();
+            assert_eq!(err.kind(), ErrorKind::InvalidData);
+        }
+    }
+}
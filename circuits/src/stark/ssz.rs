@@ -0,0 +1,379 @@
+//! SSZ encoding and `hash_tree_root` of the public values, so verifier
+//! contracts on other chains can read them. `U256`s are `uint256`, hashes
+//! `Bytes32`, addresses `Bytes20` and goldilocks hashes `Vector[uint64, 4]`.
+//! The returned values are a `List[uint64, 4]`, the memory image and the
+//! code region `List[Vector[uint64, 2], 1048576]`s of `(addr, value)` cells.
+//! The start and end registers are `Vector[uint64, 10]`s, the end memory
+//! image is a list like the memory image.
+
+use core::program::{MAX_RETURN_VALUES, REGISTER_NUM};
+use core::types::merkle_tree::ZkHash;
+use core::types::ssz::{
+    chunk, list_root, merkleize, mix_in_length, ContainerDecoder, ContainerEncoder, HashTreeRoot,
+    Root, Ssz, SszError,
+};
+use core::types::{Field, GoldilocksField, PrimeField64};
+use ethereum_types::{Address, H256, U256};
+
+use super::proof::{BlockMetadata, PublicValues, TrieRoots};
+
+/// Limit of `PublicValues::program_hashes`, `List[Vector[uint64, 4], 1024]`.
+pub const PROGRAM_HASHES_LIMIT: usize = 1 << 10;
+
+/// Limit of `PublicValues::memory_image`.
+pub const MEMORY_IMAGE_LIMIT: usize = 1 << 20;
+
+/// Limit of `PublicValues::code_region`.
+pub const CODE_REGION_LIMIT: usize = 1 << 20;
+
+/// Limit of `PublicValues::end_memory_image`.
+pub const END_MEMORY_IMAGE_LIMIT: usize = 1 << 20;
+
+/// Limit of `PublicValues::executed_opcodes`, above the number of opcodes.
+pub const EXECUTED_OPCODES_LIMIT: usize = 1 << 6;
+
+fn u256_bytes(value: &U256) -> [u8; 32] {
+    let mut bytes = [0; 32];
+    value.to_little_endian(&mut bytes);
+    bytes
+}
+
+fn to_zk_hash(limbs: &[u64; 4]) -> ZkHash {
+    limbs.map(GoldilocksField::from_canonical_u64)
+}
+
+fn from_zk_hash(hash: &ZkHash) -> [u64; 4] {
+    hash.map(|limb| limb.to_canonical_u64())
+}
+
+impl Ssz for TrieRoots {
+    fn fixed_len() -> Option<usize> {
+        Some(3 * 32)
+    }
+
+    fn ssz_append(&self, buf: &mut Vec<u8>) {
+        ContainerEncoder::new()
+            .field(&self.state_root.0)
+            .field(&self.transactions_root.0)
+            .field(&self.receipts_root.0)
+            .finish(buf);
+    }
+
+    fn from_ssz_bytes(bytes: &[u8]) -> Result<Self, SszError> {
+        let mut decoder = ContainerDecoder::new(bytes);
+        for _ in 0..3 {
+            decoder.register::<[u8; 32]>()?;
+        }
+        decoder.finish()?;
+        Ok(Self {
+            state_root: H256(decoder.decode_next()?),
+            transactions_root: H256(decoder.decode_next()?),
+            receipts_root: H256(decoder.decode_next()?),
+        })
+    }
+}
+
+impl HashTreeRoot for TrieRoots {
+    fn hash_tree_root(&self) -> Root {
+        let fields = [self.state_root, self.transactions_root, self.receipts_root]
+            .map(|root| root.0.hash_tree_root());
+        merkleize(&fields, fields.len())
+    }
+}
+
+impl BlockMetadata {
+    fn u256_fields(&self) -> [U256; 6] {
+        [
+            self.block_timestamp,
+            self.block_number,
+            self.block_difficulty,
+            self.block_gaslimit,
+            self.block_chain_id,
+            self.block_base_fee,
+        ]
+    }
+}
+
+impl Ssz for BlockMetadata {
+    fn fixed_len() -> Option<usize> {
+        Some(20 + 6 * 32)
+    }
+
+    fn ssz_append(&self, buf: &mut Vec<u8>) {
+        let mut encoder = ContainerEncoder::new();
+        encoder.field(&self.block_beneficiary.0);
+        for value in self.u256_fields() {
+            encoder.field(&u256_bytes(&value));
+        }
+        encoder.finish(buf);
+    }
+
+    fn from_ssz_bytes(bytes: &[u8]) -> Result<Self, SszError> {
+        let mut decoder = ContainerDecoder::new(bytes);
+        decoder.register::<[u8; 20]>()?;
+        for _ in 0..6 {
+            decoder.register::<[u8; 32]>()?;
+        }
+        decoder.finish()?;
+        let block_beneficiary = Address::from(decoder.decode_next::<[u8; 20]>()?);
+        let mut u256 = || -> Result<U256, SszError> {
+            Ok(U256::from_little_endian(
+                &decoder.decode_next::<[u8; 32]>()?,
+            ))
+        };
+        Ok(Self {
+            block_beneficiary,
+            block_timestamp: u256()?,
+            block_number: u256()?,
+            block_difficulty: u256()?,
+            block_gaslimit: u256()?,
+            block_chain_id: u256()?,
+            block_base_fee: u256()?,
+        })
+    }
+}
+
+impl HashTreeRoot for BlockMetadata {
+    fn hash_tree_root(&self) -> Root {
+        let mut fields = vec![self.block_beneficiary.0.hash_tree_root()];
+        fields.extend(
+            self.u256_fields()
+                .iter()
+                .map(|value| chunk(&u256_bytes(value))),
+        );
+        merkleize(&fields, fields.len())
+    }
+}
+
+impl Ssz for PublicValues {
+    fn fixed_len() -> Option<usize> {
+        None
+    }
+
+    fn ssz_append(&self, buf: &mut Vec<u8>) {
+        let program_hashes: Vec<ZkHash> = self.program_hashes.iter().map(to_zk_hash).collect();
+        ContainerEncoder::new()
+            .field(&self.trie_roots_before)
+            .field(&self.trie_roots_after)
+            .field(&self.block_metadata)
+            .field(&to_zk_hash(&self.prophet_commitment))
+            .field(&program_hashes)
+            .field(&to_zk_hash(&self.fork_id))
+            .field(&self.chain_id)
+            .field(&to_zk_hash(&self.tx_hash))
+            .field(&self.returned_values)
+            .field(&self.memory_image)
+            .field(&self.code_region)
+            .field(&self.executed_opcodes)
+            .field(&self.start_pc)
+            .field(&self.start_registers)
+            .field(&self.end_pc)
+            .field(&self.end_registers)
+            .field(&self.gas_used)
+            .field(&self.stopped)
+            .field(&self.end_memory_image)
+            .finish(buf);
+    }
+
+    fn from_ssz_bytes(bytes: &[u8]) -> Result<Self, SszError> {
+        let mut decoder = ContainerDecoder::new(bytes);
+        decoder.register::<TrieRoots>()?;
+        decoder.register::<TrieRoots>()?;
+        decoder.register::<BlockMetadata>()?;
+        decoder.register::<ZkHash>()?;
+        decoder.register::<Vec<ZkHash>>()?;
+        decoder.register::<ZkHash>()?;
+        decoder.register::<u64>()?;
+        decoder.register::<ZkHash>()?;
+        decoder.register::<Vec<u64>>()?;
+        decoder.register::<Vec<[u64; 2]>>()?;
+        decoder.register::<Vec<[u64; 2]>>()?;
+        decoder.register::<Vec<[u64; 2]>>()?;
+        decoder.register::<u64>()?;
+        decoder.register::<[u64; REGISTER_NUM]>()?;
+        decoder.register::<u64>()?;
+        decoder.register::<[u64; REGISTER_NUM]>()?;
+        decoder.register::<u64>()?;
+        decoder.register::<bool>()?;
+        decoder.register::<Vec<[u64; 2]>>()?;
+        decoder.finish()?;
+        let trie_roots_before = decoder.decode_next()?;
+        let trie_roots_after = decoder.decode_next()?;
+        let block_metadata = decoder.decode_next()?;
+        let prophet_commitment = from_zk_hash(&decoder.decode_next()?);
+        let program_hashes = decoder.decode_next::<Vec<ZkHash>>()?;
+        let fork_id = from_zk_hash(&decoder.decode_next()?);
+        let chain_id = decoder.decode_next()?;
+        let tx_hash = from_zk_hash(&decoder.decode_next()?);
+        let returned_values = decoder.decode_next::<Vec<u64>>()?;
+        let memory_image = decoder.decode_next::<Vec<[u64; 2]>>()?;
+        let code_region = decoder.decode_next::<Vec<[u64; 2]>>()?;
+        let executed_opcodes = decoder.decode_next::<Vec<[u64; 2]>>()?;
+        let start_pc = decoder.decode_next()?;
+        let start_registers = decoder.decode_next()?;
+        let end_pc = decoder.decode_next()?;
+        let end_registers = decoder.decode_next()?;
+        let gas_used = decoder.decode_next()?;
+        let stopped = decoder.decode_next()?;
+        let end_memory_image = decoder.decode_next::<Vec<[u64; 2]>>()?;
+        if program_hashes.len() > PROGRAM_HASHES_LIMIT {
+            return Err(SszError::ListTooLong(
+                program_hashes.len(),
+                PROGRAM_HASHES_LIMIT,
+            ));
+        }
+        if returned_values.len() > MAX_RETURN_VALUES {
+            return Err(SszError::ListTooLong(
+                returned_values.len(),
+                MAX_RETURN_VALUES,
+            ));
+        }
+        if memory_image.len() > MEMORY_IMAGE_LIMIT {
+            return Err(SszError::ListTooLong(
+                memory_image.len(),
+                MEMORY_IMAGE_LIMIT,
+            ));
+        }
+        if code_region.len() > CODE_REGION_LIMIT {
+            return Err(SszError::ListTooLong(code_region.len(), CODE_REGION_LIMIT));
+        }
+        if end_memory_image.len() > END_MEMORY_IMAGE_LIMIT {
+            return Err(SszError::ListTooLong(
+                end_memory_image.len(),
+                END_MEMORY_IMAGE_LIMIT,
+            ));
+        }
+        if executed_opcodes.len() > EXECUTED_OPCODES_LIMIT {
+            return Err(SszError::ListTooLong(
+                executed_opcodes.len(),
+                EXECUTED_OPCODES_LIMIT,
+            ));
+        }
+        Ok(Self {
+            trie_roots_before,
+            trie_roots_after,
+            block_metadata,
+            prophet_commitment,
+            program_hashes: program_hashes.iter().map(from_zk_hash).collect(),
+            fork_id,
+            chain_id,
+            tx_hash,
+            returned_values,
+            memory_image,
+            code_region,
+            executed_opcodes,
+            start_pc,
+            start_registers,
+            end_pc,
+            end_registers,
+            gas_used,
+            stopped,
+            end_memory_image,
+        })
+    }
+}
+
+impl HashTreeRoot for PublicValues {
+    fn hash_tree_root(&self) -> Root {
+        let program_hashes: Vec<ZkHash> = self.program_hashes.iter().map(to_zk_hash).collect();
+        let fields = [
+            self.trie_roots_before.hash_tree_root(),
+            self.trie_roots_after.hash_tree_root(),
+            self.block_metadata.hash_tree_root(),
+            to_zk_hash(&self.prophet_commitment).hash_tree_root(),
+            list_root(&program_hashes, PROGRAM_HASHES_LIMIT),
+            to_zk_hash(&self.fork_id).hash_tree_root(),
+            self.chain_id.hash_tree_root(),
+            to_zk_hash(&self.tx_hash).hash_tree_root(),
+            // at most four uint64s pack into one chunk
+            mix_in_length(
+                &chunk(&self.returned_values.to_ssz()),
+                self.returned_values.len(),
+            ),
+            list_root(&self.memory_image, MEMORY_IMAGE_LIMIT),
+            list_root(&self.code_region, CODE_REGION_LIMIT),
+            list_root(&self.executed_opcodes, EXECUTED_OPCODES_LIMIT),
+            self.start_pc.hash_tree_root(),
+            self.start_registers.hash_tree_root(),
+            self.end_pc.hash_tree_root(),
+            self.end_registers.hash_tree_root(),
+            self.gas_used.hash_tree_root(),
+            self.stopped.hash_tree_root(),
+            list_root(&self.end_memory_image, END_MEMORY_IMAGE_LIMIT),
+        ];
+        merkleize(&fields, fields.len())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use core::types::ssz::{HashTreeRoot, Ssz};
+    use ethereum_types::{Address, H256, U256};
+
+    use crate::stark::proof::{BlockMetadata, PublicValues, TrieRoots};
+
+    #[test]
+    fn test_public_values_ssz() {
+        let roots = |seed: u8| TrieRoots {
+            state_root: H256([seed; 32]),
+            transactions_root: H256([seed + 1; 32]),
+            receipts_root: H256([seed + 2; 32]),
+        };
+        let values = PublicValues {
+            trie_roots_before: roots(1),
+            trie_roots_after: roots(4),
+            block_metadata: BlockMetadata {
+                block_beneficiary: Address::from([9; 20]),
+                block_timestamp: U256::from(1_700_000_000u64),
+                block_number: U256::from(42),
+                block_chain_id: U256::MAX,
+                ..Default::default()
+            },
+            prophet_commitment: [1, 2, 3, 4],
+            program_hashes: vec![[5, 6, 7, 8], [9, 10, 11, 12]],
+            fork_id: [13, 14, 15, 16],
+            chain_id: 17,
+            tx_hash: [18, 19, 20, 21],
+            returned_values: vec![22, 23],
+            memory_image: vec![[25, 26]],
+            code_region: vec![[27, 28], [29, 30]],
+            executed_opcodes: vec![[31, 32]],
+            start_pc: 33,
+            start_registers: [34; 10],
+            end_pc: 35,
+            end_registers: [36; 10],
+            gas_used: 39,
+            stopped: true,
+            end_memory_image: vec![[37, 38]],
+        };
+        let bytes = values.to_ssz();
+        // fixed part with the offsets of the six lists, then the lists
+        let fixed = 2 * 96 + 212 + 32 + 4 + 32 + 8 + 32 + 4 + 4 + 4 + 4 + 8 + 10 * 8;
+        let fixed = fixed + 8 + 10 * 8 + 8 + 1 + 4;
+        assert_eq!(bytes.len(), fixed + 2 * 32 + 2 * 8 + 16 + 2 * 16 + 16 + 16);
+        let decoded = PublicValues::from_ssz_bytes(&bytes).unwrap();
+        assert_eq!(decoded.to_ssz(), bytes);
+        assert_eq!(decoded.block_metadata.block_chain_id, U256::MAX);
+        assert_eq!(decoded.program_hashes, values.program_hashes);
+        assert_eq!(decoded.fork_id, values.fork_id);
+        assert_eq!(decoded.chain_id, values.chain_id);
+        assert_eq!(decoded.tx_hash, values.tx_hash);
+        assert_eq!(decoded.returned_values, values.returned_values);
+        assert_eq!(decoded.memory_image, values.memory_image);
+        assert_eq!(decoded.code_region, values.code_region);
+        assert_eq!(decoded.executed_opcodes, values.executed_opcodes);
+        assert_eq!(decoded.start_pc, values.start_pc);
+        assert_eq!(decoded.start_registers, values.start_registers);
+        assert_eq!(decoded.end_pc, values.end_pc);
+        assert_eq!(decoded.end_registers, values.end_registers);
+        assert_eq!(decoded.gas_used, values.gas_used);
+        assert_eq!(decoded.stopped, values.stopped);
+        assert_eq!(decoded.end_memory_image, values.end_memory_image);
+        assert_eq!(decoded.hash_tree_root(), values.hash_tree_root());
+
+        let mut changed = values.clone();
+        changed.program_hashes.pop();
+        assert_ne!(changed.hash_tree_root(), values.hash_tree_root());
+        assert!(PublicValues::from_ssz_bytes(&bytes[..bytes.len() - 1]).is_err());
+    }
+}
@@ -0,0 +1,415 @@
+use std::collections::BTreeMap;
+use std::fmt::Write;
+
+use plonky2::field::extension::Extendable;
+use plonky2::field::polynomial::PolynomialValues;
+use plonky2::field::types::Field;
+use plonky2::hash::hash_types::RichField;
+use plonky2_util::log2_strict;
+use rand::rngs::StdRng;
+use rand::seq::index;
+use rand::SeedableRng;
+
+use super::constraint_consumer::ConstraintConsumer;
+use super::ola_stark::{OlaStark, Table, NUM_TABLES};
+use super::stark::Stark;
+use super::vars::StarkEvaluationVars;
+use crate::builtins::bitwise::bitwise_stark::BitwiseStark;
+use crate::builtins::bitwise::columns::get_bitwise_col_name_map;
+use crate::builtins::cmp::cmp_stark::CmpStark;
+use crate::builtins::cmp::columns::get_cmp_col_name_map;
+use crate::builtins::poseidon::columns::{
+    get_poseidon_chunk_col_name_map, get_poseidon_col_name_map,
+};
+use crate::builtins::poseidon::poseidon_chunk_stark::PoseidonChunkStark;
+use crate::builtins::poseidon::poseidon_stark::PoseidonStark;
+use crate::builtins::rangecheck::columns::get_rangecheck_col_name_map;
+use crate::builtins::sccall::columns::get_sccall_col_name_map;
+use crate::builtins::sccall::sccall_stark::SCCallStark;
+use crate::builtins::storage::columns::get_storage_access_col_name_map;
+use crate::builtins::storage::storage_access_stark::StorageAccessStark;
+use crate::builtins::tape::columns::get_tape_col_name_map;
+use crate::builtins::transient::columns::get_transient_col_name_map;
+use crate::cpu::columns::get_cpu_col_name_map;
+use crate::cpu::cpu_stark::CpuStark;
+use crate::memory::columns::get_memory_col_name_map;
+use crate::memory::memory_stark::MemoryStark;
+use crate::program::columns::{get_prog_chunk_col_name_map, get_prog_col_name_map};
+use crate::program::prog_chunk_stark::ProgChunkStark;
+use crate::program::program_stark::ProgramStark;
+
+/// Evaluate the constraints of `stark` on every row of the generated `trace`
+/// and describe the first row that violates one, with the values of its
+/// local and next row named by `col_names`. Columns missing from `col_names`
+/// are shown by index.
+pub fn check_trace_constraints<F, S, const D: usize>(
+    table: Table,
+    stark: &S,
+    trace: &[PolynomialValues<F>],
+    col_names: &BTreeMap<usize, String>,
+) -> Result<(), String>
+where
+    F: RichField + Extendable<D>,
+    S: Stark<F, D>,
+    [(); S::COLUMNS]:,
+{
+    assert_eq!(trace.len(), S::COLUMNS, "trace width mismatch");
+    check_rows(table, stark, trace, col_names, 0..trace[0].len())
+}
+
+fn check_rows<F, S, const D: usize>(
+    table: Table,
+    stark: &S,
+    trace: &[PolynomialValues<F>],
+    col_names: &BTreeMap<usize, String>,
+    rows: impl IntoIterator<Item = usize>,
+) -> Result<(), String>
+where
+    F: RichField + Extendable<D>,
+    S: Stark<F, D>,
+    [(); S::COLUMNS]:,
+{
+    let len = trace[0].len();
+    let last = F::primitive_root_of_unity(log2_strict(len)).inverse();
+    let subgroup =
+        F::cyclic_subgroup_known_order(F::primitive_root_of_unity(log2_strict(len)), len);
+
+    for i in rows {
+        let local_values: [F; S::COLUMNS] = std::array::from_fn(|c| trace[c].values[i]);
+        let next_values: [F; S::COLUMNS] = std::array::from_fn(|c| trace[c].values[(i + 1) % len]);
+        let mut consumer = ConstraintConsumer::new_recording(
+            subgroup[i] - last,
+            if i == 0 { F::ONE } else { F::ZERO },
+            if i == len - 1 { F::ONE } else { F::ZERO },
+        );
+        stark.eval_packed_generic::<F, F, 1>(
+            StarkEvaluationVars {
+                local_values: &local_values,
+                next_values: &next_values,
+            },
+            &mut consumer,
+        );
+        let failed: Vec<usize> = consumer
+            .recorded()
+            .iter()
+            .enumerate()
+            .filter(|(_, value)| !value.is_zero())
+            .map(|(index, _)| index)
+            .collect();
+        if failed.is_empty() {
+            continue;
+        }
+
+        let mut msg = format!(
+            "{:?}: constraints {:?} fail on row {} of {}",
+            table, failed, i, len
+        );
+        for (name, values) in [("local", &local_values), ("next", &next_values)] {
+            write!(msg, "\n  {}:", name).unwrap();
+            for (col, value) in values.iter().enumerate() {
+                match col_names.get(&col) {
+                    Some(col_name) => write!(msg, " {}={}", col_name, value).unwrap(),
+                    None => write!(msg, " col{}={}", col, value).unwrap(),
+                }
+            }
+        }
+        return Err(msg);
+    }
+    Ok(())
+}
+
+/// Check the generated traces of every builtin STARK with
+/// `check_trace_constraints` and panic on the first violated constraint, so
+/// generation bugs surface before proving. Runs in `prove` with the
+/// `trace_check` feature.
+pub fn check_builtin_traces<F, const D: usize>(
+    ola_stark: &OlaStark<F, D>,
+    traces: &[Vec<PolynomialValues<F>>; NUM_TABLES],
+) where
+    F: RichField + Extendable<D>,
+    [(); BitwiseStark::<F, D>::COLUMNS]:,
+    [(); CmpStark::<F, D>::COLUMNS]:,
+    [(); PoseidonStark::<F, D>::COLUMNS]:,
+    [(); PoseidonChunkStark::<F, D>::COLUMNS]:,
+    [(); StorageAccessStark::<F, D>::COLUMNS]:,
+    [(); SCCallStark::<F, D>::COLUMNS]:,
+    [(); crate::builtins::rangecheck::rangecheck_stark::RangeCheckStark::<F, D>::COLUMNS]:, [(); crate::builtins::tape::tape_stark::TapeStark::<F, D>::COLUMNS]:, [(); crate::builtins::transient::transient_stark::TransientStark::<F, D>::COLUMNS]:, //HACK-BOUNDS
+{
+    let results = [
+        check_trace_constraints(
+            Table::Bitwise,
+            &ola_stark.bitwise_stark,
+            &traces[Table::Bitwise as usize],
+            &get_bitwise_col_name_map(),
+        ),
+        check_trace_constraints(
+            Table::Cmp,
+            &ola_stark.cmp_stark,
+            &traces[Table::Cmp as usize],
+            &get_cmp_col_name_map(),
+        ),
+        check_trace_constraints(
+            Table::RangeCheck,
+            &ola_stark.rangecheck_stark,
+            &traces[Table::RangeCheck as usize],
+            &BTreeMap::new(),
+        ),
+        check_trace_constraints(
+            Table::Poseidon,
+            &ola_stark.poseidon_stark,
+            &traces[Table::Poseidon as usize],
+            &get_poseidon_col_name_map(),
+        ),
+        check_trace_constraints(
+            Table::PoseidonChunk,
+            &ola_stark.poseidon_chunk_stark,
+            &traces[Table::PoseidonChunk as usize],
+            &get_poseidon_chunk_col_name_map(),
+        ),
+        check_trace_constraints(
+            Table::StorageAccess,
+            &ola_stark.storage_access_stark,
+            &traces[Table::StorageAccess as usize],
+            &get_storage_access_col_name_map(),
+        ),
+        check_trace_constraints(
+            Table::SCCall,
+            &ola_stark.sccall_stark,
+            &traces[Table::SCCall as usize],
+            &get_sccall_col_name_map(),
+        ),
+        check_trace_constraints(
+            Table::Transient,
+            &ola_stark.transient_stark,
+            &traces[Table::Transient as usize],
+            &get_transient_col_name_map(),
+        ),
+    ];
+    for result in results {
+        if let Err(msg) = result {
+            panic!("generated trace violates its constraints: {}", msg);
+        }
+    }
+}
+
+/// Rows of a table of `len` rows a spot check evaluates: about `rate` of them
+/// drawn with `seed`, plus the first and last row so the boundary and
+/// wraparound constraints are always covered.
+pub fn sample_rows(len: usize, rate: f64, seed: u64) -> Vec<usize> {
+    let count = ((len as f64 * rate).ceil() as usize).min(len);
+    let mut rng = StdRng::seed_from_u64(seed);
+    let mut rows = index::sample(&mut rng, len, count).into_vec();
+    rows.extend([0, len - 1]);
+    rows.sort_unstable();
+    rows.dedup();
+    rows
+}
+
+/// Evaluate the constraints of every table on a sample of its rows, see
+/// `sample_rows`, and describe the first row that violates one. Much faster
+/// than `check_builtin_traces` and still catches most generation bugs, as
+/// those usually break many rows. Runs in `prove` when the config sets a
+/// spot check rate.
+pub fn spot_check_traces<F, const D: usize>(
+    ola_stark: &OlaStark<F, D>,
+    traces: &[Vec<PolynomialValues<F>>; NUM_TABLES],
+    rate: f64,
+    seed: u64,
+) -> Result<(), String>
+where
+    F: RichField + Extendable<D>,
+    [(); CpuStark::<F, D>::COLUMNS]:,
+    [(); MemoryStark::<F, D>::COLUMNS]:,
+    [(); BitwiseStark::<F, D>::COLUMNS]:,
+    [(); CmpStark::<F, D>::COLUMNS]:,
+    [(); PoseidonStark::<F, D>::COLUMNS]:,
+    [(); PoseidonChunkStark::<F, D>::COLUMNS]:,
+    [(); StorageAccessStark::<F, D>::COLUMNS]:,
+    [(); SCCallStark::<F, D>::COLUMNS]:,
+    [(); crate::builtins::rangecheck::rangecheck_stark::RangeCheckStark::<F, D>::COLUMNS]:, [(); crate::builtins::tape::tape_stark::TapeStark::<F, D>::COLUMNS]:, [(); crate::builtins::transient::transient_stark::TransientStark::<F, D>::COLUMNS]:, //HACK-BOUNDS
+    [(); ProgramStark::<F, D>::COLUMNS]:,
+    [(); ProgChunkStark::<F, D>::COLUMNS]:,
+{
+    let rows = |table: Table| {
+        let len = traces[table as usize][0].len();
+        sample_rows(len, rate, seed ^ table as u64)
+    };
+    let trace = |table: Table| &traces[table as usize];
+    check_rows(
+        Table::Cpu,
+        &ola_stark.cpu_stark,
+        trace(Table::Cpu),
+        &get_cpu_col_name_map(),
+        rows(Table::Cpu),
+    )?;
+    check_rows(
+        Table::Memory,
+        &ola_stark.memory_stark,
+        trace(Table::Memory),
+        &get_memory_col_name_map(),
+        rows(Table::Memory),
+    )?;
+    check_rows(
+        Table::Bitwise,
+        &ola_stark.bitwise_stark,
+        trace(Table::Bitwise),
+        &get_bitwise_col_name_map(),
+        rows(Table::Bitwise),
+    )?;
+    check_rows(
+        Table::Cmp,
+        &ola_stark.cmp_stark,
+        trace(Table::Cmp),
+        &get_cmp_col_name_map(),
+        rows(Table::Cmp),
+    )?;
+    check_rows(
+        Table::RangeCheck,
+        &ola_stark.rangecheck_stark,
+        trace(Table::RangeCheck),
+        &get_rangecheck_col_name_map(),
+        rows(Table::RangeCheck),
+    )?;
+    check_rows(
+        Table::Poseidon,
+        &ola_stark.poseidon_stark,
+        trace(Table::Poseidon),
+        &get_poseidon_col_name_map(),
+        rows(Table::Poseidon),
+    )?;
+    check_rows(
+        Table::PoseidonChunk,
+        &ola_stark.poseidon_chunk_stark,
+        trace(Table::PoseidonChunk),
+        &get_poseidon_chunk_col_name_map(),
+        rows(Table::PoseidonChunk),
+    )?;
+    check_rows(
+        Table::StorageAccess,
+        &ola_stark.storage_access_stark,
+        trace(Table::StorageAccess),
+        &get_storage_access_col_name_map(),
+        rows(Table::StorageAccess),
+    )?;
+    check_rows(
+        Table::Tape,
+        &ola_stark.tape_stark,
+        trace(Table::Tape),
+        &get_tape_col_name_map(),
+        rows(Table::Tape),
+    )?;
+    check_rows(
+        Table::SCCall,
+        &ola_stark.sccall_stark,
+        trace(Table::SCCall),
+        &get_sccall_col_name_map(),
+        rows(Table::SCCall),
+    )?;
+    check_rows(
+        Table::Program,
+        &ola_stark.program_stark,
+        trace(Table::Program),
+        &get_prog_col_name_map(),
+        rows(Table::Program),
+    )?;
+    check_rows(
+        Table::ProgChunk,
+        &ola_stark.prog_chunk_stark,
+        trace(Table::ProgChunk),
+        &get_prog_chunk_col_name_map(),
+        rows(Table::ProgChunk),
+    )?;
+    check_rows(
+        Table::Transient,
+        &ola_stark.transient_stark,
+        trace(Table::Transient),
+        &get_transient_col_name_map(),
+        rows(Table::Transient),
+    )
+}
+
+#[cfg(test)]
+mod tests {
+    use core::trace::trace::CmpRow;
+    use std::collections::BTreeMap;
+
+    use plonky2::field::types::Field;
+    use plonky2::plonk::config::{GenericConfig, PoseidonGoldilocksConfig};
+
+    use super::{check_rows, check_trace_constraints, sample_rows};
+    use crate::builtins::cmp::cmp_stark::CmpStark;
+    use crate::builtins::cmp::columns::{get_cmp_col_name_map, COL_CMP_GTE};
+    use crate::generation::builtin::generate_cmp_trace;
+    use crate::stark::ola_stark::Table;
+    use crate::stark::util::trace_to_poly_values;
+
+    const D: usize = 2;
+    type C = PoseidonGoldilocksConfig;
+    type F = <C as GenericConfig<D>>::F;
+
+    #[test]
+    fn test_check_trace_constraints() {
+        let stark = CmpStark::<F, D>::default();
+        let row = CmpRow {
+            op0: F::ONE,
+            op1: F::TWO,
+            gte: F::ZERO,
+            abs_diff: F::ONE,
+            abs_diff_inv: F::ONE,
+            filter_looking_rc: F::ONE,
+        };
+        let mut trace = trace_to_poly_values(generate_cmp_trace::<F>(&[row]));
+        check_trace_constraints(Table::Cmp, &stark, &trace, &get_cmp_col_name_map()).unwrap();
+
+        trace[COL_CMP_GTE].values[0] = F::ONE;
+        let msg = check_trace_constraints(Table::Cmp, &stark, &trace, &get_cmp_col_name_map())
+            .unwrap_err();
+        assert!(msg.starts_with("Cmp: constraints"));
+        assert!(msg.contains("fail on row 0 of"));
+        assert!(msg.contains("CMP_GTE=1"));
+        let msg =
+            check_trace_constraints(Table::Cmp, &stark, &trace, &BTreeMap::new()).unwrap_err();
+        assert!(msg.contains(&format!("col{}=1", COL_CMP_GTE)));
+    }
+
+    #[test]
+    fn test_spot_check() {
+        let rows = sample_rows(1 << 10, 0.01, 7);
+        assert_eq!(rows, sample_rows(1 << 10, 0.01, 7));
+        assert!(rows.len() >= 11 && rows.len() <= 13);
+        assert_eq!(rows[0], 0);
+        assert_eq!(*rows.last().unwrap(), (1 << 10) - 1);
+        assert!(rows.windows(2).all(|w| w[0] < w[1]));
+        assert_eq!(sample_rows(4, 2.0, 0), vec![0, 1, 2, 3]);
+
+        let stark = CmpStark::<F, D>::default();
+        let row = CmpRow {
+            op0: F::ONE,
+            op1: F::TWO,
+            gte: F::ZERO,
+            abs_diff: F::ONE,
+            abs_diff_inv: F::ONE,
+            filter_looking_rc: F::ONE,
+        };
+        let mut trace = trace_to_poly_values(generate_cmp_trace::<F>(&[row]));
+        let len = trace[0].len();
+        let names = get_cmp_col_name_map();
+        check_rows(
+            Table::Cmp,
+            &stark,
+            &trace,
+            &names,
+            sample_rows(len, 0.01, 1),
+        )
+        .unwrap();
+        trace[COL_CMP_GTE].values[0] = F::ONE;
+        assert!(check_rows(
+            Table::Cmp,
+            &stark,
+            &trace,
+            &names,
+            sample_rows(len, 0.01, 1)
+        )
+        .is_err());
+    }
+}
@@ -58,7 +58,6 @@ pub fn trace_to_poly_values<F: Field, const COLUMNS: usize>(
 }
 
 /// Returns the 32-bit little-endian limbs of a `U256`.
-#[allow(unused)]
 pub(crate) fn u256_limbs<F: Field>(u256: U256) -> [F; 8] {
     u256.0
         .into_iter()
@@ -74,7 +73,6 @@ pub(crate) fn u256_limbs<F: Field>(u256: U256) -> [F; 8] {
 }
 
 /// Returns the 32-bit little-endian limbs of a `H256`.
-#[allow(unused)]
 pub(crate) fn h256_limbs<F: Field>(h256: H256) -> [F; 8] {
     h256.0
         .chunks(4)
@@ -86,7 +84,6 @@ pub(crate) fn h256_limbs<F: Field>(h256: H256) -> [F; 8] {
 }
 
 /// Returns the 32-bit limbs of a `U160`.
-#[allow(unused)]
 pub(crate) fn h160_limbs<F: Field>(h160: H160) -> [F; 5] {
     h160.0
         .chunks(4)
@@ -1,6 +1,15 @@
-use std::any::type_name;
-
-use anyhow::{ensure, Result};
+use std::collections::BTreeMap;
+#[cfg(feature = "proof_compat")]
+use std::collections::HashMap;
+
+use core::program::REGISTER_NUM;
+use core::types::field::fields_from_bytes;
+use core::types::merkle_tree::tree_value_to_h256;
+use core::types::{GoldilocksField, PrimeField64};
+use core::vm::chain_spec::ChainSpec;
+use core::vm::opcodes::OlaOpcode;
+use ethereum_types::{H256, U256};
+use maybe_rayon::*;
 use plonky2::field::extension::{Extendable, FieldExtension};
 use plonky2::field::types::Field;
 use plonky2::fri::verifier::verify_fri_proof;
@@ -11,10 +20,13 @@ use plonky2::plonk::plonk_common::reduce_with_powers;
 use super::config::StarkConfig;
 use super::constraint_consumer::ConstraintConsumer;
 use super::cross_table_lookup::{verify_cross_table_lookups, CtlCheckVars};
-use super::ola_stark::{OlaStark, Table, NUM_TABLES};
-use super::permutation::{GrandProductChallenge, PermutationCheckVars};
+use super::error::VerifierError;
+use super::layout::layout_fingerprints;
+use super::ola_stark::{OlaStark, Table, ALL_TABLES};
+use super::permutation::{GrandProductChallengeSet, PermutationCheckVars};
 use super::proof::{
-    AllProof, AllProofChallenges, PublicValues, StarkOpeningSet, StarkProof, StarkProofChallenges,
+    AllProof, AllProofChallenges, ProofVersion, PublicValues, StarkOpeningSet, StarkProof,
+    StarkProofChallenges,
 };
 use super::stark::Stark;
 use super::vanishing_poly::eval_vanishing_poly;
@@ -28,15 +40,120 @@ use crate::builtins::sccall::sccall_stark::SCCallStark;
 use crate::builtins::storage::storage_access_stark::StorageAccessStark;
 // use crate::builtins::tape::tape_stark::TapeStark;
 use crate::cpu::cpu_stark::CpuStark;
+use crate::generation::prog::program_compress_challenge;
 use crate::memory::memory_stark::MemoryStark;
 use crate::program::prog_chunk_stark::ProgChunkStark;
 use crate::program::program_stark::ProgramStark;
+use executor::continuation::{memory_commitment, ChunkBoundary};
+
+/// Check every program the proof claims to execute is in `whitelist`, then
+/// verify it like `verify_proof`. The whitelist check is cheap, so bridges
+/// reject proofs of unexpected programs before paying for verification; the
+/// claimed hashes are still looked up in the prog chunk table by
+/// `verify_proof`. Hashes are `core::program::hash_program` values as
+/// canonical u64s.
+pub fn verify_proof_for_program<
+    F: RichField + Extendable<D>,
+    C: GenericConfig<D, F = F>,
+    const D: usize,
+>(
+    ola_stark: OlaStark<F, D>,
+    all_proof: AllProof<F, C, D>,
+    whitelist: &[[u64; 4]],
+    config: &StarkConfig,
+) -> Result<(), VerifierError>
+where
+    [(); C::Hasher::HASH_SIZE]:,
+    [(); CpuStark::<F, D>::COLUMNS]:,
+    [(); MemoryStark::<F, D>::COLUMNS]:,
+    [(); BitwiseStark::<F, D>::COLUMNS]:,
+    [(); CmpStark::<F, D>::COLUMNS]:,
+    [(); RangeCheckStark::<F, D>::COLUMNS]:,
+    [(); PoseidonStark::<F, D>::COLUMNS]:,
+    [(); PoseidonChunkStark::<F, D>::COLUMNS]:,
+    [(); StorageAccessStark::<F, D>::COLUMNS]:,
+    // [(); TapeStark::<F, D>::COLUMNS]:,
+    [(); SCCallStark::<F, D>::COLUMNS]:,
+    [(); crate::builtins::rangecheck::rangecheck_stark::RangeCheckStark::<F, D>::COLUMNS]:, [(); crate::builtins::tape::tape_stark::TapeStark::<F, D>::COLUMNS]:, [(); crate::builtins::transient::transient_stark::TransientStark::<F, D>::COLUMNS]:, //HACK-BOUNDS
+    [(); ProgramStark::<F, D>::COLUMNS]:,
+    [(); ProgChunkStark::<F, D>::COLUMNS]:,
+{
+    let program_hashes = &all_proof.public_values.program_hashes;
+    if program_hashes.is_empty() {
+        return Err(VerifierError::NoProgram);
+    }
+    if let Some(hash) = program_hashes.iter().find(|h| !whitelist.contains(h)) {
+        return Err(VerifierError::ProgramNotWhitelisted(*hash));
+    }
+    verify_proof(ola_stark, all_proof, config)
+}
 
+/// Check the proof claims the fork id `spec` sets for the block number in its
+/// public values, executes only opcodes the fork enables and was generated
+/// for a transaction of its chain, then verify it like `verify_proof`.
+///
+/// The executed opcodes are counted by the instruction rows of the cpu table
+/// and the chain id is looked up in it, so `verify_proof` fails unless they
+/// are the ones of the execution.
+pub fn verify_proof_for_chain<
+    F: RichField + Extendable<D>,
+    C: GenericConfig<D, F = F>,
+    const D: usize,
+>(
+    ola_stark: OlaStark<F, D>,
+    all_proof: AllProof<F, C, D>,
+    spec: &ChainSpec,
+    config: &StarkConfig,
+) -> Result<(), VerifierError>
+where
+    [(); C::Hasher::HASH_SIZE]:,
+    [(); CpuStark::<F, D>::COLUMNS]:,
+    [(); MemoryStark::<F, D>::COLUMNS]:,
+    [(); BitwiseStark::<F, D>::COLUMNS]:,
+    [(); CmpStark::<F, D>::COLUMNS]:,
+    [(); RangeCheckStark::<F, D>::COLUMNS]:,
+    [(); PoseidonStark::<F, D>::COLUMNS]:,
+    [(); PoseidonChunkStark::<F, D>::COLUMNS]:,
+    [(); StorageAccessStark::<F, D>::COLUMNS]:,
+    // [(); TapeStark::<F, D>::COLUMNS]:,
+    [(); SCCallStark::<F, D>::COLUMNS]:,
+    [(); crate::builtins::rangecheck::rangecheck_stark::RangeCheckStark::<F, D>::COLUMNS]:, [(); crate::builtins::tape::tape_stark::TapeStark::<F, D>::COLUMNS]:, [(); crate::builtins::transient::transient_stark::TransientStark::<F, D>::COLUMNS]:, //HACK-BOUNDS
+    [(); ProgramStark::<F, D>::COLUMNS]:,
+    [(); ProgChunkStark::<F, D>::COLUMNS]:,
+{
+    let block_number = all_proof.public_values.block_metadata.block_number;
+    let height = block_number.low_u64();
+    if U256::from(height) != block_number {
+        return Err(VerifierError::BlockNumberOverflow);
+    }
+    let fork_id = spec.fork_id(height).map(|limb| limb.to_canonical_u64());
+    if all_proof.public_values.fork_id != fork_id {
+        return Err(VerifierError::ForkIdMismatch(height));
+    }
+    for [mask, _] in &all_proof.public_values.executed_opcodes {
+        let enabled = OlaOpcode::from_bit_mask(*mask)
+            .map_or(false, |opcode| spec.opcode_enabled(opcode, height));
+        if !enabled {
+            return Err(VerifierError::OpcodeDisabled(*mask, height));
+        }
+    }
+    if all_proof.public_values.chain_id != spec.chain_id {
+        return Err(VerifierError::ChainIdMismatch(
+            all_proof.public_values.chain_id,
+        ));
+    }
+    verify_proof(ola_stark, all_proof, config)
+}
+
+/// Verify a proof of the current `PROOF_VERSION`, other versions are
+/// rejected. See `verify_proof_with_compatibility` for accepting older proofs.
+/// The proof has to be of a whole execution, starting from pc and registers
+/// zero; see `verify_chunk_proof` for chunks of one.
 pub fn verify_proof<F: RichField + Extendable<D>, C: GenericConfig<D, F = F>, const D: usize>(
     ola_stark: OlaStark<F, D>,
     all_proof: AllProof<F, C, D>,
     config: &StarkConfig,
-) -> Result<()>
+) -> Result<(), VerifierError>
 where
     [(); C::Hasher::HASH_SIZE]:,
     [(); CpuStark::<F, D>::COLUMNS]:,
@@ -49,171 +166,549 @@ where
     [(); StorageAccessStark::<F, D>::COLUMNS]:,
     // [(); TapeStark::<F, D>::COLUMNS]:,
     [(); SCCallStark::<F, D>::COLUMNS]:,
+    [(); crate::builtins::rangecheck::rangecheck_stark::RangeCheckStark::<F, D>::COLUMNS]:, [(); crate::builtins::tape::tape_stark::TapeStark::<F, D>::COLUMNS]:, [(); crate::builtins::transient::transient_stark::TransientStark::<F, D>::COLUMNS]:, //HACK-BOUNDS
     [(); ProgramStark::<F, D>::COLUMNS]:,
     [(); ProgChunkStark::<F, D>::COLUMNS]:,
 {
-    let AllProofChallenges {
-        stark_challenges,
-        ctl_challenges,
-    } = all_proof.get_challenges(&ola_stark, config);
+    check_start_state(&all_proof.public_values, 0, [0; REGISTER_NUM])?;
+    verify_current_proof(ola_stark, all_proof, config)
+}
 
-    let nums_permutation_zs = ola_stark.nums_permutation_zs(config);
+/// Verify the proof of a chunk `execute_in_chunks` split an execution into
+/// like `verify_proof`, but starting from the pc, registers, memory and
+/// storage root of `start`, the end boundary of the chunk before. See
+/// `check_chunk_link` for chaining the proofs of the following chunks.
+pub fn verify_chunk_proof<
+    F: RichField + Extendable<D>,
+    C: GenericConfig<D, F = F>,
+    const D: usize,
+>(
+    ola_stark: OlaStark<F, D>,
+    all_proof: AllProof<F, C, D>,
+    start: &ChunkBoundary,
+    config: &StarkConfig,
+) -> Result<(), VerifierError>
+where
+    [(); C::Hasher::HASH_SIZE]:,
+    [(); CpuStark::<F, D>::COLUMNS]:,
+    [(); MemoryStark::<F, D>::COLUMNS]:,
+    [(); BitwiseStark::<F, D>::COLUMNS]:,
+    [(); CmpStark::<F, D>::COLUMNS]:,
+    [(); RangeCheckStark::<F, D>::COLUMNS]:,
+    [(); PoseidonStark::<F, D>::COLUMNS]:,
+    [(); PoseidonChunkStark::<F, D>::COLUMNS]:,
+    [(); StorageAccessStark::<F, D>::COLUMNS]:,
+    // [(); TapeStark::<F, D>::COLUMNS]:,
+    [(); SCCallStark::<F, D>::COLUMNS]:,
+    [(); crate::builtins::rangecheck::rangecheck_stark::RangeCheckStark::<F, D>::COLUMNS]:, [(); crate::builtins::tape::tape_stark::TapeStark::<F, D>::COLUMNS]:, [(); crate::builtins::transient::transient_stark::TransientStark::<F, D>::COLUMNS]:, //HACK-BOUNDS
+    [(); ProgramStark::<F, D>::COLUMNS]:,
+    [(); ProgChunkStark::<F, D>::COLUMNS]:,
+{
+    let registers = start.registers.map(|reg| reg.to_canonical_u64());
+    check_start_state(&all_proof.public_values, start.pc, registers)?;
+    let storage_root = H256(tree_value_to_h256(&start.storage_root).0);
+    if all_proof.public_values.trie_roots_before.state_root != storage_root {
+        return Err(VerifierError::StartStateMismatch);
+    }
+    let cells = start_memory(&all_proof.public_values);
+    let image: BTreeMap<u64, GoldilocksField> = cells
+        .iter()
+        .map(|[addr, value]| (*addr, GoldilocksField::from_canonical_u64(*value)))
+        .collect();
+    if image.len() != cells.len() || memory_commitment(&image) != start.memory_commitment {
+        return Err(VerifierError::StartStateMismatch);
+    }
+    verify_current_proof(ola_stark, all_proof, config)
+}
 
-    let OlaStark {
-        cpu_stark,
-        memory_stark,
-        mut bitwise_stark,
-        cmp_stark,
-        rangecheck_stark,
-        poseidon_stark,
-        poseidon_chunk_stark,
-        storage_access_stark,
-        tape_stark,
-        sccall_stark,
-        mut program_stark,
-        prog_chunk_stark,
-        cross_table_lookups,
-    } = ola_stark;
-
-    if bitwise_stark.get_compress_challenge().is_none() {
-        bitwise_stark
-            .set_compress_challenge(all_proof.compress_challenges[Table::Bitwise as usize])
-            .unwrap();
+/// Check the chunk proof with the public values `next` continues the one with
+/// `prev`: `prev` stopped, and its end pc, registers, memory and storage root
+/// are the ones `next` starts from. Both proofs still have to be verified, the
+/// first chunk with `verify_chunk_proof` against the start of the execution.
+pub fn check_chunk_link(prev: &PublicValues, next: &PublicValues) -> Result<(), VerifierError> {
+    let mut end_memory = prev.end_memory_image.clone();
+    end_memory.sort();
+    if !prev.stopped
+        || end_memory != start_memory(next)
+        || prev.trie_roots_after.state_root != next.trie_roots_before.state_root
+    {
+        return Err(VerifierError::StartStateMismatch);
     }
-    if program_stark.get_compress_challenge().is_none() {
-        program_stark
-            .set_compress_challenge(all_proof.compress_challenges[Table::Program as usize])
-            .unwrap();
+    check_start_state(next, prev.end_pc, prev.end_registers)
+}
+
+/// `(addr, value)` of every cell a chunk proof starts with, its memory image
+/// and code region, by address.
+fn start_memory(public_values: &PublicValues) -> Vec<[u64; 2]> {
+    let mut cells: Vec<[u64; 2]> = public_values
+        .memory_image
+        .iter()
+        .chain(&public_values.code_region)
+        .copied()
+        .collect();
+    cells.sort();
+    cells
+}
+
+/// The first row of the cpu table is looked up against the start state of
+/// the public values, check it is the one the caller expects.
+fn check_start_state(
+    public_values: &PublicValues,
+    pc: u64,
+    registers: [u64; REGISTER_NUM],
+) -> Result<(), VerifierError> {
+    if public_values.start_pc != pc || public_values.start_registers != registers {
+        return Err(VerifierError::StartStateMismatch);
     }
+    Ok(())
+}
 
+fn verify_current_proof<
+    F: RichField + Extendable<D>,
+    C: GenericConfig<D, F = F>,
+    const D: usize,
+>(
+    ola_stark: OlaStark<F, D>,
+    all_proof: AllProof<F, C, D>,
+    config: &StarkConfig,
+) -> Result<(), VerifierError>
+where
+    [(); C::Hasher::HASH_SIZE]:,
+    [(); CpuStark::<F, D>::COLUMNS]:,
+    [(); MemoryStark::<F, D>::COLUMNS]:,
+    [(); BitwiseStark::<F, D>::COLUMNS]:,
+    [(); CmpStark::<F, D>::COLUMNS]:,
+    [(); RangeCheckStark::<F, D>::COLUMNS]:,
+    [(); PoseidonStark::<F, D>::COLUMNS]:,
+    [(); PoseidonChunkStark::<F, D>::COLUMNS]:,
+    [(); StorageAccessStark::<F, D>::COLUMNS]:,
+    // [(); TapeStark::<F, D>::COLUMNS]:,
+    [(); SCCallStark::<F, D>::COLUMNS]:,
+    [(); crate::builtins::rangecheck::rangecheck_stark::RangeCheckStark::<F, D>::COLUMNS]:, [(); crate::builtins::tape::tape_stark::TapeStark::<F, D>::COLUMNS]:, [(); crate::builtins::transient::transient_stark::TransientStark::<F, D>::COLUMNS]:, //HACK-BOUNDS
+    [(); ProgramStark::<F, D>::COLUMNS]:,
+    [(); ProgChunkStark::<F, D>::COLUMNS]:,
+{
+    if all_proof.version != ProofVersion::default() {
+        return Err(VerifierError::UnsupportedProofVersion(all_proof.version.0));
+    }
+    verify_versioned_proof(ola_stark, all_proof, config)
+}
+
+/// Configs previous proof versions were generated with, so proofs in flight
+/// during a rolling upgrade of the provers still verify.
+#[cfg(feature = "proof_compat")]
+#[derive(Debug, Clone, Default)]
+pub struct ProofCompatibility {
+    configs: HashMap<ProofVersion, StarkConfig>,
+}
+
+#[cfg(feature = "proof_compat")]
+impl ProofCompatibility {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// Accept proofs of `version` and check their shape against `config`.
+    pub fn with_version(mut self, version: ProofVersion, config: StarkConfig) -> Self {
+        self.configs.insert(version, config);
+        self
+    }
+
+    pub fn config_for(&self, version: ProofVersion) -> Option<&StarkConfig> {
+        self.configs.get(&version)
+    }
+}
+
+/// Like `verify_proof`, but also accepts versions listed in `compat`. Current
+/// version proofs are verified against `config`.
+#[cfg(feature = "proof_compat")]
+pub fn verify_proof_with_compatibility<
+    F: RichField + Extendable<D>,
+    C: GenericConfig<D, F = F>,
+    const D: usize,
+>(
+    ola_stark: OlaStark<F, D>,
+    all_proof: AllProof<F, C, D>,
+    config: &StarkConfig,
+    compat: &ProofCompatibility,
+) -> Result<(), VerifierError>
+where
+    [(); C::Hasher::HASH_SIZE]:,
+    [(); CpuStark::<F, D>::COLUMNS]:,
+    [(); MemoryStark::<F, D>::COLUMNS]:,
+    [(); BitwiseStark::<F, D>::COLUMNS]:,
+    [(); CmpStark::<F, D>::COLUMNS]:,
+    [(); RangeCheckStark::<F, D>::COLUMNS]:,
+    [(); PoseidonStark::<F, D>::COLUMNS]:,
+    [(); PoseidonChunkStark::<F, D>::COLUMNS]:,
+    [(); StorageAccessStark::<F, D>::COLUMNS]:,
+    // [(); TapeStark::<F, D>::COLUMNS]:,
+    [(); SCCallStark::<F, D>::COLUMNS]:,
+    [(); crate::builtins::rangecheck::rangecheck_stark::RangeCheckStark::<F, D>::COLUMNS]:, [(); crate::builtins::tape::tape_stark::TapeStark::<F, D>::COLUMNS]:, [(); crate::builtins::transient::transient_stark::TransientStark::<F, D>::COLUMNS]:, //HACK-BOUNDS
+    [(); ProgramStark::<F, D>::COLUMNS]:,
+    [(); ProgChunkStark::<F, D>::COLUMNS]:,
+{
+    let config = if all_proof.version == ProofVersion::default() {
+        config
+    } else {
+        compat
+            .config_for(all_proof.version)
+            .ok_or(VerifierError::UnsupportedProofVersion(all_proof.version.0))?
+    };
+    check_start_state(&all_proof.public_values, 0, [0; REGISTER_NUM])?;
+    verify_versioned_proof(ola_stark, all_proof, config)
+}
+
+/// Verify `all_proof` under the rules of its own version, `config` has to be
+/// the config that version was proven with.
+fn verify_versioned_proof<
+    F: RichField + Extendable<D>,
+    C: GenericConfig<D, F = F>,
+    const D: usize,
+>(
+    ola_stark: OlaStark<F, D>,
+    all_proof: AllProof<F, C, D>,
+    config: &StarkConfig,
+) -> Result<(), VerifierError>
+where
+    [(); C::Hasher::HASH_SIZE]:,
+    [(); CpuStark::<F, D>::COLUMNS]:,
+    [(); MemoryStark::<F, D>::COLUMNS]:,
+    [(); BitwiseStark::<F, D>::COLUMNS]:,
+    [(); CmpStark::<F, D>::COLUMNS]:,
+    [(); RangeCheckStark::<F, D>::COLUMNS]:,
+    [(); PoseidonStark::<F, D>::COLUMNS]:,
+    [(); PoseidonChunkStark::<F, D>::COLUMNS]:,
+    [(); StorageAccessStark::<F, D>::COLUMNS]:,
+    // [(); TapeStark::<F, D>::COLUMNS]:,
+    [(); SCCallStark::<F, D>::COLUMNS]:,
+    [(); crate::builtins::rangecheck::rangecheck_stark::RangeCheckStark::<F, D>::COLUMNS]:, [(); crate::builtins::tape::tape_stark::TapeStark::<F, D>::COLUMNS]:, [(); crate::builtins::transient::transient_stark::TransientStark::<F, D>::COLUMNS]:, //HACK-BOUNDS
+    [(); ProgramStark::<F, D>::COLUMNS]:,
+    [(); ProgChunkStark::<F, D>::COLUMNS]:,
+{
+    let (ola_stark, challenges) = prepare_proof(ola_stark, &all_proof, config)?;
+    let nums_permutation_zs = ola_stark.nums_permutation_zs(config);
     let ctl_vars_per_table = CtlCheckVars::from_proofs(
         &all_proof.stark_proofs,
-        &cross_table_lookups,
-        &ctl_challenges,
+        &ola_stark.cross_table_lookups,
+        &challenges.ctl_challenges,
         &nums_permutation_zs,
     );
 
-    verify_stark_proof_with_challenges(
-        cpu_stark,
-        &all_proof.stark_proofs[Table::Cpu as usize],
-        &stark_challenges[Table::Cpu as usize],
-        &ctl_vars_per_table[Table::Cpu as usize],
-        config,
-    )?;
-    verify_stark_proof_with_challenges(
-        memory_stark,
-        &all_proof.stark_proofs[Table::Memory as usize],
-        &stark_challenges[Table::Memory as usize],
-        &ctl_vars_per_table[Table::Memory as usize],
-        config,
-    )?;
-    verify_stark_proof_with_challenges(
-        bitwise_stark,
-        &all_proof.stark_proofs[Table::Bitwise as usize],
-        &stark_challenges[Table::Bitwise as usize],
-        &ctl_vars_per_table[Table::Bitwise as usize],
-        config,
-    )?;
+    for table in ALL_TABLES {
+        verify_table_proof(
+            &ola_stark,
+            table,
+            &all_proof.stark_proofs[table as usize],
+            &challenges.stark_challenges[table as usize],
+            &ctl_vars_per_table[table as usize],
+            config,
+        )?;
+    }
 
-    verify_stark_proof_with_challenges(
-        cmp_stark,
-        &all_proof.stark_proofs[Table::Cmp as usize],
-        &stark_challenges[Table::Cmp as usize],
-        &ctl_vars_per_table[Table::Cmp as usize],
-        config,
-    )?;
+    verify_proof_ctls(&ola_stark, &all_proof, &challenges.ctl_challenges, config)
+}
 
-    verify_stark_proof_with_challenges(
-        rangecheck_stark,
-        &all_proof.stark_proofs[Table::RangeCheck as usize],
-        &stark_challenges[Table::RangeCheck as usize],
-        &ctl_vars_per_table[Table::RangeCheck as usize],
-        config,
-    )?;
+/// Verify several proofs of the current `PROOF_VERSION`, e.g. the blocks a
+/// syncing node catches up on, in parallel. Nothing is amortized: each proof
+/// replays its own transcript and checks its own FRI queries and Merkle
+/// paths, the table proofs of all proofs are just spread over one thread
+/// pool instead of verified proof after proof. Sets with a proof of another
+/// version fall back to `verify_proof` for each proof. Errors carry the index
+/// of the failing proof.
+pub fn verify_proofs_parallel<
+    F: RichField + Extendable<D>,
+    C: GenericConfig<D, F = F>,
+    const D: usize,
+>(
+    ola_stark: OlaStark<F, D>,
+    proofs: &[AllProof<F, C, D>],
+    config: &StarkConfig,
+) -> Result<(), VerifierError>
+where
+    [(); C::Hasher::HASH_SIZE]:,
+    [(); CpuStark::<F, D>::COLUMNS]:,
+    [(); MemoryStark::<F, D>::COLUMNS]:,
+    [(); BitwiseStark::<F, D>::COLUMNS]:,
+    [(); CmpStark::<F, D>::COLUMNS]:,
+    [(); RangeCheckStark::<F, D>::COLUMNS]:,
+    [(); PoseidonStark::<F, D>::COLUMNS]:,
+    [(); PoseidonChunkStark::<F, D>::COLUMNS]:,
+    [(); StorageAccessStark::<F, D>::COLUMNS]:,
+    // [(); TapeStark::<F, D>::COLUMNS]:,
+    [(); SCCallStark::<F, D>::COLUMNS]:,
+    [(); crate::builtins::rangecheck::rangecheck_stark::RangeCheckStark::<F, D>::COLUMNS]:, [(); crate::builtins::tape::tape_stark::TapeStark::<F, D>::COLUMNS]:, [(); crate::builtins::transient::transient_stark::TransientStark::<F, D>::COLUMNS]:, //HACK-BOUNDS
+    [(); ProgramStark::<F, D>::COLUMNS]:,
+    [(); ProgChunkStark::<F, D>::COLUMNS]:,
+{
+    let in_batch = |i: usize| move |e: VerifierError| VerifierError::InBatch(i, Box::new(e));
+    if proofs.iter().any(|p| p.version != ProofVersion::default()) {
+        for (i, proof) in proofs.iter().enumerate() {
+            verify_proof(ola_stark.clone(), proof.clone(), config).map_err(in_batch(i))?;
+        }
+        return Ok(());
+    }
 
-    verify_stark_proof_with_challenges(
-        poseidon_stark,
-        &all_proof.stark_proofs[Table::Poseidon as usize],
-        &stark_challenges[Table::Poseidon as usize],
-        &ctl_vars_per_table[Table::Poseidon as usize],
-        config,
-    )?;
+    let prepared = proofs
+        .iter()
+        .enumerate()
+        .map(|(i, proof)| {
+            check_start_state(&proof.public_values, 0, [0; REGISTER_NUM])
+                .and_then(|_| prepare_proof(ola_stark.clone(), proof, config))
+                .map_err(in_batch(i))
+        })
+        .collect::<Result<Vec<_>, _>>()?;
+    let nums_permutation_zs = ola_stark.nums_permutation_zs(config);
+    let ctl_vars = proofs
+        .iter()
+        .zip(&prepared)
+        .map(|(proof, (ola_stark, challenges))| {
+            CtlCheckVars::from_proofs(
+                &proof.stark_proofs,
+                &ola_stark.cross_table_lookups,
+                &challenges.ctl_challenges,
+                &nums_permutation_zs,
+            )
+        })
+        .collect::<Vec<_>>();
+
+    let table_proofs = (0..proofs.len())
+        .flat_map(|i| ALL_TABLES.map(|table| (i, table)))
+        .collect::<Vec<_>>();
+    table_proofs.par_iter().try_for_each(|&(i, table)| {
+        let (ola_stark, challenges) = &prepared[i];
+        verify_table_proof(
+            ola_stark,
+            table,
+            &proofs[i].stark_proofs[table as usize],
+            &challenges.stark_challenges[table as usize],
+            &ctl_vars[i][table as usize],
+            config,
+        )
+        .map_err(in_batch(i))
+    })?;
 
-    verify_stark_proof_with_challenges(
-        poseidon_chunk_stark,
-        &all_proof.stark_proofs[Table::PoseidonChunk as usize],
-        &stark_challenges[Table::PoseidonChunk as usize],
-        &ctl_vars_per_table[Table::PoseidonChunk as usize],
-        config,
-    )?;
+    for (i, (proof, (ola_stark, challenges))) in proofs.iter().zip(&prepared).enumerate() {
+        verify_proof_ctls(ola_stark, proof, &challenges.ctl_challenges, config)
+            .map_err(in_batch(i))?;
+    }
+    Ok(())
+}
 
-    verify_stark_proof_with_challenges(
-        storage_access_stark,
-        &all_proof.stark_proofs[Table::StorageAccess as usize],
-        &stark_challenges[Table::StorageAccess as usize],
-        &ctl_vars_per_table[Table::StorageAccess as usize],
-        config,
-    )?;
+/// Check the compress challenges of `all_proof`, replay its transcript and
+/// hand the compress challenges to the starks that use them.
+fn prepare_proof<F: RichField + Extendable<D>, C: GenericConfig<D, F = F>, const D: usize>(
+    mut ola_stark: OlaStark<F, D>,
+    all_proof: &AllProof<F, C, D>,
+    config: &StarkConfig,
+) -> Result<(OlaStark<F, D>, AllProofChallenges<F, D>), VerifierError>
+where
+    [(); C::Hasher::HASH_SIZE]:,
+{
+    if all_proof.version.carries_layout() {
+        let layout = layout_fingerprints();
+        if let Some(table) = ALL_TABLES
+            .into_iter()
+            .find(|table| all_proof.layout[*table as usize] != layout[*table as usize])
+        {
+            return Err(VerifierError::LayoutMismatch(table));
+        }
+    }
+    if all_proof.version.binds_public_values() {
+        verify_program_compress_challenge(
+            &all_proof.public_values,
+            all_proof.compress_challenges[Table::Program as usize],
+        )?;
+    }
 
-    verify_stark_proof_with_challenges(
-        tape_stark,
-        &all_proof.stark_proofs[Table::Tape as usize],
-        &stark_challenges[Table::Tape as usize],
-        &ctl_vars_per_table[Table::Tape as usize],
-        config,
-    )?;
+    let challenges = all_proof.get_challenges(&ola_stark, config);
 
-    verify_stark_proof_with_challenges(
-        sccall_stark,
-        &all_proof.stark_proofs[Table::SCCall as usize],
-        &stark_challenges[Table::SCCall as usize],
-        &ctl_vars_per_table[Table::SCCall as usize],
-        config,
-    )?;
+    if ola_stark.bitwise_stark.get_compress_challenge().is_none() {
+        ola_stark
+            .bitwise_stark
+            .set_compress_challenge(all_proof.compress_challenges[Table::Bitwise as usize])
+            .unwrap();
+    }
+    if ola_stark.program_stark.get_compress_challenge().is_none() {
+        ola_stark
+            .program_stark
+            .set_compress_challenge(all_proof.compress_challenges[Table::Program as usize])
+            .unwrap();
+    }
+    Ok((ola_stark, challenges))
+}
 
-    verify_stark_proof_with_challenges(
-        program_stark,
-        &all_proof.stark_proofs[Table::Program as usize],
-        &stark_challenges[Table::Program as usize],
-        &ctl_vars_per_table[Table::Program as usize],
-        config,
-    )?;
+fn verify_table_proof<F: RichField + Extendable<D>, C: GenericConfig<D, F = F>, const D: usize>(
+    ola_stark: &OlaStark<F, D>,
+    table: Table,
+    proof: &StarkProof<F, C, D>,
+    challenges: &StarkProofChallenges<F, D>,
+    ctl_vars: &[CtlCheckVars<F, F::Extension, F::Extension, D>],
+    config: &StarkConfig,
+) -> Result<(), VerifierError>
+where
+    [(); C::Hasher::HASH_SIZE]:,
+    [(); CpuStark::<F, D>::COLUMNS]:,
+    [(); MemoryStark::<F, D>::COLUMNS]:,
+    [(); BitwiseStark::<F, D>::COLUMNS]:,
+    [(); CmpStark::<F, D>::COLUMNS]:,
+    [(); RangeCheckStark::<F, D>::COLUMNS]:,
+    [(); PoseidonStark::<F, D>::COLUMNS]:,
+    [(); PoseidonChunkStark::<F, D>::COLUMNS]:,
+    [(); StorageAccessStark::<F, D>::COLUMNS]:,
+    // [(); TapeStark::<F, D>::COLUMNS]:,
+    [(); SCCallStark::<F, D>::COLUMNS]:,
+    [(); crate::builtins::rangecheck::rangecheck_stark::RangeCheckStark::<F, D>::COLUMNS]:, [(); crate::builtins::tape::tape_stark::TapeStark::<F, D>::COLUMNS]:, [(); crate::builtins::transient::transient_stark::TransientStark::<F, D>::COLUMNS]:, //HACK-BOUNDS
+    [(); ProgramStark::<F, D>::COLUMNS]:,
+    [(); ProgChunkStark::<F, D>::COLUMNS]:,
+{
+    let config = &config.for_table(table);
+    match table {
+        Table::Cpu => verify_stark_proof_with_challenges(
+            ola_stark.cpu_stark.clone(),
+            table,
+            proof,
+            challenges,
+            ctl_vars,
+            config,
+        ),
+        Table::Memory => verify_stark_proof_with_challenges(
+            ola_stark.memory_stark.clone(),
+            table,
+            proof,
+            challenges,
+            ctl_vars,
+            config,
+        ),
+        Table::Bitwise => verify_stark_proof_with_challenges(
+            ola_stark.bitwise_stark.clone(),
+            table,
+            proof,
+            challenges,
+            ctl_vars,
+            config,
+        ),
+        Table::Cmp => verify_stark_proof_with_challenges(
+            ola_stark.cmp_stark.clone(),
+            table,
+            proof,
+            challenges,
+            ctl_vars,
+            config,
+        ),
+        Table::RangeCheck => verify_stark_proof_with_challenges(
+            ola_stark.rangecheck_stark.clone(),
+            table,
+            proof,
+            challenges,
+            ctl_vars,
+            config,
+        ),
+        Table::Poseidon => verify_stark_proof_with_challenges(
+            ola_stark.poseidon_stark.clone(),
+            table,
+            proof,
+            challenges,
+            ctl_vars,
+            config,
+        ),
+        Table::PoseidonChunk => verify_stark_proof_with_challenges(
+            ola_stark.poseidon_chunk_stark.clone(),
+            table,
+            proof,
+            challenges,
+            ctl_vars,
+            config,
+        ),
+        Table::StorageAccess => verify_stark_proof_with_challenges(
+            ola_stark.storage_access_stark.clone(),
+            table,
+            proof,
+            challenges,
+            ctl_vars,
+            config,
+        ),
+        Table::Tape => verify_stark_proof_with_challenges(
+            ola_stark.tape_stark.clone(),
+            table,
+            proof,
+            challenges,
+            ctl_vars,
+            config,
+        ),
+        Table::SCCall => verify_stark_proof_with_challenges(
+            ola_stark.sccall_stark.clone(),
+            table,
+            proof,
+            challenges,
+            ctl_vars,
+            config,
+        ),
+        Table::Program => verify_stark_proof_with_challenges(
+            ola_stark.program_stark.clone(),
+            table,
+            proof,
+            challenges,
+            ctl_vars,
+            config,
+        ),
+        Table::ProgChunk => verify_stark_proof_with_challenges(
+            ola_stark.prog_chunk_stark.clone(),
+            table,
+            proof,
+            challenges,
+            ctl_vars,
+            config,
+        ),
+        Table::Transient => verify_stark_proof_with_challenges(
+            ola_stark.transient_stark.clone(),
+            table,
+            proof,
+            challenges,
+            ctl_vars,
+            config,
+        ),
+    }
+}
 
-    verify_stark_proof_with_challenges(
-        prog_chunk_stark,
-        &all_proof.stark_proofs[Table::ProgChunk as usize],
-        &stark_challenges[Table::ProgChunk as usize],
-        &ctl_vars_per_table[Table::ProgChunk as usize],
-        config,
-    )?;
-
-    // TODO:
-    // let public_values = all_proof.public_values;
-    let extra_looking_products = vec![vec![F::ONE; config.num_challenges]; NUM_TABLES];
-    // extra_looking_products.push(Vec::new());
-    // for c in 0..config.num_challenges {
-    //     extra_looking_products[Table::StorageAccess as usize].push(
-    //         get_storagehash_extra_looking_products(&public_values,
-    // ctl_challenges.challenges[c]),     );
-    // }
+fn verify_proof_ctls<F: RichField + Extendable<D>, C: GenericConfig<D, F = F>, const D: usize>(
+    ola_stark: &OlaStark<F, D>,
+    all_proof: &AllProof<F, C, D>,
+    ctl_challenges: &GrandProductChallengeSet<F>,
+    config: &StarkConfig,
+) -> Result<(), VerifierError> {
+    // the public values stand in for the looking tables of public lookups.
+    let extra_looking_products = ola_stark
+        .cross_table_lookups
+        .iter()
+        .map(|ctl| ctl.extra_looking_products(&all_proof.public_values, ctl_challenges))
+        .collect();
 
     verify_cross_table_lookups::<F, C, D>(
-        cross_table_lookups,
-        all_proof.stark_proofs.map(|p| p.openings.ctl_zs_last),
+        ola_stark.cross_table_lookups.clone(),
+        std::array::from_fn(|i| all_proof.stark_proofs[i].openings.ctl_zs_last.clone()),
         extra_looking_products,
         config,
     )
 }
 
-pub(crate) fn get_storagehash_extra_looking_products<F, const D: usize>(
-    _public_values: &PublicValues,
-    _challenge: GrandProductChallenge<F>,
-) -> F
-where
-    F: RichField + Extendable<D>,
-{
-    let prod = F::ONE;
-    prod
+/// The program table compresses its rows with a challenge derived from the
+/// state roots, recompute it from the roots the proof claims.
+fn verify_program_compress_challenge<F: RichField>(
+    public_values: &PublicValues,
+    challenge: F,
+) -> Result<(), VerifierError> {
+    let root_limbs = |root: &H256| {
+        fields_from_bytes::<GoldilocksField>(&root.0)
+            .ok()
+            .and_then(|limbs| <[GoldilocksField; 4]>::try_from(limbs).ok())
+    };
+    let roots = root_limbs(&public_values.trie_roots_before.state_root)
+        .zip(root_limbs(&public_values.trie_roots_after.state_root));
+    match roots {
+        Some(roots) if program_compress_challenge::<F>(roots) == challenge => Ok(()),
+        _ => Err(VerifierError::CompressChallengeMismatch(Table::Program)),
+    }
 }
 
 pub(crate) fn verify_stark_proof_with_challenges<
@@ -223,16 +718,17 @@ pub(crate) fn verify_stark_proof_with_challenges<
     const D: usize,
 >(
     stark: S,
+    table: Table,
     proof: &StarkProof<F, C, D>,
     challenges: &StarkProofChallenges<F, D>,
     ctl_vars: &[CtlCheckVars<F, F::Extension, F::Extension, D>],
     config: &StarkConfig,
-) -> Result<()>
+) -> Result<(), VerifierError>
 where
     [(); S::COLUMNS]:,
     [(); C::Hasher::HASH_SIZE]:,
 {
-    validate_proof_shape(&stark, proof, config, ctl_vars.len())?;
+    validate_proof_shape(&stark, table, proof, config, ctl_vars.len())?;
     let StarkOpeningSet {
         local_values,
         next_values,
@@ -291,11 +787,9 @@ where
         .chunks(stark.quotient_degree_factor())
         .enumerate()
     {
-        ensure!(
-            vanishing_polys_zeta[i] == z_h_zeta * reduce_with_powers(chunk, zeta_pow_deg),
-            "Mismatch between evaluation and opening of quotient polynomial in {}",
-            type_name::<S>()
-        );
+        if vanishing_polys_zeta[i] != z_h_zeta * reduce_with_powers(chunk, zeta_pow_deg) {
+            return Err(VerifierError::QuotientMismatch(table, i));
+        }
     }
 
     let merkle_caps = vec![
@@ -317,17 +811,19 @@ where
         &merkle_caps,
         &proof.opening_proof,
         &config.fri_params(degree_bits),
-    )?;
+    )
+    .map_err(|e| VerifierError::FriFailure(table, e.to_string()))?;
 
     Ok(())
 }
 
 fn validate_proof_shape<F, C, S, const D: usize>(
     stark: &S,
+    table: Table,
     proof: &StarkProof<F, C, D>,
     config: &StarkConfig,
     num_ctl_zs: usize,
-) -> anyhow::Result<()>
+) -> Result<(), VerifierError>
 where
     F: RichField + Extendable<D>,
     C: GenericConfig<D, F = F>,
@@ -359,16 +855,32 @@ where
     let cap_height = fri_params.config.cap_height;
     let num_zs = num_ctl_zs + stark.num_permutation_batches(config);
 
-    ensure!(trace_cap.height() == cap_height);
-    ensure!(permutation_ctl_zs_cap.height() == cap_height);
-    ensure!(quotient_polys_cap.height() == cap_height);
-
-    ensure!(local_values.len() == S::COLUMNS);
-    ensure!(next_values.len() == S::COLUMNS);
-    ensure!(permutation_ctl_zs.len() == num_zs);
-    ensure!(permutation_ctl_zs_next.len() == num_zs);
-    ensure!(ctl_zs_last.len() == num_ctl_zs);
-    ensure!(quotient_polys.len() == stark.num_quotient_polys(config));
+    let checks = [
+        (trace_cap.height() == cap_height, "trace cap height"),
+        (
+            permutation_ctl_zs_cap.height() == cap_height,
+            "permutation ctl zs cap height",
+        ),
+        (
+            quotient_polys_cap.height() == cap_height,
+            "quotient polys cap height",
+        ),
+        (local_values.len() == S::COLUMNS, "local values len"),
+        (next_values.len() == S::COLUMNS, "next values len"),
+        (permutation_ctl_zs.len() == num_zs, "permutation ctl zs len"),
+        (
+            permutation_ctl_zs_next.len() == num_zs,
+            "permutation ctl zs next len",
+        ),
+        (ctl_zs_last.len() == num_ctl_zs, "ctl zs last len"),
+        (
+            quotient_polys.len() == stark.num_quotient_polys(config),
+            "quotient polys len",
+        ),
+    ];
+    if let Some((_, what)) = checks.iter().find(|(ok, _)| !ok) {
+        return Err(VerifierError::ShapeMismatch(table, *what));
+    }
 
     Ok(())
 }
@@ -0,0 +1,470 @@
+//! The fixed parameters proofs are checked against, for verifiers built
+//! outside this crate, e.g. on hardware wallets or in enclaves: the FRI
+//! config, the range of table degrees, the tables and the cross-table
+//! lookups. `to_rust` renders them as a module of constants that only needs
+//! `core`, so it builds in `no_std` crates, and `to_c_header` as a C header.
+//! Both pin the proof version they were generated for.
+
+use std::fmt::Write;
+
+use core::vm::chain_spec::ChainSpec;
+use plonky2::field::extension::Extendable;
+use plonky2::fri::reduction_strategies::FriReductionStrategy;
+use plonky2::hash::hash_types::RichField;
+
+use super::config::{StarkConfig, TranscriptHasher};
+use super::cross_table_lookup::{Column, TableWithColumns};
+use super::error::ConfigError;
+use super::layout::{layout_fingerprint, table_columns};
+use super::ola_stark::{OlaStark, Table, ALL_TABLES};
+use super::proof::PROOF_VERSION;
+
+/// Tables are padded to at least two rows.
+pub const MIN_DEGREE_BITS: usize = 1;
+
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct TableConstants {
+    pub table: Table,
+    pub width: usize,
+    pub layout_fingerprint: u64,
+    pub quotient_degree_factor: usize,
+    /// FRI parameters of the table, overrides applied.
+    pub cap_height: usize,
+    pub num_query_rounds: usize,
+    pub proof_of_work_bits: u32,
+}
+
+/// `(column, coefficient)` terms plus a constant.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct LinearCombination {
+    pub terms: Vec<(usize, u64)>,
+    pub constant: u64,
+}
+
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct CtlSideConstants {
+    pub table: Table,
+    pub columns: Vec<LinearCombination>,
+    /// `None` if every row takes part.
+    pub filter: Option<LinearCombination>,
+}
+
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct CtlConstants {
+    pub name: String,
+    pub looking: Vec<CtlSideConstants>,
+    pub looked: CtlSideConstants,
+}
+
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct VerifierConstants {
+    pub proof_version: u32,
+    pub chain_id: u64,
+    pub security_bits: usize,
+    pub num_challenges: usize,
+    pub rate_bits: usize,
+    /// Every FRI reduction folds by `2^reduction_arity_bits` until the
+    /// polynomial has at most `2^final_poly_bits` coefficients.
+    pub reduction_arity_bits: usize,
+    pub final_poly_bits: usize,
+    pub transcript_hasher: TranscriptHasher,
+    /// Degree bits a table of a proof may have, `max_degree_bits` from the
+    /// row capacity of a block.
+    pub min_degree_bits: usize,
+    pub max_degree_bits: usize,
+    pub tables: Vec<TableConstants>,
+    pub ctls: Vec<CtlConstants>,
+}
+
+fn linear_combination<F: RichField>(column: &Column<F>) -> LinearCombination {
+    let (terms, constant) = column.terms();
+    LinearCombination { terms, constant }
+}
+
+fn ctl_side<F: RichField>(side: &TableWithColumns<F>) -> CtlSideConstants {
+    CtlSideConstants {
+        table: side.table(),
+        columns: side.columns().iter().map(linear_combination).collect(),
+        filter: side.filter_column().map(linear_combination),
+    }
+}
+
+impl VerifierConstants {
+    /// Constants of proofs of `ola_stark` under `config`, for blocks of
+    /// `spec`.
+    pub fn new<F: RichField + Extendable<D>, const D: usize>(
+        ola_stark: &OlaStark<F, D>,
+        config: &StarkConfig,
+        spec: &ChainSpec,
+    ) -> Result<Self, ConfigError> {
+        let (reduction_arity_bits, final_poly_bits) = match &config.fri_config.reduction_strategy {
+            FriReductionStrategy::ConstantArityBits(arity_bits, final_poly_bits) => {
+                (*arity_bits, *final_poly_bits)
+            }
+            strategy => return Err(ConfigError::UnpinnedReduction(format!("{:?}", strategy))),
+        };
+        let quotient_degree_factors = ola_stark.quotient_degree_factors();
+        let tables = ALL_TABLES
+            .iter()
+            .map(|&table| {
+                let fri_config = config.for_table(table).fri_config;
+                TableConstants {
+                    table,
+                    width: table_columns(table).0,
+                    layout_fingerprint: layout_fingerprint(table),
+                    quotient_degree_factor: quotient_degree_factors[table as usize],
+                    cap_height: fri_config.cap_height,
+                    num_query_rounds: fri_config.num_query_rounds,
+                    proof_of_work_bits: fri_config.proof_of_work_bits,
+                }
+            })
+            .collect();
+        let ctls = ola_stark
+            .cross_table_lookups
+            .iter()
+            .map(|ctl| CtlConstants {
+                name: ctl.name().to_string(),
+                looking: ctl.looking_tables().iter().map(ctl_side).collect(),
+                looked: ctl_side(ctl.looked_table()),
+            })
+            .collect();
+        Ok(Self {
+            proof_version: PROOF_VERSION,
+            chain_id: spec.chain_id,
+            security_bits: config.security_bits,
+            num_challenges: config.num_challenges,
+            rate_bits: config.fri_config.rate_bits,
+            reduction_arity_bits,
+            final_poly_bits,
+            transcript_hasher: config.transcript_hasher,
+            min_degree_bits: MIN_DEGREE_BITS,
+            max_degree_bits: spec.max_rows.trailing_zeros() as usize,
+            tables,
+            ctls,
+        })
+    }
+
+    fn transcript_hasher_name(&self) -> &'static str {
+        match self.transcript_hasher {
+            TranscriptHasher::Poseidon => "poseidon",
+            TranscriptHasher::Keccak => "keccak",
+        }
+    }
+
+    /// A Rust module of the constants, using nothing but `core`.
+    pub fn to_rust(&self) -> String {
+        fn lc(lc: &LinearCombination) -> String {
+            let terms = lc
+                .terms
+                .iter()
+                .map(|(column, coeff)| format!("({}, {})", column, coeff))
+                .collect::<Vec<_>>()
+                .join(", ");
+            format!(
+                "LinearCombination {{ terms: &[{}], constant: {} }}",
+                terms, lc.constant
+            )
+        }
+        fn side(side: &CtlSideConstants) -> String {
+            let columns = side.columns.iter().map(lc).collect::<Vec<_>>().join(", ");
+            let filter = side
+                .filter
+                .as_ref()
+                .map_or("None".to_string(), |f| format!("Some({})", lc(f)));
+            format!(
+                "CtlSide {{ table: {}, columns: &[{}], filter: {} }}",
+                side.table as usize, columns, filter
+            )
+        }
+
+        let mut out = String::new();
+        writeln!(
+            out,
+            "//! Verifier constants of OlaVM proof version {}, generated by\n\
+             //! `olavm verifier-constants`. Do not edit.\n\
+             //!\n\
+             //! Only `core` is used, the module builds in `no_std` crates.\n",
+            self.proof_version
+        )
+        .unwrap();
+        out.push_str(
+            "pub struct TableParams {\n    pub name: &'static str,\n    pub width: usize,\n    \
+             pub layout_fingerprint: u64,\n    pub quotient_degree_factor: usize,\n    \
+             pub cap_height: usize,\n    pub num_query_rounds: usize,\n    \
+             pub proof_of_work_bits: u32,\n}\n\n\
+             /// `(column, coefficient)` terms plus a constant.\n\
+             pub struct LinearCombination {\n    pub terms: &'static [(usize, u64)],\n    \
+             pub constant: u64,\n}\n\n\
+             /// One side of a cross-table lookup, `table` indexes `TABLES`.\n\
+             pub struct CtlSide {\n    pub table: usize,\n    \
+             pub columns: &'static [LinearCombination],\n    \
+             pub filter: Option<LinearCombination>,\n}\n\n\
+             pub struct Ctl {\n    pub name: &'static str,\n    \
+             pub looking: &'static [CtlSide],\n    pub looked: CtlSide,\n}\n\n",
+        );
+        for (name, ty, value) in [
+            ("PROOF_VERSION", "u32", self.proof_version.to_string()),
+            ("CHAIN_ID", "u64", self.chain_id.to_string()),
+            ("SECURITY_BITS", "usize", self.security_bits.to_string()),
+            ("NUM_CHALLENGES", "usize", self.num_challenges.to_string()),
+            ("RATE_BITS", "usize", self.rate_bits.to_string()),
+            (
+                "REDUCTION_ARITY_BITS",
+                "usize",
+                self.reduction_arity_bits.to_string(),
+            ),
+            ("FINAL_POLY_BITS", "usize", self.final_poly_bits.to_string()),
+            (
+                "TRANSCRIPT_HASHER",
+                "&str",
+                format!("{:?}", self.transcript_hasher_name()),
+            ),
+            ("MIN_DEGREE_BITS", "usize", self.min_degree_bits.to_string()),
+            ("MAX_DEGREE_BITS", "usize", self.max_degree_bits.to_string()),
+        ] {
+            writeln!(out, "pub const {}: {} = {};", name, ty, value).unwrap();
+        }
+
+        writeln!(
+            out,
+            "\npub const TABLES: [TableParams; {}] = [",
+            self.tables.len()
+        )
+        .unwrap();
+        for table in &self.tables {
+            writeln!(
+                out,
+                "    TableParams {{ name: \"{:?}\", width: {}, layout_fingerprint: {:#018x}, \
+                 quotient_degree_factor: {}, cap_height: {}, num_query_rounds: {}, \
+                 proof_of_work_bits: {} }},",
+                table.table,
+                table.width,
+                table.layout_fingerprint,
+                table.quotient_degree_factor,
+                table.cap_height,
+                table.num_query_rounds,
+                table.proof_of_work_bits
+            )
+            .unwrap();
+        }
+        out.push_str("];\n");
+
+        writeln!(out, "\npub const CTLS: [Ctl; {}] = [", self.ctls.len()).unwrap();
+        for ctl in &self.ctls {
+            let looking = ctl.looking.iter().map(side).collect::<Vec<_>>().join(", ");
+            writeln!(
+                out,
+                "    Ctl {{ name: {:?}, looking: &[{}], looked: {} }},",
+                ctl.name,
+                looking,
+                side(&ctl.looked)
+            )
+            .unwrap();
+        }
+        out.push_str("];\n");
+        out
+    }
+
+    /// A C header of the constants, the tables and lookups as static arrays.
+    pub fn to_c_header(&self) -> String {
+        // Defines the term array of `lc` as `name` and returns the
+        // initializer of the combination.
+        fn lc(defs: &mut String, name: &str, lc: &LinearCombination) -> String {
+            if lc.terms.is_empty() {
+                return format!("{{ NULL, 0u, {}ull }}", lc.constant);
+            }
+            let terms = lc
+                .terms
+                .iter()
+                .map(|(column, coeff)| format!("{{ {}u, {}ull }}", column, coeff))
+                .collect::<Vec<_>>()
+                .join(", ");
+            writeln!(
+                defs,
+                "static const olavm_term_t {}[{}] = {{ {} }};",
+                name,
+                lc.terms.len(),
+                terms
+            )
+            .unwrap();
+            format!("{{ {}, {}u, {}ull }}", name, lc.terms.len(), lc.constant)
+        }
+        fn side(defs: &mut String, name: &str, side: &CtlSideConstants) -> String {
+            let columns = side
+                .columns
+                .iter()
+                .enumerate()
+                .map(|(i, c)| lc(defs, &format!("{}_col{}_terms", name, i), c))
+                .collect::<Vec<_>>()
+                .join(", ");
+            writeln!(
+                defs,
+                "static const olavm_linear_combination_t {}_columns[{}] = {{ {} }};",
+                name,
+                side.columns.len(),
+                columns
+            )
+            .unwrap();
+            let filter = match &side.filter {
+                Some(filter) => format!(
+                    "1u, {}",
+                    lc(defs, &format!("{}_filter_terms", name), filter)
+                ),
+                None => "0u, { NULL, 0u, 0ull }".to_string(),
+            };
+            format!(
+                "{{ {}u, {}_columns, {}u, {} }}",
+                side.table as usize,
+                name,
+                side.columns.len(),
+                filter
+            )
+        }
+
+        let mut out = String::new();
+        writeln!(
+            out,
+            "/* Verifier constants of OlaVM proof version {}, generated by\n \
+             * `olavm verifier-constants`. Do not edit. */\n\
+             #ifndef OLAVM_VERIFIER_CONSTANTS_H\n\
+             #define OLAVM_VERIFIER_CONSTANTS_H\n\n\
+             #include <stddef.h>\n\
+             #include <stdint.h>\n",
+            self.proof_version
+        )
+        .unwrap();
+        for (name, value) in [
+            ("PROOF_VERSION", format!("{}u", self.proof_version)),
+            ("CHAIN_ID", format!("{}ull", self.chain_id)),
+            ("SECURITY_BITS", format!("{}u", self.security_bits)),
+            ("NUM_CHALLENGES", format!("{}u", self.num_challenges)),
+            ("RATE_BITS", format!("{}u", self.rate_bits)),
+            (
+                "REDUCTION_ARITY_BITS",
+                format!("{}u", self.reduction_arity_bits),
+            ),
+            ("FINAL_POLY_BITS", format!("{}u", self.final_poly_bits)),
+            (
+                "TRANSCRIPT_HASHER",
+                format!("{:?}", self.transcript_hasher_name()),
+            ),
+            ("MIN_DEGREE_BITS", format!("{}u", self.min_degree_bits)),
+            ("MAX_DEGREE_BITS", format!("{}u", self.max_degree_bits)),
+            ("NUM_TABLES", format!("{}u", self.tables.len())),
+            ("NUM_CTLS", format!("{}u", self.ctls.len())),
+        ] {
+            writeln!(out, "#define OLAVM_{} {}", name, value).unwrap();
+        }
+        out.push_str(
+            "\ntypedef struct {\n    const char *name;\n    uint32_t width;\n    \
+             uint64_t layout_fingerprint;\n    uint32_t quotient_degree_factor;\n    \
+             uint32_t cap_height;\n    uint32_t num_query_rounds;\n    \
+             uint32_t proof_of_work_bits;\n} olavm_table_params_t;\n\n\
+             typedef struct {\n    uint32_t column;\n    uint64_t coeff;\n} olavm_term_t;\n\n\
+             typedef struct {\n    const olavm_term_t *terms;\n    uint32_t num_terms;\n    \
+             uint64_t constant;\n} olavm_linear_combination_t;\n\n\
+             /* `table` indexes OLAVM_TABLES. */\n\
+             typedef struct {\n    uint32_t table;\n    \
+             const olavm_linear_combination_t *columns;\n    uint32_t num_columns;\n    \
+             uint8_t has_filter;\n    olavm_linear_combination_t filter;\n} olavm_ctl_side_t;\n\n\
+             typedef struct {\n    const char *name;\n    const olavm_ctl_side_t *looking;\n    \
+             uint32_t num_looking;\n    olavm_ctl_side_t looked;\n} olavm_ctl_t;\n\n",
+        );
+
+        out.push_str("static const olavm_table_params_t OLAVM_TABLES[OLAVM_NUM_TABLES] = {\n");
+        for table in &self.tables {
+            writeln!(
+                out,
+                "    {{ \"{:?}\", {}u, {:#018x}ull, {}u, {}u, {}u, {}u }},",
+                table.table,
+                table.width,
+                table.layout_fingerprint,
+                table.quotient_degree_factor,
+                table.cap_height,
+                table.num_query_rounds,
+                table.proof_of_work_bits
+            )
+            .unwrap();
+        }
+        out.push_str("};\n\n");
+
+        let mut defs = String::new();
+        let mut ctls = vec![];
+        for (i, ctl) in self.ctls.iter().enumerate() {
+            let looking = ctl
+                .looking
+                .iter()
+                .enumerate()
+                .map(|(j, s)| side(&mut defs, &format!("olavm_ctl{}_looking{}", i, j), s))
+                .collect::<Vec<_>>()
+                .join(", ");
+            writeln!(
+                defs,
+                "static const olavm_ctl_side_t olavm_ctl{}_looking[{}] = {{ {} }};",
+                i,
+                ctl.looking.len(),
+                looking
+            )
+            .unwrap();
+            let looked = side(&mut defs, &format!("olavm_ctl{}_looked", i), &ctl.looked);
+            ctls.push(format!(
+                "    {{ {:?}, olavm_ctl{}_looking, {}u, {} }},",
+                ctl.name,
+                i,
+                ctl.looking.len(),
+                looked
+            ));
+        }
+        out.push_str(&defs);
+        writeln!(
+            out,
+            "\nstatic const olavm_ctl_t OLAVM_CTLS[OLAVM_NUM_CTLS] = {{\n{}\n}};",
+            ctls.join("\n")
+        )
+        .unwrap();
+        out.push_str("\n#endif /* OLAVM_VERIFIER_CONSTANTS_H */\n");
+        out
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use core::vm::chain_spec::ChainSpec;
+    use plonky2::plonk::config::{GenericConfig, PoseidonGoldilocksConfig};
+
+    use crate::stark::config::StarkConfig;
+    use crate::stark::ola_stark::{OlaStark, Table, NUM_TABLES};
+    use crate::stark::verifier_constants::VerifierConstants;
+
+    const D: usize = 2;
+    type F = <PoseidonGoldilocksConfig as GenericConfig<D>>::F;
+
+    #[test]
+    fn test_verifier_constants() {
+        let ola_stark = OlaStark::<F, D>::default();
+        let spec = ChainSpec::default();
+        let config = StarkConfig::from_chain_spec(&spec).unwrap();
+        let constants = VerifierConstants::new(&ola_stark, &config, &spec).unwrap();
+        assert_eq!(constants.tables.len(), NUM_TABLES);
+        assert_eq!(constants.tables[Table::Tape as usize].table, Table::Tape);
+        assert_eq!(constants.max_degree_bits, 22);
+        assert_eq!(constants.ctls.len(), ola_stark.describe_ctls().len());
+        for ctl in &constants.ctls {
+            for looking in &ctl.looking {
+                assert_eq!(looking.columns.len(), ctl.looked.columns.len());
+            }
+        }
+
+        let rust = constants.to_rust();
+        assert!(rust.contains(&format!("pub const TABLES: [TableParams; {}]", NUM_TABLES)));
+        assert!(rust.contains("pub const MAX_DEGREE_BITS: usize = 22;"));
+        assert!(!rust.contains("std::"));
+        let header = constants.to_c_header();
+        assert!(header.starts_with("/* Verifier constants"));
+        assert!(header.contains("#define OLAVM_RATE_BITS 3u"));
+        assert!(header.ends_with("#endif /* OLAVM_VERIFIER_CONSTANTS_H */\n"));
+        for text in [&rust, &header] {
+            assert_eq!(text.matches('{').count(), text.matches('}').count());
+        }
+    }
+}
@@ -1,6 +1,5 @@
-use core::crypto::hash::Hasher;
-use core::crypto::ZkHasher;
 use core::merkle_tree::log::{StorageLog, WitnessStorageLog};
+use core::program::{hash_program, program_code};
 use core::types::merkle_tree::{encode_addr, tree_key_default};
 use core::{program::Program, trace::trace::Trace, types::account::Address};
 use std::collections::HashMap;
@@ -30,6 +29,53 @@ pub fn test_stark_with_asm_path<Row, const COL_NUM: usize, E, H>(
         &mut ConstraintConsumer<GoldilocksField>,
     ) -> (),
     H: Fn(usize, StarkEvaluationVars<GoldilocksField, GoldilocksField, COL_NUM>) -> (),
+{
+    test_stark_with_random_padding(
+        path,
+        get_trace_rows,
+        generate_trace,
+        &[],
+        eval_packed_generic,
+        error_hook,
+        call_data,
+        db_name,
+    )
+}
+
+/// Overwrite `columns` of the padding `rows` with random values.
+fn randomize_padding<const COL_NUM: usize>(
+    trace: &mut [Vec<GoldilocksField>; COL_NUM],
+    rows: impl Iterator<Item = usize> + Clone,
+    columns: &[usize],
+) {
+    for &col in columns {
+        for i in rows.clone() {
+            trace[col][i] = GoldilocksField::rand();
+        }
+    }
+}
+
+/// Same as `test_stark_with_asm_path`, but the `random_padding_cols` of the
+/// padding rows are filled with random values before the constraints are
+/// checked, so the constraints must not rely on them being what
+/// `generate_*_trace` pads with. Only pass columns the AIR leaves free in
+/// padding rows.
+pub fn test_stark_with_random_padding<Row, const COL_NUM: usize, G, E, H>(
+    path: String,
+    get_trace_rows: fn(Trace) -> Vec<Row>,
+    generate_trace: G,
+    random_padding_cols: &[usize],
+    eval_packed_generic: E,
+    error_hook: Option<H>,
+    call_data: Option<Vec<GoldilocksField>>,
+    db_name: Option<String>,
+) where
+    E: Fn(
+        StarkEvaluationVars<GoldilocksField, GoldilocksField, COL_NUM>,
+        &mut ConstraintConsumer<GoldilocksField>,
+    ) -> (),
+    G: Fn(&Vec<Row>) -> [Vec<GoldilocksField>; COL_NUM],
+    H: Fn(usize, StarkEvaluationVars<GoldilocksField, GoldilocksField, COL_NUM>) -> (),
 {
     let mut db = match db_name {
         Some(name) => {
@@ -42,13 +88,9 @@ pub fn test_stark_with_asm_path<Row, const COL_NUM: usize, E, H>(
     };
 
     let program = encode_asm_from_json_file(path).unwrap();
-    let hash = ZkHasher::default();
     let instructions = program.bytecode.split("\n");
-    let code: Vec<_> = instructions
-        .clone()
-        .map(|e| GoldilocksField::from_canonical_u64(u64::from_str_radix(&e[2..], 16).unwrap()))
-        .collect();
-    let code_hash = hash.hash_bytes(&code);
+    let code = program_code(&program).unwrap();
+    let code_hash = hash_program(&program);
     let mut prophets = HashMap::new();
     for item in program.prophets {
         prophets.insert(item.host as u64, item);
@@ -108,7 +150,7 @@ pub fn test_stark_with_asm_path<Row, const COL_NUM: usize, E, H>(
         storage_log: StorageLog::new_write_log(callee_exe_addr, code_hash),
         previous_value: tree_key_default(),
     }]);
-    let _ = db.save();
+    db.commit().unwrap();
 
     let start = db.root_hash();
 
@@ -128,11 +170,13 @@ pub fn test_stark_with_asm_path<Row, const COL_NUM: usize, E, H>(
     }
     let hash_roots = gen_storage_hash_table(&mut process, &mut program, &mut db);
     gen_storage_table(&mut process, &mut program, hash_roots).unwrap();
+    process.finalize(&mut db, true).unwrap();
     program.trace.start_end_roots = (start, db.root_hash());
 
     let raw_trace_rows = get_trace_rows(program.trace);
-    let rows = generate_trace(&raw_trace_rows);
+    let mut rows = generate_trace(&raw_trace_rows);
     let len = rows[0].len();
+    randomize_padding(&mut rows, raw_trace_rows.len()..len, random_padding_cols);
     println!(
         "raw trace len:{}, extended len: {}",
         raw_trace_rows.len(),
@@ -203,6 +247,36 @@ pub fn simple_test_stark<const COL_NUM: usize, E, H>(
         &mut ConstraintConsumer<GoldilocksField>,
     ) -> (),
     H: Fn(usize, StarkEvaluationVars<GoldilocksField, GoldilocksField, COL_NUM>) -> (),
+{
+    simple_test_stark_with_random_padding(
+        path,
+        generate_trace,
+        None,
+        eval_packed_generic,
+        error_hook,
+        call_data,
+        db_name,
+    )
+}
+
+/// Same as `simple_test_stark`, but `random_padding` names the is padding
+/// column of the table and the columns to randomize: those columns of the
+/// padding rows are filled with random values before the constraints are
+/// checked. Only pass columns the AIR leaves free in padding rows.
+pub fn simple_test_stark_with_random_padding<const COL_NUM: usize, E, H>(
+    path: String,
+    generate_trace: fn(Trace) -> [Vec<GoldilocksField>; COL_NUM],
+    random_padding: Option<(usize, &[usize])>,
+    eval_packed_generic: E,
+    error_hook: Option<H>,
+    call_data: Option<Vec<GoldilocksField>>,
+    db_name: Option<String>,
+) where
+    E: Fn(
+        StarkEvaluationVars<GoldilocksField, GoldilocksField, COL_NUM>,
+        &mut ConstraintConsumer<GoldilocksField>,
+    ) -> (),
+    H: Fn(usize, StarkEvaluationVars<GoldilocksField, GoldilocksField, COL_NUM>) -> (),
 {
     let mut db = match db_name {
         Some(name) => {
@@ -215,13 +289,9 @@ pub fn simple_test_stark<const COL_NUM: usize, E, H>(
     };
 
     let program = encode_asm_from_json_file(path).unwrap();
-    let hash = ZkHasher::default();
     let instructions = program.bytecode.split("\n");
-    let code: Vec<_> = instructions
-        .clone()
-        .map(|e| GoldilocksField::from_canonical_u64(u64::from_str_radix(&e[2..], 16).unwrap()))
-        .collect();
-    let code_hash = hash.hash_bytes(&code);
+    let code = program_code(&program).unwrap();
+    let code_hash = hash_program(&program);
     let mut prophets = HashMap::new();
     for item in program.prophets {
         prophets.insert(item.host as u64, item);
@@ -234,6 +304,7 @@ pub fn simple_test_stark<const COL_NUM: usize, E, H>(
         prophets,
         pre_exe_flag: false,
         print_flag: false,
+        return_arity: program.return_arity,
     };
 
     for inst in instructions {
@@ -288,7 +359,7 @@ pub fn simple_test_stark<const COL_NUM: usize, E, H>(
         storage_log: StorageLog::new_write_log(callee_exe_addr, code_hash),
         previous_value: tree_key_default(),
     }]);
-    let _ = db.save();
+    db.commit().unwrap();
 
     let start = db.root_hash();
 
@@ -307,10 +378,17 @@ pub fn simple_test_stark<const COL_NUM: usize, E, H>(
     }
     let hash_roots = gen_storage_hash_table(&mut process, &mut program, &mut db);
     gen_storage_table(&mut process, &mut program, hash_roots).unwrap();
+    process.finalize(&mut db, true).unwrap();
     program.trace.start_end_roots = (start, db.root_hash());
 
-    let rows = generate_trace(program.trace);
+    let mut rows = generate_trace(program.trace);
     let len = rows[0].len();
+    if let Some((is_padding_col, columns)) = random_padding {
+        let padding_rows: Vec<_> = (0..len)
+            .filter(|&i| rows[is_padding_col][i] == GoldilocksField::ONE)
+            .collect();
+        randomize_padding(&mut rows, padding_rows.into_iter(), columns);
+    }
 
     let last = GoldilocksField::primitive_root_of_unity(log2_strict(len)).inverse();
     let subgroup = GoldilocksField::cyclic_subgroup_known_order(
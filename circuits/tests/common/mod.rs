@@ -0,0 +1,56 @@
+//! Shared by the feature matrix smoke tests. Each `smoke*.rs` is its own test
+//! binary, built only with the features it names in `Cargo.toml`, so the
+//! public prover API is exercised under every feature combination:
+//!
+//! ```text
+//! cargo test -p circuits --test smoke
+//! cargo test -p circuits --features trace_check --test smoke_trace_check
+//! ```
+
+#![allow(dead_code)]
+
+use circuits::fixtures::fixture;
+use circuits::generation::GenerationInputs;
+use circuits::stark::config::StarkConfig;
+use circuits::stark::ola_stark::OlaStark;
+use circuits::stark::proof::AllProof;
+use circuits::stark::prover::prove;
+use circuits::stark::serialization::Buffer;
+use circuits::stark::verifier::verify_proof;
+use plonky2::plonk::config::{GenericConfig, PoseidonGoldilocksConfig};
+use plonky2::util::timing::TimingTree;
+
+pub const D: usize = 2;
+pub type C = PoseidonGoldilocksConfig;
+pub type F = <C as GenericConfig<D>>::F;
+
+/// Executes the fibonacci fixture and proves it with `prove`, which runs the
+/// feature gated stages, under `config`.
+pub fn prove_fibonacci(config: &StarkConfig) -> AllProof<F, C, D> {
+    let program = fixture("fibonacci").unwrap().execute().unwrap();
+    let mut ola_stark = OlaStark::<F, D>::default();
+    prove::<F, C, D>(
+        program,
+        &mut ola_stark,
+        GenerationInputs::default(),
+        config,
+        &mut TimingTree::default(),
+    )
+    .unwrap()
+}
+
+/// Proves the fibonacci fixture, verifies the proof and checks it survives
+/// a serialization round trip.
+pub fn prove_and_verify() -> AllProof<F, C, D> {
+    let config = StarkConfig::dev_config();
+    let proof = prove_fibonacci(&config);
+    verify_proof(OlaStark::default(), proof.clone(), &config).unwrap();
+
+    let mut buffer = Buffer::new(Vec::new());
+    buffer.write_all_proof(&proof).unwrap();
+    let read = Buffer::new(buffer.bytes())
+        .read_all_proof::<F, C, D>()
+        .unwrap();
+    verify_proof(OlaStark::default(), read, &config).unwrap();
+    proof
+}
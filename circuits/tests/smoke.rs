@@ -0,0 +1,19 @@
+//! Prover API smoke test with the default features.
+
+mod common;
+
+use circuits::stark::config::StarkConfig;
+use circuits::stark::ola_stark::OlaStark;
+use circuits::stark::verifier::verify_proof;
+
+#[test]
+fn prove_and_verify() {
+    common::prove_and_verify();
+}
+
+#[test]
+fn prove_with_spot_check() {
+    let config = StarkConfig::dev_config().with_spot_check(0.01);
+    let proof = common::prove_fibonacci(&config);
+    verify_proof(OlaStark::default(), proof, &config).unwrap();
+}
@@ -0,0 +1,24 @@
+//! Prover API smoke test with `determinism_check`: `prove` generates the
+//! traces serially and in parallel and compares them, `generate_traces_checked`
+//! also executes the program twice.
+
+mod common;
+
+use circuits::fixtures::fixture;
+use circuits::generation::{generate_traces_checked, GenerationInputs};
+use circuits::stark::ola_stark::OlaStark;
+
+#[test]
+fn prove_and_verify() {
+    common::prove_and_verify();
+}
+
+#[test]
+fn generate_traces_checked_agrees() {
+    let mut ola_stark = OlaStark::<common::F, { common::D }>::default();
+    generate_traces_checked(
+        || fixture("fibonacci").unwrap().execute().unwrap(),
+        &mut ola_stark,
+        GenerationInputs::default(),
+    );
+}
@@ -0,0 +1,18 @@
+//! Prover API smoke test with `heap_profile` and the tracking allocator
+//! installed, as a binary of its own since it replaces the global allocator.
+
+mod common;
+
+use circuits::stark::config::StarkConfig;
+use circuits::stark::heap_profile::{peak_bytes, report, TrackingAlloc};
+
+#[global_allocator]
+static GLOBAL: TrackingAlloc = TrackingAlloc;
+
+#[test]
+fn prove_records_heap_use() {
+    common::prove_fibonacci(&StarkConfig::dev_config());
+    assert!(!report().entries.is_empty());
+    assert!(peak_bytes() > 0);
+    assert!(!report().to_string().is_empty());
+}
@@ -0,0 +1,87 @@
+//! Verifier API smoke test with `proof_compat`.
+
+mod common;
+
+use circuits::fixtures::fixture;
+use circuits::generation::{generate_traces, GenerationInputs};
+use circuits::stark::config::StarkConfig;
+use circuits::stark::error::VerifierError;
+use circuits::stark::ola_stark::OlaStark;
+use circuits::stark::proof::{AllProof, ProofVersion};
+use circuits::stark::prover::prove_with_traces_at_version;
+use circuits::stark::serialization::Buffer;
+use circuits::stark::verifier::{
+    verify_proof, verify_proof_with_compatibility, ProofCompatibility,
+};
+use common::{C, D, F};
+use plonky2::util::timing::TimingTree;
+
+fn prove_fibonacci_at(version: ProofVersion, config: &StarkConfig) -> AllProof<F, C, D> {
+    let program = fixture("fibonacci").unwrap().execute().unwrap();
+    let mut ola_stark = OlaStark::<F, D>::default();
+    let (traces, public_values) =
+        generate_traces(program, &mut ola_stark, GenerationInputs::default());
+    prove_with_traces_at_version::<F, C, D>(
+        &ola_stark,
+        config,
+        traces,
+        public_values,
+        version,
+        &mut TimingTree::default(),
+    )
+    .unwrap()
+}
+
+#[test]
+fn verify_with_compatibility() {
+    let config = StarkConfig::dev_config();
+    let proof = common::prove_and_verify();
+    verify_proof_with_compatibility(
+        OlaStark::default(),
+        proof,
+        &config,
+        &ProofCompatibility::new(),
+    )
+    .unwrap();
+}
+
+#[test]
+fn verify_previous_version() {
+    let config = StarkConfig::dev_config();
+    let previous = ProofVersion::legacy();
+    let proof = prove_fibonacci_at(previous, &config);
+    assert_eq!(proof.version, previous);
+    assert!(matches!(
+        verify_proof(OlaStark::default(), proof.clone(), &config),
+        Err(VerifierError::UnsupportedProofVersion(v)) if v == previous.0
+    ));
+
+    let compat = ProofCompatibility::new().with_version(previous, config.clone());
+    verify_proof_with_compatibility(OlaStark::default(), proof.clone(), &config, &compat).unwrap();
+    let mut buffer = Buffer::new(Vec::new());
+    buffer.write_all_proof(&proof).unwrap();
+    let mut read = Buffer::new(buffer.bytes())
+        .read_all_proof::<F, C, D>()
+        .unwrap();
+    assert_eq!(read.version, previous);
+    // v0 proofs carry no public values, the verifier gets them alongside.
+    read.public_values = proof.public_values.clone();
+    verify_proof_with_compatibility(OlaStark::default(), read, &config, &compat).unwrap();
+
+    // the version the proof claims decides the transcript
+    let mut relabeled = proof;
+    relabeled.version = ProofVersion::default();
+    assert!(
+        verify_proof_with_compatibility(OlaStark::default(), relabeled, &config, &compat).is_err()
+    );
+}
+
+#[test]
+fn reject_version_outside_compatibility() {
+    let config = StarkConfig::dev_config();
+    let legacy = prove_fibonacci_at(ProofVersion::legacy(), &config);
+    assert!(matches!(
+        verify_proof_with_compatibility(OlaStark::default(), legacy, &config, &ProofCompatibility::new()),
+        Err(VerifierError::UnsupportedProofVersion(0))
+    ));
+}
@@ -0,0 +1,9 @@
+//! Prover API smoke test with `trace_check`: `prove` checks the builtin
+//! traces against their constraints first.
+
+mod common;
+
+#[test]
+fn prove_and_verify() {
+    common::prove_and_verify();
+}
@@ -1,20 +1,30 @@
 extern crate clap;
 
 use assembler::encoder::encode_asm_from_json_file;
+use circuits::generation::padding::padding_report;
 use circuits::generation::GenerationInputs;
 use circuits::stark::config::StarkConfig;
 use circuits::stark::ola_stark::OlaStark;
 use circuits::stark::prover::prove;
 use circuits::stark::serialization::Buffer;
 use circuits::stark::verifier::verify_proof;
+use circuits::stark::verifier_constants::VerifierConstants;
 use clap::{arg, Command};
 use core::merkle_tree::tree::AccountTree;
 use core::program::binary_program::BinaryProgram;
 use core::program::Program;
-use core::trace::trace::Trace;
+use core::trace::redact::{check_structure, redact_trace, RedactMode};
+use core::trace::schema::load_trace;
+use core::trace::stream::ChunkedTraceReader;
+use core::types::storage::layout::{diff_layouts, StorageLayout};
+use core::vm::chain_spec::ChainSpec;
 use core::vm::transaction::init_tx_context_mock;
 use core::vm::vm_state::Address;
+use executor::chrome_trace::ChromeTrace;
+use executor::hooks::NoHooks;
 use executor::load_tx::init_tape;
+use executor::repro::{ReproBundle, REPRO_TAIL_STEPS};
+use executor::semantics::OpcodeSemantics;
 use executor::Process;
 use plonky2::field::goldilocks_field::GoldilocksField;
 use plonky2::field::types::Field;
@@ -22,7 +32,8 @@ use plonky2::plonk::config::{GenericConfig, PoseidonGoldilocksConfig};
 use plonky2::util::timing::TimingTree;
 use std::collections::{BTreeMap, HashMap};
 use std::fs::{self, metadata, File};
-use std::io::{BufRead, BufReader, Read, Write};
+use std::io::{BufRead, BufReader, BufWriter, Read, Write};
+use std::path::{Path, PathBuf};
 use std::time::Instant;
 
 #[allow(dead_code)]
@@ -33,7 +44,7 @@ type C = PoseidonGoldilocksConfig;
 type F = <C as GenericConfig<D>>::F;
 
 fn main() {
-    let matches = Command::new("olavm")
+    let cmd = Command::new("olavm")
         .about("Olavm cli")
         .subcommand_required(true)
         .arg_required_else_help(true)
@@ -54,15 +65,29 @@ fn main() {
                     arg!(-i --input <INPUT> "Must set a binary file for OlaVM executing"),
                     arg!(-a --args <INPUT> "Must set a input args file for OlaVM executing"),
                     arg!(-o --output <OUTPUT> "Must set a output file for OlaVM executing"),
+                    arg!(--"chrome-trace" <PROFILE> "Write a Chrome trace-event profile of the run")
+                        .required(false),
+                    arg!(--"repro-dir" <DIR> "Write a repro bundle into this directory if the run fails")
+                        .required(false),
                 ])
                 .arg_required_else_help(true),
         )
+        .subcommand(
+            Command::new("replay")
+                .about("Replay a repro bundle written by a failed run")
+                .args(&[arg!(-i --input <BUNDLE> "Must set a repro bundle file")])
+                .arg_required_else_help(true),
+        )
         .subcommand(
             Command::new("prove")
                 .about("generate proof from executed program")
                 .args(&[
                     arg!(-i --input <Trace> "Must set a trace file generated by OlaVM executor"),
                     arg!(-o --output <Proof> "Must set a file for save proofs"),
+                    arg!(--"spot-check" <RATE> "Check the constraints on this share of the rows, e.g. 0.01, before proving")
+                        .required(false),
+                    arg!(--chunks <CHUNKS> "Trace chunks streamed by the executor, merged into the input trace")
+                        .required(false),
                 ])
                 .arg_required_else_help(true),
         )
@@ -72,7 +97,54 @@ fn main() {
                 .args(&[arg!(-i --input <Trace> "Must set a proof file generated by OlaVM prover")])
                 .arg_required_else_help(true),
         )
-        .get_matches();
+        .subcommand(
+            Command::new("redact-trace")
+                .about("Strip or hash storage values and calldata of a trace before sharing it")
+                .args(&[
+                    arg!(-i --input <Trace> "Must set a trace file generated by OlaVM executor"),
+                    arg!(-o --output <OUTPUT> "Must set a output file for the redacted trace"),
+                    arg!(--hash "Hash values instead of zeroing them"),
+                    arg!(--verify "Check the redacted trace keeps the structure of the original"),
+                ])
+                .arg_required_else_help(true),
+        )
+        .subcommand(
+            Command::new("layout-diff")
+                .about("Check storage layout compatibility and generate a migration program")
+                .args(&[
+                    arg!(--old <OLD> "Must set the storage layout file of the deployed contract"),
+                    arg!(--new <NEW> "Must set the storage layout file of the upgraded contract"),
+                    arg!(-o --output <OUTPUT> "Must set a output file for the migration asm bundle"),
+                ])
+                .arg_required_else_help(true),
+        )
+        .subcommand(
+            Command::new("opcodes")
+                .about("Write the opcode semantics table as JSON")
+                .args(&[arg!(-o --output <OUTPUT> "Must set a output file for the opcode table")])
+                .arg_required_else_help(true),
+        )
+        .subcommand(
+            Command::new("verifier-constants")
+                .about("Write the verifier constants as a no_std Rust module and a C header")
+                .args(&[
+                    arg!(--rust <RUST> "Must set a output file for the Rust module"),
+                    arg!(--header <HEADER> "Must set a output file for the C header"),
+                    arg!(--spec <SPEC> "The chain spec to take the limits from").required(false),
+                ])
+                .arg_required_else_help(true),
+        );
+    #[cfg(feature = "arrow")]
+    let cmd = cmd.subcommand(
+        Command::new("export")
+            .about("Export trace tables as parquet files for external analysis")
+            .args(&[
+                arg!(-i --input <Trace> "Must set a trace file generated by OlaVM executor"),
+                arg!(-o --output <DIR> "Must set a directory for the parquet files"),
+            ])
+            .arg_required_else_help(true),
+    );
+    let matches = cmd.get_matches();
 
     match matches.subcommand() {
         Some(("asm", sub_matches)) => {
@@ -152,24 +224,74 @@ fn main() {
                 &init_tx_context_mock(),
             );
 
-            process
-                .execute(
-                    &mut program,
-                    &mut AccountTree::new_db_test("./db_test".to_string()),
-                )
-                .expect("OlaVM execute fail");
+            process.repro_dir = sub_matches
+                .get_one::<String>("repro-dir")
+                .map(PathBuf::from);
+
+            // steps are only recorded when a profile is written
+            let profile_path = sub_matches.get_one::<String>("chrome-trace");
+            let mut chrome_trace = ChromeTrace::new();
+            let mut account_tree = chrome_trace.span("open account tree", || {
+                AccountTree::new_db_test("./db_test".to_string())
+            });
+            if profile_path.is_some() {
+                process.execute_with_hooks(&mut program, &mut account_tree, &mut chrome_trace)
+            } else {
+                process.execute(&mut program, &mut account_tree)
+            }
+            .expect("OlaVM execute fail");
             println!("exec time:{}", now.elapsed().as_millis());
 
             let now = Instant::now();
 
             let path = sub_matches.get_one::<String>("output").expect("required");
             println!("Output trace file path: {}", path);
-            let file = File::create(path).unwrap();
-            serde_json::to_writer(file, &program.trace).unwrap();
+            chrome_trace.span("write trace", || {
+                let file = File::create(path).unwrap();
+                serde_json::to_writer(file, &program.trace).unwrap();
+            });
             println!("write time:{}", now.elapsed().as_millis());
 
+            if let Some(profile_path) = profile_path {
+                println!("Output chrome trace file path: {}", profile_path);
+                let file = File::create(profile_path).unwrap();
+                chrome_trace.write(BufWriter::new(file)).unwrap();
+            }
+
             println!("Run done!");
         }
+        Some(("replay", sub_matches)) => {
+            let path = sub_matches.get_one::<String>("input").expect("required");
+            println!("Input repro bundle file path: {}", path);
+            let bundle = ReproBundle::load(Path::new(path)).unwrap();
+            println!("Recorded error: {}", bundle.error);
+            let replay = bundle.replay(&mut NoHooks).unwrap();
+            match &replay.result {
+                Err(error) => println!("Replay error: {}", error),
+                Ok(_) => println!("Replay succeeded, the failure did not reproduce"),
+            }
+            let mut navigator = replay.navigator();
+            navigator.seek_last();
+            for _ in 1..REPRO_TAIL_STEPS.min(navigator.len()) {
+                navigator.step_back();
+            }
+            loop {
+                if let Some(state) = navigator.state() {
+                    let asm = replay.program.trace.instructions.get(&state.pc);
+                    println!(
+                        "clk {} pc {} {} {:?}",
+                        state.clk,
+                        state.pc,
+                        asm.map_or("", |inst| inst.0.as_str()),
+                        state.registers
+                    );
+                }
+                if !navigator.step_forward() {
+                    break;
+                }
+            }
+            println!("Replay done!");
+        }
         Some(("prove", sub_matches)) => {
             let path = sub_matches.get_one::<String>("input").expect("required");
             println!("Input trace file path: {}", path);
@@ -177,7 +299,13 @@ fn main() {
             let file = File::open(path).unwrap();
             let reader = BufReader::new(file);
 
-            let trace: Trace = serde_json::from_reader(reader).unwrap();
+            let trace = load_trace(reader).unwrap();
+            let trace = match sub_matches.get_one::<String>("chunks") {
+                Some(path) => ChunkedTraceReader::new(BufReader::new(File::open(path).unwrap()))
+                    .and_then(|reader| reader.assemble(trace))
+                    .unwrap(),
+                None => trace,
+            };
             let program: Program = Program {
                 instructions: trace.raw_binary_instructions.clone(),
                 trace,
@@ -185,12 +313,17 @@ fn main() {
                 prophets: HashMap::new(),
                 pre_exe_flag: false,
                 print_flag: false,
+                return_arity: None,
             };
 
+            print!("{}", padding_report(&program.trace));
             let inputs = GenerationInputs::default();
 
             let mut ola_stark = OlaStark::<F, D>::default();
-            let config = StarkConfig::standard_fast_config();
+            let mut config = StarkConfig::standard_fast_config();
+            if let Some(rate) = sub_matches.get_one::<String>("spot-check") {
+                config = config.with_spot_check(rate.parse().expect("spot check rate"));
+            }
             let proof = prove::<F, C, D>(
                 program,
                 &mut ola_stark,
@@ -236,6 +369,93 @@ fn main() {
                 _ => println!("Verify succeed!"),
             }
         }
+        Some(("redact-trace", sub_matches)) => {
+            let path = sub_matches.get_one::<String>("input").expect("required");
+            println!("Input trace file path: {}", path);
+            let trace = load_trace(BufReader::new(File::open(path).unwrap())).unwrap();
+            let mode = if sub_matches.contains_id("hash") {
+                RedactMode::Hash
+            } else {
+                RedactMode::Zero
+            };
+            let redacted = redact_trace(&trace, mode);
+            if sub_matches.contains_id("verify") {
+                match check_structure(&trace, &redacted) {
+                    Ok(()) => println!("Redacted trace keeps the structure of the original"),
+                    Err(e) => panic!("redacted trace changes the structure: {}", e),
+                }
+            }
+            let path = sub_matches.get_one::<String>("output").expect("required");
+            println!("Output redacted trace file path: {}", path);
+            let file = File::create(path).unwrap();
+            serde_json::to_writer(file, &redacted).unwrap();
+            println!("Redact done!");
+        }
+        Some(("layout-diff", sub_matches)) => {
+            let read_layout = |name: &str| -> StorageLayout {
+                let path = sub_matches.get_one::<String>(name).expect("required");
+                let file = File::open(path).unwrap();
+                serde_json::from_reader(BufReader::new(file)).unwrap()
+            };
+            let diff = diff_layouts(&read_layout("old"), &read_layout("new"));
+            for incompatibility in &diff.incompatibilities {
+                println!("Incompatible: {}", incompatibility);
+            }
+            if !diff.is_compatible() {
+                println!("Layout diff failed!");
+                return;
+            }
+
+            let path = sub_matches.get_one::<String>("output").expect("required");
+            println!("Output migration asm file path: {}", path);
+            let bundle = serde_json::json!({
+                "program": diff.migration_program(),
+                "prophets": [],
+            });
+            fs::write(path, serde_json::to_string_pretty(&bundle).unwrap()).unwrap();
+            println!("Migrated slots: {}", diff.migration.len());
+            println!("Layout diff done!");
+        }
+        Some(("opcodes", sub_matches)) => {
+            let path = sub_matches.get_one::<String>("output").expect("required");
+            println!("Output opcode table file path: {}", path);
+            fs::write(path, OpcodeSemantics::new().to_json()).unwrap();
+            println!("Opcodes done!");
+        }
+        Some(("verifier-constants", sub_matches)) => {
+            let spec = match sub_matches.get_one::<String>("spec") {
+                Some(path) => ChainSpec::from_file(path).unwrap(),
+                None => ChainSpec::default(),
+            };
+            let config = StarkConfig::from_chain_spec(&spec).unwrap();
+            let ola_stark = OlaStark::<F, D>::default();
+            let constants = VerifierConstants::new(&ola_stark, &config, &spec).unwrap();
+
+            let rust = sub_matches.get_one::<String>("rust").expect("required");
+            println!("Output Rust module file path: {}", rust);
+            fs::write(rust, constants.to_rust()).unwrap();
+            let header = sub_matches.get_one::<String>("header").expect("required");
+            println!("Output C header file path: {}", header);
+            fs::write(header, constants.to_c_header()).unwrap();
+            println!("Verifier constants done!");
+        }
+        #[cfg(feature = "arrow")]
+        Some(("export", sub_matches)) => {
+            let path = sub_matches.get_one::<String>("input").expect("required");
+            println!("Input trace file path: {}", path);
+            let file = File::open(path).unwrap();
+            let reader = BufReader::new(file);
+            let trace = load_trace(reader).unwrap();
+
+            let output = sub_matches.get_one::<String>("output").expect("required");
+            let files =
+                core::trace::export::write_parquet_tables(&trace, std::path::Path::new(output))
+                    .unwrap();
+            for file in files {
+                println!("Wrote {}", file.display());
+            }
+            println!("Export done!");
+        }
         _ => unreachable!(),
     }
 }
@@ -0,0 +1,318 @@
+use crate::crypto::poseidon_trace::calculate_arbitrary_poseidon;
+use crate::types::account::Address;
+use crate::types::merkle_tree::TreeValue;
+use plonky2::field::goldilocks_field::GoldilocksField;
+use plonky2::field::types::{Field, PrimeField64};
+use std::fmt::{Display, Formatter};
+use std::str::FromStr;
+use thiserror::Error;
+
+// Calldata ABI. A call is encoded as the words of its arguments in order,
+// followed by the argument word count and the function selector, which is how
+// the entry point finds them at the end of the calldata. The dispatch
+// routine receives the selector in r1, the word count in r2 and a pointer to
+// the argument words in r3.
+
+#[derive(Error, Debug, Clone, PartialEq, Eq)]
+pub enum AbiError {
+    #[error("invalid function signature: {0}")]
+    InvalidSignature(String),
+    #[error("unknown abi type: {0}")]
+    UnknownType(String),
+    #[error("expected {0} arguments, found {1}")]
+    ArgumentCount(usize, usize),
+    #[error("argument {0} is not a {1}")]
+    TypeMismatch(usize, AbiType),
+    #[error("argument {0} is out of range for {1}")]
+    OutOfRange(usize, AbiType),
+    #[error("calldata ends before argument {0}")]
+    ShortInput(usize),
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum AbiType {
+    U32,
+    Field,
+    Bool,
+    Address,
+    Hash,
+    /// A length word followed by that many field words.
+    Fields,
+}
+
+impl AbiType {
+    /// Number of words of the encoding, `None` if it depends on the value.
+    pub fn word_len(&self) -> Option<usize> {
+        match self {
+            AbiType::U32 | AbiType::Field | AbiType::Bool => Some(1),
+            AbiType::Address | AbiType::Hash => Some(4),
+            AbiType::Fields => None,
+        }
+    }
+}
+
+impl Display for AbiType {
+    fn fmt(&self, f: &mut Formatter<'_>) -> std::fmt::Result {
+        let name = match self {
+            AbiType::U32 => "u32",
+            AbiType::Field => "field",
+            AbiType::Bool => "bool",
+            AbiType::Address => "address",
+            AbiType::Hash => "hash",
+            AbiType::Fields => "fields",
+        };
+        write!(f, "{}", name)
+    }
+}
+
+impl FromStr for AbiType {
+    type Err = AbiError;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        match s {
+            "u32" => Ok(AbiType::U32),
+            "field" => Ok(AbiType::Field),
+            "bool" => Ok(AbiType::Bool),
+            "address" => Ok(AbiType::Address),
+            "hash" => Ok(AbiType::Hash),
+            "fields" => Ok(AbiType::Fields),
+            _ => Err(AbiError::UnknownType(s.to_string())),
+        }
+    }
+}
+
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum AbiValue {
+    U32(u32),
+    Field(GoldilocksField),
+    Bool(bool),
+    Address(Address),
+    Hash(TreeValue),
+    Fields(Vec<GoldilocksField>),
+}
+
+impl AbiValue {
+    pub fn abi_type(&self) -> AbiType {
+        match self {
+            AbiValue::U32(_) => AbiType::U32,
+            AbiValue::Field(_) => AbiType::Field,
+            AbiValue::Bool(_) => AbiType::Bool,
+            AbiValue::Address(_) => AbiType::Address,
+            AbiValue::Hash(_) => AbiType::Hash,
+            AbiValue::Fields(_) => AbiType::Fields,
+        }
+    }
+
+    pub fn encode(&self, words: &mut Vec<GoldilocksField>) {
+        match self {
+            AbiValue::U32(value) => words.push(GoldilocksField::from_canonical_u32(*value)),
+            AbiValue::Field(value) => words.push(*value),
+            AbiValue::Bool(value) => words.push(GoldilocksField::from_bool(*value)),
+            AbiValue::Address(value) | AbiValue::Hash(value) => words.extend(value),
+            AbiValue::Fields(values) => {
+                words.push(GoldilocksField::from_canonical_usize(values.len()));
+                words.extend(values);
+            }
+        }
+    }
+}
+
+/// A function signature such as `transfer(address,u32)`.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct FunctionSignature {
+    pub name: String,
+    pub params: Vec<AbiType>,
+}
+
+impl FunctionSignature {
+    pub fn selector(&self) -> u32 {
+        function_selector(&self.to_string())
+    }
+
+    /// Number of argument words, `None` if a parameter has a dynamic length.
+    pub fn word_len(&self) -> Option<usize> {
+        self.params.iter().map(AbiType::word_len).sum()
+    }
+
+    /// Calldata calling this function with `args`.
+    pub fn encode_call(&self, args: &[AbiValue]) -> Result<Vec<GoldilocksField>, AbiError> {
+        if args.len() != self.params.len() {
+            return Err(AbiError::ArgumentCount(self.params.len(), args.len()));
+        }
+        let mut words = Vec::new();
+        for (i, (arg, param)) in args.iter().zip(&self.params).enumerate() {
+            if arg.abi_type() != *param {
+                return Err(AbiError::TypeMismatch(i, *param));
+            }
+            arg.encode(&mut words);
+        }
+        words.push(GoldilocksField::from_canonical_usize(words.len()));
+        words.push(GoldilocksField::from_canonical_u32(self.selector()));
+        Ok(words)
+    }
+
+    /// Decode the argument words of a call, without the trailing word count
+    /// and selector.
+    pub fn decode_args(&self, words: &[GoldilocksField]) -> Result<Vec<AbiValue>, AbiError> {
+        decode_values(&self.params, words)
+    }
+}
+
+impl Display for FunctionSignature {
+    fn fmt(&self, f: &mut Formatter<'_>) -> std::fmt::Result {
+        let params: Vec<String> = self.params.iter().map(|p| p.to_string()).collect();
+        write!(f, "{}({})", self.name, params.join(","))
+    }
+}
+
+impl FromStr for FunctionSignature {
+    type Err = AbiError;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        let invalid = || AbiError::InvalidSignature(s.to_string());
+        let (name, rest) = s.trim().split_once('(').ok_or_else(invalid)?;
+        let params = rest.strip_suffix(')').ok_or_else(invalid)?;
+        let name = name.trim();
+        if name.is_empty() || !name.chars().all(|c| c.is_ascii_alphanumeric() || c == '_') {
+            return Err(invalid());
+        }
+        let params = if params.trim().is_empty() {
+            vec![]
+        } else {
+            params
+                .split(',')
+                .map(|p| p.trim().parse())
+                .collect::<Result<_, _>>()?
+        };
+        Ok(Self {
+            name: name.to_string(),
+            params,
+        })
+    }
+}
+
+/// Selector of a canonical signature: the low 32 bits of the first word of
+/// the poseidon hash of its bytes.
+pub fn function_selector(signature: &str) -> u32 {
+    let bytes: Vec<GoldilocksField> = signature
+        .bytes()
+        .map(GoldilocksField::from_canonical_u8)
+        .collect();
+    calculate_arbitrary_poseidon(&bytes)[0].to_canonical_u64() as u32
+}
+
+/// Decode `words` as consecutive values of `types`, e.g. return data.
+pub fn decode_values(
+    types: &[AbiType],
+    words: &[GoldilocksField],
+) -> Result<Vec<AbiValue>, AbiError> {
+    let mut values = Vec::with_capacity(types.len());
+    let mut pos = 0;
+    for (i, ty) in types.iter().enumerate() {
+        let take = |pos: usize, len: usize| {
+            pos.checked_add(len)
+                .and_then(|end| words.get(pos..end))
+                .ok_or(AbiError::ShortInput(i))
+        };
+        let (value, len) = match ty {
+            AbiType::U32 => {
+                let word = take(pos, 1)?[0].to_canonical_u64();
+                let value = u32::try_from(word).map_err(|_| AbiError::OutOfRange(i, *ty))?;
+                (AbiValue::U32(value), 1)
+            }
+            AbiType::Field => (AbiValue::Field(take(pos, 1)?[0]), 1),
+            AbiType::Bool => match take(pos, 1)?[0].to_canonical_u64() {
+                0 => (AbiValue::Bool(false), 1),
+                1 => (AbiValue::Bool(true), 1),
+                _ => return Err(AbiError::OutOfRange(i, *ty)),
+            },
+            AbiType::Address => (AbiValue::Address(take(pos, 4)?.try_into().unwrap()), 4),
+            AbiType::Hash => (AbiValue::Hash(take(pos, 4)?.try_into().unwrap()), 4),
+            AbiType::Fields => {
+                let len = take(pos, 1)?[0].to_canonical_u64() as usize;
+                (AbiValue::Fields(take(pos + 1, len)?.to_vec()), len + 1)
+            }
+        };
+        values.push(value);
+        pos += len;
+    }
+    Ok(values)
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::abi::{decode_values, AbiError, AbiType, AbiValue, FunctionSignature};
+    use plonky2::field::goldilocks_field::GoldilocksField;
+    use plonky2::field::types::Field;
+
+    #[test]
+    fn test_signature() {
+        let sig: FunctionSignature = "transfer( address, u32 )".parse().unwrap();
+        assert_eq!(sig.params, [AbiType::Address, AbiType::U32]);
+        assert_eq!(sig.to_string(), "transfer(address,u32)");
+        assert_eq!(sig.word_len(), Some(5));
+        assert_eq!(
+            sig.selector(),
+            "transfer(address,u32)"
+                .parse::<FunctionSignature>()
+                .unwrap()
+                .selector()
+        );
+        assert_ne!(
+            sig.selector(),
+            "transfer(u32,address)"
+                .parse::<FunctionSignature>()
+                .unwrap()
+                .selector()
+        );
+        assert_eq!(
+            "main()".parse::<FunctionSignature>().unwrap().params,
+            vec![]
+        );
+        assert!("main".parse::<FunctionSignature>().is_err());
+        assert!("f(u64)".parse::<FunctionSignature>().is_err());
+    }
+
+    #[test]
+    fn test_encode_decode_call() {
+        let word = GoldilocksField::from_canonical_u64;
+        let sig: FunctionSignature = "f(u32,fields,bool)".parse().unwrap();
+        assert_eq!(sig.word_len(), None);
+        let args = vec![
+            AbiValue::U32(7),
+            AbiValue::Fields(vec![word(1), word(2)]),
+            AbiValue::Bool(true),
+        ];
+        let calldata = sig.encode_call(&args).unwrap();
+        assert_eq!(
+            calldata,
+            [
+                word(7),
+                word(2),
+                word(1),
+                word(2),
+                word(1),
+                word(5),
+                word(sig.selector() as u64)
+            ]
+        );
+        assert_eq!(sig.decode_args(&calldata[..5]).unwrap(), args);
+
+        assert_eq!(
+            sig.encode_call(&args[..2]),
+            Err(AbiError::ArgumentCount(3, 2))
+        );
+        assert_eq!(
+            sig.encode_call(&[args[0].clone(), args[0].clone(), args[2].clone()]),
+            Err(AbiError::TypeMismatch(1, AbiType::Fields))
+        );
+        assert_eq!(
+            decode_values(&[AbiType::U32], &[word(1 << 32)]),
+            Err(AbiError::OutOfRange(0, AbiType::U32))
+        );
+        assert_eq!(
+            decode_values(&[AbiType::Hash], &[word(1)]),
+            Err(AbiError::ShortInput(0))
+        );
+    }
+}
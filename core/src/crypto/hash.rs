@@ -1,5 +1,5 @@
 use crate::crypto::poseidon_trace::PoseidonType;
-use crate::trace::trace::PoseidonRow;
+use crate::trace::trace::{HashTrace, PoseidonRow, Trace};
 use plonky2::field::goldilocks_field::GoldilocksField;
 
 pub trait Hasher<Hash> {
@@ -9,4 +9,15 @@ pub trait Hasher<Hash> {
     fn hash_elements<I: IntoIterator<Item = Hash>>(&self, elements: I) -> Hash;
     /// Merges two hashes into one.
     fn compress(&self, lhs: &Hash, rhs: &Hash, node_type: PoseidonType) -> (Hash, PoseidonRow);
+    /// Merges every pair, all of `node_type`, without the permutation rows.
+    fn compress_batch(&self, pairs: &[(Hash, Hash)], node_type: PoseidonType) -> Vec<Hash> {
+        pairs
+            .iter()
+            .map(|(lhs, rhs)| self.compress(lhs, rhs, node_type).0)
+            .collect()
+    }
+    /// Records one tree level, the new and the previous path hash, in
+    /// `trace`. Only `PoseidonHasher` hands its rows to the builtin tables,
+    /// other hashers just mark the trace as one the prover rejects.
+    fn record_level(&self, trace: &mut Trace, level: &HashTrace);
 }
@@ -1,6 +1,72 @@
 pub mod hash;
 pub mod poseidon;
+pub mod poseidon2;
 pub mod poseidon_trace;
 
+use crate::crypto::hash::Hasher;
 use crate::crypto::poseidon::PoseidonHasher;
-pub type ZkHasher = PoseidonHasher;
+use crate::crypto::poseidon2::Poseidon2Hasher;
+use crate::crypto::poseidon_trace::PoseidonType;
+use crate::trace::trace::{HashTrace, PoseidonRow, Trace};
+use crate::types::merkle_tree::{TreeKey, TreeValue};
+use plonky2::field::goldilocks_field::GoldilocksField;
+use serde::{Deserialize, Serialize};
+
+/// Hash of the account tree levels, picked when the tree is created. The
+/// choice is executor side only: the storage hash table and its lookups are
+/// fixed to the Poseidon builtin, so proofs exist for `Poseidon` trees alone
+/// and a `Poseidon2` tree can be executed but not proven.
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq, Serialize, Deserialize)]
+pub enum TreeHasher {
+    #[default]
+    Poseidon,
+    Poseidon2,
+}
+
+impl Hasher<TreeValue> for TreeHasher {
+    fn hash_bytes(&self, value: &Vec<GoldilocksField>) -> TreeValue {
+        match self {
+            TreeHasher::Poseidon => PoseidonHasher.hash_bytes(value),
+            TreeHasher::Poseidon2 => Poseidon2Hasher.hash_bytes(value),
+        }
+    }
+
+    fn hash_elements<I: IntoIterator<Item = TreeValue>>(&self, elements: I) -> TreeKey {
+        match self {
+            TreeHasher::Poseidon => PoseidonHasher.hash_elements(elements),
+            TreeHasher::Poseidon2 => Poseidon2Hasher.hash_elements(elements),
+        }
+    }
+
+    fn compress(
+        &self,
+        lhs: &TreeKey,
+        rhs: &TreeKey,
+        node_type: PoseidonType,
+    ) -> (TreeKey, PoseidonRow) {
+        match self {
+            TreeHasher::Poseidon => PoseidonHasher.compress(lhs, rhs, node_type),
+            TreeHasher::Poseidon2 => Poseidon2Hasher.compress(lhs, rhs, node_type),
+        }
+    }
+
+    fn compress_batch(
+        &self,
+        pairs: &[(TreeKey, TreeKey)],
+        node_type: PoseidonType,
+    ) -> Vec<TreeKey> {
+        match self {
+            TreeHasher::Poseidon => PoseidonHasher.compress_batch(pairs, node_type),
+            TreeHasher::Poseidon2 => Poseidon2Hasher.compress_batch(pairs, node_type),
+        }
+    }
+
+    fn record_level(&self, trace: &mut Trace, level: &HashTrace) {
+        match self {
+            TreeHasher::Poseidon => PoseidonHasher.record_level(trace, level),
+            TreeHasher::Poseidon2 => Poseidon2Hasher.record_level(trace, level),
+        }
+    }
+}
+
+pub type ZkHasher = TreeHasher;
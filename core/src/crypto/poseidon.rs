@@ -1,9 +1,9 @@
 use crate::crypto::hash::Hasher;
 use crate::crypto::poseidon_trace::{
-    calculate_poseidon_and_generate_intermediate_trace, PoseidonType,
+    calculate_poseidon_and_generate_intermediate_trace, calculate_poseidon_batch, PoseidonType,
     PoseidonType::{Branch, Leaf},
 };
-use crate::trace::trace::PoseidonRow;
+use crate::trace::trace::{HashTrace, PoseidonRow, Trace};
 use crate::types::merkle_tree::{tree_key_default, TreeKey, TreeValue, TREE_VALUE_LEN};
 use crate::util::poseidon_utils::POSEIDON_INPUT_NUM;
 use plonky2::field::goldilocks_field::GoldilocksField;
@@ -64,4 +64,34 @@ impl Hasher<TreeValue> for PoseidonHasher {
         }
         (tree_key, hash)
     }
+
+    fn compress_batch(
+        &self,
+        pairs: &[(TreeKey, TreeKey)],
+        node_type: PoseidonType,
+    ) -> Vec<TreeKey> {
+        let inputs: Vec<_> = pairs
+            .iter()
+            .map(|(lhs, rhs)| {
+                let mut input = [GoldilocksField::ZERO; POSEIDON_INPUT_NUM];
+                input[0..TREE_VALUE_LEN].clone_from_slice(lhs);
+                input[TREE_VALUE_LEN..TREE_VALUE_LEN * 2].clone_from_slice(rhs);
+                match node_type {
+                    Branch => {}
+                    Leaf => input[TREE_VALUE_LEN * 2] = GoldilocksField::ONE,
+                    _ => panic!("compress not support node type"),
+                }
+                input
+            })
+            .collect();
+        calculate_poseidon_batch(&inputs)
+            .iter()
+            .map(|output| output[0..TREE_VALUE_LEN].try_into().unwrap())
+            .collect()
+    }
+
+    fn record_level(&self, trace: &mut Trace, level: &HashTrace) {
+        trace.insert_poseidon(level.0);
+        trace.insert_poseidon(level.5);
+    }
 }
@@ -0,0 +1,76 @@
+use crate::crypto::hash::Hasher;
+use crate::crypto::poseidon_trace::{
+    PoseidonType,
+    PoseidonType::{Branch, Leaf},
+    POSEIDON_INPUT_VALUE_LEN,
+};
+use crate::crypto::TreeHasher;
+use crate::trace::trace::{HashTrace, PoseidonRow, Trace};
+use crate::types::merkle_tree::{tree_key_default, TreeKey, TreeValue, TREE_VALUE_LEN};
+use crate::util::poseidon_utils::POSEIDON_INPUT_NUM;
+use plonky2::field::goldilocks_field::GoldilocksField;
+use plonky2::field::types::Field;
+use plonky2::hash::poseidon2::Poseidon2;
+
+/// Account tree hash over the Poseidon2 permutation, with the same input
+/// layout as `PoseidonHasher`. There is no Poseidon2 builtin table yet, so
+/// trees hashed with it can be executed but not proven: the levels it hashes
+/// mark the trace, and the prover rejects marked traces.
+#[derive(Default, Clone, Debug)]
+pub struct Poseidon2Hasher;
+
+impl Hasher<TreeValue> for Poseidon2Hasher {
+    fn hash_bytes(&self, value: &Vec<GoldilocksField>) -> TreeValue {
+        let mut state = [GoldilocksField::ZERO; POSEIDON_INPUT_NUM];
+        for chunk in value.chunks(POSEIDON_INPUT_VALUE_LEN) {
+            state[0..chunk.len()].clone_from_slice(chunk);
+            state = GoldilocksField::poseidon2(state);
+        }
+        let mut hash = tree_key_default();
+        hash.clone_from_slice(&state[0..TREE_VALUE_LEN]);
+        hash
+    }
+
+    fn hash_elements<I: IntoIterator<Item = TreeValue>>(&self, elements: I) -> TreeKey {
+        let mut tree_key = tree_key_default();
+        let elems: Vec<GoldilocksField> = elements.into_iter().flatten().collect();
+        if elems.len() > POSEIDON_INPUT_VALUE_LEN {
+            return tree_key;
+        }
+        let mut input = [GoldilocksField::ZERO; POSEIDON_INPUT_NUM];
+        input[0..elems.len()].clone_from_slice(&elems);
+        tree_key.clone_from_slice(&GoldilocksField::poseidon2(input)[0..TREE_VALUE_LEN]);
+        tree_key
+    }
+
+    fn compress(
+        &self,
+        lhs: &TreeKey,
+        rhs: &TreeKey,
+        node_type: PoseidonType,
+    ) -> (TreeKey, PoseidonRow) {
+        let mut input = [GoldilocksField::ZERO; POSEIDON_INPUT_NUM];
+        input[0..TREE_VALUE_LEN].clone_from_slice(lhs);
+        input[TREE_VALUE_LEN..TREE_VALUE_LEN * 2].clone_from_slice(rhs);
+        match node_type {
+            Branch => {}
+            Leaf => input[TREE_VALUE_LEN * 2] = GoldilocksField::ONE,
+            _ => panic!("compress not support node type"),
+        }
+
+        // Only input and output are meaningful, the round columns belong to
+        // the poseidon table.
+        let row = PoseidonRow {
+            input,
+            output: GoldilocksField::poseidon2(input),
+            ..Default::default()
+        };
+        let mut tree_key = tree_key_default();
+        tree_key.clone_from_slice(&row.output[0..TREE_VALUE_LEN]);
+        (tree_key, row)
+    }
+
+    fn record_level(&self, trace: &mut Trace, _level: &HashTrace) {
+        trace.tree_hasher = TreeHasher::Poseidon2;
+    }
+}
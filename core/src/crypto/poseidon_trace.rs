@@ -1,32 +1,33 @@
-use std::cmp::min;
-
 use crate::{
     trace::trace::PoseidonRow,
     util::poseidon_utils::{
         constant_layer_field, mds_layer_field, mds_partial_layer_fast_field,
         mds_partial_layer_init, partial_first_constant_layer, sbox_layer_field, sbox_monomial,
-        POSEIDON_INPUT_NUM, POSEIDON_OUTPUT_NUM, POSEIDON_STATE_WIDTH,
+        POSEIDON_INPUT_NUM, POSEIDON_OUTPUT_NUM, POSEIDON_PARTIAL_ROUND_NUM, POSEIDON_STATE_WIDTH,
     },
 };
 
 use plonky2::{
-    field::{goldilocks_field::GoldilocksField, types::Field},
+    field::{
+        goldilocks_field::GoldilocksField, packable::Packable, packed::PackedField, types::Field,
+    },
     hash::poseidon::{self, Poseidon},
 };
 
 pub const POSEIDON_INPUT_VALUE_LEN: usize = 8;
 pub const POSEIDON_OUTPUT_VALUE_LEN: usize = 4;
 
-#[derive(PartialEq)]
+#[derive(PartialEq, Clone, Copy)]
 pub enum PoseidonType {
     Normal,
     Branch,
     Leaf,
 }
 
-pub fn calculate_poseidon(
-    full_input: [GoldilocksField; POSEIDON_INPUT_NUM],
-) -> [GoldilocksField; POSEIDON_OUTPUT_NUM] {
+/// The poseidon permutation of every lane of `state`.
+fn permute<P: PackedField<Scalar = GoldilocksField>>(
+    full_input: [P; POSEIDON_INPUT_NUM],
+) -> [P; POSEIDON_OUTPUT_NUM] {
     let mut state = full_input;
     let mut round_ctr = 0;
 
@@ -64,38 +65,167 @@ pub fn calculate_poseidon(
     state
 }
 
+pub fn calculate_poseidon(
+    full_input: [GoldilocksField; POSEIDON_INPUT_NUM],
+) -> [GoldilocksField; POSEIDON_OUTPUT_NUM] {
+    permute(full_input)
+}
+
 pub fn calculate_arbitrary_poseidon(inputs: &[GoldilocksField]) -> [GoldilocksField; 4] {
-    let mut state: [GoldilocksField; POSEIDON_STATE_WIDTH] =
-        [GoldilocksField::ZERO; POSEIDON_STATE_WIDTH];
+    let mut sponge = PoseidonSponge::new();
+    sponge.absorb(inputs);
+    sponge.squeeze_hash()
+}
+
+/// Poseidon sponge over variable length input, in the overwrite mode of
+/// `calculate_arbitrary_poseidon`: every 8 absorbed elements replace the rate
+/// part of the state and are permuted, a partial last chunk only replaces its
+/// own lanes. Outputs are squeezed 4 at a time from the front of the state.
+///
+/// A sponge built by `with_trace` records every permutation as a
+/// `PoseidonRow`. Consecutive rows chain, the input of a row is the output of
+/// the previous one with the absorbed lanes replaced, as the chunk table
+/// expects.
+#[derive(Debug, Clone)]
+pub struct PoseidonSponge {
+    state: [GoldilocksField; POSEIDON_STATE_WIDTH],
+    pending: Vec<GoldilocksField>,
+    // outputs of the current state already squeezed.
+    squeezed: usize,
+    rows: Option<Vec<PoseidonRow>>,
+}
+
+impl PoseidonSponge {
+    pub fn new() -> Self {
+        Self {
+            state: [GoldilocksField::ZERO; POSEIDON_STATE_WIDTH],
+            pending: Vec::with_capacity(POSEIDON_INPUT_VALUE_LEN),
+            squeezed: 0,
+            rows: None,
+        }
+    }
+
+    pub fn with_trace() -> Self {
+        Self {
+            rows: Some(vec![]),
+            ..Self::new()
+        }
+    }
+
+    fn permute(&mut self) {
+        match &mut self.rows {
+            Some(rows) => {
+                let row = calculate_poseidon_and_generate_intermediate_trace(self.state);
+                self.state = row.output;
+                rows.push(row);
+            }
+            None => self.state = calculate_poseidon(self.state),
+        }
+        self.squeezed = 0;
+    }
+
+    fn flush(&mut self) {
+        self.state[..self.pending.len()].copy_from_slice(&self.pending);
+        self.pending.clear();
+        self.permute();
+    }
+
+    pub fn absorb(&mut self, inputs: &[GoldilocksField]) {
+        for input in inputs {
+            self.pending.push(*input);
+            if self.pending.len() == POSEIDON_INPUT_VALUE_LEN {
+                self.flush();
+            }
+        }
+    }
+
+    pub fn squeeze(&mut self, n: usize) -> Vec<GoldilocksField> {
+        if !self.pending.is_empty() {
+            self.flush();
+        }
+        let mut outputs = Vec::with_capacity(n);
+        while outputs.len() < n {
+            if self.squeezed == POSEIDON_OUTPUT_VALUE_LEN {
+                self.permute();
+            }
+            outputs.push(self.state[self.squeezed]);
+            self.squeezed += 1;
+        }
+        outputs
+    }
+
+    /// The first 4 outputs, the hash of everything absorbed so far.
+    pub fn squeeze_hash(&mut self) -> [GoldilocksField; 4] {
+        self.squeeze(POSEIDON_OUTPUT_VALUE_LEN).try_into().unwrap()
+    }
 
-    for input_chunk in inputs.chunks(8) {
-        let end = min(input_chunk.len(), 8);
-        state[0..end].copy_from_slice(&input_chunk[0..end]);
-        state = calculate_poseidon(state);
+    /// Rows of every permutation so far, empty unless built `with_trace`.
+    pub fn into_rows(self) -> Vec<PoseidonRow> {
+        self.rows.unwrap_or_default()
     }
-    state[0..4].try_into().expect("slice with incorrect length")
 }
 
-pub fn calculate_poseidon_and_generate_intermediate_trace(
-    full_input: [GoldilocksField; POSEIDON_INPUT_NUM],
-) -> PoseidonRow {
-    let mut cell = PoseidonRow {
-        input: [GoldilocksField::default(); 12],
-        full_0_1: [GoldilocksField::default(); 12],
-        full_0_2: [GoldilocksField::default(); 12],
-        full_0_3: [GoldilocksField::default(); 12],
-        partial: [GoldilocksField::default(); 22],
-        full_1_0: [GoldilocksField::default(); 12],
-        full_1_1: [GoldilocksField::default(); 12],
-        full_1_2: [GoldilocksField::default(); 12],
-        full_1_3: [GoldilocksField::default(); 12],
-        output: [GoldilocksField::default(); 12],
-        filter_looked_normal: false,
-        filter_looked_treekey: false,
-        filter_looked_storage: false,
-        filter_looked_storage_branch: false,
+impl Default for PoseidonSponge {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+/// The states a `PoseidonRow` records, for every lane of `P`.
+struct PackedRow<P> {
+    input: [P; POSEIDON_STATE_WIDTH],
+    full_0_1: [P; POSEIDON_STATE_WIDTH],
+    full_0_2: [P; POSEIDON_STATE_WIDTH],
+    full_0_3: [P; POSEIDON_STATE_WIDTH],
+    partial: [P; POSEIDON_PARTIAL_ROUND_NUM],
+    full_1_0: [P; POSEIDON_STATE_WIDTH],
+    full_1_1: [P; POSEIDON_STATE_WIDTH],
+    full_1_2: [P; POSEIDON_STATE_WIDTH],
+    full_1_3: [P; POSEIDON_STATE_WIDTH],
+    output: [P; POSEIDON_STATE_WIDTH],
+}
+
+impl<P: PackedField<Scalar = GoldilocksField>> PackedRow<P> {
+    fn lane(&self, lane: usize) -> PoseidonRow {
+        fn unpack<P: PackedField, const N: usize>(states: &[P; N], lane: usize) -> [P::Scalar; N] {
+            states.map(|state| state.as_slice()[lane])
+        }
+        PoseidonRow {
+            input: unpack(&self.input, lane),
+            full_0_1: unpack(&self.full_0_1, lane),
+            full_0_2: unpack(&self.full_0_2, lane),
+            full_0_3: unpack(&self.full_0_3, lane),
+            partial: unpack(&self.partial, lane),
+            full_1_0: unpack(&self.full_1_0, lane),
+            full_1_1: unpack(&self.full_1_1, lane),
+            full_1_2: unpack(&self.full_1_2, lane),
+            full_1_3: unpack(&self.full_1_3, lane),
+            output: unpack(&self.output, lane),
+            filter_looked_normal: false,
+            filter_looked_treekey: false,
+            filter_looked_storage: false,
+            filter_looked_storage_branch: false,
+        }
+    }
+}
+
+/// `permute`, keeping the intermediate states of the rounds.
+fn permute_with_trace<P: PackedField<Scalar = GoldilocksField>>(
+    full_input: [P; POSEIDON_INPUT_NUM],
+) -> PackedRow<P> {
+    let zeros = [P::ZEROS; POSEIDON_STATE_WIDTH];
+    let mut cell = PackedRow {
+        input: full_input,
+        full_0_1: zeros,
+        full_0_2: zeros,
+        full_0_3: zeros,
+        partial: [P::ZEROS; POSEIDON_PARTIAL_ROUND_NUM],
+        full_1_0: zeros,
+        full_1_1: zeros,
+        full_1_2: zeros,
+        full_1_3: zeros,
+        output: zeros,
     };
-    cell.input[..].clone_from_slice(&full_input[..]);
 
     let mut state = full_input;
     let mut round_ctr = 0;
@@ -104,15 +234,9 @@ pub fn calculate_poseidon_and_generate_intermediate_trace(
     for r in 0..poseidon::HALF_N_FULL_ROUNDS {
         constant_layer_field(&mut state, round_ctr);
         match r {
-            1 => {
-                cell.full_0_1[..].clone_from_slice(&state[..]);
-            }
-            2 => {
-                cell.full_0_2[..].clone_from_slice(&state[..]);
-            }
-            3 => {
-                cell.full_0_3[..].clone_from_slice(&state[..]);
-            }
+            1 => cell.full_0_1 = state,
+            2 => cell.full_0_2 = state,
+            3 => cell.full_0_3 = state,
             _ => {}
         }
         sbox_layer_field(&mut state);
@@ -141,18 +265,10 @@ pub fn calculate_poseidon_and_generate_intermediate_trace(
     for r in 0..poseidon::HALF_N_FULL_ROUNDS {
         constant_layer_field(&mut state, round_ctr);
         match r {
-            0 => {
-                cell.full_1_0[..].clone_from_slice(&state[..]);
-            }
-            1 => {
-                cell.full_1_1[..].clone_from_slice(&state[..]);
-            }
-            2 => {
-                cell.full_1_2[..].clone_from_slice(&state[..]);
-            }
-            3 => {
-                cell.full_1_3[..].clone_from_slice(&state[..]);
-            }
+            0 => cell.full_1_0 = state,
+            1 => cell.full_1_1 = state,
+            2 => cell.full_1_2 = state,
+            3 => cell.full_1_3 = state,
             _ => {}
         }
         sbox_layer_field(&mut state);
@@ -160,34 +276,115 @@ pub fn calculate_poseidon_and_generate_intermediate_trace(
         round_ctr += 1;
     }
 
-    cell.output[..].clone_from_slice(&state[..]);
+    cell.output = state;
     cell
 }
 
+pub fn calculate_poseidon_and_generate_intermediate_trace(
+    full_input: [GoldilocksField; POSEIDON_INPUT_NUM],
+) -> PoseidonRow {
+    permute_with_trace(full_input).lane(0)
+}
+
+/// Packing the batch functions permute with, `Packing::WIDTH` inputs at a
+/// time. It is the field itself unless the build enables AVX2 or AVX-512
+/// (e.g. `-C target-cpu=native`), then the permutations run in SIMD lanes.
+type Packing = <GoldilocksField as Packable>::Packing;
+
+/// `inputs` a packing at a time, the last one padded with zero lanes.
+fn packed_inputs(
+    inputs: &[[GoldilocksField; POSEIDON_INPUT_NUM]],
+) -> impl Iterator<Item = (usize, [Packing; POSEIDON_INPUT_NUM])> + '_ {
+    inputs.chunks(Packing::WIDTH).map(|chunk| {
+        let mut lanes = vec![GoldilocksField::ZERO; Packing::WIDTH];
+        let packed = std::array::from_fn(|i| {
+            for (lane, input) in lanes.iter_mut().zip(chunk) {
+                *lane = input[i];
+            }
+            *Packing::from_slice(&lanes)
+        });
+        (chunk.len(), packed)
+    })
+}
+
+/// `calculate_poseidon` of every input, interleaving independent
+/// permutations for better pipelining and SIMD where the build has it.
+pub fn calculate_poseidon_batch(
+    inputs: &[[GoldilocksField; POSEIDON_INPUT_NUM]],
+) -> Vec<[GoldilocksField; POSEIDON_OUTPUT_NUM]> {
+    let mut outputs = Vec::with_capacity(inputs.len());
+    for (len, packed) in packed_inputs(inputs) {
+        let state = permute(packed);
+        outputs.extend((0..len).map(|lane| state.map(|p| p.as_slice()[lane])));
+    }
+    outputs
+}
+
+/// `calculate_poseidon_and_generate_intermediate_trace` of every input, see
+/// `calculate_poseidon_batch`.
+pub fn calculate_poseidon_and_generate_intermediate_trace_batch(
+    inputs: &[[GoldilocksField; POSEIDON_INPUT_NUM]],
+) -> Vec<PoseidonRow> {
+    let mut rows = Vec::with_capacity(inputs.len());
+    for (len, packed) in packed_inputs(inputs) {
+        let row = permute_with_trace(packed);
+        rows.extend((0..len).map(|lane| row.lane(lane)));
+    }
+    rows
+}
+
+/// `calculate_arbitrary_poseidon` of every input. The sponges run in
+/// lockstep, the n-th permutations of all inputs long enough to need one are
+/// batched together.
+pub fn calculate_arbitrary_poseidon_batch(
+    inputs: &[&[GoldilocksField]],
+) -> Vec<[GoldilocksField; 4]> {
+    let mut states = vec![[GoldilocksField::ZERO; POSEIDON_STATE_WIDTH]; inputs.len()];
+    let rounds = inputs
+        .iter()
+        .map(|input| (input.len() + POSEIDON_INPUT_VALUE_LEN - 1) / POSEIDON_INPUT_VALUE_LEN)
+        .max()
+        .unwrap_or(0);
+    for round in 0..rounds {
+        let from = round * POSEIDON_INPUT_VALUE_LEN;
+        let active: Vec<usize> = (0..inputs.len())
+            .filter(|&i| inputs[i].len() > from)
+            .collect();
+        let batch: Vec<_> = active
+            .iter()
+            .map(|&i| {
+                let chunk = &inputs[i][from..inputs[i].len().min(from + POSEIDON_INPUT_VALUE_LEN)];
+                let mut state = states[i];
+                state[..chunk.len()].copy_from_slice(chunk);
+                state
+            })
+            .collect();
+        for (i, output) in active.into_iter().zip(calculate_poseidon_batch(&batch)) {
+            states[i] = output;
+        }
+    }
+    states
+        .iter()
+        .map(|state| state[..POSEIDON_OUTPUT_VALUE_LEN].try_into().unwrap())
+        .collect()
+}
+
 pub fn calculate_arbitrary_poseidon_and_generate_intermediate_trace(
     inputs: &[GoldilocksField],
 ) -> ([GoldilocksField; 4], Vec<PoseidonRow>) {
-    let mut rows: Vec<PoseidonRow> = vec![];
-    let mut state: [GoldilocksField; POSEIDON_STATE_WIDTH] =
-        [GoldilocksField::ZERO; POSEIDON_STATE_WIDTH];
-
-    for input_chunk in inputs.chunks(8) {
-        let end = min(input_chunk.len(), 8);
-        state[0..end].copy_from_slice(&input_chunk[0..end]);
-        let row = calculate_poseidon_and_generate_intermediate_trace(state);
-        state = row.output;
-        rows.push(row);
-    }
-    return (
-        state[0..4].try_into().expect("slice with incorrect length"),
-        rows,
-    );
+    let mut sponge = PoseidonSponge::with_trace();
+    sponge.absorb(inputs);
+    (sponge.squeeze_hash(), sponge.into_rows())
 }
+
 #[cfg(test)]
 mod test {
     use crate::crypto::poseidon_trace::{
         calculate_arbitrary_poseidon, calculate_arbitrary_poseidon_and_generate_intermediate_trace,
-        calculate_poseidon, calculate_poseidon_and_generate_intermediate_trace,
+        calculate_arbitrary_poseidon_batch, calculate_poseidon,
+        calculate_poseidon_and_generate_intermediate_trace,
+        calculate_poseidon_and_generate_intermediate_trace_batch, calculate_poseidon_batch,
+        PoseidonSponge,
     };
     use crate::vm::vm_state::GoldilocksField;
     use plonky2::field::types::Field;
@@ -245,4 +442,62 @@ mod test {
         let res = calculate_arbitrary_poseidon_and_generate_intermediate_trace(&inputs);
         println!("{:?}", res.0);
     }
+
+    #[test]
+    fn test_poseidon_sponge() {
+        let inputs: Vec<GoldilocksField> =
+            (0..19).map(GoldilocksField::from_canonical_u64).collect();
+        let (hash, rows) = calculate_arbitrary_poseidon_and_generate_intermediate_trace(&inputs);
+        assert_eq!(rows.len(), 3);
+        assert_eq!(hash, calculate_arbitrary_poseidon(&inputs));
+        // rows chain, only the absorbed lanes differ from the previous output.
+        assert_eq!(rows[1].input[8..], rows[0].output[8..]);
+        assert_eq!(rows[2].input[3..], rows[1].output[3..]);
+
+        // absorbing in pieces is the same as absorbing at once.
+        let mut sponge = PoseidonSponge::new();
+        sponge.absorb(&inputs[..5]);
+        sponge.absorb(&inputs[5..12]);
+        sponge.absorb(&inputs[12..]);
+        let outputs = sponge.squeeze(6);
+        assert_eq!(outputs[..4], hash);
+        let next = calculate_poseidon(rows[2].output);
+        assert_eq!(outputs[4..], next[..2]);
+        assert_eq!(sponge.squeeze(2), next[2..4]);
+
+        assert_eq!(
+            PoseidonSponge::new().squeeze_hash(),
+            [GoldilocksField::ZERO; 4]
+        );
+    }
+
+    #[test]
+    fn test_poseidon_batch() {
+        // more inputs than any packing is wide, with a partial last packing.
+        let inputs: Vec<[GoldilocksField; 12]> = (0..11u64)
+            .map(|i| {
+                std::array::from_fn(|j| GoldilocksField::from_canonical_u64(i * 12 + j as u64))
+            })
+            .collect();
+        let outputs = calculate_poseidon_batch(&inputs);
+        let rows = calculate_poseidon_and_generate_intermediate_trace_batch(&inputs);
+        assert_eq!(outputs.len(), inputs.len());
+        assert_eq!(rows.len(), inputs.len());
+        for ((input, output), row) in inputs.iter().zip(&outputs).zip(&rows) {
+            assert_eq!(*output, calculate_poseidon(*input));
+            let single = calculate_poseidon_and_generate_intermediate_trace(*input);
+            assert_eq!(row.partial, single.partial);
+            assert_eq!(row.full_1_3, single.full_1_3);
+            assert_eq!(row.output, single.output);
+        }
+        assert!(calculate_poseidon_batch(&[]).is_empty());
+
+        let data: Vec<GoldilocksField> = (0..30).map(GoldilocksField::from_canonical_u64).collect();
+        let inputs: Vec<&[GoldilocksField]> =
+            vec![&data[..], &[], &data[..8], &data[3..12], &data[..1]];
+        let hashes = calculate_arbitrary_poseidon_batch(&inputs);
+        for (input, hash) in inputs.iter().zip(hashes) {
+            assert_eq!(hash, calculate_arbitrary_poseidon(input));
+        }
+    }
 }
@@ -1,6 +1,7 @@
 #![feature(iter_next_chunk)]
 #![feature(iter_array_chunks)]
 
+pub mod abi;
 pub mod crypto;
 pub mod merkle_tree;
 pub mod program;
@@ -2,6 +2,8 @@ pub mod iter_ext;
 pub mod log;
 pub mod macros;
 pub mod patch;
+pub mod shared;
+pub mod snapshot;
 pub mod storage;
 pub mod tree;
 pub mod tree_config;
@@ -32,4 +34,18 @@ pub enum TreeError {
     StorageIoError(#[from] rocksdb::Error),
     #[error("empty patch")]
     EmptyPatch,
+    #[error("Cannot import leaves into a non-empty tree")]
+    NonEmptyTree,
+    #[error("Leaf dump is not canonical, expected leaf index {0:?}, found {1:?}")]
+    NonCanonicalDump(u64, u64),
+    #[error("Tree state of block {0} is not retained")]
+    VersionNotRetained(u32),
+    #[error("Tree snapshot of block {0} is read-only")]
+    ReadOnlySnapshot(u32),
+    #[error("Snapshot chunk {0} is out of order, expected chunk {1}")]
+    SnapshotChunkOrder(usize, usize),
+    #[error("Snapshot chunk {0} does not prove its leaves")]
+    InvalidSnapshotChunk(usize),
+    #[error("Snapshot is incomplete, received {0} of {1} chunks")]
+    IncompleteSnapshot(usize, usize),
 }
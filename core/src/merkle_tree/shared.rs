@@ -0,0 +1,144 @@
+use crate::merkle_tree::tree::AccountTree;
+use crate::merkle_tree::TreeError;
+use std::sync::{Mutex, MutexGuard};
+
+/// Account tree shared by one writer, the canonical executor of the
+/// sequencer, and any number of readers such as simulation executors.
+///
+/// Readers take a `view`, a read-only tree pinned to the last saved block,
+/// without locking the writer or cloning the tree. Views keep reading the
+/// state they were taken at while the writer processes and saves new blocks.
+#[derive(Debug)]
+pub struct SharedAccountTree {
+    writer: Mutex<AccountTree>,
+    latest: Mutex<AccountTree>,
+}
+
+impl SharedAccountTree {
+    pub fn new(tree: AccountTree) -> Self {
+        let (_, saved_block) = tree.storage.fetch_metadata();
+        let latest = tree
+            .view_at(saved_block)
+            .expect("last saved block is always retained");
+        Self {
+            writer: Mutex::new(tree),
+            latest: Mutex::new(latest),
+        }
+    }
+
+    /// Read-only view of the last saved block.
+    pub fn view(&self) -> AccountTree {
+        let latest = self.lock_latest();
+        latest.pin(latest.block_number(), latest.root_hash())
+    }
+
+    /// Read-only view of the state saved at `block_number`, for historical
+    /// queries. Fails with `TreeError::VersionNotRetained` for blocks after
+    /// the last saved one or pruned, keep the writer archival with
+    /// `AccountTree::set_archival` to serve every block.
+    pub fn at_block(&self, block_number: u32) -> Result<AccountTree, TreeError> {
+        self.lock_latest().view_at(block_number)
+    }
+
+    /// Block number of the last saved block.
+    pub fn block_number(&self) -> u32 {
+        self.lock_latest().block_number()
+    }
+
+    /// Runs `f` on the writable tree, one writer at a time, then publishes
+    /// the last saved block to new views.
+    pub fn write<R>(&self, f: impl FnOnce(&mut AccountTree) -> R) -> R {
+        let mut writer = self.writer.lock().unwrap_or_else(|err| err.into_inner());
+        let result = f(&mut writer);
+        let (_, saved_block) = writer.storage.fetch_metadata();
+        if let Ok(latest) = writer.view_at(saved_block) {
+            *self.lock_latest() = latest;
+        }
+        result
+    }
+
+    fn lock_latest(&self) -> MutexGuard<'_, AccountTree> {
+        self.latest.lock().unwrap_or_else(|err| err.into_inner())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::merkle_tree::log::{StorageLog, WitnessStorageLog};
+    use crate::merkle_tree::shared::SharedAccountTree;
+    use crate::merkle_tree::tree::AccountTree;
+    use crate::merkle_tree::TreeError;
+    use crate::types::merkle_tree::{
+        tree_key_to_leaf_index, tree_value_default, u8_arr_to_tree_key,
+    };
+    use plonky2::field::goldilocks_field::GoldilocksField;
+    use plonky2::field::types::Field;
+    use std::sync::Arc;
+    use std::thread;
+
+    #[test]
+    fn test_shared_tree_views() {
+        let word = |v: u64| [GoldilocksField::from_canonical_u64(v); 4];
+        let write_block = |tree: &mut AccountTree, value: u64| {
+            tree.process_block((1..4).map(|i| WitnessStorageLog {
+                storage_log: StorageLog::new_write_log(word(i), word(i * value)),
+                previous_value: tree_value_default(),
+            }));
+            tree.save().unwrap();
+            tree.root_hash()
+        };
+        let read = move |tree: &AccountTree, key: u64| {
+            tree.storage
+                .hash(&tree_key_to_leaf_index(&word(key)))
+                .map(|bytes| u8_arr_to_tree_key(&bytes))
+        };
+
+        let shared = Arc::new(SharedAccountTree::new(AccountTree::new_test()));
+        let root_1 = shared.write(|tree| write_block(tree, 10));
+        let view = shared.view();
+        assert_eq!(view.root_hash(), root_1);
+
+        let readers: Vec<_> = (0..4)
+            .map(|_| {
+                let shared = shared.clone();
+                thread::spawn(move || {
+                    let view = shared.view();
+                    let root = view.root_hash();
+                    // whichever block the view was taken at, it reads it
+                    // consistently while the writer goes on
+                    for _ in 0..20 {
+                        let value = read(&view, 2).unwrap();
+                        assert!(value == word(20) || value == word(40));
+                        assert_eq!(view.root_hash_at(view.block_number()).unwrap(), root);
+                    }
+                })
+            })
+            .collect();
+        let root_2 = shared.write(|tree| write_block(tree, 20));
+        for reader in readers {
+            reader.join().unwrap();
+        }
+
+        assert_ne!(root_1, root_2);
+        assert_eq!(read(&view, 2), Some(word(20)));
+        assert_eq!(read(&shared.view(), 2), Some(word(40)));
+        assert_eq!(shared.view().root_hash(), root_2);
+        assert_eq!(shared.block_number(), view.block_number() + 1);
+
+        let old = shared.at_block(view.block_number()).unwrap();
+        assert_eq!(old.root_hash(), root_1);
+        assert_eq!(old.read_value(&word(2)), word(20));
+        assert_eq!(
+            shared.at_block(shared.block_number()).unwrap().root_hash(),
+            root_2
+        );
+        assert!(matches!(
+            shared.at_block(shared.block_number() + 1),
+            Err(TreeError::VersionNotRetained(_))
+        ));
+
+        let mut view = view;
+        assert!(matches!(view.save(), Err(TreeError::ReadOnlySnapshot(1))));
+        assert!(matches!(view.prune(0), Err(TreeError::ReadOnlySnapshot(1))));
+    }
+}
@@ -0,0 +1,308 @@
+use crate::crypto::hash::Hasher;
+use crate::crypto::poseidon_trace::PoseidonType;
+use crate::crypto::ZkHasher;
+use crate::merkle_tree::tree::AccountTree;
+use crate::merkle_tree::tree_config::TreeConfig;
+use crate::merkle_tree::TreeError;
+use crate::types::merkle_tree::constant::ROOT_TREE_DEPTH;
+use crate::types::merkle_tree::{tree_key_to_u256, tree_key_to_u8_arr, TreeDump, TreeLeaf, ZkHash};
+use serde::{Deserialize, Serialize};
+use std::collections::{BTreeMap, BTreeSet, HashSet};
+use web3::types::U256;
+
+/// One chunk of a state snapshot: consecutive leaves of the canonical leaf
+/// dump and the sibling hashes binding them to `root_hash`, so every chunk
+/// can be checked on its own when it arrives from an untrusted peer.
+#[derive(Clone, Debug, Deserialize, Serialize, PartialEq)]
+pub struct SnapshotChunk {
+    pub root_hash: ZkHash,
+    pub chunk_idx: usize,
+    pub num_chunks: usize,
+    pub leaves: Vec<TreeLeaf>,
+    /// Sibling hashes missing to recompute the root from `leaves`, level by
+    /// level from the leaves up and by position within a level. `None` is an
+    /// empty subtree.
+    pub proof: Vec<Option<ZkHash>>,
+}
+
+fn node_type(depth: usize) -> PoseidonType {
+    if depth == 0 {
+        PoseidonType::Leaf
+    } else {
+        PoseidonType::Branch
+    }
+}
+
+fn empty_hash(config: &TreeConfig<ZkHasher>, depth: usize) -> ZkHash {
+    *config.empty_tree()[ROOT_TREE_DEPTH - depth].hash()
+}
+
+/// Every non empty node of the tree holding `leaves`, by depth above the
+/// leaves and position.
+fn tree_levels(config: &TreeConfig<ZkHasher>, leaves: &[TreeLeaf]) -> Vec<BTreeMap<U256, ZkHash>> {
+    let mut levels = vec![leaves
+        .iter()
+        .map(|leaf| (tree_key_to_u256(&leaf.key), leaf.value))
+        .collect::<BTreeMap<_, _>>()];
+    for depth in 0..ROOT_TREE_DEPTH {
+        let level = &levels[depth];
+        let (mut parents, mut pairs) = (vec![], vec![]);
+        for (&pos, hash) in level {
+            let parent = pos >> 1;
+            if parents.last() == Some(&parent) {
+                continue;
+            }
+            let sibling = level
+                .get(&(pos ^ U256::one()))
+                .copied()
+                .unwrap_or_else(|| empty_hash(config, depth));
+            let (left, right) = if pos.bit(0) {
+                (sibling, *hash)
+            } else {
+                (*hash, sibling)
+            };
+            parents.push(parent);
+            pairs.push((left, right));
+        }
+        let hashes = config.hasher().compress_batch(&pairs, node_type(depth));
+        let parents: BTreeMap<_, _> = parents.into_iter().zip(hashes).collect();
+        levels.push(parents);
+    }
+    levels
+}
+
+fn multiproof(levels: &[BTreeMap<U256, ZkHash>], leaves: &[TreeLeaf]) -> Vec<Option<ZkHash>> {
+    let mut known: BTreeSet<U256> = leaves
+        .iter()
+        .map(|leaf| tree_key_to_u256(&leaf.key))
+        .collect();
+    let mut proof = vec![];
+    for level in levels.iter().take(ROOT_TREE_DEPTH) {
+        for pos in &known {
+            let sibling = *pos ^ U256::one();
+            if !known.contains(&sibling) {
+                proof.push(level.get(&sibling).copied());
+            }
+        }
+        known = known.iter().map(|pos| *pos >> 1).collect();
+    }
+    proof
+}
+
+/// Root of a tree holding `leaves`, with the rest of the tree given by
+/// `proof`. `None` if `proof` doesn't fit the leaves.
+fn root_from_multiproof(
+    config: &TreeConfig<ZkHasher>,
+    leaves: &[TreeLeaf],
+    proof: &[Option<ZkHash>],
+) -> Option<ZkHash> {
+    if leaves.is_empty() {
+        return proof.is_empty().then(|| config.default_root_hash());
+    }
+    let mut level: BTreeMap<U256, ZkHash> = leaves
+        .iter()
+        .map(|leaf| (tree_key_to_u256(&leaf.key), leaf.value))
+        .collect();
+    let mut proof = proof.iter();
+    for depth in 0..ROOT_TREE_DEPTH {
+        let (mut parents, mut pairs) = (vec![], vec![]);
+        for (&pos, hash) in &level {
+            let parent = pos >> 1;
+            if parents.last() == Some(&parent) {
+                continue;
+            }
+            let sibling = match level.get(&(pos ^ U256::one())) {
+                Some(sibling) => *sibling,
+                None => (*proof.next()?).unwrap_or_else(|| empty_hash(config, depth)),
+            };
+            let (left, right) = if pos.bit(0) {
+                (sibling, *hash)
+            } else {
+                (*hash, sibling)
+            };
+            parents.push(parent);
+            pairs.push((left, right));
+        }
+        let hashes = config.hasher().compress_batch(&pairs, node_type(depth));
+        level = parents.into_iter().zip(hashes).collect();
+    }
+    if proof.next().is_some() {
+        return None;
+    }
+    level.remove(&U256::zero())
+}
+
+impl AccountTree {
+    /// Streams the saved leaves of the tree in chunks of `chunk_size` leaves,
+    /// in the order of `export_leaves`. An empty tree is one empty chunk.
+    pub fn snapshot_chunks(&self, chunk_size: usize) -> impl Iterator<Item = SnapshotChunk> {
+        assert!(chunk_size > 0, "snapshot chunk size must not be zero");
+        let dump = self.export_leaves();
+        let levels = tree_levels(&self.config, &dump.leaves);
+        let num_chunks = ((dump.leaves.len() + chunk_size - 1) / chunk_size).max(1);
+        (0..num_chunks).map(move |chunk_idx| {
+            let start = (chunk_idx * chunk_size).min(dump.leaves.len());
+            let end = (start + chunk_size).min(dump.leaves.len());
+            let leaves = dump.leaves[start..end].to_vec();
+            SnapshotChunk {
+                root_hash: dump.root_hash,
+                chunk_idx,
+                num_chunks,
+                proof: multiproof(&levels, &leaves),
+                leaves,
+            }
+        })
+    }
+}
+
+/// Collects the chunks of a snapshot of the tree with root `root_hash`,
+/// checks each one against the root as it arrives and rebuilds the tree once
+/// all are there.
+pub struct SnapshotReceiver {
+    root_hash: ZkHash,
+    config: TreeConfig<ZkHasher>,
+    num_chunks: Option<usize>,
+    next_chunk: usize,
+    leaves: Vec<TreeLeaf>,
+    keys: HashSet<U256>,
+}
+
+impl SnapshotReceiver {
+    pub fn new(root_hash: ZkHash, hasher: ZkHasher) -> Self {
+        Self {
+            root_hash,
+            config: TreeConfig::new(hasher),
+            num_chunks: None,
+            next_chunk: 0,
+            leaves: vec![],
+            keys: HashSet::new(),
+        }
+    }
+
+    /// Number of chunks accepted so far.
+    pub fn received(&self) -> usize {
+        self.next_chunk
+    }
+
+    pub fn is_complete(&self) -> bool {
+        self.num_chunks == Some(self.next_chunk)
+    }
+
+    /// Accepts the next chunk if it belongs to the snapshot and its leaves are
+    /// in the tree of `root_hash`. A rejected chunk leaves the receiver
+    /// unchanged, so it can be requested again from another peer.
+    pub fn receive(&mut self, chunk: &SnapshotChunk) -> Result<(), TreeError> {
+        if chunk.root_hash != self.root_hash {
+            return Err(TreeError::TreeRootsDiffer(
+                tree_key_to_u8_arr(&chunk.root_hash),
+                tree_key_to_u8_arr(&self.root_hash),
+            ));
+        }
+        let num_chunks = self.num_chunks.unwrap_or(chunk.num_chunks);
+        if chunk.chunk_idx != self.next_chunk || chunk.num_chunks != num_chunks {
+            return Err(TreeError::SnapshotChunkOrder(
+                chunk.chunk_idx,
+                self.next_chunk,
+            ));
+        }
+        for (expected, leaf) in (self.leaves.len() as u64 + 1..).zip(chunk.leaves.iter()) {
+            if leaf.index != expected {
+                return Err(TreeError::NonCanonicalDump(expected, leaf.index));
+            }
+        }
+        let keys: HashSet<U256> = chunk
+            .leaves
+            .iter()
+            .map(|leaf| tree_key_to_u256(&leaf.key))
+            .collect();
+        if keys.len() != chunk.leaves.len() || !keys.is_disjoint(&self.keys) {
+            return Err(TreeError::InvalidSnapshotChunk(chunk.chunk_idx));
+        }
+        match root_from_multiproof(&self.config, &chunk.leaves, &chunk.proof) {
+            Some(root) if root == self.root_hash => {}
+            _ => return Err(TreeError::InvalidSnapshotChunk(chunk.chunk_idx)),
+        }
+
+        self.num_chunks = Some(num_chunks);
+        self.next_chunk += 1;
+        self.leaves.extend(chunk.leaves.iter().cloned());
+        self.keys.extend(keys);
+        Ok(())
+    }
+
+    /// Imports the received leaves into the empty `tree` and saves it.
+    pub fn finish(self, tree: &mut AccountTree) -> Result<(), TreeError> {
+        if !self.is_complete() {
+            return Err(TreeError::IncompleteSnapshot(
+                self.next_chunk,
+                self.num_chunks.unwrap_or(0),
+            ));
+        }
+        tree.import_leaves(&TreeDump {
+            root_hash: self.root_hash,
+            leaves: self.leaves,
+        })
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::crypto::ZkHasher;
+    use crate::merkle_tree::log::{StorageLog, WitnessStorageLog};
+    use crate::merkle_tree::snapshot::SnapshotReceiver;
+    use crate::merkle_tree::tree::AccountTree;
+    use crate::types::merkle_tree::tree_value_default;
+    use plonky2::field::goldilocks_field::GoldilocksField;
+    use plonky2::field::types::Field;
+
+    #[test]
+    fn test_snapshot_chunks() {
+        let word = |v: u64| [GoldilocksField::from_canonical_u64(v); 4];
+        let mut tree = AccountTree::new_test();
+        tree.process_block((1..8).map(|i| WitnessStorageLog {
+            storage_log: StorageLog::new_write_log(word(i), word(i * 10)),
+            previous_value: tree_value_default(),
+        }));
+        tree.save().unwrap();
+
+        let chunks: Vec<_> = tree.snapshot_chunks(3).collect();
+        assert_eq!(chunks.len(), 3);
+        assert_eq!(chunks[2].leaves.len(), 1);
+
+        let mut receiver = SnapshotReceiver::new(tree.root_hash(), ZkHasher::default());
+        // chunks have to come in order
+        assert!(receiver.receive(&chunks[1]).is_err());
+        let mut tampered = chunks[0].clone();
+        tampered.leaves[0].value = word(7);
+        assert!(receiver.receive(&tampered).is_err());
+        let mut tampered = chunks[0].clone();
+        tampered.proof.pop();
+        assert!(receiver.receive(&tampered).is_err());
+        assert_eq!(receiver.received(), 0);
+
+        receiver.receive(&chunks[0]).unwrap();
+        assert!(receiver.receive(&chunks[0]).is_err());
+        receiver.receive(&chunks[1]).unwrap();
+        assert!(!receiver.is_complete());
+        receiver.receive(&chunks[2]).unwrap();
+        assert!(receiver.is_complete());
+
+        let mut restored = AccountTree::new_test();
+        receiver.finish(&mut restored).unwrap();
+        assert_eq!(restored.root_hash(), tree.root_hash());
+        assert_eq!(restored.export_leaves(), tree.export_leaves());
+    }
+
+    #[test]
+    fn test_empty_snapshot() {
+        let tree = AccountTree::new_test();
+        let chunks: Vec<_> = tree.snapshot_chunks(4).collect();
+        assert_eq!(chunks.len(), 1);
+
+        let mut receiver = SnapshotReceiver::new(tree.root_hash(), ZkHasher::default());
+        let mut restored = AccountTree::new_test();
+        receiver.receive(&chunks[0]).unwrap();
+        receiver.finish(&mut restored).unwrap();
+        assert!(restored.is_empty());
+    }
+}
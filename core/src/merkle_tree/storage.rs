@@ -1,8 +1,9 @@
 use crate::merkle_tree::TreeError;
 use crate::storage::db::{MerkleTreeColumnFamily, RocksDB};
 use crate::types::merkle_tree::{
-    tree_key_to_u8_arr, u8_arr_to_tree_key, InitialStorageWrite, LeafIndices, LevelIndex,
-    RepeatedStorageWrite, TreeKey, TreeOperation, ZkHash,
+    tree_key_to_leaf_index, tree_key_to_u8_arr, tree_value_default, u8_arr_to_tree_key,
+    InitialStorageWrite, LeafIndices, LevelIndex, RepeatedStorageWrite, TreeKey, TreeOperation,
+    TreeValue, ZkHash,
 };
 use crate::utils::{deserialize_block_number, serialize_block_number, serialize_tree_leaf};
 use byteorder::{BigEndian, ByteOrder, ReadBytesExt};
@@ -11,11 +12,13 @@ use log::info;
 use plonky2::field::goldilocks_field::GoldilocksField;
 use plonky2::field::types::Field;
 use rocksdb::WriteBatch;
-use std::collections::HashMap;
+use std::collections::{HashMap, HashSet};
 use std::fmt::{Debug, Formatter};
+use std::sync::Arc;
 
 const BLOCK_NUMBER_KEY: &[u8; 12] = b"block_number";
 const LEAF_INDEX_KEY: &[u8; 10] = b"leaf_index";
+const RETAINED_FROM_KEY: &[u8; 13] = b"retained_from";
 
 // Represents pending update that is yet to be flushed in RocksDB.
 #[derive(Default)]
@@ -27,54 +30,163 @@ impl Debug for PendingPatch {
     }
 }
 
+/// What the pending patch writes, read back in place of the saved state so
+/// several blocks can be processed before one save.
+#[derive(Debug, Default)]
+struct PendingReads {
+    hashes: HashMap<Vec<u8>, Vec<u8>>,
+    /// `None` for leaves the patch deletes.
+    leaf_indices: HashMap<TreeKey, Option<u64>>,
+    leaf_index: Option<u64>,
+}
+
 /// Storage wrapper around RocksDB.
-/// Stores hashes of branch nodes in merkle tree and current block number
+/// Stores hashes of branch nodes in merkle tree and current block number.
+/// Every save also records the hashes it overwrites in the history column
+/// family, keyed by block number, so that recent tree states can still be
+/// read after they were replaced.
+///
+/// A storage pinned to a block reads the hashes as they were saved at that
+/// block, while other handles on the same db keep saving, and can't save
+/// itself.
 #[derive(Debug)]
 pub struct Storage {
-    db: RocksDB,
+    db: Arc<RocksDB>,
     pub pending_patch: PendingPatch,
+    /// Hashes overwritten by the pending patch, `None` for absent nodes.
+    pending_history: HashMap<LevelIndex, Option<Vec<u8>>>,
+    pending_reads: PendingReads,
+    /// Archival storage never prunes its history.
+    archival: bool,
+    pinned: Option<u32>,
 }
 
 impl Storage {
     pub fn new(db: RocksDB) -> Self {
         Self {
-            db,
+            db: Arc::new(db),
+            pending_patch: PendingPatch(WriteBatch::default()),
+            pending_history: HashMap::new(),
+            pending_reads: PendingReads::default(),
+            archival: false,
+            pinned: None,
+        }
+    }
+
+    /// Read-only storage on the same db, pinned to the state saved at
+    /// `block_number`.
+    pub fn pinned_at(&self, block_number: u32) -> Self {
+        Self {
+            db: self.db.clone(),
             pending_patch: PendingPatch(WriteBatch::default()),
+            pending_history: HashMap::new(),
+            pending_reads: PendingReads::default(),
+            archival: self.archival,
+            pinned: Some(block_number),
         }
     }
 
+    /// Block the storage is pinned to, `None` for the writable storage.
+    pub fn pinned(&self) -> Option<u32> {
+        self.pinned
+    }
+
+    pub fn is_archival(&self) -> bool {
+        self.archival
+    }
+
+    pub fn set_archival(&mut self, archival: bool) {
+        self.archival = archival;
+    }
+
     /// Fetches hashes of merkle tree branches from db
     pub fn hashes<'a, I: 'a>(&'a self, keys: I) -> Vec<Option<Vec<u8>>>
     where
         I: IntoIterator<Item = &'a LevelIndex>,
     {
-        self.db
-            .multi_get(keys.into_iter().map(LevelIndex::bin_key))
-            .into_iter()
-            .collect::<Result<_, _>>()
-            .unwrap()
+        match self.pinned {
+            Some(block_number) => self
+                .hashes_at(keys, block_number)
+                .expect("pinned block is no longer retained"),
+            None => self.current_hashes(keys),
+        }
     }
 
     pub fn hash(&self, key: &LevelIndex) -> Option<Vec<u8>> {
-        self.db.get(LevelIndex::bin_key(key)).unwrap()
+        match self.pinned {
+            Some(_) => self.hashes(std::iter::once(key)).pop().unwrap(),
+            None => self.current_hashes(std::iter::once(key)).pop().unwrap(),
+        }
+    }
+
+    /// Hashes as the pending patch leaves them.
+    fn current_hashes<'a, I: 'a>(&'a self, keys: I) -> Vec<Option<Vec<u8>>>
+    where
+        I: IntoIterator<Item = &'a LevelIndex>,
+    {
+        let keys: Vec<_> = keys.into_iter().map(LevelIndex::bin_key).collect();
+        let mut saved = self
+            .db
+            .multi_get(
+                keys.iter()
+                    .filter(|key| !self.pending_reads.hashes.contains_key(*key)),
+            )
+            .into_iter();
+        keys.iter()
+            .map(|key| match self.pending_reads.hashes.get(key) {
+                Some(hash) => Some(hash.clone()),
+                None => saved.next().unwrap().unwrap(),
+            })
+            .collect()
     }
 
     /// Prepares db update
     pub fn pre_save(&mut self, branches: HashMap<LevelIndex, TreeKey>) {
+        let overwritten: Vec<_> = branches
+            .keys()
+            .filter(|level_index| !self.pending_history.contains_key(level_index))
+            .cloned()
+            .collect();
+        let previous = self.hashes(overwritten.iter());
+        self.pending_history
+            .extend(overwritten.into_iter().zip(previous));
+
         for (level_index, value) in branches {
-            self.pending_patch
-                .0
-                .put(level_index.bin_key(), tree_key_to_u8_arr(&value));
+            let hash = tree_key_to_u8_arr(&value);
+            self.pending_patch.0.put(level_index.bin_key(), &hash);
+            self.pending_reads
+                .hashes
+                .insert(level_index.bin_key(), hash);
         }
     }
 
     /// Saves current state to db
     pub fn save(&mut self, block_number: u32) -> Result<(), TreeError> {
+        if let Some(pinned) = self.pinned {
+            return Err(TreeError::ReadOnlySnapshot(pinned));
+        }
         if self.pending_patch.0.is_empty() {
             return Err(TreeError::EmptyPatch);
         }
         let mut write_batch =
             std::mem::replace(&mut self.pending_patch, PendingPatch(WriteBatch::default())).0;
+        self.pending_reads = PendingReads::default();
+        if self.db.get(RETAINED_FROM_KEY)?.is_none() {
+            // History starts at the last saved state, trees created before
+            // history was recorded can't be read any further back.
+            let (_, saved_block) = self.fetch_metadata();
+            write_batch.put(RETAINED_FROM_KEY, serialize_block_number(saved_block));
+        }
+        let cf = self
+            .db
+            .cf_merkle_tree_handle(MerkleTreeColumnFamily::History);
+        for (level_index, hash) in self.pending_history.drain() {
+            write_batch.put_cf(
+                cf,
+                history_key(block_number, &level_index),
+                hash.unwrap_or_default(),
+            );
+        }
         write_batch.put(BLOCK_NUMBER_KEY, serialize_block_number(block_number));
 
         // Sync write is not used here intentionally. It somewhat improves write
@@ -86,6 +198,96 @@ impl Storage {
             .map_err(TreeError::StorageIoError)
     }
 
+    /// Drops the pending patch together with the history it would record.
+    pub fn discard_pending(&mut self) {
+        self.pending_patch = PendingPatch::default();
+        self.pending_history.clear();
+        self.pending_reads = PendingReads::default();
+    }
+
+    /// Returns the oldest block whose tree state can still be read.
+    pub fn retained_from(&self) -> u32 {
+        self.db
+            .get(RETAINED_FROM_KEY)
+            .expect("failed to fetch retained block")
+            .map(|bytes| deserialize_block_number(&bytes))
+            .unwrap_or_else(|| self.fetch_metadata().1)
+    }
+
+    /// Fetches hashes of merkle tree branches as they were saved at
+    /// `block_number`, replaying the history recorded by later saves.
+    pub fn hashes_at<'a, I: 'a>(
+        &'a self,
+        keys: I,
+        block_number: u32,
+    ) -> Result<Vec<Option<Vec<u8>>>, TreeError>
+    where
+        I: IntoIterator<Item = &'a LevelIndex>,
+    {
+        let (_, current_block) = self.fetch_metadata();
+        if block_number < self.retained_from() || block_number > current_block {
+            return Err(TreeError::VersionNotRetained(block_number));
+        }
+        // No shortcut for the current block: a save running concurrently
+        // records what it overwrites in the history replayed below, so the
+        // result is the same whether it lands before or after this read.
+        let keys: Vec<_> = keys.into_iter().collect();
+        let mut hashes = self.current_hashes(keys.iter().cloned());
+
+        let positions: HashMap<_, _> = keys
+            .iter()
+            .enumerate()
+            .map(|(position, level_index)| (level_index.bin_key(), position))
+            .collect();
+        let mut restored = HashSet::new();
+        let cf = self
+            .db
+            .cf_merkle_tree_handle(MerkleTreeColumnFamily::History);
+        // The first entry recorded after `block_number` holds the hash that
+        // was overwritten, later ones are superseded by it.
+        for (key, hash) in self
+            .db
+            .iter_cf_from(cf, &serialize_block_number(block_number + 1))
+        {
+            if let Some(&position) = positions.get(&key[4..]) {
+                if restored.insert(position) {
+                    hashes[position] = (!hash.is_empty()).then(|| hash.to_vec());
+                }
+            }
+        }
+        // a concurrent prune may have dropped history replayed above
+        if block_number < self.retained_from() {
+            return Err(TreeError::VersionNotRetained(block_number));
+        }
+        Ok(hashes)
+    }
+
+    /// Drops the history needed to read states older than `keep_blocks`
+    /// blocks before the last saved one. Archival storage keeps everything.
+    pub fn prune(&mut self, keep_blocks: u32) -> Result<(), TreeError> {
+        if let Some(pinned) = self.pinned {
+            return Err(TreeError::ReadOnlySnapshot(pinned));
+        }
+        let (_, current_block) = self.fetch_metadata();
+        let retain_from = current_block.saturating_sub(keep_blocks);
+        if self.archival || retain_from <= self.retained_from() {
+            return Ok(());
+        }
+        let cf = self
+            .db
+            .cf_merkle_tree_handle(MerkleTreeColumnFamily::History);
+        let mut write_batch = WriteBatch::default();
+        write_batch.delete_range_cf(
+            cf,
+            serialize_block_number(0),
+            serialize_block_number(retain_from + 1),
+        );
+        write_batch.put(RETAINED_FROM_KEY, serialize_block_number(retain_from));
+        self.db
+            .write(write_batch)
+            .map_err(TreeError::StorageIoError)
+    }
+
     /// Updates mapping between leaf index and its historical first occurrence
     /// and returns it
     ///
@@ -100,15 +302,18 @@ impl Storage {
         let cf = self
             .db
             .cf_merkle_tree_handle(MerkleTreeColumnFamily::LeafIndices);
-        let mut current_index = self
-            .db
-            .get_cf(cf, LEAF_INDEX_KEY)
-            .expect("failed to fetch current leaf index")
-            .map(|bytes| deserialize_leaf_index(&bytes))
-            .unwrap_or(1);
+        let mut current_index = self.pending_reads.leaf_index.unwrap_or_else(|| {
+            self.db
+                .get_cf(cf, LEAF_INDEX_KEY)
+                .expect("failed to fetch current leaf index")
+                .map(|bytes| deserialize_leaf_index(&bytes))
+                .unwrap_or(1)
+        });
 
         let mut write_batch = std::mem::take(&mut self.pending_patch).0;
         let mut new_writes = HashMap::new();
+        let mut deletes = Vec::new();
+        let pending_indices = &self.pending_reads.leaf_indices;
 
         let result = self
             .db
@@ -119,6 +324,10 @@ impl Storage {
             )
             .into_iter()
             .zip(storage_logs)
+            .map(|(raw_data, log)| match pending_indices.get(&log.1 .0) {
+                Some(index) => (Ok(index.map(serialize_leaf_index)), log),
+                None => (raw_data, log),
+            })
             .group_by(|(_, &(block, _))| block)
             .into_iter()
             .map(|(_block, group)| {
@@ -135,6 +344,7 @@ impl Storage {
                             // revert of first occurrence
                             (_, TreeOperation::Delete) => {
                                 write_batch.delete_cf(cf, serialize_tree_leaf(leaf));
+                                deletes.push(leaf);
                                 current_index -= 1;
                                 0
                             }
@@ -190,16 +400,61 @@ impl Storage {
 
         write_batch.put_cf(cf, LEAF_INDEX_KEY, serialize_leaf_index(current_index));
         self.pending_patch = PendingPatch(write_batch);
+        let pending = &mut self.pending_reads;
+        pending.leaf_index = Some(current_index);
+        pending.leaf_indices.extend(
+            new_writes
+                .into_iter()
+                .map(|(leaf, index)| (leaf, Some(index))),
+        );
+        pending
+            .leaf_indices
+            .extend(deletes.into_iter().map(|leaf| (leaf, None)));
 
         Ok(result)
     }
 
+    /// Fetches all saved leaves as (leaf index, key, value), sorted by leaf
+    /// index. Pending patches are not visible.
+    pub fn leaves(&self) -> Vec<(u64, TreeKey, TreeValue)> {
+        let cf = self
+            .db
+            .cf_merkle_tree_handle(MerkleTreeColumnFamily::LeafIndices);
+        let indices: Vec<(u64, TreeKey)> = self
+            .db
+            .iter_cf(cf)
+            .filter(|(key, _)| key.as_ref() != LEAF_INDEX_KEY)
+            .map(|(key, index)| {
+                (
+                    deserialize_leaf_index(&index),
+                    u8_arr_to_tree_key(&key.to_vec()),
+                )
+            })
+            .collect();
+        let level_indices: Vec<_> = indices
+            .iter()
+            .map(|(_, key)| tree_key_to_leaf_index(key))
+            .collect();
+        let mut leaves: Vec<_> = indices
+            .into_iter()
+            .zip(self.hashes(level_indices.iter()))
+            .map(|((index, key), value)| {
+                let value = value
+                    .map(|bytes| u8_arr_to_tree_key(&bytes))
+                    .unwrap_or_else(tree_value_default);
+                (index, key, value)
+            })
+            .collect();
+        leaves.sort_by_key(|(index, _, _)| *index);
+        leaves
+    }
+
     /// Fetches high-level metadata about merkle tree state
     pub fn fetch_metadata(&self) -> StoredTreeMetadata {
         // Fetch root hash. It is represented by level index (0, 0).
         let binding = (0, 0.into()).into();
         let keys = vec![&binding];
-        let root_hash = self.hashes(keys)[0].clone();
+        let root_hash = self.current_hashes(keys)[0].clone();
         // let root_hash = self.hashes(vec![&(0, 0.into()).into()])[0].clone();
 
         let block_number = self
@@ -232,6 +487,13 @@ pub(crate) fn serialize_leaf_index_to_key(leaf_index: u64) -> TreeKey {
     key
 }
 
+/// History entries are ordered by block first so pruning is a range delete.
+fn history_key(block_number: u32, level_index: &LevelIndex) -> Vec<u8> {
+    let mut key = serialize_block_number(block_number);
+    key.extend(level_index.bin_key());
+    key
+}
+
 pub(crate) fn serialize_leaf_index(leaf_index: u64) -> Vec<u8> {
     let mut bytes = vec![0; 8];
     BigEndian::write_u64(&mut bytes, leaf_index);
@@ -1,4 +1,4 @@
-use crate::merkle_tree::log::{StorageLogKind, WitnessStorageLog};
+use crate::merkle_tree::log::{StorageLog, StorageLogKind, WitnessStorageLog};
 use crate::merkle_tree::patch::{TreePatch, Update, UpdatesBatch};
 use crate::merkle_tree::storage::Storage;
 use crate::merkle_tree::tree_config::TreeConfig;
@@ -8,20 +8,23 @@ use crate::trace::trace::HashTrace;
 use crate::trace::trace::PoseidonRow;
 use crate::types::merkle_tree::constant::ROOT_TREE_DEPTH;
 use crate::types::merkle_tree::{
-    tree_key_default, tree_key_to_u256, u256_to_tree_key, u8_arr_to_tree_key, LeafIndices,
-    LevelIndex, NodeEntry, TreeKey, TreeMetadata, TreeOperation, TreeValue, ZkHash,
+    tree_key_default, tree_key_to_leaf_index, tree_key_to_u256, tree_key_to_u8_arr,
+    tree_value_default, u256_to_tree_key, u8_arr_to_tree_key, LeafIndices, LevelIndex, NodeEntry,
+    TreeDump, TreeKey, TreeLeaf, TreeMetadata, TreeOperation, TreeValue, ZkHash,
 };
 use crate::types::proof::StorageLogMetadata;
 use itertools::Itertools;
 use log::{debug, info};
 use std::borrow::{Borrow, BorrowMut};
 use std::collections::hash_map::Entry;
-use std::collections::{HashMap, HashSet};
+use std::collections::{BTreeMap, HashMap, HashSet};
 
 use std::iter::once;
 use std::sync::Arc;
 use tempfile::TempDir;
 
+use crate::crypto::hash::Hasher;
+use crate::crypto::poseidon_trace::PoseidonType;
 use crate::crypto::ZkHasher;
 use crate::storage::db::{Database, RocksDB};
 use web3::types::U256;
@@ -37,8 +40,14 @@ pub struct AccountTree {
 impl AccountTree {
     /// Creates new ZkSyncTree instance
     pub fn new(db: RocksDB) -> Self {
+        Self::new_with_hasher(db, ZkHasher::default())
+    }
+
+    /// Opens the tree with `hasher` for its levels. The roots stored in `db`
+    /// have to come from the same hasher.
+    pub fn new_with_hasher(db: RocksDB, hasher: ZkHasher) -> Self {
         let storage = Storage::new(db);
-        let config = TreeConfig::new(ZkHasher::default());
+        let config = TreeConfig::new(hasher);
         let (root_hash, block_number) = storage.fetch_metadata();
         let root_hash = root_hash.unwrap_or_else(|| config.default_root_hash());
         Self {
@@ -50,18 +59,13 @@ impl AccountTree {
     }
 
     pub fn new_test() -> Self {
+        Self::new_test_with_hasher(ZkHasher::default())
+    }
+
+    pub fn new_test_with_hasher(hasher: ZkHasher) -> Self {
         let db_path = TempDir::new().expect("failed get temporary directory for RocksDB");
         let db = RocksDB::new(Database::MerkleTree, db_path, true);
-        let storage = Storage::new(db);
-        let config = TreeConfig::new(ZkHasher::default());
-        let (root_hash, block_number) = storage.fetch_metadata();
-        let root_hash = root_hash.unwrap_or_else(|| config.default_root_hash());
-        Self {
-            storage,
-            config,
-            root_hash,
-            block_number,
-        }
+        Self::new_with_hasher(db, hasher)
     }
 
     pub fn new_db_test(db_path: String) -> Self {
@@ -90,8 +94,16 @@ impl AccountTree {
         self.block_number
     }
 
+    /// Value of the leaf at `key`, the default value if it was never written.
+    pub fn read_value(&self, key: &TreeKey) -> TreeValue {
+        self.storage
+            .hash(&tree_key_to_leaf_index(key))
+            .map(|bytes| u8_arr_to_tree_key(&bytes))
+            .unwrap_or_else(tree_value_default)
+    }
+
     /// Returns current hasher.
-    fn hasher(&self) -> &ZkHasher {
+    pub fn hasher(&self) -> &ZkHasher {
         self.config.hasher()
     }
 
@@ -459,4 +471,350 @@ impl AccountTree {
     pub fn save(&mut self) -> Result<(), TreeError> {
         self.storage.save(self.block_number)
     }
+
+    /// Saves the blocks processed since the last save, like `save` but having
+    /// nothing to save is not an error.
+    pub fn commit(&mut self) -> Result<(), TreeError> {
+        match self.save() {
+            Err(TreeError::EmptyPatch) => Ok(()),
+            res => res,
+        }
+    }
+
+    /// Drops the blocks processed since the last save, the tree is back at the
+    /// root and block number it last saved.
+    pub fn discard_pending(&mut self) {
+        self.storage.discard_pending();
+        if self.storage.pinned().is_none() {
+            let (root_hash, block_number) = self.storage.fetch_metadata();
+            self.root_hash = root_hash.unwrap_or_else(|| self.config.default_root_hash());
+            self.block_number = block_number;
+        }
+    }
+
+    /// Returns the root hash the tree had when `block_number` was saved.
+    pub fn root_hash_at(&self, block_number: u32) -> Result<ZkHash, TreeError> {
+        let root = (0, U256::zero()).into();
+        let root_hash = self.storage.hashes_at(once(&root), block_number)?[0].clone();
+        Ok(root_hash
+            .map(|bytes| u8_arr_to_tree_key(&bytes))
+            .unwrap_or_else(|| self.config.default_root_hash()))
+    }
+
+    /// Read-only tree sharing the db, reading the state saved at
+    /// `block_number` while this tree keeps processing and saving blocks.
+    /// Reads panic once `prune` drops the history of `block_number`.
+    pub fn view_at(&self, block_number: u32) -> Result<AccountTree, TreeError> {
+        let root_hash = self.root_hash_at(block_number)?;
+        Ok(self.pin(block_number, root_hash))
+    }
+
+    pub(crate) fn pin(&self, block_number: u32, root_hash: ZkHash) -> AccountTree {
+        AccountTree {
+            storage: self.storage.pinned_at(block_number),
+            config: self.config.clone(),
+            root_hash,
+            block_number,
+        }
+    }
+
+    /// Returns the oldest saved block whose state can still be read.
+    pub fn retained_from(&self) -> u32 {
+        self.storage.retained_from()
+    }
+
+    /// Keeps the tree in archival mode, where `prune` leaves history intact.
+    pub fn set_archival(&mut self, archival: bool) {
+        self.storage.set_archival(archival);
+    }
+
+    /// Drops node history older than `keep_blocks` blocks before the last
+    /// saved one.
+    pub fn prune(&mut self, keep_blocks: u32) -> Result<(), TreeError> {
+        self.storage.prune(keep_blocks)
+    }
+
+    /// Dumps the saved leaves of the tree, sorted by leaf index, together with
+    /// the root hash they produce.
+    pub fn export_leaves(&self) -> TreeDump {
+        let leaves = self
+            .storage
+            .leaves()
+            .into_iter()
+            .map(|(index, key, value)| TreeLeaf { index, key, value })
+            .collect();
+        TreeDump {
+            root_hash: self.root_hash(),
+            leaves,
+        }
+    }
+
+    /// Rebuilds an empty tree from a dump produced by `export_leaves` and saves
+    /// it. Nothing is saved if the rebuilt root differs from the dumped one.
+    pub fn import_leaves(&mut self, dump: &TreeDump) -> Result<(), TreeError> {
+        if !self.is_empty() {
+            return Err(TreeError::NonEmptyTree);
+        }
+        for (expected, leaf) in (1..).zip(dump.leaves.iter()) {
+            if leaf.index != expected {
+                return Err(TreeError::NonCanonicalDump(expected, leaf.index));
+            }
+        }
+        if dump.leaves.is_empty() {
+            return self.check_root(&dump.root_hash);
+        }
+
+        let (root_hash, block_number) = (self.root_hash(), self.block_number);
+        let logs: Vec<_> = dump
+            .leaves
+            .iter()
+            .map(|leaf| WitnessStorageLog {
+                storage_log: StorageLog::new_write_log(leaf.key, leaf.value),
+                previous_value: tree_value_default(),
+            })
+            .collect();
+        self.process_block(logs);
+        if let Err(err) = self.check_root(&dump.root_hash) {
+            self.storage.discard_pending();
+            self.root_hash = root_hash;
+            self.block_number = block_number;
+            return Err(err);
+        }
+        self.save()
+    }
+
+    fn check_root(&self, expected: &ZkHash) -> Result<(), TreeError> {
+        if self.root_hash != *expected {
+            return Err(TreeError::TreeRootsDiffer(
+                tree_key_to_u8_arr(&self.root_hash),
+                tree_key_to_u8_arr(expected),
+            ));
+        }
+        Ok(())
+    }
+}
+
+/// Computes the root of a tree holding `entries`, without a storage backend.
+/// Meant for genesis states and cross-checking state dumps; a later entry
+/// overwrites an earlier one with the same key.
+pub fn compute_root(entries: impl Iterator<Item = (TreeKey, TreeValue)>) -> TreeKey {
+    compute_root_with_hasher(entries, ZkHasher::default())
+}
+
+/// Same as `compute_root`, for a tree created with `hasher`.
+pub fn compute_root_with_hasher(
+    entries: impl Iterator<Item = (TreeKey, TreeValue)>,
+    hasher: ZkHasher,
+) -> TreeKey {
+    let config = TreeConfig::new(hasher);
+    let mut level: BTreeMap<U256, TreeKey> = entries
+        .map(|(key, value)| (tree_key_to_u256(&key), value))
+        .collect();
+    for depth in 0..ROOT_TREE_DEPTH {
+        let empty = *config.empty_tree()[ROOT_TREE_DEPTH - depth].hash();
+        let node_type = if depth == 0 {
+            PoseidonType::Leaf
+        } else {
+            PoseidonType::Branch
+        };
+        let (parents, pairs): (Vec<_>, Vec<_>) = level
+            .iter()
+            .group_by(|(idx, _)| **idx >> 1)
+            .into_iter()
+            .map(|(parent, children)| {
+                let (mut left, mut right) = (empty, empty);
+                for (idx, hash) in children {
+                    if idx.bit(0) {
+                        right = *hash;
+                    } else {
+                        left = *hash;
+                    }
+                }
+                (parent, (left, right))
+            })
+            .unzip();
+        let hashes = config.hasher().compress_batch(&pairs, node_type);
+        level = parents.into_iter().zip(hashes).collect();
+    }
+    level
+        .remove(&U256::zero())
+        .unwrap_or_else(|| config.default_root_hash())
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::crypto::hash::Hasher;
+    use crate::crypto::TreeHasher;
+    use crate::merkle_tree::log::{StorageLog, WitnessStorageLog};
+    use crate::merkle_tree::tree::{compute_root, compute_root_with_hasher, AccountTree};
+    use crate::trace::trace::Trace;
+    use crate::types::merkle_tree::tree_value_default;
+    use plonky2::field::goldilocks_field::GoldilocksField;
+    use plonky2::field::types::Field;
+
+    #[test]
+    fn test_export_import_leaves() {
+        let word = |v: u64| [GoldilocksField::from_canonical_u64(v); 4];
+        let mut tree = AccountTree::new_test();
+        tree.process_block((1..4).map(|i| WitnessStorageLog {
+            storage_log: StorageLog::new_write_log(word(i), word(i * 10)),
+            previous_value: tree_value_default(),
+        }));
+        tree.save().unwrap();
+
+        let dump = tree.export_leaves();
+        assert_eq!(dump.leaves.len(), 3);
+        assert_eq!(dump.leaves[1].index, 2);
+        assert_eq!(dump.leaves[1].value, word(20));
+
+        let mut restored = AccountTree::new_test();
+        restored.import_leaves(&dump).unwrap();
+        assert_eq!(restored.root_hash(), tree.root_hash());
+        assert_eq!(restored.export_leaves(), dump);
+        assert!(restored.import_leaves(&dump).is_err());
+
+        let mut tampered = dump.clone();
+        tampered.leaves[0].value = word(7);
+        let mut other = AccountTree::new_test();
+        assert!(other.import_leaves(&tampered).is_err());
+        assert!(other.is_empty());
+    }
+
+    #[test]
+    fn test_prune_history() {
+        let word = |v: u64| [GoldilocksField::from_canonical_u64(v); 4];
+        let mut tree = AccountTree::new_test();
+        let mut roots = vec![tree.root_hash()];
+        for i in 1..5 {
+            tree.process_block(vec![WitnessStorageLog {
+                storage_log: StorageLog::new_write_log(word(1), word(i)),
+                previous_value: tree_value_default(),
+            }]);
+            tree.save().unwrap();
+            roots.push(tree.root_hash());
+        }
+
+        assert_eq!(tree.retained_from(), 0);
+        for (block, root) in roots.iter().enumerate() {
+            assert_eq!(tree.root_hash_at(block as u32).unwrap(), *root);
+        }
+        assert!(tree.root_hash_at(5).is_err());
+
+        tree.set_archival(true);
+        tree.prune(1).unwrap();
+        assert_eq!(tree.root_hash_at(0).unwrap(), roots[0]);
+
+        tree.set_archival(false);
+        tree.prune(1).unwrap();
+        assert_eq!(tree.retained_from(), 3);
+        assert!(tree.root_hash_at(2).is_err());
+        assert_eq!(tree.root_hash_at(3).unwrap(), roots[3]);
+        assert_eq!(tree.root_hash_at(4).unwrap(), roots[4]);
+    }
+
+    #[test]
+    fn test_blocks_before_save() {
+        let word = |v: u64| [GoldilocksField::from_canonical_u64(v); 4];
+        let write = |key: u64, value: u64| WitnessStorageLog {
+            storage_log: StorageLog::new_write_log(word(key), word(value)),
+            previous_value: tree_value_default(),
+        };
+        let blocks = [
+            vec![write(1, 1), write(2, 2)],
+            vec![write(2, 3), write(3, 4)],
+        ];
+        let mut saved = AccountTree::new_test();
+        for block in &blocks {
+            saved.process_block(block);
+            saved.save().unwrap();
+        }
+
+        // the second block reads what the first one left pending
+        let mut tree = AccountTree::new_test();
+        for block in &blocks {
+            tree.process_block(block);
+        }
+        assert_eq!(tree.root_hash(), saved.root_hash());
+        assert_eq!(tree.read_value(&word(2)), word(3));
+        tree.discard_pending();
+        assert!(tree.is_empty());
+        assert_eq!(tree.read_value(&word(2)), tree_value_default());
+
+        for block in &blocks {
+            tree.process_block(block);
+        }
+        tree.save().unwrap();
+        assert_eq!(tree.root_hash(), saved.root_hash());
+        assert_eq!(tree.block_number(), saved.block_number());
+        assert_eq!(tree.export_leaves(), saved.export_leaves());
+    }
+
+    #[test]
+    fn test_tree_hasher() {
+        let word = |v: u64| [GoldilocksField::from_canonical_u64(v); 4];
+        let logs: Vec<_> = (1..3)
+            .map(|i| WitnessStorageLog {
+                storage_log: StorageLog::new_write_log(word(i), word(i * 10)),
+                previous_value: tree_value_default(),
+            })
+            .collect();
+        let mut poseidon = AccountTree::new_test();
+        let mut poseidon2 = AccountTree::new_test_with_hasher(TreeHasher::Poseidon2);
+        assert_ne!(poseidon.root_hash(), poseidon2.root_hash());
+        assert!(poseidon2.is_empty());
+
+        let (traces, _) = poseidon.process_block(logs.iter());
+        let (traces2, _) = poseidon2.process_block(logs.iter());
+        assert_eq!(traces.len(), traces2.len());
+        assert_ne!(poseidon.root_hash(), poseidon2.root_hash());
+        assert_eq!(
+            traces2.last().unwrap().0.output[0..4],
+            poseidon2.root_hash()
+        );
+
+        let mut trace = Trace::default();
+        poseidon.hasher().record_level(&mut trace, &traces[0]);
+        assert_eq!(trace.builtin_poseidon.len(), 2);
+        let mut trace = Trace::default();
+        poseidon2.hasher().record_level(&mut trace, &traces2[0]);
+        assert!(trace.builtin_poseidon.is_empty());
+        assert_eq!(trace.tree_hasher, TreeHasher::Poseidon2);
+    }
+
+    #[test]
+    fn test_compute_root() {
+        let word = |v: u64| [GoldilocksField::from_canonical_u64(v); 4];
+        assert_eq!(
+            compute_root(std::iter::empty()),
+            AccountTree::new_test().root_hash()
+        );
+
+        let entries: Vec<_> = [1, 2, 3, 255, 1 << 40]
+            .into_iter()
+            .map(|i| (word(i), word(i * 10)))
+            .collect();
+        for hasher in [TreeHasher::Poseidon, TreeHasher::Poseidon2] {
+            let mut tree = AccountTree::new_test_with_hasher(hasher);
+            tree.process_block(entries.iter().map(|(key, value)| WitnessStorageLog {
+                storage_log: StorageLog::new_write_log(*key, *value),
+                previous_value: tree_value_default(),
+            }));
+            assert_eq!(
+                compute_root_with_hasher(entries.iter().cloned(), hasher),
+                tree.root_hash()
+            );
+        }
+
+        // the last entry of a key wins.
+        let mut updated = entries.clone();
+        updated[1].1 = word(7);
+        assert_eq!(
+            compute_root(
+                entries
+                    .into_iter()
+                    .chain(std::iter::once((word(2), word(7))))
+            ),
+            compute_root(updated.into_iter())
+        );
+    }
 }
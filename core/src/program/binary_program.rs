@@ -19,6 +19,9 @@ pub struct BinaryProgram {
     pub prophets: Vec<OlaProphet>,
     #[serde(skip_serializing)]
     pub debug_info: Option<BTreeMap<usize, String>>,
+    /// Number of return registers declared with `.returns`.
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub return_arity: Option<usize>,
 }
 
 impl BinaryProgram {
@@ -48,12 +51,14 @@ impl BinaryProgram {
                 bytecode,
                 prophets,
                 debug_info,
+                return_arity: None,
             })
         } else {
             Ok(BinaryProgram {
                 bytecode,
                 prophets,
                 debug_info: None,
+                return_arity: None,
             })
         }
     }
@@ -85,7 +90,8 @@ impl BinaryInstruction {
         };
         len += match self.op1 {
             Some(OlaOperand::ImmediateOperand { .. })
-            | Some(OlaOperand::RegisterWithOffset { .. }) => 1,
+            | Some(OlaOperand::RegisterWithOffset { .. })
+            | Some(OlaOperand::RegisterWithFactor { .. }) => 1,
             _ => 0,
         };
         len
@@ -323,7 +329,9 @@ impl BinaryInstruction {
             | OlaOpcode::NEQ
             | OlaOpcode::GTE
             | OlaOpcode::TLOAD
-            | OlaOpcode::POSEIDON => {
+            | OlaOpcode::POSEIDON
+            | OlaOpcode::SSCAN
+            | OlaOpcode::CMOV => {
                 format!(
                     "{} {} {} {}",
                     self.opcode.token(),
@@ -333,7 +341,12 @@ impl BinaryInstruction {
                 )
             }
 
-            OlaOpcode::MOV | OlaOpcode::NOT | OlaOpcode::MLOAD | OlaOpcode::TSTORE => {
+            OlaOpcode::MOV
+            | OlaOpcode::NOT
+            | OlaOpcode::MLOAD
+            | OlaOpcode::TSTORE
+            | OlaOpcode::TLOADT
+            | OlaOpcode::TXCTX => {
                 format!(
                     "{} {} {}",
                     self.opcode.token(),
@@ -351,7 +364,20 @@ impl BinaryInstruction {
                 )
             }
 
-            OlaOpcode::CJMP | OlaOpcode::SCCALL | OlaOpcode::SLOAD | OlaOpcode::SSTORE => {
+            OlaOpcode::CJMP
+            | OlaOpcode::SCCALL
+            | OlaOpcode::SLOAD
+            | OlaOpcode::SSTORE
+            | OlaOpcode::TSTORET => {
+                format!(
+                    "{} {} {}",
+                    self.opcode.token(),
+                    self.op0.clone().unwrap().get_asm_token(),
+                    self.op1.clone().unwrap().get_asm_token()
+                )
+            }
+
+            OlaOpcode::ASSERT if self.op0.is_some() => {
                 format!(
                     "{} {} {}",
                     self.opcode.token(),
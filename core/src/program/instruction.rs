@@ -53,6 +53,15 @@ pub enum Opcode {
     TLOAD = 9,
     TSTORE = 8,
     SCCALL = 7,
+    // transient storage, reset per tx
+    TLOADT = 6,
+    TSTORET = 5,
+    // bounded iteration over storage slots
+    SSCAN = 4,
+    // chain id and tx hash of the running tx
+    TXCTX = 3,
+    // dst = op1 if op0 is 1, else dst is kept
+    CMOV = 2,
 }
 
 impl fmt::Display for Opcode {
@@ -83,6 +92,11 @@ impl fmt::Display for Opcode {
             Opcode::TLOAD => write!(f, "tload"),
             Opcode::TSTORE => write!(f, "tstore"),
             Opcode::SCCALL => write!(f, "sccall"),
+            Opcode::TLOADT => write!(f, "tload_t"),
+            Opcode::TSTORET => write!(f, "tstore_t"),
+            Opcode::SSCAN => write!(f, "sscan"),
+            Opcode::TXCTX => write!(f, "txctx"),
+            Opcode::CMOV => write!(f, "cmov"),
         }
     }
 }
@@ -1,9 +1,12 @@
-use crate::program::binary_program::OlaProphet;
+use crate::crypto::poseidon_trace::calculate_arbitrary_poseidon;
+use crate::program::binary_program::{BinaryProgram, OlaProphet};
 use crate::trace::trace::Trace;
+use crate::vm::error::ProcessorError;
 use plonky2::field::goldilocks_field::GoldilocksField;
-use plonky2::field::types::Field64;
+use plonky2::field::types::{Field, Field64};
 use serde::{Deserialize, Serialize};
 use std::collections::{BTreeMap, HashMap};
+use std::num::ParseIntError;
 
 pub mod binary_program;
 pub mod decoder;
@@ -13,8 +16,17 @@ pub mod instruction;
 pub const REGISTER_NUM: usize = 10;
 
 pub const CTX_REGISTER_NUM: usize = 4;
+
+/// Registers `r0..` a program may return values in at `end`.
+pub const MAX_RETURN_VALUES: usize = 4;
+
 pub const FIELD_ORDER: u64 = GoldilocksField::ORDER;
 
+/// Code words a program may have unless a chain spec sets its own limit, the
+/// row capacity of a default block. The program table takes a row per word
+/// and prog_chunk one per 8 words, a larger program can't be committed to.
+pub const MAX_PROGRAM_WORDS: usize = 1 << 22;
+
 #[derive(Clone, Debug, Serialize, Deserialize, Default)]
 pub struct Program {
     pub instructions: Vec<String>,
@@ -23,6 +35,93 @@ pub struct Program {
     pub print_flag: bool,
     pub prophets: HashMap<u64, OlaProphet>,
     pub pre_exe_flag: bool,
+    /// Number of registers `r0..` holding the return values at the `end` of
+    /// the entry program, from the `.returns` directive.
+    pub return_arity: Option<usize>,
 }
 
 impl Program {}
+
+/// Check a return arity is at most `MAX_RETURN_VALUES`.
+pub fn check_return_arity(arity: Option<usize>) -> Result<(), ProcessorError> {
+    match arity {
+        Some(n) if n > MAX_RETURN_VALUES => {
+            Err(ProcessorError::InvalidReturnArity(n, MAX_RETURN_VALUES))
+        }
+        _ => Ok(()),
+    }
+}
+
+/// Check a program of `words` code words is within `max_words`.
+pub fn check_program_size(words: usize, max_words: usize) -> Result<(), ProcessorError> {
+    if words > max_words {
+        return Err(ProcessorError::ProgramTooLarge(words, max_words));
+    }
+    Ok(())
+}
+
+/// Decode the hex bytecode lines of `program` into code words.
+pub fn program_code(program: &BinaryProgram) -> Result<Vec<GoldilocksField>, ParseIntError> {
+    program
+        .bytecode
+        .split('\n')
+        .map(|e| {
+            u64::from_str_radix(e.trim_start_matches("0x"), 16)
+                .map(GoldilocksField::from_canonical_u64)
+        })
+        .collect()
+}
+
+/// Chunked poseidon commitment of the program code, the same value the
+/// prog_chunk table ends on and the code hash the account tree stores.
+///
+/// Panics if the bytecode is not valid hex, see `program_code`.
+pub fn hash_program(program: &BinaryProgram) -> [GoldilocksField; 4] {
+    let code = program_code(program).expect("invalid program bytecode");
+    calculate_arbitrary_poseidon(&code)
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::crypto::hash::Hasher;
+    use crate::crypto::ZkHasher;
+    use crate::program::binary_program::BinaryProgram;
+    use crate::program::{check_program_size, hash_program, program_code, MAX_PROGRAM_WORDS};
+    use crate::vm::error::ProcessorError;
+
+    #[test]
+    fn test_hash_program() {
+        let program = BinaryProgram {
+            bytecode: (1..=19)
+                .map(|i| format!("0x{:016x}", i * 0x1000_0001))
+                .collect::<Vec<_>>()
+                .join("\n"),
+            prophets: vec![],
+            debug_info: None,
+            return_arity: None,
+        };
+        let code = program_code(&program).unwrap();
+        assert_eq!(code.len(), 19);
+        assert_eq!(
+            hash_program(&program),
+            ZkHasher::default().hash_bytes(&code)
+        );
+    }
+
+    #[test]
+    fn test_check_program_size() {
+        assert!(check_program_size(MAX_PROGRAM_WORDS, MAX_PROGRAM_WORDS).is_ok());
+        let err = check_program_size(MAX_PROGRAM_WORDS + 1, MAX_PROGRAM_WORDS).unwrap_err();
+        assert!(
+            matches!(err, ProcessorError::ProgramTooLarge(words, _) if words == MAX_PROGRAM_WORDS + 1)
+        );
+        assert_eq!(
+            err.to_string(),
+            format!(
+                "program has {} code words, over the limit {}",
+                MAX_PROGRAM_WORDS + 1,
+                MAX_PROGRAM_WORDS
+            )
+        );
+    }
+}
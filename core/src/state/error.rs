@@ -1,9 +1,12 @@
+use crate::merkle_tree::TreeError;
 use thiserror::Error;
 
 #[derive(Error, Debug)]
 pub enum StateError {
     #[error("storage access error")]
     StorageIoError(#[from] rocksdb::Error),
+    #[error("account tree error: {0}")]
+    TreeError(#[from] TreeError),
     #[error("VM execute error:{0}")]
     VmExecError(String),
     #[error("VM json serde error")]
@@ -120,6 +120,10 @@ where
             &mut self.txs_trace.get_mut(&0).unwrap().tape,
             Vec::new(),
         ));
+        trace.transient.extend(std::mem::replace(
+            &mut self.txs_trace.get_mut(&0).unwrap().transient,
+            Vec::new(),
+        ));
         trace.exec.extend(std::mem::replace(
             &mut self.txs_trace.get_mut(&0).unwrap().exec,
             Vec::new(),
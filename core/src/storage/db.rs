@@ -1,8 +1,8 @@
 use log::info;
 use once_cell::sync::Lazy;
 use rocksdb::{
-    AsColumnFamilyRef, BlockBasedOptions, ColumnFamily, ColumnFamilyDescriptor, Options,
-    WriteBatch, DB,
+    AsColumnFamilyRef, BlockBasedOptions, ColumnFamily, ColumnFamilyDescriptor, Direction,
+    IteratorMode, Options, WriteBatch, DB,
 };
 use std::path::Path;
 use std::sync::{Condvar, Mutex};
@@ -32,6 +32,7 @@ pub enum Database {
 pub enum MerkleTreeColumnFamily {
     Tree,
     LeafIndices,
+    History,
 }
 
 #[derive(Debug)]
@@ -54,7 +55,7 @@ pub enum SequencerColumnFamily {
 
 impl MerkleTreeColumnFamily {
     fn all() -> &'static [Self] {
-        &[Self::Tree, Self::LeafIndices]
+        &[Self::Tree, Self::LeafIndices, Self::History]
     }
 }
 
@@ -86,6 +87,7 @@ impl std::fmt::Display for MerkleTreeColumnFamily {
         let value = match self {
             MerkleTreeColumnFamily::Tree => "default",
             MerkleTreeColumnFamily::LeafIndices => "leaf_indices",
+            MerkleTreeColumnFamily::History => "history",
         };
         write!(formatter, "{}", value)
     }
@@ -232,6 +234,28 @@ impl RocksDB {
         self.db.get_cf(cf, key)
     }
 
+    /// Iterates all key-value pairs of a column family in key order.
+    pub fn iter_cf<'a>(
+        &'a self,
+        cf: &'a impl AsColumnFamilyRef,
+    ) -> impl Iterator<Item = (Box<[u8]>, Box<[u8]>)> + 'a {
+        self.db
+            .iterator_cf(cf, IteratorMode::Start)
+            .map(|item| item.expect("failed to iterate column family"))
+    }
+
+    /// Iterates key-value pairs of a column family in key order, starting at
+    /// the first key not less than `from`.
+    pub fn iter_cf_from<'a>(
+        &'a self,
+        cf: &'a impl AsColumnFamilyRef,
+        from: &[u8],
+    ) -> impl Iterator<Item = (Box<[u8]>, Box<[u8]>)> + 'a {
+        self.db
+            .iterator_cf(cf, IteratorMode::From(from, Direction::Forward))
+            .map(|item| item.expect("failed to iterate column family"))
+    }
+
     /// awaits termination of all running rocksdb instances
     pub fn await_rocksdb_termination() {
         let (lock, cvar) = &*ROCKSDB_INSTANCE_COUNTER;
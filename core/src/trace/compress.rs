@@ -0,0 +1,275 @@
+//! Compact encoding of stored traces.
+//!
+//! Row tables are stored column by column. Every column is delta encoded, as
+//! clks, addresses and counters are nearly monotone, and the deltas are
+//! written as zigzag varints, then the whole package is compressed with zstd.
+//! Tables whose rows hold something other than u64s and bools, and every
+//! other field of the trace, are kept as json.
+//!
+//! Layout: `OLAW`, format version byte, then zstd of the json header length
+//! (u32 le), the json header and the encoded columns in header order.
+
+use crate::trace::schema::{migrate_trace, TraceSchemaError};
+use crate::trace::trace::Trace;
+use serde::{Deserialize, Serialize};
+use serde_json::{Map, Value};
+use thiserror::Error;
+
+const MAGIC: &[u8; 4] = b"OLAW";
+const FORMAT_VERSION: u8 = 1;
+pub const DEFAULT_LEVEL: i32 = 3;
+
+#[derive(Error, Debug)]
+pub enum TraceCompressError {
+    #[error("trace compress io error")]
+    IoError(#[from] std::io::Error),
+    #[error("trace serde error")]
+    JsonSerdeError(#[from] serde_json::Error),
+    #[error("trace schema error: {0}")]
+    Schema(#[from] TraceSchemaError),
+    #[error("not a compressed trace")]
+    BadMagic,
+    #[error("compressed trace format {0} is not supported")]
+    UnsupportedFormat(u8),
+    #[error("compressed trace is truncated")]
+    Truncated,
+}
+
+#[derive(Debug, Serialize, Deserialize)]
+struct TableHeader {
+    name: String,
+    /// First row, its leaves are replaced by the column values of each row.
+    template: Value,
+    rows: usize,
+}
+
+#[derive(Debug, Serialize, Deserialize)]
+struct Header {
+    rest: Value,
+    tables: Vec<TableHeader>,
+}
+
+/// Leaves in `flatten_value` order, `None` if a leaf is not a u64 or bool.
+fn leaves(value: &Value, out: &mut Vec<u64>) -> Option<()> {
+    match value {
+        Value::Number(n) => out.push(n.as_u64()?),
+        Value::Bool(b) => out.push(*b as u64),
+        Value::Array(items) => items.iter().try_for_each(|item| leaves(item, out))?,
+        Value::Object(fields) => fields.values().try_for_each(|item| leaves(item, out))?,
+        Value::Null | Value::String(_) => return None,
+    }
+    Some(())
+}
+
+/// Whether `value` has the shape of `template`, leaf kinds included.
+fn same_shape(template: &Value, value: &Value) -> bool {
+    match (template, value) {
+        (Value::Number(_), Value::Number(_)) | (Value::Bool(_), Value::Bool(_)) => true,
+        (Value::Array(a), Value::Array(b)) => {
+            a.len() == b.len() && a.iter().zip(b).all(|(a, b)| same_shape(a, b))
+        }
+        (Value::Object(a), Value::Object(b)) => {
+            a.len() == b.len()
+                && a.iter()
+                    .zip(b)
+                    .all(|((ka, va), (kb, vb))| ka == kb && same_shape(va, vb))
+        }
+        _ => false,
+    }
+}
+
+/// `template` with its leaves taken from `values` in order.
+fn fill(template: &Value, values: &mut impl Iterator<Item = u64>) -> Value {
+    match template {
+        Value::Number(_) => Value::from(values.next().unwrap_or_default()),
+        Value::Bool(_) => Value::Bool(values.next().unwrap_or_default() != 0),
+        Value::Array(items) => Value::Array(items.iter().map(|item| fill(item, values)).collect()),
+        Value::Object(fields) => Value::Object(
+            fields
+                .iter()
+                .map(|(key, item)| (key.clone(), fill(item, values)))
+                .collect::<Map<_, _>>(),
+        ),
+        other => other.clone(),
+    }
+}
+
+/// Columns of a row table, `None` if its rows cannot be stored as columns.
+fn columns(rows: &[Value]) -> Option<Vec<Vec<u64>>> {
+    let template = rows.first()?;
+    let mut first = vec![];
+    leaves(template, &mut first)?;
+    let mut columns: Vec<Vec<u64>> = first.into_iter().map(|v| vec![v]).collect();
+    for row in &rows[1..] {
+        if !same_shape(template, row) {
+            return None;
+        }
+        let mut cells = vec![];
+        leaves(row, &mut cells)?;
+        for (column, cell) in columns.iter_mut().zip(cells) {
+            column.push(cell);
+        }
+    }
+    Some(columns)
+}
+
+fn write_varint(mut value: u64, out: &mut Vec<u8>) {
+    while value >= 0x80 {
+        out.push(value as u8 | 0x80);
+        value >>= 7;
+    }
+    out.push(value as u8);
+}
+
+fn read_varint(bytes: &[u8], pos: &mut usize) -> Result<u64, TraceCompressError> {
+    let mut value = 0u64;
+    for shift in (0..64).step_by(7) {
+        let byte = *bytes.get(*pos).ok_or(TraceCompressError::Truncated)?;
+        *pos += 1;
+        value |= ((byte & 0x7f) as u64) << shift;
+        if byte & 0x80 == 0 {
+            return Ok(value);
+        }
+    }
+    Err(TraceCompressError::Truncated)
+}
+
+/// Deltas to the previous value as zigzag varints.
+fn encode_column(column: &[u64], out: &mut Vec<u8>) {
+    let mut prev = 0u64;
+    for &value in column {
+        let delta = value.wrapping_sub(prev) as i64;
+        write_varint(((delta << 1) ^ (delta >> 63)) as u64, out);
+        prev = value;
+    }
+}
+
+fn decode_column(
+    bytes: &[u8],
+    pos: &mut usize,
+    rows: usize,
+) -> Result<Vec<u64>, TraceCompressError> {
+    let mut column = Vec::with_capacity(rows);
+    let mut prev = 0u64;
+    for _ in 0..rows {
+        let zigzag = read_varint(bytes, pos)?;
+        let delta = (zigzag >> 1) as i64 ^ -((zigzag & 1) as i64);
+        prev = prev.wrapping_add(delta as u64);
+        column.push(prev);
+    }
+    Ok(column)
+}
+
+/// Compresses `trace` at zstd `level`, `DEFAULT_LEVEL` is a good trade off.
+pub fn compress_trace(trace: &Trace, level: i32) -> Result<Vec<u8>, TraceCompressError> {
+    let mut rest = serde_json::to_value(trace)?;
+    let mut tables = vec![];
+    let mut data = vec![];
+    if let Value::Object(fields) = &mut rest {
+        let names: Vec<String> = fields.keys().cloned().collect();
+        for name in names {
+            let rows = match fields.get(&name) {
+                Some(Value::Array(rows)) if !rows.is_empty() => rows,
+                _ => continue,
+            };
+            if let Some(columns) = columns(rows) {
+                tables.push(TableHeader {
+                    name: name.clone(),
+                    template: rows[0].clone(),
+                    rows: rows.len(),
+                });
+                for column in &columns {
+                    encode_column(column, &mut data);
+                }
+                fields.remove(&name);
+            }
+        }
+    }
+
+    let header = serde_json::to_vec(&Header { rest, tables })?;
+    let mut payload = Vec::with_capacity(4 + header.len() + data.len());
+    payload.extend_from_slice(&(header.len() as u32).to_le_bytes());
+    payload.extend_from_slice(&header);
+    payload.extend_from_slice(&data);
+
+    let mut out = MAGIC.to_vec();
+    out.push(FORMAT_VERSION);
+    out.extend(zstd::encode_all(payload.as_slice(), level)?);
+    Ok(out)
+}
+
+/// Restores a trace written by `compress_trace`, migrating older layouts.
+pub fn decompress_trace(bytes: &[u8]) -> Result<Trace, TraceCompressError> {
+    if bytes.len() < MAGIC.len() + 1 || &bytes[..MAGIC.len()] != MAGIC {
+        return Err(TraceCompressError::BadMagic);
+    }
+    if bytes[MAGIC.len()] != FORMAT_VERSION {
+        return Err(TraceCompressError::UnsupportedFormat(bytes[MAGIC.len()]));
+    }
+    let payload = zstd::decode_all(&bytes[MAGIC.len() + 1..])?;
+    let header_len = payload
+        .get(..4)
+        .map(|len| u32::from_le_bytes(len.try_into().unwrap()) as usize)
+        .ok_or(TraceCompressError::Truncated)?;
+    let header: Header = serde_json::from_slice(
+        payload
+            .get(4..4 + header_len)
+            .ok_or(TraceCompressError::Truncated)?,
+    )?;
+
+    let mut pos = 4 + header_len;
+    let mut trace = header.rest;
+    for table in header.tables {
+        let mut num_columns = vec![];
+        leaves(&table.template, &mut num_columns);
+        let columns = num_columns
+            .iter()
+            .map(|_| decode_column(&payload, &mut pos, table.rows))
+            .collect::<Result<Vec<_>, _>>()?;
+        let rows: Vec<Value> = (0..table.rows)
+            .map(|row| {
+                fill(
+                    &table.template,
+                    &mut columns.iter().map(|column| column[row]),
+                )
+            })
+            .collect();
+        trace[table.name.as_str()] = Value::Array(rows);
+    }
+    Ok(migrate_trace(trace)?)
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::trace::compress::{compress_trace, decompress_trace, DEFAULT_LEVEL};
+    use crate::trace::trace::{TapeRow, Trace};
+    use plonky2::field::goldilocks_field::GoldilocksField;
+    use plonky2::field::types::{Field, Field64};
+
+    #[test]
+    fn test_compress_trace_roundtrip() {
+        let mut trace = Trace::default();
+        for i in 0..1000u64 {
+            trace.insert_tape(TapeRow {
+                is_init: i < 4,
+                opcode: GoldilocksField::from_canonical_u64(i % 3),
+                addr: GoldilocksField::from_canonical_u64(i),
+                value: GoldilocksField::from_canonical_u64(GoldilocksField::ORDER - 1 - i),
+                filter_looked: GoldilocksField::ONE,
+            });
+        }
+        trace.ret.push(GoldilocksField::from_canonical_u64(9));
+
+        let bytes = compress_trace(&trace, DEFAULT_LEVEL).unwrap();
+        let json = serde_json::to_vec(&trace).unwrap();
+        assert!(bytes.len() * 10 < json.len());
+        let restored = decompress_trace(&bytes).unwrap();
+        assert_eq!(
+            serde_json::to_value(&restored).unwrap(),
+            serde_json::to_value(&trace).unwrap()
+        );
+
+        assert!(decompress_trace(b"OLAW").is_err());
+        assert!(decompress_trace(&bytes[..bytes.len() / 2]).is_err());
+    }
+}
@@ -0,0 +1,220 @@
+use crate::trace::trace::Trace;
+use serde::Serialize;
+use serde_json::Value;
+use thiserror::Error;
+
+#[cfg(feature = "arrow")]
+use arrow::array::{ArrayRef, UInt64Array};
+#[cfg(feature = "arrow")]
+use arrow::datatypes::{DataType, Field as ArrowField, Schema};
+#[cfg(feature = "arrow")]
+use arrow::record_batch::RecordBatch;
+#[cfg(feature = "arrow")]
+use std::path::{Path, PathBuf};
+#[cfg(feature = "arrow")]
+use std::sync::Arc;
+
+#[derive(Error, Debug)]
+pub enum TraceExportError {
+    #[error("trace export io error")]
+    IoError(#[from] std::io::Error),
+    #[error("trace row serde error")]
+    JsonSerdeError(#[from] serde_json::Error),
+    #[error("table {0}: row {1} has a different column layout")]
+    RaggedRow(String, usize),
+    #[error("column {0}: {1} is not a u64")]
+    UnsupportedValue(String, Value),
+    #[cfg(feature = "arrow")]
+    #[error("arrow error")]
+    ArrowError(#[from] arrow::error::ArrowError),
+    #[cfg(feature = "arrow")]
+    #[error("parquet error")]
+    ParquetError(#[from] parquet::errors::ParquetError),
+}
+
+/// A trace table flattened into named u64 columns.
+///
+/// Nested rows are flattened with `.` between struct fields and `_i` for array
+/// elements, e.g. `register_selector.op0_reg_sel_3`. Booleans become 0/1, any
+/// other value that is not a u64 is an error.
+#[derive(Debug, Clone, Default)]
+pub struct TraceTable {
+    pub name: String,
+    pub columns: Vec<(String, Vec<u64>)>,
+}
+
+impl TraceTable {
+    pub fn num_rows(&self) -> usize {
+        self.columns.first().map(|c| c.1.len()).unwrap_or(0)
+    }
+
+    pub fn column(&self, name: &str) -> Option<&Vec<u64>> {
+        self.columns.iter().find(|c| c.0 == name).map(|c| &c.1)
+    }
+
+    pub fn from_rows<T: Serialize>(name: &str, rows: &[T]) -> Result<Self, TraceExportError> {
+        let mut table = TraceTable {
+            name: name.to_string(),
+            columns: Vec::new(),
+        };
+        for (row_idx, row) in rows.iter().enumerate() {
+            let mut cells = Vec::new();
+            flatten_value(String::new(), &serde_json::to_value(row)?, &mut cells)?;
+            if row_idx == 0 {
+                table.columns = cells
+                    .into_iter()
+                    .map(|(name, value)| (name, vec![value]))
+                    .collect();
+                continue;
+            }
+            if cells.len() != table.columns.len() {
+                return Err(TraceExportError::RaggedRow(name.to_string(), row_idx));
+            }
+            for (column, (cell_name, value)) in table.columns.iter_mut().zip(cells) {
+                if column.0 != cell_name {
+                    return Err(TraceExportError::RaggedRow(name.to_string(), row_idx));
+                }
+                column.1.push(value);
+            }
+        }
+        Ok(table)
+    }
+
+    #[cfg(feature = "arrow")]
+    pub fn to_record_batch(&self) -> Result<RecordBatch, TraceExportError> {
+        let schema = Schema::new(
+            self.columns
+                .iter()
+                .map(|(name, _)| ArrowField::new(name, DataType::UInt64, false))
+                .collect::<Vec<_>>(),
+        );
+        let arrays = self
+            .columns
+            .iter()
+            .map(|(_, values)| Arc::new(UInt64Array::from(values.clone())) as ArrayRef)
+            .collect::<Vec<_>>();
+        Ok(RecordBatch::try_new(Arc::new(schema), arrays)?)
+    }
+}
+
+fn flatten_value(
+    prefix: String,
+    value: &Value,
+    cells: &mut Vec<(String, u64)>,
+) -> Result<(), TraceExportError> {
+    match value {
+        Value::Number(n) => match n.as_u64() {
+            Some(n) => cells.push((prefix, n)),
+            None => return Err(TraceExportError::UnsupportedValue(prefix, value.clone())),
+        },
+        Value::Bool(b) => cells.push((prefix, *b as u64)),
+        Value::Array(items) => {
+            for (i, item) in items.iter().enumerate() {
+                flatten_value(format!("{}_{}", prefix, i), item, cells)?;
+            }
+        }
+        Value::Object(fields) => {
+            for (key, item) in fields {
+                let name = if prefix.is_empty() {
+                    key.clone()
+                } else {
+                    format!("{}.{}", prefix, key)
+                };
+                flatten_value(name, item, cells)?;
+            }
+        }
+        Value::Null | Value::String(_) => {
+            return Err(TraceExportError::UnsupportedValue(prefix, value.clone()))
+        }
+    }
+    Ok(())
+}
+
+/// Flatten every row-based table of the trace, one `TraceTable` per table.
+pub fn trace_tables(trace: &Trace) -> Result<Vec<TraceTable>, TraceExportError> {
+    Ok(vec![
+        TraceTable::from_rows("cpu", &trace.exec)?,
+        TraceTable::from_rows("memory", &trace.memory)?,
+        TraceTable::from_rows("rangecheck", &trace.builtin_rangecheck)?,
+        TraceTable::from_rows("bitwise", &trace.builtin_bitwise_combined)?,
+        TraceTable::from_rows("cmp", &trace.builtin_cmp)?,
+        TraceTable::from_rows("poseidon", &trace.builtin_poseidon)?,
+        TraceTable::from_rows("poseidon_chunk", &trace.builtin_poseidon_chunk)?,
+        TraceTable::from_rows("storage", &trace.builtin_storage)?,
+        TraceTable::from_rows("storage_hash", &trace.builtin_storage_hash)?,
+        TraceTable::from_rows("program_hash", &trace.builtin_program_hash)?,
+        TraceTable::from_rows("tape", &trace.tape)?,
+        TraceTable::from_rows("sccall", &trace.sc_call)?,
+        TraceTable::from_rows("transient", &trace.transient)?,
+    ])
+}
+
+/// Write one `<table>.parquet` file per non-empty trace table into `dir`.
+#[cfg(feature = "arrow")]
+pub fn write_parquet_tables(trace: &Trace, dir: &Path) -> Result<Vec<PathBuf>, TraceExportError> {
+    std::fs::create_dir_all(dir)?;
+    let mut paths = Vec::new();
+    for table in trace_tables(trace)? {
+        if table.num_rows() == 0 {
+            continue;
+        }
+        let batch = table.to_record_batch()?;
+        let path = dir.join(format!("{}.parquet", table.name));
+        let file = std::fs::File::create(&path)?;
+        let mut writer = parquet::arrow::ArrowWriter::try_new(file, batch.schema(), None)?;
+        writer.write(&batch)?;
+        writer.close()?;
+        paths.push(path);
+    }
+    Ok(paths)
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::trace::export::{TraceExportError, TraceTable};
+    use crate::trace::trace::{CmpRow, TapeRow};
+    use plonky2::field::goldilocks_field::GoldilocksField;
+    use plonky2::field::types::Field;
+    use serde::Serialize;
+
+    #[test]
+    fn flatten_rows_to_named_columns() {
+        let rows = vec![
+            TapeRow {
+                is_init: true,
+                opcode: GoldilocksField::ZERO,
+                addr: GoldilocksField::from_canonical_u64(3),
+                value: GoldilocksField::from_canonical_u64(7),
+                filter_looked: GoldilocksField::ONE,
+            };
+            2
+        ];
+        let table = TraceTable::from_rows("tape", &rows).unwrap();
+        assert_eq!(table.num_rows(), 2);
+        assert_eq!(table.column("is_init"), Some(&vec![1, 1]));
+        assert_eq!(table.column("value"), Some(&vec![7, 7]));
+
+        let empty: Vec<CmpRow> = Vec::new();
+        assert_eq!(TraceTable::from_rows("cmp", &empty).unwrap().num_rows(), 0);
+    }
+
+    #[test]
+    fn reject_values_not_u64() {
+        #[derive(Serialize)]
+        struct Row {
+            delta: i64,
+            label: Option<String>,
+        }
+        let row = |delta: i64, label: Option<&str>| Row {
+            delta,
+            label: label.map(|l| l.to_string()),
+        };
+        let column = |rows: &[Row]| match TraceTable::from_rows("row", rows) {
+            Err(TraceExportError::UnsupportedValue(column, _)) => column,
+            res => panic!("unexpected {:?}", res),
+        };
+        assert_eq!(column(&[row(-1, Some("a"))]), "delta");
+        assert_eq!(column(&[row(1, None)]), "label");
+        assert_eq!(column(&[row(1, Some("a"))]), "label");
+    }
+}
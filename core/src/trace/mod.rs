@@ -1,2 +1,7 @@
+pub mod compress;
 pub mod dump;
+pub mod export;
+pub mod redact;
+pub mod schema;
+pub mod stream;
 pub mod trace;
@@ -0,0 +1,405 @@
+//! Redaction of traces shared for debugging.
+//!
+//! `redact_trace` replaces the storage values and calldata words of a trace
+//! wherever they show up as a value: in the storage, transient and tape
+//! tables, memory cells, registers, register operands and the builtin rows
+//! computed from them. Limbs, hash states and differences derived from a
+//! replaced value are zeroed. Addresses, clks, pcs, opcodes, selectors and
+//! filters are kept, as are 0 and 1, which are flags far more often than
+//! data. Merkle path hashes of the storage tree are kept too.
+//!
+//! The redacted trace no longer satisfies the value constraints, but has the
+//! shape of the original, `check_structure` confirms that it still shows a
+//! failure of the structural columns before the trace is shared.
+
+use crate::crypto::poseidon_trace::calculate_arbitrary_poseidon;
+use crate::trace::trace::{PoseidonRow, Trace};
+use plonky2::field::goldilocks_field::GoldilocksField;
+use plonky2::field::types::{Field, PrimeField64};
+use std::collections::HashSet;
+use std::fmt::Debug;
+
+/// Words of the tx context at the start of the init tape, see `TxCtxInfo`.
+const TX_CONTEXT_WORDS: u64 = 25;
+/// Words of the caller, callee and code addresses after the calldata.
+const CTX_ADDR_WORDS: u64 = 12;
+
+#[derive(Debug, Copy, Clone, PartialEq, Eq)]
+pub enum RedactMode {
+    /// Replace every value with zero.
+    Zero,
+    /// Replace every value with the first word of its Poseidon hash, equal
+    /// values stay equal so lookups between tables still line up.
+    Hash,
+}
+
+struct Redactor {
+    values: HashSet<u64>,
+    mode: RedactMode,
+}
+
+impl Redactor {
+    fn is_sensitive(&self, value: &GoldilocksField) -> bool {
+        self.values.contains(&value.to_canonical_u64())
+    }
+
+    /// Replace `value` if it is sensitive, true if it was.
+    fn redact(&self, value: &mut GoldilocksField) -> bool {
+        if !self.is_sensitive(value) {
+            return false;
+        }
+        *value = match self.mode {
+            RedactMode::Zero => GoldilocksField::ZERO,
+            RedactMode::Hash => calculate_arbitrary_poseidon(&[*value])[0],
+        };
+        true
+    }
+
+    fn redact_all(&self, values: &mut [GoldilocksField]) -> bool {
+        values
+            .iter_mut()
+            .fold(false, |redacted, value| self.redact(value) | redacted)
+    }
+}
+
+/// Calldata words of the tx, from the init rows of the tape.
+pub fn calldata_words(trace: &Trace) -> Vec<GoldilocksField> {
+    let init_len = trace.tape.iter().filter(|row| row.is_init).count() as u64;
+    let calldata = TX_CONTEXT_WORDS..init_len.saturating_sub(CTX_ADDR_WORDS);
+    trace
+        .tape
+        .iter()
+        .filter(|row| row.is_init && calldata.contains(&row.addr.to_canonical_u64()))
+        .map(|row| row.value)
+        .collect()
+}
+
+/// Storage values and calldata words of `trace`, but 0 and 1.
+pub fn sensitive_values(trace: &Trace) -> HashSet<u64> {
+    let storage = trace.builtin_storage.iter().flat_map(|row| row.value);
+    let transient = trace.transient.iter().map(|row| row.value);
+    storage
+        .chain(transient)
+        .chain(calldata_words(trace))
+        .map(|value| value.to_canonical_u64())
+        .filter(|value| *value > 1)
+        .collect()
+}
+
+/// A copy of `trace` with its sensitive values replaced as `mode` says.
+pub fn redact_trace(trace: &Trace, mode: RedactMode) -> Trace {
+    let redactor = Redactor {
+        values: sensitive_values(trace),
+        mode,
+    };
+    let mut redacted = trace.clone();
+    for step in redacted.exec.iter_mut() {
+        redactor.redact_all(&mut step.regs);
+        let sel = &mut step.register_selector;
+        for value in [
+            &mut sel.op0,
+            &mut sel.op1,
+            &mut sel.dst,
+            &mut sel.aux0,
+            &mut sel.aux1,
+        ] {
+            redactor.redact(value);
+        }
+    }
+    for cell in redacted.memory.iter_mut() {
+        redactor.redact(&mut cell.value);
+        redactor.redact(&mut cell.rc_value);
+    }
+    for row in redacted.tape.iter_mut() {
+        redactor.redact(&mut row.value);
+    }
+    for row in redacted.transient.iter_mut() {
+        redactor.redact(&mut row.value);
+    }
+    for row in redacted.builtin_storage.iter_mut() {
+        redactor.redact_all(&mut row.value);
+    }
+    for row in redacted.builtin_rangecheck.iter_mut() {
+        if redactor.redact(&mut row.val) {
+            row.limb_lo = GoldilocksField::ZERO;
+            row.limb_hi = GoldilocksField::ZERO;
+        }
+    }
+    for row in redacted.builtin_bitwise_combined.iter_mut() {
+        let mut ops = [row.op0, row.op1, row.res];
+        if redactor.redact_all(&mut ops) {
+            [row.op0, row.op1, row.res] = ops;
+            for limb in [
+                &mut row.op0_0,
+                &mut row.op0_1,
+                &mut row.op0_2,
+                &mut row.op0_3,
+                &mut row.op1_0,
+                &mut row.op1_1,
+                &mut row.op1_2,
+                &mut row.op1_3,
+                &mut row.res_0,
+                &mut row.res_1,
+                &mut row.res_2,
+                &mut row.res_3,
+            ] {
+                *limb = GoldilocksField::ZERO;
+            }
+        }
+    }
+    for row in redacted.builtin_cmp.iter_mut() {
+        if redactor.redact(&mut row.op0) | redactor.redact(&mut row.op1) {
+            row.abs_diff = GoldilocksField::ZERO;
+            row.abs_diff_inv = GoldilocksField::ZERO;
+        }
+    }
+    for row in redacted.builtin_poseidon_chunk.iter_mut() {
+        if redactor.redact_all(&mut row.value) {
+            row.cap = [GoldilocksField::ZERO; 4];
+            row.hash = [GoldilocksField::ZERO; 12];
+        }
+    }
+    for row in redacted.builtin_poseidon.iter_mut() {
+        if row.input.iter().any(|value| redactor.is_sensitive(value)) {
+            *row = PoseidonRow {
+                filter_looked_normal: row.filter_looked_normal,
+                filter_looked_treekey: row.filter_looked_treekey,
+                filter_looked_storage: row.filter_looked_storage,
+                filter_looked_storage_branch: row.filter_looked_storage_branch,
+                ..Default::default()
+            };
+        }
+    }
+    redacted
+}
+
+fn same<T: PartialEq + Debug>(
+    table: &str,
+    row: usize,
+    column: &str,
+    original: T,
+    redacted: T,
+) -> Result<(), String> {
+    if original != redacted {
+        return Err(format!(
+            "{} row {}: {} {:?} != {:?}",
+            table, row, column, original, redacted
+        ));
+    }
+    Ok(())
+}
+
+fn same_len(table: &str, original: usize, redacted: usize) -> Result<(), String> {
+    if original != redacted {
+        return Err(format!(
+            "{} has {} rows instead of {}",
+            table, redacted, original
+        ));
+    }
+    Ok(())
+}
+
+/// Check that `redacted` keeps the table lengths and the structural columns
+/// of `original`, so a failure of those columns reproduces on it. The error
+/// names the first column that differs.
+pub fn check_structure(original: &Trace, redacted: &Trace) -> Result<(), String> {
+    for (table, a, b) in [
+        ("exec", original.exec.len(), redacted.exec.len()),
+        ("memory", original.memory.len(), redacted.memory.len()),
+        ("tape", original.tape.len(), redacted.tape.len()),
+        (
+            "transient",
+            original.transient.len(),
+            redacted.transient.len(),
+        ),
+        (
+            "rangecheck",
+            original.builtin_rangecheck.len(),
+            redacted.builtin_rangecheck.len(),
+        ),
+        (
+            "bitwise",
+            original.builtin_bitwise_combined.len(),
+            redacted.builtin_bitwise_combined.len(),
+        ),
+        (
+            "cmp",
+            original.builtin_cmp.len(),
+            redacted.builtin_cmp.len(),
+        ),
+        (
+            "poseidon",
+            original.builtin_poseidon.len(),
+            redacted.builtin_poseidon.len(),
+        ),
+        (
+            "poseidon_chunk",
+            original.builtin_poseidon_chunk.len(),
+            redacted.builtin_poseidon_chunk.len(),
+        ),
+        (
+            "storage",
+            original.builtin_storage.len(),
+            redacted.builtin_storage.len(),
+        ),
+        (
+            "storage_hash",
+            original.builtin_storage_hash.len(),
+            redacted.builtin_storage_hash.len(),
+        ),
+        ("sccall", original.sc_call.len(), redacted.sc_call.len()),
+    ] {
+        same_len(table, a, b)?;
+    }
+
+    for (i, (a, b)) in original.exec.iter().zip(&redacted.exec).enumerate() {
+        same("exec", i, "env_idx", a.env_idx, b.env_idx)?;
+        same("exec", i, "clk", a.clk, b.clk)?;
+        same("exec", i, "pc", a.pc, b.pc)?;
+        same("exec", i, "tp", a.tp, b.tp)?;
+        same("exec", i, "instruction", a.instruction, b.instruction)?;
+        same("exec", i, "opcode", a.opcode, b.opcode)?;
+        same("exec", i, "op1_imm", a.op1_imm, b.op1_imm)?;
+        same("exec", i, "is_ext_line", a.is_ext_line, b.is_ext_line)?;
+        same("exec", i, "ext_cnt", a.ext_cnt, b.ext_cnt)?;
+        let (sa, sb) = (&a.register_selector, &b.register_selector);
+        same("exec", i, "op0_reg_sel", sa.op0_reg_sel, sb.op0_reg_sel)?;
+        same("exec", i, "op1_reg_sel", sa.op1_reg_sel, sb.op1_reg_sel)?;
+        same("exec", i, "dst_reg_sel", sa.dst_reg_sel, sb.dst_reg_sel)?;
+    }
+    for (i, (a, b)) in original.memory.iter().zip(&redacted.memory).enumerate() {
+        same("memory", i, "env_idx", a.env_idx, b.env_idx)?;
+        same("memory", i, "addr", a.addr, b.addr)?;
+        same("memory", i, "clk", a.clk, b.clk)?;
+        same("memory", i, "op", a.op, b.op)?;
+        same("memory", i, "is_rw", a.is_rw, b.is_rw)?;
+        same("memory", i, "is_write", a.is_write, b.is_write)?;
+        same(
+            "memory",
+            i,
+            "region_prophet",
+            a.region_prophet,
+            b.region_prophet,
+        )?;
+        same("memory", i, "region_heap", a.region_heap, b.region_heap)?;
+    }
+    for (i, (a, b)) in original.tape.iter().zip(&redacted.tape).enumerate() {
+        same("tape", i, "is_init", a.is_init, b.is_init)?;
+        same("tape", i, "opcode", a.opcode, b.opcode)?;
+        same("tape", i, "addr", a.addr, b.addr)?;
+    }
+    for (i, (a, b)) in original
+        .builtin_storage
+        .iter()
+        .zip(&redacted.builtin_storage)
+        .enumerate()
+    {
+        same("storage", i, "env_idx", a.env_idx, b.env_idx)?;
+        same("storage", i, "clk", a.clk, b.clk)?;
+        same("storage", i, "opcode", a.opcode, b.opcode)?;
+        same("storage", i, "addr", a.addr, b.addr)?;
+    }
+    for (i, (a, b)) in original
+        .transient
+        .iter()
+        .zip(&redacted.transient)
+        .enumerate()
+    {
+        same("transient", i, "clk", a.clk, b.clk)?;
+        same("transient", i, "key", a.key, b.key)?;
+        same("transient", i, "is_write", a.is_write, b.is_write)?;
+    }
+    Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::trace::redact::{check_structure, redact_trace, sensitive_values, RedactMode};
+    use crate::trace::trace::{MemoryTraceCell, StorageRow, TapeRow, Trace};
+    use crate::vm::transaction::init_tx_context_mock;
+    use plonky2::field::goldilocks_field::GoldilocksField;
+    use plonky2::field::types::Field;
+
+    fn field(value: u64) -> GoldilocksField {
+        GoldilocksField::from_canonical_u64(value)
+    }
+
+    #[test]
+    fn test_redact_trace() {
+        let context = bincode::serialize(&init_tx_context_mock()).unwrap();
+        assert_eq!(context.len() as u64 / 8, super::TX_CONTEXT_WORDS);
+
+        let mut trace = Trace::default();
+        // 25 context words, 2 calldata words, 12 address words
+        for addr in 0..39 {
+            let value = match addr {
+                25 => 777,
+                26 => 1,
+                _ => 1000 + addr,
+            };
+            trace.insert_tape(TapeRow {
+                is_init: true,
+                opcode: GoldilocksField::ZERO,
+                addr: field(addr),
+                value: field(value),
+                filter_looked: GoldilocksField::ZERO,
+            });
+        }
+        trace.builtin_storage.push(StorageRow {
+            env_idx: GoldilocksField::ZERO,
+            clk: 3,
+            diff_clk: 0,
+            opcode: field(1 << 10),
+            root: [GoldilocksField::ZERO; 4],
+            addr: [field(5); 4],
+            value: [field(888), field(0), field(0), field(0)],
+        });
+        for value in [777, 888, 1005] {
+            trace.insert_memory(MemoryTraceCell {
+                env_idx: GoldilocksField::ZERO,
+                addr: field(value),
+                clk: GoldilocksField::ZERO,
+                is_rw: GoldilocksField::ONE,
+                op: GoldilocksField::ZERO,
+                is_write: GoldilocksField::ONE,
+                diff_addr: GoldilocksField::ZERO,
+                diff_addr_inv: GoldilocksField::ZERO,
+                diff_clk: GoldilocksField::ZERO,
+                diff_addr_cond: GoldilocksField::ZERO,
+                filter_looked_for_main: GoldilocksField::ONE,
+                rw_addr_unchanged: GoldilocksField::ZERO,
+                region_prophet: GoldilocksField::ZERO,
+                region_heap: GoldilocksField::ZERO,
+                value: field(value),
+                rc_value: GoldilocksField::ZERO,
+            });
+        }
+        assert_eq!(sensitive_values(&trace), [777, 888].into_iter().collect());
+
+        let zeroed = redact_trace(&trace, RedactMode::Zero);
+        assert_eq!(zeroed.tape[25].value, GoldilocksField::ZERO);
+        assert_eq!(zeroed.tape[26].value, GoldilocksField::ONE);
+        assert_eq!(zeroed.tape[5].value, field(1005));
+        assert_eq!(zeroed.builtin_storage[0].value[0], GoldilocksField::ZERO);
+        assert_eq!(zeroed.builtin_storage[0].addr, [field(5); 4]);
+        let values: Vec<GoldilocksField> = zeroed.memory.iter().map(|cell| cell.value).collect();
+        assert_eq!(values, [field(0), field(0), field(1005)]);
+        check_structure(&trace, &zeroed).unwrap();
+
+        let hashed = redact_trace(&trace, RedactMode::Hash);
+        assert_ne!(hashed.tape[25].value, field(777));
+        assert_eq!(hashed.tape[25].value, hashed.memory[0].value);
+        check_structure(&trace, &hashed).unwrap();
+
+        let mut moved = hashed.clone();
+        moved.memory[1].addr = field(889);
+        assert_eq!(
+            check_structure(&trace, &moved).unwrap_err(),
+            "memory row 1: addr 888 != 889"
+        );
+        moved.memory.pop();
+        assert!(check_structure(&trace, &moved)
+            .unwrap_err()
+            .starts_with("memory has 2 rows"));
+    }
+}
@@ -0,0 +1,181 @@
+use crate::crypto::TreeHasher;
+use crate::trace::trace::Trace;
+use crate::types::merkle_tree::tree_value_default;
+use crate::types::{Field, GoldilocksField};
+use serde::{Deserialize, Serialize};
+use serde_json::Value;
+use std::io::Read;
+use thiserror::Error;
+
+/// Layout version of serialized traces written by this build.
+///
+/// Bump it whenever a field is added to or changed in `Trace` or its rows,
+/// and append the matching `migrate_vN_to_vN1` to `MIGRATIONS`.
+pub const TRACE_VERSION: u32 = 2;
+
+/// Upgrades of a serialized trace, `MIGRATIONS[n]` lifts version n to n + 1.
+const MIGRATIONS: [fn(&mut Value); TRACE_VERSION as usize] = [migrate_v0_to_v1, migrate_v1_to_v2];
+
+#[derive(Error, Debug)]
+pub enum TraceSchemaError {
+    #[error("trace serde error")]
+    JsonSerdeError(#[from] serde_json::Error),
+    #[error("trace is not a json object")]
+    NotAnObject,
+    #[error("trace version {0} is newer than supported version {1}")]
+    UnsupportedVersion(u32, u32),
+}
+
+/// Schema version stored in a `Trace`. Traces written before versioning
+/// carry no version field and deserialize as version 0.
+#[derive(Debug, Copy, Clone, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(transparent)]
+pub struct TraceVersion(pub u32);
+
+impl TraceVersion {
+    pub fn legacy() -> Self {
+        Self(0)
+    }
+}
+
+impl Default for TraceVersion {
+    fn default() -> Self {
+        Self(TRACE_VERSION)
+    }
+}
+
+/// v1 added the prophet commitment and records, the hasher of the account
+/// tree levels, the transient storage rows, the fork id of the block, the
+/// chain id and hash of the tx, the values the program returned and the gas
+/// used. Older traces were hashed with poseidon.
+pub fn migrate_v0_to_v1(trace: &mut Value) {
+    if let Some(obj) = trace.as_object_mut() {
+        obj.entry("prophet_commitment")
+            .or_insert_with(|| serde_json::to_value(tree_value_default()).unwrap());
+        obj.entry("prophet_records")
+            .or_insert_with(|| Value::Array(vec![]));
+        obj.entry("tree_hasher")
+            .or_insert_with(|| serde_json::to_value(TreeHasher::Poseidon).unwrap());
+        obj.entry("transient")
+            .or_insert_with(|| Value::Array(vec![]));
+        obj.entry("fork_id")
+            .or_insert_with(|| serde_json::to_value(tree_value_default()).unwrap());
+        obj.entry("chain_id")
+            .or_insert_with(|| serde_json::to_value(GoldilocksField::ZERO).unwrap());
+        obj.entry("tx_hash")
+            .or_insert_with(|| serde_json::to_value(tree_value_default()).unwrap());
+        obj.entry("returned_values")
+            .or_insert_with(|| Value::Array(vec![]));
+        obj.entry("gas_used").or_insert_with(|| Value::from(0u64));
+    }
+}
+
+/// v2 added the cpu step of the transient storage rows, filled when the
+/// traces are generated, and the state a chunk of an execution stopped with.
+pub fn migrate_v1_to_v2(trace: &mut Value) {
+    if let Some(rows) = trace.get_mut("transient").and_then(Value::as_array_mut) {
+        for row in rows.iter_mut().filter_map(Value::as_object_mut) {
+            row.entry("step").or_insert_with(|| Value::from(0u64));
+        }
+    }
+    if let Some(obj) = trace.as_object_mut() {
+        obj.entry("stop_state").or_insert(Value::Null);
+    }
+}
+
+/// Upgrade a serialized trace of any supported version to `TRACE_VERSION`.
+pub fn migrate_trace(mut trace: Value) -> Result<Trace, TraceSchemaError> {
+    let version = trace
+        .as_object()
+        .ok_or(TraceSchemaError::NotAnObject)?
+        .get("version")
+        .map(|v| serde_json::from_value::<u32>(v.clone()))
+        .transpose()?
+        .unwrap_or(0);
+    if version > TRACE_VERSION {
+        return Err(TraceSchemaError::UnsupportedVersion(version, TRACE_VERSION));
+    }
+    for migrate in &MIGRATIONS[version as usize..] {
+        migrate(&mut trace);
+    }
+    trace["version"] = Value::from(TRACE_VERSION);
+    Ok(serde_json::from_value(trace)?)
+}
+
+/// Load a json trace file, migrating it from older layouts if needed.
+pub fn load_trace<R: Read>(reader: R) -> Result<Trace, TraceSchemaError> {
+    migrate_trace(serde_json::from_reader(reader)?)
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::crypto::TreeHasher;
+    use crate::trace::schema::{load_trace, TraceSchemaError, TraceVersion, TRACE_VERSION};
+    use crate::trace::trace::{Trace, TransientRow};
+    use crate::types::merkle_tree::tree_value_default;
+    use crate::types::{Field, GoldilocksField};
+
+    #[test]
+    fn test_load_legacy_trace() {
+        let mut legacy = serde_json::to_value(Trace::default()).unwrap();
+        let obj = legacy.as_object_mut().unwrap();
+        obj.remove("version");
+        obj.remove("prophet_commitment");
+        obj.remove("prophet_records");
+        obj.remove("tree_hasher");
+        obj.remove("transient");
+        obj.remove("fork_id");
+        obj.remove("chain_id");
+        obj.remove("tx_hash");
+        obj.remove("returned_values");
+        obj.remove("gas_used");
+        let json = serde_json::to_string(&legacy).unwrap();
+
+        let trace = load_trace(json.as_bytes()).unwrap();
+        assert_eq!(trace.version, TraceVersion(TRACE_VERSION));
+        assert_eq!(
+            trace.prophet_commitment,
+            Trace::default().prophet_commitment
+        );
+        assert!(trace.prophet_records.is_empty());
+        assert_eq!(trace.tree_hasher, TreeHasher::Poseidon);
+        assert!(trace.transient.is_empty());
+        assert_eq!(trace.fork_id, tree_value_default());
+        assert_eq!(trace.chain_id, GoldilocksField::ZERO);
+        assert_eq!(trace.tx_hash, tree_value_default());
+        assert!(trace.returned_values.is_empty());
+        assert_eq!(trace.gas_used, 0);
+
+        legacy["version"] = (TRACE_VERSION + 1).into();
+        let json = serde_json::to_string(&legacy).unwrap();
+        assert!(matches!(
+            load_trace(json.as_bytes()),
+            Err(TraceSchemaError::UnsupportedVersion(..))
+        ));
+    }
+
+    #[test]
+    fn test_load_v1_trace() {
+        let mut trace = Trace::default();
+        trace.transient.push(TransientRow {
+            env_idx: GoldilocksField::ZERO,
+            clk: 3,
+            addr_storage: [GoldilocksField::ONE; 4],
+            key: GoldilocksField::TWO,
+            value: GoldilocksField::ONE,
+            is_write: true,
+            step: 0,
+        });
+        let mut v1 = serde_json::to_value(&trace).unwrap();
+        v1["version"] = 1.into();
+        v1["transient"][0].as_object_mut().unwrap().remove("step");
+        v1.as_object_mut().unwrap().remove("stop_state");
+        let json = serde_json::to_string(&v1).unwrap();
+
+        let trace = load_trace(json.as_bytes()).unwrap();
+        assert_eq!(trace.version, TraceVersion(TRACE_VERSION));
+        assert_eq!(trace.transient[0].clk, 3);
+        assert_eq!(trace.transient[0].step, 0);
+        assert_eq!(trace.stop_state, None);
+    }
+}
@@ -0,0 +1,230 @@
+//! Chunked stream of the cpu, memory and rangecheck tables, written while a
+//! long execution runs so they never have to be held in memory at once.
+//!
+//! Layout: `OLAS`, format version byte, then the chunks, each the length of
+//! its bincode as u64 le followed by the bincode of a `TraceChunk`.
+
+use crate::trace::trace::{MemoryTraceCell, RangeCheckRow, Step, Trace};
+use serde::{Deserialize, Serialize};
+use std::io::{ErrorKind, Read, Write};
+use thiserror::Error;
+
+const MAGIC: &[u8; 4] = b"OLAS";
+const FORMAT_VERSION: u8 = 1;
+pub const DEFAULT_CHUNK_ROWS: usize = 1 << 16;
+
+#[derive(Error, Debug)]
+pub enum TraceStreamError {
+    #[error("trace stream io error: {0}")]
+    IoError(#[from] std::io::Error),
+    #[error("trace chunk encoding error: {0}")]
+    Bincode(#[from] bincode::Error),
+    #[error("not a trace stream")]
+    BadMagic,
+    #[error("trace stream format {0} is not supported")]
+    UnsupportedFormat(u8),
+}
+
+/// Rows of the streamed tables, in execution order.
+#[derive(Debug, Clone, Default, Serialize, Deserialize)]
+pub struct TraceChunk {
+    pub exec: Vec<Step>,
+    pub memory: Vec<MemoryTraceCell>,
+    pub builtin_rangecheck: Vec<RangeCheckRow>,
+}
+
+impl TraceChunk {
+    /// Moves the streamed tables out of `trace`.
+    pub fn take(trace: &mut Trace) -> Self {
+        Self {
+            exec: std::mem::take(&mut trace.exec),
+            memory: std::mem::take(&mut trace.memory),
+            builtin_rangecheck: std::mem::take(&mut trace.builtin_rangecheck),
+        }
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.exec.is_empty() && self.memory.is_empty() && self.builtin_rangecheck.is_empty()
+    }
+}
+
+/// Where `Process::execute_with_sink` flushes trace chunks to.
+pub trait TraceSink {
+    /// Cpu rows collected before a chunk is flushed.
+    fn chunk_rows(&self) -> usize {
+        DEFAULT_CHUNK_ROWS
+    }
+
+    fn write_chunk(&mut self, chunk: &TraceChunk) -> Result<(), TraceStreamError>;
+}
+
+/// Writes chunks in the stream format, e.g. into a buffered file.
+pub struct ChunkedTraceWriter<W: Write> {
+    writer: W,
+    chunk_rows: usize,
+    started: bool,
+}
+
+impl<W: Write> ChunkedTraceWriter<W> {
+    pub fn new(writer: W) -> Self {
+        Self {
+            writer,
+            chunk_rows: DEFAULT_CHUNK_ROWS,
+            started: false,
+        }
+    }
+
+    pub fn with_chunk_rows(mut self, chunk_rows: usize) -> Self {
+        self.chunk_rows = chunk_rows;
+        self
+    }
+
+    /// Flushes and returns the writer. A stream without chunks still gets its
+    /// header.
+    pub fn finish(mut self) -> Result<W, TraceStreamError> {
+        self.start()?;
+        self.writer.flush()?;
+        Ok(self.writer)
+    }
+
+    fn start(&mut self) -> Result<(), TraceStreamError> {
+        if !self.started {
+            self.writer.write_all(MAGIC)?;
+            self.writer.write_all(&[FORMAT_VERSION])?;
+            self.started = true;
+        }
+        Ok(())
+    }
+}
+
+impl<W: Write> TraceSink for ChunkedTraceWriter<W> {
+    fn chunk_rows(&self) -> usize {
+        self.chunk_rows
+    }
+
+    fn write_chunk(&mut self, chunk: &TraceChunk) -> Result<(), TraceStreamError> {
+        if chunk.is_empty() {
+            return Ok(());
+        }
+        self.start()?;
+        let bytes = bincode::serialize(chunk)?;
+        self.writer.write_all(&(bytes.len() as u64).to_le_bytes())?;
+        self.writer.write_all(&bytes)?;
+        Ok(())
+    }
+}
+
+/// Reads back the chunks of a stream, one at a time.
+pub struct ChunkedTraceReader<R: Read> {
+    reader: R,
+}
+
+impl<R: Read> ChunkedTraceReader<R> {
+    pub fn new(mut reader: R) -> Result<Self, TraceStreamError> {
+        let mut header = [0u8; 5];
+        reader.read_exact(&mut header)?;
+        if &header[..4] != MAGIC {
+            return Err(TraceStreamError::BadMagic);
+        }
+        if header[4] != FORMAT_VERSION {
+            return Err(TraceStreamError::UnsupportedFormat(header[4]));
+        }
+        Ok(Self { reader })
+    }
+
+    /// The next chunk, `None` at the end of the stream.
+    pub fn next_chunk(&mut self) -> Result<Option<TraceChunk>, TraceStreamError> {
+        let mut len = [0u8; 8];
+        match self.reader.read_exact(&mut len) {
+            Err(e) if e.kind() == ErrorKind::UnexpectedEof => return Ok(None),
+            res => res?,
+        }
+        let mut bytes = vec![0u8; u64::from_le_bytes(len) as usize];
+        self.reader.read_exact(&mut bytes)?;
+        Ok(Some(bincode::deserialize(&bytes)?))
+    }
+
+    /// Appends the rows of every chunk to the tables of `trace`, the rest of
+    /// the trace left in `program.trace` by the streamed execution, so it can
+    /// be proven like a trace executed in memory.
+    pub fn assemble(mut self, mut trace: Trace) -> Result<Trace, TraceStreamError> {
+        while let Some(chunk) = self.next_chunk()? {
+            trace.exec.extend(chunk.exec);
+            trace.memory.extend(chunk.memory);
+            trace.builtin_rangecheck.extend(chunk.builtin_rangecheck);
+        }
+        Ok(trace)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::trace::stream::{
+        ChunkedTraceReader, ChunkedTraceWriter, TraceChunk, TraceSink, TraceStreamError,
+    };
+    use crate::trace::trace::Trace;
+    use plonky2::field::goldilocks_field::GoldilocksField;
+    use plonky2::field::types::Field;
+
+    #[test]
+    fn test_trace_stream() {
+        let mut trace = Trace::default();
+        for i in 0..5 {
+            trace.insert_rangecheck(
+                GoldilocksField::from_canonical_u64(i),
+                (
+                    GoldilocksField::ONE,
+                    GoldilocksField::ZERO,
+                    GoldilocksField::ZERO,
+                    GoldilocksField::ZERO,
+                    GoldilocksField::ZERO,
+                ),
+            );
+        }
+
+        let mut writer = ChunkedTraceWriter::new(vec![]);
+        let mut first = trace.clone();
+        first.builtin_rangecheck.truncate(3);
+        writer.write_chunk(&TraceChunk::take(&mut first)).unwrap();
+        assert!(first.builtin_rangecheck.is_empty());
+        writer.write_chunk(&TraceChunk::default()).unwrap();
+        let mut second = trace.clone();
+        second.builtin_rangecheck.drain(..3);
+        writer.write_chunk(&TraceChunk::take(&mut second)).unwrap();
+        let bytes = writer.finish().unwrap();
+
+        let mut reader = ChunkedTraceReader::new(bytes.as_slice()).unwrap();
+        assert_eq!(
+            reader
+                .next_chunk()
+                .unwrap()
+                .unwrap()
+                .builtin_rangecheck
+                .len(),
+            3
+        );
+        let assembled = ChunkedTraceReader::new(bytes.as_slice())
+            .unwrap()
+            .assemble(Trace::default())
+            .unwrap();
+        let values = |trace: &Trace| {
+            trace
+                .builtin_rangecheck
+                .iter()
+                .map(|row| row.val)
+                .collect::<Vec<_>>()
+        };
+        assert_eq!(values(&assembled), values(&trace));
+
+        let empty = ChunkedTraceWriter::new(vec![]).finish().unwrap();
+        let assembled = ChunkedTraceReader::new(empty.as_slice())
+            .unwrap()
+            .assemble(Trace::default())
+            .unwrap();
+        assert!(assembled.builtin_rangecheck.is_empty());
+        assert!(matches!(
+            ChunkedTraceReader::new(&b"OLAW\x01"[..]),
+            Err(TraceStreamError::BadMagic)
+        ));
+    }
+}
@@ -1,7 +1,10 @@
+use crate::crypto::TreeHasher;
 use crate::program::REGISTER_NUM;
+use crate::trace::schema::TraceVersion;
 use crate::types::{account::Address, merkle_tree::TreeValue};
 use crate::utils::split_limbs_from_field;
 use crate::utils::split_u16_limbs_from_field;
+use crate::vm::prophet::ProphetDebugRecord;
 use plonky2::field::goldilocks_field::GoldilocksField;
 use plonky2::field::types::Field;
 use plonky2::field::types::PrimeField64;
@@ -139,6 +142,58 @@ pub struct RangeCheckRow {
     pub filter_looked_for_storage: GoldilocksField,
 }
 
+impl RangeCheckRow {
+    fn filters_mut(&mut self) -> [&mut GoldilocksField; 5] {
+        [
+            &mut self.filter_looked_for_mem_sort,
+            &mut self.filter_looked_for_mem_region,
+            &mut self.filter_looked_for_cpu,
+            &mut self.filter_looked_for_comparison,
+            &mut self.filter_looked_for_storage,
+        ]
+    }
+}
+
+/// Fold range checks of the same value by different consumers into shared
+/// rows. A row is looked up once per filter set in it, so each consumer still
+/// finds the value as often as it checked it, and the table only needs as
+/// many rows for a value as its busiest consumer.
+pub fn merge_rangecheck_rows(rows: &[RangeCheckRow]) -> Vec<RangeCheckRow> {
+    let mut merged: Vec<RangeCheckRow> = Vec::with_capacity(rows.len());
+    let mut rows_of_value: HashMap<u64, Vec<usize>> = HashMap::new();
+    for row in rows {
+        let mut unfiltered = row.clone();
+        let filters: Vec<usize> = unfiltered
+            .filters_mut()
+            .into_iter()
+            .enumerate()
+            .filter(|(_, filter)| filter.is_nonzero())
+            .map(|(i, filter)| {
+                *filter = GoldilocksField::ZERO;
+                i
+            })
+            .collect();
+        if filters.is_empty() {
+            merged.push(row.clone());
+            continue;
+        }
+        let shared = rows_of_value.entry(row.val.to_canonical_u64()).or_default();
+        for i in filters {
+            let free = shared
+                .iter()
+                .copied()
+                .find(|index| merged[*index].filters_mut()[i].is_zero());
+            let index = free.unwrap_or_else(|| {
+                merged.push(unfiltered.clone());
+                shared.push(merged.len() - 1);
+                merged.len() - 1
+            });
+            *merged[index].filters_mut()[i] = GoldilocksField::ONE;
+        }
+    }
+    merged
+}
+
 #[derive(Debug, Clone, Serialize, Deserialize)]
 pub struct BitwiseCombinedRow {
     pub opcode: u64,
@@ -303,6 +358,28 @@ pub struct TapeRow {
     pub filter_looked: GoldilocksField,
 }
 
+// state before the step an execution stopped at, see `Trace::stop_state`
+#[derive(Debug, Copy, Clone, PartialEq, Eq, Serialize, Deserialize)]
+pub struct StopState {
+    pub pc: u64,
+    pub registers: [GoldilocksField; REGISTER_NUM],
+    pub tp: GoldilocksField,
+}
+
+// one access of tload_t/tstore_t, rows are sorted by slot then by access
+#[derive(Debug, Copy, Clone, Serialize, Deserialize)]
+pub struct TransientRow {
+    pub env_idx: GoldilocksField,
+    pub clk: u32,
+    pub addr_storage: Address,
+    pub key: GoldilocksField,
+    pub value: GoldilocksField,
+    pub is_write: bool,
+    // cpu row of the access, set when the traces are generated
+    #[serde(default)]
+    pub step: u64,
+}
+
 #[derive(Debug, Copy, Clone, Serialize, Deserialize)]
 pub struct SCCallRow {
     pub caller_env_idx: GoldilocksField,
@@ -318,6 +395,8 @@ pub struct SCCallRow {
 
 #[derive(Debug, Default, Clone, Serialize, Deserialize)]
 pub struct Trace {
+    #[serde(default = "TraceVersion::legacy")]
+    pub version: TraceVersion,
     //(inst_asm_str, imm_flag, step, inst_encode, imm_val)
     pub instructions: HashMap<u64, (String, u8, u64, GoldilocksField, GoldilocksField)>,
     // pub raw_instructions: HashMap<u64, Instruction>,
@@ -337,11 +416,68 @@ pub struct Trace {
     pub builtin_storage_hash: Vec<StorageHashRow>,
     pub builtin_program_hash: Vec<StorageHashRow>,
     pub tape: Vec<TapeRow>,
+    #[serde(default)]
+    pub transient: Vec<TransientRow>,
     pub sc_call: Vec<SCCallRow>,
     pub ret: Vec<GoldilocksField>,
+    // registers `r0..` at the end of a program declaring its return arity
+    #[serde(default)]
+    pub returned_values: Vec<GoldilocksField>,
+    pub prophet_commitment: TreeValue,
+    // `ChainSpec::fork_id` of the block executed, zero without a chain spec
+    #[serde(default)]
+    pub fork_id: TreeValue,
+    // chain id and hash of the tx executed, from its context
+    #[serde(default)]
+    pub chain_id: GoldilocksField,
+    #[serde(default)]
+    pub tx_hash: TreeValue,
+    // gas charged by the `GasSchedule` of the execution
+    #[serde(default)]
+    pub gas_used: u64,
+    // every prophet run, only filled when the process records prophets
+    #[serde(default)]
+    pub prophet_records: Vec<ProphetDebugRecord>,
+    // hash of the account tree levels in `builtin_storage_hash`
+    #[serde(default)]
+    pub tree_hasher: TreeHasher,
+    // state a chunk of an execution stopped with at its step limit, the
+    // state the next chunk starts from
+    #[serde(default)]
+    pub stop_state: Option<StopState>,
 }
 
 impl Trace {
+    pub fn insert_memory(&mut self, cell: MemoryTraceCell) {
+        self.memory.push(cell);
+    }
+
+    pub fn insert_tape(&mut self, row: TapeRow) {
+        self.tape.push(row);
+    }
+
+    pub fn insert_transient(&mut self, row: TransientRow) {
+        self.transient.push(row);
+    }
+
+    pub fn insert_poseidon(&mut self, row: PoseidonRow) {
+        self.builtin_poseidon.push(row);
+    }
+
+    pub fn insert_storage_hash(&mut self, row: StorageHashRow) {
+        self.builtin_storage_hash.push(row);
+    }
+
+    pub fn insert_ret(&mut self, value: GoldilocksField) {
+        self.ret.push(value);
+    }
+
+    /// Append steps built outside `insert_step`, e.g. the ext lines of an
+    /// instruction.
+    pub fn insert_steps(&mut self, steps: Vec<Step>) {
+        self.exec.extend(steps);
+    }
+
     pub fn insert_cmp(
         &mut self,
         op0: GoldilocksField,
@@ -411,11 +547,11 @@ impl Trace {
             GoldilocksField,
         ),
     ) {
-        let split_limbs = split_u16_limbs_from_field(&input);
+        let (limb_lo, limb_hi) = split_u16_limbs_from_field(&input);
         self.builtin_rangecheck.push(RangeCheckRow {
             val: input,
-            limb_lo: GoldilocksField(split_limbs.0),
-            limb_hi: GoldilocksField(split_limbs.1),
+            limb_lo: GoldilocksField(limb_lo),
+            limb_hi: GoldilocksField(limb_hi),
             filter_looked_for_mem_sort: filter_looked_for_memory_cpu_cmp.0,
             filter_looked_for_cpu: filter_looked_for_memory_cpu_cmp.1,
             filter_looked_for_comparison: filter_looked_for_memory_cpu_cmp.2,
@@ -541,3 +677,48 @@ impl Trace {
         });
     }
 }
+
+#[cfg(test)]
+mod tests {
+    use crate::trace::trace::{merge_rangecheck_rows, RangeCheckRow, Trace};
+    use plonky2::field::goldilocks_field::GoldilocksField;
+    use plonky2::field::types::Field;
+
+    #[test]
+    fn test_merge_rangecheck_rows() {
+        let mut trace = Trace::default();
+        let (a, b) = (GoldilocksField(0x1234_5678), GoldilocksField(7));
+        let (o, z) = (GoldilocksField::ONE, GoldilocksField::ZERO);
+        trace.insert_rangecheck(a, (o, z, z, z, z));
+        trace.insert_rangecheck(a, (z, o, z, z, z));
+        trace.insert_rangecheck(b, (z, z, o, z, z));
+        trace.insert_rangecheck(a, (z, z, o, z, z));
+        trace.insert_rangecheck(a, (z, o, z, z, z));
+
+        let merged = merge_rangecheck_rows(&trace.builtin_rangecheck);
+        assert_eq!(merged.len(), 3);
+        assert_eq!(merged[0].val, a);
+        assert_eq!(merged[0].limb_lo, GoldilocksField(0x5678));
+        assert_eq!(merged[0].limb_hi, GoldilocksField(0x1234));
+        assert_eq!(merged[0].filter_looked_for_mem_sort, GoldilocksField::ONE);
+        assert_eq!(merged[0].filter_looked_for_cpu, GoldilocksField::ONE);
+        assert_eq!(merged[0].filter_looked_for_comparison, GoldilocksField::ONE);
+        assert_eq!(merged[1].val, b);
+        assert_eq!(merged[2].val, a);
+        assert_eq!(merged[2].filter_looked_for_mem_sort, GoldilocksField::ZERO);
+        assert_eq!(merged[2].filter_looked_for_cpu, GoldilocksField::ONE);
+
+        let checks = |rows: &[RangeCheckRow]| -> GoldilocksField {
+            rows.iter()
+                .map(|r| {
+                    r.filter_looked_for_mem_sort
+                        + r.filter_looked_for_mem_region
+                        + r.filter_looked_for_cpu
+                        + r.filter_looked_for_comparison
+                        + r.filter_looked_for_storage
+                })
+                .sum()
+        };
+        assert_eq!(checks(&merged), checks(&trace.builtin_rangecheck));
+    }
+}
@@ -1 +1 @@
-
+pub mod witness;
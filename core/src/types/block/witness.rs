@@ -0,0 +1,205 @@
+use crate::crypto::poseidon_trace::calculate_arbitrary_poseidon;
+use crate::types::merkle_tree::{TreeValue, ZkHash};
+use crate::types::ssz::{
+    merkleize, ContainerDecoder, ContainerEncoder, HashTreeRoot, Root, Ssz, SszError,
+};
+use plonky2::field::goldilocks_field::GoldilocksField;
+use plonky2::field::types::Field;
+use serde::{Deserialize, Serialize};
+
+// Leaves and inner nodes are hashed with distinct leading tags, so a node can
+// not be passed off as a transaction commitment.
+const LEAF_TAG: u64 = 0;
+const NODE_TAG: u64 = 1;
+
+/// What a transaction proof commits to.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
+pub struct TxCommitment {
+    pub program_hash: ZkHash,
+    pub state_root_before: TreeValue,
+    pub state_root_after: TreeValue,
+    pub receipt_hash: ZkHash,
+}
+
+impl TxCommitment {
+    pub fn leaf_hash(&self) -> ZkHash {
+        let mut input = vec![GoldilocksField::from_canonical_u64(LEAF_TAG)];
+        input.extend_from_slice(&self.program_hash);
+        input.extend_from_slice(&self.state_root_before);
+        input.extend_from_slice(&self.state_root_after);
+        input.extend_from_slice(&self.receipt_hash);
+        calculate_arbitrary_poseidon(&input)
+    }
+}
+
+impl Ssz for TxCommitment {
+    fn fixed_len() -> Option<usize> {
+        Some(4 * 32)
+    }
+
+    fn ssz_append(&self, buf: &mut Vec<u8>) {
+        ContainerEncoder::new()
+            .field(&self.program_hash)
+            .field(&self.state_root_before)
+            .field(&self.state_root_after)
+            .field(&self.receipt_hash)
+            .finish(buf);
+    }
+
+    fn from_ssz_bytes(bytes: &[u8]) -> Result<Self, SszError> {
+        let mut decoder = ContainerDecoder::new(bytes);
+        for _ in 0..4 {
+            decoder.register::<ZkHash>()?;
+        }
+        decoder.finish()?;
+        Ok(Self {
+            program_hash: decoder.decode_next()?,
+            state_root_before: decoder.decode_next()?,
+            state_root_after: decoder.decode_next()?,
+            receipt_hash: decoder.decode_next()?,
+        })
+    }
+}
+
+impl HashTreeRoot for TxCommitment {
+    fn hash_tree_root(&self) -> Root {
+        let fields = [
+            self.program_hash,
+            self.state_root_before,
+            self.state_root_after,
+            self.receipt_hash,
+        ]
+        .map(|hash| hash.hash_tree_root());
+        merkleize(&fields, fields.len())
+    }
+}
+
+fn node_hash(left: &ZkHash, right: &ZkHash) -> ZkHash {
+    let mut input = vec![GoldilocksField::from_canonical_u64(NODE_TAG)];
+    input.extend_from_slice(left);
+    input.extend_from_slice(right);
+    calculate_arbitrary_poseidon(&input)
+}
+
+/// Merkle tree over the transaction commitments of a block. Leaves are padded
+/// with zero hashes to a power of two.
+#[derive(Debug, Clone)]
+pub struct BlockWitnessTree {
+    levels: Vec<Vec<ZkHash>>,
+    tx_count: usize,
+}
+
+/// Path from a transaction commitment to the block witness root.
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+pub struct TxInclusionProof {
+    pub index: u64,
+    pub siblings: Vec<ZkHash>,
+}
+
+impl BlockWitnessTree {
+    pub fn new(txs: &[TxCommitment]) -> Self {
+        let width = txs.len().max(1).next_power_of_two();
+        let mut leaves: Vec<ZkHash> = txs.iter().map(|tx| tx.leaf_hash()).collect();
+        leaves.resize(width, [GoldilocksField::ZERO; 4]);
+        let mut levels = vec![leaves];
+        while levels.last().unwrap().len() > 1 {
+            let next = levels
+                .last()
+                .unwrap()
+                .chunks(2)
+                .map(|pair| node_hash(&pair[0], &pair[1]))
+                .collect();
+            levels.push(next);
+        }
+        Self {
+            levels,
+            tx_count: txs.len(),
+        }
+    }
+
+    pub fn root(&self) -> ZkHash {
+        self.levels.last().unwrap()[0]
+    }
+
+    pub fn inclusion_proof(&self, index: usize) -> Option<TxInclusionProof> {
+        if index >= self.tx_count {
+            return None;
+        }
+        let siblings = self.levels[..self.levels.len() - 1]
+            .iter()
+            .enumerate()
+            .map(|(depth, level)| level[(index >> depth) ^ 1])
+            .collect();
+        Some(TxInclusionProof {
+            index: index as u64,
+            siblings,
+        })
+    }
+}
+
+impl TxInclusionProof {
+    pub fn verify(&self, root: &ZkHash, tx: &TxCommitment) -> bool {
+        let mut hash = tx.leaf_hash();
+        for (depth, sibling) in self.siblings.iter().enumerate() {
+            hash = if (self.index >> depth) & 1 == 0 {
+                node_hash(&hash, sibling)
+            } else {
+                node_hash(sibling, &hash)
+            };
+        }
+        self.index >> self.siblings.len() == 0 && hash == *root
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::types::block::witness::{BlockWitnessTree, TxCommitment};
+    use crate::types::ssz::Ssz;
+    use plonky2::field::goldilocks_field::GoldilocksField;
+    use plonky2::field::types::Field;
+
+    fn tx(seed: u64) -> TxCommitment {
+        let hash = |offset: u64| [GoldilocksField::from_canonical_u64(seed * 16 + offset); 4];
+        TxCommitment {
+            program_hash: hash(0),
+            state_root_before: hash(1),
+            state_root_after: hash(2),
+            receipt_hash: hash(3),
+        }
+    }
+
+    #[test]
+    fn test_block_witness_tree() {
+        let txs: Vec<TxCommitment> = (0..5).map(tx).collect();
+        let tree = BlockWitnessTree::new(&txs);
+        let root = tree.root();
+        for (index, commitment) in txs.iter().enumerate() {
+            let proof = tree.inclusion_proof(index).unwrap();
+            assert_eq!(proof.siblings.len(), 3);
+            assert!(proof.verify(&root, commitment));
+        }
+        assert!(tree.inclusion_proof(5).is_none());
+
+        let mut proof = tree.inclusion_proof(2).unwrap();
+        assert!(!proof.verify(&root, &txs[3]));
+        proof.index = 3;
+        assert!(!proof.verify(&root, &txs[2]));
+
+        let mut reordered = txs.clone();
+        reordered.swap(0, 1);
+        assert_ne!(BlockWitnessTree::new(&reordered).root(), root);
+        assert_eq!(
+            BlockWitnessTree::new(&[]).root(),
+            [GoldilocksField::ZERO; 4]
+        );
+    }
+
+    #[test]
+    fn test_tx_commitment_ssz() {
+        let commitment = tx(3);
+        let bytes = commitment.to_ssz();
+        assert_eq!(bytes.len(), 128);
+        assert_eq!(TxCommitment::from_ssz_bytes(&bytes).unwrap(), commitment);
+        assert!(TxCommitment::from_ssz_bytes(&bytes[1..]).is_err());
+    }
+}
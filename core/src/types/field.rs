@@ -0,0 +1,181 @@
+//! Wire representation of field elements shared by every public format.
+//!
+//! A field element is written as its canonical u64: eight big-endian bytes in
+//! binary formats and a plain number in serde formats. Decoding rejects values
+//! that are not below the field order, so every element has exactly one
+//! encoding. `GoldilocksField` itself serializes the same way, but reduces such
+//! values on read, as they were written before, and so does
+//! `fields_from_bytes_reduced` for bytes stored in a db. Version 0 proofs
+//! are the one exception, they wrote little-endian bytes.
+
+use crate::types::merkle_tree::GOLDILOCKS_FIELD_U8_LEN;
+use plonky2::field::types::{Field64, PrimeField64};
+use thiserror::Error;
+
+#[derive(Error, Debug, Clone, PartialEq, Eq)]
+pub enum FieldEncodingError {
+    #[error("{0} is not a canonical field element")]
+    NonCanonical(u64),
+
+    #[error("{0} bytes do not hold a whole number of field elements")]
+    InvalidLength(usize),
+}
+
+pub fn field_from_canonical<F: Field64>(value: u64) -> Result<F, FieldEncodingError> {
+    if value >= F::ORDER {
+        return Err(FieldEncodingError::NonCanonical(value));
+    }
+    Ok(F::from_canonical_u64(value))
+}
+
+pub fn field_to_bytes<F: PrimeField64>(value: &F) -> [u8; GOLDILOCKS_FIELD_U8_LEN] {
+    value.to_canonical_u64().to_be_bytes()
+}
+
+pub fn field_from_bytes<F: Field64>(
+    bytes: [u8; GOLDILOCKS_FIELD_U8_LEN],
+) -> Result<F, FieldEncodingError> {
+    field_from_canonical(u64::from_be_bytes(bytes))
+}
+
+pub fn fields_to_bytes<F: PrimeField64>(values: &[F]) -> Vec<u8> {
+    values.iter().flat_map(field_to_bytes).collect()
+}
+
+pub fn fields_from_bytes<F: Field64>(bytes: &[u8]) -> Result<Vec<F>, FieldEncodingError> {
+    if bytes.len() % GOLDILOCKS_FIELD_U8_LEN != 0 {
+        return Err(FieldEncodingError::InvalidLength(bytes.len()));
+    }
+    bytes
+        .chunks(GOLDILOCKS_FIELD_U8_LEN)
+        .map(|chunk| field_from_bytes(chunk.try_into().unwrap()))
+        .collect()
+}
+
+/// Like `fields_from_bytes`, but reduces values not below the field order
+/// instead of rejecting them. Storage written before the canonical encoding
+/// may hold such values, so keys and values read back from a db go through
+/// this.
+pub fn fields_from_bytes_reduced<F: Field64>(bytes: &[u8]) -> Result<Vec<F>, FieldEncodingError> {
+    if bytes.len() % GOLDILOCKS_FIELD_U8_LEN != 0 {
+        return Err(FieldEncodingError::InvalidLength(bytes.len()));
+    }
+    Ok(bytes
+        .chunks(GOLDILOCKS_FIELD_U8_LEN)
+        .map(|chunk| F::from_noncanonical_u64(u64::from_be_bytes(chunk.try_into().unwrap())))
+        .collect())
+}
+
+/// Serde adapter for `#[serde(with = "canonical")]` on fields typed by a
+/// generic field.
+pub mod canonical {
+    use super::field_from_canonical;
+    use plonky2::field::types::{Field64, PrimeField64};
+    use serde::{de::Error, Deserialize, Deserializer, Serializer};
+
+    pub fn serialize<F: PrimeField64, S: Serializer>(
+        value: &F,
+        serializer: S,
+    ) -> Result<S::Ok, S::Error> {
+        serializer.serialize_u64(value.to_canonical_u64())
+    }
+
+    pub fn deserialize<'de, F: Field64, D: Deserializer<'de>>(
+        deserializer: D,
+    ) -> Result<F, D::Error> {
+        field_from_canonical(u64::deserialize(deserializer)?).map_err(D::Error::custom)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::types::field::{
+        canonical, field_from_bytes, field_to_bytes, fields_from_bytes, fields_from_bytes_reduced,
+        fields_to_bytes, FieldEncodingError,
+    };
+    use plonky2::field::goldilocks_field::GoldilocksField;
+    use plonky2::field::types::{Field, Field64};
+    use serde::{Deserialize, Serialize};
+
+    #[derive(Debug, PartialEq, Serialize, Deserialize)]
+    struct Wrapped {
+        #[serde(with = "canonical")]
+        value: GoldilocksField,
+    }
+
+    #[test]
+    fn test_field_bytes_round_trip() {
+        let values = vec![
+            GoldilocksField::ZERO,
+            GoldilocksField::from_canonical_u64(0x0102030405060708),
+            GoldilocksField::NEG_ONE,
+        ];
+        let bytes = fields_to_bytes(&values);
+        assert_eq!(&bytes[8..16], &[1, 2, 3, 4, 5, 6, 7, 8]);
+        assert_eq!(
+            fields_from_bytes::<GoldilocksField>(&bytes).unwrap(),
+            values
+        );
+
+        // a non reduced element encodes like its canonical value.
+        let unreduced = GoldilocksField(GoldilocksField::ORDER + 5);
+        assert_eq!(field_to_bytes(&unreduced), 5u64.to_be_bytes());
+
+        assert_eq!(
+            field_from_bytes::<GoldilocksField>(GoldilocksField::ORDER.to_be_bytes()),
+            Err(FieldEncodingError::NonCanonical(GoldilocksField::ORDER))
+        );
+        assert_eq!(
+            fields_from_bytes::<GoldilocksField>(&bytes[1..]),
+            Err(FieldEncodingError::InvalidLength(23))
+        );
+
+        // stored bytes of a non reduced element read back reduced.
+        let mut stored = bytes.clone();
+        stored[..8].copy_from_slice(&(GoldilocksField::ORDER + 5).to_be_bytes());
+        assert_eq!(
+            fields_from_bytes_reduced::<GoldilocksField>(&stored).unwrap()[0],
+            GoldilocksField::from_canonical_u64(5)
+        );
+        assert_eq!(
+            fields_from_bytes_reduced::<GoldilocksField>(&bytes[1..]),
+            Err(FieldEncodingError::InvalidLength(23))
+        );
+    }
+
+    #[test]
+    fn test_field_serde_round_trip() {
+        let unreduced = Wrapped {
+            value: GoldilocksField(GoldilocksField::ORDER + 5),
+        };
+        let json = serde_json::to_string(&unreduced).unwrap();
+        assert_eq!(json, r#"{"value":5}"#);
+        assert_eq!(
+            serde_json::from_str::<Wrapped>(&json).unwrap(),
+            Wrapped {
+                value: GoldilocksField::from_canonical_u64(5)
+            }
+        );
+        assert!(serde_json::from_str::<Wrapped>(&format!(
+            r#"{{"value":{}}}"#,
+            GoldilocksField::ORDER
+        ))
+        .is_err());
+
+        // the encoding of the field type itself is canonical as well.
+        assert_eq!(
+            serde_json::to_string(&GoldilocksField(GoldilocksField::ORDER + 5)).unwrap(),
+            "5"
+        );
+        assert_eq!(
+            serde_json::from_str::<GoldilocksField>("5").unwrap(),
+            GoldilocksField::from_canonical_u64(5)
+        );
+        // but reads the non reduced values written before.
+        assert_eq!(
+            serde_json::from_str::<GoldilocksField>(&(GoldilocksField::ORDER + 5).to_string())
+                .unwrap(),
+            GoldilocksField::from_canonical_u64(5)
+        );
+    }
+}
@@ -1,8 +1,8 @@
 pub mod constant;
 use crate::impl_from_wrapper;
+use crate::types::field::{fields_from_bytes_reduced, fields_to_bytes};
 use crate::types::merkle_tree::constant::ROOT_TREE_DEPTH;
 use crate::vm::vm_state::Address;
-use itertools::Itertools;
 use plonky2::field::goldilocks_field::GoldilocksField;
 use plonky2::field::types::Field;
 use serde::{Deserialize, Serialize};
@@ -75,6 +75,20 @@ pub struct RepeatedStorageWrite {
     pub value: TreeValue,
 }
 
+#[derive(Clone, Debug, Deserialize, Serialize, Default, Eq, PartialEq)]
+pub struct TreeLeaf {
+    pub index: u64,
+    pub key: TreeKey,
+    pub value: TreeValue,
+}
+
+/// Canonical dump of an account tree, leaves sorted by leaf index.
+#[derive(Clone, Debug, Deserialize, Serialize, Default, Eq, PartialEq)]
+pub struct TreeDump {
+    pub root_hash: ZkHash,
+    pub leaves: Vec<TreeLeaf>,
+}
+
 #[derive(Clone, Copy, Debug, PartialEq)]
 pub enum TreeOperation {
     Write {
@@ -150,27 +164,14 @@ pub fn u8_arr_to_tree_key(value: &Vec<u8>) -> TreeKey {
         GOLDILOCKS_FIELD_U8_LEN * TREE_VALUE_LEN,
         "u8_array len is not equal TreeKey len"
     );
-    value
-        .iter()
-        .chunks(GOLDILOCKS_FIELD_U8_LEN)
-        .into_iter()
-        .enumerate()
-        .fold(
-            [GoldilocksField::ZERO; TREE_VALUE_LEN],
-            |mut tree_key, (index, chunk)| {
-                tree_key[index] = GoldilocksField::from_canonical_u64(u64::from_be_bytes(
-                    chunk.map(|e| *e).collect::<Vec<_>>().try_into().unwrap(),
-                ));
-                tree_key
-            },
-        )
+    fields_from_bytes_reduced(value)
+        .unwrap()
+        .try_into()
+        .unwrap()
 }
 
 pub fn tree_key_to_u8_arr(value: &TreeKey) -> Vec<u8> {
-    value.iter().fold(Vec::new(), |mut key_vec, item| {
-        key_vec.extend(item.0.to_be_bytes().to_vec());
-        key_vec
-    })
+    fields_to_bytes(value)
 }
 
 pub fn encode_addr(addr: &Address) -> String {
@@ -1,7 +1,9 @@
 pub mod account;
 pub mod block;
+pub mod field;
 pub mod merkle_tree;
 pub mod proof;
+pub mod ssz;
 pub mod storage;
 
 pub use plonky2::field::goldilocks_field::GoldilocksField;
@@ -0,0 +1,560 @@
+//! SimpleSerialize (SSZ) encoding and merkleization of the values OlaVM
+//! exports to other chains, following the consensus spec encoding rules:
+//! little endian integers, fixed length fields inline, variable length fields
+//! behind 4 byte offsets, and sha256 `hash_tree_root`s over 32 byte chunks.
+//!
+//! A goldilocks element is a `uint64` holding its canonical value, so a
+//! `ZkHash` is a `Vector[uint64, 4]`.
+
+use crate::types::merkle_tree::ZkHash;
+use plonky2::field::goldilocks_field::GoldilocksField;
+use plonky2::field::types::{Field, Field64, PrimeField64};
+use sha2::{Digest, Sha256};
+use thiserror::Error;
+
+pub const BYTES_PER_CHUNK: usize = 32;
+pub const BYTES_PER_OFFSET: usize = 4;
+
+pub type Root = [u8; BYTES_PER_CHUNK];
+
+#[derive(Debug, Error, PartialEq, Eq)]
+pub enum SszError {
+    #[error("ssz: expected {0} bytes, got {1}")]
+    InvalidLength(usize, usize),
+
+    #[error("ssz: invalid offset {0}")]
+    InvalidOffset(usize),
+
+    #[error("ssz: {0} is not a canonical field element")]
+    NonCanonicalField(u64),
+
+    #[error("ssz: list of {0} items over limit {1}")]
+    ListTooLong(usize, usize),
+
+    #[error("ssz: {0} is not a boolean")]
+    InvalidBool(u8),
+}
+
+/// A type with a canonical SSZ encoding.
+pub trait Ssz: Sized {
+    /// Length of the encoding if every value encodes to the same length,
+    /// `None` for variable length types.
+    fn fixed_len() -> Option<usize>;
+
+    fn ssz_append(&self, buf: &mut Vec<u8>);
+
+    fn from_ssz_bytes(bytes: &[u8]) -> Result<Self, SszError>;
+
+    fn to_ssz(&self) -> Vec<u8> {
+        let mut buf = vec![];
+        self.ssz_append(&mut buf);
+        buf
+    }
+}
+
+/// The `hash_tree_root` of a value. Lists don't implement it since their
+/// root depends on the list limit, use `list_root` instead.
+pub trait HashTreeRoot {
+    fn hash_tree_root(&self) -> Root;
+}
+
+fn check_len(bytes: &[u8], len: usize) -> Result<(), SszError> {
+    if bytes.len() != len {
+        return Err(SszError::InvalidLength(len, bytes.len()));
+    }
+    Ok(())
+}
+
+fn read_offset(bytes: &[u8]) -> Result<usize, SszError> {
+    check_len(bytes, BYTES_PER_OFFSET)?;
+    Ok(u32::from_le_bytes(bytes.try_into().unwrap()) as usize)
+}
+
+fn write_offset(buf: &mut Vec<u8>, offset: usize) {
+    buf.extend_from_slice(&(offset as u32).to_le_bytes());
+}
+
+/// A 32 byte chunk holding `bytes` with zero padding on the right.
+pub fn chunk(bytes: &[u8]) -> Root {
+    assert!(bytes.len() <= BYTES_PER_CHUNK);
+    let mut chunk = [0; BYTES_PER_CHUNK];
+    chunk[..bytes.len()].copy_from_slice(bytes);
+    chunk
+}
+
+pub fn hash_pair(left: &Root, right: &Root) -> Root {
+    let mut hasher = Sha256::new();
+    hasher.update(left);
+    hasher.update(right);
+    hasher.finalize().into()
+}
+
+/// Root of the binary merkle tree over `chunks` padded with zero chunks to
+/// `limit` leaves rounded up to a power of two.
+pub fn merkleize(chunks: &[Root], limit: usize) -> Root {
+    assert!(chunks.len() <= limit.max(1));
+    let depth = limit.max(1).next_power_of_two().trailing_zeros() as usize;
+    let mut zero = [0; BYTES_PER_CHUNK];
+    let mut level = chunks.to_vec();
+    for _ in 0..depth {
+        if level.len() % 2 == 1 {
+            level.push(zero);
+        }
+        level = level
+            .chunks(2)
+            .map(|pair| hash_pair(&pair[0], &pair[1]))
+            .collect();
+        zero = hash_pair(&zero, &zero);
+    }
+    level.first().copied().unwrap_or(zero)
+}
+
+pub fn mix_in_length(root: &Root, len: usize) -> Root {
+    hash_pair(root, &chunk(&(len as u64).to_le_bytes()))
+}
+
+/// `hash_tree_root` of a `List[T, limit]` of composite items.
+pub fn list_root<T: HashTreeRoot>(items: &[T], limit: usize) -> Root {
+    let roots: Vec<Root> = items.iter().map(|item| item.hash_tree_root()).collect();
+    mix_in_length(&merkleize(&roots, limit), items.len())
+}
+
+/// Decodes a `List[T, limit]`.
+pub fn list_from_ssz_bytes<T: Ssz>(bytes: &[u8], limit: usize) -> Result<Vec<T>, SszError> {
+    let items = Vec::<T>::from_ssz_bytes(bytes)?;
+    if items.len() > limit {
+        return Err(SszError::ListTooLong(items.len(), limit));
+    }
+    Ok(items)
+}
+
+impl Ssz for u64 {
+    fn fixed_len() -> Option<usize> {
+        Some(8)
+    }
+
+    fn ssz_append(&self, buf: &mut Vec<u8>) {
+        buf.extend_from_slice(&self.to_le_bytes());
+    }
+
+    fn from_ssz_bytes(bytes: &[u8]) -> Result<Self, SszError> {
+        check_len(bytes, 8)?;
+        Ok(u64::from_le_bytes(bytes.try_into().unwrap()))
+    }
+}
+
+impl HashTreeRoot for u64 {
+    fn hash_tree_root(&self) -> Root {
+        chunk(&self.to_le_bytes())
+    }
+}
+
+impl Ssz for bool {
+    fn fixed_len() -> Option<usize> {
+        Some(1)
+    }
+
+    fn ssz_append(&self, buf: &mut Vec<u8>) {
+        buf.push(*self as u8);
+    }
+
+    fn from_ssz_bytes(bytes: &[u8]) -> Result<Self, SszError> {
+        check_len(bytes, 1)?;
+        match bytes[0] {
+            0 => Ok(false),
+            1 => Ok(true),
+            byte => Err(SszError::InvalidBool(byte)),
+        }
+    }
+}
+
+impl HashTreeRoot for bool {
+    fn hash_tree_root(&self) -> Root {
+        chunk(&[*self as u8])
+    }
+}
+
+impl Ssz for GoldilocksField {
+    fn fixed_len() -> Option<usize> {
+        Some(8)
+    }
+
+    fn ssz_append(&self, buf: &mut Vec<u8>) {
+        self.to_canonical_u64().ssz_append(buf);
+    }
+
+    fn from_ssz_bytes(bytes: &[u8]) -> Result<Self, SszError> {
+        let value = u64::from_ssz_bytes(bytes)?;
+        if value >= GoldilocksField::ORDER {
+            return Err(SszError::NonCanonicalField(value));
+        }
+        Ok(GoldilocksField(value))
+    }
+}
+
+impl HashTreeRoot for GoldilocksField {
+    fn hash_tree_root(&self) -> Root {
+        self.to_canonical_u64().hash_tree_root()
+    }
+}
+
+impl Ssz for ZkHash {
+    fn fixed_len() -> Option<usize> {
+        Some(32)
+    }
+
+    fn ssz_append(&self, buf: &mut Vec<u8>) {
+        for limb in self {
+            limb.ssz_append(buf);
+        }
+    }
+
+    fn from_ssz_bytes(bytes: &[u8]) -> Result<Self, SszError> {
+        check_len(bytes, 32)?;
+        let mut hash = [GoldilocksField::ZERO; 4];
+        for (limb, bytes) in hash.iter_mut().zip(bytes.chunks(8)) {
+            *limb = GoldilocksField::from_ssz_bytes(bytes)?;
+        }
+        Ok(hash)
+    }
+}
+
+impl HashTreeRoot for ZkHash {
+    // four packed uint64s fill exactly one chunk
+    fn hash_tree_root(&self) -> Root {
+        chunk(&self.to_ssz())
+    }
+}
+
+/// `Vector[byte, N]`, e.g. `Bytes32` for hashes or `Bytes20` for addresses.
+impl<const N: usize> Ssz for [u8; N] {
+    fn fixed_len() -> Option<usize> {
+        Some(N)
+    }
+
+    fn ssz_append(&self, buf: &mut Vec<u8>) {
+        buf.extend_from_slice(self);
+    }
+
+    fn from_ssz_bytes(bytes: &[u8]) -> Result<Self, SszError> {
+        check_len(bytes, N)?;
+        Ok(bytes.try_into().unwrap())
+    }
+}
+
+impl<const N: usize> HashTreeRoot for [u8; N] {
+    fn hash_tree_root(&self) -> Root {
+        let chunks: Vec<Root> = self.chunks(BYTES_PER_CHUNK).map(chunk).collect();
+        merkleize(&chunks, (N + BYTES_PER_CHUNK - 1) / BYTES_PER_CHUNK)
+    }
+}
+
+/// `Vector[uint64, N]`, e.g. an `(addr, value)` memory cell.
+impl<const N: usize> Ssz for [u64; N] {
+    fn fixed_len() -> Option<usize> {
+        Some(8 * N)
+    }
+
+    fn ssz_append(&self, buf: &mut Vec<u8>) {
+        for value in self {
+            value.ssz_append(buf);
+        }
+    }
+
+    fn from_ssz_bytes(bytes: &[u8]) -> Result<Self, SszError> {
+        check_len(bytes, 8 * N)?;
+        let mut values = [0; N];
+        for (value, bytes) in values.iter_mut().zip(bytes.chunks(8)) {
+            *value = u64::from_ssz_bytes(bytes)?;
+        }
+        Ok(values)
+    }
+}
+
+impl<const N: usize> HashTreeRoot for [u64; N] {
+    // uint64s are packed four to a chunk
+    fn hash_tree_root(&self) -> Root {
+        let bytes = self.to_ssz();
+        let chunks: Vec<Root> = bytes.chunks(BYTES_PER_CHUNK).map(chunk).collect();
+        merkleize(&chunks, (8 * N + BYTES_PER_CHUNK - 1) / BYTES_PER_CHUNK)
+    }
+}
+
+impl<T: Ssz> Ssz for Vec<T> {
+    fn fixed_len() -> Option<usize> {
+        None
+    }
+
+    fn ssz_append(&self, buf: &mut Vec<u8>) {
+        if T::fixed_len().is_some() {
+            for item in self {
+                item.ssz_append(buf);
+            }
+            return;
+        }
+        let items: Vec<Vec<u8>> = self.iter().map(|item| item.to_ssz()).collect();
+        let mut offset = items.len() * BYTES_PER_OFFSET;
+        for item in &items {
+            write_offset(buf, offset);
+            offset += item.len();
+        }
+        for item in items {
+            buf.extend(item);
+        }
+    }
+
+    fn from_ssz_bytes(bytes: &[u8]) -> Result<Self, SszError> {
+        if let Some(len) = T::fixed_len() {
+            if bytes.len() % len != 0 {
+                return Err(SszError::InvalidLength(
+                    bytes.len() / len * len + len,
+                    bytes.len(),
+                ));
+            }
+            return bytes.chunks(len).map(T::from_ssz_bytes).collect();
+        }
+        if bytes.is_empty() {
+            return Ok(vec![]);
+        }
+        let first = read_offset(bytes.get(..BYTES_PER_OFFSET).unwrap_or(bytes))?;
+        if first % BYTES_PER_OFFSET != 0 || first == 0 || first > bytes.len() {
+            return Err(SszError::InvalidOffset(first));
+        }
+        let mut offsets = bytes[..first]
+            .chunks(BYTES_PER_OFFSET)
+            .map(read_offset)
+            .collect::<Result<Vec<_>, _>>()?;
+        offsets.push(bytes.len());
+        offsets
+            .windows(2)
+            .map(|pair| match bytes.get(pair[0]..pair[1]) {
+                Some(item) => T::from_ssz_bytes(item),
+                None => Err(SszError::InvalidOffset(pair[0])),
+            })
+            .collect()
+    }
+}
+
+/// Encodes the fields of a container in order.
+#[derive(Default)]
+pub struct ContainerEncoder {
+    // encoded fields, with whether the field is variable length
+    fields: Vec<(bool, Vec<u8>)>,
+}
+
+impl ContainerEncoder {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    pub fn field<T: Ssz>(&mut self, value: &T) -> &mut Self {
+        self.fields.push((T::fixed_len().is_none(), value.to_ssz()));
+        self
+    }
+
+    pub fn finish(&self, buf: &mut Vec<u8>) {
+        let mut offset: usize = self
+            .fields
+            .iter()
+            .map(|(variable, bytes)| {
+                if *variable {
+                    BYTES_PER_OFFSET
+                } else {
+                    bytes.len()
+                }
+            })
+            .sum();
+        for (variable, bytes) in &self.fields {
+            if *variable {
+                write_offset(buf, offset);
+                offset += bytes.len();
+            } else {
+                buf.extend_from_slice(bytes);
+            }
+        }
+        for (_, bytes) in self.fields.iter().filter(|(variable, _)| *variable) {
+            buf.extend_from_slice(bytes);
+        }
+    }
+}
+
+enum FieldBytes<'a> {
+    Fixed(&'a [u8]),
+    Offset(usize),
+}
+
+/// Decodes the fields of a container: declare every field type with
+/// `register` in order, then read them with `decode_next` in the same order.
+pub struct ContainerDecoder<'a> {
+    bytes: &'a [u8],
+    fixed_end: usize,
+    fields: Vec<FieldBytes<'a>>,
+    resolved: Vec<&'a [u8]>,
+    next: usize,
+}
+
+impl<'a> ContainerDecoder<'a> {
+    pub fn new(bytes: &'a [u8]) -> Self {
+        Self {
+            bytes,
+            fixed_end: 0,
+            fields: vec![],
+            resolved: vec![],
+            next: 0,
+        }
+    }
+
+    pub fn register<T: Ssz>(&mut self) -> Result<(), SszError> {
+        let len = T::fixed_len().unwrap_or(BYTES_PER_OFFSET);
+        let bytes =
+            self.bytes
+                .get(self.fixed_end..self.fixed_end + len)
+                .ok_or(SszError::InvalidLength(
+                    self.fixed_end + len,
+                    self.bytes.len(),
+                ))?;
+        self.fixed_end += len;
+        self.fields.push(match T::fixed_len() {
+            Some(_) => FieldBytes::Fixed(bytes),
+            None => FieldBytes::Offset(read_offset(bytes)?),
+        });
+        Ok(())
+    }
+
+    /// Resolves the variable length fields once all fields are registered.
+    pub fn finish(&mut self) -> Result<(), SszError> {
+        let mut offsets: Vec<usize> = self
+            .fields
+            .iter()
+            .filter_map(|field| match field {
+                FieldBytes::Offset(offset) => Some(*offset),
+                FieldBytes::Fixed(_) => None,
+            })
+            .collect();
+        match offsets.first() {
+            Some(&first) if first != self.fixed_end => return Err(SszError::InvalidOffset(first)),
+            None if self.bytes.len() != self.fixed_end => {
+                return Err(SszError::InvalidLength(self.fixed_end, self.bytes.len()))
+            }
+            _ => {}
+        }
+        offsets.push(self.bytes.len());
+        let mut variable = offsets.windows(2);
+        for field in &self.fields {
+            self.resolved.push(match field {
+                FieldBytes::Fixed(bytes) => *bytes,
+                FieldBytes::Offset(offset) => {
+                    let pair = variable.next().unwrap();
+                    self.bytes
+                        .get(pair[0]..pair[1])
+                        .ok_or(SszError::InvalidOffset(*offset))?
+                }
+            });
+        }
+        Ok(())
+    }
+
+    pub fn decode_next<T: Ssz>(&mut self) -> Result<T, SszError> {
+        let bytes = self.resolved[self.next];
+        self.next += 1;
+        T::from_ssz_bytes(bytes)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::types::merkle_tree::ZkHash;
+    use crate::types::ssz::{
+        hash_pair, list_from_ssz_bytes, list_root, merkleize, ContainerDecoder, ContainerEncoder,
+        HashTreeRoot, Ssz, SszError,
+    };
+    use plonky2::field::goldilocks_field::GoldilocksField;
+    use plonky2::field::types::Field;
+
+    #[test]
+    fn test_ssz_basic_types() {
+        assert_eq!(0x0102u64.to_ssz(), vec![2, 1, 0, 0, 0, 0, 0, 0]);
+        assert_eq!(true.to_ssz(), vec![1]);
+        assert_eq!(bool::from_ssz_bytes(&[2]), Err(SszError::InvalidBool(2)));
+        assert_eq!(
+            GoldilocksField::from_ssz_bytes(&u64::MAX.to_ssz()),
+            Err(SszError::NonCanonicalField(u64::MAX))
+        );
+
+        let hash: ZkHash = [1, 2, 3, 4].map(GoldilocksField::from_canonical_u64);
+        let bytes = hash.to_ssz();
+        assert_eq!(bytes.len(), 32);
+        assert_eq!(ZkHash::from_ssz_bytes(&bytes).unwrap(), hash);
+        assert_eq!(hash.hash_tree_root().to_vec(), bytes);
+
+        // a Bytes20 root is the right padded chunk
+        let mut expected = [0u8; 32];
+        expected[..20].copy_from_slice(&[7u8; 20]);
+        assert_eq!([7u8; 20].hash_tree_root(), expected);
+
+        let cell = [5u64, 6];
+        assert_eq!(<[u64; 2]>::from_ssz_bytes(&cell.to_ssz()).unwrap(), cell);
+        let mut expected = [0u8; 32];
+        expected[..16].copy_from_slice(&cell.to_ssz());
+        assert_eq!(cell.hash_tree_root(), expected);
+    }
+
+    #[test]
+    fn test_ssz_merkleize() {
+        let a = [1u8; 32];
+        let b = [2u8; 32];
+        let zero = [0u8; 32];
+        assert_eq!(merkleize(&[a], 1), a);
+        assert_eq!(merkleize(&[a, b], 2), hash_pair(&a, &b));
+        assert_eq!(
+            merkleize(&[a], 4),
+            hash_pair(&hash_pair(&a, &zero), &hash_pair(&zero, &zero))
+        );
+        assert_eq!(merkleize(&[], 2), hash_pair(&zero, &zero));
+
+        let items: Vec<ZkHash> = vec![[GoldilocksField::ONE; 4], [GoldilocksField::TWO; 4]];
+        let mut len = [0u8; 32];
+        len[0] = 2;
+        assert_eq!(
+            list_root(&items, 4),
+            hash_pair(
+                &merkleize(&[items[0].hash_tree_root(), items[1].hash_tree_root()], 4),
+                &len
+            )
+        );
+    }
+
+    #[test]
+    fn test_ssz_container() {
+        let values = vec![vec![1u64, 2], vec![], vec![3]];
+        let mut buf = vec![];
+        ContainerEncoder::new()
+            .field(&9u64)
+            .field(&values)
+            .field(&[5u8; 2])
+            .finish(&mut buf);
+        // fixed part: uint64, offset, Bytes2
+        assert_eq!(&buf[8..12], &14u32.to_le_bytes());
+
+        let mut decoder = ContainerDecoder::new(&buf);
+        decoder.register::<u64>().unwrap();
+        decoder.register::<Vec<Vec<u64>>>().unwrap();
+        decoder.register::<[u8; 2]>().unwrap();
+        decoder.finish().unwrap();
+        assert_eq!(decoder.decode_next::<u64>().unwrap(), 9);
+        assert_eq!(decoder.decode_next::<Vec<Vec<u64>>>().unwrap(), values);
+        assert_eq!(decoder.decode_next::<[u8; 2]>().unwrap(), [5, 5]);
+
+        assert_eq!(
+            list_from_ssz_bytes::<u64>(&vec![1u64, 2, 3].to_ssz(), 2),
+            Err(SszError::ListTooLong(3, 2))
+        );
+        let mut bad = buf.clone();
+        bad[8] = 13;
+        let mut decoder = ContainerDecoder::new(&bad);
+        decoder.register::<u64>().unwrap();
+        decoder.register::<Vec<Vec<u64>>>().unwrap();
+        decoder.register::<[u8; 2]>().unwrap();
+        assert!(decoder.finish().is_err());
+    }
+}
@@ -0,0 +1,195 @@
+use crate::types::merkle_tree::{TreeKey, TREE_VALUE_LEN};
+use plonky2::field::types::PrimeField64;
+use serde::{Deserialize, Serialize};
+use std::collections::HashMap;
+use std::fmt::{Display, Formatter};
+
+/// A named contract storage variable and the slot key it is stored under.
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+pub struct StorageSlot {
+    pub name: String,
+    pub ty: String,
+    pub slot: TreeKey,
+}
+
+/// Storage layout descriptor of a contract.
+#[derive(Debug, Clone, Default, PartialEq, Eq, Serialize, Deserialize)]
+pub struct StorageLayout {
+    pub slots: Vec<StorageSlot>,
+}
+
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum LayoutIncompatibility {
+    /// A slot of the old layout holds a different variable in the new one.
+    SlotReused {
+        slot: TreeKey,
+        old: String,
+        new: String,
+    },
+    /// A variable kept its name but changed its type.
+    TypeChanged {
+        name: String,
+        old_ty: String,
+        new_ty: String,
+    },
+}
+
+impl Display for LayoutIncompatibility {
+    fn fmt(&self, f: &mut Formatter<'_>) -> std::fmt::Result {
+        match self {
+            LayoutIncompatibility::SlotReused { slot, old, new } => write!(
+                f,
+                "slot {:?} reused: {} -> {}",
+                slot.map(|e| e.to_canonical_u64()),
+                old,
+                new
+            ),
+            LayoutIncompatibility::TypeChanged {
+                name,
+                old_ty,
+                new_ty,
+            } => write!(f, "type of {} changed: {} -> {}", name, old_ty, new_ty),
+        }
+    }
+}
+
+#[derive(Debug, Clone, Default, PartialEq, Eq)]
+pub struct LayoutDiff {
+    pub incompatibilities: Vec<LayoutIncompatibility>,
+    /// (old key, new key) pairs for variables that moved to another slot.
+    pub migration: Vec<(TreeKey, TreeKey)>,
+}
+
+pub fn diff_layouts(old: &StorageLayout, new: &StorageLayout) -> LayoutDiff {
+    let old_by_name: HashMap<&str, &StorageSlot> =
+        old.slots.iter().map(|s| (s.name.as_str(), s)).collect();
+    let old_by_slot: HashMap<TreeKey, &StorageSlot> =
+        old.slots.iter().map(|s| (s.slot, s)).collect();
+    let new_by_name: HashMap<&str, &StorageSlot> =
+        new.slots.iter().map(|s| (s.name.as_str(), s)).collect();
+    // kept its type and moved to another slot, so it is in the migration
+    let migrated = |name: &str| match (old_by_name.get(name), new_by_name.get(name)) {
+        (Some(prev), Some(next)) => prev.ty == next.ty && prev.slot != next.slot,
+        _ => false,
+    };
+
+    let mut diff = LayoutDiff::default();
+    for slot in &new.slots {
+        // a variable that kept its slot but changed its type is reported as
+        // TypeChanged below
+        if let Some(prev) = old_by_slot.get(&slot.slot) {
+            // moves between migrated variables, e.g. a swap, are safe: the
+            // migration loads every old slot before writing the new ones
+            if prev.name != slot.name && !(migrated(&prev.name) && migrated(&slot.name)) {
+                diff.incompatibilities
+                    .push(LayoutIncompatibility::SlotReused {
+                        slot: slot.slot,
+                        old: prev.name.clone(),
+                        new: slot.name.clone(),
+                    });
+            }
+        }
+        if let Some(prev) = old_by_name.get(slot.name.as_str()) {
+            if prev.ty != slot.ty {
+                diff.incompatibilities
+                    .push(LayoutIncompatibility::TypeChanged {
+                        name: slot.name.clone(),
+                        old_ty: prev.ty.clone(),
+                        new_ty: slot.ty.clone(),
+                    });
+            } else if prev.slot != slot.slot {
+                diff.migration.push((prev.slot, slot.slot));
+            }
+        }
+    }
+    diff
+}
+
+impl LayoutDiff {
+    pub fn is_compatible(&self) -> bool {
+        self.incompatibilities.is_empty()
+    }
+
+    /// Assembly program executing the migration plan. All old slots are loaded
+    /// before any new slot is written, so chained and swapped moves are safe.
+    pub fn migration_program(&self) -> String {
+        let key_offset: i64 = -6;
+        let value_offset = |i: usize| -> i64 { key_offset - (TREE_VALUE_LEN * (i + 1)) as i64 };
+        let frame = 6 + TREE_VALUE_LEN * self.migration.len();
+
+        let mut lines = vec!["main:".to_string(), format!(".frame {}", frame)];
+        let mut emit = |key: &TreeKey, value_at: i64, op: &str| {
+            for (i, limb) in key.iter().enumerate() {
+                lines.push(format!("mov r0 {}", limb.to_canonical_u64()));
+                lines.push(format!("mstore [r9,{}] r0", key_offset + i as i64));
+            }
+            lines.push(format!("add r1 r9 {}", key_offset));
+            lines.push(format!("add r2 r9 {}", value_at));
+            lines.push(format!("{} r1 r2", op));
+        };
+        for (i, (old_key, _)) in self.migration.iter().enumerate() {
+            emit(old_key, value_offset(i), "sload");
+        }
+        for (i, (_, new_key)) in self.migration.iter().enumerate() {
+            emit(new_key, value_offset(i), "sstore");
+        }
+        lines.push("end".to_string());
+        lines.join("\n")
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::types::storage::layout::{
+        diff_layouts, LayoutIncompatibility, StorageLayout, StorageSlot,
+    };
+    use plonky2::field::goldilocks_field::GoldilocksField;
+    use plonky2::field::types::Field;
+
+    fn slot(name: &str, ty: &str, key: u64) -> StorageSlot {
+        StorageSlot {
+            name: name.to_string(),
+            ty: ty.to_string(),
+            slot: [
+                GoldilocksField::ZERO,
+                GoldilocksField::ZERO,
+                GoldilocksField::ZERO,
+                GoldilocksField::from_canonical_u64(key),
+            ],
+        }
+    }
+
+    #[test]
+    fn test_diff_layouts() {
+        let old = StorageLayout {
+            slots: vec![slot("owner", "address", 0), slot("total", "u32", 1)],
+        };
+        let new = StorageLayout {
+            slots: vec![
+                slot("paused", "bool", 0),
+                slot("owner", "address", 2),
+                slot("total", "u64", 1),
+            ],
+        };
+        let diff = diff_layouts(&old, &new);
+        assert!(!diff.is_compatible());
+        assert_eq!(diff.incompatibilities.len(), 2);
+        assert!(matches!(
+            diff.incompatibilities[0],
+            LayoutIncompatibility::SlotReused { .. }
+        ));
+        assert!(matches!(
+            diff.incompatibilities[1],
+            LayoutIncompatibility::TypeChanged { .. }
+        ));
+        assert_eq!(diff.migration, vec![(old.slots[0].slot, new.slots[1].slot)]);
+
+        // swapped slots are migrated, not reused
+        let new = StorageLayout {
+            slots: vec![slot("owner", "address", 1), slot("total", "u32", 0)],
+        };
+        let diff = diff_layouts(&old, &new);
+        assert!(diff.is_compatible());
+        assert_eq!(diff.migration.len(), 2);
+    }
+}
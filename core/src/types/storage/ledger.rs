@@ -0,0 +1,214 @@
+//! Double-entry balance bookkeeping of token contracts.
+//!
+//! A token keeps its balances and total supply in slots under one prefix
+//! word `base`: the total supply at `[base, 0, 0, 0]` and the balance of a
+//! holder at `[base, h0, h1, h2]`, the first three words of the poseidon of
+//! its address. Amounts are u32 in the last word of the slot value, which is
+//! what `std_ledger_transfer` range checks.
+//!
+//! Every balance change is booked against another balance or against the
+//! total supply, so the balances always sum to the supply. `Ledger` keeps
+//! that for genesis state and tests, the executor checks it on the storage
+//! diff of a transaction.
+
+use crate::crypto::poseidon_trace::calculate_arbitrary_poseidon;
+use crate::types::account::Address;
+use crate::types::merkle_tree::{TreeKey, TreeValue, TREE_VALUE_LEN};
+use plonky2::field::goldilocks_field::GoldilocksField;
+use plonky2::field::types::{Field, PrimeField64};
+use std::collections::BTreeMap;
+use thiserror::Error;
+
+#[derive(Error, Debug, Clone, PartialEq, Eq)]
+pub enum LedgerError {
+    #[error("balance {balance} is less than {amount}")]
+    InsufficientBalance { balance: u32, amount: u32 },
+    #[error("total supply overflows minting {0}")]
+    SupplyOverflow(u32),
+    #[error("balances sum to {balances}, total supply is {supply}")]
+    Unbalanced { balances: u64, supply: u64 },
+}
+
+/// Slots of a token, see the module docs.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct LedgerLayout {
+    pub base: GoldilocksField,
+}
+
+impl LedgerLayout {
+    pub fn new(base: u64) -> Self {
+        Self {
+            base: GoldilocksField::from_canonical_u64(base),
+        }
+    }
+
+    pub fn supply_slot(&self) -> TreeKey {
+        [
+            self.base,
+            GoldilocksField::ZERO,
+            GoldilocksField::ZERO,
+            GoldilocksField::ZERO,
+        ]
+    }
+
+    pub fn balance_slot(&self, holder: &Address) -> TreeKey {
+        let hash = calculate_arbitrary_poseidon(holder);
+        [self.base, hash[0], hash[1], hash[2]]
+    }
+
+    pub fn is_balance_slot(&self, slot: &TreeKey) -> bool {
+        slot[0] == self.base && *slot != self.supply_slot()
+    }
+
+    pub fn is_supply_slot(&self, slot: &TreeKey) -> bool {
+        *slot == self.supply_slot()
+    }
+}
+
+/// Amount held by a balance or supply slot value.
+pub fn amount_of(value: &TreeValue) -> u64 {
+    value[TREE_VALUE_LEN - 1].to_canonical_u64()
+}
+
+pub fn amount_value(amount: u32) -> TreeValue {
+    let mut value = [GoldilocksField::ZERO; TREE_VALUE_LEN];
+    value[TREE_VALUE_LEN - 1] = GoldilocksField::from_canonical_u64(amount as u64);
+    value
+}
+
+/// Balances and total supply of a token, updated by double entries only.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct Ledger {
+    pub layout: LedgerLayout,
+    balances: BTreeMap<[u64; TREE_VALUE_LEN], (Address, u32)>,
+    total_supply: u32,
+}
+
+impl Ledger {
+    pub fn new(layout: LedgerLayout) -> Self {
+        Self {
+            layout,
+            balances: BTreeMap::new(),
+            total_supply: 0,
+        }
+    }
+
+    pub fn total_supply(&self) -> u32 {
+        self.total_supply
+    }
+
+    pub fn balance(&self, holder: &Address) -> u32 {
+        self.balances
+            .get(&holder.map(|w| w.to_canonical_u64()))
+            .map_or(0, |(_, balance)| *balance)
+    }
+
+    fn set_balance(&mut self, holder: &Address, balance: u32) {
+        self.balances
+            .insert(holder.map(|w| w.to_canonical_u64()), (*holder, balance));
+    }
+
+    /// Credits `to` against the total supply.
+    pub fn mint(&mut self, to: &Address, amount: u32) -> Result<(), LedgerError> {
+        self.total_supply = self
+            .total_supply
+            .checked_add(amount)
+            .ok_or(LedgerError::SupplyOverflow(amount))?;
+        // bounded by the supply, which did not overflow
+        self.set_balance(to, self.balance(to) + amount);
+        Ok(())
+    }
+
+    /// Debits `from` against the total supply.
+    pub fn burn(&mut self, from: &Address, amount: u32) -> Result<(), LedgerError> {
+        let balance = self.balance(from);
+        if balance < amount {
+            return Err(LedgerError::InsufficientBalance { balance, amount });
+        }
+        self.set_balance(from, balance - amount);
+        self.total_supply -= amount;
+        Ok(())
+    }
+
+    /// Debits `from` and credits `to`, the supply is unchanged.
+    pub fn transfer(
+        &mut self,
+        from: &Address,
+        to: &Address,
+        amount: u32,
+    ) -> Result<(), LedgerError> {
+        let balance = self.balance(from);
+        if balance < amount {
+            return Err(LedgerError::InsufficientBalance { balance, amount });
+        }
+        self.set_balance(from, balance - amount);
+        self.set_balance(to, self.balance(to) + amount);
+        Ok(())
+    }
+
+    /// Checks the balances sum to the total supply.
+    pub fn check(&self) -> Result<(), LedgerError> {
+        let balances: u64 = self.balances.values().map(|(_, b)| *b as u64).sum();
+        if balances != self.total_supply as u64 {
+            return Err(LedgerError::Unbalanced {
+                balances,
+                supply: self.total_supply as u64,
+            });
+        }
+        Ok(())
+    }
+
+    /// Slot writes storing the ledger, the total supply first, then the
+    /// balances.
+    pub fn slots(&self) -> Vec<(TreeKey, TreeValue)> {
+        let mut slots = vec![(self.layout.supply_slot(), amount_value(self.total_supply))];
+        for (holder, balance) in self.balances.values() {
+            slots.push((self.layout.balance_slot(holder), amount_value(*balance)));
+        }
+        slots
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::types::storage::ledger::{amount_of, Ledger, LedgerError, LedgerLayout};
+    use plonky2::field::goldilocks_field::GoldilocksField;
+    use plonky2::field::types::Field;
+
+    #[test]
+    fn test_ledger() {
+        let layout = LedgerLayout::new(7);
+        let alice = [GoldilocksField::ONE; 4];
+        let bob = [GoldilocksField::TWO; 4];
+        let mut ledger = Ledger::new(layout);
+        ledger.mint(&alice, 100).unwrap();
+        ledger.transfer(&alice, &bob, 30).unwrap();
+        ledger.burn(&bob, 10).unwrap();
+        assert_eq!(ledger.balance(&alice), 70);
+        assert_eq!(ledger.balance(&bob), 20);
+        assert_eq!(ledger.total_supply(), 90);
+        assert!(ledger.check().is_ok());
+
+        assert_eq!(
+            ledger.transfer(&bob, &alice, 21),
+            Err(LedgerError::InsufficientBalance {
+                balance: 20,
+                amount: 21
+            })
+        );
+        assert_eq!(
+            ledger.mint(&bob, u32::MAX),
+            Err(LedgerError::SupplyOverflow(u32::MAX))
+        );
+
+        let slots = ledger.slots();
+        assert_eq!(slots.len(), 3);
+        assert!(layout.is_supply_slot(&slots[0].0));
+        assert_eq!(amount_of(&slots[0].1), 90);
+        assert!(slots[1..]
+            .iter()
+            .all(|(slot, _)| layout.is_balance_slot(slot)));
+        assert_ne!(layout.balance_slot(&alice), layout.balance_slot(&bob));
+        assert!(!layout.is_balance_slot(&LedgerLayout::new(8).balance_slot(&alice)));
+    }
+}
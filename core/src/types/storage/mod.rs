@@ -1,15 +1,26 @@
+pub mod layout;
+pub mod ledger;
+
 use crate::crypto::poseidon_trace::calculate_poseidon_and_generate_intermediate_trace;
 use crate::trace::trace::PoseidonRow;
 use crate::types::account::{AccountTreeId, Address};
+use crate::types::field::{fields_from_bytes_reduced, fields_to_bytes};
 use crate::types::merkle_tree::{
     tree_key_default, TreeKey, GOLDILOCKS_FIELD_U8_LEN, TREE_VALUE_LEN,
 };
 use crate::util::poseidon_utils::POSEIDON_INPUT_NUM;
-use itertools::Itertools;
 use plonky2::field::goldilocks_field::GoldilocksField;
 use plonky2::field::types::Field;
 use serde::{Deserialize, Serialize};
 
+/// Key domain of contract slots.
+pub const SLOT_DOMAIN: u64 = 0;
+/// Key domain of the record linking a slot to the slot listed before it
+/// under its prefix. Leaf hashes take 1 in the domain word.
+pub const SLOT_LINK_DOMAIN: u64 = 2;
+/// Key domain of the record pointing to the slot listed last under a prefix.
+pub const SLOT_HEAD_DOMAIN: u64 = 3;
+
 /// Typed fully qualified key of the storage slot in global state tree.
 #[derive(Debug, Copy, Clone, Hash, Eq, PartialEq, Serialize, Deserialize)]
 pub struct StorageKey {
@@ -35,10 +46,22 @@ impl StorageKey {
     }
 
     pub fn raw_hashed_key(address: &Address, key: &TreeKey) -> (TreeKey, PoseidonRow) {
+        Self::raw_domain_hashed_key(address, key, SLOT_DOMAIN)
+    }
+
+    /// Tree key of `key` of the contract at `address` in `domain`, the word
+    /// hashed after the key. Slots are in `SLOT_DOMAIN`, the records of the
+    /// slot lists `sscan` walks in `SLOT_LINK_DOMAIN` and `SLOT_HEAD_DOMAIN`.
+    pub fn raw_domain_hashed_key(
+        address: &Address,
+        key: &TreeKey,
+        domain: u64,
+    ) -> (TreeKey, PoseidonRow) {
         let mut tree_key = tree_key_default();
         let mut input = [GoldilocksField::ZERO; POSEIDON_INPUT_NUM];
         input[0..TREE_VALUE_LEN].clone_from_slice(address);
         input[TREE_VALUE_LEN..TREE_VALUE_LEN * 2].clone_from_slice(key);
+        input[TREE_VALUE_LEN * 2] = GoldilocksField::from_canonical_u64(domain);
         let mut hash = calculate_poseidon_and_generate_intermediate_trace(input);
         hash.filter_looked_treekey = true;
         tree_key.clone_from_slice(&hash.output[0..TREE_VALUE_LEN]);
@@ -51,10 +74,7 @@ impl StorageKey {
 }
 
 pub fn field_arr_to_u8_arr(value: &Vec<GoldilocksField>) -> Vec<u8> {
-    value.iter().fold(Vec::new(), |mut key_vec, item| {
-        key_vec.extend(item.0.to_be_bytes().to_vec());
-        key_vec
-    })
+    fields_to_bytes(value)
 }
 
 pub fn u8_arr_to_field_arr(value: &Vec<u8>) -> Vec<GoldilocksField> {
@@ -63,15 +83,5 @@ pub fn u8_arr_to_field_arr(value: &Vec<u8>) -> Vec<GoldilocksField> {
         0,
         "u8_array len is not align to field"
     );
-    value
-        .iter()
-        .chunks(GOLDILOCKS_FIELD_U8_LEN)
-        .into_iter()
-        .enumerate()
-        .map(|(_index, chunk)| {
-            GoldilocksField::from_canonical_u64(u64::from_be_bytes(
-                chunk.map(|e| *e).collect::<Vec<_>>().try_into().unwrap(),
-            ))
-        })
-        .collect()
+    fields_from_bytes_reduced(value).unwrap()
 }
@@ -1,4 +1,6 @@
+use crate::types::field::fields_to_bytes;
 use crate::types::merkle_tree::TREE_VALUE_LEN;
+use crate::vm::memory::MEMORY_MODEL;
 use byteorder::ReadBytesExt;
 use byteorder::{BigEndian, ByteOrder};
 use plonky2::field::goldilocks_field::GoldilocksField;
@@ -15,13 +17,10 @@ pub fn split_limbs_from_field(value: &GoldilocksField) -> (u64, u64, u64, u64) {
     (limb0_u32, limb1_u32, limb2_u32, limb3_u32)
 }
 
+/// Range check limbs of `value`, see `MemoryModel::split_rc_limbs`.
 pub fn split_u16_limbs_from_field(value: &GoldilocksField) -> (u64, u64) {
-    let input = value.0;
-
-    let limb_lo = input & U16_BITS_MASK;
-    let limb_hi = input >> 16 & U16_BITS_MASK;
-
-    (limb_lo, limb_hi)
+    let limbs = MEMORY_MODEL.split_rc_limbs(value.0);
+    (limbs[0], limbs[1])
 }
 
 pub fn serialize_block_number(block_number: u32) -> Vec<u8> {
@@ -37,10 +36,5 @@ pub fn deserialize_block_number(mut bytes: &[u8]) -> u32 {
 }
 
 pub fn serialize_tree_leaf(leaf: [GoldilocksField; TREE_VALUE_LEN]) -> Vec<u8> {
-    let mut bytes = vec![0; 32];
-    for (index, item) in leaf.iter().enumerate() {
-        let field_array = item.0.to_be_bytes();
-        bytes[index * 8..(index * 8 + 8)].copy_from_slice(&field_array);
-    }
-    bytes
+    fields_to_bytes(&leaf)
 }
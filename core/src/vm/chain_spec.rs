@@ -0,0 +1,333 @@
+use crate::crypto::poseidon_trace::calculate_arbitrary_poseidon;
+use crate::types::merkle_tree::constant::ROOT_TREE_DEPTH;
+use crate::types::merkle_tree::TreeValue;
+use crate::vm::opcodes::OlaOpcode;
+use enum_iterator::all;
+use plonky2::field::goldilocks_field::GoldilocksField;
+use plonky2::field::types::Field;
+use serde::{Deserialize, Serialize};
+use std::path::Path;
+use thiserror::Error;
+
+/// Builtins an opcode can be switched off with, named after their trace
+/// table.
+pub const BUILTINS: [&str; 8] = [
+    "rangecheck",
+    "bitwise",
+    "cmp",
+    "poseidon",
+    "storage",
+    "tape",
+    "sccall",
+    "transient",
+];
+
+#[derive(Error, Debug)]
+pub enum ChainSpecError {
+    #[error("read chain spec: {0}")]
+    Io(#[from] std::io::Error),
+
+    #[error("parse chain spec: {0}")]
+    Parse(#[from] toml::de::Error),
+
+    #[error("unknown opcode {0} in fork at height {1}")]
+    UnknownOpcode(String, u64),
+
+    #[error("unknown builtin {0} in fork at height {1}")]
+    UnknownBuiltin(String, u64),
+
+    #[error("fork at height {0} does not follow the fork at height {1}")]
+    UnorderedForks(u64, u64),
+
+    #[error("storage tree depth {0} is not supported, the tree has depth {1}")]
+    TreeDepth(usize, usize),
+
+    #[error("invalid {0}: {1}")]
+    InvalidLimit(&'static str, usize),
+}
+
+/// Rules in force from block `height` on, until the next fork.
+#[derive(Debug, Clone, Default, Serialize, Deserialize, PartialEq, Eq)]
+pub struct ForkSpec {
+    pub height: u64,
+    /// Opcode tokens enabled by the fork, all of them if not given.
+    #[serde(default)]
+    pub opcodes: Option<Vec<String>>,
+    /// Builtins enabled by the fork, all of them if not given. Opcodes
+    /// using a disabled builtin are disabled too.
+    #[serde(default)]
+    pub builtins: Option<Vec<String>>,
+}
+
+impl ForkSpec {
+    pub fn opcode_enabled(&self, opcode: OlaOpcode) -> bool {
+        let enabled = |list: &Option<Vec<String>>, name: &str| {
+            list.as_ref()
+                .map_or(true, |list| list.iter().any(|item| item == name))
+        };
+        enabled(&self.opcodes, &opcode.token())
+            && builtin_of(opcode).map_or(true, |builtin| enabled(&self.builtins, builtin))
+    }
+}
+
+/// Prover parameters of the chain, see `StarkConfig`.
+#[derive(Debug, Clone, Serialize, Deserialize, PartialEq, Eq)]
+pub struct ProverSpec {
+    pub security_bits: usize,
+    pub rate_bits: usize,
+    pub cap_height: usize,
+    pub proof_of_work_bits: u32,
+    pub num_query_rounds: usize,
+}
+
+impl Default for ProverSpec {
+    fn default() -> Self {
+        Self {
+            security_bits: 100,
+            rate_bits: 3,
+            cap_height: 4,
+            proof_of_work_bits: 16,
+            num_query_rounds: 28,
+        }
+    }
+}
+
+/// Consensus parameters of a network, loaded from TOML:
+///
+/// ```toml
+/// chain_id = 1
+/// max_cycles = 4194304
+/// max_rows = 4194304
+/// max_program_words = 1048576
+/// tree_depth = 256
+///
+/// [[forks]]
+/// height = 0
+/// builtins = ["rangecheck", "bitwise", "cmp", "poseidon", "storage", "tape"]
+///
+/// [[forks]]
+/// height = 1000
+/// ```
+///
+/// A network upgrade adds a fork instead of changing constants.
+#[derive(Debug, Clone, Serialize, Deserialize, PartialEq, Eq)]
+pub struct ChainSpec {
+    pub chain_id: u64,
+    /// Cycles a block, and so each of its transactions, may execute.
+    pub max_cycles: usize,
+    /// Row capacity every table of a block has to fit into once padded.
+    pub max_rows: usize,
+    /// Code words a program may have, at most `max_rows` as the program
+    /// table takes a row per word. `max_rows` if not set.
+    #[serde(default)]
+    pub max_program_words: Option<usize>,
+    pub tree_depth: usize,
+    /// Forks ordered by height. No fork at a height means every opcode and
+    /// builtin is enabled.
+    #[serde(default)]
+    pub forks: Vec<ForkSpec>,
+    #[serde(default)]
+    pub prover: ProverSpec,
+}
+
+impl Default for ChainSpec {
+    fn default() -> Self {
+        Self {
+            chain_id: 1,
+            max_cycles: 1 << 22,
+            max_rows: 1 << 22,
+            max_program_words: None,
+            tree_depth: ROOT_TREE_DEPTH,
+            forks: vec![],
+            prover: ProverSpec::default(),
+        }
+    }
+}
+
+impl ChainSpec {
+    pub fn from_toml(text: &str) -> Result<Self, ChainSpecError> {
+        let spec: ChainSpec = toml::from_str(text)?;
+        spec.validate()?;
+        Ok(spec)
+    }
+
+    pub fn from_file<P: AsRef<Path>>(path: P) -> Result<Self, ChainSpecError> {
+        Self::from_toml(&std::fs::read_to_string(path)?)
+    }
+
+    pub fn validate(&self) -> Result<(), ChainSpecError> {
+        if self.tree_depth != ROOT_TREE_DEPTH {
+            return Err(ChainSpecError::TreeDepth(self.tree_depth, ROOT_TREE_DEPTH));
+        }
+        if self.max_cycles == 0 || self.max_cycles > u32::MAX as usize {
+            return Err(ChainSpecError::InvalidLimit("max_cycles", self.max_cycles));
+        }
+        if !self.max_rows.is_power_of_two() {
+            return Err(ChainSpecError::InvalidLimit("max_rows", self.max_rows));
+        }
+        match self.max_program_words {
+            Some(words) if words == 0 || words > self.max_rows => {
+                return Err(ChainSpecError::InvalidLimit("max_program_words", words));
+            }
+            _ => {}
+        }
+        let tokens: Vec<String> = all::<OlaOpcode>().map(|opcode| opcode.token()).collect();
+        for (index, fork) in self.forks.iter().enumerate() {
+            if index > 0 && fork.height <= self.forks[index - 1].height {
+                return Err(ChainSpecError::UnorderedForks(
+                    fork.height,
+                    self.forks[index - 1].height,
+                ));
+            }
+            for opcode in fork.opcodes.iter().flatten() {
+                if !tokens.contains(opcode) {
+                    return Err(ChainSpecError::UnknownOpcode(opcode.clone(), fork.height));
+                }
+            }
+            for builtin in fork.builtins.iter().flatten() {
+                if !BUILTINS.contains(&builtin.as_str()) {
+                    return Err(ChainSpecError::UnknownBuiltin(builtin.clone(), fork.height));
+                }
+            }
+        }
+        Ok(())
+    }
+
+    /// Code words a program may have.
+    pub fn program_words_limit(&self) -> usize {
+        self.max_program_words.unwrap_or(self.max_rows)
+    }
+
+    /// The fork in force at block `height`, `None` before the first fork.
+    pub fn fork_at(&self, height: u64) -> Option<&ForkSpec> {
+        self.forks.iter().rev().find(|fork| fork.height <= height)
+    }
+
+    pub fn opcode_enabled(&self, opcode: OlaOpcode, height: u64) -> bool {
+        self.fork_at(height)
+            .map_or(true, |fork| fork.opcode_enabled(opcode))
+    }
+
+    /// Fingerprint of the rules a block at `height` is executed under: the
+    /// chain id, the fork in force with the opcodes it enables and the prover
+    /// params. Proofs carry it in their public values, verifiers check the
+    /// executed opcodes of the proof against the fork separately.
+    pub fn fork_id(&self, height: u64) -> TreeValue {
+        let fork_height = self.fork_at(height).map_or(0, |fork| fork.height);
+        let enabled = all::<OlaOpcode>()
+            .enumerate()
+            .filter(|(_, opcode)| self.opcode_enabled(*opcode, height))
+            .fold(0u64, |mask, (index, _)| mask | 1 << index);
+        let prover = &self.prover;
+        let inputs: Vec<GoldilocksField> = [
+            self.chain_id,
+            fork_height,
+            enabled,
+            prover.security_bits as u64,
+            prover.rate_bits as u64,
+            prover.cap_height as u64,
+            prover.proof_of_work_bits as u64,
+            prover.num_query_rounds as u64,
+        ]
+        .iter()
+        .flat_map(|value| [*value as u32 as u64, *value >> 32])
+        .map(GoldilocksField::from_canonical_u64)
+        .collect();
+        calculate_arbitrary_poseidon(&inputs)
+    }
+
+    /// Opcodes disabled at block `height`.
+    pub fn disabled_opcodes(&self, height: u64) -> Vec<OlaOpcode> {
+        all::<OlaOpcode>()
+            .filter(|opcode| !self.opcode_enabled(*opcode, height))
+            .collect()
+    }
+}
+
+/// The builtin executing `opcode` besides the cpu, if any.
+pub fn builtin_of(opcode: OlaOpcode) -> Option<&'static str> {
+    match opcode {
+        OlaOpcode::RC => Some("rangecheck"),
+        OlaOpcode::AND | OlaOpcode::OR | OlaOpcode::XOR => Some("bitwise"),
+        OlaOpcode::GTE => Some("cmp"),
+        OlaOpcode::POSEIDON => Some("poseidon"),
+        OlaOpcode::SLOAD | OlaOpcode::SSTORE | OlaOpcode::SSCAN => Some("storage"),
+        OlaOpcode::TLOAD | OlaOpcode::TSTORE => Some("tape"),
+        OlaOpcode::SCCALL => Some("sccall"),
+        OlaOpcode::TLOADT | OlaOpcode::TSTORET => Some("transient"),
+        _ => None,
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::vm::chain_spec::{ChainSpec, ChainSpecError};
+    use crate::vm::opcodes::OlaOpcode;
+
+    #[test]
+    fn test_chain_spec_forks() {
+        let spec = ChainSpec::from_toml(
+            r#"
+            chain_id = 7
+            max_cycles = 65536
+            max_rows = 1048576
+            tree_depth = 256
+
+            [[forks]]
+            height = 0
+            builtins = ["rangecheck", "bitwise", "cmp", "poseidon", "storage", "tape"]
+
+            [[forks]]
+            height = 100
+
+            [prover]
+            security_bits = 100
+            rate_bits = 3
+            cap_height = 2
+            proof_of_work_bits = 16
+            num_query_rounds = 28
+            "#,
+        )
+        .unwrap();
+        assert_eq!(spec.chain_id, 7);
+        assert_eq!(spec.prover.cap_height, 2);
+        assert!(!spec.opcode_enabled(OlaOpcode::SCCALL, 0));
+        assert!(!spec.opcode_enabled(OlaOpcode::TSTORET, 99));
+        assert!(spec.opcode_enabled(OlaOpcode::ADD, 99));
+        assert!(spec.opcode_enabled(OlaOpcode::SCCALL, 100));
+        assert_eq!(
+            spec.disabled_opcodes(5),
+            vec![OlaOpcode::SCCALL, OlaOpcode::TLOADT, OlaOpcode::TSTORET]
+        );
+        assert!(spec.disabled_opcodes(100).is_empty());
+        assert_eq!(spec.fork_id(0), spec.fork_id(99));
+        assert_ne!(spec.fork_id(99), spec.fork_id(100));
+        let mut other_chain = spec.clone();
+        other_chain.chain_id = 8;
+        assert_ne!(other_chain.fork_id(100), spec.fork_id(100));
+
+        let unknown = ChainSpec::from_toml(
+            "chain_id = 1\nmax_cycles = 8\nmax_rows = 8\ntree_depth = 256\n\
+             [[forks]]\nheight = 0\nopcodes = [\"add\", \"sub\"]\n",
+        );
+        assert!(matches!(unknown, Err(ChainSpecError::UnknownOpcode(op, 0)) if op == "sub"));
+        let depth =
+            ChainSpec::from_toml("chain_id = 1\nmax_cycles = 8\nmax_rows = 8\ntree_depth = 64\n");
+        assert!(matches!(depth, Err(ChainSpecError::TreeDepth(64, 256))));
+        assert!(ChainSpec::from_toml("chain_id = 1").is_err());
+
+        assert_eq!(spec.program_words_limit(), 1048576);
+        let program = ChainSpec::from_toml(
+            "chain_id = 1\nmax_cycles = 8\nmax_rows = 8\nmax_program_words = 4\ntree_depth = 256\n",
+        )
+        .unwrap();
+        assert_eq!(program.program_words_limit(), 4);
+        let program = ChainSpec::from_toml(
+            "chain_id = 1\nmax_cycles = 8\nmax_rows = 8\nmax_program_words = 16\ntree_depth = 256\n",
+        );
+        assert!(matches!(
+            program,
+            Err(ChainSpecError::InvalidLimit("max_program_words", 16))
+        ));
+    }
+}
@@ -0,0 +1,94 @@
+use crate::vm::error::ProcessorError;
+use plonky2::field::goldilocks_field::GoldilocksField;
+use plonky2::field::types::Field;
+use serde::{Deserialize, Serialize};
+use std::fmt::{Display, Formatter};
+
+/// Highest clk an execution may reach. The memory table range checks clk
+/// differences as u32 and a `sccall` step writes `clk + 1` to its table, so
+/// clk stays below `u32::MAX`.
+pub const MAX_CLK: u32 = u32::MAX - 1;
+
+/// Step counter of an execution, a u32 in every trace table. Stepping past
+/// the max is an error instead of a wrap around.
+#[derive(
+    Debug, Clone, Copy, Default, PartialEq, Eq, PartialOrd, Ord, Hash, Serialize, Deserialize,
+)]
+#[serde(transparent)]
+pub struct Clk(u32);
+
+impl Clk {
+    pub const ZERO: Clk = Clk(0);
+
+    pub fn new(clk: u32) -> Self {
+        Self(clk)
+    }
+
+    pub fn get(self) -> u32 {
+        self.0
+    }
+
+    pub fn to_field(self) -> GoldilocksField {
+        GoldilocksField::from_canonical_u32(self.0)
+    }
+
+    /// The next clk, `ClkOverflow` once it would pass `max`, capped at
+    /// `MAX_CLK`.
+    pub fn checked_next(self, max: u32) -> Result<Clk, ProcessorError> {
+        let max = max.min(MAX_CLK);
+        if self.0 >= max {
+            return Err(ProcessorError::ClkOverflow(self.0, max));
+        }
+        Ok(Self(self.0 + 1))
+    }
+
+    pub fn next(self) -> Result<Clk, ProcessorError> {
+        self.checked_next(MAX_CLK)
+    }
+
+    /// Steps from `earlier` to this clk, as the memory table range checks
+    /// them.
+    pub fn since(self, earlier: Clk) -> Result<u32, ProcessorError> {
+        self.0
+            .checked_sub(earlier.0)
+            .ok_or(ProcessorError::ClkBackwards(earlier.0, self.0))
+    }
+}
+
+impl From<Clk> for u32 {
+    fn from(clk: Clk) -> Self {
+        clk.0
+    }
+}
+
+impl Display for Clk {
+    fn fmt(&self, f: &mut Formatter<'_>) -> std::fmt::Result {
+        write!(f, "{}", self.0)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::vm::clk::{Clk, MAX_CLK};
+    use crate::vm::error::ProcessorError;
+
+    #[test]
+    fn test_clk_bounds() {
+        let clk = Clk::new(9).next().unwrap();
+        assert_eq!(clk.get(), 10);
+        assert!(matches!(
+            clk.checked_next(10),
+            Err(ProcessorError::ClkOverflow(10, 10))
+        ));
+        assert!(matches!(
+            Clk::new(MAX_CLK).checked_next(u32::MAX),
+            Err(ProcessorError::ClkOverflow(_, MAX_CLK))
+        ));
+        assert_eq!(Clk::new(MAX_CLK - 1).next().unwrap().get(), MAX_CLK);
+        assert_eq!(clk.since(Clk::new(4)).unwrap(), 6);
+        assert!(matches!(
+            Clk::new(4).since(clk),
+            Err(ProcessorError::ClkBackwards(10, 4))
+        ));
+    }
+}
@@ -1,3 +1,7 @@
+use crate::abi::AbiError;
+use crate::merkle_tree::TreeError;
+use crate::trace::stream::TraceStreamError;
+use crate::vm::prophet::ProphetDebugRecord;
 use thiserror::Error;
 
 #[derive(Error, Debug)]
@@ -14,12 +18,16 @@ pub enum ProcessorError {
     #[error("U32 range check fail, value out range")]
     U32RangeCheckFail,
 
-    #[error("assert fail: reg: {0}, value: {1}")]
-    AssertFail(u64, u64),
+    /// The register and value asserted, and the message id of `assert reg id`.
+    #[error("assert fail: reg: {0}, value: {1}{}", message_id_suffix(.2))]
+    AssertFail(u64, u64, Option<u64>),
 
     #[error("Memory visit invalid, bound addr: {0}")]
     MemVistInv(u64),
 
+    #[error("write to read only code region, addr: {0}")]
+    CodeRegionWrite(u64),
+
     #[error("Tape visit invalid, bound addr: {0}")]
     TapeVistInv(u64),
 
@@ -28,4 +36,74 @@ pub enum ProcessorError {
 
     #[error("Tload flag is invalid: {0}")]
     TloadFlagInvalid(u64),
+
+    #[error("txctx selector is invalid: {0}")]
+    TxCtxSelectorInvalid(u64),
+
+    #[error("cmov condition is not 0 or 1: {0}")]
+    CmovConditionInvalid(u64),
+
+    #[error("sscan cursor flag is not 0 or 1: {0}")]
+    SscanFlagInvalid(u64),
+
+    #[error("nondeterministic execution: table {0} diverges at row {1}")]
+    NondeterministicTrace(String, usize),
+
+    #[error("decode binary program fail: {0}")]
+    DecodeError(String),
+
+    #[error("table {0} needs about {1} rows, over capacity {2}")]
+    TraceCapacityExceeded(String, usize, usize),
+
+    #[error("{0} over limit {1}")]
+    LimitExceeded(String, u64),
+
+    #[error("clk {0} reached its max {1}")]
+    ClkOverflow(u32, u32),
+
+    #[error("clk {0} is later than clk {1}")]
+    ClkBackwards(u32, u32),
+
+    #[error("execution interrupted by the host at step {0}")]
+    Interrupted(u32),
+
+    #[error("out of gas: {0} needed, limit {1}")]
+    OutOfGas(u64, u64),
+
+    #[error("opcode {0} is not enabled at this block height")]
+    OpcodeDisabled(String),
+
+    #[error("program has {0} code words, over the limit {1}")]
+    ProgramTooLarge(usize, usize),
+
+    #[error("return arity {0} is over the limit {1}")]
+    InvalidReturnArity(usize, usize),
+
+    #[error("chunked execution does not support {0}")]
+    ChunkUnsupported(String),
+
+    #[error("{0} is not supported by the emulator")]
+    EmulatorUnsupported(String),
+
+    /// Contract, and how much its balances and total supply moved.
+    #[error("ledger of {0} is unbalanced: balances moved by {1}, total supply by {2}")]
+    LedgerUnbalanced(String, i128, i128),
+
+    #[error("abi: {0}")]
+    Abi(#[from] AbiError),
+
+    #[error("account tree: {0}")]
+    Tree(#[from] TreeError),
+
+    #[error("trace sink: {0}")]
+    TraceSink(#[from] TraceStreamError),
+
+    #[error("{0}")]
+    ProphetFailed(Box<ProphetDebugRecord>),
+}
+
+fn message_id_suffix(message_id: &Option<u64>) -> String {
+    message_id
+        .map(|id| format!(", message id: {}", id))
+        .unwrap_or_default()
 }
@@ -5,9 +5,61 @@ use plonky2::field::types::Field64;
 use serde::{Deserialize, Serialize};
 use std::collections::BTreeMap;
 
-pub const MEM_SPAN_SIZE: u64 = u32::MAX as u64;
-pub const PSP_START_ADDR: u64 = GoldilocksField::ORDER - MEM_SPAN_SIZE;
-pub const HP_START_ADDR: u64 = GoldilocksField::ORDER - 2 * MEM_SPAN_SIZE;
+/// Word and address space sizes the executor and the memory and range check
+/// tables are built for. The prophet region is the top `span` addresses of
+/// the field, the heap the `span` addresses below it and the stack the rest.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct MemoryModel {
+    /// Bits of a range checked word.
+    pub word_bits: u32,
+    /// Number of addresses of the heap and of the prophet region.
+    pub span: u64,
+    /// Bits of each limb a word is split into for the range check lookup.
+    pub rc_limb_bits: u32,
+}
+
+impl MemoryModel {
+    pub const U32: MemoryModel = MemoryModel {
+        word_bits: 32,
+        span: u32::MAX as u64,
+        rc_limb_bits: 16,
+    };
+
+    /// Largest value passing the word range check.
+    pub const fn word_max(&self) -> u64 {
+        u64::MAX >> (64 - self.word_bits)
+    }
+
+    pub const fn psp_start(&self) -> u64 {
+        GoldilocksField::ORDER - self.span
+    }
+
+    pub const fn hp_start(&self) -> u64 {
+        GoldilocksField::ORDER - 2 * self.span
+    }
+
+    /// Size of the fixed lookup table of one range check limb.
+    pub const fn rc_limb_size(&self) -> usize {
+        1 << self.rc_limb_bits
+    }
+
+    pub const fn rc_limbs(&self) -> usize {
+        (self.word_bits / self.rc_limb_bits) as usize
+    }
+
+    /// Split `value` into its `rc_limbs` limbs, least significant first.
+    pub fn split_rc_limbs(&self, value: u64) -> Vec<u64> {
+        let mask = (1 << self.rc_limb_bits) - 1;
+        (0..self.rc_limbs())
+            .map(|i| value >> (i as u32 * self.rc_limb_bits) & mask)
+            .collect()
+    }
+}
+
+pub const MEMORY_MODEL: MemoryModel = MemoryModel::U32;
+pub const MEM_SPAN_SIZE: u64 = MEMORY_MODEL.span;
+pub const PSP_START_ADDR: u64 = MEMORY_MODEL.psp_start();
+pub const HP_START_ADDR: u64 = MEMORY_MODEL.hp_start();
 
 #[derive(Debug, Copy, Clone, Serialize, Deserialize)]
 pub struct MemoryCell {
@@ -27,6 +79,8 @@ pub struct MemoryTree {
     // visit by memory address, MemoryCell vector store memory trace value， the last one is the
     // current status
     pub trace: BTreeMap<u64, Vec<MemoryCell>>,
+    // accesses since the last drain, only recorded when enabled
+    pub accesses: Option<Vec<(u64, MemoryCell)>>,
 }
 
 impl MemoryTree {
@@ -61,6 +115,9 @@ impl MemoryTree {
                 value: last_value,
             };
             mem_data.push(new_value);
+            if let Some(accesses) = &mut self.accesses {
+                accesses.push((addr, new_value));
+            }
             Ok(last_value)
         } else {
             Err(ProcessorError::MemVistInv(addr))
@@ -93,6 +150,9 @@ impl MemoryTree {
             value,
             env_idx,
         };
+        if let Some(accesses) = &mut self.accesses {
+            accesses.push((addr, new_cell));
+        }
         self.trace
             .entry(addr)
             .and_modify(|addr_trace| addr_trace.push(new_cell))
@@ -112,3 +172,34 @@ macro_rules! memory_zone_process {
         }
     };
 }
+
+#[cfg(test)]
+mod tests {
+    use crate::vm::memory::{MemoryModel, HP_START_ADDR, MEMORY_MODEL, PSP_START_ADDR};
+    use plonky2::field::goldilocks_field::GoldilocksField;
+    use plonky2::field::types::Field64;
+
+    #[test]
+    fn test_memory_model() {
+        assert_eq!(MEMORY_MODEL.word_max(), u32::MAX as u64);
+        assert_eq!(PSP_START_ADDR, GoldilocksField::ORDER - u32::MAX as u64);
+        assert_eq!(HP_START_ADDR, GoldilocksField::ORDER - 2 * u32::MAX as u64);
+        assert_eq!(MEMORY_MODEL.rc_limbs(), 2);
+        assert_eq!(MEMORY_MODEL.rc_limb_size(), 1 << 16);
+        assert_eq!(
+            MEMORY_MODEL.split_rc_limbs(0x1234_5678),
+            vec![0x5678, 0x1234]
+        );
+
+        let wide = MemoryModel {
+            word_bits: 48,
+            span: 1 << 40,
+            rc_limb_bits: 16,
+        };
+        assert_eq!(wide.word_max(), (1 << 48) - 1);
+        assert_eq!(
+            wide.split_rc_limbs(0x1111_2222_3333),
+            vec![0x3333, 0x2222, 0x1111]
+        );
+    }
+}
@@ -1,8 +1,11 @@
+pub mod chain_spec;
+pub mod clk;
 pub mod error;
 pub mod hardware;
 pub mod heap;
 pub mod memory;
 pub mod opcodes;
 pub mod operands;
+pub mod prophet;
 pub mod transaction;
 pub mod vm_state;
@@ -30,6 +30,11 @@ pub enum OlaOpcode {
     TLOAD,
     TSTORE,
     SCCALL,
+    TLOADT,
+    TSTORET,
+    SSCAN,
+    TXCTX,
+    CMOV,
 }
 
 impl Display for OlaOpcode {
@@ -68,6 +73,11 @@ impl OlaOpcode {
             OlaOpcode::TLOAD => "tload".to_string(),
             OlaOpcode::TSTORE => "tstore".to_string(),
             OlaOpcode::SCCALL => "sccall".to_string(),
+            OlaOpcode::TLOADT => "tload_t".to_string(),
+            OlaOpcode::TSTORET => "tstore_t".to_string(),
+            OlaOpcode::SSCAN => "sscan".to_string(),
+            OlaOpcode::TXCTX => "txctx".to_string(),
+            OlaOpcode::CMOV => "cmov".to_string(),
         }
     }
 
@@ -98,12 +108,22 @@ impl OlaOpcode {
             OlaOpcode::TLOAD => 9,
             OlaOpcode::TSTORE => 8,
             OlaOpcode::SCCALL => 7,
+            OlaOpcode::TLOADT => 6,
+            OlaOpcode::TSTORET => 5,
+            OlaOpcode::SSCAN => 4,
+            OlaOpcode::TXCTX => 3,
+            OlaOpcode::CMOV => 2,
         }
     }
 
     pub fn binary_bit_mask(&self) -> u64 {
         1 << self.binary_bit_shift()
     }
+
+    /// The opcode `binary_bit_mask` encodes as `mask`, if any.
+    pub fn from_bit_mask(mask: u64) -> Option<Self> {
+        all::<OlaOpcode>().find(|op| op.binary_bit_mask() == mask)
+    }
 }
 
 impl FromStr for OlaOpcode {
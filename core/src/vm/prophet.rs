@@ -0,0 +1,32 @@
+use crate::program::binary_program::OlaProphet;
+use serde::{Deserialize, Serialize};
+use std::fmt;
+
+/// Everything the prophet interpreter saw and returned for one prophet run,
+/// enough to replay the prophet on its own.
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct ProphetDebugRecord {
+    /// Pc of the instruction the prophet is attached to.
+    pub host: u64,
+    /// The prophet as run, its ctx includes the `HEAP_PTR` binding.
+    pub prophet: OlaProphet,
+    /// Code handed to the interpreter, without the `%{ %}` delimiters.
+    pub code: String,
+    /// Input values read from registers and the stack, in prophet input order.
+    pub inputs: Vec<u64>,
+    /// Returned values, the new heap ptr last. Empty if the run failed.
+    pub outputs: Vec<u64>,
+    pub error: Option<String>,
+}
+
+impl fmt::Display for ProphetDebugRecord {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(
+            f,
+            "prophet at pc {} with inputs {:?}: {}",
+            self.host,
+            self.inputs,
+            self.error.as_deref().unwrap_or("ok")
+        )
+    }
+}
@@ -0,0 +1,35 @@
+//! Trace export smoke test with the `arrow` feature.
+
+use core::trace::export::{trace_tables, write_parquet_tables};
+use core::trace::trace::Trace;
+use plonky2::field::goldilocks_field::GoldilocksField;
+use plonky2::field::types::Field;
+use tempfile::TempDir;
+
+#[test]
+fn write_parquet() {
+    let mut trace = Trace::default();
+    for i in 0..4 {
+        trace.insert_rangecheck(
+            GoldilocksField::from_canonical_u64(i),
+            (
+                GoldilocksField::ONE,
+                GoldilocksField::ZERO,
+                GoldilocksField::ZERO,
+                GoldilocksField::ZERO,
+                GoldilocksField::ZERO,
+            ),
+        );
+    }
+    let rangecheck = trace_tables(&trace)
+        .unwrap()
+        .into_iter()
+        .find(|table| table.name == "rangecheck")
+        .unwrap();
+    assert_eq!(rangecheck.to_record_batch().unwrap().num_rows(), 4);
+
+    let dir = TempDir::new().unwrap();
+    let paths = write_parquet_tables(&trace, dir.path()).unwrap();
+    assert_eq!(paths, vec![dir.path().join("rangecheck.parquet")]);
+    assert!(paths[0].exists());
+}
@@ -0,0 +1,55 @@
+use crate::preflight::TraceEstimate;
+use core::vm::chain_spec::ChainSpec;
+
+/// Proving envelope a block's witness has to fit into.
+#[derive(Debug, Clone)]
+pub struct BlockBudget {
+    /// Cpu rows, i.e. executed cycles, of all transactions together.
+    pub max_cycles: usize,
+    /// Row capacity every table of the block has to fit into once padded.
+    pub max_rows: usize,
+}
+
+impl BlockBudget {
+    pub fn from_chain_spec(spec: &ChainSpec) -> Self {
+        Self {
+            max_cycles: spec.max_cycles,
+            max_rows: spec.max_rows,
+        }
+    }
+
+    pub fn fits(&self, rows: &TraceEstimate) -> bool {
+        rows.cpu <= self.max_cycles && rows.check_capacity(self.max_rows).is_ok()
+    }
+}
+
+#[derive(Debug, Clone, Default, PartialEq, Eq)]
+pub struct PackedBlock {
+    /// Indices of the transactions in the block, in their original order.
+    pub included: Vec<usize>,
+    /// Indices of the transactions left for a later block.
+    pub excluded: Vec<usize>,
+    /// Summed rows of the included transactions.
+    pub rows: TraceEstimate,
+}
+
+/// Packs transactions into one block in the given order. A transaction is
+/// included when the block still fits `budget` with its rows added, otherwise
+/// it is skipped and later, smaller ones are still tried.
+///
+/// `txs` are the rows of each transaction, from `estimate_trace` before
+/// execution or `execution_rows` after a simulation.
+pub fn pack_block(txs: &[TraceEstimate], budget: &BlockBudget) -> PackedBlock {
+    let mut block = PackedBlock::default();
+    for (index, tx) in txs.iter().enumerate() {
+        let mut rows = block.rows.clone();
+        rows.add_scaled(tx, 1);
+        if budget.fits(&rows) {
+            block.rows = rows;
+            block.included.push(index);
+        } else {
+            block.excluded.push(index);
+        }
+    }
+    block
+}
@@ -0,0 +1,136 @@
+use crate::hooks::{Hooks, ProcessView};
+use core::vm::chain_spec::builtin_of;
+use core::vm::opcodes::OlaOpcode;
+use serde::Serialize;
+use serde_json::{json, Value};
+use std::io::Write;
+use std::str::FromStr;
+use std::time::Instant;
+
+const PID: u64 = 1;
+/// Thread id of the steps, host phases recorded with `span` go on their own.
+const STEP_TID: u64 = 1;
+const HOST_TID: u64 = 2;
+
+/// One complete ("X") event of the Chrome trace-event format, times are
+/// microseconds since the trace started.
+#[derive(Debug, Clone, Serialize)]
+pub struct TraceEvent {
+    pub name: String,
+    pub cat: String,
+    pub ph: &'static str,
+    pub ts: f64,
+    pub dur: f64,
+    pub pid: u64,
+    pub tid: u64,
+    pub args: Value,
+}
+
+/// Wall clock profile of a native execution in the Chrome trace-event format,
+/// open the written file in chrome://tracing or Perfetto.
+///
+/// Pass it as `Hooks` to `Process::execute_with_hooks` to get an event per
+/// step, named by its opcode and categorized by the table it drives
+/// ("storage", "poseidon", "memory", ...), and a "prophet" event nested in
+/// every step running a prophet. Wrap host work like tree saves in `span`.
+#[derive(Debug, Clone)]
+pub struct ChromeTrace {
+    start: Instant,
+    /// Start and pc of the running step.
+    step_start: Option<(Instant, u64)>,
+    prophet_start: Option<Instant>,
+    pub events: Vec<TraceEvent>,
+}
+
+impl Default for ChromeTrace {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+/// Trace category of the instruction `asm`.
+fn category(asm: &str) -> &'static str {
+    let token = asm.split_whitespace().next().unwrap_or_default();
+    match OlaOpcode::from_str(token) {
+        Ok(OlaOpcode::MLOAD | OlaOpcode::MSTORE) => "memory",
+        Ok(
+            OlaOpcode::JMP | OlaOpcode::CJMP | OlaOpcode::CALL | OlaOpcode::RET | OlaOpcode::END,
+        ) => "control",
+        Ok(opcode) => builtin_of(opcode).unwrap_or("cpu"),
+        Err(_) => "unknown",
+    }
+}
+
+impl ChromeTrace {
+    pub fn new() -> Self {
+        Self {
+            start: Instant::now(),
+            step_start: None,
+            prophet_start: None,
+            events: vec![],
+        }
+    }
+
+    fn micros(&self, at: Instant) -> f64 {
+        at.duration_since(self.start).as_secs_f64() * 1e6
+    }
+
+    fn push(&mut self, name: &str, cat: &str, tid: u64, from: Instant, args: Value) {
+        let now = Instant::now();
+        self.events.push(TraceEvent {
+            name: name.to_string(),
+            cat: cat.to_string(),
+            ph: "X",
+            ts: self.micros(from),
+            dur: now.duration_since(from).as_secs_f64() * 1e6,
+            pid: PID,
+            tid,
+            args,
+        });
+    }
+
+    /// Runs `f` and records it as a host phase named `name`.
+    pub fn span<T>(&mut self, name: &str, f: impl FnOnce() -> T) -> T {
+        let from = Instant::now();
+        let out = f();
+        self.push(name, "host", HOST_TID, from, json!({}));
+        out
+    }
+
+    pub fn to_json(&self) -> Value {
+        json!({
+            "traceEvents": self.events,
+            "displayTimeUnit": "ns",
+        })
+    }
+
+    pub fn write<W: Write>(&self, writer: W) -> serde_json::Result<()> {
+        serde_json::to_writer(writer, &self.to_json())
+    }
+}
+
+impl Hooks for ChromeTrace {
+    fn before_step(&mut self, view: &ProcessView) {
+        self.step_start = Some((Instant::now(), view.pc));
+    }
+
+    fn after_step(&mut self, view: &ProcessView) {
+        if let Some((from, pc)) = self.step_start.take() {
+            let name = view.asm.split_whitespace().next().unwrap_or_default();
+            let args = json!({ "clk": view.clk, "pc": pc, "asm": view.asm.trim() });
+            self.push(name, category(view.asm), STEP_TID, from, args);
+        }
+    }
+
+    fn before_prophet(&mut self, _view: &ProcessView) {
+        self.prophet_start = Some(Instant::now());
+    }
+
+    fn after_prophet(&mut self, view: &ProcessView) {
+        if let Some(from) = self.prophet_start.take() {
+            let pc = self.step_start.map(|(_, pc)| pc);
+            let args = json!({ "clk": view.clk, "pc": pc });
+            self.push("prophet", "prophet", STEP_TID, from, args);
+        }
+    }
+}
@@ -0,0 +1,200 @@
+use crate::load_tx::init_tape;
+use crate::Process;
+use core::merkle_tree::tree::AccountTree;
+use core::program::binary_program::BinaryProgram;
+use core::program::{Program, REGISTER_NUM};
+use core::types::account::Address;
+use core::vm::transaction::init_tx_context_mock;
+use itertools::Itertools;
+use plonky2::field::goldilocks_field::GoldilocksField;
+use plonky2::field::types::{Field, PrimeField64};
+use serde::{Deserialize, Serialize};
+use std::collections::BTreeMap;
+use std::path::Path;
+
+// Conformance cases pin down VM semantics as data: an asm program, its
+// calldata and the state expected once it ends. Any engine that can report a
+// `FinalState` can be checked against them with `check_case`.
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct ConformanceCase {
+    pub name: String,
+    #[serde(default)]
+    pub description: String,
+    pub program: String,
+    #[serde(default)]
+    pub calldata: Vec<u64>,
+    pub expect: Expectation,
+}
+
+/// Only the listed registers, memory cells and storage slots are checked.
+#[derive(Debug, Clone, Default, Serialize, Deserialize)]
+pub struct Expectation {
+    /// Execution must fail, nothing else is checked.
+    #[serde(default)]
+    pub error: bool,
+    /// Executed instructions, `end` included.
+    #[serde(default)]
+    pub steps: Option<u64>,
+    /// Register name (`r0`..`r9`) to value.
+    #[serde(default)]
+    pub registers: BTreeMap<String, u64>,
+    /// Memory address to last written value.
+    #[serde(default)]
+    pub memory: BTreeMap<u64, u64>,
+    /// Storage tree key to last written value.
+    #[serde(default)]
+    pub storage: Vec<([u64; 4], [u64; 4])>,
+    /// Values returned on the tape by `end`.
+    #[serde(default)]
+    pub ret: Vec<u64>,
+}
+
+#[derive(Debug, Clone, Default, PartialEq, Eq, Serialize, Deserialize)]
+pub struct FinalState {
+    pub steps: u64,
+    pub registers: [u64; REGISTER_NUM],
+    pub memory: BTreeMap<u64, u64>,
+    /// (tree key, value) pairs sorted by key.
+    pub storage: Vec<([u64; 4], [u64; 4])>,
+    pub ret: Vec<u64>,
+}
+
+fn to_u64s<const N: usize>(words: &[GoldilocksField; N]) -> [u64; N] {
+    words.map(|w| w.to_canonical_u64())
+}
+
+pub fn load_cases(dir: &Path) -> Result<Vec<ConformanceCase>, String> {
+    let mut paths: Vec<_> = std::fs::read_dir(dir)
+        .map_err(|e| e.to_string())?
+        .filter_map(|entry| entry.ok().map(|e| e.path()))
+        .filter(|path| path.extension().map_or(false, |ext| ext == "json"))
+        .collect();
+    paths.sort();
+    paths
+        .iter()
+        .map(|path| {
+            let json = std::fs::read_to_string(path).map_err(|e| e.to_string())?;
+            serde_json::from_str(&json).map_err(|e| format!("{}: {}", path.display(), e))
+        })
+        .collect()
+}
+
+/// Run `program`, the encoded `case.program`, on this executor.
+pub fn execute_case(case: &ConformanceCase, program: BinaryProgram) -> Result<FinalState, String> {
+    let mut program = Program {
+        instructions: program
+            .bytecode
+            .split("\n")
+            .map(|e| e.to_string())
+            .collect(),
+        trace: Default::default(),
+        debug_info: None,
+        prophets: program
+            .prophets
+            .into_iter()
+            .map(|p| (p.host as u64, p))
+            .collect(),
+        pre_exe_flag: false,
+        print_flag: false,
+        return_arity: program.return_arity,
+    };
+    let mut process = Process::new();
+    if !case.calldata.is_empty() {
+        init_tape(
+            &mut process,
+            case.calldata
+                .iter()
+                .map(|v| GoldilocksField::from_canonical_u64(*v))
+                .collect(),
+            Address::default(),
+            Address::default(),
+            Address::default(),
+            &init_tx_context_mock(),
+        );
+    }
+    process
+        .execute(&mut program, &mut AccountTree::new_test())
+        .map_err(|e| e.to_string())?;
+
+    Ok(FinalState {
+        steps: program
+            .trace
+            .exec
+            .iter()
+            .filter(|step| step.is_ext_line == GoldilocksField::ZERO)
+            .count() as u64,
+        registers: to_u64s(&process.registers),
+        memory: process
+            .memory
+            .trace
+            .iter()
+            .filter_map(|(addr, cells)| cells.last().map(|c| (*addr, c.value.to_canonical_u64())))
+            .collect(),
+        storage: process
+            .storage
+            .iter()
+            .filter_map(|(key, cells)| cells.last().map(|c| (to_u64s(key), to_u64s(&c.value))))
+            .sorted()
+            .collect(),
+        ret: program
+            .trace
+            .ret
+            .iter()
+            .map(|v| v.to_canonical_u64())
+            .collect(),
+    })
+}
+
+/// Mismatches between the outcome of an engine and the case expectation,
+/// empty if the engine conforms.
+pub fn check_case(case: &ConformanceCase, outcome: &Result<FinalState, String>) -> Vec<String> {
+    let expect = &case.expect;
+    let state = match (outcome, expect.error) {
+        (Err(_), true) => return vec![],
+        (Ok(_), true) => return vec!["expected execution to fail".to_string()],
+        (Err(e), false) => return vec![format!("execution failed: {}", e)],
+        (Ok(state), false) => state,
+    };
+
+    let mut mismatches = vec![];
+    if let Some(steps) = expect.steps {
+        if state.steps != steps {
+            mismatches.push(format!("steps: expected {}, got {}", steps, state.steps));
+        }
+    }
+    for (reg, value) in &expect.registers {
+        let actual = reg
+            .strip_prefix("r")
+            .and_then(|i| i.parse::<usize>().ok())
+            .and_then(|i| state.registers.get(i));
+        if actual != Some(value) {
+            mismatches.push(format!("{}: expected {}, got {:?}", reg, value, actual));
+        }
+    }
+    for (addr, value) in &expect.memory {
+        let actual = state.memory.get(addr);
+        if actual != Some(value) {
+            mismatches.push(format!(
+                "mem[{}]: expected {}, got {:?}",
+                addr, value, actual
+            ));
+        }
+    }
+    for (key, value) in &expect.storage {
+        let actual = state.storage.iter().find(|(k, _)| k == key).map(|(_, v)| v);
+        if actual != Some(value) {
+            mismatches.push(format!(
+                "storage{:?}: expected {:?}, got {:?}",
+                key, value, actual
+            ));
+        }
+    }
+    if !expect.ret.is_empty() && expect.ret != state.ret {
+        mismatches.push(format!(
+            "ret: expected {:?}, got {:?}",
+            expect.ret, state.ret
+        ));
+    }
+    mismatches
+}
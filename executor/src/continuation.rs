@@ -0,0 +1,250 @@
+//! Execution split into chunks of at most `chunk_clk` steps, so executions
+//! too long for one trace can be proven chunk by chunk.
+//!
+//! Once a chunk reaches its clk limit its tables are finalized and the state
+//! it stops in is carried into a fresh process for the next chunk: registers
+//! and pc, memory (the heap ptr and prophet outputs included), the tape,
+//! transient slots and the prophet commitment. The code reads in the program
+//! log of the first process are repeated in every chunk, so each chunk proves
+//! the code it runs is in the tree. Storage writes of every chunk
+//! are processed into the account tree as a block the next chunk reads, and
+//! saved together once the last chunk ends, so a failed execution leaves
+//! none of them. Every chunk starts at clk 0 again.
+//!
+//! `ChunkBoundary` is the linking data of the continuation prover: the end
+//! boundary of a chunk is the start boundary of the next. The proof of a
+//! chunk starts from the pc and registers of its start boundary and the
+//! memory image its `memory_commitment` is over, prophet outputs included,
+//! which its public values carry. A chunk that stops ends with the pc,
+//! registers and memory the next one starts from, also in its public values.
+
+use crate::tape::TapeTree;
+use crate::trace::{
+    gen_memory_table, gen_storage_hash_table, gen_storage_table, gen_tape_table,
+    gen_transient_table,
+};
+use crate::transient::TransientStorage;
+use crate::Process;
+use core::crypto::poseidon_trace::calculate_arbitrary_poseidon;
+use core::merkle_tree::tree::AccountTree;
+use core::program::{Program, REGISTER_NUM};
+use core::trace::trace::StopState;
+use core::types::merkle_tree::TreeValue;
+use core::vm::error::ProcessorError;
+use core::vm::memory::PSP_START_ADDR;
+use core::vm::vm_state::VMState;
+use plonky2::field::goldilocks_field::GoldilocksField;
+use plonky2::field::types::Field;
+use std::collections::BTreeMap;
+
+/// State between two chunks.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct ChunkBoundary {
+    /// Steps executed before the boundary, over all chunks.
+    pub clk: u64,
+    pub pc: u64,
+    pub registers: [GoldilocksField; REGISTER_NUM],
+    /// See `memory_commitment`.
+    pub memory_commitment: TreeValue,
+    pub storage_root: TreeValue,
+    pub prophet_commitment: TreeValue,
+}
+
+#[derive(Debug)]
+pub struct Chunk {
+    pub index: usize,
+    /// The program with the trace of this chunk.
+    pub program: Program,
+    pub start: ChunkBoundary,
+    pub end: ChunkBoundary,
+    /// Execution ended in this chunk.
+    pub last: bool,
+}
+
+/// Last value of every memory address.
+fn memory_image(process: &Process) -> BTreeMap<u64, GoldilocksField> {
+    process
+        .memory
+        .trace
+        .iter()
+        .filter_map(|(addr, cells)| cells.last().map(|cell| (*addr, cell.value)))
+        .collect()
+}
+
+/// Poseidon over the (addr, value) pairs of the memory image in address
+/// order.
+pub fn memory_commitment(image: &BTreeMap<u64, GoldilocksField>) -> TreeValue {
+    let inputs: Vec<GoldilocksField> = image
+        .iter()
+        .flat_map(|(addr, value)| [GoldilocksField::from_canonical_u64(*addr), *value])
+        .collect();
+    calculate_arbitrary_poseidon(&inputs)
+}
+
+fn boundary(process: &Process, tree: &AccountTree, clk: u64) -> ChunkBoundary {
+    ChunkBoundary {
+        clk,
+        pc: process.pc,
+        registers: process.registers,
+        memory_commitment: memory_commitment(&memory_image(process)),
+        storage_root: tree.root_hash(),
+        prophet_commitment: process.prophet_commitment,
+    }
+}
+
+/// A process continuing where `process` stopped.
+fn next_process(process: &mut Process) -> Result<Process, ProcessorError> {
+    let mut next = Process::new();
+    next.env_idx = process.env_idx;
+    next.call_sc_cnt = process.call_sc_cnt;
+    next.addr_storage = process.addr_storage;
+    next.addr_code = process.addr_code;
+    next.registers = process.registers;
+    next.pc = process.pc;
+    next.psp = process.psp;
+    next.psp_start = process.psp_start;
+    next.hp = process.hp;
+    next.tp = process.tp;
+    next.prophet_commitment = process.prophet_commitment;
+    next.yield_hook = process.yield_hook.take();
+    next.record_prophets = process.record_prophets;
+    next.disabled_opcodes = process.disabled_opcodes.clone();
+    next.fork_id = process.fork_id;
+    next.max_program_words = process.max_program_words;
+    next.chain_id = process.chain_id;
+    next.tx_hash = process.tx_hash;
+    next.gas_schedule = process.gas_schedule.clone();
+    next.gas_limit = process.gas_limit;
+    next.gas_used = process.gas_used;
+
+    let mut image = memory_image(process);
+    if let Some(region) = process.code_region.clone() {
+        let words: Vec<_> = region
+            .clone()
+            .filter_map(|addr| image.remove(&addr))
+            .collect();
+        next.map_code(region.start, &words)?;
+    }
+    let prophet_outputs = image.split_off(&PSP_START_ADDR);
+    next.load_memory_image(&image)?;
+    next.load_prophet_outputs(&prophet_outputs)?;
+
+    let mut tape = TapeTree::default();
+    for (addr, cells) in &process.tape.trace {
+        if let Some(cell) = cells.last() {
+            tape.write(
+                *addr,
+                0,
+                GoldilocksField::ZERO,
+                cell.is_init,
+                GoldilocksField::ZERO,
+                cell.value,
+            );
+        }
+    }
+    next.tape = tape;
+
+    let mut transient = TransientStorage::default();
+    for (slot, cells) in &process.transient.trace {
+        if let Some(cell) = cells.last() {
+            let mut cell = *cell;
+            cell.clk = 0;
+            cell.is_write = true;
+            transient.trace.insert(*slot, vec![cell]);
+        }
+    }
+    next.transient = transient;
+    Ok(next)
+}
+
+/// Executes `program` with `process` in chunks of at most `chunk_clk` steps,
+/// the step limit of `process` is replaced by it. Storage writes of all
+/// chunks are saved to `tree` once the last one ends, on an error the tree
+/// drops them. Contract calls are not supported.
+pub fn execute_in_chunks(
+    process: Process,
+    program: &Program,
+    tree: &mut AccountTree,
+    chunk_clk: u32,
+) -> Result<Vec<Chunk>, ProcessorError> {
+    if chunk_clk == 0 {
+        return Err(ProcessorError::LimitExceeded("chunk clk".to_string(), 0));
+    }
+    let chunks = execute_chunks(process, program, tree, chunk_clk);
+    if chunks.is_err() {
+        tree.discard_pending();
+    }
+    chunks
+}
+
+fn execute_chunks(
+    mut process: Process,
+    program: &Program,
+    tree: &mut AccountTree,
+    chunk_clk: u32,
+) -> Result<Vec<Chunk>, ProcessorError> {
+    let mut chunks = vec![];
+    let mut start = boundary(&process, tree, 0);
+    let program_log = process.program_log.clone();
+    loop {
+        let mut chunk_program = Program {
+            instructions: program.instructions.clone(),
+            debug_info: program.debug_info.clone(),
+            prophets: program.prophets.clone(),
+            return_arity: program.return_arity,
+            ..Default::default()
+        };
+        chunk_program.trace.addr_program_hash = program.trace.addr_program_hash.clone();
+        let start_root = tree.root_hash();
+
+        process.step_limit = Some(chunk_clk);
+        let last = match process.execute(&mut chunk_program, tree) {
+            Ok(VMState::ExeEnd(_)) => true,
+            Ok(VMState::SCCall(_)) => {
+                return Err(ProcessorError::ChunkUnsupported("sccall".to_string()))
+            }
+            // stopped before the step at the clk limit, finalize the tables
+            // the way the end of execution does
+            Err(ProcessorError::LimitExceeded(kind, _)) if kind == "steps" => {
+                chunk_program.trace.prophet_commitment = process.prophet_commitment;
+                chunk_program.trace.fork_id = process.fork_id;
+                chunk_program.trace.chain_id = process.chain_id;
+                chunk_program.trace.tx_hash = process.tx_hash;
+                chunk_program.trace.gas_used = process.gas_used;
+                chunk_program.trace.stop_state = Some(StopState {
+                    pc: process.pc,
+                    registers: process.registers,
+                    tp: process.tp,
+                });
+                gen_memory_table(&mut process, &mut chunk_program)?;
+                gen_tape_table(&mut process, &mut chunk_program)?;
+                gen_transient_table(&process, &mut chunk_program);
+                false
+            }
+            Err(err) => return Err(err),
+        };
+        let hash_roots = gen_storage_hash_table(&mut process, &mut chunk_program, tree);
+        gen_storage_table(&mut process, &mut chunk_program, hash_roots)?;
+        if last {
+            process.finalize(tree, true)?;
+        }
+        chunk_program.trace.start_end_roots = (start_root, tree.root_hash());
+
+        // the last chunk also executed the step at its final clk
+        let steps = process.clk.get() as u64 + last as u64;
+        let end = boundary(&process, tree, start.clk + steps);
+        chunks.push(Chunk {
+            index: chunks.len(),
+            program: chunk_program,
+            start,
+            end: end.clone(),
+            last,
+        });
+        if last {
+            return Ok(chunks);
+        }
+        process = next_process(&mut process)?;
+        process.program_log = program_log.clone();
+        start = end;
+    }
+}
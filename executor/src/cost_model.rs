@@ -0,0 +1,232 @@
+use crate::preflight::{estimate_trace, PreflightConfig, TraceEstimate};
+use crate::SSCAN_MAX_SLOTS;
+use core::program::binary_program::BinaryInstruction;
+use core::trace::trace::{merge_rangecheck_rows, Trace};
+use core::types::merkle_tree::constant::ROOT_TREE_DEPTH;
+use core::types::merkle_tree::TREE_VALUE_LEN;
+use core::vm::opcodes::OlaOpcode;
+use core::vm::operands::OlaOperand;
+use enum_iterator::all;
+
+const STORAGE_KEY_MEM_OPS: usize = 8;
+pub(crate) const POSEIDON_RATE: usize = 8;
+const POSEIDON_OUTPUT_LEN: usize = 4;
+
+pub(crate) fn immediate(operand: &Option<OlaOperand>) -> Option<u64> {
+    match operand {
+        Some(OlaOperand::ImmediateOperand { value }) => value.to_u64().ok(),
+        _ => None,
+    }
+}
+
+fn storage_access() -> TraceEstimate {
+    TraceEstimate {
+        cpu: 2,
+        memory: STORAGE_KEY_MEM_OPS,
+        poseidon: 1 + ROOT_TREE_DEPTH,
+        storage_hash: ROOT_TREE_DEPTH,
+        ..Default::default()
+    }
+}
+
+// a read or write of a slot list record on an ext line of its own
+fn slot_record_access() -> TraceEstimate {
+    TraceEstimate {
+        cpu: 1,
+        poseidon: 1 + ROOT_TREE_DEPTH,
+        storage_hash: ROOT_TREE_DEPTH,
+        ..Default::default()
+    }
+}
+
+/// Rows a single execution of `inst` adds to each table. Operand dependent
+/// lengths fall back to the defaults of `config` unless they are immediates.
+pub fn instruction_cost(inst: &BinaryInstruction, config: &PreflightConfig) -> TraceEstimate {
+    let single = TraceEstimate {
+        cpu: 1,
+        ..Default::default()
+    };
+    match inst.opcode {
+        OlaOpcode::CALL | OlaOpcode::RET => TraceEstimate {
+            memory: 2,
+            ..single
+        },
+        OlaOpcode::MLOAD | OlaOpcode::MSTORE => TraceEstimate {
+            memory: 1,
+            ..single
+        },
+        OlaOpcode::RC => TraceEstimate {
+            rangecheck: 1,
+            ..single
+        },
+        OlaOpcode::AND | OlaOpcode::OR | OlaOpcode::XOR => TraceEstimate {
+            bitwise: 1,
+            ..single
+        },
+        OlaOpcode::GTE => TraceEstimate {
+            rangecheck: 1,
+            cmp: 1,
+            ..single
+        },
+        OlaOpcode::SLOAD => storage_access(),
+        // listing a slot not listed yet takes four record accesses
+        OlaOpcode::SSTORE => {
+            let mut estimate = storage_access();
+            estimate.add_scaled(&slot_record_access(), 4);
+            estimate
+        }
+        // a head line reading the start key and flag and range checking K,
+        // the record the walk starts from, then per slot up to the bound a
+        // storage line and the read of its link
+        OlaOpcode::SSCAN => {
+            let mut estimate = TraceEstimate {
+                cpu: 2,
+                memory: TREE_VALUE_LEN + 1,
+                rangecheck: 1,
+                ..single
+            };
+            estimate.add_scaled(&slot_record_access(), 1);
+            let slot = TraceEstimate {
+                cpu: 1,
+                ..storage_access()
+            };
+            estimate.add_scaled(&slot, SSCAN_MAX_SLOTS as usize);
+            estimate.add_scaled(&slot_record_access(), SSCAN_MAX_SLOTS as usize);
+            estimate
+        }
+        OlaOpcode::POSEIDON => {
+            let len = immediate(&inst.op1)
+                .map(|v| v as usize)
+                .unwrap_or(config.default_poseidon_len)
+                .max(1);
+            let chunks = (len + POSEIDON_RATE - 1) / POSEIDON_RATE;
+            TraceEstimate {
+                memory: len + POSEIDON_OUTPUT_LEN,
+                poseidon: chunks,
+                poseidon_chunk: chunks + 1,
+                ..single
+            }
+        }
+        OlaOpcode::TLOAD => {
+            let len = immediate(&inst.op1)
+                .map(|v| v as usize)
+                .unwrap_or(config.calldata_len);
+            TraceEstimate {
+                memory: len,
+                tape: len,
+                ..single
+            }
+        }
+        OlaOpcode::TSTORE => {
+            let len = immediate(&inst.op1)
+                .map(|v| v as usize)
+                .unwrap_or(config.default_tstore_len);
+            TraceEstimate {
+                memory: len,
+                tape: len,
+                ..single
+            }
+        }
+        OlaOpcode::SCCALL => TraceEstimate {
+            memory: STORAGE_KEY_MEM_OPS,
+            sccall: 1,
+            ..single
+        },
+        _ => single,
+    }
+}
+
+/// Row footprint of every opcode with register operands.
+pub fn opcode_costs(config: &PreflightConfig) -> Vec<(OlaOpcode, TraceEstimate)> {
+    all::<OlaOpcode>()
+        .map(|opcode| {
+            let inst = BinaryInstruction {
+                opcode,
+                op0: None,
+                op1: None,
+                dst: None,
+                prophet: None,
+            };
+            (opcode, instruction_cost(&inst, config))
+        })
+        .collect()
+}
+
+/// Rows each table holds after executing a program, before padding.
+pub fn execution_rows(trace: &Trace) -> TraceEstimate {
+    let program: usize = trace
+        .exec
+        .iter()
+        .filter(|e| e.is_ext_line.0 == 0)
+        .map(|e| {
+            if e.op1_imm.0 == 1
+                || e.opcode.0 == OlaOpcode::MLOAD.binary_bit_mask()
+                || e.opcode.0 == OlaOpcode::MSTORE.binary_bit_mask()
+            {
+                2
+            } else {
+                1
+            }
+        })
+        .sum();
+    let code_len: usize = trace.addr_program_hash.values().map(|p| p.len()).sum();
+    let prog_chunk = trace
+        .addr_program_hash
+        .values()
+        .map(|p| (p.len() + POSEIDON_RATE - 1) / POSEIDON_RATE)
+        .sum();
+
+    TraceEstimate {
+        cpu: trace.exec.len(),
+        memory: trace.memory.len(),
+        rangecheck: merge_rangecheck_rows(&trace.builtin_rangecheck).len(),
+        bitwise: trace.builtin_bitwise_combined.len(),
+        cmp: trace.builtin_cmp.len(),
+        poseidon: trace.builtin_poseidon.len(),
+        poseidon_chunk: trace.builtin_poseidon_chunk.len(),
+        storage_hash: trace.builtin_storage_hash.len() + trace.builtin_program_hash.len(),
+        tape: trace.tape.len(),
+        sccall: trace.sc_call.len(),
+        program: program.max(code_len),
+        prog_chunk,
+    }
+}
+
+/// Executed steps of each opcode, extension lines excluded.
+pub fn opcode_counts(trace: &Trace) -> Vec<(OlaOpcode, usize)> {
+    all::<OlaOpcode>()
+        .map(|opcode| {
+            let count = trace
+                .exec
+                .iter()
+                .filter(|e| e.is_ext_line.0 == 0 && e.opcode.0 == opcode.binary_bit_mask())
+                .count();
+            (opcode, count)
+        })
+        .filter(|(_, count)| *count > 0)
+        .collect()
+}
+
+/// Folds per table rows into one cost, weighting each table's rows by the
+/// matching field of `weights`.
+pub fn score(rows: &TraceEstimate, weights: &TraceEstimate) -> u64 {
+    rows.tables()
+        .iter()
+        .zip(weights.tables().iter())
+        .map(|((_, rows), (_, weight))| (rows * weight) as u64)
+        .sum()
+}
+
+/// Scores `instructions` statically, see `estimate_trace`.
+pub fn score_program(
+    instructions: &[BinaryInstruction],
+    config: &PreflightConfig,
+    weights: &TraceEstimate,
+) -> u64 {
+    score(&estimate_trace(instructions, config), weights)
+}
+
+/// Scores the tables an execution actually produced.
+pub fn score_execution(trace: &Trace, weights: &TraceEstimate) -> u64 {
+    score(&execution_rows(trace), weights)
+}
@@ -1,6 +1,8 @@
 use core::program::instruction::{Opcode, *};
 use core::vm::error::ProcessorError;
 use log::debug;
+use plonky2::field::goldilocks_field::GoldilocksField;
+use plonky2::field::types::{Field, Field64};
 
 pub(crate) const REG_NOT_USED: u8 = 0xff;
 
@@ -15,6 +17,31 @@ fn parse_hex_str(hex_str: &str) -> Result<u64, ProcessorError> {
     }
 }
 
+/// Memory offsets in the upper half of the field are printed as negative
+/// numbers, `p - 2` decodes to `-2`.
+fn format_offset(imm: u64) -> String {
+    if imm > GoldilocksField::ORDER / 2 {
+        format!("-{}", GoldilocksField::ORDER - imm)
+    } else {
+        imm.to_string()
+    }
+}
+
+/// Parses a decimal memory offset, negative offsets wrap around the field.
+pub(crate) fn parse_offset(offset: &str) -> Result<GoldilocksField, ProcessorError> {
+    let (negative, digits) = match offset.strip_prefix('-') {
+        Some(digits) => (true, digits),
+        None => (false, offset),
+    };
+    let value = digits
+        .parse::<u64>()
+        .ok()
+        .filter(|value| *value < GoldilocksField::ORDER)
+        .ok_or(ProcessorError::ParseIntError)?;
+    let value = GoldilocksField::from_canonical_u64(value);
+    Ok(if negative { -value } else { value })
+}
+
 fn get_index(data: u64) -> u8 {
     let mut i: i128 = 63;
     while i >= 0 {
@@ -57,7 +84,9 @@ pub fn decode_raw_instruction(
             | Opcode::GTE
             | Opcode::EQ
             | Opcode::TLOAD
-            | Opcode::POSEIDON => {
+            | Opcode::POSEIDON
+            | Opcode::SSCAN
+            | Opcode::CMOV => {
                 instruction += &op_code.to_string();
                 instruction += " ";
                 let reg0_name = format!("r{}", reg0);
@@ -75,7 +104,12 @@ pub fn decode_raw_instruction(
                     instruction += &reg2_name;
                 }
             }
-            Opcode::CJMP | Opcode::TSTORE | Opcode::SCCALL | Opcode::SLOAD | Opcode::SSTORE => {
+            Opcode::CJMP
+            | Opcode::TSTORE
+            | Opcode::SCCALL
+            | Opcode::SLOAD
+            | Opcode::SSTORE
+            | Opcode::TSTORET => {
                 instruction += &op_code.to_string();
                 instruction += " ";
                 let reg1_name = format!("r{}", reg1);
@@ -90,7 +124,7 @@ pub fn decode_raw_instruction(
                     instruction += &reg2_name;
                 }
             }
-            Opcode::MOV | Opcode::NOT => {
+            Opcode::MOV | Opcode::NOT | Opcode::TLOADT | Opcode::TXCTX => {
                 instruction += &op_code.to_string();
                 instruction += " ";
                 let reg0_name = format!("r{}", reg0);
@@ -119,14 +153,14 @@ pub fn decode_raw_instruction(
                 instruction += " ";
                 if imm_flag == 1 {
                     let imm = parse_hex_str(imm_str.trim_start_matches("0x"))?;
-                    instruction += &imm.to_string();
+                    instruction += &format_offset(imm);
                     instruction += " ";
                 } else if reg2 != REG_NOT_USED {
                     let reg2_name = format!("r{}", reg2);
                     instruction += &reg2_name;
                     instruction += " ";
                     let imm = parse_hex_str(imm_str.trim_start_matches("0x"))?;
-                    instruction += &imm.to_string();
+                    instruction += &format_offset(imm);
                     instruction += " ";
                 } else {
                     panic!("MSTORE op1 must be a reg or immediate");
@@ -164,19 +198,34 @@ pub fn decode_raw_instruction(
 
                 if reg2 == REG_NOT_USED && imm_flag == 1 {
                     let imm = parse_hex_str(imm_str.trim_start_matches("0x"))?;
-                    instruction += &imm.to_string();
+                    instruction += &format_offset(imm);
                 } else if reg2 != REG_NOT_USED {
                     let reg2_name = format!("r{}", reg2);
                     instruction += &reg2_name;
                     instruction += " ";
                     let imm = parse_hex_str(imm_str.trim_start_matches("0x"))?;
-                    instruction += &imm.to_string();
+                    instruction += &format_offset(imm);
                     instruction += " ";
                 } else {
                     panic!("MLOAD op1 must be a reg or immediate");
                 }
                 step = IMM_INSTRUCTION_LEN;
             }
+            Opcode::ASSERT if reg1 != REG_NOT_USED => {
+                // assert op0 message_id
+                instruction += &op_code.to_string();
+                instruction += " ";
+                let reg1_name = format!("r{}", reg1);
+                instruction += &reg1_name;
+                instruction += " ";
+                if imm_flag == 1 {
+                    let imm = parse_hex_str(imm_str.trim_start_matches("0x"))?;
+                    instruction += &imm.to_string();
+                    step = IMM_INSTRUCTION_LEN;
+                } else {
+                    panic!("ASSERT message id must be immediate");
+                }
+            }
             Opcode::ASSERT | Opcode::JMP | Opcode::CALL | Opcode::RC => {
                 instruction += &op_code.to_string();
                 instruction += " ";
@@ -213,3 +262,188 @@ fn decode_raw_instruction_test() {
     let inst_str = decode_raw_instruction(&inst_str, imm);
     println!("inst_str: {:?}", inst_str);
 }
+
+#[cfg(test)]
+mod tests {
+    use super::{decode_raw_instruction, format_offset, REG_NOT_USED};
+    use assembler::encoder::encode_asm;
+    use core::program::binary_program::BinaryInstruction;
+    use core::program::decoder::decode_binary_program_to_instructions;
+    use core::vm::opcodes::OlaOpcode;
+    use core::vm::operands::OlaOperand;
+
+    /// The text form `decode_raw_instruction` gives for `inst`.
+    fn legacy_text(inst: &BinaryInstruction) -> String {
+        let operand = |op: &Option<OlaOperand>| match op {
+            Some(OlaOperand::RegisterOperand { register }) => register.to_string(),
+            Some(OlaOperand::ImmediateOperand { value }) => match inst.opcode {
+                OlaOpcode::MSTORE | OlaOpcode::MLOAD => format_offset(value.to_u64().unwrap()),
+                _ => value.to_u64().unwrap().to_string(),
+            },
+            Some(OlaOperand::RegisterWithFactor { register, factor }) => {
+                format!("{} {}", register, format_offset(factor.to_u64().unwrap()))
+            }
+            Some(OlaOperand::SpecialReg { .. }) => format!("r{}", REG_NOT_USED),
+            op => panic!("no text form for {:?}", op),
+        };
+        let token = inst.opcode.token();
+        let (op0, op1, dst) = (&inst.op0, &inst.op1, &inst.dst);
+        match inst.opcode {
+            OlaOpcode::ADD
+            | OlaOpcode::MUL
+            | OlaOpcode::AND
+            | OlaOpcode::OR
+            | OlaOpcode::XOR
+            | OlaOpcode::EQ
+            | OlaOpcode::NEQ
+            | OlaOpcode::GTE
+            | OlaOpcode::TLOAD
+            | OlaOpcode::POSEIDON
+            | OlaOpcode::SSCAN
+            | OlaOpcode::CMOV => {
+                format!(
+                    "{} {} {} {}",
+                    token,
+                    operand(dst),
+                    operand(op0),
+                    operand(op1)
+                )
+            }
+            OlaOpcode::MOV | OlaOpcode::NOT | OlaOpcode::TLOADT | OlaOpcode::TXCTX => {
+                format!("{} {} {}", token, operand(dst), operand(op1))
+            }
+            OlaOpcode::CJMP
+            | OlaOpcode::TSTORE
+            | OlaOpcode::SCCALL
+            | OlaOpcode::SLOAD
+            | OlaOpcode::SSTORE
+            | OlaOpcode::TSTORET => format!("{} {} {}", token, operand(op0), operand(op1)),
+            OlaOpcode::ASSERT if op0.is_some() => {
+                format!("{} {} {}", token, operand(op0), operand(op1))
+            }
+            OlaOpcode::MSTORE => {
+                format!(
+                    "{} {} {} {}",
+                    token,
+                    operand(op0),
+                    operand(op1),
+                    operand(dst)
+                )
+            }
+            OlaOpcode::MLOAD => {
+                format!(
+                    "{} {} {} {}",
+                    token,
+                    operand(dst),
+                    operand(op0),
+                    operand(op1)
+                )
+            }
+            OlaOpcode::ASSERT | OlaOpcode::JMP | OlaOpcode::CALL | OlaOpcode::RC => {
+                format!("{} {}", token, operand(op1))
+            }
+            OlaOpcode::RET | OlaOpcode::END => token,
+        }
+    }
+
+    /// Assembles `asm`, then checks the binary decoding round trips and that
+    /// the executor decodes the same words to `legacy`.
+    fn check_decoders(asm: &str, legacy: &str) {
+        let program = encode_asm(&format!("main:\n{}\nend", asm)).unwrap();
+        let words: Vec<String> = program.bytecode.lines().map(|w| w.to_string()).collect();
+        let inst = decode_binary_program_to_instructions(program)
+            .unwrap()
+            .remove(0);
+        let len = inst.binary_length() as usize;
+        let encoded = inst.encode().unwrap();
+        assert_eq!(encoded, words[..len]);
+        let decoded = BinaryInstruction::decode(encoded, None).unwrap();
+        assert_eq!(decoded.opcode, inst.opcode);
+        assert_eq!(decoded.op0, inst.op0);
+        assert_eq!(decoded.op1, inst.op1);
+        assert_eq!(decoded.dst, inst.dst);
+
+        let imm = if len == 2 { words[1].as_str() } else { "" };
+        let (text, step) = decode_raw_instruction(&words[0], imm).unwrap();
+        let text = text.split_whitespace().collect::<Vec<_>>().join(" ");
+        assert_eq!(step as usize, len, "{}", asm);
+        assert_eq!(text, legacy, "{}", asm);
+        assert_eq!(legacy_text(&inst), legacy, "{}", asm);
+    }
+
+    /// One test per opcode and operand form, `name: asm => executor text`.
+    macro_rules! decode_matrix {
+        ($($name:ident: $asm:literal => $legacy:literal,)*) => {
+            $(
+                #[test]
+                fn $name() {
+                    check_decoders($asm, $legacy);
+                }
+            )*
+        };
+    }
+
+    decode_matrix! {
+        add_reg: "add r1 r2 r3" => "add r1 r2 r3",
+        add_imm: "add r1 r2 7" => "add r1 r2 7",
+        add_neg_imm: "add r7 r8 -2" => "add r7 r8 18446744069414584319",
+        mul_reg: "mul r0 r1 r2" => "mul r0 r1 r2",
+        mul_imm: "mul r0 r1 3" => "mul r0 r1 3",
+        and_reg: "and r3 r4 r5" => "and r3 r4 r5",
+        and_imm: "and r3 r4 255" => "and r3 r4 255",
+        or_reg: "or r3 r4 r5" => "or r3 r4 r5",
+        or_imm: "or r3 r4 1" => "or r3 r4 1",
+        xor_reg: "xor r3 r4 r5" => "xor r3 r4 r5",
+        xor_imm: "xor r3 r4 6" => "xor r3 r4 6",
+        eq_reg: "eq r1 r2 r3" => "eq r1 r2 r3",
+        eq_imm: "eq r1 r2 0" => "eq r1 r2 0",
+        neq_reg: "neq r1 r2 r3" => "neq r1 r2 r3",
+        neq_imm: "neq r1 r2 9" => "neq r1 r2 9",
+        gte_reg: "gte r1 r2 r3" => "gte r1 r2 r3",
+        gte_imm: "gte r1 r2 10" => "gte r1 r2 10",
+        tload_reg: "tload r1 r2 r3" => "tload r1 r2 r3",
+        tload_imm: "tload r1 r2 4" => "tload r1 r2 4",
+        poseidon_reg: "poseidon r1 r2 r3" => "poseidon r1 r2 r3",
+        poseidon_imm: "poseidon r1 r2 8" => "poseidon r1 r2 8",
+        mov_reg: "mov r1 r2" => "mov r1 r2",
+        mov_imm: "mov r1 100" => "mov r1 100",
+        mov_psp: "mov r1 psp" => "mov r1 r255",
+        not_reg: "not r1 r2" => "not r1 r2",
+        not_imm: "not r1 5" => "not r1 5",
+        cjmp_reg: "cjmp r1 r2" => "cjmp r1 r2",
+        cjmp_imm: "cjmp r1 12" => "cjmp r1 12",
+        tstore_reg: "tstore r1 r2" => "tstore r1 r2",
+        tstore_imm: "tstore r1 3" => "tstore r1 3",
+        sccall_reg: "sccall r1 r2" => "sccall r1 r2",
+        sccall_imm: "sccall r1 1" => "sccall r1 1",
+        sload_reg: "sload r1 r2" => "sload r1 r2",
+        sstore_reg: "sstore r1 r2" => "sstore r1 r2",
+        tload_t_reg: "tload_t r1 r2" => "tload_t r1 r2",
+        tload_t_imm: "tload_t r1 7" => "tload_t r1 7",
+        tstore_t_reg: "tstore_t r1 r2" => "tstore_t r1 r2",
+        tstore_t_imm: "tstore_t r1 1" => "tstore_t r1 1",
+        sscan_reg: "sscan r1 r2 r3" => "sscan r1 r2 r3",
+        sscan_imm: "sscan r1 r2 100" => "sscan r1 r2 100",
+        txctx_reg: "txctx r1 r2" => "txctx r1 r2",
+        txctx_imm: "txctx r1 2" => "txctx r1 2",
+        cmov_reg: "cmov r1 r2 r3" => "cmov r1 r2 r3",
+        cmov_imm: "cmov r1 r2 7" => "cmov r1 r2 7",
+        mstore_imm: "mstore [r9,3] r1" => "mstore r9 3 r1",
+        mstore_neg_imm: "mstore [r9,-3] r1" => "mstore r9 -3 r1",
+        mstore_factor: "mstore [r9,r2,4] r1" => "mstore r9 r2 4 r1",
+        mstore_neg_factor: "mstore [r9,r2,-4] r1" => "mstore r9 r2 -4 r1",
+        mload_imm: "mload r1 [r9,3]" => "mload r1 r9 3",
+        mload_neg_imm: "mload r1 [r9,-3]" => "mload r1 r9 -3",
+        mload_factor: "mload r1 [r9,r2,4]" => "mload r1 r9 r2 4",
+        mload_neg_factor: "mload r1 [r9,r2,-4]" => "mload r1 r9 r2 -4",
+        assert_reg: "assert r1" => "assert r1",
+        assert_message: "assert r1 42" => "assert r1 42",
+        jmp_reg: "jmp r1" => "jmp r1",
+        jmp_imm: "jmp 6" => "jmp 6",
+        call_reg: "call r1" => "call r1",
+        call_imm: "call 6" => "call 6",
+        range_reg: "range r1" => "range r1",
+        ret: "ret" => "ret",
+        end: "end" => "end",
+    }
+}
@@ -0,0 +1,168 @@
+use crate::trace::{gen_storage_hash_table, gen_storage_table};
+use crate::Process;
+use core::merkle_tree::tree::AccountTree;
+use core::program::Program;
+use core::trace::export::{trace_tables, TraceTable};
+use core::trace::trace::Trace;
+use core::types::GoldilocksField;
+use core::vm::error::ProcessorError;
+use itertools::Itertools;
+use log::debug;
+
+/// Compare the committed tables of two traces, returning the first table and
+/// row where they differ.
+pub fn first_trace_divergence(lhs: &Trace, rhs: &Trace) -> Option<(String, usize)> {
+    let lhs_tables = trace_tables(lhs).expect("trace rows are serializable");
+    let rhs_tables = trace_tables(rhs).expect("trace rows are serializable");
+    for (l, r) in lhs_tables.iter().zip(rhs_tables.iter()) {
+        if let Some(row) = first_row_divergence(l, r) {
+            return Some((l.name.clone(), row));
+        }
+    }
+    if let Some(row) = first_value_divergence(&lhs.ret, &rhs.ret) {
+        return Some(("ret".to_string(), row));
+    }
+    if let Some(row) = first_value_divergence(&lhs.returned_values, &rhs.returned_values) {
+        return Some(("returned_values".to_string(), row));
+    }
+    if lhs.prophet_commitment != rhs.prophet_commitment {
+        return Some(("prophet_commitment".to_string(), 0));
+    }
+    if lhs.fork_id != rhs.fork_id {
+        return Some(("fork_id".to_string(), 0));
+    }
+    if lhs.chain_id != rhs.chain_id || lhs.tx_hash != rhs.tx_hash {
+        return Some(("tx_context".to_string(), 0));
+    }
+    if lhs.gas_used != rhs.gas_used {
+        return Some(("gas_used".to_string(), 0));
+    }
+    None
+}
+
+fn first_value_divergence(lhs: &[GoldilocksField], rhs: &[GoldilocksField]) -> Option<usize> {
+    let len = lhs.len().min(rhs.len());
+    (0..len)
+        .find(|&i| lhs[i] != rhs[i])
+        .or_else(|| (lhs.len() != rhs.len()).then_some(len))
+}
+
+fn first_row_divergence(lhs: &TraceTable, rhs: &TraceTable) -> Option<usize> {
+    let rows = lhs.num_rows().min(rhs.num_rows());
+    if lhs.columns.len() == rhs.columns.len() {
+        for row in 0..rows {
+            let diverged = lhs
+                .columns
+                .iter()
+                .zip(rhs.columns.iter())
+                .any(|(l, r)| l.0 != r.0 || l.1[row] != r.1[row]);
+            if diverged {
+                return Some(row);
+            }
+        }
+    } else if rows > 0 {
+        return Some(0);
+    }
+    if lhs.num_rows() != rhs.num_rows() {
+        return Some(rows);
+    }
+    None
+}
+
+/// A column two traces disagree on and the first row where they do.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct ColumnDivergence {
+    pub table: String,
+    pub column: String,
+    pub row: usize,
+}
+
+/// Compare every column of every committed table of two traces, e.g. the
+/// traces of two engines running the same program. A column missing on one
+/// side diverges at row 0, a shorter one where it ends.
+pub fn column_divergences(lhs: &Trace, rhs: &Trace) -> Vec<ColumnDivergence> {
+    let lhs_tables = trace_tables(lhs).expect("trace rows are serializable");
+    let rhs_tables = trace_tables(rhs).expect("trace rows are serializable");
+    let mut divergences = vec![];
+    for (l, r) in lhs_tables.iter().zip(rhs_tables.iter()) {
+        let names = l
+            .columns
+            .iter()
+            .chain(r.columns.iter())
+            .map(|c| c.0.as_str())
+            .unique();
+        for name in names {
+            let row = match (l.column(name), r.column(name)) {
+                (Some(lc), Some(rc)) => lc
+                    .iter()
+                    .zip(rc.iter())
+                    .position(|(a, b)| a != b)
+                    .or_else(|| (lc.len() != rc.len()).then(|| lc.len().min(rc.len()))),
+                _ => Some(0),
+            };
+            if let Some(row) = row {
+                divergences.push(ColumnDivergence {
+                    table: l.name.clone(),
+                    column: name.to_string(),
+                    row,
+                });
+            }
+        }
+    }
+    divergences
+}
+
+/// Panics listing every diverging column unless both traces commit to the
+/// same tables.
+pub fn assert_traces_match(lhs: &Trace, rhs: &Trace) {
+    let divergences = column_divergences(lhs, rhs);
+    assert!(
+        divergences.is_empty(),
+        "traces diverge in {} columns:\n{}",
+        divergences.len(),
+        divergences
+            .iter()
+            .map(|d| format!("  {}.{} at row {}", d.table, d.column, d.row))
+            .join("\n")
+    );
+    assert_eq!(first_trace_divergence(lhs, rhs), None);
+}
+
+/// Execute the program produced by `setup` `runs` times and check every run
+/// commits to the same trace, storage tables included.
+///
+/// `setup` must build a fresh `Process` and `Program` (tape, calldata and
+/// prophets included) on each call, `new_tree` the account tree a run starts
+/// from, e.g. a clone of the state under test. A run never sees the blocks
+/// hashed into the tree by an earlier one. Returns the trace of the first
+/// run.
+pub fn audit_determinism<S, T>(setup: S, new_tree: T, runs: usize) -> Result<Trace, ProcessorError>
+where
+    S: Fn() -> (Process, Program),
+    T: Fn() -> AccountTree,
+{
+    assert!(runs >= 2, "determinism audit needs at least two runs");
+    let mut reference: Option<Trace> = None;
+    for run in 0..runs {
+        let (mut process, mut program) = setup();
+        let mut account_tree = new_tree();
+        process.execute(&mut program, &mut account_tree)?;
+        let hash_roots = gen_storage_hash_table(&mut process, &mut program, &mut account_tree);
+        gen_storage_table(&mut process, &mut program, hash_roots)?;
+        process.finalize(&mut account_tree, false)?;
+        let trace = std::mem::replace(&mut program.trace, Trace::default());
+        match reference {
+            None => reference = Some(trace),
+            Some(ref expected) => {
+                if let Some((table, row)) = first_trace_divergence(expected, &trace) {
+                    debug!(
+                        "determinism audit: run {} diverges in {} at row {}",
+                        run, table, row
+                    );
+                    return Err(ProcessorError::NondeterministicTrace(table, row));
+                }
+            }
+        }
+    }
+    Ok(reference.unwrap())
+}
@@ -0,0 +1,413 @@
+//! Plain u64 interpreter for simulation that is never proven.
+//!
+//! `Emulator` runs the same decoded instruction stream as `Process` and
+//! advances `clk` and `pc` exactly as it does, but keeps registers and memory
+//! as canonical u64 values and records no trace. It is meant for contract unit
+//! tests, fuzzing and quick what-if runs where the field wrappers, memory
+//! cells and trace rows of the provable executor are pure overhead.
+//!
+//! Divergence policy. For every program that only uses the supported opcodes
+//! the emulator must end with the same registers, `pc`, `clk` and memory
+//! values as `Process`, and fail with the same `ProcessorError` where
+//! `Process` fails with one. Anything else is a bug in the emulator. The
+//! known, intended differences are:
+//!
+//! - `add`, `mul`, `not` and address offsets are computed modulo the Goldilocks
+//!   prime, immediates are reduced into the field when decoded. `and`, `or`,
+//!   `xor`, `eq`, `neq`, `gte` and `range` act on the canonical u64 values.
+//!   Where `Process` panics on an out of field value or an address underflow
+//!   the emulator wraps modulo the prime instead.
+//! - `sstore` and `sload` use a flat map from the 4 word slot key to the value,
+//!   the key is not hashed with the contract address and no account tree is
+//!   read. Unwritten slots read as zero.
+//! - `tload`, `tstore`, `sccall` and prophets need the tape and the host
+//!   environment and fail with `ProcessorError::EmulatorUnsupported`.
+//! - No trace, storage log or return data is produced.
+use crate::decode::{decode_raw_instruction, parse_offset, REG_NOT_USED};
+use crate::FP_REG_INDEX;
+use core::crypto::poseidon_trace::{calculate_arbitrary_poseidon, POSEIDON_OUTPUT_VALUE_LEN};
+use core::program::{Program, REGISTER_NUM};
+use core::types::merkle_tree::TREE_VALUE_LEN;
+use core::vm::clk::{Clk, MAX_CLK};
+use core::vm::error::ProcessorError;
+use core::vm::memory::{HP_START_ADDR, MEMORY_MODEL, PSP_START_ADDR};
+use core::vm::opcodes::OlaOpcode;
+use plonky2::field::goldilocks_field::GoldilocksField;
+use plonky2::field::types::{Field, Field64, PrimeField64};
+use std::collections::{HashMap, HashSet};
+use std::str::FromStr;
+
+const ORDER: u64 = GoldilocksField::ORDER;
+
+fn add_mod(a: u64, b: u64) -> u64 {
+    ((a as u128 + b as u128) % ORDER as u128) as u64
+}
+
+fn mul_mod(a: u64, b: u64) -> u64 {
+    ((a as u128 * b as u128) % ORDER as u128) as u64
+}
+
+fn sub_mod(a: u64, b: u64) -> u64 {
+    add_mod(a, ORDER - b % ORDER)
+}
+
+#[derive(Debug, Clone, Copy)]
+enum Operand {
+    Reg(usize),
+    Imm(u64),
+    /// The not used register reads as the start of the prophet region.
+    Psp,
+}
+
+#[derive(Debug, Clone, Copy)]
+enum MemOffset {
+    Imm(u64),
+    /// `imm * reg`
+    Scaled(usize, u64),
+}
+
+#[derive(Debug, Clone, Copy)]
+enum Inst {
+    Mov(usize, Operand),
+    Not(usize, Operand),
+    Binary(OlaOpcode, usize, usize, Operand),
+    Assert(Operand),
+    AssertMessage(usize, u64),
+    Jmp(Operand),
+    Cjmp(usize, Operand),
+    /// `cmov dst cond op1`
+    Cmov(usize, usize, Operand),
+    Call(Operand),
+    Ret,
+    Mstore(usize, MemOffset, usize),
+    Mload(usize, usize, MemOffset),
+    Range(usize),
+    Poseidon(usize, usize, Operand),
+    Sstore(usize, Operand),
+    Sload(usize, Operand),
+    End,
+    Unsupported(OlaOpcode),
+}
+
+fn parse_reg(op: &str) -> Result<usize, ProcessorError> {
+    op.strip_prefix('r')
+        .and_then(|index| index.parse().ok())
+        .ok_or_else(|| ProcessorError::DecodeError(op.to_string()))
+}
+
+fn parse_operand(op: &str) -> Result<Operand, ProcessorError> {
+    if let Ok(imm) = op.parse::<u64>() {
+        return Ok(Operand::Imm(imm % ORDER));
+    }
+    match parse_reg(op)? {
+        index if index == REG_NOT_USED as usize => Ok(Operand::Psp),
+        index if index < REGISTER_NUM => Ok(Operand::Reg(index)),
+        _ => Err(ProcessorError::DecodeError(op.to_string())),
+    }
+}
+
+fn parse_mem_offset(ops: &[&str]) -> Result<MemOffset, ProcessorError> {
+    let imm = |op: &str| parse_offset(op).map(|imm| imm.to_canonical_u64());
+    match ops {
+        [imm_str] => Ok(MemOffset::Imm(imm(imm_str)?)),
+        [reg, imm_str] => Ok(MemOffset::Scaled(parse_reg(reg)?, imm(imm_str)?)),
+        _ => Err(ProcessorError::ParseOpcodeError),
+    }
+}
+
+fn parse_inst(txt: &str) -> Result<Inst, ProcessorError> {
+    let ops: Vec<&str> = txt.split_whitespace().collect();
+    let opcode = ops
+        .first()
+        .and_then(|token| OlaOpcode::from_str(token).ok())
+        .ok_or(ProcessorError::ParseOpcodeError)?;
+    let inst = match (opcode, ops.len()) {
+        (OlaOpcode::MOV, 3) => Inst::Mov(parse_reg(ops[1])?, parse_operand(ops[2])?),
+        (OlaOpcode::NOT, 3) => Inst::Not(parse_reg(ops[1])?, parse_operand(ops[2])?),
+        (
+            OlaOpcode::ADD
+            | OlaOpcode::MUL
+            | OlaOpcode::AND
+            | OlaOpcode::OR
+            | OlaOpcode::XOR
+            | OlaOpcode::EQ
+            | OlaOpcode::NEQ
+            | OlaOpcode::GTE,
+            4,
+        ) => Inst::Binary(
+            opcode,
+            parse_reg(ops[1])?,
+            parse_reg(ops[2])?,
+            parse_operand(ops[3])?,
+        ),
+        (OlaOpcode::ASSERT, 2) => Inst::Assert(parse_operand(ops[1])?),
+        (OlaOpcode::ASSERT, 3) => match parse_operand(ops[2])? {
+            Operand::Imm(id) => Inst::AssertMessage(parse_reg(ops[1])?, id),
+            _ => return Err(ProcessorError::ParseOpcodeError),
+        },
+        (OlaOpcode::JMP, 2) => Inst::Jmp(parse_operand(ops[1])?),
+        (OlaOpcode::CJMP, 3) => Inst::Cjmp(parse_reg(ops[1])?, parse_operand(ops[2])?),
+        (OlaOpcode::CMOV, 4) => Inst::Cmov(
+            parse_reg(ops[1])?,
+            parse_reg(ops[2])?,
+            parse_operand(ops[3])?,
+        ),
+        (OlaOpcode::CALL, 2) => Inst::Call(parse_operand(ops[1])?),
+        (OlaOpcode::RET, 1) => Inst::Ret,
+        (OlaOpcode::MSTORE, 4 | 5) => Inst::Mstore(
+            parse_reg(ops[1])?,
+            parse_mem_offset(&ops[2..ops.len() - 1])?,
+            parse_reg(ops[ops.len() - 1])?,
+        ),
+        (OlaOpcode::MLOAD, 4 | 5) => Inst::Mload(
+            parse_reg(ops[1])?,
+            parse_reg(ops[2])?,
+            parse_mem_offset(&ops[3..])?,
+        ),
+        (OlaOpcode::RC, 2) => Inst::Range(parse_reg(ops[1])?),
+        (OlaOpcode::POSEIDON, 4) => Inst::Poseidon(
+            parse_reg(ops[1])?,
+            parse_reg(ops[2])?,
+            parse_operand(ops[3])?,
+        ),
+        (OlaOpcode::SSTORE, 3) => Inst::Sstore(parse_reg(ops[1])?, parse_operand(ops[2])?),
+        (OlaOpcode::SLOAD, 3) => Inst::Sload(parse_reg(ops[1])?, parse_operand(ops[2])?),
+        (OlaOpcode::END, 1) => Inst::End,
+        (
+            OlaOpcode::TLOAD
+            | OlaOpcode::TSTORE
+            | OlaOpcode::SCCALL
+            | OlaOpcode::TLOADT
+            | OlaOpcode::TSTORET
+            | OlaOpcode::SSCAN
+            | OlaOpcode::TXCTX,
+            _,
+        ) => Inst::Unsupported(opcode),
+        _ => return Err(ProcessorError::ParseOpcodeError),
+    };
+    Ok(inst)
+}
+
+#[derive(Debug, Clone)]
+pub struct Emulator {
+    pub clk: Clk,
+    pub pc: u64,
+    pub registers: [u64; REGISTER_NUM],
+    pub memory: HashMap<u64, u64>,
+    pub storage: HashMap<[u64; TREE_VALUE_LEN], [u64; TREE_VALUE_LEN]>,
+    pub step_limit: Option<u32>,
+    // (instruction, step) by pc, `None` on immediate words.
+    code: Vec<Option<(Inst, u64)>>,
+    prophet_pcs: HashSet<u64>,
+}
+
+impl Emulator {
+    /// Decode `program` up front, with the same initial state `Process`
+    /// starts from.
+    pub fn new(program: &Program) -> Result<Self, ProcessorError> {
+        let len = program.instructions.len();
+        let mut code = vec![None; len];
+        let mut pc = 0;
+        while pc < len {
+            let next = program.instructions.get(pc + 1).map_or("", |s| s.trim());
+            let (txt, step) = decode_raw_instruction(program.instructions[pc].trim(), next)?;
+            code[pc] = Some((parse_inst(&txt)?, step));
+            pc += step as usize;
+        }
+        let mut memory = HashMap::new();
+        memory.insert(HP_START_ADDR, HP_START_ADDR + 1);
+        Ok(Self {
+            clk: Clk::ZERO,
+            pc: 0,
+            registers: [0; REGISTER_NUM],
+            memory,
+            storage: HashMap::new(),
+            step_limit: None,
+            code,
+            prophet_pcs: program.prophets.keys().copied().collect(),
+        })
+    }
+
+    pub fn with_step_limit(mut self, limit: u32) -> Self {
+        self.step_limit = Some(limit);
+        self
+    }
+
+    /// Run until `end` or until `pc` leaves the program, returning the final
+    /// `clk`.
+    pub fn run(&mut self) -> Result<Clk, ProcessorError> {
+        loop {
+            if let Some(limit) = self.step_limit {
+                if self.clk.get() >= limit {
+                    return Err(ProcessorError::LimitExceeded(
+                        "steps".to_string(),
+                        limit as u64,
+                    ));
+                }
+            }
+            if !self.step()? || self.pc >= self.code.len() as u64 {
+                return Ok(self.clk);
+            }
+            self.clk = self.clk.checked_next(self.step_limit.unwrap_or(MAX_CLK))?;
+        }
+    }
+
+    /// Execute the instruction at `pc`, false once `end` is reached.
+    pub fn step(&mut self) -> Result<bool, ProcessorError> {
+        let (inst, step) = self
+            .code
+            .get(self.pc as usize)
+            .copied()
+            .flatten()
+            .ok_or(ProcessorError::PcVistInv(self.pc))?;
+        if self.prophet_pcs.contains(&self.pc) {
+            return Err(ProcessorError::EmulatorUnsupported("prophet".to_string()));
+        }
+        let next_pc = self.pc + step;
+        match inst {
+            Inst::Mov(dst, src) => self.registers[dst] = self.value(src),
+            Inst::Not(dst, src) => self.registers[dst] = ORDER - 1 - self.value(src),
+            Inst::Binary(opcode, dst, op0, op1) => {
+                let (a, b) = (self.registers[op0], self.value(op1));
+                self.registers[dst] = match opcode {
+                    OlaOpcode::ADD => add_mod(a, b),
+                    OlaOpcode::MUL => mul_mod(a, b),
+                    OlaOpcode::AND => a & b,
+                    OlaOpcode::OR => a | b,
+                    OlaOpcode::XOR => a ^ b,
+                    OlaOpcode::EQ => (a == b) as u64,
+                    OlaOpcode::NEQ => (a != b) as u64,
+                    OlaOpcode::GTE => (a >= b) as u64,
+                    _ => unreachable!(),
+                };
+            }
+            Inst::Assert(op1) => {
+                let value = self.value(op1);
+                if value != 1 {
+                    let reg = match op1 {
+                        Operand::Reg(index) => index as u64,
+                        _ => REG_NOT_USED as u64,
+                    };
+                    return Err(ProcessorError::AssertFail(reg, value, None));
+                }
+            }
+            Inst::AssertMessage(reg, id) => {
+                if self.registers[reg] != 1 {
+                    return Err(ProcessorError::AssertFail(
+                        reg as u64,
+                        self.registers[reg],
+                        Some(id),
+                    ));
+                }
+            }
+            Inst::Jmp(op1) => {
+                self.pc = self.value(op1);
+                return Ok(true);
+            }
+            Inst::Cjmp(op0, op1) => {
+                if self.registers[op0] == 1 {
+                    self.pc = self.value(op1);
+                    return Ok(true);
+                }
+            }
+            Inst::Cmov(dst, cond, op1) => match self.registers[cond] {
+                0 => {}
+                1 => self.registers[dst] = self.value(op1),
+                cond => return Err(ProcessorError::CmovConditionInvalid(cond)),
+            },
+            Inst::Call(op1) => {
+                let fp = self.registers[FP_REG_INDEX];
+                self.write(sub_mod(fp, 1), next_pc)?;
+                self.read(sub_mod(fp, 2))?;
+                self.pc = self.value(op1);
+                return Ok(true);
+            }
+            Inst::Ret => {
+                let fp = self.registers[FP_REG_INDEX];
+                self.pc = self.read(sub_mod(fp, 1))?;
+                self.registers[FP_REG_INDEX] = self.read(sub_mod(fp, 2))?;
+                return Ok(true);
+            }
+            Inst::Mstore(op0, offset, src) => {
+                let addr = add_mod(self.registers[op0], self.offset(offset));
+                self.write(addr, self.registers[src])?;
+            }
+            Inst::Mload(dst, op0, offset) => {
+                let addr = add_mod(self.registers[op0], self.offset(offset));
+                self.registers[dst] = self.read(addr)?;
+            }
+            Inst::Range(reg) => {
+                if self.registers[reg] > MEMORY_MODEL.word_max() {
+                    return Err(ProcessorError::U32RangeCheckFail);
+                }
+            }
+            Inst::Poseidon(dst, src, len) => {
+                let (dst, src) = (self.registers[dst], self.registers[src]);
+                let input = (0..self.value(len))
+                    .map(|i| self.read(src + i).map(GoldilocksField::from_canonical_u64))
+                    .collect::<Result<Vec<_>, _>>()?;
+                let output = calculate_arbitrary_poseidon(&input);
+                for (i, word) in output.iter().take(POSEIDON_OUTPUT_VALUE_LEN).enumerate() {
+                    self.write(dst + i as u64, word.to_canonical_u64())?;
+                }
+            }
+            Inst::Sstore(key, value) => {
+                let key = self.read_words(self.registers[key])?;
+                let value = self.read_words(self.value(value))?;
+                self.storage.insert(key, value);
+            }
+            Inst::Sload(key, dst) => {
+                let key = self.read_words(self.registers[key])?;
+                let value = self.storage.get(&key).copied().unwrap_or_default();
+                let dst = self.value(dst);
+                for (i, word) in value.iter().enumerate() {
+                    self.write(dst + i as u64, *word)?;
+                }
+            }
+            Inst::End => return Ok(false),
+            Inst::Unsupported(opcode) => {
+                return Err(ProcessorError::EmulatorUnsupported(opcode.token()))
+            }
+        }
+        self.pc = next_pc;
+        Ok(true)
+    }
+
+    fn value(&self, op: Operand) -> u64 {
+        match op {
+            Operand::Reg(index) => self.registers[index],
+            Operand::Imm(imm) => imm,
+            Operand::Psp => PSP_START_ADDR,
+        }
+    }
+
+    fn offset(&self, offset: MemOffset) -> u64 {
+        match offset {
+            MemOffset::Imm(imm) => imm,
+            MemOffset::Scaled(reg, imm) => mul_mod(imm, self.registers[reg]),
+        }
+    }
+
+    fn read(&self, addr: u64) -> Result<u64, ProcessorError> {
+        self.memory
+            .get(&addr)
+            .copied()
+            .ok_or(ProcessorError::MemVistInv(addr))
+    }
+
+    fn write(&mut self, addr: u64, value: u64) -> Result<(), ProcessorError> {
+        if addr >= PSP_START_ADDR {
+            return Err(ProcessorError::MemVistInv(addr));
+        }
+        self.memory.insert(addr, value);
+        Ok(())
+    }
+
+    fn read_words(&self, addr: u64) -> Result<[u64; TREE_VALUE_LEN], ProcessorError> {
+        let mut words = [0; TREE_VALUE_LEN];
+        for (i, word) in words.iter_mut().enumerate() {
+            *word = self.read(addr + i as u64)?;
+        }
+        Ok(words)
+    }
+}
@@ -0,0 +1,110 @@
+use crate::hooks::{Hooks, ProcessView};
+use core::program::REGISTER_NUM;
+use crossbeam_channel::{bounded, Receiver, Sender, TrySendError};
+use plonky2::field::goldilocks_field::GoldilocksField;
+
+/// One executed step, as streamed to live monitors.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct StepEvent {
+    pub clk: u32,
+    pub pc: u64,
+    /// Mnemonic of the executed instruction, e.g. `add`.
+    pub opcode: String,
+    /// Registers the step changed with their new value, empty unless
+    /// `StepEvents::with_register_deltas` is set.
+    pub register_deltas: Vec<(usize, GoldilocksField)>,
+}
+
+/// What `StepEvents` does with a step while the channel is full.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Backpressure {
+    /// Pause execution until the consumer catches up.
+    Block,
+    /// Keep executing and drop the event, counted in `StepEvents::dropped`.
+    Drop,
+}
+
+/// `Hooks` sending a `StepEvent` per executed step into a bounded channel,
+/// for live visualizers of `Process::execute_with_hooks`. Once the receiver
+/// is dropped the events are discarded and execution goes on.
+pub struct StepEvents {
+    sender: Sender<StepEvent>,
+    backpressure: Backpressure,
+    register_deltas: bool,
+    // clk, pc and registers before the running step
+    clk: u32,
+    pc: u64,
+    registers: [GoldilocksField; REGISTER_NUM],
+    disconnected: bool,
+    pub dropped: u64,
+}
+
+impl StepEvents {
+    /// Hooks and the receiving end of a channel holding up to `capacity`
+    /// events.
+    pub fn channel(capacity: usize, backpressure: Backpressure) -> (Self, Receiver<StepEvent>) {
+        let (sender, receiver) = bounded(capacity);
+        let events = Self {
+            sender,
+            backpressure,
+            register_deltas: false,
+            clk: 0,
+            pc: 0,
+            registers: [GoldilocksField::default(); REGISTER_NUM],
+            disconnected: false,
+            dropped: 0,
+        };
+        (events, receiver)
+    }
+
+    /// Include the registers each step changed in its event.
+    pub fn with_register_deltas(mut self) -> Self {
+        self.register_deltas = true;
+        self
+    }
+
+    fn send(&mut self, event: StepEvent) {
+        let res = match self.backpressure {
+            Backpressure::Block => self.sender.send(event).map_err(|_| ()),
+            Backpressure::Drop => match self.sender.try_send(event) {
+                Err(TrySendError::Full(_)) => {
+                    self.dropped += 1;
+                    Ok(())
+                }
+                res => res.map_err(|_| ()),
+            },
+        };
+        self.disconnected = res.is_err();
+    }
+}
+
+impl Hooks for StepEvents {
+    fn before_step(&mut self, view: &ProcessView) {
+        self.clk = view.clk;
+        self.pc = view.pc;
+        if self.register_deltas {
+            self.registers = *view.registers;
+        }
+    }
+
+    fn after_step(&mut self, view: &ProcessView) {
+        if self.disconnected {
+            return;
+        }
+        let register_deltas = if self.register_deltas {
+            (0..REGISTER_NUM)
+                .filter(|&i| view.registers[i] != self.registers[i])
+                .map(|i| (i, view.registers[i]))
+                .collect()
+        } else {
+            vec![]
+        };
+        let opcode = view.asm.split_whitespace().next().unwrap_or_default();
+        self.send(StepEvent {
+            clk: self.clk,
+            pc: self.pc,
+            opcode: opcode.to_string(),
+            register_deltas,
+        });
+    }
+}
@@ -0,0 +1,93 @@
+//! Gas metering of `Process::execute`. Every step is charged the cost of its
+//! opcode in the `GasSchedule` of the process, a step taking the gas used
+//! over `Process::gas_limit` fails with `ProcessorError::OutOfGas` before it
+//! runs. The gas used ends up in `Trace::gas_used`.
+//!
+//! Proofs attest the gas of the default schedule: the cpu table charges each
+//! main line the cost `DEFAULT_GAS_COSTS` gives its opcode selector, and the
+//! gas it accumulates over its steps is the `gas_used` of the public values,
+//! see `steps_gas`. Another schedule bounds the execution but is not proven.
+//!
+//! A contract called by `sccall` runs on the gas used and the limit of its
+//! caller, which goes on from the gas used at the end of the callee.
+
+use core::trace::trace::Step;
+use core::vm::opcodes::OlaOpcode;
+use serde::{Deserialize, Serialize};
+use std::collections::BTreeMap;
+
+/// Gas of a step of opcodes without an entry in the schedule.
+pub const DEFAULT_STEP_GAS: u64 = 1;
+
+/// Opcodes `GasSchedule::default` prices apart from `DEFAULT_STEP_GAS`.
+/// Memory and tape accesses cost a little more than register ops, hashing
+/// and storage accesses much more as they fill the builtin tables. The cpu
+/// table has the same costs, so every opcode here needs its own selector.
+pub const DEFAULT_GAS_COSTS: [(OlaOpcode, u64); 11] = [
+    (OlaOpcode::MLOAD, 2),
+    (OlaOpcode::MSTORE, 2),
+    (OlaOpcode::TLOAD, 3),
+    (OlaOpcode::TSTORE, 3),
+    (OlaOpcode::TLOADT, 3),
+    (OlaOpcode::TSTORET, 3),
+    (OlaOpcode::POSEIDON, 30),
+    (OlaOpcode::SLOAD, 100),
+    (OlaOpcode::SSTORE, 200),
+    (OlaOpcode::SSCAN, 400),
+    (OlaOpcode::SCCALL, 100),
+];
+
+/// Gas each opcode costs per executed step.
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+pub struct GasSchedule {
+    pub default_cost: u64,
+    /// Costs by opcode token, e.g. `sstore`.
+    pub costs: BTreeMap<String, u64>,
+}
+
+impl Default for GasSchedule {
+    /// `DEFAULT_GAS_COSTS`, the schedule proofs attest.
+    fn default() -> Self {
+        DEFAULT_GAS_COSTS
+            .iter()
+            .fold(Self::flat(DEFAULT_STEP_GAS), |schedule, (opcode, cost)| {
+                schedule.with_cost(*opcode, *cost)
+            })
+    }
+}
+
+impl GasSchedule {
+    /// Every opcode costs `cost`, e.g. 1 to meter steps.
+    pub fn flat(cost: u64) -> Self {
+        Self {
+            default_cost: cost,
+            costs: BTreeMap::new(),
+        }
+    }
+
+    pub fn with_cost(mut self, opcode: OlaOpcode, cost: u64) -> Self {
+        self.costs.insert(opcode.token(), cost);
+        self
+    }
+
+    /// Gas of a step of the opcode with token `opcode`.
+    pub fn cost(&self, opcode: &str) -> u64 {
+        self.costs.get(opcode).copied().unwrap_or(self.default_cost)
+    }
+}
+
+/// Gas of the main lines of `steps` under the default schedule, the gas the
+/// cpu table of their proof accumulates. It is the gas used of an execution
+/// on the default schedule, one main line per executed step.
+pub fn steps_gas(steps: &[Step]) -> u64 {
+    let schedule = GasSchedule::default();
+    steps
+        .iter()
+        .filter(|step| step.is_ext_line.0 == 0)
+        .map(|step| {
+            OlaOpcode::from_bit_mask(step.opcode.0).map_or(schedule.default_cost, |opcode| {
+                schedule.cost(&opcode.token())
+            })
+        })
+        .sum()
+}
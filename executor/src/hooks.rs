@@ -0,0 +1,72 @@
+use core::program::REGISTER_NUM;
+use core::vm::memory::MemoryCell;
+use plonky2::field::goldilocks_field::GoldilocksField;
+use std::fmt::{Debug, Formatter};
+
+use crate::Process;
+
+/// Read-only snapshot of the process handed to `Hooks`.
+#[derive(Debug, Clone, Copy)]
+pub struct ProcessView<'a> {
+    pub clk: u32,
+    pub pc: u64,
+    pub tp: GoldilocksField,
+    pub env_idx: GoldilocksField,
+    pub asm: &'a str,
+    pub registers: &'a [GoldilocksField; REGISTER_NUM],
+}
+
+/// Instrumentation callbacks of `Process::execute_with_hooks`.
+///
+/// All callbacks default to no-ops, so an implementation only overrides what
+/// it needs and `NoHooks` compiles down to the plain main loop.
+pub trait Hooks {
+    /// Record memory accesses for `on_memory_access`. Off by default since it
+    /// copies every accessed memory cell.
+    const OBSERVES_MEMORY: bool = false;
+
+    /// Called with the state before the instruction at `view.pc` executes.
+    fn before_step(&mut self, _view: &ProcessView) {}
+
+    /// Called with the state after the instruction executed, `view.asm` is the
+    /// executed instruction.
+    fn after_step(&mut self, _view: &ProcessView) {}
+
+    /// Called for every memory read and write of the step, in order, before
+    /// `after_step`.
+    fn on_memory_access(&mut self, _addr: u64, _cell: &MemoryCell) {}
+
+    /// Called before the prophet of the step at `view.pc` runs, after the
+    /// instruction executed.
+    fn before_prophet(&mut self, _view: &ProcessView) {}
+
+    /// Called once the prophet of the step ran, before `after_step`.
+    fn after_prophet(&mut self, _view: &ProcessView) {}
+}
+
+pub struct NoHooks;
+
+impl Hooks for NoHooks {}
+
+/// What the callback of `Process::yield_every_n_steps` asks the process to do.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum YieldAction {
+    Continue,
+    /// Stop with `ProcessorError::Interrupted`.
+    Abort,
+}
+
+/// Host callback run every `interval` steps, see
+/// `Process::yield_every_n_steps`.
+pub struct YieldHook {
+    pub interval: u32,
+    pub callback: Box<dyn FnMut(&Process) -> YieldAction + Send>,
+}
+
+impl Debug for YieldHook {
+    fn fmt(&self, f: &mut Formatter<'_>) -> std::fmt::Result {
+        f.debug_struct("YieldHook")
+            .field("interval", &self.interval)
+            .finish_non_exhaustive()
+    }
+}
@@ -0,0 +1,52 @@
+//! Supply invariant of tokens keeping a `core::types::storage::ledger`
+//! layout, checked on the storage writes of a transaction before they are
+//! committed to the account tree.
+
+use crate::Process;
+use core::merkle_tree::tree::AccountTree;
+use core::types::account::Address;
+use core::types::merkle_tree::{
+    encode_addr, tree_key_to_leaf_index, tree_value_default, u8_arr_to_tree_key, TreeKey, TreeValue,
+};
+use core::types::storage::ledger::{amount_of, LedgerLayout};
+use core::vm::error::ProcessorError;
+
+fn committed_value(tree: &AccountTree, key: &TreeKey) -> TreeValue {
+    tree.storage
+        .hash(&tree_key_to_leaf_index(key))
+        .map(|bytes| u8_arr_to_tree_key(&bytes))
+        .unwrap_or_else(tree_value_default)
+}
+
+/// Checks the balances of the token at `contract` changed by exactly as much
+/// as its total supply over the slots `process` accessed, transfers net to
+/// zero. `tree` holds the state before the transaction.
+pub fn check_ledger_invariant(
+    process: &Process,
+    tree: &AccountTree,
+    contract: &Address,
+    layout: &LedgerLayout,
+) -> Result<(), ProcessorError> {
+    let (mut balances, mut supply) = (0i128, 0i128);
+    for (key, cells) in process.storage.iter() {
+        let slot = match process.storage.slot(key) {
+            Some(slot) if slot.address() == contract => slot.key,
+            _ => continue,
+        };
+        let value = cells.last().expect("empty address trace").value;
+        let diff = amount_of(&value) as i128 - amount_of(&committed_value(tree, key)) as i128;
+        if layout.is_balance_slot(&slot) {
+            balances += diff;
+        } else if layout.is_supply_slot(&slot) {
+            supply += diff;
+        }
+    }
+    if balances != supply {
+        return Err(ProcessorError::LedgerUnbalanced(
+            encode_addr(contract),
+            balances,
+            supply,
+        ));
+    }
+    Ok(())
+}
@@ -1,9 +1,13 @@
 #![feature(const_trait_impl)]
 
-use crate::decode::{decode_raw_instruction, REG_NOT_USED};
+use crate::decode::{decode_raw_instruction, parse_offset, REG_NOT_USED};
+use crate::gas::GasSchedule;
+use crate::hooks::{Hooks, NoHooks, ProcessView, YieldAction, YieldHook};
 use crate::storage::StorageTree;
+use core::vm::chain_spec::ChainSpec;
+use core::vm::clk::{Clk, MAX_CLK};
 use core::vm::error::ProcessorError;
-use core::vm::memory::{MemoryTree, HP_START_ADDR, PSP_START_ADDR};
+use core::vm::memory::{MemoryTree, HP_START_ADDR, MEMORY_MODEL, PSP_START_ADDR};
 
 use core::merkle_tree::log::StorageLog;
 use core::merkle_tree::log::WitnessStorageLog;
@@ -11,13 +15,15 @@ use core::merkle_tree::tree::AccountTree;
 
 use core::program::instruction::IMM_INSTRUCTION_LEN;
 use core::program::instruction::{ImmediateOrRegName, Opcode};
-use core::program::{Program, REGISTER_NUM};
+use core::program::{
+    check_program_size, check_return_arity, Program, MAX_PROGRAM_WORDS, REGISTER_NUM,
+};
 use core::trace::trace::{ComparisonOperation, RegisterSelector};
 use core::trace::trace::{FilterLockForMain, MemoryOperation, MemoryType};
 use core::types::account::AccountTreeId;
 
 use core::crypto::poseidon_trace::{
-    calculate_arbitrary_poseidon_and_generate_intermediate_trace,
+    calculate_arbitrary_poseidon, calculate_arbitrary_poseidon_and_generate_intermediate_trace,
     calculate_poseidon_and_generate_intermediate_trace, POSEIDON_INPUT_VALUE_LEN,
     POSEIDON_OUTPUT_VALUE_LEN,
 };
@@ -26,23 +32,29 @@ use core::program::binary_program::OlaProphetInput;
 use core::types::account::Address;
 use core::types::merkle_tree::tree_key_default;
 use core::types::merkle_tree::tree_key_to_leaf_index;
-use core::types::merkle_tree::{u8_arr_to_tree_key, TREE_VALUE_LEN};
-use core::types::storage::StorageKey;
+use core::types::merkle_tree::{u8_arr_to_tree_key, TreeKey, TreeValue, TREE_VALUE_LEN};
+use core::types::storage::{StorageKey, SLOT_HEAD_DOMAIN, SLOT_LINK_DOMAIN};
 use core::util::poseidon_utils::POSEIDON_INPUT_NUM;
 use core::vm::heap::HEAP_PTR;
-use interpreter::interpreter::Interpreter;
-use interpreter::utils::number::NumberRet::{Multiple, Single};
-use log::{debug, info};
+use core::vm::prophet::ProphetDebugRecord;
+use log::{debug, info, warn};
 use plonky2::field::goldilocks_field::GoldilocksField;
 use plonky2::field::types::Field64;
 use plonky2::field::types::{Field, PrimeField64};
 use regex::Regex;
-use std::collections::{BTreeMap, HashMap};
+use std::collections::BTreeMap;
+use std::iter;
+use std::ops::Range;
+use std::path::PathBuf;
 
 use crate::load_tx::{init_ctx_addr_info, load_ctx_addr_info};
+use crate::prophet::interpret;
+use crate::repro::ReproBundle;
 use crate::tape::TapeTree;
-use crate::trace::{gen_memory_table, gen_tape_table};
+use crate::trace::{gen_memory_table, gen_tape_table, gen_transient_table};
+use crate::transient::TransientStorage;
 use core::memory_zone_process;
+use core::trace::stream::{TraceChunk, TraceSink};
 use core::trace::trace::Step;
 use core::vm::vm_state::SCCallType;
 use core::vm::vm_state::VMState;
@@ -51,12 +63,35 @@ use std::time::Instant;
 
 mod decode;
 
+pub mod block_builder;
+pub mod chrome_trace;
+pub mod conformance;
+pub mod continuation;
+pub mod cost_model;
+pub mod determinism;
+pub mod emulator;
+pub mod events;
+pub mod gas;
+pub mod hooks;
+pub mod ledger;
 pub mod load_tx;
+pub mod memory_audit;
+pub mod navigator;
+pub mod preflight;
+pub mod profile;
+pub mod prophet;
+pub mod repro;
+#[cfg(feature = "rpc")]
+pub mod rpc;
+pub mod semantics;
+pub mod simulate;
+pub mod snapshot;
 pub mod storage;
 mod tape;
 #[cfg(test)]
 mod tests;
 pub mod trace;
+mod transient;
 
 #[macro_export]
 macro_rules! memory_zone_detect {
@@ -90,9 +125,14 @@ macro_rules! memory_op {
             region_prophet = GoldilocksField::ONE;
             region_heap = GoldilocksField::ZERO;
         });
+        let is_rw = if $v.in_code_region($mem_addr) {
+            MemoryType::WriteOnce
+        } else {
+            is_rw
+        };
         $read_addr = $v.memory.read(
             $mem_addr,
-            $v.clk,
+            $v.clk.get(),
             GoldilocksField::from_canonical_u64(1 << $opcode as u64),
             GoldilocksField::from_canonical_u64(is_rw as u64),
             GoldilocksField::from_canonical_u64(MemoryOperation::Read as u64),
@@ -107,9 +147,12 @@ macro_rules! memory_op {
         let region_prophet;
         let region_heap;
         memory_zone_detect!($mem_addr, is_rw, region_prophet, region_heap, $panic);
+        if $v.in_code_region($mem_addr) {
+            return Err(ProcessorError::CodeRegionWrite($mem_addr));
+        }
         $v.memory.write(
             $mem_addr,
-            $v.clk,
+            $v.clk.get(),
             GoldilocksField::from_canonical_u64(1 << $opcode as u64),
             GoldilocksField::from_canonical_u64(is_rw as u64),
             GoldilocksField::from_canonical_u64(MemoryOperation::Write as u64),
@@ -126,7 +169,7 @@ macro_rules! memory_op {
 macro_rules! aux_insert {
     ($v: expr, $aux_steps: tt, $ctx_regs_status: tt, $ctx_code_regs_status: tt, $registers_status: tt, $register_selector: expr, $ext_cnt: tt, $filter_tape_looking: tt) => {
         $aux_steps.push(Step {
-            clk: $v.clk,
+            clk: $v.clk.get(),
             pc: $v.pc,
             tp: $v.tp,
             instruction: $v.instruction,
@@ -182,6 +225,27 @@ const PROPHET_INPUT_REG_END_INDEX: usize = PROPHET_INPUT_REG_START_INDEX + PROPH
 // start from fp-3
 const PROPHET_INPUT_FP_START_OFFSET: u64 = 3;
 const TP_START_ADDR: GoldilocksField = GoldilocksField::ZERO;
+/// Most slots one `sscan` may read.
+pub const SSCAN_MAX_SLOTS: u64 = 16;
+/// Ext line selector of the slot list lines holding whether the slot an
+/// `sstore` writes was listed.
+pub const SLOT_LISTED_SEL: usize = 2 * TREE_VALUE_LEN;
+/// Ext line selector holding the key domain of a slot list record.
+pub const SLOT_DOMAIN_SEL: usize = 2 * TREE_VALUE_LEN + 1;
+
+/// Slot list record pointing at slot `next` of the prefix, or at the end of
+/// the list when `has_next` is 0. Word 0 is 1 so a written record is never
+/// the zero value of one never written.
+fn slot_pointer(next: GoldilocksField, has_next: GoldilocksField) -> TreeValue {
+    [GoldilocksField::ONE, next, has_next, GoldilocksField::ZERO]
+}
+
+/// Key of the head record of the slot list of the prefix of `key`.
+fn slot_head_key(key: &TreeKey) -> TreeKey {
+    let mut head_key = *key;
+    head_key[TREE_VALUE_LEN - 1] = GoldilocksField::ZERO;
+    head_key
+}
 
 #[derive(Debug, Clone)]
 enum MemRangeType {
@@ -192,7 +256,7 @@ enum MemRangeType {
 pub struct Process {
     pub env_idx: GoldilocksField,
     pub call_sc_cnt: GoldilocksField,
-    pub clk: u32,
+    pub clk: Clk,
     pub addr_storage: Address,
     pub addr_code: Address,
     pub registers: [GoldilocksField; REGISTER_NUM],
@@ -211,7 +275,35 @@ pub struct Process {
     pub program_log: Vec<WitnessStorageLog>,
     pub tp: GoldilocksField,
     pub tape: TapeTree,
+    // addresses mapped read only by `map_code`
+    pub code_region: Option<Range<u64>>,
+    // tload_t/tstore_t slots of the current tx
+    pub transient: TransientStorage,
     pub storage_access_idx: GoldilocksField,
+    // running poseidon commitment over every prophet output of this process
+    pub prophet_commitment: TreeValue,
+    // abort execution once clk reaches this many steps
+    pub step_limit: Option<u32>,
+    // gas charged per step, see `gas`
+    pub gas_schedule: GasSchedule,
+    // fail with `ProcessorError::OutOfGas` past this much gas
+    pub gas_limit: Option<u64>,
+    pub gas_used: u64,
+    // host callback run every few steps, set by `yield_every_n_steps`
+    pub yield_hook: Option<YieldHook>,
+    // keep a debug record of every prophet run in the trace
+    pub record_prophets: bool,
+    // opcode tokens the chain spec disables at the block height executed
+    pub disabled_opcodes: Vec<String>,
+    // `ChainSpec::fork_id` of the block height executed
+    pub fork_id: TreeValue,
+    // code words a program may have, see `ChainSpec::max_program_words`
+    pub max_program_words: usize,
+    // chain and hash of the tx executed, read by `txctx`
+    pub chain_id: GoldilocksField,
+    pub tx_hash: TreeValue,
+    // write a `repro::ReproBundle` here when execution fails
+    pub repro_dir: Option<PathBuf>,
 }
 
 impl Process {
@@ -219,7 +311,7 @@ impl Process {
         Self {
             env_idx: Default::default(),
             call_sc_cnt: Default::default(),
-            clk: 0,
+            clk: Clk::ZERO,
             addr_storage: Address::default(),
             addr_code: Address::default(),
             registers: [Default::default(); REGISTER_NUM],
@@ -229,23 +321,159 @@ impl Process {
             immediate_data: Default::default(),
             opcode: Default::default(),
             op1_imm: Default::default(),
-            memory: MemoryTree {
-                trace: BTreeMap::new(),
-            },
+            memory: MemoryTree::default(),
             psp: GoldilocksField(PSP_START_ADDR),
             psp_start: GoldilocksField(PSP_START_ADDR),
             hp: GoldilocksField(HP_START_ADDR),
             storage_log: Vec::new(),
             program_log: Vec::new(),
-            storage: StorageTree {
-                trace: HashMap::new(),
-            },
+            storage: StorageTree::default(),
             tp: TP_START_ADDR,
             tape: TapeTree {
                 trace: BTreeMap::new(),
             },
+            code_region: None,
+            transient: TransientStorage::default(),
             storage_access_idx: GoldilocksField::ZERO,
+            prophet_commitment: tree_key_default(),
+            step_limit: None,
+            gas_schedule: GasSchedule::default(),
+            gas_limit: None,
+            gas_used: 0,
+            yield_hook: None,
+            record_prophets: false,
+            disabled_opcodes: Vec::new(),
+            fork_id: tree_key_default(),
+            max_program_words: MAX_PROGRAM_WORDS,
+            chain_id: GoldilocksField::ZERO,
+            tx_hash: tree_key_default(),
+            repro_dir: None,
+        }
+    }
+
+    /// Preload memory, e.g. with the memory of a previous continuation chunk.
+    /// Every cell becomes a write at clk 0 that is not looked up by the cpu,
+    /// so the first instruction touching it reads the image value. Only the
+    /// read-write regions can be preloaded, a heap ptr in the image replaces
+    /// its initial value. A proof of the execution carries the image in its
+    /// public values.
+    pub fn load_memory_image(
+        &mut self,
+        image: &BTreeMap<u64, GoldilocksField>,
+    ) -> Result<(), ProcessorError> {
+        for (&addr, &value) in image {
+            if addr >= PSP_START_ADDR || self.memory.trace.contains_key(&addr) {
+                return Err(ProcessorError::MemVistInv(addr));
+            }
+            let region_heap = if addr >= HP_START_ADDR {
+                GoldilocksField::ONE
+            } else {
+                GoldilocksField::ZERO
+            };
+            self.memory.write(
+                addr,
+                0,
+                GoldilocksField::from_canonical_u64(1 << Opcode::MSTORE as u8),
+                GoldilocksField::from_canonical_u64(MemoryType::ReadWrite as u64),
+                GoldilocksField::from_canonical_u64(MemoryOperation::Write as u64),
+                GoldilocksField::from_canonical_u64(FilterLockForMain::False as u64),
+                GoldilocksField::ZERO,
+                region_heap,
+                value,
+                self.env_idx,
+            );
+        }
+        Ok(())
+    }
+
+    /// Preload prophet outputs of a previous continuation chunk. They become
+    /// image cells like the ones of `load_memory_image`, but write once in
+    /// the prophet region, so the public image covers them and prophets can't
+    /// overwrite them. They have to lie below `psp`.
+    pub fn load_prophet_outputs(
+        &mut self,
+        outputs: &BTreeMap<u64, GoldilocksField>,
+    ) -> Result<(), ProcessorError> {
+        for (&addr, &value) in outputs {
+            if addr < PSP_START_ADDR
+                || addr >= self.psp.to_canonical_u64()
+                || self.memory.trace.contains_key(&addr)
+            {
+                return Err(ProcessorError::MemVistInv(addr));
+            }
+            self.memory.write(
+                addr,
+                0,
+                GoldilocksField::from_canonical_u64(1 << Opcode::MSTORE as u8),
+                GoldilocksField::from_canonical_u64(MemoryType::WriteOnce as u64),
+                GoldilocksField::from_canonical_u64(MemoryOperation::Write as u64),
+                GoldilocksField::from_canonical_u64(FilterLockForMain::False as u64),
+                GoldilocksField::ONE,
+                GoldilocksField::ZERO,
+                value,
+                self.env_idx,
+            );
         }
+        Ok(())
+    }
+
+    /// Map `words`, e.g. the code or rodata of the program, read only into the
+    /// stack region at `base`. They are written at clk 0 like prophet outputs,
+    /// after that the memory table only allows mloads of them and memory
+    /// writes fail with `CodeRegionWrite`. Only one region can be mapped.
+    pub fn map_code(&mut self, base: u64, words: &[GoldilocksField]) -> Result<(), ProcessorError> {
+        let end = base + words.len() as u64;
+        if self.code_region.is_some() || end > HP_START_ADDR {
+            return Err(ProcessorError::MemVistInv(end));
+        }
+        if let Some((&addr, _)) = self.memory.trace.range(base..end).next() {
+            return Err(ProcessorError::MemVistInv(addr));
+        }
+        for (addr, &word) in (base..end).zip(words) {
+            self.memory.write(
+                addr,
+                0,
+                GoldilocksField::ZERO,
+                GoldilocksField::from_canonical_u64(MemoryType::WriteOnce as u64),
+                GoldilocksField::from_canonical_u64(MemoryOperation::Write as u64),
+                GoldilocksField::from_canonical_u64(FilterLockForMain::False as u64),
+                GoldilocksField::ZERO,
+                GoldilocksField::ZERO,
+                word,
+                self.env_idx,
+            );
+        }
+        self.code_region = Some(base..end);
+        Ok(())
+    }
+
+    /// Ends the use of `account_tree` by the process. With `commit` the blocks
+    /// processed into it since its last save, e.g. by `gen_storage_hash_table`,
+    /// are saved, otherwise they are dropped together with the storage logs
+    /// not hashed into it yet. A failed save drops them as well, the tree
+    /// never keeps part of an execution.
+    pub fn finalize(
+        &mut self,
+        account_tree: &mut AccountTree,
+        commit: bool,
+    ) -> Result<(), ProcessorError> {
+        if commit {
+            if let Err(err) = account_tree.commit() {
+                account_tree.discard_pending();
+                return Err(err.into());
+            }
+        } else {
+            account_tree.discard_pending();
+            self.storage_log.clear();
+            self.program_log.clear();
+        }
+        Ok(())
+    }
+
+    fn in_code_region(&self, addr: u64) -> bool {
+        self.code_region
+            .as_ref()
+            .map_or(false, |region| region.contains(&addr))
     }
 
     pub fn get_reg_index(&self, reg_str: &str) -> usize {
@@ -300,7 +528,7 @@ impl Process {
                 .memory
                 .read(
                     self.registers[FP_REG_INDEX].0 - *fp,
-                    self.clk,
+                    self.clk.get(),
                     GoldilocksField::from_canonical_u64(0 as u64),
                     GoldilocksField::from_canonical_u64(MemoryType::ReadWrite as u64),
                     GoldilocksField::from_canonical_u64(MemoryOperation::Read as u64),
@@ -317,7 +545,7 @@ impl Process {
                 .memory
                 .read(
                     value,
-                    self.clk,
+                    self.clk.get(),
                     GoldilocksField::from_canonical_u64(0 as u64),
                     GoldilocksField::from_canonical_u64(MemoryType::ReadWrite as u64),
                     GoldilocksField::from_canonical_u64(MemoryOperation::Read as u64),
@@ -331,14 +559,16 @@ impl Process {
         Ok(value)
     }
 
-    pub fn prophet(&mut self, prophet: &mut OlaProphet) -> Result<(), ProcessorError> {
+    pub fn prophet(
+        &mut self,
+        prophet: &mut OlaProphet,
+    ) -> Result<ProphetDebugRecord, ProcessorError> {
         debug!("prophet code:{}", prophet.code);
 
         let re = Regex::new(r"^%\{([\s\S]*)%}$").unwrap();
 
         let code = re.captures(&prophet.code).unwrap().get(1).unwrap().as_str();
         debug!("code:{}", code);
-        let mut interpreter = Interpreter::new(code);
 
         let mut values = Vec::new();
 
@@ -361,36 +591,59 @@ impl Process {
         }
 
         prophet.ctx.push((HEAP_PTR.to_string(), self.hp.0));
-        let res = interpreter.run(prophet, values, &self.memory);
-        // todo: need process error!
-        debug!("interpreter:{:?}", res);
-
-        if let Ok(out) = res {
-            match out {
-                Single(_) => return Err(ProcessorError::ParseIntError),
-                Multiple(mut values) => {
-                    self.psp_start = self.psp;
-                    self.hp = GoldilocksField(values.pop().unwrap().get_number() as u64);
-                    debug!("prophet addr:{}", self.psp.0);
-                    for value in values {
-                        self.memory.write(
-                            self.psp.0,
-                            0, //write， clk is 0
-                            GoldilocksField::from_canonical_u64(0 as u64),
-                            GoldilocksField::from_canonical_u64(MemoryType::WriteOnce as u64),
-                            GoldilocksField::from_canonical_u64(MemoryOperation::Write as u64),
-                            GoldilocksField::from_canonical_u64(FilterLockForMain::False as u64),
-                            GoldilocksField::from_canonical_u64(1_u64),
-                            GoldilocksField::from_canonical_u64(0_u64),
-                            GoldilocksField(value.get_number() as u64),
-                            self.env_idx,
-                        );
-                        self.psp += GoldilocksField::ONE;
-                    }
-                }
-            }
+        let mut record = ProphetDebugRecord {
+            host: prophet.host as u64,
+            prophet: prophet.clone(),
+            code: code.to_string(),
+            inputs: values,
+            outputs: Vec::new(),
+            error: None,
+        };
+        interpret(&mut record, &self.memory);
+        if record.error.is_some() {
+            return Err(ProcessorError::ProphetFailed(Box::new(record)));
         }
-        Ok(())
+
+        let mut values = record.outputs.clone();
+        self.psp_start = self.psp;
+        let hp = values.pop().unwrap();
+        // the heap only grows and must stay below the prophet region.
+        if hp < self.hp.0 || hp >= PSP_START_ADDR {
+            return Err(ProcessorError::MemVistInv(hp));
+        }
+        self.hp = GoldilocksField(hp);
+        self.commit_prophet_outputs(
+            prophet.host as u64,
+            values.iter().map(|v| GoldilocksField(*v)),
+        );
+        debug!("prophet addr:{}", self.psp.0);
+        for value in values {
+            self.memory.write(
+                self.psp.0,
+                0, //write， clk is 0
+                GoldilocksField::from_canonical_u64(0 as u64),
+                GoldilocksField::from_canonical_u64(MemoryType::WriteOnce as u64),
+                GoldilocksField::from_canonical_u64(MemoryOperation::Write as u64),
+                GoldilocksField::from_canonical_u64(FilterLockForMain::False as u64),
+                GoldilocksField::from_canonical_u64(1_u64),
+                GoldilocksField::from_canonical_u64(0_u64),
+                GoldilocksField(value),
+                self.env_idx,
+            );
+            self.psp += GoldilocksField::ONE;
+        }
+        Ok(record)
+    }
+
+    fn commit_prophet_outputs<I: Iterator<Item = GoldilocksField>>(
+        &mut self,
+        host: u64,
+        outputs: I,
+    ) {
+        let mut input = self.prophet_commitment.to_vec();
+        input.push(GoldilocksField::from_canonical_u64(host));
+        input.extend(outputs);
+        self.prophet_commitment = calculate_arbitrary_poseidon(&input);
     }
 
     fn print_vm_state(&mut self, instruction: &str) {
@@ -438,7 +691,7 @@ impl Process {
         }
         println!("--------------- storage ---------------");
         tmp_cnt = 0;
-        self.storage.trace.iter().for_each(|(_, v)| {
+        self.storage.iter().for_each(|(_, v)| {
             tmp_cnt += 1;
             let cell = v.last().unwrap();
             let tree_key = cell.addr;
@@ -477,7 +730,7 @@ impl Process {
 
         let mut immediate_data = GoldilocksField::ZERO;
 
-        let next_instr = if (instrs_len - 2) >= pc {
+        let next_instr = if pc + 1 < instrs_len {
             program.instructions[(pc + 1) as usize].trim()
         } else {
             ""
@@ -607,27 +860,43 @@ impl Process {
 
     fn execute_inst_assert(&mut self, ops: &[&str], step: u64) -> Result<(), ProcessorError> {
         let opcode = ops.first().unwrap().to_lowercase();
-        assert_eq!(
-            ops.len(),
-            2,
+        assert!(
+            ops.len() == 2 || ops.len() == 3,
             "{}",
-            format!("{} params len is 2", opcode.as_str())
+            format!("{} params len is 1 or 2", opcode.as_str())
         );
-        let value = self.get_index_value(ops[1]);
 
-        self.register_selector.op1 = value.0;
+        // `assert op1` checks op1, `assert op0 message_id` checks op0 and
+        // carries the message id as immediate op1.
         let mut reg_index = 0xff;
-        if let ImmediateOrRegName::RegName(op1_index) = value.1 {
-            reg_index = op1_index;
-            self.register_selector.op1_reg_sel[op1_index] = GoldilocksField::from_canonical_u64(1);
-        }
+        let mut message_id = None;
+        let value = if ops.len() == 3 {
+            let op0_index = self.get_reg_index(ops[1]);
+            let id = self.get_index_value(ops[2]);
+            reg_index = op0_index;
+            message_id = Some(id.0.to_canonical_u64());
+            self.register_selector.op0 = self.registers[op0_index];
+            self.register_selector.op0_reg_sel[op0_index] = GoldilocksField::from_canonical_u64(1);
+            self.register_selector.op1 = id.0;
+            self.registers[op0_index]
+        } else {
+            let value = self.get_index_value(ops[1]);
+            self.register_selector.op1 = value.0;
+            if let ImmediateOrRegName::RegName(op1_index) = value.1 {
+                reg_index = op1_index;
+                self.register_selector.op1_reg_sel[op1_index] =
+                    GoldilocksField::from_canonical_u64(1);
+            }
+            value.0
+        };
 
         let op_type = match opcode.as_str() {
             "assert" => {
-                if GoldilocksField::ONE != value.0 {
+                if GoldilocksField::ONE != value {
                     return Err(ProcessorError::AssertFail(
                         reg_index as u64,
-                        value.0.to_canonical_u64(),
+                        value.to_canonical_u64(),
+                        message_id,
                     ));
                 }
                 Opcode::ASSERT
@@ -778,7 +1047,7 @@ impl Process {
             "{}",
             format!("{} params len is not match", opcode.as_str())
         );
-        let mut offset_addr = 0;
+        let offset_addr;
         let op0_value = self.get_index_value(ops[1]);
 
         self.register_selector.op0 = op0_value.0;
@@ -789,12 +1058,9 @@ impl Process {
         }
         let dst_index;
         if ops.len() == 4 {
-            let offset_res = u64::from_str_radix(ops[2], 10);
-            if let Ok(offset) = offset_res {
-                offset_addr = offset;
-                self.op1_imm = GoldilocksField::ONE;
-            }
-            self.register_selector.op1 = GoldilocksField::from_canonical_u64(offset_addr);
+            offset_addr = parse_offset(ops[2])?;
+            self.op1_imm = GoldilocksField::ONE;
+            self.register_selector.op1 = offset_addr;
             //fixme.
             self.register_selector.aux0 = GoldilocksField::ZERO;
             dst_index = self.get_reg_index(ops[3]);
@@ -802,20 +1068,17 @@ impl Process {
             let op1_index = self.get_reg_index(ops[2]);
             self.register_selector.op1 = self.registers[op1_index];
             self.register_selector.op1_reg_sel[op1_index] = GoldilocksField::from_canonical_u64(1);
-            let offset_res = u64::from_str_radix(ops[3], 10);
-            if let Ok(offset) = offset_res {
-                self.register_selector.aux0 = GoldilocksField::from_canonical_u64(offset);
-                offset_addr = offset * self.register_selector.op1.to_canonical_u64();
-                self.op1_imm = GoldilocksField::ZERO;
-            }
+            let offset = parse_offset(ops[3])?;
+            self.register_selector.aux0 = offset;
+            offset_addr = offset * self.register_selector.op1;
+            self.op1_imm = GoldilocksField::ZERO;
             dst_index = self.get_reg_index(ops[4]);
         }
 
         self.register_selector.dst = self.registers[dst_index];
         self.register_selector.dst_reg_sel[dst_index] = GoldilocksField::from_canonical_u64(1);
 
-        let write_addr =
-            (op0_value.0 + GoldilocksField::from_canonical_u64(offset_addr)).to_canonical_u64();
+        let write_addr = (op0_value.0 + offset_addr).to_canonical_u64();
         self.register_selector.aux1 = GoldilocksField::from_canonical_u64(write_addr);
 
         memory_op!(
@@ -849,15 +1112,12 @@ impl Process {
 
         self.register_selector.op0 = op0_value.0;
 
-        let mut offset_addr = 0;
+        let offset_addr;
 
         if ops.len() == 4 {
-            let offset_res = u64::from_str_radix(ops[3], 10);
-            if let Ok(offset) = offset_res {
-                offset_addr = offset;
-                self.op1_imm = GoldilocksField::ONE;
-            }
-            self.register_selector.op1 = GoldilocksField::from_canonical_u64(offset_addr);
+            offset_addr = parse_offset(ops[3])?;
+            self.op1_imm = GoldilocksField::ONE;
+            self.register_selector.op1 = offset_addr;
             //fixme.
             self.register_selector.aux0 = GoldilocksField::ZERO;
         } else {
@@ -865,16 +1125,13 @@ impl Process {
             self.register_selector.op1 = self.registers[op1_index];
             debug!("op1:{}", self.register_selector.op1);
             self.register_selector.op1_reg_sel[op1_index] = GoldilocksField::from_canonical_u64(1);
-            let offset_res = u64::from_str_radix(ops[4], 10);
-            if let Ok(offset) = offset_res {
-                self.register_selector.aux0 = GoldilocksField::from_canonical_u64(offset);
-                offset_addr = offset * self.register_selector.op1.to_canonical_u64();
-                self.op1_imm = GoldilocksField::ZERO;
-            }
+            let offset = parse_offset(ops[4])?;
+            self.register_selector.aux0 = offset;
+            offset_addr = offset * self.register_selector.op1;
+            self.op1_imm = GoldilocksField::ZERO;
         }
 
-        let read_addr =
-            (op0_value.0 + GoldilocksField::from_canonical_u64(offset_addr)).to_canonical_u64();
+        let read_addr = (op0_value.0 + offset_addr).to_canonical_u64();
         self.register_selector.aux1 = GoldilocksField::from_canonical_u64(read_addr);
 
         memory_op!(self, read_addr, self.registers[dst_index], Opcode::MLOAD);
@@ -901,7 +1158,7 @@ impl Process {
             format!("{} params len is 1", opcode.as_str())
         );
         let op1_index = self.get_reg_index(ops[1]);
-        if self.registers[op1_index].to_canonical_u64() > u32::MAX as u64 {
+        if self.registers[op1_index].to_canonical_u64() > MEMORY_MODEL.word_max() {
             return Err(ProcessorError::U32RangeCheckFail);
         }
 
@@ -1028,7 +1285,7 @@ impl Process {
                 abs_diff = self.register_selector.op1 - self.register_selector.op0;
             }
 
-            if abs_diff.to_canonical_u64() > u32::MAX as u64 {
+            if abs_diff.to_canonical_u64() > MEMORY_MODEL.word_max() {
                 return Err(ProcessorError::U32RangeCheckFail);
             }
             program.trace.insert_rangecheck(
@@ -1074,16 +1331,22 @@ impl Process {
         if len != GoldilocksField::ZERO {
             let len = len.to_canonical_u64();
             for i in 0..len {
-                program.trace.ret.push(
+                program.trace.insert_ret(
                     self.tape
                         .read_without_trace(self.tp.to_canonical_u64() - len - 1 + i)?,
                 );
             }
         }
+        // only the end of the entry program returns to the caller of the vm
+        if self.env_idx == GoldilocksField::ZERO {
+            if let Some(arity) = program.return_arity {
+                program.trace.returned_values = self.registers[..arity].to_vec();
+            }
+        }
         let mut end_step = None;
         if !program.pre_exe_flag {
             program.trace.insert_step(
-                self.clk,
+                self.clk.get(),
                 pc_status,
                 self.tp,
                 self.instruction,
@@ -1104,7 +1367,7 @@ impl Process {
 
             if self.env_idx.ne(&GoldilocksField::ZERO) {
                 self.register_selector.aux0 = self.env_idx;
-                self.register_selector.aux1 = GoldilocksField::from_canonical_u64(self.clk as u64);
+                self.register_selector.aux1 = self.clk.to_field();
                 let register_selector = self.register_selector.clone();
                 end_step = Some(Step {
                     env_idx: GoldilocksField::default(),
@@ -1131,9 +1394,12 @@ impl Process {
         Ok(end_step)
     }
 
+    /// `sstore key value` stores the 4 words at memory `value` under the slot
+    /// whose 4 word key is at memory `key`.
     fn execute_inst_sstore(
         &mut self,
         program: &mut Program,
+        account_tree: &AccountTree,
         aux_steps: &mut Vec<Step>,
         ops: &[&str],
         step: u64,
@@ -1178,9 +1444,12 @@ impl Process {
         let storage_key = StorageKey::new(AccountTreeId::new(self.addr_storage.clone()), slot_key);
         let (tree_key, hash_row) = storage_key.hashed_key();
         register_selector_regs.dst_reg_sel[0..TREE_VALUE_LEN].clone_from_slice(&tree_key);
+        register_selector_regs.dst_reg_sel[TREE_VALUE_LEN..TREE_VALUE_LEN * 2]
+            .clone_from_slice(&slot_key);
+        self.storage.note_slot(tree_key, storage_key);
 
         self.storage.write(
-            self.clk,
+            self.clk.get(),
             GoldilocksField::from_canonical_u64(1 << Opcode::SSTORE as u64),
             tree_key,
             store_value,
@@ -1188,30 +1457,44 @@ impl Process {
             self.env_idx,
         );
         self.storage_access_idx += GoldilocksField::ONE;
+        let slot_storage_idx = self.storage_access_idx;
 
         if !program.pre_exe_flag {
             self.storage_log.push(WitnessStorageLog {
                 storage_log: StorageLog::new_write_log(tree_key, store_value),
                 previous_value: tree_key_default(),
             });
+            program.trace.insert_poseidon(hash_row);
+        }
+        let list_lines = self.list_slot(program, account_tree, &slot_key);
 
-            program.trace.builtin_poseidon.push(hash_row);
-            let ext_cnt = GoldilocksField::ONE;
-            let filter_tape_looking = GoldilocksField::ZERO;
-
-            let ctx_regs_status = ctx_regs_status.clone();
-            let ctx_code_regs_status = ctx_code_regs_status.clone();
-            let registers_status = registers_status.clone();
-            aux_insert!(
-                self,
-                aux_steps,
-                ctx_regs_status,
-                ctx_code_regs_status,
-                registers_status,
-                register_selector_regs,
-                ext_cnt,
-                filter_tape_looking
-            );
+        if !program.pre_exe_flag {
+            for (index, mut register_selector_regs) in iter::once(register_selector_regs)
+                .chain(list_lines)
+                .enumerate()
+            {
+                register_selector_regs.op0 = self.register_selector.op0;
+                register_selector_regs.op1 = self.register_selector.op1;
+                let ext_cnt = GoldilocksField::from_canonical_u64(index as u64 + 1);
+                let filter_tape_looking = GoldilocksField::ZERO;
+
+                let ctx_regs_status = ctx_regs_status.clone();
+                let ctx_code_regs_status = ctx_code_regs_status.clone();
+                let registers_status = registers_status.clone();
+                aux_insert!(
+                    self,
+                    aux_steps,
+                    ctx_regs_status,
+                    ctx_code_regs_status,
+                    registers_status,
+                    register_selector_regs,
+                    ext_cnt,
+                    filter_tape_looking
+                );
+                // every ext line is one storage access
+                aux_steps.last_mut().unwrap().storage_access_idx =
+                    slot_storage_idx + GoldilocksField::from_canonical_usize(index);
+            }
         }
         self.pc += step;
         if program.print_flag {
@@ -1239,10 +1522,126 @@ impl Process {
         Ok(())
     }
 
+    /// Current value of the slot at `tree_key`: the last access of this
+    /// execution, else the account tree.
+    fn storage_value(&self, account_tree: &AccountTree, tree_key: &TreeKey) -> TreeValue {
+        if let Some(data) = self.storage.get(tree_key) {
+            return data.last().unwrap().value;
+        }
+        match account_tree.storage.hash(&tree_key_to_leaf_index(tree_key)) {
+            Some(value) => u8_arr_to_tree_key(&value),
+            None => {
+                debug!("sload can not read data from addr:{:?}", tree_key);
+                tree_key_default()
+            }
+        }
+    }
+
+    /// Reads the slot list record of `key` in `domain` of the current
+    /// contract, or writes `write` to it, as a storage access of the current
+    /// instruction. Returns the record and the selectors of its ext line: the
+    /// key in op0_sel[4..8], the record in op1_sel[4..8], the tree key in
+    /// dst_sel[0..4] and the domain in dst_sel[9].
+    fn access_slot_record(
+        &mut self,
+        program: &mut Program,
+        account_tree: &AccountTree,
+        key: TreeKey,
+        domain: u64,
+        write: Option<TreeValue>,
+    ) -> (TreeValue, RegisterSelector) {
+        let (tree_key, hash_row) =
+            StorageKey::raw_domain_hashed_key(&self.addr_storage, &key, domain);
+        let (record, storage_log) = match write {
+            Some(record) => {
+                self.storage.write(
+                    self.clk.get(),
+                    self.opcode,
+                    tree_key,
+                    record,
+                    tree_key_default(),
+                    self.env_idx,
+                );
+                (record, StorageLog::new_write_log(tree_key, record))
+            }
+            None => {
+                let record = self.storage.read(
+                    self.clk.get(),
+                    self.opcode,
+                    tree_key,
+                    tree_key_default(),
+                    self.storage_value(account_tree, &tree_key),
+                    self.env_idx,
+                );
+                (record, StorageLog::new_read_log(tree_key, record))
+            }
+        };
+        self.storage_access_idx += GoldilocksField::ONE;
+        if !program.pre_exe_flag {
+            self.storage_log.push(WitnessStorageLog {
+                storage_log,
+                previous_value: tree_key_default(),
+            });
+            program.trace.insert_poseidon(hash_row);
+        }
+
+        let mut register_selector_regs = RegisterSelector::default();
+        register_selector_regs.op0_reg_sel[TREE_VALUE_LEN..TREE_VALUE_LEN * 2]
+            .clone_from_slice(&key);
+        register_selector_regs.op1_reg_sel[TREE_VALUE_LEN..TREE_VALUE_LEN * 2]
+            .clone_from_slice(&record);
+        register_selector_regs.dst_reg_sel[0..TREE_VALUE_LEN].clone_from_slice(&tree_key);
+        register_selector_regs.dst_reg_sel[SLOT_DOMAIN_SEL] =
+            GoldilocksField::from_canonical_u64(domain);
+        (record, register_selector_regs)
+    }
+
+    /// Lists the slot `key` of the current contract under its prefix unless
+    /// it is listed. The link of the slot is read, and if it was never
+    /// written the head of the prefix is read, then the link set to the slot
+    /// the head points to and the head to the slot. Returns the selectors of
+    /// these ext lines, which carry the key in dst_sel[4..8] and whether the
+    /// slot was listed in dst_sel[8].
+    fn list_slot(
+        &mut self,
+        program: &mut Program,
+        account_tree: &AccountTree,
+        key: &TreeKey,
+    ) -> Vec<RegisterSelector> {
+        let (link, link_line) =
+            self.access_slot_record(program, account_tree, *key, SLOT_LINK_DOMAIN, None);
+        let listed = link[0];
+        let mut lines = vec![link_line];
+        if listed.is_zero() {
+            let head_key = slot_head_key(key);
+            let (head, head_line) =
+                self.access_slot_record(program, account_tree, head_key, SLOT_HEAD_DOMAIN, None);
+            let link = slot_pointer(head[1], head[2]);
+            let (_, link_line) =
+                self.access_slot_record(program, account_tree, *key, SLOT_LINK_DOMAIN, Some(link));
+            let head = slot_pointer(key[TREE_VALUE_LEN - 1], GoldilocksField::ONE);
+            let (_, new_head_line) = self.access_slot_record(
+                program,
+                account_tree,
+                head_key,
+                SLOT_HEAD_DOMAIN,
+                Some(head),
+            );
+            lines.extend([head_line, link_line, new_head_line]);
+        }
+        for line in lines.iter_mut() {
+            line.dst_reg_sel[TREE_VALUE_LEN..TREE_VALUE_LEN * 2].clone_from_slice(key);
+            line.dst_reg_sel[SLOT_LISTED_SEL] = listed;
+        }
+        lines
+    }
+
+    /// `sload key value` loads the slot whose 4 word key is at memory `key`
+    /// into the 4 words at memory `value`.
     fn execute_inst_sload(
         &mut self,
         program: &mut Program,
-        account_tree: &mut AccountTree,
+        account_tree: &AccountTree,
         aux_steps: &mut Vec<Step>,
         ops: &[&str],
         step: u64,
@@ -1279,21 +1678,10 @@ impl Process {
 
         let storage_key = StorageKey::new(AccountTreeId::new(self.addr_storage.clone()), slot_key);
         let (tree_key, hash_row) = storage_key.hashed_key();
-        let path = tree_key_to_leaf_index(&tree_key);
         register_selector_regs.dst_reg_sel[0..TREE_VALUE_LEN].clone_from_slice(&tree_key);
+        self.storage.note_slot(tree_key, storage_key);
 
-        let read_value;
-        if let Some(data) = self.storage.trace.get(&tree_key) {
-            read_value = data.last().unwrap().value.clone();
-        } else {
-            let read_db = account_tree.storage.hash(&path);
-            if let Some(value) = read_db {
-                read_value = u8_arr_to_tree_key(&value);
-            } else {
-                debug!("sload can not read data from addr:{:?}", tree_key);
-                read_value = tree_key_default();
-            }
-        }
+        let read_value = self.storage_value(account_tree, &tree_key);
 
         for index in 0..TREE_VALUE_LEN {
             let mem_addr = value_mem_addr + index as u64;
@@ -1310,7 +1698,7 @@ impl Process {
         }
 
         self.storage.read(
-            self.clk,
+            self.clk.get(),
             GoldilocksField::from_canonical_u64(1 << Opcode::SLOAD as u64),
             tree_key,
             tree_key_default(),
@@ -1326,7 +1714,7 @@ impl Process {
                 previous_value: tree_key_default(),
             });
 
-            program.trace.builtin_poseidon.push(hash_row);
+            program.trace.insert_poseidon(hash_row);
 
             let ext_cnt = GoldilocksField::ONE;
             let filter_tape_looking = GoldilocksField::ZERO;
@@ -1371,6 +1759,197 @@ impl Process {
         Ok(())
     }
 
+    /// `sscan dst key out` reads up to K slots of the contract from the slot
+    /// list of a prefix, K being the value of `dst`. Memory at `key` holds
+    /// the 3 word prefix, a last word and a flag: flag 0 starts at the head
+    /// of the list, ignoring the last word, flag 1 right after the slot the
+    /// 4 words name. Lists hold every slot `sstore` ever wrote under the
+    /// prefix, newest first, cleared slots reading 0. The key and value of
+    /// slot `i` are written as 8 words to memory at `out + 8 * i` and the
+    /// number of slots read to `dst`.
+    ///
+    /// The first ext line reads the key and flag, the second the list record
+    /// the walk starts from. Then each slot takes a storage ext line proving
+    /// its value like `sload` does and a line reading its link to the next
+    /// slot. The list records are storage under the tree root, so the walk
+    /// is a provable enumeration: it stops at the end of the list or after K
+    /// slots, and no listed slot in between can be skipped.
+    fn execute_inst_sscan(
+        &mut self,
+        program: &mut Program,
+        account_tree: &AccountTree,
+        aux_steps: &mut Vec<Step>,
+        ops: &[&str],
+        step: u64,
+        ctx_regs_status: &Address,
+        ctx_code_regs_status: &Address,
+    ) -> Result<(), ProcessorError> {
+        self.opcode = GoldilocksField::from_canonical_u64(1 << Opcode::SSCAN as u8);
+        let dst_index = self.get_reg_index(ops[1]);
+        let op0_index = self.get_reg_index(ops[2]);
+        let out = self.get_index_value(ops[3]);
+
+        self.register_selector.op0 = self.registers[op0_index];
+        self.register_selector.op1 = out.0;
+        self.register_selector.aux0 = self.registers[dst_index];
+        self.register_selector.op0_reg_sel[op0_index] = GoldilocksField::from_canonical_u64(1);
+        if let ImmediateOrRegName::RegName(op1_index) = out.1 {
+            self.register_selector.op1_reg_sel[op1_index] = GoldilocksField::from_canonical_u64(1);
+        }
+
+        let limit = self.registers[dst_index].to_canonical_u64();
+        if limit > SSCAN_MAX_SLOTS {
+            return Err(ProcessorError::LimitExceeded(
+                "sscan slots".to_string(),
+                SSCAN_MAX_SLOTS,
+            ));
+        }
+
+        let key_mem_addr = self.registers[op0_index].to_canonical_u64();
+        let out_mem_addr = out.0.to_canonical_u64();
+        let mut start_key = [GoldilocksField::ZERO; 4];
+        let mut head_selector = RegisterSelector::default();
+        for index in 0..TREE_VALUE_LEN {
+            let mem_addr = key_mem_addr + index as u64;
+            memory_op!(self, mem_addr, start_key[index], Opcode::SSCAN);
+            head_selector.op0_reg_sel[index] = GoldilocksField::from_canonical_u64(mem_addr);
+            head_selector.op0_reg_sel[TREE_VALUE_LEN + index] = start_key[index];
+        }
+        let flag_mem_addr = key_mem_addr + TREE_VALUE_LEN as u64;
+        let flag;
+        memory_op!(self, flag_mem_addr, flag, Opcode::SSCAN);
+        head_selector.op1_reg_sel[0] = GoldilocksField::from_canonical_u64(flag_mem_addr);
+        head_selector.op1_reg_sel[TREE_VALUE_LEN] = flag;
+        if !flag.is_zero() && !flag.is_one() {
+            return Err(ProcessorError::SscanFlagInvalid(flag.to_canonical_u64()));
+        }
+
+        let head_storage_idx = self.storage_access_idx;
+        let mut lines = vec![head_selector];
+        let (mut record, record_line) = if flag.is_zero() {
+            let head_key = slot_head_key(&start_key);
+            self.access_slot_record(program, account_tree, head_key, SLOT_HEAD_DOMAIN, None)
+        } else {
+            self.access_slot_record(program, account_tree, start_key, SLOT_LINK_DOMAIN, None)
+        };
+        lines.push(record_line);
+
+        let mut count = 0;
+        while count < limit && record[2].is_one() {
+            let mut slot_key = start_key;
+            slot_key[TREE_VALUE_LEN - 1] = record[1];
+            let storage_key = StorageKey::new(AccountTreeId::new(self.addr_storage), slot_key);
+            let (tree_key, hash_row) = storage_key.hashed_key();
+            self.storage.note_slot(tree_key, storage_key);
+            let read_value = self.storage_value(account_tree, &tree_key);
+
+            let mut register_selector_regs = RegisterSelector::default();
+            register_selector_regs.dst_reg_sel[0..TREE_VALUE_LEN].clone_from_slice(&tree_key);
+            let base = out_mem_addr + count * 2 * TREE_VALUE_LEN as u64;
+            for index in 0..TREE_VALUE_LEN {
+                let key_addr = base + index as u64;
+                let value_addr = key_addr + TREE_VALUE_LEN as u64;
+                memory_op!(
+                    self,
+                    key_addr,
+                    slot_key[index],
+                    Opcode::SSCAN,
+                    return Err(ProcessorError::MemVistInv(key_addr))
+                );
+                memory_op!(
+                    self,
+                    value_addr,
+                    read_value[index],
+                    Opcode::SSCAN,
+                    return Err(ProcessorError::MemVistInv(value_addr))
+                );
+                register_selector_regs.op0_reg_sel[index] =
+                    GoldilocksField::from_canonical_u64(key_addr);
+                register_selector_regs.op0_reg_sel[TREE_VALUE_LEN + index] = slot_key[index];
+                register_selector_regs.op1_reg_sel[index] =
+                    GoldilocksField::from_canonical_u64(value_addr);
+                register_selector_regs.op1_reg_sel[TREE_VALUE_LEN + index] = read_value[index];
+            }
+
+            self.storage.read(
+                self.clk.get(),
+                GoldilocksField::from_canonical_u64(1 << Opcode::SSCAN as u64),
+                tree_key,
+                tree_key_default(),
+                read_value,
+                self.env_idx,
+            );
+            self.storage_access_idx += GoldilocksField::ONE;
+            if !program.pre_exe_flag {
+                self.storage_log.push(WitnessStorageLog {
+                    storage_log: StorageLog::new_read_log(tree_key, read_value),
+                    previous_value: tree_key_default(),
+                });
+                program.trace.insert_poseidon(hash_row);
+            }
+            lines.push(register_selector_regs);
+
+            let (link, link_line) =
+                self.access_slot_record(program, account_tree, slot_key, SLOT_LINK_DOMAIN, None);
+            record = link;
+            lines.push(link_line);
+            count += 1;
+        }
+
+        let count = GoldilocksField::from_canonical_u64(count);
+        self.registers[dst_index] = count;
+        self.register_selector.dst = count;
+        self.register_selector.dst_reg_sel[dst_index] = GoldilocksField::from_canonical_u64(1);
+
+        if !program.pre_exe_flag {
+            // K - count is range checked on the head line
+            program.trace.insert_rangecheck(
+                GoldilocksField::from_canonical_u64(limit) - count,
+                (
+                    GoldilocksField::ZERO,
+                    GoldilocksField::ONE,
+                    GoldilocksField::ZERO,
+                    GoldilocksField::ZERO,
+                    GoldilocksField::ZERO,
+                ),
+            );
+            for (index, mut register_selector_regs) in lines.into_iter().enumerate() {
+                register_selector_regs.op0 = self.register_selector.op0;
+                register_selector_regs.op1 = self.register_selector.op1;
+                register_selector_regs.dst = self.register_selector.dst;
+                register_selector_regs.aux0 = self.register_selector.aux0;
+                // the prefix is carried along the ext lines from the head
+                register_selector_regs.dst_reg_sel[TREE_VALUE_LEN..TREE_VALUE_LEN * 2 - 1]
+                    .clone_from_slice(&start_key[..TREE_VALUE_LEN - 1]);
+                let ext_cnt = GoldilocksField::from_canonical_u64(index as u64 + 1);
+                let filter_tape_looking = GoldilocksField::ZERO;
+                let ctx_regs_status = ctx_regs_status.clone();
+                let ctx_code_regs_status = ctx_code_regs_status.clone();
+                // ext lines hold the registers after the instruction, so the
+                // main line sees `dst` updated in its next row
+                let registers_status = self.registers;
+                aux_insert!(
+                    self,
+                    aux_steps,
+                    ctx_regs_status,
+                    ctx_code_regs_status,
+                    registers_status,
+                    register_selector_regs,
+                    ext_cnt,
+                    filter_tape_looking
+                );
+                // every line after the head is one storage access
+                aux_steps.last_mut().unwrap().storage_access_idx =
+                    head_storage_idx + GoldilocksField::from_canonical_usize(index);
+            }
+        }
+        self.pc += step;
+        Ok(())
+    }
+
+    /// `poseidon dst src len` hashes `len` words read from memory at `src`
+    /// and writes the 4 word digest to memory at `dst`. Only memory is
+    /// touched, registers other than the operands stay live.
     fn execute_inst_poseidon(
         &mut self,
         program: &mut Program,
@@ -1407,7 +1986,7 @@ impl Process {
         if !program.pre_exe_flag {
             program.trace.insert_poseidon_chunk(
                 self.env_idx,
-                self.clk,
+                self.clk.get(),
                 self.opcode,
                 self.register_selector.dst,
                 self.register_selector.op0,
@@ -1440,7 +2019,7 @@ impl Process {
                 hash_cap.clone_from_slice(&hash_pre[POSEIDON_INPUT_VALUE_LEN..]);
                 program.trace.insert_poseidon_chunk(
                     self.env_idx,
-                    self.clk,
+                    self.clk.get(),
                     self.opcode,
                     self.register_selector.dst,
                     GoldilocksField::from_canonical_u64(src_mem_addr + read_ptr - 8),
@@ -1452,7 +2031,7 @@ impl Process {
                     GoldilocksField::ONE,
                 );
                 hash_pre.clone_from_slice(&row.output);
-                program.trace.builtin_poseidon.push(row);
+                program.trace.insert_poseidon(row);
             }
 
             if read_ptr + 8 > input_len {
@@ -1481,7 +2060,7 @@ impl Process {
                 hash_cap.clone_from_slice(&hash_pre[POSEIDON_INPUT_VALUE_LEN..]);
                 program.trace.insert_poseidon_chunk(
                     self.env_idx,
-                    self.clk,
+                    self.clk.get(),
                     self.opcode,
                     self.register_selector.dst,
                     GoldilocksField::from_canonical_u64(src_mem_addr + read_ptr),
@@ -1492,7 +2071,7 @@ impl Process {
                     row.output,
                     GoldilocksField::ONE,
                 );
-                program.trace.builtin_poseidon.push(row);
+                program.trace.insert_poseidon(row);
             }
         }
 
@@ -1572,22 +2151,27 @@ impl Process {
         tape_copy!(self,
             let value = self.tape.read(
                 tape_addr,
-                self.clk,
+                self.clk.get(),
                 GoldilocksField::from_canonical_u64(1 << Opcode::TLOAD as u64),
                 GoldilocksField::ONE,
             )?,
-            self.memory.write(
-                mem_addr,
-                self.clk,
-                GoldilocksField::from_canonical_u64(1 << Opcode::TLOAD as u64),
-                GoldilocksField::from_canonical_u64(is_rw as u64),
-                GoldilocksField::from_canonical_u64(MemoryOperation::Write as u64),
-                GoldilocksField::from_canonical_u64(FilterLockForMain::True as u64),
-                region_prophet,
-                region_heap,
-                value,
-                self.env_idx
-            ), ctx_regs_status, ctx_code_regs_status, registers_status, zone_length,  mem_base_addr, tape_base_addr, aux_steps,
+            {
+                if self.in_code_region(mem_addr) {
+                    return Err(ProcessorError::CodeRegionWrite(mem_addr));
+                }
+                self.memory.write(
+                    mem_addr,
+                    self.clk.get(),
+                    GoldilocksField::from_canonical_u64(1 << Opcode::TLOAD as u64),
+                    GoldilocksField::from_canonical_u64(is_rw as u64),
+                    GoldilocksField::from_canonical_u64(MemoryOperation::Write as u64),
+                    GoldilocksField::from_canonical_u64(FilterLockForMain::True as u64),
+                    region_prophet,
+                    region_heap,
+                    value,
+                    self.env_idx
+                )
+            }, ctx_regs_status, ctx_code_regs_status, registers_status, zone_length,  mem_base_addr, tape_base_addr, aux_steps,
             mem_addr, tape_addr, is_rw, region_prophet, region_heap, value);
 
         self.pc += step;
@@ -1638,7 +2222,7 @@ impl Process {
         tape_copy!(self,
              let value = self.memory.read(
                 mem_addr,
-                 self.clk,
+                 self.clk.get(),
                 GoldilocksField::from_canonical_u64(1 << Opcode::TSTORE as u64),
                 GoldilocksField::from_canonical_u64(is_rw as u64),
                 GoldilocksField::from_canonical_u64(MemoryOperation::Read as u64),
@@ -1649,7 +2233,7 @@ impl Process {
             )?,
                 self.tape.write(
                 tape_addr,
-                self.clk,
+                self.clk.get(),
                 GoldilocksField::from_canonical_u64(1 << Opcode::TSTORE as u64),
                 GoldilocksField::ZERO,
                 GoldilocksField::ONE,
@@ -1661,6 +2245,125 @@ impl Process {
         Ok(())
     }
 
+    fn execute_inst_tload_t(&mut self, ops: &[&str], step: u64) {
+        let opcode = ops.first().unwrap().to_lowercase();
+        assert_eq!(
+            ops.len(),
+            3,
+            "{}",
+            format!("{} params len is 2", opcode.as_str())
+        );
+        self.opcode = GoldilocksField::from_canonical_u64(1 << Opcode::TLOADT as u8);
+        let dst_index = self.get_reg_index(ops[1]);
+        let key = self.get_index_value(ops[2]);
+        self.register_selector.op1 = key.0;
+        if let ImmediateOrRegName::RegName(op1_index) = key.1 {
+            self.register_selector.op1_reg_sel[op1_index] = GoldilocksField::from_canonical_u64(1);
+        }
+
+        self.registers[dst_index] =
+            self.transient
+                .read(&self.addr_storage, key.0, self.env_idx, self.clk.get());
+        self.register_selector.dst = self.registers[dst_index];
+        self.register_selector.dst_reg_sel[dst_index] = GoldilocksField::from_canonical_u64(1);
+
+        self.pc += step;
+    }
+
+    fn execute_inst_tstore_t(&mut self, ops: &[&str], step: u64) {
+        let opcode = ops.first().unwrap().to_lowercase();
+        assert_eq!(
+            ops.len(),
+            3,
+            "{}",
+            format!("{} params len is 2", opcode.as_str())
+        );
+        self.opcode = GoldilocksField::from_canonical_u64(1 << Opcode::TSTORET as u8);
+        let op0_index = self.get_reg_index(ops[1]);
+        let value = self.get_index_value(ops[2]);
+        self.register_selector.op0 = self.registers[op0_index];
+        self.register_selector.op0_reg_sel[op0_index] = GoldilocksField::from_canonical_u64(1);
+        self.register_selector.op1 = value.0;
+        if let ImmediateOrRegName::RegName(op1_index) = value.1 {
+            self.register_selector.op1_reg_sel[op1_index] = GoldilocksField::from_canonical_u64(1);
+        }
+
+        self.transient.write(
+            &self.addr_storage,
+            self.registers[op0_index],
+            self.env_idx,
+            self.clk.get(),
+            value.0,
+        );
+
+        self.pc += step;
+    }
+
+    /// `txctx dst sel` reads the chain id with selector 0 and word `sel - 1`
+    /// of the tx hash with selectors 1 to 4.
+    fn execute_inst_txctx(&mut self, ops: &[&str], step: u64) -> Result<(), ProcessorError> {
+        let opcode = ops.first().unwrap().to_lowercase();
+        assert_eq!(
+            ops.len(),
+            3,
+            "{}",
+            format!("{} params len is 2", opcode.as_str())
+        );
+        self.opcode = GoldilocksField::from_canonical_u64(1 << Opcode::TXCTX as u8);
+        let dst_index = self.get_reg_index(ops[1]);
+        let selector = self.get_index_value(ops[2]);
+        self.register_selector.op1 = selector.0;
+        if let ImmediateOrRegName::RegName(op1_index) = selector.1 {
+            self.register_selector.op1_reg_sel[op1_index] = GoldilocksField::from_canonical_u64(1);
+        }
+
+        self.registers[dst_index] = match selector.0.to_canonical_u64() {
+            0 => self.chain_id,
+            sel @ 1..=4 => self.tx_hash[sel as usize - 1],
+            sel => return Err(ProcessorError::TxCtxSelectorInvalid(sel)),
+        };
+        self.register_selector.dst = self.registers[dst_index];
+        self.register_selector.dst_reg_sel[dst_index] = GoldilocksField::from_canonical_u64(1);
+
+        self.pc += step;
+        Ok(())
+    }
+
+    /// `cmov dst cond op1` writes op1 to dst when cond is 1 and keeps dst when
+    /// it is 0, aux0 holds the old dst for the select constraint.
+    fn execute_inst_cmov(&mut self, ops: &[&str], step: u64) -> Result<(), ProcessorError> {
+        let opcode = ops.first().unwrap().to_lowercase();
+        assert_eq!(
+            ops.len(),
+            4,
+            "{}",
+            format!("{} params len is 3", opcode.as_str())
+        );
+        self.opcode = GoldilocksField::from_canonical_u64(1 << Opcode::CMOV as u8);
+        let dst_index = self.get_reg_index(ops[1]);
+        let op0_index = self.get_reg_index(ops[2]);
+        let value = self.get_index_value(ops[3]);
+
+        self.register_selector.op0 = self.registers[op0_index];
+        self.register_selector.op1 = value.0;
+        self.register_selector.op0_reg_sel[op0_index] = GoldilocksField::from_canonical_u64(1);
+        if let ImmediateOrRegName::RegName(op1_index) = value.1 {
+            self.register_selector.op1_reg_sel[op1_index] = GoldilocksField::from_canonical_u64(1);
+        }
+        self.register_selector.aux0 = self.registers[dst_index];
+
+        match self.registers[op0_index].to_canonical_u64() {
+            0 => {}
+            1 => self.registers[dst_index] = value.0,
+            cond => return Err(ProcessorError::CmovConditionInvalid(cond)),
+        }
+        self.register_selector.dst = self.registers[dst_index];
+        self.register_selector.dst_reg_sel[dst_index] = GoldilocksField::from_canonical_u64(1);
+
+        self.pc += step;
+        Ok(())
+    }
+
     fn execute_inst_sccall(
         &mut self,
         program: &mut Program,
@@ -1727,15 +2430,15 @@ impl Process {
                 self.addr_storage,
                 self.addr_code,
                 self.register_selector.op1,
-                GoldilocksField::from_canonical_u64(self.clk as u64),
-                GoldilocksField::from_canonical_u64((self.clk + 1) as u64),
+                self.clk.to_field(),
+                self.clk.next()?.to_field(),
                 registers_status.clone(),
                 self.register_selector.aux0,
                 GoldilocksField::ZERO,
             );
 
             program.trace.insert_step(
-                self.clk,
+                self.clk.get(),
                 pc_status,
                 self.tp,
                 self.instruction,
@@ -1760,7 +2463,7 @@ impl Process {
             register_selector_regs.op0_reg_sel[TREE_VALUE_LEN..TREE_VALUE_LEN * 2]
                 .clone_from_slice(ctx_code_regs_status);
             program.trace.insert_step(
-                self.clk,
+                self.clk.get(),
                 pc_status,
                 self.tp,
                 self.instruction,
@@ -1781,7 +2484,7 @@ impl Process {
         }
 
         self.pc += step;
-        self.clk += 1;
+        self.clk = self.clk.checked_next(self.max_clk())?;
         if op1_value.0 == GoldilocksField::ONE {
             return Ok(VMState::SCCall(SCCallType::DelegateCall(callee_address)));
         } else if op1_value.0 == GoldilocksField::ZERO {
@@ -1791,11 +2494,199 @@ impl Process {
         }
     }
 
+    /// Run `callback` every `n` steps, before the step executes, so a host
+    /// embedding the executor can service timeouts during long executions.
+    /// The callback sees the whole process, so it can persist a snapshot, and
+    /// may block to pause the execution or return `YieldAction::Abort` to stop
+    /// it with `ProcessorError::Interrupted`. Processes of called contracts
+    /// don't inherit it.
+    pub fn yield_every_n_steps(
+        &mut self,
+        n: u32,
+        callback: impl FnMut(&Process) -> YieldAction + Send + 'static,
+    ) {
+        assert!(n > 0, "yield interval must not be zero");
+        self.yield_hook = Some(YieldHook {
+            interval: n,
+            callback: Box::new(callback),
+        });
+    }
+
+    /// Execute under the rules `spec` sets for block `height`: opcodes it
+    /// disables fail with `ProcessorError::OpcodeDisabled`, execution stops
+    /// at the cycle limit of a block, programs over its size limit fail with
+    /// `ProcessorError::ProgramTooLarge` and the trace carries the fork id.
+    pub fn apply_chain_spec(&mut self, spec: &ChainSpec, height: u64) {
+        self.disabled_opcodes = spec
+            .disabled_opcodes(height)
+            .iter()
+            .map(|opcode| opcode.token())
+            .collect();
+        self.fork_id = spec.fork_id(height);
+        self.max_program_words = spec.program_words_limit();
+        let max_cycles = spec.max_cycles.min(u32::MAX as usize) as u32;
+        self.step_limit = Some(
+            self.step_limit
+                .map_or(max_cycles, |limit| limit.min(max_cycles)),
+        );
+    }
+
+    /// A process for the next block of a sequencer loop, after this one ran
+    /// a block. It keeps the configuration, the hooks and the code mapped by
+    /// `map_code`, takes the chain spec rules of `height` and starts with
+    /// fresh registers, memory, logs and tx context. The account tree is not
+    /// owned by the process, pass the same one to `execute` again.
+    pub fn next_block(&mut self, spec: &ChainSpec, height: u64) -> Result<Self, ProcessorError> {
+        let mut next = Process::new();
+        next.step_limit = self.step_limit;
+        next.gas_schedule = self.gas_schedule.clone();
+        next.gas_limit = self.gas_limit;
+        next.yield_hook = self.yield_hook.take();
+        next.record_prophets = self.record_prophets;
+        next.chain_id = self.chain_id;
+        next.repro_dir = self.repro_dir.clone();
+        next.apply_chain_spec(spec, height);
+
+        if let Some(region) = self.code_region.clone() {
+            let words: Vec<GoldilocksField> = region
+                .clone()
+                .map(|addr| {
+                    self.memory
+                        .trace
+                        .get(&addr)
+                        .and_then(|cells| cells.last())
+                        .map_or(GoldilocksField::ZERO, |cell| cell.value)
+                })
+                .collect();
+            next.map_code(region.start, &words)?;
+        }
+        Ok(next)
+    }
+
+    /// Charges the gas of a step of `opcode`, failing before the step if it
+    /// takes the gas used over the gas limit.
+    fn charge_gas(&mut self, opcode: &str) -> Result<(), ProcessorError> {
+        let used = self.gas_used.saturating_add(self.gas_schedule.cost(opcode));
+        if let Some(limit) = self.gas_limit {
+            if used > limit {
+                return Err(ProcessorError::OutOfGas(used, limit));
+            }
+        }
+        self.gas_used = used;
+        Ok(())
+    }
+
+    /// Clk the execution may reach, the step limit if set.
+    fn max_clk(&self) -> u32 {
+        self.step_limit.unwrap_or(MAX_CLK)
+    }
+
+    fn run_yield_hook(&mut self) -> Result<(), ProcessorError> {
+        let mut hook = match self.yield_hook.take() {
+            Some(hook) if self.clk.get() > 0 && self.clk.get() % hook.interval == 0 => hook,
+            hook => {
+                self.yield_hook = hook;
+                return Ok(());
+            }
+        };
+        let action = (hook.callback)(self);
+        self.yield_hook = Some(hook);
+        match action {
+            YieldAction::Continue => Ok(()),
+            YieldAction::Abort => Err(ProcessorError::Interrupted(self.clk.get())),
+        }
+    }
+
+    pub fn view<'a>(&'a self, asm: &'a str) -> ProcessView<'a> {
+        ProcessView {
+            clk: self.clk.get(),
+            pc: self.pc,
+            tp: self.tp,
+            env_idx: self.env_idx,
+            asm,
+            registers: &self.registers,
+        }
+    }
+
+    fn finish_step<H: Hooks>(&mut self, hooks: &mut H, asm: &str) {
+        if H::OBSERVES_MEMORY {
+            if let Some(accesses) = self.memory.accesses.as_mut() {
+                for (addr, cell) in accesses.drain(..) {
+                    hooks.on_memory_access(addr, &cell);
+                }
+            }
+        }
+        hooks.after_step(&self.view(asm));
+    }
+
     pub fn execute(
         &mut self,
         program: &mut Program,
         account_tree: &mut AccountTree,
     ) -> Result<VMState, ProcessorError> {
+        self.execute_with_hooks(program, account_tree, &mut NoHooks)
+    }
+
+    pub fn execute_with_hooks<H: Hooks>(
+        &mut self,
+        program: &mut Program,
+        account_tree: &mut AccountTree,
+        hooks: &mut H,
+    ) -> Result<VMState, ProcessorError> {
+        self.execute_with_repro(program, account_tree, hooks, None)
+    }
+
+    /// Like `execute`, but hands the cpu, memory and rangecheck rows to `sink`
+    /// every `TraceSink::chunk_rows` steps instead of keeping them in
+    /// `program.trace`. Memory rows are only known once execution ends and
+    /// go with the last chunk. The other tables stay in `program.trace`,
+    /// `ChunkedTraceReader::assemble` puts the trace back together.
+    pub fn execute_with_sink(
+        &mut self,
+        program: &mut Program,
+        account_tree: &mut AccountTree,
+        sink: &mut impl TraceSink,
+    ) -> Result<VMState, ProcessorError> {
+        let state =
+            self.execute_with_repro(program, account_tree, &mut NoHooks, Some(&mut *sink))?;
+        sink.write_chunk(&TraceChunk::take(&mut program.trace))?;
+        Ok(state)
+    }
+
+    fn execute_with_repro<H: Hooks>(
+        &mut self,
+        program: &mut Program,
+        account_tree: &mut AccountTree,
+        hooks: &mut H,
+        sink: Option<&mut dyn TraceSink>,
+    ) -> Result<VMState, ProcessorError> {
+        let bundle = self
+            .repro_dir
+            .as_ref()
+            .map(|_| ReproBundle::capture(self, program));
+        let res = self.execute_read_only(program, account_tree, hooks, sink);
+        if let (Err(err), Some(mut bundle)) = (&res, bundle) {
+            bundle.finish(self, program, account_tree, err);
+            let dir = self.repro_dir.as_ref().unwrap();
+            match bundle.write(dir) {
+                Ok(path) => info!("repro bundle written to {}", path.display()),
+                Err(e) => warn!("write repro bundle to {}: {}", dir.display(), e),
+            }
+        }
+        res
+    }
+
+    /// Execution only ever reads the account tree, storage writes stay in
+    /// `self.storage` until the storage tables are generated.
+    pub(crate) fn execute_read_only<H: Hooks>(
+        &mut self,
+        program: &mut Program,
+        account_tree: &AccountTree,
+        hooks: &mut H,
+        mut sink: Option<&mut dyn TraceSink>,
+    ) -> Result<VMState, ProcessorError> {
+        check_program_size(program.instructions.len(), self.max_program_words)?;
+        check_return_arity(program.return_arity)?;
         let instrs_len = program.instructions.len() as u64;
         // program.trace.raw_binary_instructions.clear();
         let start = Instant::now();
@@ -1804,19 +2695,21 @@ impl Process {
             while pc < instrs_len {
                 pc = self.execute_decode(program, pc, instrs_len).unwrap();
             }
-            // init heap ptr
-            self.memory.write(
-                HP_START_ADDR,
-                0, //write， clk is 0
-                GoldilocksField::from_canonical_u64(0 as u64),
-                GoldilocksField::from_canonical_u64(MemoryType::ReadWrite as u64),
-                GoldilocksField::from_canonical_u64(MemoryOperation::Write as u64),
-                GoldilocksField::from_canonical_u64(FilterLockForMain::False as u64),
-                GoldilocksField::from_canonical_u64(0_u64),
-                GoldilocksField::from_canonical_u64(1_u64),
-                GoldilocksField(HP_START_ADDR + 1),
-                self.env_idx,
-            );
+            // init heap ptr, unless a memory image set it
+            if !self.memory.trace.contains_key(&HP_START_ADDR) {
+                self.memory.write(
+                    HP_START_ADDR,
+                    0, //write， clk is 0
+                    GoldilocksField::from_canonical_u64(0 as u64),
+                    GoldilocksField::from_canonical_u64(MemoryType::ReadWrite as u64),
+                    GoldilocksField::from_canonical_u64(MemoryOperation::Write as u64),
+                    GoldilocksField::from_canonical_u64(FilterLockForMain::False as u64),
+                    GoldilocksField::from_canonical_u64(0_u64),
+                    GoldilocksField::from_canonical_u64(1_u64),
+                    GoldilocksField(HP_START_ADDR + 1),
+                    self.env_idx,
+                );
+            }
         }
         let decode_time = start.elapsed();
         debug!("decode_time: {}", decode_time.as_secs());
@@ -1831,7 +2724,7 @@ impl Process {
         // todo : why need clear?
         //self.storage_log.clear();
         let mut end_step = None;
-        let mut prog_hash_rows = calculate_arbitrary_poseidon_and_generate_intermediate_trace(
+        let prog_hash_rows = calculate_arbitrary_poseidon_and_generate_intermediate_trace(
             program
                 .instructions
                 .iter()
@@ -1844,12 +2737,24 @@ impl Process {
                 .as_slice(),
         )
         .1;
-        for row in &mut prog_hash_rows {
+        for mut row in prog_hash_rows {
             row.filter_looked_normal = true;
+            program.trace.insert_poseidon(row);
         }
-        program.trace.builtin_poseidon.extend(prog_hash_rows);
 
+        if H::OBSERVES_MEMORY {
+            self.memory.accesses.get_or_insert_with(Vec::new).clear();
+        }
         loop {
+            if let Some(limit) = self.step_limit {
+                if self.clk.get() >= limit {
+                    return Err(ProcessorError::LimitExceeded(
+                        "steps".to_string(),
+                        limit as u64,
+                    ));
+                }
+            }
+            self.run_yield_hook()?;
             self.register_selector = RegisterSelector::default();
             let registers_status = self.registers;
             let ctx_regs_status = self.addr_storage.clone();
@@ -1883,6 +2788,8 @@ impl Process {
                 self.print_vm_state(&instruction.0);
             }
 
+            hooks.before_step(&self.view(&instruction.0));
+
             let ops: Vec<&str> = instruction.0.split_whitespace().collect();
             let opcode = ops.first().unwrap().to_lowercase();
             self.op1_imm = GoldilocksField::from_canonical_u64(instruction.1 as u64);
@@ -1890,6 +2797,10 @@ impl Process {
             self.instruction = instruction.3;
             self.immediate_data = instruction.4;
             debug!("execute opcode: {:?}", ops);
+            if self.disabled_opcodes.contains(&opcode) {
+                return Err(ProcessorError::OpcodeDisabled(opcode));
+            }
+            self.charge_gas(&opcode)?;
             match opcode.as_str() {
                 //todo: not need move to arithmatic library
                 "mov" | "not" => self.execute_inst_mov_not(&ops, step),
@@ -1913,10 +2824,12 @@ impl Process {
                         &registers_status,
                         &ctx_code_regs_status,
                     )?;
+                    self.finish_step(hooks, &instruction.0);
                     break;
                 }
                 "sstore" => self.execute_inst_sstore(
                     program,
+                    account_tree,
                     &mut aux_steps,
                     &ops,
                     step,
@@ -1934,6 +2847,15 @@ impl Process {
                     &registers_status,
                     &ctx_code_regs_status,
                 )?,
+                "sscan" => self.execute_inst_sscan(
+                    program,
+                    account_tree,
+                    &mut aux_steps,
+                    &ops,
+                    step,
+                    &ctx_regs_status,
+                    &ctx_code_regs_status,
+                )?,
                 "poseidon" => self.execute_inst_poseidon(program, &ops, step)?,
                 "tload" => self.execute_inst_tload(
                     program,
@@ -1952,8 +2874,12 @@ impl Process {
                     &registers_status,
                     &ctx_code_regs_status,
                 )?,
+                "tload_t" => self.execute_inst_tload_t(&ops, step),
+                "tstore_t" => self.execute_inst_tstore_t(&ops, step),
+                "txctx" => self.execute_inst_txctx(&ops, step)?,
+                "cmov" => self.execute_inst_cmov(&ops, step)?,
                 "sccall" => {
-                    return self.execute_inst_sccall(
+                    let state = self.execute_inst_sccall(
                         program,
                         &ops,
                         step,
@@ -1961,14 +2887,22 @@ impl Process {
                         &ctx_regs_status,
                         &registers_status,
                         &ctx_code_regs_status,
-                    )
+                    );
+                    self.finish_step(hooks, &instruction.0);
+                    return state;
                 }
                 _ => panic!("not match opcode:{}", opcode),
             }
 
-            if program.prophets.get(&pc_status).is_some() {
-                self.prophet(&mut program.prophets[&pc_status].clone())?
+            if let Some(mut prophet) = program.prophets.get(&pc_status).cloned() {
+                hooks.before_prophet(&self.view(&instruction.0));
+                let record = self.prophet(&mut prophet)?;
+                hooks.after_prophet(&self.view(&instruction.0));
+                if self.record_prophets {
+                    program.trace.prophet_records.push(record);
+                }
             }
+            self.finish_step(hooks, &instruction.0);
 
             if program.print_flag {
                 println!("↑↑↑↑↑↑↑↑↑↑↑↑↑↑↑↑↑↑↑↑↑↑↑↑ end step ↑↑↑↑↑↑↑↑↑↑↑↑↑↑↑↑↑↑↑↑↑↑↑↑");
@@ -1982,7 +2916,7 @@ impl Process {
 
             if !program.pre_exe_flag {
                 program.trace.insert_step(
-                    self.clk,
+                    self.clk.get(),
                     pc_status,
                     tp_status,
                     self.instruction,
@@ -2002,23 +2936,34 @@ impl Process {
                 );
 
                 if !aux_steps.is_empty() {
-                    program.trace.exec.extend(aux_steps);
+                    program.trace.insert_steps(aux_steps);
+                }
+                if let Some(sink) = &mut sink {
+                    if program.trace.exec.len() >= sink.chunk_rows() {
+                        sink.write_chunk(&TraceChunk::take(&mut program.trace))?;
+                    }
                 }
             }
             if self.pc >= instrs_len {
                 break;
             }
 
-            self.clk += 1;
-            if self.clk % 1000000 == 0 {
+            self.clk = self.clk.checked_next(self.max_clk())?;
+            if self.clk.get() % 1000000 == 0 {
                 let decode_time = start.elapsed();
                 debug!("100000_step_time: {}", decode_time.as_millis());
                 start = Instant::now();
             }
         }
 
+        program.trace.prophet_commitment = self.prophet_commitment;
+        program.trace.fork_id = self.fork_id;
+        program.trace.chain_id = self.chain_id;
+        program.trace.tx_hash = self.tx_hash;
+        program.trace.gas_used = self.gas_used;
         gen_memory_table(self, program)?;
         gen_tape_table(self, program)?;
+        gen_transient_table(self, program);
         Ok(ExeEnd(end_step))
     }
 }
@@ -1,5 +1,7 @@
 use crate::Process;
 use byteorder::{LittleEndian, ReadBytesExt};
+use core::abi::{AbiValue, FunctionSignature};
+use core::vm::error::ProcessorError;
 use core::vm::transaction::TxCtxInfo;
 use core::vm::vm_state::Address;
 
@@ -21,6 +23,16 @@ pub fn load_tx_calldata(process: &mut Process, calldate: &Vec<GoldilocksField>)
     }
 }
 
+/// Calldata calling `signature` with `args`, encoded with the ABI of
+/// `core::abi`.
+pub fn encode_call(
+    signature: &str,
+    args: &[AbiValue],
+) -> Result<Vec<GoldilocksField>, ProcessorError> {
+    let signature: FunctionSignature = signature.parse()?;
+    Ok(signature.encode_call(args)?)
+}
+
 #[derive(Clone, Debug, Serialize, Deserialize, Default)]
 pub struct CtxAddrInfo {
     pub caller_exe_addr: Address,
@@ -76,6 +88,8 @@ pub fn init_tape(
     callee_exe_addr: Address,
     ctx_info: &TxCtxInfo,
 ) {
+    process.chain_id = ctx_info.chain_id;
+    process.tx_hash = ctx_info.tx_hash;
     let tp_start = load_tx_context(process, ctx_info);
     process.tp = GoldilocksField::from_canonical_u64(tp_start as u64);
     load_tx_calldata(process, &calldata);
@@ -0,0 +1,120 @@
+use crate::hooks::{Hooks, ProcessView};
+use core::trace::trace::{MemoryOperation, Trace};
+use core::vm::error::ProcessorError;
+use core::vm::memory::{MemoryCell, PSP_START_ADDR};
+use plonky2::field::goldilocks_field::GoldilocksField;
+use plonky2::field::types::{Field, PrimeField64};
+use std::collections::{HashMap, HashSet};
+
+/// A read of a write once (prophet) cell that no write filled, which points
+/// at a prophet hand-off the compiler got wrong.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct UnwrittenRead {
+    pub env_idx: u64,
+    pub addr: u64,
+    pub clk: u32,
+    /// Pc of the `mload`, `None` if the trace has no cpu step at `clk`.
+    pub pc: Option<u64>,
+}
+
+/// Hooks collecting reads of write once cells that were never written.
+///
+/// Reading an unwritten cell makes the process fail with `MemVistInv`, so the
+/// run stops at the first one; hand the error to `record_error` to attribute
+/// it to the `mload` that caused it. Use `unwritten_write_once_reads` to list
+/// every such read of a generated trace.
+#[derive(Debug, Default)]
+pub struct WriteOnceAudit {
+    pub reads: Vec<UnwrittenRead>,
+    written: HashSet<(u64, u64)>,
+    step: Option<(u64, u32, u64, String)>,
+}
+
+impl WriteOnceAudit {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// Record the failing read if `err` is an unwritten write once cell read
+    /// by the `mload` of the last step.
+    pub fn record_error(&mut self, err: &ProcessorError) {
+        let addr = match err {
+            ProcessorError::MemVistInv(addr) if *addr >= PSP_START_ADDR => *addr,
+            _ => return,
+        };
+        if let Some((pc, clk, env_idx, asm)) = &self.step {
+            if asm.trim_start().starts_with("mload") {
+                self.reads.push(UnwrittenRead {
+                    env_idx: *env_idx,
+                    addr,
+                    clk: *clk,
+                    pc: Some(*pc),
+                });
+            }
+        }
+    }
+}
+
+impl Hooks for WriteOnceAudit {
+    const OBSERVES_MEMORY: bool = true;
+
+    fn before_step(&mut self, view: &ProcessView) {
+        self.step = Some((
+            view.pc,
+            view.clk,
+            view.env_idx.to_canonical_u64(),
+            view.asm.to_string(),
+        ));
+    }
+
+    fn on_memory_access(&mut self, addr: u64, cell: &MemoryCell) {
+        if !cell.region_prophet.is_one() {
+            return;
+        }
+        let key = (cell.env_idx.to_canonical_u64(), addr);
+        if cell.is_write == op_write() {
+            self.written.insert(key);
+        } else if !self.written.contains(&key) {
+            self.reads.push(UnwrittenRead {
+                env_idx: key.0,
+                addr,
+                clk: cell.clk,
+                pc: self.step.as_ref().map(|step| step.0),
+            });
+        }
+    }
+}
+
+/// List every read of a write once cell in `trace.memory` that no earlier
+/// write of the same environment filled, with the pc of the reading step.
+pub fn unwritten_write_once_reads(trace: &Trace) -> Vec<UnwrittenRead> {
+    let pcs: HashMap<(u64, u32), u64> = trace
+        .exec
+        .iter()
+        .map(|step| ((step.env_idx.to_canonical_u64(), step.clk), step.pc))
+        .collect();
+    let mut written = HashSet::new();
+    let mut reads = Vec::new();
+    for cell in trace.memory.iter().filter(|c| c.region_prophet.is_one()) {
+        let key = (
+            cell.env_idx.to_canonical_u64(),
+            cell.addr.to_canonical_u64(),
+        );
+        if cell.is_write == op_write() {
+            written.insert(key);
+        } else if !written.contains(&key) {
+            let clk = cell.clk.to_canonical_u64() as u32;
+            reads.push(UnwrittenRead {
+                env_idx: key.0,
+                addr: key.1,
+                clk,
+                pc: pcs.get(&(key.0, clk)).copied(),
+            });
+        }
+    }
+    reads
+}
+
+fn op_write() -> GoldilocksField {
+    GoldilocksField::from_canonical_u64(MemoryOperation::Write as u64)
+}
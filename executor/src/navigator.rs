@@ -0,0 +1,170 @@
+use core::program::REGISTER_NUM;
+use core::trace::trace::{Step, Trace};
+use core::vm::memory::MemoryTree;
+use plonky2::field::goldilocks_field::GoldilocksField;
+use plonky2::field::types::{Field, PrimeField64};
+use std::collections::{BTreeMap, HashMap};
+
+/// Machine state right before the instruction at `clk` executes.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct MachineState {
+    pub env_idx: u64,
+    pub clk: u32,
+    pub pc: u64,
+    pub registers: [GoldilocksField; REGISTER_NUM],
+    pub memory: BTreeMap<u64, GoldilocksField>,
+}
+
+/// Moves back and forth over a recorded run.
+///
+/// Every main cpu row already holds the registers the instruction starts from,
+/// so registers need no replay. Memory is the last write before the cursor clk
+/// of each address, looked up in the write history.
+pub struct TraceNavigator<'a> {
+    steps: Vec<&'a Step>,
+    // (env_idx, addr) to the (clk, value) writes in clk order.
+    writes: HashMap<(u64, u64), Vec<(u32, GoldilocksField)>>,
+    cursor: usize,
+}
+
+impl<'a> TraceNavigator<'a> {
+    fn with_writes(
+        trace: &'a Trace,
+        writes: HashMap<(u64, u64), Vec<(u32, GoldilocksField)>>,
+    ) -> Self {
+        let steps = trace
+            .exec
+            .iter()
+            .filter(|step| step.is_ext_line.is_zero())
+            .collect();
+        Self {
+            steps,
+            writes,
+            cursor: 0,
+        }
+    }
+
+    /// Navigator over a finished run, memory is read from the memory table.
+    pub fn new(trace: &'a Trace) -> Self {
+        let mut writes: HashMap<(u64, u64), Vec<(u32, GoldilocksField)>> = HashMap::new();
+        for cell in trace.memory.iter().filter(|cell| cell.is_write.is_one()) {
+            writes
+                .entry((
+                    cell.env_idx.to_canonical_u64(),
+                    cell.addr.to_canonical_u64(),
+                ))
+                .or_default()
+                .push((cell.clk.to_canonical_u64() as u32, cell.value));
+        }
+        for history in writes.values_mut() {
+            history.sort_by_key(|(clk, _)| *clk);
+        }
+        Self::with_writes(trace, writes)
+    }
+
+    /// Navigator over a run that stopped early, e.g. on an error, before the
+    /// memory table was generated. Memory is read from the process memory.
+    pub fn from_memory(trace: &'a Trace, memory: &MemoryTree) -> Self {
+        let mut writes: HashMap<(u64, u64), Vec<(u32, GoldilocksField)>> = HashMap::new();
+        for (addr, cells) in memory.trace.iter() {
+            for cell in cells.iter().filter(|cell| cell.is_write.is_one()) {
+                writes
+                    .entry((cell.env_idx.to_canonical_u64(), *addr))
+                    .or_default()
+                    .push((cell.clk, cell.value));
+            }
+        }
+        for history in writes.values_mut() {
+            history.sort_by_key(|(clk, _)| *clk);
+        }
+        Self::with_writes(trace, writes)
+    }
+
+    pub fn len(&self) -> usize {
+        self.steps.len()
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.steps.is_empty()
+    }
+
+    pub fn position(&self) -> usize {
+        self.cursor
+    }
+
+    pub fn current(&self) -> Option<&'a Step> {
+        self.steps.get(self.cursor).copied()
+    }
+
+    pub fn step_forward(&mut self) -> bool {
+        if self.cursor + 1 >= self.steps.len() {
+            return false;
+        }
+        self.cursor += 1;
+        true
+    }
+
+    pub fn step_back(&mut self) -> bool {
+        if self.cursor == 0 {
+            return false;
+        }
+        self.cursor -= 1;
+        true
+    }
+
+    /// Move to the main row at `clk` in the environment of the current row.
+    pub fn seek_clk(&mut self, clk: u32) -> bool {
+        let env_idx = match self.current() {
+            Some(step) => step.env_idx,
+            None => return false,
+        };
+        match self
+            .steps
+            .iter()
+            .position(|step| step.env_idx == env_idx && step.clk == clk)
+        {
+            Some(position) => {
+                self.cursor = position;
+                true
+            }
+            None => false,
+        }
+    }
+
+    pub fn seek_last(&mut self) {
+        self.cursor = self.steps.len().saturating_sub(1);
+    }
+
+    /// Value of `addr` right before the current instruction executes.
+    pub fn memory_at(&self, addr: u64) -> Option<GoldilocksField> {
+        let step = self.current()?;
+        self.last_write_before(step.env_idx.to_canonical_u64(), addr, step.clk)
+    }
+
+    fn last_write_before(&self, env_idx: u64, addr: u64, clk: u32) -> Option<GoldilocksField> {
+        let history = self.writes.get(&(env_idx, addr))?;
+        let written = history.partition_point(|(write_clk, _)| *write_clk < clk);
+        written.checked_sub(1).map(|i| history[i].1)
+    }
+
+    pub fn state(&self) -> Option<MachineState> {
+        let step = self.current()?;
+        let env_idx = step.env_idx.to_canonical_u64();
+        let memory = self
+            .writes
+            .keys()
+            .filter(|(env, _)| *env == env_idx)
+            .filter_map(|&(_, addr)| {
+                self.last_write_before(env_idx, addr, step.clk)
+                    .map(|value| (addr, value))
+            })
+            .collect();
+        Some(MachineState {
+            env_idx,
+            clk: step.clk,
+            pc: step.pc,
+            registers: step.regs,
+            memory,
+        })
+    }
+}
@@ -0,0 +1,148 @@
+use crate::cost_model::{immediate, instruction_cost, POSEIDON_RATE};
+use core::program::binary_program::{BinaryInstruction, BinaryProgram};
+use core::program::decoder::decode_binary_program_to_instructions;
+use core::types::merkle_tree::constant::ROOT_TREE_DEPTH;
+use core::vm::error::ProcessorError;
+use core::vm::opcodes::OlaOpcode;
+use std::collections::HashMap;
+
+#[derive(Debug, Clone, Default)]
+pub struct PreflightConfig {
+    pub calldata_len: usize,
+    /// Iteration count of loops, keyed by the pc of the backward `jmp`/`cjmp`
+    /// closing the loop. Unannotated loops are counted as one iteration.
+    pub loop_bounds: HashMap<u64, u64>,
+    /// Input length assumed for `poseidon` when it is not an immediate.
+    pub default_poseidon_len: usize,
+    /// Word count assumed for `tstore` when it is not an immediate.
+    pub default_tstore_len: usize,
+}
+
+/// Estimated row count of each trace table, before padding.
+#[derive(Debug, Clone, Default, PartialEq, Eq)]
+pub struct TraceEstimate {
+    pub cpu: usize,
+    pub memory: usize,
+    pub rangecheck: usize,
+    pub bitwise: usize,
+    pub cmp: usize,
+    pub poseidon: usize,
+    pub poseidon_chunk: usize,
+    pub storage_hash: usize,
+    pub tape: usize,
+    pub sccall: usize,
+    pub program: usize,
+    pub prog_chunk: usize,
+}
+
+impl TraceEstimate {
+    pub fn tables(&self) -> Vec<(&'static str, usize)> {
+        vec![
+            ("cpu", self.cpu),
+            ("memory", self.memory),
+            ("rangecheck", self.rangecheck),
+            ("bitwise", self.bitwise),
+            ("cmp", self.cmp),
+            ("poseidon", self.poseidon),
+            ("poseidon_chunk", self.poseidon_chunk),
+            ("storage_hash", self.storage_hash),
+            ("tape", self.tape),
+            ("sccall", self.sccall),
+            ("program", self.program),
+            ("prog_chunk", self.prog_chunk),
+        ]
+    }
+
+    /// Check every table fits into `max_rows` rows once padded.
+    pub fn check_capacity(&self, max_rows: usize) -> Result<(), ProcessorError> {
+        for (name, rows) in self.tables() {
+            if rows.next_power_of_two() > max_rows {
+                return Err(ProcessorError::TraceCapacityExceeded(
+                    name.to_string(),
+                    rows,
+                    max_rows,
+                ));
+            }
+        }
+        Ok(())
+    }
+
+    pub(crate) fn add_scaled(&mut self, other: &TraceEstimate, times: usize) {
+        self.cpu += other.cpu * times;
+        self.memory += other.memory * times;
+        self.rangecheck += other.rangecheck * times;
+        self.bitwise += other.bitwise * times;
+        self.cmp += other.cmp * times;
+        self.poseidon += other.poseidon * times;
+        self.poseidon_chunk += other.poseidon_chunk * times;
+        self.storage_hash += other.storage_hash * times;
+        self.tape += other.tape * times;
+        self.sccall += other.sccall * times;
+    }
+}
+
+/// Estimate the per-table row counts of executing `instructions` without
+/// running them.
+///
+/// Every instruction is counted once, multiplied by the bound of each loop
+/// enclosing it. Loops are the ranges closed by a backward `jmp`/`cjmp` to an
+/// immediate target. Calls are not followed, so callees are counted once.
+pub fn estimate_trace(
+    instructions: &[BinaryInstruction],
+    config: &PreflightConfig,
+) -> TraceEstimate {
+    let mut pcs = Vec::with_capacity(instructions.len());
+    let mut pc = 0u64;
+    for inst in instructions {
+        pcs.push(pc);
+        pc += inst.binary_length() as u64;
+    }
+    let code_len = pc as usize;
+
+    let mut loops: Vec<(u64, u64, u64)> = vec![];
+    for (inst, &pc) in instructions.iter().zip(pcs.iter()) {
+        if inst.opcode != OlaOpcode::JMP && inst.opcode != OlaOpcode::CJMP {
+            continue;
+        }
+        if let Some(target) = immediate(&inst.op1) {
+            if target <= pc {
+                let bound = config.loop_bounds.get(&pc).cloned().unwrap_or(1);
+                loops.push((target, pc, bound));
+            }
+        }
+    }
+
+    let mut estimate = TraceEstimate::default();
+    let mut main_lines = 0;
+    for (inst, &pc) in instructions.iter().zip(pcs.iter()) {
+        let times: u64 = loops
+            .iter()
+            .filter(|(start, end, _)| *start <= pc && pc <= *end)
+            .map(|(_, _, bound)| *bound)
+            .product();
+        estimate.add_scaled(&instruction_cost(inst, config), times as usize);
+        main_lines += inst.binary_length() as usize * times as usize;
+    }
+
+    // memory sorting range checks, tape init and the program hash read.
+    estimate.rangecheck += estimate.memory;
+    estimate.tape += config.calldata_len;
+    estimate.poseidon += ROOT_TREE_DEPTH;
+    estimate.storage_hash += ROOT_TREE_DEPTH;
+    estimate.program = main_lines.max(code_len);
+    estimate.prog_chunk = (code_len + POSEIDON_RATE - 1) / POSEIDON_RATE;
+    estimate
+}
+
+/// Reject `program` when any estimated table would not fit into `max_rows`.
+pub fn preflight(
+    program: BinaryProgram,
+    config: &PreflightConfig,
+    max_rows: usize,
+) -> Result<TraceEstimate, ProcessorError> {
+    let instructions =
+        decode_binary_program_to_instructions(program).map_err(ProcessorError::DecodeError)?;
+    let estimate = estimate_trace(&instructions, config);
+    estimate.check_capacity(max_rows)?;
+    Ok(estimate)
+}
@@ -0,0 +1,71 @@
+use crate::hooks::{Hooks, ProcessView};
+use core::program::Program;
+use core::trace::trace::Trace;
+use plonky2::field::types::Field;
+use std::collections::BTreeMap;
+use std::fmt::Write;
+
+/// How often each pc retired, for profile guided optimization in the compiler
+/// frontend and for picking superinstruction candidates.
+///
+/// Collect it during execution by passing it as `Hooks` to
+/// `Process::execute_with_hooks`, or afterwards from the cpu trace with
+/// `from_trace`. Counts are per pc of one program, steps of called contracts
+/// are counted under their own pcs.
+#[derive(Debug, Clone, Default, PartialEq, Eq)]
+pub struct ExecutionProfile {
+    pub counts: BTreeMap<u64, u64>,
+}
+
+impl ExecutionProfile {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// Profile of the executed steps in `trace.exec`, extension lines
+    /// excluded.
+    pub fn from_trace(trace: &Trace) -> Self {
+        let mut profile = Self::new();
+        for step in trace.exec.iter().filter(|e| e.is_ext_line.is_zero()) {
+            profile.record(step.pc);
+        }
+        profile
+    }
+
+    pub fn record(&mut self, pc: u64) {
+        *self.counts.entry(pc).or_insert(0) += 1;
+    }
+
+    pub fn steps(&self) -> u64 {
+        self.counts.values().sum()
+    }
+
+    /// Executed pcs with their counts, hottest first, ties by pc.
+    pub fn hot_pcs(&self) -> Vec<(u64, u64)> {
+        let mut pcs: Vec<(u64, u64)> = self.counts.iter().map(|(&pc, &n)| (pc, n)).collect();
+        pcs.sort_by(|a, b| b.1.cmp(&a.1).then(a.0.cmp(&b.0)));
+        pcs
+    }
+
+    /// The disassembly of the decoded `program`, one instruction per line
+    /// prefixed with its execution count and pc. Run the program first so
+    /// `program.trace.instructions` is filled.
+    pub fn annotate(&self, program: &Program) -> String {
+        let mut pcs: Vec<&u64> = program.trace.instructions.keys().collect();
+        pcs.sort();
+        let mut out = String::new();
+        for pc in pcs {
+            let asm = &program.trace.instructions[pc].0;
+            let count = self.counts.get(pc).copied().unwrap_or(0);
+            writeln!(out, "{:>10} {:>6}: {}", count, pc, asm.trim()).unwrap();
+        }
+        out
+    }
+}
+
+impl Hooks for ExecutionProfile {
+    // Counted when the step starts, so a failing last step is included.
+    fn before_step(&mut self, view: &ProcessView) {
+        self.record(view.pc);
+    }
+}
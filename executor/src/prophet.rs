@@ -0,0 +1,35 @@
+use core::vm::memory::MemoryTree;
+use core::vm::prophet::ProphetDebugRecord;
+use interpreter::interpreter::Interpreter;
+use interpreter::utils::number::NumberRet::{Multiple, Single};
+use log::debug;
+
+/// Run the interpreter on the code and inputs of `record` and fill in its
+/// outputs, or its error if the run fails.
+pub(crate) fn interpret(record: &mut ProphetDebugRecord, memory: &MemoryTree) {
+    let mut interpreter = Interpreter::new(&record.code);
+    let res = interpreter.run(&record.prophet, record.inputs.clone(), memory);
+    debug!("interpreter:{:?}", res);
+    match res {
+        Ok(Multiple(values)) => {
+            record.outputs = values.iter().map(|v| v.get_number() as u64).collect();
+        }
+        Ok(Single(_)) => {
+            record.error = Some("interpreter returned a single value".to_string());
+        }
+        Err(err) => record.error = Some(err),
+    }
+}
+
+/// Re-run just the prophet of `record` against `memory`, e.g. to debug a
+/// record taken from a `ProcessorError::ProphetFailed` or reloaded from a
+/// trace dump. The returned record holds the fresh outputs or error.
+pub fn replay_prophet(record: &ProphetDebugRecord, memory: &MemoryTree) -> ProphetDebugRecord {
+    let mut replay = ProphetDebugRecord {
+        outputs: Vec::new(),
+        error: None,
+        ..record.clone()
+    };
+    interpret(&mut replay, memory);
+    replay
+}
@@ -0,0 +1,375 @@
+//! Repro bundles of failed executions.
+//!
+//! With `Process::repro_dir` set, an execution failing with a
+//! `ProcessorError` writes everything needed to run it again to a json file
+//! in that directory: the program, the tape with the tx context and calldata,
+//! the memory image, the process options, the committed values of every
+//! storage key it touched, the error and the last steps it executed.
+//! `ReproBundle::load` reads a bundle back and `ReproBundle::replay` reruns it
+//! against a test tree holding the captured slots, with hooks to trace it or
+//! a `TraceNavigator` over the result to step through it.
+
+use crate::gas::GasSchedule;
+use crate::hooks::Hooks;
+use crate::navigator::TraceNavigator;
+use crate::Process;
+use core::merkle_tree::log::{StorageLog, WitnessStorageLog};
+use core::merkle_tree::tree::AccountTree;
+use core::merkle_tree::TreeError;
+use core::program::binary_program::OlaProphet;
+use core::program::{Program, REGISTER_NUM};
+use core::trace::trace::Step;
+use core::types::merkle_tree::{
+    tree_key_default, tree_key_to_leaf_index, tree_value_default, u8_arr_to_tree_key,
+};
+use core::vm::error::ProcessorError;
+use core::vm::memory::MemoryCell;
+use core::vm::vm_state::VMState;
+use plonky2::field::goldilocks_field::GoldilocksField;
+use plonky2::field::types::{Field, PrimeField64};
+use serde::{Deserialize, Serialize};
+use std::collections::BTreeMap;
+use std::fmt;
+use std::fs::File;
+use std::io::{BufReader, BufWriter};
+use std::path::{Path, PathBuf};
+use std::time::{SystemTime, UNIX_EPOCH};
+
+/// Steps at the end of the trace kept in a bundle.
+pub const REPRO_TAIL_STEPS: usize = 32;
+
+// Not derived with `thiserror`: its expansion refers to `::core`, which names
+// the vm crate in this one.
+#[derive(Debug)]
+pub enum ReproError {
+    Io(std::io::Error),
+    Json(serde_json::Error),
+    Load(ProcessorError),
+    Tree(TreeError),
+}
+
+impl fmt::Display for ReproError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            ReproError::Io(e) => write!(f, "repro bundle io error: {}", e),
+            ReproError::Json(e) => write!(f, "repro bundle serde error: {}", e),
+            ReproError::Load(e) => write!(f, "load repro bundle into a process: {}", e),
+            ReproError::Tree(e) => write!(f, "repro bundle storage: {}", e),
+        }
+    }
+}
+
+impl std::error::Error for ReproError {
+    fn source(&self) -> Option<&(dyn std::error::Error + 'static)> {
+        match self {
+            ReproError::Io(e) => Some(e),
+            ReproError::Json(e) => Some(e),
+            ReproError::Load(e) => Some(e),
+            ReproError::Tree(e) => Some(e),
+        }
+    }
+}
+
+impl From<std::io::Error> for ReproError {
+    fn from(e: std::io::Error) -> Self {
+        ReproError::Io(e)
+    }
+}
+
+impl From<serde_json::Error> for ReproError {
+    fn from(e: serde_json::Error) -> Self {
+        ReproError::Json(e)
+    }
+}
+
+impl From<TreeError> for ReproError {
+    fn from(e: TreeError) -> Self {
+        ReproError::Tree(e)
+    }
+}
+
+/// Process fields an execution starts from besides its tape and memory.
+#[derive(Debug, Clone, Default, Serialize, Deserialize)]
+pub struct ReproOptions {
+    pub env_idx: u64,
+    pub call_sc_cnt: u64,
+    pub addr_storage: [u64; 4],
+    pub addr_code: [u64; 4],
+    pub pc: u64,
+    pub registers: Vec<u64>,
+    pub tp: u64,
+    pub step_limit: Option<u32>,
+    #[serde(default)]
+    pub gas_schedule: GasSchedule,
+    #[serde(default)]
+    pub gas_limit: Option<u64>,
+    #[serde(default)]
+    pub gas_used: u64,
+    pub disabled_opcodes: Vec<String>,
+    pub fork_id: [u64; 4],
+    pub chain_id: u64,
+    pub tx_hash: [u64; 4],
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct ReproBundle {
+    pub instructions: Vec<String>,
+    pub prophets: Vec<OlaProphet>,
+    #[serde(default)]
+    pub debug_info: Option<BTreeMap<usize, String>>,
+    #[serde(default)]
+    pub return_arity: Option<usize>,
+    /// Tape address to value, the tx context, calldata and call addresses.
+    pub tape: BTreeMap<u64, u64>,
+    /// Read-write memory image loaded before the execution.
+    #[serde(default)]
+    pub memory: BTreeMap<u64, u64>,
+    /// Base and words of the region mapped by `Process::map_code`.
+    #[serde(default)]
+    pub code: Option<(u64, Vec<u64>)>,
+    pub options: ReproOptions,
+    /// Tree key to committed value of every storage key the execution
+    /// touched, unset slots included as zero.
+    pub storage: Vec<([u64; 4], [u64; 4])>,
+    pub error: String,
+    /// Last `REPRO_TAIL_STEPS` steps, the failing one is not in the trace.
+    pub tail: Vec<Step>,
+}
+
+fn to_u64s<const N: usize>(words: &[GoldilocksField; N]) -> [u64; N] {
+    words.map(|w| w.to_canonical_u64())
+}
+
+fn last_value(cells: &[MemoryCell]) -> Option<u64> {
+    cells.last().map(|cell| cell.value.to_canonical_u64())
+}
+
+fn to_fields<const N: usize>(words: &[u64; N]) -> [GoldilocksField; N] {
+    words.map(GoldilocksField::from_canonical_u64)
+}
+
+/// The outcome of replaying a bundle.
+#[derive(Debug)]
+pub struct Replay {
+    pub process: Process,
+    pub program: Program,
+    pub result: Result<VMState, ProcessorError>,
+}
+
+impl Replay {
+    /// Navigator over the replayed steps, the memory table of a failed run
+    /// is never generated so memory is read from the process.
+    pub fn navigator(&self) -> TraceNavigator<'_> {
+        TraceNavigator::from_memory(&self.program.trace, &self.process.memory)
+    }
+}
+
+impl ReproBundle {
+    /// Inputs of `process` about to execute `program`, before anything ran.
+    pub fn capture(process: &Process, program: &Program) -> Self {
+        let code_region = process.code_region.clone().unwrap_or_default();
+        let mut prophets: Vec<OlaProphet> = program.prophets.values().cloned().collect();
+        prophets.sort_by_key(|prophet| prophet.host);
+        Self {
+            instructions: program.instructions.clone(),
+            prophets,
+            debug_info: program.debug_info.clone(),
+            return_arity: program.return_arity,
+            tape: process
+                .tape
+                .trace
+                .iter()
+                .filter_map(|(addr, cells)| {
+                    cells.last().map(|c| (*addr, c.value.to_canonical_u64()))
+                })
+                .collect(),
+            memory: process
+                .memory
+                .trace
+                .iter()
+                .filter(|(addr, _)| !code_region.contains(addr))
+                .filter_map(|(addr, cells)| last_value(cells).map(|value| (*addr, value)))
+                .collect(),
+            code: process.code_region.clone().map(|region| {
+                let words = region
+                    .clone()
+                    .filter_map(|addr| last_value(&process.memory.trace[&addr]))
+                    .collect();
+                (region.start, words)
+            }),
+            options: ReproOptions {
+                env_idx: process.env_idx.to_canonical_u64(),
+                call_sc_cnt: process.call_sc_cnt.to_canonical_u64(),
+                addr_storage: to_u64s(&process.addr_storage),
+                addr_code: to_u64s(&process.addr_code),
+                pc: process.pc,
+                registers: process
+                    .registers
+                    .iter()
+                    .map(|r| r.to_canonical_u64())
+                    .collect(),
+                tp: process.tp.to_canonical_u64(),
+                step_limit: process.step_limit,
+                gas_schedule: process.gas_schedule.clone(),
+                gas_limit: process.gas_limit,
+                gas_used: process.gas_used,
+                disabled_opcodes: process.disabled_opcodes.clone(),
+                fork_id: to_u64s(&process.fork_id),
+                chain_id: process.chain_id.to_canonical_u64(),
+                tx_hash: to_u64s(&process.tx_hash),
+            },
+            storage: vec![],
+            error: String::new(),
+            tail: vec![],
+        }
+    }
+
+    /// Records how the captured execution failed: `error`, the steps leading
+    /// to it and the values `tree` holds for the storage keys it touched.
+    pub fn finish(
+        &mut self,
+        process: &Process,
+        program: &Program,
+        tree: &AccountTree,
+        error: &ProcessorError,
+    ) {
+        self.error = error.to_string();
+        let exec = &program.trace.exec;
+        self.tail = exec[exec.len().saturating_sub(REPRO_TAIL_STEPS)..].to_vec();
+        self.storage = process
+            .storage
+            .iter()
+            .map(|(key, _)| {
+                let value = tree
+                    .storage
+                    .hash(&tree_key_to_leaf_index(key))
+                    .map(|bytes| u8_arr_to_tree_key(&bytes))
+                    .unwrap_or_else(tree_value_default);
+                (to_u64s(key), to_u64s(&value))
+            })
+            .collect();
+        self.storage.sort();
+    }
+
+    /// Writes the bundle as `repro-<unix secs>-<n>.json` into `dir`, with the
+    /// first `n` not taken, and returns its path.
+    pub fn write(&self, dir: &Path) -> Result<PathBuf, ReproError> {
+        std::fs::create_dir_all(dir)?;
+        let secs = SystemTime::now()
+            .duration_since(UNIX_EPOCH)
+            .map_or(0, |d| d.as_secs());
+        let mut n = 0;
+        let (path, file) = loop {
+            let path = dir.join(format!("repro-{}-{}.json", secs, n));
+            match File::options().write(true).create_new(true).open(&path) {
+                Ok(file) => break (path, file),
+                Err(e) if e.kind() == std::io::ErrorKind::AlreadyExists => n += 1,
+                Err(e) => return Err(e.into()),
+            }
+        };
+        serde_json::to_writer_pretty(BufWriter::new(file), self)?;
+        Ok(path)
+    }
+
+    pub fn load(path: &Path) -> Result<Self, ReproError> {
+        let file = File::open(path)?;
+        Ok(serde_json::from_reader(BufReader::new(file))?)
+    }
+
+    /// Tree holding the captured slots, unset ones are left out.
+    fn tree(&self) -> Result<AccountTree, ReproError> {
+        let mut tree = AccountTree::new_test();
+        let logs: Vec<_> = self
+            .storage
+            .iter()
+            .filter(|(_, value)| to_fields(value) != tree_value_default())
+            .map(|(key, value)| WitnessStorageLog {
+                storage_log: StorageLog::new_write_log(to_fields(key), to_fields(value)),
+                previous_value: tree_key_default(),
+            })
+            .collect();
+        if !logs.is_empty() {
+            tree.process_block(logs);
+            tree.commit()?;
+        }
+        Ok(tree)
+    }
+
+    /// Process and program the captured execution started from.
+    pub fn restore(&self) -> Result<(Process, Program), ReproError> {
+        let options = &self.options;
+        let mut process = Process::new();
+        process.env_idx = GoldilocksField::from_canonical_u64(options.env_idx);
+        process.call_sc_cnt = GoldilocksField::from_canonical_u64(options.call_sc_cnt);
+        process.addr_storage = to_fields(&options.addr_storage);
+        process.addr_code = to_fields(&options.addr_code);
+        process.pc = options.pc;
+        for (reg, value) in process
+            .registers
+            .iter_mut()
+            .zip(options.registers.iter().take(REGISTER_NUM))
+        {
+            *reg = GoldilocksField::from_canonical_u64(*value);
+        }
+        process.tp = GoldilocksField::from_canonical_u64(options.tp);
+        process.step_limit = options.step_limit;
+        process.gas_schedule = options.gas_schedule.clone();
+        process.gas_limit = options.gas_limit;
+        process.gas_used = options.gas_used;
+        process.disabled_opcodes = options.disabled_opcodes.clone();
+        process.fork_id = to_fields(&options.fork_id);
+        process.chain_id = GoldilocksField::from_canonical_u64(options.chain_id);
+        process.tx_hash = to_fields(&options.tx_hash);
+        for (addr, value) in &self.tape {
+            process.tape.write(
+                *addr,
+                0,
+                GoldilocksField::ZERO,
+                GoldilocksField::ONE,
+                GoldilocksField::ZERO,
+                GoldilocksField::from_canonical_u64(*value),
+            );
+        }
+        if let Some((base, words)) = &self.code {
+            let words: Vec<_> = words
+                .iter()
+                .map(|w| GoldilocksField::from_canonical_u64(*w))
+                .collect();
+            process.map_code(*base, &words).map_err(ReproError::Load)?;
+        }
+        let image = self
+            .memory
+            .iter()
+            .map(|(addr, value)| (*addr, GoldilocksField::from_canonical_u64(*value)))
+            .collect();
+        process
+            .load_memory_image(&image)
+            .map_err(ReproError::Load)?;
+
+        let program = Program {
+            instructions: self.instructions.clone(),
+            trace: Default::default(),
+            debug_info: self.debug_info.clone(),
+            print_flag: false,
+            prophets: self
+                .prophets
+                .iter()
+                .map(|p| (p.host as u64, p.clone()))
+                .collect(),
+            pre_exe_flag: false,
+            return_arity: self.return_arity,
+        };
+        Ok((process, program))
+    }
+
+    /// Runs the captured execution again with `hooks`.
+    pub fn replay<H: Hooks>(&self, hooks: &mut H) -> Result<Replay, ReproError> {
+        let (mut process, mut program) = self.restore()?;
+        let result = process.execute_with_hooks(&mut program, &mut self.tree()?, hooks);
+        Ok(Replay {
+            process,
+            program,
+            result,
+        })
+    }
+}
@@ -0,0 +1,343 @@
+//! JSON-RPC 2.0 handlers over the simulation APIs, enabled by the `rpc`
+//! feature. Serve `router` with axum:
+//!
+//! - `ola_call {contract, caller?, calldata}` simulates a call and returns the
+//!   returned values, steps and state diff. Nothing is written.
+//! - `ola_estimateCycles {contract, caller?, calldata}` returns the steps of
+//!   the call.
+//! - `ola_getStorageAt {contract, slot, block?}` returns the value of a slot at
+//!   a saved block, the last one if not given. Older blocks need an archival
+//!   tree or a block not pruned yet.
+//!
+//! Addresses, slots and values are 64 hex digits as `encode_addr` prints them,
+//! calldata and returned values are u64s. Every request runs against a view of
+//! the last saved block of the shared account tree, so it never waits for the
+//! block being executed.
+
+use crate::simulate::{simulate_tx_with_limits, SimulationLimits, StateView};
+use axum::extract::State;
+use axum::routing::post;
+use axum::{Json, Router};
+use core::merkle_tree::shared::SharedAccountTree;
+use core::program::binary_program::BinaryProgram;
+use core::types::account::{AccountTreeId, Address};
+use core::types::merkle_tree::{decode_addr, encode_addr, TreeKey};
+use core::types::storage::StorageKey;
+use core::vm::transaction::TxCtxInfo;
+use plonky2::field::goldilocks_field::GoldilocksField;
+use plonky2::field::types::{Field, Field64, PrimeField64};
+use serde::{Deserialize, Serialize};
+use serde_json::Value;
+use std::sync::Arc;
+
+pub const INVALID_REQUEST: i64 = -32600;
+pub const METHOD_NOT_FOUND: i64 = -32601;
+pub const INVALID_PARAMS: i64 = -32602;
+/// The call failed to execute or the contract has no code.
+pub const EXECUTION_ERROR: i64 = -32000;
+
+/// Where the code of a contract comes from, e.g. the node state.
+pub trait ProgramSource: Send + Sync {
+    fn program(&self, contract: &Address) -> Option<BinaryProgram>;
+}
+
+#[derive(Clone)]
+pub struct RpcState {
+    pub tree: Arc<SharedAccountTree>,
+    pub programs: Arc<dyn ProgramSource>,
+    /// Tx context calls are simulated in.
+    pub ctx: TxCtxInfo,
+    pub limits: SimulationLimits,
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct RpcRequest {
+    pub jsonrpc: String,
+    #[serde(default)]
+    pub id: Value,
+    pub method: String,
+    #[serde(default)]
+    pub params: Value,
+}
+
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct RpcError {
+    pub code: i64,
+    pub message: String,
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct RpcResponse {
+    pub jsonrpc: String,
+    pub id: Value,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub result: Option<Value>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub error: Option<RpcError>,
+}
+
+#[derive(Debug, Deserialize)]
+struct CallParams {
+    contract: String,
+    #[serde(default)]
+    caller: Option<String>,
+    #[serde(default)]
+    calldata: Vec<u64>,
+}
+
+#[derive(Debug, Deserialize)]
+struct StorageParams {
+    contract: String,
+    slot: String,
+    #[serde(default)]
+    block: Option<u32>,
+}
+
+#[derive(Debug, Serialize)]
+struct StorageChange {
+    key: String,
+    previous: String,
+    value: String,
+}
+
+#[derive(Debug, Serialize)]
+struct CallResult {
+    ret: Vec<u64>,
+    steps: u64,
+    state_diff: Vec<StorageChange>,
+}
+
+fn error(code: i64, message: impl ToString) -> RpcError {
+    RpcError {
+        code,
+        message: message.to_string(),
+    }
+}
+
+fn parse_word(name: &str, hex: &str) -> Result<TreeKey, RpcError> {
+    let hex = hex.trim_start_matches("0x");
+    if hex.len() != 64 || !hex.chars().all(|c| c.is_ascii_hexdigit()) {
+        return Err(error(
+            INVALID_PARAMS,
+            format!("{} is not 64 hex digits", name),
+        ));
+    }
+    Ok(decode_addr(hex.to_string()))
+}
+
+fn params<T: for<'de> Deserialize<'de>>(params: Value) -> Result<T, RpcError> {
+    serde_json::from_value(params).map_err(|e| error(INVALID_PARAMS, e))
+}
+
+fn call(state: &RpcState, params: CallParams) -> Result<CallResult, RpcError> {
+    let contract = parse_word("contract", &params.contract)?;
+    let caller = match &params.caller {
+        Some(caller) => parse_word("caller", caller)?,
+        None => Address::default(),
+    };
+    let calldata = params
+        .calldata
+        .iter()
+        .map(|value| {
+            if *value >= GoldilocksField::ORDER {
+                return Err(error(
+                    INVALID_PARAMS,
+                    format!("{} is not a field element", value),
+                ));
+            }
+            Ok(GoldilocksField::from_canonical_u64(*value))
+        })
+        .collect::<Result<Vec<_>, _>>()?;
+    let program = state
+        .programs
+        .program(&contract)
+        .ok_or_else(|| error(EXECUTION_ERROR, "contract has no code"))?;
+
+    let tree = state.tree.view();
+    let view = StateView {
+        tree: &tree,
+        caller,
+        contract,
+        ctx: &state.ctx,
+    };
+    let outcome = simulate_tx_with_limits(program, calldata, &view, &state.limits)
+        .map_err(|e| error(EXECUTION_ERROR, e))?;
+    Ok(CallResult {
+        ret: outcome
+            .ret
+            .iter()
+            .map(|value| value.to_canonical_u64())
+            .collect(),
+        steps: outcome.steps,
+        state_diff: outcome
+            .state_diff
+            .iter()
+            .map(|diff| StorageChange {
+                key: encode_addr(&diff.key),
+                previous: encode_addr(&diff.previous),
+                value: encode_addr(&diff.value),
+            })
+            .collect(),
+    })
+}
+
+fn storage_at(state: &RpcState, params: StorageParams) -> Result<String, RpcError> {
+    let contract = parse_word("contract", &params.contract)?;
+    let slot = parse_word("slot", &params.slot)?;
+    let (key, _) = StorageKey::new(AccountTreeId::new(contract), slot).hashed_key();
+    let tree = match params.block {
+        Some(block) => state
+            .tree
+            .at_block(block)
+            .map_err(|e| error(INVALID_PARAMS, e))?,
+        None => state.tree.view(),
+    };
+    Ok(encode_addr(&tree.read_value(&key)))
+}
+
+/// Answers one request. Blocks while the call executes.
+pub fn dispatch(state: &RpcState, request: RpcRequest) -> RpcResponse {
+    let result = if request.jsonrpc != "2.0" {
+        Err(error(INVALID_REQUEST, "jsonrpc must be 2.0"))
+    } else {
+        match request.method.as_str() {
+            "ola_call" => params(request.params)
+                .and_then(|params| call(state, params))
+                .map(|result| serde_json::to_value(result).unwrap()),
+            "ola_estimateCycles" => params(request.params)
+                .and_then(|params| call(state, params))
+                .map(|result| Value::from(result.steps)),
+            "ola_getStorageAt" => params(request.params)
+                .and_then(|params| storage_at(state, params))
+                .map(Value::from),
+            method => Err(error(
+                METHOD_NOT_FOUND,
+                format!("unknown method {}", method),
+            )),
+        }
+    };
+    let (result, error) = match result {
+        Ok(result) => (Some(result), None),
+        Err(error) => (None, Some(error)),
+    };
+    RpcResponse {
+        jsonrpc: "2.0".to_string(),
+        id: request.id,
+        result,
+        error,
+    }
+}
+
+async fn handle(
+    State(state): State<RpcState>,
+    Json(request): Json<RpcRequest>,
+) -> Json<RpcResponse> {
+    let id = request.id.clone();
+    let response = tokio::task::spawn_blocking(move || dispatch(&state, request))
+        .await
+        .unwrap_or_else(|e| RpcResponse {
+            jsonrpc: "2.0".to_string(),
+            id,
+            result: None,
+            error: Some(error(EXECUTION_ERROR, e)),
+        });
+    Json(response)
+}
+
+/// Router answering JSON-RPC requests posted to `/`.
+pub fn router(state: RpcState) -> Router {
+    Router::new().route("/", post(handle)).with_state(state)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::{dispatch, ProgramSource, RpcRequest, RpcState, METHOD_NOT_FOUND};
+    use crate::simulate::SimulationLimits;
+    use assembler::encoder::encode_asm;
+    use core::merkle_tree::shared::SharedAccountTree;
+    use core::merkle_tree::tree::AccountTree;
+    use core::program::binary_program::BinaryProgram;
+    use core::types::account::Address;
+    use core::types::merkle_tree::{encode_addr, tree_value_default};
+    use core::vm::transaction::init_tx_context_mock;
+    use serde_json::json;
+    use std::sync::Arc;
+
+    struct OneProgram(BinaryProgram);
+
+    impl ProgramSource for OneProgram {
+        fn program(&self, _contract: &Address) -> Option<BinaryProgram> {
+            Some(self.0.clone())
+        }
+    }
+
+    #[test]
+    fn test_rpc_dispatch() {
+        let program = encode_asm("main:\nmov r1 2\nadd r1 r1 3\nend").unwrap();
+        let state = RpcState {
+            tree: Arc::new(SharedAccountTree::new(AccountTree::new_test())),
+            programs: Arc::new(OneProgram(program)),
+            ctx: init_tx_context_mock(),
+            limits: SimulationLimits::default(),
+        };
+        let request = |method: &str, params| RpcRequest {
+            jsonrpc: "2.0".to_string(),
+            id: json!(1),
+            method: method.to_string(),
+            params,
+        };
+        let zero = encode_addr(&Address::default());
+
+        let response = dispatch(
+            &state,
+            request("ola_call", json!({ "contract": zero, "calldata": [] })),
+        );
+        assert_eq!(response.id, json!(1));
+        let result = response.result.unwrap();
+        assert_eq!(result["steps"], json!(3));
+        assert_eq!(result["state_diff"], json!([]));
+
+        let response = dispatch(
+            &state,
+            request("ola_estimateCycles", json!({ "contract": zero })),
+        );
+        assert_eq!(response.result, Some(json!(3)));
+
+        let response = dispatch(
+            &state,
+            request(
+                "ola_getStorageAt",
+                json!({ "contract": zero, "slot": zero }),
+            ),
+        );
+        assert_eq!(
+            response.result,
+            Some(json!(encode_addr(&tree_value_default())))
+        );
+
+        let response = dispatch(
+            &state,
+            request(
+                "ola_getStorageAt",
+                json!({ "contract": zero, "slot": zero, "block": 0 }),
+            ),
+        );
+        assert_eq!(
+            response.result,
+            Some(json!(encode_addr(&tree_value_default())))
+        );
+        let response = dispatch(
+            &state,
+            request(
+                "ola_getStorageAt",
+                json!({ "contract": zero, "slot": zero, "block": 1 }),
+            ),
+        );
+        assert_eq!(response.error.unwrap().code, super::INVALID_PARAMS);
+
+        let response = dispatch(&state, request("ola_call", json!({ "contract": "0x12" })));
+        assert_eq!(response.error.unwrap().code, super::INVALID_PARAMS);
+        let response = dispatch(&state, request("eth_call", json!({})));
+        assert_eq!(response.error.unwrap().code, METHOD_NOT_FOUND);
+    }
+}
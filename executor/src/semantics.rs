@@ -0,0 +1,270 @@
+use crate::cost_model::opcode_costs;
+use crate::preflight::PreflightConfig;
+use core::program::instruction::{
+    IMM_FLAG_FIELD_BIT_POSITION, OPCODE_FLAG_FIELD_BIT_POSITION, OPCODE_FLAG_FIELD_LEN,
+    REG0_FIELD_BIT_POSITION, REG1_FIELD_BIT_POSITION, REG2_FIELD_BIT_POSITION, REG_FIELD_BIT_LEN,
+};
+use core::vm::opcodes::OlaOpcode;
+use serde::Serialize;
+
+/// Bit range `[start, start + len)` of a field in the first instruction word.
+#[derive(Debug, Clone, Serialize, PartialEq, Eq)]
+pub struct BitField {
+    pub start: u64,
+    pub len: u64,
+}
+
+/// Layout of the first word of every instruction, an immediate follows it in
+/// a second word when `imm_flag` is set.
+#[derive(Debug, Clone, Serialize, PartialEq, Eq)]
+pub struct EncodingLayout {
+    pub opcode_flags: BitField,
+    pub op0: BitField,
+    pub op1: BitField,
+    pub dst: BitField,
+    pub imm_flag: BitField,
+}
+
+impl EncodingLayout {
+    pub fn new() -> Self {
+        let reg = |start| BitField {
+            start,
+            len: REG_FIELD_BIT_LEN,
+        };
+        Self {
+            opcode_flags: BitField {
+                start: OPCODE_FLAG_FIELD_BIT_POSITION,
+                len: OPCODE_FLAG_FIELD_LEN,
+            },
+            op0: reg(REG0_FIELD_BIT_POSITION),
+            op1: reg(REG1_FIELD_BIT_POSITION),
+            dst: reg(REG2_FIELD_BIT_POSITION),
+            imm_flag: BitField {
+                start: IMM_FLAG_FIELD_BIT_POSITION,
+                len: 1,
+            },
+        }
+    }
+}
+
+impl Default for EncodingLayout {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+/// Semantics of one opcode as the executor implements it.
+#[derive(Debug, Clone, Serialize, PartialEq, Eq)]
+pub struct OpcodeSpec {
+    pub token: String,
+    /// Bit of the opcode in the opcode flag field.
+    pub bit_shift: u8,
+    pub bit_mask: u64,
+    /// Operand slots in assembly order, e.g. `["dst", "op0", "op1"]`.
+    pub operands: Vec<&'static str>,
+    /// Whether `op1` may be an immediate instead of a register.
+    pub op1_imm: bool,
+    /// Operand slots whose register is read.
+    pub reads: Vec<&'static str>,
+    /// Operand slots whose register is written.
+    pub writes: Vec<&'static str>,
+    /// Trace tables one execution adds rows to, besides the program tables.
+    pub tables: Vec<&'static str>,
+    /// `ProcessorError` variants the opcode can fail with.
+    pub errors: Vec<&'static str>,
+}
+
+/// The opcode semantics table, for tools outside the repo which decode or
+/// generate OlaVM code.
+#[derive(Debug, Clone, Serialize, PartialEq, Eq)]
+pub struct OpcodeSemantics {
+    pub encoding: EncodingLayout,
+    /// `ProcessorError` variants any instruction can fail with.
+    pub common_errors: Vec<&'static str>,
+    pub opcodes: Vec<OpcodeSpec>,
+}
+
+fn operands(opcode: OlaOpcode) -> Vec<&'static str> {
+    match opcode {
+        OlaOpcode::ADD
+        | OlaOpcode::MUL
+        | OlaOpcode::AND
+        | OlaOpcode::OR
+        | OlaOpcode::XOR
+        | OlaOpcode::EQ
+        | OlaOpcode::NEQ
+        | OlaOpcode::GTE
+        | OlaOpcode::TLOAD
+        | OlaOpcode::POSEIDON
+        | OlaOpcode::MLOAD
+        | OlaOpcode::SSCAN
+        | OlaOpcode::CMOV => vec!["dst", "op0", "op1"],
+        OlaOpcode::MOV | OlaOpcode::NOT | OlaOpcode::TLOADT | OlaOpcode::TXCTX => {
+            vec!["dst", "op1"]
+        }
+        OlaOpcode::CJMP
+        | OlaOpcode::TSTORE
+        | OlaOpcode::SCCALL
+        | OlaOpcode::SLOAD
+        | OlaOpcode::SSTORE
+        | OlaOpcode::TSTORET => vec!["op0", "op1"],
+        OlaOpcode::MSTORE => vec!["op0", "op1", "dst"],
+        // `assert op0 message_id` carries the message id as immediate op1
+        OlaOpcode::ASSERT | OlaOpcode::JMP | OlaOpcode::CALL | OlaOpcode::RC => vec!["op1"],
+        OlaOpcode::RET | OlaOpcode::END => vec![],
+    }
+}
+
+fn writes(opcode: OlaOpcode) -> Vec<&'static str> {
+    match opcode {
+        OlaOpcode::ADD
+        | OlaOpcode::MUL
+        | OlaOpcode::AND
+        | OlaOpcode::OR
+        | OlaOpcode::XOR
+        | OlaOpcode::EQ
+        | OlaOpcode::NEQ
+        | OlaOpcode::GTE
+        | OlaOpcode::MOV
+        | OlaOpcode::NOT
+        | OlaOpcode::MLOAD
+        | OlaOpcode::TLOADT
+        | OlaOpcode::SSCAN
+        | OlaOpcode::TXCTX
+        | OlaOpcode::CMOV => vec!["dst"],
+        _ => vec![],
+    }
+}
+
+fn reads(opcode: OlaOpcode) -> Vec<&'static str> {
+    match opcode {
+        // the dst register holds the memory address written to, or for sscan
+        // the most slots read
+        OlaOpcode::TLOAD | OlaOpcode::POSEIDON | OlaOpcode::MSTORE | OlaOpcode::SSCAN => {
+            vec!["op0", "op1", "dst"]
+        }
+        _ => operands(opcode)
+            .into_iter()
+            .filter(|slot| *slot != "dst")
+            .collect(),
+    }
+}
+
+fn errors(opcode: OlaOpcode) -> Vec<&'static str> {
+    match opcode {
+        OlaOpcode::ASSERT => vec!["AssertFail"],
+        OlaOpcode::RC | OlaOpcode::GTE => vec!["U32RangeCheckFail"],
+        OlaOpcode::MSTORE => vec!["MemVistInv", "CodeRegionWrite"],
+        OlaOpcode::MLOAD
+        | OlaOpcode::CALL
+        | OlaOpcode::RET
+        | OlaOpcode::POSEIDON
+        | OlaOpcode::SLOAD
+        | OlaOpcode::SSTORE
+        | OlaOpcode::SCCALL
+        | OlaOpcode::TSTORE => vec!["MemVistInv"],
+        OlaOpcode::TLOAD => vec![
+            "MemVistInv",
+            "TloadFlagInvalid",
+            "TapeVistInv",
+            "CodeRegionWrite",
+        ],
+        OlaOpcode::SSCAN => vec![
+            "MemVistInv",
+            "CodeRegionWrite",
+            "LimitExceeded",
+            "SscanFlagInvalid",
+        ],
+        OlaOpcode::TXCTX => vec!["TxCtxSelectorInvalid"],
+        OlaOpcode::CMOV => vec!["CmovConditionInvalid"],
+        OlaOpcode::END => vec!["LimitExceeded"],
+        _ => vec![],
+    }
+}
+
+impl OpcodeSemantics {
+    /// Semantics of every `OlaOpcode`, in enum order.
+    pub fn new() -> Self {
+        let opcodes = opcode_costs(&PreflightConfig::default())
+            .into_iter()
+            .map(|(opcode, cost)| {
+                let mut tables: Vec<&'static str> = cost
+                    .tables()
+                    .into_iter()
+                    .filter(|(_, rows)| *rows > 0)
+                    .map(|(name, _)| name)
+                    .collect();
+                // tables the cost model doesn't count or sizes by the operand
+                let extra: &[&'static str] = match opcode {
+                    OlaOpcode::TLOAD | OlaOpcode::TSTORE => &["memory", "tape"],
+                    OlaOpcode::TLOADT | OlaOpcode::TSTORET => &["transient"],
+                    _ => &[],
+                };
+                for table in extra {
+                    if !tables.contains(table) {
+                        tables.push(table);
+                    }
+                }
+                OpcodeSpec {
+                    token: opcode.token(),
+                    bit_shift: opcode.binary_bit_shift(),
+                    bit_mask: opcode.binary_bit_mask(),
+                    operands: operands(opcode),
+                    op1_imm: operands(opcode).contains(&"op1"),
+                    reads: reads(opcode),
+                    writes: writes(opcode),
+                    tables,
+                    errors: errors(opcode),
+                }
+            })
+            .collect();
+        Self {
+            encoding: EncodingLayout::new(),
+            common_errors: vec!["PcVistInv", "ProphetFailed"],
+            opcodes,
+        }
+    }
+
+    pub fn to_json(&self) -> String {
+        serde_json::to_string_pretty(self).unwrap()
+    }
+}
+
+impl Default for OpcodeSemantics {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::OpcodeSemantics;
+    use core::vm::opcodes::OlaOpcode;
+    use enum_iterator::all;
+
+    #[test]
+    fn test_opcode_semantics() {
+        let semantics = OpcodeSemantics::new();
+        assert_eq!(semantics.opcodes.len(), all::<OlaOpcode>().count());
+        for spec in &semantics.opcodes {
+            assert_eq!(spec.bit_mask, 1 << spec.bit_shift);
+            assert!(spec.tables.contains(&"cpu"), "{}", spec.token);
+            for slot in spec.reads.iter().chain(spec.writes.iter()) {
+                assert!(spec.operands.contains(slot), "{} {}", spec.token, slot);
+            }
+        }
+
+        let json: serde_json::Value = serde_json::from_str(&semantics.to_json()).unwrap();
+        let add = &json["opcodes"][0];
+        assert_eq!(add["token"], "add");
+        assert_eq!(add["writes"], serde_json::json!(["dst"]));
+        let mstore = json["opcodes"]
+            .as_array()
+            .unwrap()
+            .iter()
+            .find(|spec| spec["token"] == "mstore")
+            .unwrap();
+        assert_eq!(mstore["tables"], serde_json::json!(["cpu", "memory"]));
+        assert_eq!(json["encoding"]["imm_flag"]["start"], 62);
+    }
+}
@@ -0,0 +1,234 @@
+use crate::hooks::NoHooks;
+use crate::load_tx::init_tape;
+use crate::preflight::{preflight, PreflightConfig};
+use crate::Process;
+use core::merkle_tree::tree::AccountTree;
+use core::program::binary_program::BinaryProgram;
+use core::program::Program;
+use core::types::account::Address;
+use core::types::merkle_tree::{
+    tree_key_to_leaf_index, tree_value_default, u8_arr_to_tree_key, TreeKey, TreeValue,
+};
+use core::types::ssz::{
+    list_from_ssz_bytes, list_root, merkleize, ContainerDecoder, ContainerEncoder, HashTreeRoot,
+    Root, Ssz, SszError,
+};
+use core::vm::error::ProcessorError;
+use core::vm::transaction::TxCtxInfo;
+use core::vm::vm_state::VMState;
+use plonky2::field::goldilocks_field::GoldilocksField;
+use plonky2::field::types::Field;
+
+/// Read-only state a transaction is simulated against.
+#[derive(Debug, Clone, Copy)]
+pub struct StateView<'a> {
+    pub tree: &'a AccountTree,
+    pub caller: Address,
+    /// Storage and code address of the simulated contract.
+    pub contract: Address,
+    pub ctx: &'a TxCtxInfo,
+}
+
+#[derive(Debug, Clone)]
+pub struct SimulationLimits {
+    pub max_steps: u32,
+    pub max_calldata_len: usize,
+    pub max_storage_ops: usize,
+    /// Row capacity every table of the static estimate has to fit into.
+    pub max_rows: usize,
+}
+
+impl Default for SimulationLimits {
+    fn default() -> Self {
+        Self {
+            max_steps: 1 << 20,
+            max_calldata_len: 1 << 10,
+            max_storage_ops: 1 << 10,
+            max_rows: 1 << 22,
+        }
+    }
+}
+
+/// A storage slot the transaction would change.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct StorageDiff {
+    pub key: TreeKey,
+    pub previous: TreeValue,
+    pub value: TreeValue,
+}
+
+/// Limit of the SSZ list of a state diff, `List[StorageDiff,
+/// STATE_DIFF_LIMIT]`.
+pub const STATE_DIFF_LIMIT: usize = 1 << 16;
+
+impl Ssz for StorageDiff {
+    fn fixed_len() -> Option<usize> {
+        Some(3 * 32)
+    }
+
+    fn ssz_append(&self, buf: &mut Vec<u8>) {
+        ContainerEncoder::new()
+            .field(&self.key)
+            .field(&self.previous)
+            .field(&self.value)
+            .finish(buf);
+    }
+
+    fn from_ssz_bytes(bytes: &[u8]) -> Result<Self, SszError> {
+        let mut decoder = ContainerDecoder::new(bytes);
+        for _ in 0..3 {
+            decoder.register::<TreeValue>()?;
+        }
+        decoder.finish()?;
+        Ok(Self {
+            key: decoder.decode_next()?,
+            previous: decoder.decode_next()?,
+            value: decoder.decode_next()?,
+        })
+    }
+}
+
+impl HashTreeRoot for StorageDiff {
+    fn hash_tree_root(&self) -> Root {
+        let fields = [self.key, self.previous, self.value].map(|v| v.hash_tree_root());
+        merkleize(&fields, fields.len())
+    }
+}
+
+/// SSZ encoding of a state diff.
+pub fn state_diff_to_ssz(diff: &[StorageDiff]) -> Vec<u8> {
+    let mut buf = vec![];
+    for slot in diff {
+        slot.ssz_append(&mut buf);
+    }
+    buf
+}
+
+pub fn state_diff_from_ssz(bytes: &[u8]) -> Result<Vec<StorageDiff>, SszError> {
+    list_from_ssz_bytes(bytes, STATE_DIFF_LIMIT)
+}
+
+/// `hash_tree_root` of a state diff, for light clients to check a slot
+/// against.
+pub fn state_diff_root(diff: &[StorageDiff]) -> Root {
+    list_root(diff, STATE_DIFF_LIMIT)
+}
+
+#[derive(Debug)]
+pub struct SimulationOutcome {
+    pub state: VMState,
+    pub steps: u64,
+    /// Values the program returned on the tape.
+    pub ret: Vec<GoldilocksField>,
+    pub storage_reads: usize,
+    pub storage_writes: usize,
+    /// Changed slots in first access order.
+    pub state_diff: Vec<StorageDiff>,
+}
+
+/// Simulate `program` with `calldata` under the default limits, see
+/// `simulate_tx_with_limits`.
+pub fn simulate_tx(
+    program: BinaryProgram,
+    calldata: Vec<GoldilocksField>,
+    state_view: &StateView,
+) -> Result<SimulationOutcome, ProcessorError> {
+    simulate_tx_with_limits(program, calldata, state_view, &SimulationLimits::default())
+}
+
+/// Run `program` without recording a trace, for mempool admission.
+///
+/// Calldata length and the static row estimate are checked before anything
+/// executes. Execution aborts after `limits.max_steps` steps and never writes
+/// to `state_view.tree`. A call into another contract ends the simulation
+/// with `VMState::SCCall`, the callee is not run.
+pub fn simulate_tx_with_limits(
+    program: BinaryProgram,
+    calldata: Vec<GoldilocksField>,
+    state_view: &StateView,
+    limits: &SimulationLimits,
+) -> Result<SimulationOutcome, ProcessorError> {
+    if calldata.len() > limits.max_calldata_len {
+        return Err(ProcessorError::LimitExceeded(
+            "calldata".to_string(),
+            limits.max_calldata_len as u64,
+        ));
+    }
+    let config = PreflightConfig {
+        calldata_len: calldata.len(),
+        ..Default::default()
+    };
+    preflight(program.clone(), &config, limits.max_rows)?;
+
+    let mut program = Program {
+        instructions: program
+            .bytecode
+            .split('\n')
+            .map(|e| e.to_string())
+            .collect(),
+        trace: Default::default(),
+        debug_info: None,
+        prophets: program
+            .prophets
+            .into_iter()
+            .map(|p| (p.host as u64, p))
+            .collect(),
+        pre_exe_flag: true,
+        print_flag: false,
+        return_arity: program.return_arity,
+    };
+    let mut process = Process::new();
+    process.addr_storage = state_view.contract;
+    process.addr_code = state_view.contract;
+    process.step_limit = Some(limits.max_steps);
+    init_tape(
+        &mut process,
+        calldata,
+        state_view.caller,
+        state_view.contract,
+        state_view.contract,
+        state_view.ctx,
+    );
+    let state = process.execute_read_only(&mut program, state_view.tree, &mut NoHooks, None)?;
+
+    let (mut storage_reads, mut storage_writes) = (0, 0);
+    let mut state_diff = Vec::new();
+    for (key, cells) in process.storage.iter() {
+        // sstore also reads and writes the slot list records
+        storage_reads += cells.iter().filter(|cell| !cell.is_write).count();
+        let writes = cells.iter().filter(|cell| cell.is_write).count();
+        storage_writes += writes;
+        if writes == 0 {
+            continue;
+        }
+        let previous = state_view
+            .tree
+            .storage
+            .hash(&tree_key_to_leaf_index(key))
+            .map(|bytes| u8_arr_to_tree_key(&bytes))
+            .unwrap_or_else(tree_value_default);
+        let value = cells.last().expect("empty address trace").value;
+        if value != previous {
+            state_diff.push(StorageDiff {
+                key: *key,
+                previous,
+                value,
+            });
+        }
+    }
+    if storage_reads + storage_writes > limits.max_storage_ops {
+        return Err(ProcessorError::LimitExceeded(
+            "storage ops".to_string(),
+            limits.max_storage_ops as u64,
+        ));
+    }
+
+    Ok(SimulationOutcome {
+        state,
+        steps: process.clk.get() as u64 + 1,
+        ret: program.trace.ret,
+        storage_reads,
+        storage_writes,
+        state_diff,
+    })
+}
@@ -0,0 +1,170 @@
+//! Checkpoints of a process, to pause a long execution and resume it later.
+//!
+//! A snapshot holds the state a step depends on: registers, pc, clk, the
+//! memory, tape, storage and transient traces, the stack, heap and tape
+//! pointers, the prophet commitment, the gas used and the chain spec rules
+//! applied. Trace rows already collected in `program.trace` are not part of
+//! it. Restoring and executing the same program again goes on from the step
+//! the snapshot was taken at, e.g. after `YieldAction::Abort`.
+
+use crate::gas::GasSchedule;
+use crate::storage::{StorageCell, StorageTree};
+use crate::tape::{TapeCell, TapeTree};
+use crate::transient::{TransientCell, TransientStorage};
+use crate::Process;
+use core::merkle_tree::log::WitnessStorageLog;
+use core::program::REGISTER_NUM;
+use core::types::account::Address;
+use core::types::merkle_tree::{TreeKey, TreeValue};
+use core::types::storage::StorageKey;
+use core::vm::clk::Clk;
+use core::vm::memory::{MemoryCell, MemoryTree};
+use plonky2::field::goldilocks_field::GoldilocksField;
+use plonky2::field::types::PrimeField64;
+use serde::{Deserialize, Serialize};
+use std::collections::BTreeMap;
+use std::ops::Range;
+
+/// State of a process between two steps. Traces keyed by tuples are kept as
+/// lists so the snapshot also serializes to formats with string keys only.
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct ProcessSnapshot {
+    pub clk: Clk,
+    pub pc: u64,
+    pub registers: [GoldilocksField; REGISTER_NUM],
+    pub env_idx: GoldilocksField,
+    pub call_sc_cnt: GoldilocksField,
+    pub addr_storage: Address,
+    pub addr_code: Address,
+    pub psp: GoldilocksField,
+    pub psp_start: GoldilocksField,
+    pub hp: GoldilocksField,
+    pub tp: GoldilocksField,
+    pub storage_access_idx: GoldilocksField,
+    pub prophet_commitment: TreeValue,
+    memory: BTreeMap<u64, Vec<MemoryCell>>,
+    tape: BTreeMap<u64, Vec<TapeCell>>,
+    // address traces in first access order
+    storage: Vec<Vec<StorageCell>>,
+    storage_slots: Vec<(TreeKey, StorageKey)>,
+    transient: Vec<(([u64; 4], u64), Vec<TransientCell>)>,
+    storage_log: Vec<WitnessStorageLog>,
+    program_log: Vec<WitnessStorageLog>,
+    code_region: Option<Range<u64>>,
+    step_limit: Option<u32>,
+    gas_schedule: GasSchedule,
+    gas_limit: Option<u64>,
+    gas_used: u64,
+    record_prophets: bool,
+    disabled_opcodes: Vec<String>,
+    fork_id: TreeValue,
+    max_program_words: usize,
+    chain_id: GoldilocksField,
+    tx_hash: TreeValue,
+}
+
+impl Process {
+    /// Captures the state execution resumes from. The yield hook and the
+    /// repro dir belong to the host and are left out.
+    pub fn snapshot(&self) -> ProcessSnapshot {
+        let mut storage_slots: Vec<_> = self
+            .storage
+            .slots()
+            .map(|(addr, slot)| (*addr, *slot))
+            .collect();
+        storage_slots.sort_by_key(|(addr, _)| addr.map(|e| e.to_canonical_u64()));
+        ProcessSnapshot {
+            clk: self.clk,
+            pc: self.pc,
+            registers: self.registers,
+            env_idx: self.env_idx,
+            call_sc_cnt: self.call_sc_cnt,
+            addr_storage: self.addr_storage,
+            addr_code: self.addr_code,
+            psp: self.psp,
+            psp_start: self.psp_start,
+            hp: self.hp,
+            tp: self.tp,
+            storage_access_idx: self.storage_access_idx,
+            prophet_commitment: self.prophet_commitment,
+            memory: self.memory.trace.clone(),
+            tape: self.tape.trace.clone(),
+            storage: self
+                .storage
+                .iter()
+                .map(|(_, cells)| cells.clone())
+                .collect(),
+            storage_slots,
+            transient: self
+                .transient
+                .trace
+                .iter()
+                .map(|(slot, cells)| (*slot, cells.clone()))
+                .collect(),
+            storage_log: self.storage_log.clone(),
+            program_log: self.program_log.clone(),
+            code_region: self.code_region.clone(),
+            step_limit: self.step_limit,
+            gas_schedule: self.gas_schedule.clone(),
+            gas_limit: self.gas_limit,
+            gas_used: self.gas_used,
+            record_prophets: self.record_prophets,
+            disabled_opcodes: self.disabled_opcodes.clone(),
+            fork_id: self.fork_id,
+            max_program_words: self.max_program_words,
+            chain_id: self.chain_id,
+            tx_hash: self.tx_hash,
+        }
+    }
+
+    /// A process in the state of `snapshot`. Install a yield hook again with
+    /// `yield_every_n_steps` if one is needed.
+    pub fn restore(snapshot: ProcessSnapshot) -> Self {
+        let mut process = Process::new();
+        process.clk = snapshot.clk;
+        process.pc = snapshot.pc;
+        process.registers = snapshot.registers;
+        process.env_idx = snapshot.env_idx;
+        process.call_sc_cnt = snapshot.call_sc_cnt;
+        process.addr_storage = snapshot.addr_storage;
+        process.addr_code = snapshot.addr_code;
+        process.psp = snapshot.psp;
+        process.psp_start = snapshot.psp_start;
+        process.hp = snapshot.hp;
+        process.tp = snapshot.tp;
+        process.storage_access_idx = snapshot.storage_access_idx;
+        process.prophet_commitment = snapshot.prophet_commitment;
+        process.memory = MemoryTree {
+            trace: snapshot.memory,
+            accesses: None,
+        };
+        process.tape = TapeTree {
+            trace: snapshot.tape,
+        };
+        let mut storage = StorageTree::default();
+        for cells in snapshot.storage {
+            storage.insert_trace(cells);
+        }
+        for (addr, slot) in snapshot.storage_slots {
+            storage.note_slot(addr, slot);
+        }
+        process.storage = storage;
+        process.transient = TransientStorage {
+            trace: snapshot.transient.into_iter().collect(),
+        };
+        process.storage_log = snapshot.storage_log;
+        process.program_log = snapshot.program_log;
+        process.code_region = snapshot.code_region;
+        process.step_limit = snapshot.step_limit;
+        process.gas_schedule = snapshot.gas_schedule;
+        process.gas_limit = snapshot.gas_limit;
+        process.gas_used = snapshot.gas_used;
+        process.record_prophets = snapshot.record_prophets;
+        process.disabled_opcodes = snapshot.disabled_opcodes;
+        process.fork_id = snapshot.fork_id;
+        process.max_program_words = snapshot.max_program_words;
+        process.chain_id = snapshot.chain_id;
+        process.tx_hash = snapshot.tx_hash;
+        process
+    }
+}
@@ -1,9 +1,12 @@
 use core::types::merkle_tree::TreeKey;
 use core::types::merkle_tree::TreeValue;
 use core::types::merkle_tree::ZkHash;
+use core::types::merkle_tree::TREE_VALUE_LEN;
+use core::types::storage::StorageKey;
 use plonky2::field::goldilocks_field::GoldilocksField;
+use plonky2::field::types::PrimeField64;
 use std::cmp::Ordering;
-use std::collections::HashMap;
+use std::collections::{BTreeMap, HashMap};
 use std::marker::Destruct;
 
 use serde::{Deserialize, Serialize};
@@ -13,6 +16,7 @@ pub struct StorageCell {
     pub env_idx: GoldilocksField,
     pub clk: u32,
     pub op: GoldilocksField,
+    pub is_write: bool,
     pub root: ZkHash,
     pub addr: TreeKey,
     pub value: TreeValue,
@@ -76,12 +80,87 @@ impl PartialOrd for StorageCell {
     }
 }
 
-#[derive(Debug, Default)]
+/// Storage accesses of one execution, grouped into address traces.
+///
+/// Address traces are kept ordered by the clock of their first access and
+/// then by key, so walking the tree gives the same order on every run.
+#[derive(Debug, Default, Clone)]
 pub struct StorageTree {
-    pub trace: HashMap<TreeKey, Vec<StorageCell>>,
+    trace: BTreeMap<(u32, [u64; TREE_VALUE_LEN]), Vec<StorageCell>>,
+    /// First access clock of every address, to locate its trace.
+    first_access: HashMap<TreeKey, u32>,
+    /// Contract and slot every address was derived from.
+    slots: HashMap<TreeKey, StorageKey>,
 }
 
 impl StorageTree {
+    /// Returns the address trace of `addr`, if it was accessed.
+    pub fn get(&self, addr: &TreeKey) -> Option<&Vec<StorageCell>> {
+        let clk = self.first_access.get(addr)?;
+        self.trace.get(&(*clk, canonical_key(addr)))
+    }
+
+    /// Contract and slot `addr` was derived from, if it was accessed.
+    pub fn slot(&self, addr: &TreeKey) -> Option<&StorageKey> {
+        self.slots.get(addr)
+    }
+
+    /// Records that `addr` is the hashed key of `slot`.
+    pub fn note_slot(&mut self, addr: TreeKey, slot: StorageKey) {
+        self.slots.insert(addr, slot);
+    }
+
+    /// Iterates address traces in first access order.
+    pub fn iter(&self) -> impl Iterator<Item = (&TreeKey, &Vec<StorageCell>)> {
+        self.trace.values().map(|cells| (&cells[0].addr, cells))
+    }
+
+    /// Consumes the tree, yielding every cell in first access order of its
+    /// address.
+    pub fn into_cells(self) -> impl Iterator<Item = StorageCell> {
+        self.trace.into_values().flatten()
+    }
+
+    /// Every accessed address derived from a contract and slot.
+    pub fn slots(&self) -> impl Iterator<Item = (&TreeKey, &StorageKey)> {
+        self.slots.iter()
+    }
+
+    /// Moves the address traces of `other` into this tree. An address
+    /// accessed in both keeps the trace of `other`.
+    pub fn extend(&mut self, other: StorageTree) {
+        self.slots.extend(other.slots);
+        for cells in other.trace.into_values() {
+            self.insert_trace(cells);
+        }
+    }
+
+    /// Sets the address trace of the address of `cells`, replacing the trace
+    /// it had.
+    pub fn insert_trace(&mut self, cells: Vec<StorageCell>) {
+        let addr = cells[0].addr;
+        if let Some(clk) = self.first_access.remove(&addr) {
+            self.trace.remove(&(clk, canonical_key(&addr)));
+        }
+        self.first_access.insert(addr, cells[0].clk);
+        self.trace
+            .insert((cells[0].clk, canonical_key(&addr)), cells);
+    }
+
+    /// Returns the trace of `addr`, creating it at `clk` on first access.
+    fn addr_trace(&mut self, clk: u32, addr: TreeKey) -> (&mut Vec<StorageCell>, bool) {
+        let first_clk = *self.first_access.entry(addr).or_insert(clk);
+        let mut is_new = false;
+        let cells = self
+            .trace
+            .entry((first_clk, canonical_key(&addr)))
+            .or_insert_with(|| {
+                is_new = true;
+                Vec::new()
+            });
+        (cells, is_new)
+    }
+
     pub fn read(
         &mut self,
         clk: u32,
@@ -95,34 +174,22 @@ impl StorageTree {
         // prev_value) to it; if this is the first time we access this address,
         // create address trace for it with entry (clk, [ZERO, 4]). in both
         // cases, return the last value in the address trace.
-        self.trace
-            .entry(addr)
-            .and_modify(|addr_trace| {
-                let last_value = addr_trace.last().expect("empty address trace").value;
-                let new_value = StorageCell {
-                    clk,
-                    op,
-                    addr,
-                    root,
-                    value: last_value,
-                    env_idx,
-                };
-                addr_trace.push(new_value);
-            })
-            .or_insert_with(|| {
-                let new_value = StorageCell {
-                    clk,
-                    op,
-                    addr,
-                    root,
-                    value,
-                    env_idx,
-                };
-                vec![new_value]
-            })
-            .last()
-            .expect("empty address trace")
-            .value
+        let (addr_trace, is_new) = self.addr_trace(clk, addr);
+        let value = if is_new {
+            value
+        } else {
+            addr_trace.last().expect("empty address trace").value
+        };
+        addr_trace.push(StorageCell {
+            clk,
+            op,
+            is_write: false,
+            addr,
+            root,
+            value,
+            env_idx,
+        });
+        value
     }
 
     pub fn write(
@@ -139,14 +206,16 @@ impl StorageTree {
         let new_cell = StorageCell {
             clk,
             op,
+            is_write: true,
             addr,
             value,
             root,
             env_idx,
         };
-        self.trace
-            .entry(addr)
-            .and_modify(|addr_trace| addr_trace.push(new_cell))
-            .or_insert_with(|| vec![new_cell]);
+        self.addr_trace(clk, addr).0.push(new_cell);
     }
 }
+
+fn canonical_key(key: &TreeKey) -> [u64; TREE_VALUE_LEN] {
+    key.map(|e| e.to_canonical_u64())
+}
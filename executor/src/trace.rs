@@ -1,18 +1,20 @@
 use crate::{GoldilocksField, MemRangeType, Process};
+use core::crypto::hash::Hasher;
 use core::merkle_tree::log::WitnessStorageLog;
 use core::merkle_tree::tree::AccountTree;
 use core::program::Program;
 use core::trace::dump::{DumpMemoryRow, DumpStep, DumpTapeRow, DumpTrace};
-use core::trace::trace::{MemoryTraceCell, StorageHashRow, TapeRow};
+use core::trace::trace::{MemoryTraceCell, StorageHashRow, TapeRow, TransientRow};
 use core::types::merkle_tree::constant::ROOT_TREE_DEPTH;
 use core::types::merkle_tree::{tree_key_to_u256, TreeKeyU256, TREE_VALUE_LEN};
+use core::vm::clk::Clk;
 use core::vm::error::ProcessorError;
 use core::vm::memory::HP_START_ADDR;
+use core::vm::memory::MEMORY_MODEL;
 use core::vm::memory::MEM_SPAN_SIZE;
 use log::debug;
 use plonky2::field::types::{Field, Field64, PrimeField64};
 
-use std::collections::HashMap;
 use std::fs::File;
 use std::io::Write;
 
@@ -22,7 +24,7 @@ pub fn gen_memory_table(
     program: &mut Program,
 ) -> Result<(), ProcessorError> {
     let mut origin_addr = 0;
-    let mut origin_clk = 0;
+    let mut origin_clk = Clk::ZERO;
     let mut diff_addr;
     let mut diff_addr_inv;
     let mut diff_clk;
@@ -30,12 +32,15 @@ pub fn gen_memory_table(
     let mut first_row_flag = true;
     let mut first_heap_row_flag = true;
 
-    process
-        .memory
-        .trace
-        .get_mut(&HP_START_ADDR)
-        .unwrap()
-        .remove(0);
+    // the heap ptr init write is implied by the AIR, its first op may be a read.
+    // A heap ptr from a memory image is an mstore and stays.
+    if let Some(cells) = process.memory.trace.get_mut(&HP_START_ADDR) {
+        if cells.first().map_or(false, |c| {
+            c.clk == 0 && c.filter_looked_for_main.is_zero() && c.op.is_zero()
+        }) {
+            cells.remove(0);
+        }
+    }
     for (field_addr, cells) in process.memory.trace.iter() {
         let mut new_addr_flag = true;
 
@@ -61,6 +66,9 @@ pub fn gen_memory_table(
             }
             if first_row_flag {
                 let rc_value = GoldilocksField::ZERO;
+                if cell.region_heap.is_one() || cell.region_prophet.is_one() {
+                    rc_insert.push((diff_addr_cond, MemRangeType::MemRegion));
+                }
                 let trace_cell = MemoryTraceCell {
                     env_idx: cell.env_idx,
                     addr: GoldilocksField::from_canonical_u64(canonical_addr),
@@ -79,7 +87,7 @@ pub fn gen_memory_table(
                     value: cell.value,
                     rc_value,
                 };
-                program.trace.memory.push(trace_cell);
+                program.trace.insert_memory(trace_cell);
                 first_row_flag = false;
                 new_addr_flag = false;
                 if cell.region_heap == GoldilocksField::ONE {
@@ -131,16 +139,17 @@ pub fn gen_memory_table(
                     value: cell.value,
                     rc_value,
                 };
-                program.trace.memory.push(trace_cell);
+                program.trace.insert_memory(trace_cell);
                 new_addr_flag = false;
             } else {
                 diff_addr = GoldilocksField::ZERO;
                 diff_addr_inv = GoldilocksField::ZERO;
-                diff_clk = GoldilocksField::from_canonical_u64(cell.clk as u64 - origin_clk);
+                diff_clk =
+                    GoldilocksField::from_canonical_u32(Clk::new(cell.clk).since(origin_clk)?);
                 let mut rw_addr_unchanged = GoldilocksField::ONE;
                 let rc_value;
                 let mem_filter_type;
-                if cell.is_rw == GoldilocksField::ZERO {
+                if cell.region_prophet.is_one() {
                     rw_addr_unchanged = GoldilocksField::ZERO;
                     rc_value = diff_addr_cond;
                     mem_filter_type = MemRangeType::MemRegion;
@@ -171,10 +180,10 @@ pub fn gen_memory_table(
                     value: cell.value,
                     rc_value,
                 };
-                program.trace.memory.push(trace_cell);
+                program.trace.insert_memory(trace_cell);
             }
             for item in &rc_insert {
-                if item.0.to_canonical_u64() > u32::MAX as u64 {
+                if item.0.to_canonical_u64() > MEMORY_MODEL.word_max() {
                     return Err(ProcessorError::U32RangeCheckFail);
                 }
             }
@@ -193,7 +202,7 @@ pub fn gen_memory_table(
                 )
             });
 
-            origin_clk = cell.clk as u64;
+            origin_clk = Clk::new(cell.clk);
         }
         origin_addr = canonical_addr;
     }
@@ -206,9 +215,9 @@ pub fn storage_hash_table_gen(
     program: &mut Program,
     account_tree: &mut AccountTree,
 ) -> Vec<[GoldilocksField; TREE_VALUE_LEN]> {
+    let hasher = *account_tree.hasher();
     let mut pre_root = account_tree.root_hash();
     let (hash_traces, _) = account_tree.process_block(storage_logs.iter());
-    let _ = account_tree.save();
 
     let mut root_hashes = Vec::new();
 
@@ -261,13 +270,14 @@ pub fn storage_hash_table_gen(
                 if layer % 64 == 0 {
                     acc = GoldilocksField::ZERO;
                 }
-                program.trace.builtin_poseidon.push(item.1 .0);
-                program.trace.builtin_poseidon.push(item.1 .5);
+                hasher.record_level(&mut program.trace, item.1);
                 row
             })
             .collect();
         pre_root = root_hash;
-        program.trace.builtin_storage_hash.extend(rows);
+        for row in rows {
+            program.trace.insert_storage_hash(row);
+        }
     }
     program.trace.builtin_program_hash = program
         .trace
@@ -277,6 +287,9 @@ pub fn storage_hash_table_gen(
     root_hashes
 }
 
+/// Storage hash rows of the storage and program logs of `process`. The logs
+/// are processed into `account_tree` as one block that is not saved yet,
+/// `Process::finalize` saves or drops it.
 pub fn gen_storage_hash_table(
     process: &mut Process,
     program: &mut Program,
@@ -285,9 +298,9 @@ pub fn gen_storage_hash_table(
     let storage_log_len = process.storage_log.len();
     let mut trace = std::mem::replace(&mut process.storage_log, Vec::new());
     trace.extend(std::mem::replace(&mut process.program_log, Vec::new()));
+    let hasher = *account_tree.hasher();
     let mut pre_root = account_tree.root_hash();
     let (hash_traces, _) = account_tree.process_block(trace.iter());
-    let _ = account_tree.save();
 
     let mut root_hashes = Vec::new();
 
@@ -336,13 +349,14 @@ pub fn gen_storage_hash_table(
                 if layer % 64 == 0 {
                     acc = GoldilocksField::ZERO;
                 }
-                program.trace.builtin_poseidon.push(item.1 .0);
-                program.trace.builtin_poseidon.push(item.1 .5);
+                hasher.record_level(&mut program.trace, item.1);
                 row
             })
             .collect();
         pre_root = root_hash;
-        program.trace.builtin_storage_hash.extend(rows);
+        for row in rows {
+            program.trace.insert_storage_hash(row);
+        }
     }
     program.trace.builtin_program_hash = program
         .trace
@@ -361,9 +375,9 @@ pub fn gen_storage_table(
         return Ok(());
     }
 
-    let trace = std::mem::replace(&mut process.storage.trace, HashMap::new());
+    let trace = std::mem::take(&mut process.storage);
 
-    let mut traces: Vec<_> = trace.into_iter().flat_map(|e| e.1).collect();
+    let mut traces: Vec<_> = trace.into_cells().collect();
     traces.sort_by(|a, b| a.cmp(b));
     for (item, root) in traces.iter().enumerate().zip(hash_roots) {
         let diff_clk = 0;
@@ -394,7 +408,7 @@ pub fn gen_storage_table(
 pub fn gen_tape_table(process: &mut Process, program: &mut Program) -> Result<(), ProcessorError> {
     for (addr, cells) in process.tape.trace.iter() {
         for tape_row in cells {
-            program.trace.tape.push(TapeRow {
+            program.trace.insert_tape(TapeRow {
                 is_init: tape_row.is_init.is_one(),
                 opcode: tape_row.op,
                 addr: GoldilocksField::from_canonical_u64(*addr),
@@ -407,6 +421,22 @@ pub fn gen_tape_table(process: &mut Process, program: &mut Program) -> Result<()
     Ok(())
 }
 
+pub fn gen_transient_table(process: &Process, program: &mut Program) {
+    for ((addr, key), cells) in process.transient.trace.iter() {
+        for cell in cells {
+            program.trace.insert_transient(TransientRow {
+                env_idx: cell.env_idx,
+                clk: cell.clk,
+                addr_storage: addr.map(GoldilocksField::from_canonical_u64),
+                key: GoldilocksField::from_canonical_u64(*key),
+                value: cell.value,
+                is_write: cell.is_write,
+                step: 0,
+            });
+        }
+    }
+}
+
 pub fn gen_dump_file(process: &mut Process, program: &mut Program) {
     let mut dump_trace = DumpTrace {
         exec: vec![],
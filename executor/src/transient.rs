@@ -0,0 +1,73 @@
+use core::types::account::Address;
+
+use plonky2::field::goldilocks_field::GoldilocksField;
+use plonky2::field::types::{Field, PrimeField64};
+use serde::{Deserialize, Serialize};
+use std::collections::BTreeMap;
+
+#[derive(Debug, Copy, Clone, Serialize, Deserialize)]
+pub struct TransientCell {
+    pub env_idx: GoldilocksField,
+    pub clk: u32,
+    pub is_write: bool,
+    pub value: GoldilocksField,
+}
+
+/// Key value storage of `tload_t`/`tstore_t`. It lives for one tx, is shared
+/// by all contracts called in the tx and is never written to the account
+/// tree. Slots are keyed by the storage address of the contract and the key.
+#[derive(Debug, Clone, Default)]
+pub struct TransientStorage {
+    // every access of a slot in execution order, the last one holds the
+    // current value
+    pub trace: BTreeMap<([u64; 4], u64), Vec<TransientCell>>,
+}
+
+fn slot(addr: &Address, key: GoldilocksField) -> ([u64; 4], u64) {
+    (
+        addr.map(|limb| limb.to_canonical_u64()),
+        key.to_canonical_u64(),
+    )
+}
+
+impl TransientStorage {
+    /// Value of the slot, zero if it was never written in this tx.
+    pub fn read(
+        &mut self,
+        addr: &Address,
+        key: GoldilocksField,
+        env_idx: GoldilocksField,
+        clk: u32,
+    ) -> GoldilocksField {
+        let cells = self.trace.entry(slot(addr, key)).or_default();
+        let value = cells
+            .last()
+            .map_or(GoldilocksField::ZERO, |cell| cell.value);
+        cells.push(TransientCell {
+            env_idx,
+            clk,
+            is_write: false,
+            value,
+        });
+        value
+    }
+
+    pub fn write(
+        &mut self,
+        addr: &Address,
+        key: GoldilocksField,
+        env_idx: GoldilocksField,
+        clk: u32,
+        value: GoldilocksField,
+    ) {
+        self.trace
+            .entry(slot(addr, key))
+            .or_default()
+            .push(TransientCell {
+                env_idx,
+                clk,
+                is_write: true,
+                value,
+            });
+    }
+}